@@ -5,15 +5,14 @@ use radius2::{Radius, RadiusOption};
 
 // simulates the scanf("%d", dst) calls with sym inputs
 fn scanf_sim(state: &mut State, args: &[Value]) -> Value {
-    let input_len = state
+    // named scanf:0, scanf:1, ... in the order they are read
+    let new_int = state.new_symbol("scanf", 32);
+    state.memory_write_value(&args[1], &new_int, 4);
+    state
         .context
         .entry("ints".to_owned())
         .or_insert_with(Vec::new)
-        .len();
-
-    let new_int = state.symbolic_value(&format!("int{}", input_len), 32);
-    state.memory_write_value(&args[1], &new_int, 4);
-    state.context.get_mut("ints").unwrap().push(new_int);
+        .push(new_int);
     state.concrete_value(1, 64)
 }
 
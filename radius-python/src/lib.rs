@@ -0,0 +1,401 @@
+//! Python bindings for radius2, exposing `Radius`, `State`, and `Value`
+//! as the `pyradius2` module. Python callables can be hooks and sims
+//!
+//! ```python
+//! from pyradius2 import Radius
+//! radius = Radius("tests/r100")
+//! state = radius.call_state(0x004006fd)
+//! flag = state.symbolize_memory("flag", 0x100000, 12)
+//! state.set_register("rdi", 0x100000)
+//! found = radius.run_until(state, 0x004007a1, [0x00400790])
+//! print(found.evaluate_string(flag))
+//! ```
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use radius2::radius::{Radius, RadiusOption};
+use radius2::sims::make_sim;
+use radius2::state::State;
+use radius2::value::{vc, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// the default number of args passed to a python sim
+const SIM_ARGUMENTS: usize = 4;
+
+/// The python hooks and sims of a `Radius`, keyed by address
+#[derive(Default)]
+struct Callbacks {
+    hooks: HashMap<u64, Vec<PyObject>>,
+    sims: HashMap<u64, PyObject>,
+}
+
+thread_local! {
+    // hooks and sims are plain fns, so the trampolines find the python
+    // callables here. the running instance swaps its callbacks in
+    static CALLBACKS: RefCell<Callbacks> = RefCell::new(Callbacks::default());
+}
+
+/// A concrete or symbolic value
+#[pyclass(name = "Value", unsendable)]
+#[derive(Clone)]
+pub struct PyValue {
+    pub value: Value,
+}
+
+/// Python ints can be used anywhere a `Value` is expected
+#[derive(FromPyObject)]
+enum ValueArg {
+    Value(PyValue),
+    Int(u64),
+}
+
+impl From<ValueArg> for Value {
+    fn from(arg: ValueArg) -> Self {
+        match arg {
+            ValueArg::Value(v) => v.value,
+            ValueArg::Int(i) => vc(i),
+        }
+    }
+}
+
+impl From<Value> for PyValue {
+    fn from(value: Value) -> Self {
+        PyValue { value }
+    }
+}
+
+#[pymethods]
+impl PyValue {
+    #[new]
+    fn new(value: u64) -> Self {
+        vc(value).into()
+    }
+
+    fn is_symbolic(&self) -> bool {
+        self.value.is_symbolic()
+    }
+
+    fn __int__(&self) -> PyResult<u64> {
+        self.value
+            .as_u64()
+            .ok_or_else(|| PyValueError::new_err("value is symbolic"))
+    }
+
+    fn __repr__(&self) -> String {
+        match self.value.try_con() {
+            Value::Concrete(v, _) => format!("Value(0x{:x})", v),
+            Value::Symbolic(bv, _) => format!("Value({:?})", bv),
+        }
+    }
+
+    fn __add__(&self, other: ValueArg) -> PyValue {
+        self.value.add(&other.into()).into()
+    }
+
+    fn __sub__(&self, other: ValueArg) -> PyValue {
+        self.value.sub(&other.into()).into()
+    }
+
+    fn __mul__(&self, other: ValueArg) -> PyValue {
+        self.value.mul(&other.into()).into()
+    }
+
+    fn __and__(&self, other: ValueArg) -> PyValue {
+        self.value.and(&other.into()).into()
+    }
+
+    fn __or__(&self, other: ValueArg) -> PyValue {
+        self.value.or(&other.into()).into()
+    }
+
+    fn __xor__(&self, other: ValueArg) -> PyValue {
+        self.value.xor(&other.into()).into()
+    }
+
+    /// The condition that the values are equal, for `State.constrain`
+    fn eq(&self, other: ValueArg) -> PyValue {
+        self.value.eq(&other.into()).into()
+    }
+
+    fn ult(&self, other: ValueArg) -> PyValue {
+        self.value.ult(&other.into()).into()
+    }
+
+    fn ugt(&self, other: ValueArg) -> PyValue {
+        self.value.ugt(&other.into()).into()
+    }
+}
+
+/// A program state
+#[pyclass(name = "State", unsendable)]
+#[derive(Clone)]
+pub struct PyState {
+    pub state: State,
+}
+
+#[pymethods]
+impl PyState {
+    #[getter]
+    fn pc(&self) -> PyValue {
+        self.state.registers.get_pc().into()
+    }
+
+    fn get_register(&self, name: &str) -> PyValue {
+        self.state.registers.get(name).into()
+    }
+
+    fn set_register(&mut self, name: &str, value: ValueArg) {
+        self.state.registers.set(name, value.into());
+    }
+
+    fn read_memory(&mut self, addr: ValueArg, length: usize) -> PyValue {
+        self.state.memory_read_value(&addr.into(), length).into()
+    }
+
+    fn write_memory(&mut self, addr: ValueArg, value: ValueArg, length: usize) {
+        self.state
+            .memory_write_value(&addr.into(), &value.into(), length);
+    }
+
+    fn symbolic_value(&self, name: &str, bits: u32) -> PyValue {
+        self.state.symbolic_value(name, bits).into()
+    }
+
+    /// Make `length` bytes at `addr` symbolic, returns them as one value
+    fn symbolize_memory(&mut self, name: &str, addr: u64, length: usize) -> PyValue {
+        self.state.symbolize_memory(name, addr, length);
+        self.state.memory_read_value(&vc(addr), length).into()
+    }
+
+    /// Add the condition to the constraints of the state
+    fn constrain(&mut self, condition: ValueArg) {
+        self.state.assert(&condition.into());
+    }
+
+    fn is_sat(&mut self) -> bool {
+        self.state.is_sat()
+    }
+
+    fn evaluate(&mut self, value: ValueArg) -> Option<u64> {
+        self.state.eval(&value.into()).and_then(|v| v.as_u64())
+    }
+
+    fn evaluate_bytes(&mut self, py: Python, value: ValueArg) -> Option<PyObject> {
+        let bytes = self.state.evaluate_bytes(&value.into())?;
+        Some(PyBytes::new(py, &bytes).into())
+    }
+
+    fn evaluate_string(&mut self, value: ValueArg) -> Option<String> {
+        self.state.evaluate_string(&value.into())
+    }
+
+    /// The solved bytes of every named input, like stdin and argv
+    fn dump_inputs(&mut self, py: Python) -> HashMap<String, PyObject> {
+        self.state
+            .dump_inputs()
+            .into_iter()
+            .map(|(name, bytes)| (name, PyBytes::new(py, &bytes).into()))
+            .collect()
+    }
+}
+
+/// The main symbolic execution interface
+#[pyclass(name = "Radius", unsendable)]
+pub struct PyRadius {
+    radius: Radius,
+    callbacks: Callbacks,
+}
+
+impl PyRadius {
+    // run with the callbacks of this instance visible to the trampolines
+    fn with_callbacks<T>(&mut self, run: impl FnOnce(&mut Radius) -> T) -> T {
+        CALLBACKS.with(|c| std::mem::swap(&mut *c.borrow_mut(), &mut self.callbacks));
+        let result = run(&mut self.radius);
+        CALLBACKS.with(|c| std::mem::swap(&mut *c.borrow_mut(), &mut self.callbacks));
+        result
+    }
+}
+
+#[pymethods]
+impl PyRadius {
+    #[new]
+    #[pyo3(signature = (path, sims = true, syscalls = true, strict = false, max_steps = None))]
+    fn new(path: &str, sims: bool, syscalls: bool, strict: bool, max_steps: Option<u64>) -> Self {
+        let mut options = vec![
+            RadiusOption::Sims(sims),
+            RadiusOption::Syscalls(syscalls),
+            RadiusOption::Strict(strict),
+        ];
+        if let Some(steps) = max_steps {
+            options.push(RadiusOption::MaxSteps(steps));
+        }
+        PyRadius {
+            radius: Radius::new_with_options(Some(path), &options),
+            callbacks: Callbacks::default(),
+        }
+    }
+
+    fn call_state(&mut self, addr: u64) -> PyState {
+        PyState {
+            state: self.radius.call_state(addr),
+        }
+    }
+
+    fn entry_state(&mut self) -> PyState {
+        PyState {
+            state: self.radius.entry_state(),
+        }
+    }
+
+    fn blank_state(&mut self) -> PyState {
+        PyState {
+            state: self.radius.blank_state(),
+        }
+    }
+
+    fn get_address(&mut self, symbol: &str) -> PyResult<u64> {
+        self.radius
+            .get_address(symbol)
+            .map_err(PyValueError::new_err)
+    }
+
+    fn breakpoint(&mut self, addr: u64) {
+        self.radius.breakpoint(addr);
+    }
+
+    fn mergepoint(&mut self, addr: u64) {
+        self.radius.mergepoint(addr);
+    }
+
+    fn avoid(&mut self, addrs: Vec<u64>) {
+        self.radius.avoid(&addrs);
+    }
+
+    /// Call `callback(state)` when `addr` is reached, returning False
+    /// skips the instruction. Changes to the state are kept
+    fn hook(&mut self, addr: u64, callback: PyObject) {
+        let hooks = self.callbacks.hooks.entry(addr).or_default();
+        if hooks.is_empty() {
+            self.radius.hook(addr, python_hook);
+        }
+        hooks.push(callback);
+    }
+
+    /// Replace the function at `addr` with `callback(state, args)`,
+    /// which returns the return value of the function
+    #[pyo3(signature = (addr, callback, arguments = SIM_ARGUMENTS))]
+    fn simulate(&mut self, addr: u64, callback: PyObject, arguments: usize) {
+        self.callbacks.sims.insert(addr, callback);
+        self.radius
+            .simulate(addr, make_sim("python", python_sim, arguments));
+    }
+
+    #[pyo3(signature = (state, target, avoid = vec![]))]
+    fn run_until(&mut self, state: &PyState, target: u64, avoid: Vec<u64>) -> Option<PyState> {
+        let state = state.state.clone();
+        self.with_callbacks(|radius| radius.run_until(state, target, &avoid))
+            .map(|state| PyState { state })
+    }
+
+    // one thread, the callbacks are only visible on this one
+    fn run(&mut self, state: &PyState) -> Option<PyState> {
+        let state = state.state.clone();
+        self.with_callbacks(|radius| radius.run(state, 1))
+            .map(|state| PyState { state })
+    }
+
+    fn run_all(&mut self, state: &PyState) -> Vec<PyState> {
+        let state = state.state.clone();
+        self.with_callbacks(|radius| radius.run_all(state))
+            .into_iter()
+            .map(|state| PyState { state })
+            .collect()
+    }
+
+    fn close(&mut self) {
+        self.radius.close();
+    }
+}
+
+// the pc of a hook or sim is the address it was registered at
+fn current_pc(state: &State) -> u64 {
+    state.registers.get_pc().as_u64().unwrap_or_default()
+}
+
+// hand python a copy of the state and keep the changes it makes
+fn with_py_state<T>(
+    py: Python,
+    state: &mut State,
+    call: impl FnOnce(&Py<PyState>) -> T,
+) -> PyResult<T> {
+    let shared = Py::new(
+        py,
+        PyState {
+            state: state.clone(),
+        },
+    )?;
+    let result = call(&shared);
+    *state = shared.borrow(py).state.clone();
+    Ok(result)
+}
+
+fn python_hook(state: &mut State) -> bool {
+    let pc = current_pc(state);
+    Python::with_gil(|py| {
+        let hooks: Vec<PyObject> = CALLBACKS.with(|c| {
+            let callbacks = c.borrow();
+            let hooks = callbacks.hooks.get(&pc).into_iter().flatten();
+            hooks.map(|h| h.clone_ref(py)).collect()
+        });
+
+        let result = with_py_state(py, state, |shared| {
+            let mut cont = true;
+            for hook in hooks {
+                match hook.call1(py, (shared.clone_ref(py),)) {
+                    // hooks without a return value continue
+                    Ok(ret) => cont &= ret.is_none(py) || ret.is_truthy(py).unwrap_or(true),
+                    Err(e) => e.print(py),
+                }
+            }
+            cont
+        });
+        result.unwrap_or_else(|e| {
+            e.print(py);
+            true
+        })
+    })
+}
+
+fn python_sim(state: &mut State, args: &[Value]) -> Value {
+    let pc = current_pc(state);
+    Python::with_gil(|py| {
+        let sim = CALLBACKS.with(|c| c.borrow().sims.get(&pc).map(|s| s.clone_ref(py)));
+        let sim = match sim {
+            Some(sim) => sim,
+            None => return vc(0),
+        };
+
+        let args: Vec<PyValue> = args.iter().cloned().map(PyValue::from).collect();
+        let result = with_py_state(py, state, |shared| {
+            sim.call1(py, (shared.clone_ref(py), args))
+                .and_then(|ret| ret.extract::<Option<ValueArg>>(py))
+        });
+        match result.and_then(|r| r) {
+            Ok(ret) => ret.map(Value::from).unwrap_or_else(|| vc(0)),
+            Err(e) => {
+                e.print(py);
+                vc(0)
+            }
+        }
+    })
+}
+
+#[pymodule]
+fn pyradius2(m: &Bound<PyModule>) -> PyResult<()> {
+    m.add_class::<PyRadius>()?;
+    m.add_class::<PyState>()?;
+    m.add_class::<PyValue>()?;
+    Ok(())
+}
@@ -0,0 +1,229 @@
+use crate::r2_api::{BinInfo, CallingConvention, Instruction};
+use crate::state::State;
+use crate::value::{vc, Value};
+
+// the kernel returns -4095 to -1 for errors
+const MAX_ERRNO: u64 = 4095;
+
+// o32 syscalls can pass up to 6 args, the last two on the stack
+const MIPS32_SYSCALL_ARGS: usize = 6;
+
+const BRANCH_LIKELY: [&str; 10] = [
+    "beql", "bnel", "blezl", "bgtzl", "bltzl", "bgezl", "bltzall", "bgezall", "bc1tl", "bc1fl",
+];
+
+/// Architecture families whose details differ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArchKind {
+    X86,
+    /// 32 bit arm, in arm or thumb mode
+    Arm,
+    Aarch64,
+    Mips,
+    Riscv,
+    Ppc,
+    Sparc,
+    Xtensa,
+    #[default]
+    Other,
+}
+
+/// The details of the target architecture that the processor and
+/// sims need: calling conventions, return addresses, thumb mode
+/// switches, and delay slots
+///
+/// ## Example
+/// ```
+/// use radius2::arch::{Arch, ArchKind};
+/// let arch = Arch::new("arm", 16);
+/// assert_eq!(arch.kind, ArchKind::Arm);
+/// assert_eq!(arch.bits, 32);
+/// assert!(arch.thumb);
+/// assert_eq!(arch.syscall_cc("linux").unwrap().args[0], "r0");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Arch {
+    pub kind: ArchKind,
+    /// Bits of a pointer
+    pub bits: u64,
+    /// Execution starts in thumb mode, r2 says 16 bits for these binaries
+    pub thumb: bool,
+}
+
+impl Arch {
+    pub fn new(arch: &str, bits: u64) -> Self {
+        let kind = match (arch, bits) {
+            ("x86", _) => ArchKind::X86,
+            ("arm", 64) => ArchKind::Aarch64,
+            ("arm", _) => ArchKind::Arm,
+            ("mips", _) => ArchKind::Mips,
+            ("riscv", _) => ArchKind::Riscv,
+            ("ppc", _) => ArchKind::Ppc,
+            ("sparc", _) => ArchKind::Sparc,
+            ("xtensa", _) => ArchKind::Xtensa,
+            _ => ArchKind::Other,
+        };
+        let thumb = kind == ArchKind::Arm && bits == 16;
+        Arch {
+            kind,
+            bits: if thumb { 32 } else { bits },
+            thumb,
+        }
+    }
+
+    pub fn from_info(bin: &BinInfo) -> Self {
+        Arch::new(&bin.arch, bin.bits)
+    }
+
+    /// Calls push the return address onto the stack instead of a link register
+    pub fn pushes_return(&self) -> bool {
+        self.kind == ArchKind::X86
+    }
+
+    /// The standard calling convention of functions for the platform,
+    /// eg. Win64 for windows x86 64 and SysV for other x86 64 targets.
+    /// x86 32 passes args on the stack and has no entry
+    pub fn platform_cc(&self, os: &str) -> Option<CallingConvention> {
+        let (args, ret, shadow): (&[&str], &str, u64) = match (os, self.kind, self.bits) {
+            ("windows", ArchKind::X86, 64) => (&["rcx", "rdx", "r8", "r9"], "rax", 32),
+            (_, ArchKind::X86, 64) => (&["rdi", "rsi", "rdx", "rcx", "r8", "r9"], "rax", 0),
+            (_, ArchKind::Aarch64, _) => {
+                (&["x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7"], "x0", 0)
+            }
+            (_, ArchKind::Arm, _) => (&["r0", "r1", "r2", "r3"], "r0", 0),
+            // o32 reserves stack for the four register args
+            (_, ArchKind::Mips, 32) => (&["a0", "a1", "a2", "a3"], "v0", 16),
+            (_, ArchKind::Mips, 64) => (&["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"], "v0", 0),
+            (_, ArchKind::Riscv, _) => (&["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"], "a0", 0),
+            (_, ArchKind::Ppc, _) => (&["r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10"], "r3", 0),
+            (_, ArchKind::Sparc, _) => (&["o0", "o1", "o2", "o3", "o4", "o5"], "o0", 0),
+            _ => return None,
+        };
+        Some(CallingConvention::new(args, ret, shadow))
+    }
+
+    /*
+        arch/ABI      arg1  arg2  arg3  arg4  arg5  arg6  arg7  Notes
+    ──────────────────────────────────────────────────────────────
+    alpha         a0    a1    a2    a3    a4    a5    -
+    arc           r0    r1    r2    r3    r4    r5    -
+    arm/OABI      a1    a2    a3    a4    v1    v2    v3
+    arm/EABI      r0    r1    r2    r3    r4    r5    r6
+    arm64         x0    x1    x2    x3    x4    x5    -
+    blackfin      R0    R1    R2    R3    R4    R5    -
+    i386          ebx   ecx   edx   esi   edi   ebp   -
+    ia64          out0  out1  out2  out3  out4  out5  -
+    m68k          d1    d2    d3    d4    d5    a0    -
+    microblaze    r5    r6    r7    r8    r9    r10   -
+    mips/o32      a0    a1    a2    a3    -     -     -     [1]
+    mips/n32,64   a0    a1    a2    a3    a4    a5    -
+    nios2         r4    r5    r6    r7    r8    r9    -
+    parisc        r26   r25   r24   r23   r22   r21   -
+    powerpc       r3    r4    r5    r6    r7    r8    r9
+    riscv         a0    a1    a2    a3    a4    a5    -
+    s390          r2    r3    r4    r5    r6    r7    -
+    s390x         r2    r3    r4    r5    r6    r7    -
+    superh        r4    r5    r6    r7    r0    r1    r2
+    sparc/32      o0    o1    o2    o3    o4    o5    -
+    sparc/64      o0    o1    o2    o3    o4    o5    -
+    tile          R00   R01   R02   R03   R04   R05   -
+    x86-64        rdi   rsi   rdx   r10   r8    r9    -
+    x32           rdi   rsi   rdx   r10   r8    r9    -
+    xtensa        a6    a3    a4    a5    a8    a9    -
+    */
+
+    /// The calling convention of syscalls for the platform
+    pub fn syscall_cc(&self, os: &str) -> Option<CallingConvention> {
+        let (args, ret, shadow): (&[&str], &str, u64) = match (os, self.kind, self.bits) {
+            (_, ArchKind::X86, 32) => (&["ebx", "ecx", "edx", "esi", "edi", "ebp"], "eax", 0),
+            ("windows", ArchKind::X86, 64) => (&["r10", "rdx", "r8", "r9"], "rax", 32),
+            (_, ArchKind::X86, 64) => (&["rdi", "rsi", "rdx", "r10", "r8", "r9"], "rax", 0),
+            (_, ArchKind::Arm, _) => (&["r0", "r1", "r2", "r3", "r4", "r5", "r6"], "r0", 0),
+            // supposedly xnu/ios can have up 9 args
+            (_, ArchKind::Aarch64, _) => (
+                &["x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8"],
+                "x0",
+                0,
+            ),
+            (_, ArchKind::Mips, 32) => (&["a0", "a1", "a2", "a3"], "v0", 16),
+            (_, ArchKind::Mips, 64) => (&["a0", "a1", "a2", "a3", "a4", "a5"], "v0", 0),
+            (_, ArchKind::Riscv, _) => (&["a0", "a1", "a2", "a3", "a4", "a5"], "a0", 0),
+            (_, ArchKind::Sparc, _) => (&["o0", "o1", "o2", "o3", "o4", "o5"], "o0", 0),
+            // TODO errors are in r0
+            (_, ArchKind::Ppc, _) => (&["r3", "r4", "r5", "r6", "r7", "r8", "r9"], "r3", 0),
+            (_, ArchKind::Xtensa, _) => (&["a6", "a3", "a4", "a5", "a8", "a9"], "a2", 0),
+            _ => return None,
+        };
+        Some(CallingConvention::new(args, ret, shadow))
+    }
+
+    /// Get the args of a syscall, o32 mips passes the fifth and sixth
+    /// args on the stack past the space reserved for the register args
+    pub fn syscall_args(&self, state: &mut State, cc: &CallingConvention) -> Vec<Value> {
+        let count = if self.kind == ArchKind::Mips && self.bits == 32 {
+            MIPS32_SYSCALL_ARGS
+        } else {
+            cc.args.len()
+        };
+        (0..count).map(|i| state.get_arg(cc, i)).collect()
+    }
+
+    /// Set the return value of a syscall. Mips returns errors as a
+    /// positive errno in v0 and sets a3, others return the negative errno
+    pub fn set_syscall_return(&self, state: &mut State, cc: &CallingConvention, ret: Value) {
+        if self.kind != ArchKind::Mips {
+            state.registers.set(cc.ret.as_str(), ret);
+            return;
+        }
+
+        let error = ret.ugt(&(vc(0) - vc(MAX_ERRNO + 1)));
+        let errno = vc(0) - ret.to_owned();
+        let value = state.cond(&error, &errno, &ret);
+        let flag = state.cond(&error, &vc(1), &vc(0));
+        state.registers.set(cc.ret.as_str(), value);
+        state.registers.set("a3", flag);
+    }
+
+    /// Whether the instruction after `instr` runs before its branch is
+    /// taken, as r2 decoded it
+    pub fn has_delay_slot(&self, instr: &Instruction) -> bool {
+        self.kind == ArchKind::Mips && instr.delay > 0
+    }
+
+    /// Whether `instr` is a mips branch likely, whose delay slot only
+    /// runs if the branch is taken
+    pub fn is_branch_likely(&self, instr: &Instruction) -> bool {
+        let mnemonic = instr.opcode.split_whitespace().next().unwrap_or_default();
+        self.kind == ArchKind::Mips && BRANCH_LIKELY.contains(&mnemonic)
+    }
+
+    /// Whether the state runs thumb code
+    pub fn is_thumb(&self, state: &State) -> bool {
+        self.kind == ArchKind::Arm
+            && state
+                .context
+                .get("thumb")
+                .map(|t| t[0].as_u64() == Some(1))
+                .unwrap_or(self.thumb)
+    }
+
+    /// Follow an arm interworking branch by `instr` to `target`. Odd
+    /// targets switch to thumb mode and even targets of bx and blx switch
+    /// back to arm mode. Returns the address of the next instruction
+    pub fn interwork(&self, state: &mut State, instr: &Instruction, target: u64) -> u64 {
+        if self.kind != ArchKind::Arm {
+            return target;
+        }
+
+        let mnemonic = instr.opcode.split_whitespace().next().unwrap_or_default();
+        if target & 1 == 1 {
+            state.context.insert("thumb".to_owned(), vec![vc(1)]);
+            target & !1
+        } else {
+            if mnemonic.starts_with("bx") || mnemonic.starts_with("blx") {
+                state.context.insert("thumb".to_owned(), vec![vc(0)]);
+            }
+            target
+        }
+    }
+}
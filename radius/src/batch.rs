@@ -0,0 +1,198 @@
+use crate::budget::StopReason;
+use crate::radius::Radius;
+use crate::server::{get_address, get_addresses, load_options, setup_state, state_event};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How the job ended for a single binary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BatchStatus {
+    /// A state reached the target
+    Found,
+    /// Every state ended without reaching the target
+    NotFound,
+    /// The run was stopped after the timeout
+    Timeout,
+    /// The job could not be set up for the binary
+    Error(String),
+}
+
+/// The result of the job for a single binary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub path: String,
+    pub status: BatchStatus,
+    /// Time spent on the binary in milliseconds
+    pub time: u64,
+    /// The pc, status, and symbol solutions of the found state
+    pub state: Json,
+    /// Number of states that crashed
+    pub crashes: usize,
+}
+
+/// The aggregated results of a batch
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchReport {
+    pub results: Vec<BatchResult>,
+}
+
+impl BatchReport {
+    /// Get the results with the given status
+    pub fn with_status(&self, status: &BatchStatus) -> Vec<&BatchResult> {
+        self.results
+            .iter()
+            .filter(|r| &r.status == status)
+            .collect()
+    }
+
+    /// Print a line for each binary followed by the totals
+    pub fn print(&self) {
+        for result in &self.results {
+            let status = match &result.status {
+                BatchStatus::Error(e) => format!("error: {}", e),
+                status => format!("{:?}", status),
+            };
+            println!(
+                "{:<48} {:<10} {:>8}ms {:>4} crashes {}",
+                result.path, status, result.time, result.crashes, result.state["symbols"]
+            );
+        }
+        println!(
+            "\n{} binaries, {} found, {} not found, {} timed out, {} errors",
+            self.results.len(),
+            self.with_status(&BatchStatus::Found).len(),
+            self.with_status(&BatchStatus::NotFound).len(),
+            self.with_status(&BatchStatus::Timeout).len(),
+            self.results
+                .iter()
+                .filter(|r| matches!(r.status, BatchStatus::Error(_)))
+                .count()
+        );
+    }
+}
+
+/// Runs the same job on every binary in a directory with parallel
+/// workers, each with its own radius instance. The job parameters
+/// are the same as the params of a server "solve" request
+///
+/// ## Example
+/// ```no_run
+/// use radius2::batch::Batch;
+/// use serde_json::json;
+/// use std::time::Duration;
+/// let params = json!({
+///     "address": "main",
+///     "target": "sym.win",
+///     "symbols": [{"name": "input", "bits": 256, "at": "stdin"}]
+/// });
+/// let mut batch = Batch::new(params);
+/// batch.workers = 8;
+/// batch.timeout = Some(Duration::from_secs(60));
+/// let report = batch.run("firmware/").unwrap();
+/// report.print();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub params: Json,
+    /// Number of binaries run at the same time
+    pub workers: usize,
+    /// Time limit for each binary
+    pub timeout: Option<Duration>,
+}
+
+impl Batch {
+    pub fn new(params: Json) -> Self {
+        Batch {
+            params,
+            workers: 1,
+            timeout: None,
+        }
+    }
+
+    /// Run the job on every file in `dir`, the results are in path order
+    pub fn run(&self, dir: &str) -> std::io::Result<BatchReport> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        paths.sort();
+
+        let queue = Arc::new(Mutex::new(paths));
+        let results = Arc::new(Mutex::new(vec![]));
+
+        let mut handles = vec![];
+        for _ in 0..self.workers.max(1) {
+            let queue = queue.clone();
+            let results = results.clone();
+            let batch = self.clone();
+            handles.push(thread::spawn(move || loop {
+                let path = queue.lock().unwrap().pop();
+                if let Some(path) = path {
+                    let path = path.to_string_lossy().to_string();
+                    // a binary that r2 can't handle should not stop the batch
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| batch.run_binary(&path)))
+                        .unwrap_or_else(|_| BatchResult {
+                            path: path.clone(),
+                            status: BatchStatus::Error("panicked".to_owned()),
+                            time: 0,
+                            state: Json::Null,
+                            crashes: 0,
+                        });
+                    results.lock().unwrap().push(result);
+                } else {
+                    break;
+                }
+            }));
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let mut results = results.lock().unwrap().clone();
+        results.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(BatchReport { results })
+    }
+
+    /// Run the job on a single binary
+    pub fn run_binary(&self, path: &str) -> BatchResult {
+        let start = Instant::now();
+        let mut radius = Radius::new_with_options(Some(path), &load_options(&self.params));
+        radius.set_timeout(self.timeout);
+
+        let (status, state) = match self.solve(&mut radius) {
+            Ok(Some(state)) => (BatchStatus::Found, state),
+            Ok(None) if radius.processor.budget_stop == Some(StopReason::Timeout) => {
+                (BatchStatus::Timeout, Json::Null)
+            }
+            Ok(None) => (BatchStatus::NotFound, Json::Null),
+            Err(e) => (BatchStatus::Error(e), Json::Null),
+        };
+
+        let crashes = radius.processor.crashes.len();
+        radius.close();
+
+        BatchResult {
+            path: path.to_owned(),
+            status,
+            time: start.elapsed().as_millis() as u64,
+            state,
+            crashes,
+        }
+    }
+
+    fn solve(&self, radius: &mut Radius) -> Result<Option<Json>, String> {
+        let (state, symbols) = setup_state(radius, &self.params)?;
+        let target = get_address(radius, &self.params["target"])?;
+        let avoid = get_addresses(radius, &self.params["avoid"])?;
+        Ok(radius
+            .run_until(state, target, &avoid)
+            .map(|mut s| state_event(&mut s, &symbols)))
+    }
+}
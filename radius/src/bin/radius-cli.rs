@@ -0,0 +1,266 @@
+//! radius-cli runs the common radius2 workflows from the command line,
+//! eg. `radius-cli solve ./bin --find 0x4028e9 --sym-stdin 64`
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use radius2::manager::{StateManager, FOUND};
+use radius2::radius::{Radius, RadiusOption, SimArg};
+use radius2::state::State;
+use serde_json::json;
+use std::process;
+use std::time::Duration;
+
+macro_rules! collect {
+    ($m:expr, $s:expr) => {
+        $m.values_of($s).unwrap_or_default().collect::<Vec<_>>()
+    };
+}
+
+fn main() {
+    let matches = App::new("radius-cli")
+        .global_settings(&[AppSettings::ColoredHelp])
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Run common radius2 workflows without writing Rust")
+        .subcommand(
+            run_args(SubCommand::with_name("solve"))
+                .about("Find inputs that reach an address or print an output")
+                .arg(
+                    Arg::with_name("find")
+                        .long("find")
+                        .takes_value(true)
+                        .value_name("ADDR")
+                        .help("Address or symbol to reach"),
+                )
+                .arg(
+                    Arg::with_name("avoid")
+                        .long("avoid")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("ADDR")
+                        .help("Address or symbol to avoid"),
+                )
+                .arg(
+                    Arg::with_name("constrain_output")
+                        .long("constrain-output")
+                        .takes_value(true)
+                        .value_name("STRING")
+                        .help("Require stdout to contain STRING"),
+                ),
+        )
+        .subcommand(
+            run_args(SubCommand::with_name("findings"))
+                .about("Explore every path and report crashes, heap bugs, and controlled pcs"),
+        )
+        .subcommand(
+            run_args(SubCommand::with_name("corpus"))
+                .about("Write the inputs of every path to a directory to seed a fuzzer")
+                .arg(
+                    Arg::with_name("out")
+                        .long("out")
+                        .takes_value(true)
+                        .required(true)
+                        .value_name("DIR")
+                        .help("Directory to write the testcases to"),
+                ),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        ("solve", Some(solve)) => run_solve(solve),
+        ("findings", Some(findings)) => run_findings(findings),
+        ("corpus", Some(corpus)) => run_corpus(corpus),
+        _ => {}
+    }
+}
+
+// the args shared by every subcommand, about the target and its inputs
+fn run_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app.arg(
+        Arg::with_name("binary")
+            .takes_value(true)
+            .required(true)
+            .help("Path to the target binary"),
+    )
+    .arg(
+        Arg::with_name("arg")
+            .long("arg")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .allow_hyphen_values(true)
+            .value_name("ARG")
+            .help("Argument after the program name, sym:N for N symbolic bytes"),
+    )
+    .arg(
+        Arg::with_name("sym_stdin")
+            .long("sym-stdin")
+            .takes_value(true)
+            .value_name("N")
+            .help("Make stdin up to N symbolic bytes"),
+    )
+    .arg(
+        Arg::with_name("call")
+            .long("call")
+            .takes_value(true)
+            .value_name("ADDR")
+            .help("Start at the function at ADDR instead of the entry point"),
+    )
+    .arg(
+        Arg::with_name("timeout")
+            .long("timeout")
+            .takes_value(true)
+            .value_name("SECS")
+            .help("Stop exploring after SECS seconds"),
+    )
+    .arg(
+        Arg::with_name("json")
+            .short("j")
+            .long("json")
+            .help("Output JSON"),
+    )
+}
+
+// make the radius instance and the initial state from the shared args
+fn setup(matches: &ArgMatches, mut options: Vec<RadiusOption>) -> (Radius, State) {
+    let path = matches.value_of("binary").unwrap_or_default();
+    if let Some(n) = matches.value_of("sym_stdin").and_then(|n| n.parse().ok()) {
+        options.push(RadiusOption::SymbolicStdin(n));
+    }
+    if let Some(secs) = matches.value_of("timeout").and_then(|t| t.parse().ok()) {
+        options.push(RadiusOption::Timeout(Duration::from_secs(secs)));
+    }
+
+    let mut radius = Radius::new_with_options(Some(path), &options);
+    let state = if let Some(call) = matches.value_of("call") {
+        let addr = address(&mut radius, call);
+        radius.call_state(addr)
+    } else {
+        let mut args = vec![SimArg::Concrete(path.to_owned())];
+        args.extend(collect!(matches, "arg").into_iter().map(parse_arg));
+        radius.entry_state_with(&args, &[])
+    };
+    (radius, state)
+}
+
+// args like sym:N are N symbolic bytes
+fn parse_arg(arg: &str) -> SimArg {
+    match arg.strip_prefix("sym:").and_then(|n| n.parse().ok()) {
+        Some(n) => SimArg::Symbolic(n),
+        None => SimArg::Concrete(arg.to_owned()),
+    }
+}
+
+fn address(radius: &mut Radius, addr: &str) -> u64 {
+    radius.get_address(addr).unwrap_or_else(|e| {
+        eprintln!("could not resolve {}: {}", addr, e);
+        process::exit(1);
+    })
+}
+
+// keep the state if its stdout can contain the expected output
+fn expect_output(mut state: State, output: Option<&str>) -> Option<State> {
+    match output {
+        Some(output) if !state.stdout().expect(output.as_bytes()) => None,
+        _ => Some(state),
+    }
+}
+
+fn run_solve(matches: &ArgMatches) {
+    let (mut radius, state) = setup(matches, vec![]);
+    let avoid: Vec<u64> = collect!(matches, "avoid")
+        .into_iter()
+        .map(|a| address(&mut radius, a))
+        .collect();
+    let output = matches.value_of("constrain_output");
+
+    let found = if let Some(find) = matches.value_of("find") {
+        // keep exploring past the states at find whose output doesn't match
+        let find = address(&mut radius, find);
+        let mut manager = StateManager::new(state);
+        manager.keep_ended = false;
+        let mut found = None;
+        while found.is_none() && radius.explore(&mut manager, find, &avoid, 1) > 0 {
+            found = manager
+                .take(FOUND)
+                .into_iter()
+                .find_map(|s| expect_output(s, output));
+        }
+        found
+    } else {
+        radius.avoid(&avoid);
+        radius
+            .run_all(state)
+            .into_iter()
+            .find_map(|s| expect_output(s, output))
+    };
+    radius.close();
+
+    let mut state = found.unwrap_or_else(|| {
+        eprintln!("no solution found");
+        process::exit(1);
+    });
+    let inputs = state.dump_inputs();
+    if matches.occurrences_of("json") > 0 {
+        let inputs: serde_json::Map<String, serde_json::Value> = inputs
+            .iter()
+            .map(|(name, bytes)| (name.to_owned(), json!(String::from_utf8_lossy(bytes))))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&inputs).unwrap());
+    } else {
+        for (name, bytes) in &inputs {
+            println!("{}: {:?}", name, String::from_utf8_lossy(bytes));
+        }
+    }
+}
+
+fn run_findings(matches: &ArgMatches) {
+    let options = vec![
+        RadiusOption::Permissions(true),
+        RadiusOption::HeapCheck(true),
+        RadiusOption::CheckPcControl(true),
+    ];
+    let (mut radius, state) = setup(matches, options);
+    radius.run_all(state);
+    let findings = radius.findings();
+    radius.close();
+
+    if matches.occurrences_of("json") > 0 {
+        let report: Vec<serde_json::Value> = findings
+            .ranked()
+            .iter()
+            .map(|f| {
+                json!({
+                    "class": format!("{:?}", f.class),
+                    "pc": f.pc,
+                    "addr": f.addr,
+                    "severity": f.severity(),
+                    "count": f.count,
+                    "inputs": f.inputs.iter()
+                        .map(|(name, bytes)| (name.to_owned(), json!(String::from_utf8_lossy(bytes))))
+                        .collect::<serde_json::Map<_, _>>(),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        findings.print();
+    }
+}
+
+fn run_corpus(matches: &ArgMatches) {
+    let (mut radius, state) = setup(matches, vec![]);
+    let out = matches.value_of("out").unwrap_or_default();
+    let written = radius.explore_and_dump(state, out);
+    radius.close();
+
+    let count = written.unwrap_or_else(|e| {
+        eprintln!("could not write the corpus: {}", e);
+        process::exit(1);
+    });
+    if matches.occurrences_of("json") > 0 {
+        println!("{}", json!({ "testcases": count, "dir": out }));
+    } else {
+        println!("wrote {} testcases to {}", count, out);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::state::State;
+use std::fs;
+
+// resident memory is only checked every this many steps
+pub const MEMORY_CHECK_STEPS: u64 = 1024;
+
+// /proc/self/statm counts pages
+const PAGE_SIZE: u64 = 4096;
+
+/// Why a run stopped, see `RunResult`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StopReason {
+    /// Every state ended without reaching a target
+    #[default]
+    Exhausted,
+    /// A state reached a target
+    Found,
+    /// The run took longer than `RadiusOption::Timeout`
+    Timeout,
+    /// The run stepped states more than `RadiusOption::MaxSteps` times
+    MaxSteps,
+    /// The process used more memory than `RadiusOption::MaxMemory`
+    MaxMemory,
+}
+
+/// The outcome of `Radius::run_budgeted`
+///
+/// ## Example
+/// ```
+/// use radius2::budget::StopReason;
+/// use radius2::radius::{Radius, RadiusOption};
+/// use std::time::Duration;
+/// let options = [RadiusOption::Timeout(Duration::from_secs(60))];
+/// let mut radius = Radius::new_with_options(Some("../tests/r100"), &options);
+/// let mut state = radius.call_state(0x004006fd);
+/// state.symbolize_memory("flag", 0x100000, 12);
+/// state.registers.set("rdi", radius2::vc(0x100000));
+/// let result = radius.run_budgeted(state, Some(0x004007a1), &[0x00400790]);
+/// assert_eq!(result.reason, StopReason::Found);
+/// assert!(result.survivors.is_empty());
+/// ```
+#[derive(Clone, Default)]
+pub struct RunResult {
+    pub reason: StopReason,
+    /// The states that reached a target
+    pub found: Vec<State>,
+    /// The states left to explore when a budget stopped the run
+    pub survivors: Vec<State>,
+    /// Number of steps the run took
+    pub steps: u64,
+}
+
+/// The resident memory of this process in bytes, on Linux
+pub fn resident_memory() -> Option<u64> {
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * PAGE_SIZE)
+}
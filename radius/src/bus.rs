@@ -0,0 +1,154 @@
+use crate::findings::Finding;
+use crate::state::StateStatus;
+use serde_json::json;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+/// How detailed an event is, subscribers get the events at or above
+/// their level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EventLevel {
+    /// Every instruction and solver query, a lot of events
+    Trace,
+    /// Forks, pruned states, and sim calls
+    Debug,
+    /// Targets, findings, and the end of states and runs
+    Info,
+}
+
+/// Why a state was dropped before it ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PruneReason {
+    /// It can't statically reach the targets, see `Radius::prune_unreachable`
+    Unreachable,
+    /// There were more states than `RadiusOption::MaxStates`
+    Shrunk,
+}
+
+/// Something that happened during a run, sent to the receivers of `Radius::subscribe`
+#[derive(Debug, Clone)]
+pub enum RunEvent {
+    /// A state executed the instruction at `pc`
+    Executed { pc: u64 },
+    /// Executing the instruction at `pc` made `count` solver queries
+    SolverQueries { pc: u64, count: u64, time: Duration },
+    /// The sim of `name` was called at `pc`
+    SimCalled { pc: u64, name: String },
+    /// A state at `pc` was dropped
+    Pruned { pc: u64, reason: PruneReason },
+    /// The state at `pc` forked, `count` states including itself continue
+    Forked { pc: u64, count: usize },
+    /// A state ended at `pc` without reaching a target, eg. unsat, avoided, or exited
+    Died { pc: u64, status: StateStatus },
+    /// A state reached a breakpoint, goal, or exit goal at `pc`
+    TargetReached { pc: u64 },
+    /// A state crashed or failed a heap check
+    Finding(Finding),
+    /// The run stopped at its timeout with `states` states left
+    Timeout { states: usize },
+}
+
+impl RunEvent {
+    pub fn level(&self) -> EventLevel {
+        match self {
+            RunEvent::Executed { .. } | RunEvent::SolverQueries { .. } => EventLevel::Trace,
+            RunEvent::Forked { .. } | RunEvent::SimCalled { .. } | RunEvent::Pruned { .. } => {
+                EventLevel::Debug
+            }
+            _ => EventLevel::Info,
+        }
+    }
+
+    /// The event as a JSON object with an `event` field naming it
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            RunEvent::Executed { pc } => json!({ "event": "executed", "pc": pc }),
+            RunEvent::SolverQueries { pc, count, time } => json!({
+                "event": "solver_queries",
+                "pc": pc,
+                "count": count,
+                "micros": time.as_micros() as u64,
+            }),
+            RunEvent::SimCalled { pc, name } => {
+                json!({ "event": "sim_called", "pc": pc, "name": name })
+            }
+            RunEvent::Pruned { pc, reason } => json!({
+                "event": "pruned",
+                "pc": pc,
+                "reason": format!("{:?}", reason).to_lowercase(),
+            }),
+            RunEvent::Forked { pc, count } => {
+                json!({ "event": "forked", "pc": pc, "count": count })
+            }
+            RunEvent::Died { pc, status } => json!({
+                "event": "died",
+                "pc": pc,
+                "status": format!("{:?}", status),
+            }),
+            RunEvent::TargetReached { pc } => json!({ "event": "target_reached", "pc": pc }),
+            RunEvent::Finding(finding) => json!({
+                "event": "finding",
+                "class": format!("{:?}", finding.class),
+                "pc": finding.pc,
+                "addr": finding.addr,
+                "severity": finding.severity(),
+            }),
+            RunEvent::Timeout { states } => json!({ "event": "timeout", "states": states }),
+        }
+    }
+}
+
+/// Sends run events over channels, so GUIs, loggers, and orchestrators
+/// can follow a run from other threads
+///
+/// ## Example
+/// ```
+/// use radius2::bus::RunEvent;
+/// use radius2::radius::Radius;
+/// let mut radius = Radius::new("../tests/r100");
+/// let events = radius.subscribe();
+/// let mut state = radius.call_state(0x004006fd);
+/// state.symbolize_memory("flag", 0x100000, 12);
+/// state.registers.set("rdi", radius2::vc(0x100000));
+/// radius.run_until(state, 0x004007a1, &[0x00400790]);
+/// let reached = events
+///     .try_iter()
+///     .any(|e| matches!(e, RunEvent::TargetReached { pc: 0x004007a1 }));
+/// assert!(reached);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EventBus {
+    subscribers: Vec<(EventLevel, Sender<RunEvent>)>,
+}
+
+impl EventBus {
+    /// Get a receiver of the `Debug` and `Info` events from now on
+    pub fn subscribe(&mut self) -> Receiver<RunEvent> {
+        self.subscribe_level(EventLevel::Debug)
+    }
+
+    /// Get a receiver of the events at or above `level` from now on
+    pub fn subscribe_level(&mut self, level: EventLevel) -> Receiver<RunEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push((level, sender));
+        receiver
+    }
+
+    /// Whether no one is listening, so events need not be made
+    pub fn is_empty(&self) -> bool {
+        self.subscribers.is_empty()
+    }
+
+    /// Whether anyone is listening to events of `level`
+    pub fn wants(&self, level: EventLevel) -> bool {
+        self.subscribers.iter().any(|(l, _)| *l <= level)
+    }
+
+    /// Send the event to each subscriber of its level, dropping the
+    /// ones that hung up
+    pub fn emit(&mut self, event: RunEvent) {
+        let level = event.level();
+        self.subscribers
+            .retain(|(l, s)| *l > level || s.send(event.clone()).is_ok());
+    }
+}
@@ -0,0 +1,78 @@
+use crate::state::{PathBranch, State};
+use crate::value::{vc, Value};
+use std::collections::BTreeMap;
+
+/// A branch on the path of a concolic run and the inputs that take
+/// its other side instead
+#[derive(Debug, Clone)]
+pub struct Flip {
+    /// Index of the branch in the path
+    pub index: usize,
+    pub addr: u64,
+    /// The successor the seed took
+    pub target: u64,
+    /// The successor the inputs take
+    pub other: u64,
+    pub inputs: BTreeMap<String, Vec<u8>>,
+}
+
+/// The path a seed drove a state along, see `Radius::run_concolic`
+#[derive(Clone)]
+pub struct ConcolicRun {
+    /// The state at the end of the path
+    pub state: State,
+    /// The symbolic branches along the path
+    pub path: Vec<PathBranch>,
+    /// New inputs for each branch whose other side is satisfiable
+    pub flips: Vec<Flip>,
+}
+
+/// The condition that the inputs of the state are the bytes of `seed`
+pub fn seed_condition(state: &State, seed: &BTreeMap<String, Vec<u8>>) -> Value {
+    let mut condition = vc(1);
+    for (source, bytes) in seed {
+        let input = match state.inputs.get(source) {
+            Some(input) => input,
+            None => continue,
+        };
+        for (byte, b) in input.iter().zip(bytes) {
+            if byte.is_symbolic() {
+                condition = condition.and(&byte.eq(&vc(*b as u64)));
+            }
+        }
+    }
+    condition
+}
+
+/// Solve for inputs that take the other side of each two way branch
+/// on the path of the state, keeping the branches before it
+pub fn flips(state: &mut State) -> Vec<Flip> {
+    let mut flips = vec![];
+    let mut solver = state.duplicate();
+    let assertions = solver.solver.assertions.clone();
+    let branches = solver.path.clone();
+
+    for (index, branch) in branches.iter().enumerate() {
+        let other = match branch.other {
+            Some(other) => other,
+            None => continue,
+        };
+        solver.solver.assertions = assertions[..branch.constraints].to_vec();
+        let opposite = !branch.condition.to_owned();
+        if !solver.solver.check_sat(&opposite) {
+            continue;
+        }
+        solver.solver.push();
+        solver.solver.assert(&opposite);
+        let inputs = solver.dump_inputs();
+        solver.solver.pop();
+        flips.push(Flip {
+            index,
+            addr: branch.addr,
+            target: branch.target,
+            other,
+            inputs,
+        });
+    }
+    flips
+}
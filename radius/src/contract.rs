@@ -0,0 +1,117 @@
+use crate::processor::ArgLocation;
+use crate::state::State;
+use crate::value::{vc, Value};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+/// Takes the args of the function and returns the condition assumed on entry
+pub type Precondition = fn(&mut State, &[Value]) -> Value;
+/// Takes the args on entry and the return value and returns the
+/// condition that must hold when the function returns
+pub type Postcondition = fn(&mut State, &[Value], &Value) -> Value;
+
+/// A precondition and postcondition of the function at `addr`
+#[derive(Debug, Clone)]
+pub struct Contract {
+    pub addr: u64,
+    /// Where the args are read from on entry
+    pub args: Vec<ArgLocation>,
+    pub pre: Precondition,
+    pub post: Postcondition,
+}
+
+/// A return from a function that can violate its postcondition
+#[derive(Debug, Clone)]
+pub struct Violation {
+    /// Address of the function
+    pub addr: u64,
+    /// Address that the function returned to
+    pub pc: u64,
+    /// Args and return value of the counterexample
+    pub args: Vec<u64>,
+    pub ret: u64,
+    /// Inputs of the counterexample
+    pub inputs: BTreeMap<String, Vec<u8>>,
+}
+
+pub type Violations = Rc<RefCell<Vec<Violation>>>;
+
+impl Contract {
+    /// Assume the precondition and save the args for the return
+    pub fn enter(&self, state: &mut State) {
+        let args: Vec<Value> = self.args.iter().map(|a| a.read(state)).collect();
+        let ret = state.return_address();
+
+        let pre = (self.pre)(state, &args);
+        state.assert(&pre);
+
+        let frames = state.context.entry("contracts".to_owned()).or_default();
+        let index = frames.len() / 2;
+        frames.push(vc(self.addr));
+        frames.push(ret);
+        state
+            .context
+            .insert(format!("contract_args{}", index), args);
+    }
+}
+
+/// Check the postcondition if the state returned from the last
+/// function with a contract that it entered
+pub fn check_return(
+    state: &mut State,
+    contracts: &HashMap<u64, Contract>,
+    violations: &Violations,
+) {
+    let mut frames = if let Some(frames) = state.context.get("contracts") {
+        frames.to_owned()
+    } else {
+        return;
+    };
+
+    if frames.len() < 2 || frames[frames.len() - 1] != state.registers.get_pc() {
+        return;
+    }
+    let index = frames.len() / 2 - 1;
+    let addr = frames[2 * index].as_u64().unwrap_or_default();
+    frames.truncate(2 * index);
+    state.context.insert("contracts".to_owned(), frames);
+    let args = state
+        .context
+        .remove(&format!("contract_args{}", index))
+        .unwrap_or_default();
+
+    let contract = if let Some(contract) = contracts.get(&addr) {
+        contract
+    } else {
+        return;
+    };
+
+    let cc = state.r2api.get_cc(addr).unwrap_or_default();
+    let ret = state.registers.get_with_alias(&cc.ret);
+    let post = (contract.post)(state, &args, &ret);
+    let violated = !post;
+    if !state.check(&violated) {
+        return;
+    }
+
+    state.solver.push();
+    state.assert(&violated);
+    let violation = Violation {
+        addr,
+        pc: state.registers.get_pc().as_u64().unwrap_or_default(),
+        args: args
+            .iter()
+            .map(|a| state.solver.eval_to_u64(a).unwrap_or_default())
+            .collect(),
+        ret: state.solver.eval_to_u64(&ret).unwrap_or_default(),
+        inputs: state.dump_inputs(),
+    };
+    state.solver.pop();
+
+    println!(
+        "warning: postcondition of 0x{:x} violated returning to 0x{:x}",
+        violation.addr, violation.pc
+    );
+    violations.borrow_mut().push(violation);
+}
@@ -0,0 +1,64 @@
+use crate::r2_api::R2Result;
+use crate::state::State;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+/// Testcases written as states reach new blocks and end, each input
+/// source gets a directory of files like `stdin/id_000003` that can
+/// seed a fuzzer like AFL++, see `Radius::explore_and_dump`
+#[derive(Debug, Clone)]
+pub struct Corpus {
+    pub dir: PathBuf,
+    /// Number of testcases written
+    pub count: usize,
+    blocks: HashSet<u64>,
+    seen: HashSet<BTreeMap<String, Vec<u8>>>,
+}
+
+impl Corpus {
+    pub fn new(dir: &str) -> R2Result<Self> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        Ok(Corpus {
+            dir: PathBuf::from(dir),
+            count: 0,
+            blocks: HashSet::new(),
+            seen: HashSet::new(),
+        })
+    }
+
+    /// Write the inputs of the state if no testcase reached its pc yet
+    pub fn add_block(&mut self, state: &mut State) -> bool {
+        let pc = state.registers.get_pc().as_u64().unwrap_or_default();
+        self.blocks.insert(pc) && self.add(state)
+    }
+
+    /// Write the inputs of the state, unless they are the same as
+    /// those of a testcase already written or there are none
+    pub fn add(&mut self, state: &mut State) -> bool {
+        let inputs = state.dump_inputs();
+        if inputs.is_empty() || self.seen.contains(&inputs) {
+            return false;
+        }
+        let id = format!("id_{:06}", self.count);
+        for (source, bytes) in &inputs {
+            let dir = self.dir.join(file_name(source));
+            let written = fs::create_dir_all(&dir).and_then(|_| fs::write(dir.join(&id), bytes));
+            if let Err(e) = written {
+                println!("could not write testcase {} for {}: {}", id, source, e);
+            }
+        }
+        self.seen.insert(inputs);
+        self.count += 1;
+        true
+    }
+}
+
+// input sources like argv[1] as file names like argv_1
+fn file_name(source: &str) -> String {
+    let name: String = source
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    name.trim_matches('_').to_owned()
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// The blocks covered by a run, by address with the name of the function
+/// each block is in. Covered addresses outside of analyzed functions are
+/// kept with an empty function name
+///
+/// ## Example
+/// ```
+/// use radius2::radius::Radius;
+/// let mut radius = Radius::new("../tests/r100");
+/// let state = radius.call_state(0x004006fd);
+/// radius.run_until(state, 0x004007a1, &[0x00400790]);
+/// let before = radius.coverage();
+///
+/// let state = radius.call_state(0x004006fd);
+/// radius.run_until(state, 0x00400790, &[]);
+/// radius.coverage().diff(&before).print();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Coverage {
+    pub blocks: BTreeMap<u64, String>,
+}
+
+/// The blocks a run newly covered and no longer covered compared to another
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoverageDiff {
+    pub new: Vec<(u64, String)>,
+    pub lost: Vec<(u64, String)>,
+}
+
+impl Coverage {
+    /// Read coverage saved with `save`
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let data = fs::read(path)?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        fs::write(path, serde_json::to_vec(self)?)
+    }
+
+    /// Get the blocks covered here but not in `old` and the reverse
+    pub fn diff(&self, old: &Coverage) -> CoverageDiff {
+        let new = self
+            .blocks
+            .iter()
+            .filter(|(addr, _)| !old.blocks.contains_key(addr))
+            .map(|(addr, func)| (*addr, func.to_owned()))
+            .collect();
+        let lost = old
+            .blocks
+            .iter()
+            .filter(|(addr, _)| !self.blocks.contains_key(addr))
+            .map(|(addr, func)| (*addr, func.to_owned()))
+            .collect();
+        CoverageDiff { new, lost }
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+impl CoverageDiff {
+    /// Print the new and lost blocks grouped by function
+    pub fn print(&self) {
+        println!(
+            "{} new blocks, {} lost blocks",
+            self.new.len(),
+            self.lost.len()
+        );
+        print_blocks("+", &self.new);
+        print_blocks("-", &self.lost);
+    }
+}
+
+fn print_blocks(sign: &str, blocks: &[(u64, String)]) {
+    let mut functions: BTreeMap<&str, Vec<u64>> = BTreeMap::new();
+    for (addr, func) in blocks {
+        functions.entry(func.as_str()).or_default().push(*addr);
+    }
+    for (func, addrs) in functions {
+        let func = if func.is_empty() {
+            "(no function)"
+        } else {
+            func
+        };
+        let addrs: Vec<String> = addrs.iter().map(|a| format!("0x{:x}", a)).collect();
+        println!(
+            "{} {:<32} {:>4}  {}",
+            sign,
+            func,
+            addrs.len(),
+            addrs.join(" ")
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use crate::memory::READ_CACHE;
+use crate::pretty;
+use crate::solver::{BitVec, Solver};
+use crate::state::State;
+use crate::value::Value;
+use std::collections::{BTreeSet, HashMap};
+
+/// A register whose value differs between two states
+#[derive(Debug, Clone)]
+pub struct RegisterDiff {
+    pub name: String,
+    pub left: Value,
+    pub right: Value,
+}
+
+/// A range of consecutive bytes that differ between two states
+#[derive(Debug, Clone)]
+pub struct MemoryDiff {
+    pub addr: u64,
+    pub left: Vec<Value>,
+    pub right: Vec<Value>,
+}
+
+/// The registers, memory, and constraints that differ between two
+/// states, see `State::diff`. Symbolic values differ if the constraints
+/// of the first state let them be unequal
+///
+/// ## Example
+/// ```
+/// use radius2::radius::Radius;
+/// let mut radius = Radius::new("../tests/r100");
+/// let mut state = radius.call_state(0x004006fd);
+/// state.symbolize_memory("flag", 0x100000, 12);
+/// state.registers.set("rdi", radius2::vc(0x100000));
+/// let start = state.clone();
+/// let found = radius.run_until(state, 0x004007a1, &[0x00400790]).unwrap();
+/// let diff = start.diff(&found);
+/// assert!(!diff.registers.is_empty());
+/// diff.print();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StateDiff {
+    pub registers: Vec<RegisterDiff>,
+    pub memory: Vec<MemoryDiff>,
+    /// Constraints of the first state that the second does not have
+    pub left_constraints: Vec<BitVec>,
+    /// Constraints of the second state that the first does not have
+    pub right_constraints: Vec<BitVec>,
+}
+
+impl StateDiff {
+    pub fn new(left: &State, right: &State) -> Self {
+        let mut solver = left.solver.clone();
+        StateDiff {
+            registers: StateDiff::registers(&mut solver, left, right),
+            memory: StateDiff::memory(&mut solver, left, right),
+            left_constraints: StateDiff::constraints(left, right),
+            right_constraints: StateDiff::constraints(right, left),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty()
+            && self.memory.is_empty()
+            && self.left_constraints.is_empty()
+            && self.right_constraints.is_empty()
+    }
+
+    // compare the whole registers, named by the widest register of each
+    fn registers(solver: &mut Solver, left: &State, right: &State) -> Vec<RegisterDiff> {
+        let mut widest: HashMap<usize, usize> = HashMap::new();
+        for reg in &left.registers.indexes {
+            let index = widest.entry(reg.value_index).or_insert(reg.index);
+            if reg.reg_info.size > left.registers.indexes[*index].reg_info.size {
+                *index = reg.index;
+            }
+        }
+
+        let mut indexes: Vec<usize> = widest.into_values().collect();
+        indexes.sort_unstable();
+        indexes
+            .into_iter()
+            .filter_map(|index| {
+                let reg = &left.registers.indexes[index];
+                let l = &left.registers.values[reg.value_index];
+                let r = right.registers.values.get(reg.value_index)?;
+                (!same(solver, l, r)).then(|| RegisterDiff {
+                    name: reg.reg_info.name.to_owned(),
+                    left: l.to_owned(),
+                    right: r.to_owned(),
+                })
+            })
+            .collect()
+    }
+
+    // compare every chunk either state has, a chunk only one state
+    // has loaded is compared against the bytes of the binary
+    fn memory(solver: &mut Solver, left: &State, right: &State) -> Vec<MemoryDiff> {
+        let chunks: BTreeSet<u64> = left
+            .memory
+            .mem
+            .keys()
+            .chain(right.memory.mem.keys())
+            .copied()
+            .collect();
+
+        let mut r2api = left.memory.r2api.clone();
+        let mut original = |addr: u64| -> Vec<Value> {
+            let bytes = r2api.read(addr, READ_CACHE).unwrap_or_default();
+            bytes
+                .iter()
+                .map(|b| Value::Concrete(*b as u64, 0))
+                .collect()
+        };
+
+        let mut diffs: Vec<MemoryDiff> = vec![];
+        for caddr in chunks {
+            let l = left.memory.mem.get(&caddr).cloned();
+            let r = right.memory.mem.get(&caddr).cloned();
+            let (l, r) = match (l, r) {
+                (Some(l), Some(r)) => (l, r),
+                (Some(l), None) => (l, original(caddr)),
+                (None, Some(r)) => (original(caddr), r),
+                (None, None) => continue,
+            };
+
+            for (i, (lb, rb)) in l.iter().zip(&r).enumerate() {
+                if same(solver, lb, rb) {
+                    continue;
+                }
+                let addr = caddr + i as u64;
+                match diffs.last_mut() {
+                    Some(d) if d.addr + d.left.len() as u64 == addr => {
+                        d.left.push(lb.to_owned());
+                        d.right.push(rb.to_owned());
+                    }
+                    _ => diffs.push(MemoryDiff {
+                        addr,
+                        left: vec![lb.to_owned()],
+                        right: vec![rb.to_owned()],
+                    }),
+                }
+            }
+        }
+        diffs
+    }
+
+    // the constraints of a that b does not have
+    fn constraints(a: &State, b: &State) -> Vec<BitVec> {
+        let other: BTreeSet<_> = b
+            .solver
+            .assertions
+            .iter()
+            .filter_map(|c| a.solver.translate(c))
+            .map(|c| c.get_id())
+            .collect();
+        a.solver
+            .assertions
+            .iter()
+            .filter(|c| !other.contains(&c.get_id()))
+            .cloned()
+            .collect()
+    }
+
+    pub fn print(&self) {
+        for reg in &self.registers {
+            println!(
+                "{:<8} {} -> {}",
+                reg.name,
+                show(&reg.left),
+                show(&reg.right)
+            );
+        }
+        for mem in &self.memory {
+            let left: Vec<String> = mem.left.iter().map(show).collect();
+            let right: Vec<String> = mem.right.iter().map(show).collect();
+            println!(
+                "0x{:08x} [{}] -> [{}]",
+                mem.addr,
+                left.join(" "),
+                right.join(" ")
+            );
+        }
+        for c in &self.left_constraints {
+            println!("- {:?}", c);
+        }
+        for c in &self.right_constraints {
+            println!("+ {:?}", c);
+        }
+    }
+}
+
+// values are the same if they are equal constants or can't be unequal,
+// `b` is from the other state so it is moved to the solver of `a` first
+fn same(solver: &mut Solver, a: &Value, b: &Value) -> bool {
+    let b = match b.try_con() {
+        Value::Symbolic(bv, t) => match solver.translate(&bv) {
+            Some(bv) => Value::Symbolic(bv, t),
+            None => return false,
+        },
+        b => b,
+    };
+    match (a.try_con(), b) {
+        (Value::Concrete(a, _), Value::Concrete(b, _)) => a == b,
+        (Value::Symbolic(a, _), Value::Symbolic(b, _)) if a.get_id() == b.get_id() => true,
+        (a, b) => !solver.check_sat(&!a.eq(&b)),
+    }
+}
+
+fn show(value: &Value) -> String {
+    pretty::expression(value)
+}
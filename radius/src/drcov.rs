@@ -0,0 +1,134 @@
+use crate::processor::{Processor, BLOCK_END_TYPES};
+use crate::r2_api::R2Api;
+use std::fs;
+
+/// A file mapped in memory that covered blocks are relative to
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrcovModule {
+    pub path: String,
+    pub base: u64,
+    pub end: u64,
+}
+
+/// Covered blocks in the drcov format of DynamoRIO, which Lighthouse
+/// and Cutter load to show the coverage on the disassembly
+///
+/// ## Example
+/// ```
+/// use radius2::radius::Radius;
+/// let mut radius = Radius::new("../tests/r100");
+/// let state = radius.call_state(0x004006fd);
+/// radius.run_until(state, 0x004007a1, &[0x00400790]);
+/// radius.write_coverage("/tmp/r100.drcov").unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Drcov {
+    pub modules: Vec<DrcovModule>,
+    /// Address and size of each block
+    pub blocks: Vec<(u64, u64)>,
+}
+
+impl Drcov {
+    /// Group the executed instructions at `addrs` into blocks of
+    /// consecutive instructions, with the sizes the processor lifted
+    pub fn new(r2api: &mut R2Api, processor: &Processor, addrs: &[u64]) -> Self {
+        let mut addrs = addrs.to_vec();
+        addrs.sort_unstable();
+        addrs.dedup();
+
+        let mut blocks: Vec<(u64, u64)> = vec![];
+        let mut ended = true;
+        for addr in addrs {
+            let (size, end) = processor
+                .instructions
+                .get(&addr)
+                .map(|e| {
+                    let t = &e.instruction.r#type;
+                    (
+                        e.instruction.size,
+                        BLOCK_END_TYPES.iter().any(|b| t.contains(b)),
+                    )
+                })
+                .unwrap_or((1, true));
+
+            match blocks.last_mut() {
+                // drcov block sizes are 16 bits
+                Some((start, len)) if !ended && *start + *len == addr && *len + size <= 0xffff => {
+                    *len += size
+                }
+                _ => blocks.push((addr, size)),
+            }
+            ended = end;
+        }
+
+        Drcov {
+            modules: Drcov::modules(r2api),
+            blocks,
+        }
+    }
+
+    // the binary and the libraries loaded with it
+    fn modules(r2api: &mut R2Api) -> Vec<DrcovModule> {
+        let segments = r2api.get_segments().unwrap_or_default();
+        let base = r2api.info.bin.baddr;
+        let end = segments
+            .iter()
+            .map(|s| s.vaddr + s.vsize)
+            .max()
+            .unwrap_or(base);
+        let path = r2api.info.core.file.to_owned();
+
+        let mut modules = vec![DrcovModule {
+            path: path.to_owned(),
+            base,
+            end,
+        }];
+        for file in r2api.get_files().unwrap_or_default() {
+            if file.uri != path && file.from != 0 && file.size > 0 {
+                modules.push(DrcovModule {
+                    path: file.uri.to_owned(),
+                    base: file.from,
+                    end: file.from + file.size as u64,
+                });
+            }
+        }
+        modules
+    }
+
+    /// Get the file contents, blocks outside of the modules are left out
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut entries = vec![];
+        for (addr, size) in &self.blocks {
+            let module = self
+                .modules
+                .iter()
+                .position(|m| *addr >= m.base && *addr < m.end);
+            if let Some(id) = module {
+                entries.push((addr - self.modules[id].base, *size, id));
+            }
+        }
+
+        let mut header = String::from("DRCOV VERSION: 2\nDRCOV FLAVOR: drcov\n");
+        header += &format!("Module Table: version 2, count {}\n", self.modules.len());
+        header += "Columns: id, base, end, entry, checksum, timestamp, path\n";
+        for (id, module) in self.modules.iter().enumerate() {
+            header += &format!(
+                "{:>3}, 0x{:016x}, 0x{:016x}, 0x{:016x}, 0x{:08x}, 0x{:08x}, {}\n",
+                id, module.base, module.end, 0, 0, 0, module.path
+            );
+        }
+        header += &format!("BB Table: {} bbs\n", entries.len());
+
+        let mut data = header.into_bytes();
+        for (offset, size, id) in entries {
+            data.extend((offset as u32).to_le_bytes());
+            data.extend((size as u16).to_le_bytes());
+            data.extend((id as u16).to_le_bytes());
+        }
+        data
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+}
@@ -0,0 +1,148 @@
+use crate::state::State;
+use crate::value::{vc, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// How a concrete run of one model of the inputs ended
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Outcome {
+    /// Reached a breakpoint at the address, eg. the target of `run_until`
+    Break(u64),
+    /// Exited with the code
+    Exit(u64),
+    /// Crashed accessing the address
+    Crash(u64),
+    /// Ended another way, eg. at an avoided address
+    Ended,
+}
+
+/// A model of the input symbols and the outcome of running it
+#[derive(Debug, Clone)]
+pub struct EnumeratedRun {
+    /// The value of each symbol by name, eg. `flag[0]`
+    pub values: BTreeMap<String, u64>,
+    pub outcome: Outcome,
+}
+
+/// The runs of every model of the inputs enumerated by `Radius::enumerate`
+#[derive(Debug, Clone, Default)]
+pub struct Enumeration {
+    pub runs: Vec<EnumeratedRun>,
+    /// True if every model was run before reaching the limit
+    pub complete: bool,
+}
+
+impl Enumeration {
+    /// Count the runs with each outcome
+    pub fn table(&self) -> BTreeMap<Outcome, usize> {
+        let mut table = BTreeMap::new();
+        for run in &self.runs {
+            *table.entry(run.outcome.to_owned()).or_insert(0) += 1;
+        }
+        table
+    }
+
+    /// Get the runs that ended with `outcome`
+    pub fn with_outcome(&self, outcome: &Outcome) -> Vec<&EnumeratedRun> {
+        self.runs.iter().filter(|r| &r.outcome == outcome).collect()
+    }
+
+    pub fn print(&self) {
+        let total = self.runs.len();
+        let complete = if self.complete { "all" } else { "first" };
+        println!("{} {} models", complete, total);
+        for (outcome, count) in self.table() {
+            let percent = 100.0 * count as f64 / total.max(1) as f64;
+            println!("{:>8} {:>6.2}%  {:?}", count, percent, outcome);
+        }
+    }
+}
+
+/// The symbols of the inputs and named symbols of the state, by name
+pub fn input_symbols(state: &State) -> Vec<(String, Value)> {
+    let mut symbols: Vec<(String, Value)> = vec![];
+    let values = state
+        .inputs
+        .values()
+        .flatten()
+        .chain(state.symbols.values());
+    for value in values {
+        if let Value::Symbolic(bv, _t) = value {
+            if let Some(name) = bv.get_symbol() {
+                if !symbols.iter().any(|(n, _)| n == name) {
+                    symbols.push((name.to_owned(), value.to_owned()));
+                }
+            }
+        }
+    }
+    symbols
+}
+
+/// Get up to `limit` models of `symbols` that satisfy the constraints
+/// of the state, and whether there are no more
+pub fn models(
+    state: &mut State,
+    symbols: &[(String, Value)],
+    limit: usize,
+) -> (Vec<BTreeMap<String, u64>>, bool) {
+    let mut models = vec![];
+    let mut enumerator = state.duplicate();
+    let symbols: Vec<(String, Value)> = symbols
+        .iter()
+        .map(|(n, v)| (n.to_owned(), enumerator.solver.translate_value(v)))
+        .collect();
+
+    while models.len() < limit {
+        if !enumerator.is_sat() {
+            return (models, true);
+        }
+        let solver = &mut enumerator.solver;
+        solver.push();
+        let model: BTreeMap<String, u64> = symbols
+            .iter()
+            .map(|(n, v)| (n.to_owned(), solver.evalcon_to_u64(v).unwrap_or_default()))
+            .collect();
+        solver.pop();
+
+        // exclude the model to get a different one next
+        let differs = symbols
+            .iter()
+            .map(|(n, v)| !v.eq(&vc(model[n])))
+            .reduce(|a, b| a.or(&b));
+        models.push(model);
+        match differs {
+            Some(differs) => enumerator.assert(&differs),
+            None => return (models, true),
+        }
+    }
+    let complete = !enumerator.is_sat();
+    (models, complete)
+}
+
+/// Constrain the symbols of the state to the model and replace them with
+/// their values in registers and memory, so the run is mostly concrete
+pub fn concretize(state: &mut State, symbols: &[(String, Value)], model: &BTreeMap<String, u64>) {
+    for (name, value) in symbols {
+        if let Some(v) = model.get(name) {
+            let value = state.solver.translate_value(value);
+            state.assert(&value.eq(&vc(*v)));
+        }
+    }
+
+    let values: HashMap<&str, u64> = model.iter().map(|(n, v)| (n.as_str(), *v)).collect();
+    let concrete = |value: &mut Value| {
+        let replaced = match value {
+            Value::Symbolic(bv, t) => bv
+                .get_symbol()
+                .and_then(|name| values.get(name))
+                .map(|v| Value::Concrete(*v, *t)),
+            _ => None,
+        };
+        if let Some(replaced) = replaced {
+            *value = replaced;
+        }
+    };
+    state.registers.values.iter_mut().for_each(&concrete);
+    for chunk in state.memory.mem.values_mut() {
+        chunk.iter_mut().for_each(&concrete);
+    }
+}
@@ -0,0 +1,333 @@
+use crate::state::{State, StateStatus};
+use crate::value::{vc, Value};
+use serde::{Deserialize, Serialize};
+
+pub const ACCESS_VIOLATION: u64 = 0xc0000005;
+pub const INTEGER_DIVIDE_BY_ZERO: u64 = 0xc0000094;
+pub const ILLEGAL_INSTRUCTION: u64 = 0xc000001d;
+pub const DATATYPE_MISALIGNMENT: u64 = 0x80000002;
+pub const BREAKPOINT: u64 = 0x80000003;
+
+/// Handlers return to this address (in the KUSER_SHARED_DATA page)
+/// which is intercepted to continue the dispatch
+pub const EXCEPTION_RETURN: u64 = 0x7ffe0ff0;
+
+// return values of vectored handlers and filters
+const EXCEPTION_CONTINUE_EXECUTION: u64 = 0xffffffff;
+const EXCEPTION_EXECUTE_HANDLER: u64 = 1;
+
+// return value of frame based handlers
+const EXCEPTION_DISPOSITION_CONTINUE: u64 = 0;
+
+/// The phases of dispatch, handlers are tried in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Phase {
+    Vectored,
+    Structured,
+    Unhandled,
+    Finished,
+}
+
+/// An exception being dispatched to the handlers, see `raise`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dispatch {
+    pub code: u64,
+    /// Address of the faulting instruction
+    pub address: u64,
+    pub record: u64,
+    pub context: u64,
+    /// Stack the handlers are called on
+    pub stack: u64,
+    pub phase: Phase,
+    /// Index of the next vectored handler
+    pub index: usize,
+    /// Next frame of the SEH chain
+    pub frame: u64,
+    /// Address and permission of the crash if nothing handles it
+    pub fault: u64,
+    pub perm: char,
+}
+
+// (register, offset, size) of the saved registers in CONTEXT
+const CONTEXT32: [(&str, u64, usize); 10] = [
+    ("edi", 0x9c, 4),
+    ("esi", 0xa0, 4),
+    ("ebx", 0xa4, 4),
+    ("edx", 0xa8, 4),
+    ("ecx", 0xac, 4),
+    ("eax", 0xb0, 4),
+    ("ebp", 0xb4, 4),
+    ("eip", 0xb8, 4),
+    ("eflags", 0xc0, 4),
+    ("esp", 0xc4, 4),
+];
+
+const CONTEXT64: [(&str, u64, usize); 18] = [
+    ("rflags", 0x44, 4),
+    ("rax", 0x78, 8),
+    ("rcx", 0x80, 8),
+    ("rdx", 0x88, 8),
+    ("rbx", 0x90, 8),
+    ("rsp", 0x98, 8),
+    ("rbp", 0xa0, 8),
+    ("rsi", 0xa8, 8),
+    ("rdi", 0xb0, 8),
+    ("r8", 0xb8, 8),
+    ("r9", 0xc0, 8),
+    ("r10", 0xc8, 8),
+    ("r11", 0xd0, 8),
+    ("r12", 0xd8, 8),
+    ("r13", 0xe0, 8),
+    ("r14", 0xe8, 8),
+    ("r15", 0xf0, 8),
+    ("rip", 0xf8, 8),
+];
+
+/// Get the exception code for an ESIL trap number
+pub fn trap_code(trap: u64) -> Option<u64> {
+    match trap {
+        2 => Some(BREAKPOINT),
+        3 => Some(INTEGER_DIVIDE_BY_ZERO),
+        4..=6 => Some(ACCESS_VIOLATION),
+        7 => Some(ILLEGAL_INSTRUCTION),
+        8 => Some(DATATYPE_MISALIGNMENT),
+        _ => None,
+    }
+}
+
+/// Raise an access violation for a state that crashed accessing `addr`
+pub fn raise_access_violation(state: &mut State, addr: u64, perm: char) -> bool {
+    let kind = match perm {
+        'w' => 1,
+        'x' => 8,
+        _ => 0,
+    };
+    raise(state, ACCESS_VIOLATION, &[kind, addr], addr, perm)
+}
+
+/// Raise an exception at the current instruction and transfer control
+/// to the first handler, returns false if the exception is not handled.
+/// Vectored handlers are called first, then the SEH chain at fs:[0]
+/// (x86 only, x64 uses table based unwinding which is not modeled)
+/// and finally the filter set with SetUnhandledExceptionFilter
+pub fn raise(state: &mut State, code: u64, info: &[u64], fault: u64, perm: char) -> bool {
+    // exceptions during dispatch are not handled
+    if state.info.bin.arch != "x86" || state.exception.is_some() {
+        return false;
+    }
+
+    let bits = state.memory.bits;
+    let ptr = bits as usize / 8;
+    let pc = state.esil.prev_pc.as_u64().unwrap_or_default();
+    let sp = state
+        .solver
+        .evalcon_to_u64(&state.registers.get_with_alias("SP"))
+        .unwrap_or_default();
+
+    let (context_size, record_size, registers) = if bits == 64 {
+        (0x4d0, 0x98, &CONTEXT64[..])
+    } else {
+        (0x2cc, 0x50, &CONTEXT32[..])
+    };
+
+    // place the CONTEXT, EXCEPTION_RECORD and EXCEPTION_POINTERS below the stack
+    let context = (sp - context_size - 0x100) & !0xf;
+    let record = (context - record_size) & !0xf;
+    let pointers = (record - 2 * ptr as u64) & !0xf;
+    let stack = pointers - 0x40;
+
+    for offset in (0..context_size).step_by(4) {
+        state.memory.write_value(context + offset, &vc(0), 4);
+    }
+    for offset in (0..record_size).step_by(4) {
+        state.memory.write_value(record + offset, &vc(0), 4);
+    }
+
+    let flags = if bits == 64 { 0x10001f } else { 0x1003f };
+    let flags_offset = if bits == 64 { 0x30 } else { 0 };
+    state
+        .memory
+        .write_value(context + flags_offset, &vc(flags), 4);
+
+    for (reg, offset, size) in registers {
+        let value = if state.registers.regs.contains_key(*reg) {
+            state.registers.get(reg)
+        } else {
+            vc(0)
+        };
+        state.memory.write_value(context + offset, &value, *size);
+    }
+
+    // the faulting instruction is reported, not the next one
+    let pc_offset = if bits == 64 { 0xf8 } else { 0xb8 };
+    state.memory.write_value(context + pc_offset, &vc(pc), ptr);
+
+    // ExceptionCode, ExceptionAddress, NumberParameters, ExceptionInformation
+    state.memory.write_value(record, &vc(code), 4);
+    state
+        .memory
+        .write_value(record + 2 * ptr as u64, &vc(pc), ptr);
+    state
+        .memory
+        .write_value(record + 3 * ptr as u64, &vc(info.len() as u64), 4);
+    for (i, param) in info.iter().enumerate() {
+        let offset = 4 * ptr as u64 + (i * ptr) as u64;
+        state.memory.write_value(record + offset, &vc(*param), ptr);
+    }
+
+    state.memory.write_value(pointers, &vc(record), ptr);
+    state
+        .memory
+        .write_value(pointers + ptr as u64, &vc(context), ptr);
+
+    // head of the SEH chain is at the start of the TEB
+    let frame = if bits == 32 && state.registers.regs.contains_key("fs") {
+        let teb = state.registers.get("fs");
+        let head = state.memory.read_value(teb.as_u64().unwrap_or_default(), 4);
+        state.solver.evalcon_to_u64(&head).unwrap_or_default()
+    } else {
+        0
+    };
+
+    state.exception = Some(Dispatch {
+        code,
+        address: pc,
+        record,
+        context,
+        stack,
+        phase: Phase::Vectored,
+        index: 0,
+        frame,
+        fault,
+        perm,
+    });
+    state.status = StateStatus::Active;
+    dispatch_next(state)
+}
+
+/// Continue dispatching after a handler returns to `EXCEPTION_RETURN`.
+/// If the handler resolved the exception execution continues with the
+/// (possibly modified) CONTEXT, otherwise the next handler is called
+pub fn resume(state: &mut State) -> bool {
+    let dispatch = if let Some(dispatch) = state.exception {
+        dispatch
+    } else {
+        state.set_crash(EXCEPTION_RETURN, 'x');
+        return false;
+    };
+
+    let ret = state.registers.get_with_alias("R0");
+    let ret = state.solver.evalcon_to_u64(&ret).unwrap_or_default() & 0xffffffff;
+
+    let handled = match dispatch.phase {
+        Phase::Structured => ret == EXCEPTION_DISPOSITION_CONTINUE,
+        _ => ret == EXCEPTION_CONTINUE_EXECUTION,
+    };
+
+    if handled {
+        restore_context(state, dispatch.context);
+        state.exception = None;
+        true
+    } else if dispatch.phase == Phase::Finished && ret == EXCEPTION_EXECUTE_HANDLER {
+        // the filter asked for the process to terminate
+        state.exception = None;
+        state.status = StateStatus::Exit;
+        false
+    } else {
+        dispatch_next(state)
+    }
+}
+
+// call the next handler or mark the state crashed if there are none left
+fn dispatch_next(state: &mut State) -> bool {
+    let mut dispatch = state.exception.unwrap();
+    let bits = state.memory.bits;
+    let pointers = (dispatch.record - 2 * (bits / 8)) & !0xf;
+
+    loop {
+        match dispatch.phase {
+            Phase::Vectored => {
+                let handlers = &state.windows.vectored_handlers;
+                if let Some(handler) = handlers.get(dispatch.index).copied() {
+                    dispatch.index += 1;
+                    state.exception = Some(dispatch);
+                    call_handler(state, handler, &[pointers], dispatch.stack);
+                    return true;
+                }
+                dispatch.phase = Phase::Structured;
+            }
+            Phase::Structured => {
+                let frame = dispatch.frame;
+                if frame != 0 && frame != 0xffffffff {
+                    let next = state.memory.read_value(frame, 4);
+                    let handler = state.memory.read_value(frame + 4, 4);
+                    let next = state.solver.evalcon_to_u64(&next).unwrap_or_default();
+                    let handler = state.solver.evalcon_to_u64(&handler).unwrap_or_default();
+
+                    dispatch.frame = next;
+                    state.exception = Some(dispatch);
+                    let args = [dispatch.record, frame, dispatch.context, 0];
+                    call_handler(state, handler, &args, dispatch.stack);
+                    return true;
+                }
+                dispatch.phase = Phase::Unhandled;
+            }
+            Phase::Unhandled => {
+                dispatch.phase = Phase::Finished;
+                let filter = state.windows.unhandled_filter;
+                if filter != 0 {
+                    state.exception = Some(dispatch);
+                    call_handler(state, filter, &[pointers], dispatch.stack);
+                    return true;
+                }
+            }
+            Phase::Finished => {
+                // nothing handled it, so it is a real crash
+                state.exception = None;
+                state.set_crash(dispatch.fault, dispatch.perm);
+                return false;
+            }
+        }
+    }
+}
+
+// set up the args and return address and jump to the handler
+fn call_handler(state: &mut State, handler: u64, args: &[u64], stack: u64) {
+    let bits = state.memory.bits;
+    let ptr = bits as usize / 8;
+    let mut sp = stack;
+
+    if bits == 64 {
+        for (reg, arg) in ["rcx", "rdx", "r8", "r9"].iter().zip(args) {
+            state.registers.set(reg, vc(*arg));
+        }
+        sp -= 0x20; // shadow space
+    } else {
+        for arg in args.iter().rev() {
+            sp -= 4;
+            state.memory.write_value(sp, &vc(*arg), 4);
+        }
+    }
+
+    sp -= ptr as u64;
+    state.memory.write_value(sp, &vc(EXCEPTION_RETURN), ptr);
+    state.registers.set_with_alias("SP", vc(sp));
+    state.registers.set_pc(vc(handler));
+    state.backtrace.push((handler, EXCEPTION_RETURN));
+}
+
+// load the registers from the CONTEXT the handlers may have modified
+fn restore_context(state: &mut State, context: u64) {
+    let registers = if state.memory.bits == 64 {
+        &CONTEXT64[..]
+    } else {
+        &CONTEXT32[..]
+    };
+
+    for (reg, offset, size) in registers {
+        if state.registers.regs.contains_key(*reg) {
+            let value: Value = state.memory.read_value(context + offset, *size);
+            state.registers.set(reg, value);
+        }
+    }
+}
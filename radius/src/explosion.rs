@@ -0,0 +1,118 @@
+use crate::state::State;
+use crate::value::Value;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+
+/// Number of fork sites shown in a report
+pub const REPORT_SITES: usize = 10;
+
+/// An address where states forked
+#[derive(Debug, Clone, Default)]
+pub struct ForkSite {
+    pub addr: u64,
+    /// Number of new states created at the address
+    pub forks: usize,
+    /// Most times a single state reached the address, above 1 in loops
+    pub visits: usize,
+    /// Last symbolic condition of a branch at the address
+    pub condition: Option<Value>,
+}
+
+/// The fork sites seen during a run
+#[derive(Debug, Clone, Default)]
+pub struct ForkStats {
+    pub sites: HashMap<u64, ForkSite>,
+}
+
+impl ForkStats {
+    /// Record that `state` created `forks` new states at `addr`
+    pub fn record(&mut self, state: &State, addr: u64, forks: usize) {
+        let site = self.sites.entry(addr).or_insert_with(|| ForkSite {
+            addr,
+            ..ForkSite::default()
+        });
+        site.forks += forks;
+        site.visits = site
+            .visits
+            .max(state.visits.get(&addr).copied().unwrap_or(1));
+        if let Some(branch) = state.path.last().filter(|b| b.addr == addr) {
+            site.condition = Some(branch.condition.to_owned());
+        }
+    }
+}
+
+/// A fork site in a report with the inputs its condition depends on
+#[derive(Debug, Clone)]
+pub struct ReportSite {
+    pub addr: u64,
+    pub forks: usize,
+    pub visits: usize,
+    /// Input sources the branch condition depends on
+    pub symbols: Vec<String>,
+}
+
+/// Where the states came from when the number of states exceeded
+/// the threshold set with `RadiusOption::ExplosionReport`
+#[derive(Debug, Clone)]
+pub struct ExplosionReport {
+    /// Number of states when the report was made
+    pub states: usize,
+    pub steps: u64,
+    /// The sites with the most forks, most first
+    pub sites: Vec<ReportSite>,
+}
+
+impl ExplosionReport {
+    /// Make a report of the top sites in `stats`, resolving the inputs of
+    /// conditions with the inputs of `state`
+    pub fn new(stats: &ForkStats, state: &State, states: usize, steps: u64) -> Self {
+        let mut sites: Vec<&ForkSite> = stats.sites.values().collect();
+        sites.sort_by_key(|s| (Reverse(s.forks), s.addr));
+
+        let sites = sites
+            .into_iter()
+            .take(REPORT_SITES)
+            .map(|site| {
+                let symbols: BTreeSet<String> = site
+                    .condition
+                    .as_ref()
+                    .map(|c| state.input_positions(c))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(source, _)| source)
+                    .collect();
+                ReportSite {
+                    addr: site.addr,
+                    forks: site.forks,
+                    visits: site.visits,
+                    symbols: symbols.into_iter().collect(),
+                }
+            })
+            .collect();
+
+        ExplosionReport {
+            states,
+            steps,
+            sites,
+        }
+    }
+
+    pub fn print(&self) {
+        println!(
+            "state explosion: {} states after {} steps, top fork sites:",
+            self.states, self.steps
+        );
+        println!("{:<12} {:>8} {:>8}  inputs", "address", "forks", "visits");
+        for site in &self.sites {
+            let looped = if site.visits > 1 { " (loop)" } else { "" };
+            println!(
+                "0x{:010x} {:>8} {:>8}  {}{}",
+                site.addr,
+                site.forks,
+                site.visits,
+                site.symbols.join(","),
+                looped
+            );
+        }
+    }
+}
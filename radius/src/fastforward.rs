@@ -0,0 +1,277 @@
+use crate::state::State;
+use crate::value::{vc, Value};
+use std::collections::HashMap;
+use unicorn_engine::unicorn_const::{Arch, HookType, Mode, Permission};
+use unicorn_engine::{RegisterX86, Unicorn};
+
+/// Most instructions run natively before handing the state back
+pub const NATIVE_STEPS: usize = 0x10000;
+
+/// Steps to wait before trying to run natively again after no
+/// instruction could be run
+pub const NATIVE_RETRY: u64 = 32;
+
+const PAGE_SIZE: u64 = 0x1000;
+
+// the fs and gs registers of radius hold the bases of the segments
+const SEGMENT_BASES: [(&str, RegisterX86); 2] =
+    [("fs", RegisterX86::FS_BASE), ("gs", RegisterX86::GS_BASE)];
+
+// the registers copied into and out of the emulator, flags included
+const X64_REGISTERS: [(&str, RegisterX86); 18] = [
+    ("rax", RegisterX86::RAX),
+    ("rbx", RegisterX86::RBX),
+    ("rcx", RegisterX86::RCX),
+    ("rdx", RegisterX86::RDX),
+    ("rsi", RegisterX86::RSI),
+    ("rdi", RegisterX86::RDI),
+    ("rbp", RegisterX86::RBP),
+    ("rsp", RegisterX86::RSP),
+    ("r8", RegisterX86::R8),
+    ("r9", RegisterX86::R9),
+    ("r10", RegisterX86::R10),
+    ("r11", RegisterX86::R11),
+    ("r12", RegisterX86::R12),
+    ("r13", RegisterX86::R13),
+    ("r14", RegisterX86::R14),
+    ("r15", RegisterX86::R15),
+    ("rip", RegisterX86::RIP),
+    ("rflags", RegisterX86::EFLAGS),
+];
+
+const X86_REGISTERS: [(&str, RegisterX86); 10] = [
+    ("eax", RegisterX86::EAX),
+    ("ebx", RegisterX86::EBX),
+    ("ecx", RegisterX86::ECX),
+    ("edx", RegisterX86::EDX),
+    ("esi", RegisterX86::ESI),
+    ("edi", RegisterX86::EDI),
+    ("ebp", RegisterX86::EBP),
+    ("esp", RegisterX86::ESP),
+    ("eip", RegisterX86::EIP),
+    ("eflags", RegisterX86::EFLAGS),
+];
+
+// instructions radius models itself: int3, int, hlt, syscall,
+// sysenter, rdtsc, and cpuid
+fn is_modeled(bytes: &[u8]) -> bool {
+    matches!(
+        bytes,
+        [0xcc, _]
+            | [0xcd, _]
+            | [0xf4, _]
+            | [0x0f, 0x05]
+            | [0x0f, 0x34]
+            | [0x0f, 0x31]
+            | [0x0f, 0xa2]
+    )
+}
+
+// the state being run and the pages copied into the emulator
+struct Native<'a> {
+    state: &'a mut State,
+    stop: &'a dyn Fn(u64) -> bool,
+    pages: HashMap<u64, Vec<u8>>,
+    executed: Vec<u64>,
+    faulted: bool,
+}
+
+impl Native<'_> {
+    // the bytes and permissions of a page, None if it has symbolic or
+    // tainted bytes or is not in any segment
+    fn load(&mut self, page: u64) -> Option<(Vec<u8>, Permission)> {
+        let end = page + PAGE_SIZE;
+        let mut perms = Permission::NONE;
+        for seg in &self.state.memory.segs {
+            if seg.addr < end && seg.addr + seg.size > page {
+                if seg.read {
+                    perms |= Permission::READ;
+                }
+                if seg.write {
+                    perms |= Permission::WRITE;
+                }
+                if seg.exec {
+                    perms |= Permission::EXEC;
+                }
+            }
+        }
+        if perms.is_empty() {
+            return None;
+        }
+
+        let mut values = vec![Value::Concrete(0, 0); PAGE_SIZE as usize];
+        self.state
+            .memory
+            .read(page, PAGE_SIZE as usize, &mut values);
+        let bytes = values
+            .iter()
+            .map(|v| match v {
+                Value::Concrete(b, 0) => Some(*b as u8),
+                _ => None,
+            })
+            .collect::<Option<Vec<u8>>>()?;
+
+        self.pages.insert(page, bytes.clone());
+        Some((bytes, perms))
+    }
+}
+
+// copy the page containing addr into the emulator when it is first touched
+fn map_page(uc: &mut Unicorn<Native>, addr: u64) -> bool {
+    let page = addr & !(PAGE_SIZE - 1);
+    match uc.get_data_mut().load(page) {
+        Some((bytes, perms)) => uc
+            .mem_map(page, PAGE_SIZE as usize, perms)
+            .and_then(|_| uc.mem_write(page, &bytes))
+            .is_ok(),
+        None => false,
+    }
+}
+
+// the unicorn mode and registers of x86 states
+fn registers(state: &State) -> Option<(Mode, &'static [(&'static str, RegisterX86)])> {
+    match state.memory.bits {
+        64 => Some((Mode::MODE_64, &X64_REGISTERS)),
+        32 => Some((Mode::MODE_32, &X86_REGISTERS)),
+        _ => None,
+    }
+}
+
+// the concrete, untainted values of the registers
+fn register_values(
+    state: &State,
+    registers: &[(&str, RegisterX86)],
+) -> Option<Vec<(RegisterX86, u64)>> {
+    registers
+        .iter()
+        .map(|(name, reg)| {
+            state.registers.get_register(name)?;
+            match state.registers.get(name) {
+                Value::Concrete(v, 0) => Some((*reg, v)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// the concrete, untainted segment bases the state has
+fn segment_bases(state: &State) -> Option<Vec<(RegisterX86, u64)>> {
+    SEGMENT_BASES
+        .iter()
+        .filter(|(name, _)| state.registers.get_register(name).is_some())
+        .map(|(name, reg)| match state.registers.get(name) {
+            Value::Concrete(v, 0) => Some((*reg, v)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether the state can be run natively, an x86 state whose
+/// registers and segment bases are all concrete
+pub fn can_run(state: &State) -> bool {
+    registers(state)
+        .and_then(|(_, registers)| register_values(state, registers))
+        .and(segment_bases(state))
+        .is_some()
+}
+
+/// Run the state natively from its pc with unicorn until `stop` is true
+/// for an address, it reaches an instruction radius models, or it touches
+/// memory that is symbolic or unmapped. Returns the addresses executed,
+/// the state is left at the first instruction that was not
+pub fn run(state: &mut State, stop: &dyn Fn(u64) -> bool, steps: usize) -> Option<Vec<u64>> {
+    let (mode, registers) = registers(state)?;
+    let values = register_values(state, registers)?;
+    let bases = segment_bases(state)?;
+    let pc = state.registers.get_pc().as_u64()?;
+
+    let native = Native {
+        state,
+        stop,
+        pages: HashMap::new(),
+        executed: vec![],
+        faulted: false,
+    };
+    let mut emu = Unicorn::new_with_data(Arch::X86, mode, native).ok()?;
+    for (reg, v) in values {
+        emu.reg_write(reg, v).ok()?;
+    }
+    // a base unicorn can't take would make fs: and gs: accesses wrong
+    for (reg, v) in bases {
+        if emu.reg_write(reg, v).is_err() && v != 0 {
+            return None;
+        }
+    }
+
+    emu.add_code_hook(1, 0, |uc, addr, _| {
+        let mut bytes = [0u8; 2];
+        let modeled = uc.mem_read(addr, &mut bytes).is_ok() && is_modeled(&bytes);
+        let native = uc.get_data_mut();
+        if modeled || (native.stop)(addr) {
+            // stopping in a code hook leaves the instruction unexecuted
+            let _ = uc.emu_stop();
+        } else {
+            native.executed.push(addr);
+        }
+    })
+    .ok()?;
+    emu.add_mem_hook(HookType::MEM_UNMAPPED, 1, 0, |uc, _, addr, _, _| {
+        map_page(uc, addr)
+    })
+    .ok()?;
+    // exceptions like division by zero are raised by radius
+    emu.add_intr_hook(|uc, _| {
+        uc.get_data_mut().faulted = true;
+        let _ = uc.emu_stop();
+    })
+    .ok()?;
+
+    // the instruction that faulted was not executed, it was only recorded
+    // if the code hook ran for it, which it doesn't for unmapped fetches
+    if emu.emu_start(pc, 0, 0, steps).is_err() || emu.get_data().faulted {
+        let pc_reg = registers
+            .iter()
+            .find(|(name, _)| *name == "rip" || *name == "eip");
+        let fault = pc_reg.and_then(|(_, reg)| emu.reg_read(*reg).ok());
+        if fault.is_some() && emu.get_data().executed.last() == fault.as_ref() {
+            emu.get_data_mut().executed.pop();
+        }
+    }
+    if emu.get_data().executed.is_empty() {
+        return None;
+    }
+
+    let mut values = Vec::with_capacity(registers.len());
+    for (name, reg) in registers {
+        values.push((*name, emu.reg_read(*reg).ok()?));
+    }
+    let mut written = vec![];
+    for (page, original) in &emu.get_data().pages {
+        let bytes = emu.mem_read_as_vec(*page, PAGE_SIZE as usize).ok()?;
+        if &bytes != original {
+            written.push((*page, original.to_owned(), bytes));
+        }
+    }
+
+    let native = emu.get_data_mut();
+    for (name, value) in values {
+        native.state.registers.set(name, vc(value));
+    }
+    // only write the changed bytes, so unchanged code is not marked written
+    for (page, original, bytes) in written {
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == original[i] {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < bytes.len() && bytes[i] != original[i] {
+                i += 1;
+            }
+            let mut data: Vec<Value> = bytes[start..i].iter().map(|b| vc(*b as u64)).collect();
+            native.state.memory.write(page + start as u64, &mut data);
+        }
+    }
+    Some(std::mem::take(&mut native.executed))
+}
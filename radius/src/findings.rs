@@ -0,0 +1,252 @@
+use crate::contract::Violation;
+use crate::memory::HeapError;
+use crate::primitives::WritePrimitive;
+use crate::state::{State, StateStatus};
+use std::collections::{BTreeMap, HashMap};
+
+/// Most controlled bytes counted towards the severity of a finding
+const MAX_CONTROLLED: usize = 32;
+
+/// The kind of bug a finding reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BugClass {
+    /// A program counter the input can point anywhere
+    PcControl,
+    /// Execution of memory that is not executable
+    Execute,
+    /// A store whose address and value both depend on input
+    WritePrimitive,
+    /// An access to a freed heap allocation
+    UseAfterFree,
+    /// A write past the end of a heap allocation
+    HeapOverflow,
+    /// A free of a freed or unallocated heap address
+    DoubleFree,
+    /// A write to memory that is not writable
+    Write,
+    /// A return that violates the postcondition of a function
+    Contract,
+    /// A read of memory that is not readable
+    Read,
+}
+
+impl BugClass {
+    // base severity of the class, higher is worse
+    fn weight(&self) -> usize {
+        match self {
+            BugClass::PcControl => 110,
+            BugClass::Execute => 100,
+            BugClass::WritePrimitive => 90,
+            BugClass::UseAfterFree => 85,
+            BugClass::HeapOverflow => 80,
+            BugClass::DoubleFree => 75,
+            BugClass::Write => 70,
+            BugClass::Contract => 50,
+            BugClass::Read => 40,
+        }
+    }
+}
+
+/// A deduplicated bug report
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub class: BugClass,
+    /// Address of the faulting instruction, or the function for contracts
+    pub pc: u64,
+    /// Address of the heap allocation the faulting address is in
+    pub allocation: Option<u64>,
+    /// Faulting or written address
+    pub addr: u64,
+    /// Number of input bytes the faulting address or written value depends on
+    pub controlled: usize,
+    /// True if reaching the finding depends on input
+    pub reachable: bool,
+    /// Inputs that reach the first report of the finding
+    pub inputs: BTreeMap<String, Vec<u8>>,
+    /// Number of reports of the finding
+    pub count: usize,
+}
+
+impl Finding {
+    /// Make a finding from a crashed or heap violating state
+    pub fn from_crash(state: &mut State) -> Option<Self> {
+        let (addr, class) = match state.status {
+            StateStatus::Crash(addr, 'x') => (addr, BugClass::Execute),
+            StateStatus::Crash(addr, 'w') => (addr, BugClass::Write),
+            StateStatus::Crash(addr, _) => (addr, BugClass::Read),
+            StateStatus::Violation(addr, HeapError::UseAfterFree) => (addr, BugClass::UseAfterFree),
+            StateStatus::Violation(addr, HeapError::Overflow) => (addr, BugClass::HeapOverflow),
+            StateStatus::Violation(addr, _) => (addr, BugClass::DoubleFree),
+            _ => return None,
+        };
+
+        let controlled = state
+            .context
+            .get("crash_addr")
+            .map(|a| state.input_positions(&a[0]).len())
+            .unwrap_or_default();
+        let reachable = !state.input_positions(&state.path_predicate().0).is_empty();
+
+        Some(Finding {
+            class,
+            pc: state.registers.get_pc().as_u64().unwrap_or_default(),
+            allocation: state
+                .memory
+                .allocation(addr)
+                .or_else(|| state.memory.freed_allocation(addr)),
+            addr,
+            controlled,
+            reachable,
+            inputs: state.dump_inputs(),
+            count: 1,
+        })
+    }
+
+    /// Make a finding from a state stopped by `RadiusOption::CheckPcControl`
+    pub fn from_pc_control(state: &mut State) -> Option<Self> {
+        let control = state.context.get("pc_control")?.to_owned();
+        let reachable = !state.input_positions(&state.path_predicate().0).is_empty();
+        let pc = control[0].as_u64().unwrap_or_default();
+
+        Some(Finding {
+            class: BugClass::PcControl,
+            pc,
+            allocation: None,
+            addr: pc,
+            controlled: state.input_positions(&control[1]).len(),
+            reachable,
+            inputs: state.dump_inputs(),
+            count: 1,
+        })
+    }
+
+    pub fn from_primitive(primitive: &WritePrimitive) -> Self {
+        Finding {
+            class: BugClass::WritePrimitive,
+            pc: primitive.pc,
+            allocation: None,
+            addr: primitive.address_range.0,
+            controlled: primitive.controlled,
+            reachable: primitive.reachable,
+            inputs: BTreeMap::new(),
+            count: 1,
+        }
+    }
+
+    pub fn from_violation(violation: &Violation) -> Self {
+        Finding {
+            class: BugClass::Contract,
+            pc: violation.addr,
+            allocation: None,
+            addr: violation.pc,
+            controlled: 0,
+            reachable: !violation.inputs.is_empty(),
+            inputs: violation.inputs.clone(),
+            count: 1,
+        }
+    }
+
+    /// Findings with the same class, pc, and allocation are duplicates
+    pub fn key(&self) -> (BugClass, u64, Option<u64>) {
+        (self.class, self.pc, self.allocation)
+    }
+
+    /// Heuristic severity from the class, the number of controlled
+    /// bytes, and whether input reaches the finding
+    pub fn severity(&self) -> usize {
+        self.class.weight()
+            + 2 * self.controlled.min(MAX_CONTROLLED)
+            + if self.reachable { 10 } else { 0 }
+    }
+}
+
+/// Store of findings that merges duplicate reports
+///
+/// ## Example
+/// ```
+/// use radius2::radius::{Radius, RadiusOption};
+/// let mut radius = Radius::new_with_options(Some("../tests/r100"), &[RadiusOption::Permissions(true)]);
+/// let state = radius.call_state(0x004006fd);
+/// radius.run_until(state, 0x004007a1, &[0x00400790]);
+/// let findings = radius.findings();
+/// findings.print();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Findings {
+    findings: Vec<Finding>,
+    index: HashMap<(BugClass, u64, Option<u64>), usize>,
+}
+
+impl Findings {
+    pub fn new() -> Self {
+        Findings::default()
+    }
+
+    /// Add a finding, returns false if it is a duplicate of one already found
+    pub fn add(&mut self, finding: Finding) -> bool {
+        if let Some(i) = self.index.get(&finding.key()) {
+            let found = &mut self.findings[*i];
+            found.count += finding.count;
+            found.controlled = found.controlled.max(finding.controlled);
+            found.reachable |= finding.reachable;
+            false
+        } else {
+            self.index.insert(finding.key(), self.findings.len());
+            self.findings.push(finding);
+            true
+        }
+    }
+
+    /// Get the findings by severity, most severe first
+    pub fn ranked(&self) -> Vec<&Finding> {
+        let mut ranked: Vec<&Finding> = self.findings.iter().collect();
+        ranked.sort_by(|a, b| {
+            b.severity()
+                .cmp(&a.severity())
+                .then(b.count.cmp(&a.count))
+                .then(a.pc.cmp(&b.pc))
+        });
+        ranked
+    }
+
+    pub fn by_class(&self, class: BugClass) -> Vec<&Finding> {
+        self.findings.iter().filter(|f| f.class == class).collect()
+    }
+
+    /// Get the findings at the instruction or function `pc`
+    pub fn at(&self, pc: u64) -> Vec<&Finding> {
+        self.findings.iter().filter(|f| f.pc == pc).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.findings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Print a line for each finding, most severe first
+    pub fn print(&self) {
+        println!(
+            "{:<16}{:<12}{:<12}{:<12}{:>8}{:>6}{:>7}",
+            "class", "pc", "address", "alloc", "severity", "bytes", "count"
+        );
+        for f in self.ranked() {
+            let alloc = f
+                .allocation
+                .map(|a| format!("0x{:08x}", a))
+                .unwrap_or_else(|| "-".to_owned());
+            println!(
+                "{:<16}{:<12}{:<12}{:<12}{:>8}{:>6}{:>7}",
+                format!("{:?}", f.class),
+                format!("0x{:08x}", f.pc),
+                format!("0x{:08x}", f.addr),
+                alloc,
+                f.severity(),
+                f.controlled,
+                f.count
+            );
+        }
+    }
+}
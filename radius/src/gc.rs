@@ -0,0 +1,97 @@
+use crate::state::State;
+use std::mem;
+use std::rc::Rc;
+
+/// Scores a state for `GcPolicy::Custom`, the lowest scores are dropped first
+pub type GcScore = fn(&State) -> f64;
+
+/// Called with each state dropped to shrink the frontier, eg. to log or
+/// serialize it, see `Radius::on_drop`
+pub type DropCallback = fn(&State);
+
+/// Which states are dropped when there are more than `RadiusOption::MaxStates`
+///
+/// ## Example
+/// ```
+/// use radius2::gc::GcPolicy;
+/// use radius2::radius::{Radius, RadiusOption};
+/// let options = [
+///     RadiusOption::MaxStates(16),
+///     RadiusOption::GcPolicy(GcPolicy::MostConstrained),
+/// ];
+/// let mut radius = Radius::new_with_options(Some("../tests/r100"), &options);
+/// let mut state = radius.call_state(0x004006fd);
+/// state.symbolize_memory("flag", 0x100000, 12);
+/// state.registers.set("rdi", radius2::vc(0x100000));
+/// let found = radius.run_until(state, 0x004007a1, &[0x00400790]);
+/// assert!(found.is_some());
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub enum GcPolicy {
+    /// Drop the states that took the most symbolic branches, the default
+    #[default]
+    Deepest,
+    /// Drop the states with the most constraints
+    MostConstrained,
+    /// Drop the states that executed the fewest distinct instructions
+    LeastCoverage,
+    /// Drop the states the function scores lowest
+    Custom(GcScore),
+}
+
+impl PartialEq for GcPolicy {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            // function addresses are not unique, so custom policies are never equal
+            (GcPolicy::Custom(_), _) => false,
+            _ => mem::discriminant(self) == mem::discriminant(other),
+        }
+    }
+}
+
+impl GcPolicy {
+    /// Parse a policy name from the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "deepest" => Some(GcPolicy::Deepest),
+            "constrained" => Some(GcPolicy::MostConstrained),
+            "coverage" => Some(GcPolicy::LeastCoverage),
+            _ => None,
+        }
+    }
+
+    // how much the policy wants to keep the state
+    fn score(&self, state: &State) -> f64 {
+        match self {
+            GcPolicy::Deepest => -(state.path.len() as f64),
+            GcPolicy::MostConstrained => -(state.solver.assertions.len() as f64),
+            GcPolicy::LeastCoverage => state.visits.len() as f64,
+            GcPolicy::Custom(score) => score(state),
+        }
+    }
+
+    /// Split the states into the `keep` the policy keeps, in their
+    /// original order, and the ones it drops
+    pub fn shrink(&self, states: Vec<Rc<State>>, keep: usize) -> (Vec<Rc<State>>, Vec<Rc<State>>) {
+        let scores: Vec<f64> = states.iter().map(|s| self.score(s)).collect();
+        let mut ranked: Vec<usize> = (0..states.len()).collect();
+        // stable, so ties are kept in queue order
+        ranked.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+
+        let mut kept = vec![false; states.len()];
+        for index in ranked.into_iter().take(keep) {
+            kept[index] = true;
+        }
+
+        let mut keep_states = vec![];
+        let mut drop_states = vec![];
+        for (state, kept) in states.into_iter().zip(kept) {
+            if kept {
+                keep_states.push(state);
+            } else {
+                drop_states.push(state);
+            }
+        }
+        (keep_states, drop_states)
+    }
+}
@@ -0,0 +1,82 @@
+use crate::state::State;
+use std::collections::BTreeMap;
+
+/// Decides whether a state at the address of a goal satisfies it, the
+/// state is a duplicate so constraints can be asserted on it
+pub type GoalCondition = fn(&mut State) -> bool;
+
+/// A target of `Radius::run_until_goals`
+#[derive(Debug, Clone)]
+pub struct Goal {
+    pub addr: u64,
+    pub condition: Option<GoalCondition>,
+}
+
+impl Goal {
+    /// Reach `addr` with a satisfiable state
+    pub fn new(addr: u64) -> Self {
+        Goal {
+            addr,
+            condition: None,
+        }
+    }
+
+    /// Reach `addr` with a state satisfying `condition`
+    pub fn with_condition(addr: u64, condition: GoalCondition) -> Self {
+        Goal {
+            addr,
+            condition: Some(condition),
+        }
+    }
+}
+
+/// The goals of a run, each is retired by the first state that satisfies
+/// it while the states keep exploring for the rest
+#[derive(Clone, Default)]
+pub struct Goals {
+    pub goals: Vec<Goal>,
+    /// The state that satisfied each goal, by index
+    pub found: BTreeMap<usize, State>,
+}
+
+impl Goals {
+    pub fn new(goals: &[Goal]) -> Self {
+        Goals {
+            goals: goals.to_vec(),
+            found: BTreeMap::new(),
+        }
+    }
+
+    /// Retire the pending goals at `pc` that the state satisfies,
+    /// returning whether any were
+    pub fn check(&mut self, state: &mut State, pc: u64) -> bool {
+        let mut retired = false;
+        for (i, goal) in self.goals.iter().enumerate() {
+            if goal.addr != pc || self.found.contains_key(&i) {
+                continue;
+            }
+            let mut found = state.duplicate();
+            let satisfied = match goal.condition {
+                Some(condition) => condition(&mut found) && found.verify(),
+                None => found.verify(),
+            };
+            if satisfied {
+                self.found.insert(i, found);
+                retired = true;
+            }
+        }
+        retired
+    }
+
+    /// Whether a goal at `addr` is still pending
+    pub fn is_pending(&self, addr: u64) -> bool {
+        self.goals
+            .iter()
+            .enumerate()
+            .any(|(i, g)| g.addr == addr && !self.found.contains_key(&i))
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.found.len() == self.goals.len()
+    }
+}
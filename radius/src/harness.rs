@@ -0,0 +1,254 @@
+/// Length of the symbolic buffers pointer arguments point to
+pub const HARNESS_BUFFER_LEN: usize = 64;
+
+const KEYWORDS: [&str; 18] = [
+    "as", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "ref",
+    "self", "type", "use", "where", "while",
+];
+
+// names used by the generated code
+const LOCALS: [&str; 8] = [
+    "radius", "options", "state", "function", "find", "avoid", "found", "args",
+];
+
+/// A parameter of a C function prototype
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    /// The C type without the name, eg. "const char *"
+    pub c_type: String,
+}
+
+impl Param {
+    pub fn is_pointer(&self) -> bool {
+        self.c_type.contains('*') || self.c_type.contains('[')
+    }
+
+    /// Size in bytes of an integer parameter for a target with `bits`
+    pub fn size(&self, bits: u64) -> usize {
+        let words: Vec<&str> = self
+            .c_type
+            .split_whitespace()
+            .filter(|w| !["const", "volatile", "signed", "unsigned"].contains(w))
+            .collect();
+
+        match words.as_slice() {
+            ["char"] | ["bool"] | ["_Bool"] | ["int8_t"] | ["uint8_t"] => 1,
+            ["short"] | ["short", "int"] | ["int16_t"] | ["uint16_t"] => 2,
+            ["long", "long"] | ["long", "long", "int"] | ["int64_t"] | ["uint64_t"] => 8,
+            ["long"]
+            | ["long", "int"]
+            | ["size_t"]
+            | ["ssize_t"]
+            | ["uintptr_t"]
+            | ["intptr_t"] => bits as usize / 8,
+            _ => 4,
+        }
+    }
+}
+
+/// A C function prototype like "int check(char *key, int len)"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prototype {
+    pub ret: String,
+    pub name: String,
+    pub params: Vec<Param>,
+    pub variadic: bool,
+}
+
+impl Prototype {
+    /// Parse a prototype, also accepts r2 signatures like
+    /// "int sym.check (char *key, int len);"
+    pub fn parse(prototype: &str) -> Option<Self> {
+        let prototype = prototype.trim().trim_end_matches(';');
+        let open = prototype.find('(')?;
+        let close = prototype.rfind(')')?;
+
+        let (ret, name) = split_declaration(&prototype[..open])?;
+        let mut params = vec![];
+        let mut variadic = false;
+
+        for (i, param) in prototype[open + 1..close].split(',').enumerate() {
+            let param = param.trim();
+            if param.is_empty() || param == "void" {
+                continue;
+            } else if param == "..." {
+                variadic = true;
+                continue;
+            }
+
+            let param = match split_declaration(param) {
+                Some((c_type, name)) if !c_type.is_empty() => Param { name, c_type },
+                // a type without a name, eg. "int"
+                _ => Param {
+                    name: format!("arg{}", i),
+                    c_type: param.to_owned(),
+                },
+            };
+            params.push(param);
+        }
+
+        Some(Prototype {
+            ret,
+            name,
+            params,
+            variadic,
+        })
+    }
+}
+
+// split "const char *key" into the type "const char *" and the name "key"
+fn split_declaration(decl: &str) -> Option<(String, String)> {
+    let decl = decl.trim();
+    let mut name_start = decl.len();
+    let mut name_end = decl.len();
+
+    // array params like "char buf[32]"
+    if let Some(bracket) = decl.find('[') {
+        name_start = bracket;
+        name_end = bracket;
+    }
+
+    while name_start > 0 {
+        let c = decl[..name_start].chars().last()?;
+        if c.is_alphanumeric() || c == '_' || c == '.' {
+            name_start -= c.len_utf8();
+        } else {
+            break;
+        }
+    }
+
+    let name = &decl[name_start..name_end];
+    if name.is_empty() {
+        return None;
+    }
+    let c_type = format!("{}{}", decl[..name_start].trim(), &decl[name_end..]);
+    Some((c_type.trim().to_owned(), name.to_owned()))
+}
+
+// a rust identifier for the parameter
+fn variable(param: &Param, index: usize) -> String {
+    let valid = param.name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !param.name.starts_with(|c: char| c.is_numeric());
+    let name = param.name.as_str();
+    if valid && !KEYWORDS.contains(&name) && !LOCALS.contains(&name) {
+        name.to_owned()
+    } else {
+        format!("arg{}", index)
+    }
+}
+
+/// Generate the source of a Rust program that runs the function `function`
+/// of the binary at `path` with symbolic arguments from `prototype`, as a
+/// starting point for a harness. Pointer arguments point to symbolic
+/// buffers and integers are symbolic values of their size for a target
+/// with `bits`. The program runs until `find` avoiding the addresses in
+/// `avoid`, and every argument is an input that is printed when solved
+///
+/// ## Example
+/// ```
+/// use radius2::harness::generate_harness;
+/// let source = generate_harness(
+///     "tests/r100",
+///     "sym.check",
+///     "int check(char *key, int len)",
+///     64,
+///     0x004007a1,
+///     &[0x00400790],
+/// );
+/// let source = source.unwrap();
+/// assert!(source.contains("state.set_args(vec![key, len]);"));
+/// assert!(source.contains("let find = 0x4007a1;"));
+/// ```
+pub fn generate_harness(
+    path: &str,
+    function: &str,
+    prototype: &str,
+    bits: u64,
+    find: u64,
+    avoid: &[u64],
+) -> Result<String, String> {
+    let proto = Prototype::parse(prototype)
+        .ok_or_else(|| format!("could not parse prototype \"{}\"", prototype))?;
+
+    let mut out = String::new();
+    out += "use radius2::value::vc;\n";
+    out += "use radius2::{Radius, RadiusOption};\n\n";
+    out += &format!(
+        "// length of the symbolic buffers passed as pointer args\nconst BUFFER_LEN: usize = {};\n\n",
+        HARNESS_BUFFER_LEN
+    );
+    out += &format!("// harness for `{}` in {}\n", prototype.trim(), path);
+    out += "#[allow(unused_variables)]\n";
+    out += "fn main() {\n";
+    out += "    let options = [RadiusOption::Sims(true)];\n";
+    out += &format!(
+        "    let mut radius = Radius::new_with_options(Some({:?}), &options);\n",
+        path
+    );
+    out += &format!(
+        "    let function = radius.get_address({:?}).unwrap();\n",
+        function
+    );
+    out += "    let mut state = radius.call_state(function);\n";
+
+    let mut variables = vec![];
+    for (i, param) in proto.params.iter().enumerate() {
+        let var = variable(param, i);
+        out += "\n";
+        if param.is_pointer() {
+            out += &format!(
+                "    // {}: {}, a symbolic null terminated buffer\n",
+                param.name, param.c_type
+            );
+            out += &format!(
+                "    let {} = state.memory_alloc(&vc(BUFFER_LEN as u64 + 1));\n",
+                var
+            );
+            out += &format!(
+                "    let {}_bytes = state.symbolize_memory({:?}, {}.as_u64().unwrap(), BUFFER_LEN);\n",
+                var, param.name, var
+            );
+            out += &format!(
+                "    state.memory_write_value(&({}.clone() + vc(BUFFER_LEN as u64)), &vc(0), 1);\n",
+                var
+            );
+            out += "    // constrain the bytes, eg. to printable characters\n";
+            out += &format!(
+                "    // for byte in &{}_bytes {{ state.constrain_bytes(byte, \"[ -~]\"); }}\n",
+                var
+            );
+        } else {
+            let size = param.size(bits);
+            out += &format!("    // {}: {}\n", param.name, param.c_type);
+            out += &format!(
+                "    let {}_bytes = state.input_bytes({:?}, 0, {});\n",
+                var, param.name, size
+            );
+            out += &format!("    let {} = state.pack(&{}_bytes);\n", var, var);
+        }
+        variables.push(var);
+    }
+    if proto.variadic {
+        out += "\n    // the variadic args are not symbolized, append them to set_args\n";
+    }
+
+    out += &format!("\n    state.set_args(vec![{}]);\n\n", variables.join(", "));
+    let avoid: Vec<String> = avoid.iter().map(|a| format!("0x{:x}", a)).collect();
+    out += &format!("    let find = 0x{:x};\n", find);
+    out += &format!("    let avoid: Vec<u64> = vec![{}];\n\n", avoid.join(", "));
+    out +=
+        "    let mut found = radius.run_until(state, find, &avoid).expect(\"no state found\");\n";
+    out += &format!(
+        "    // add conditions on the found state here, eg. on the {} return value\n",
+        proto.ret
+    );
+    out += "    // let ret = found.get_ret();\n";
+    out += "    // found.assert(&ret.eq(&vc(1)));\n\n";
+    out += "    for (input, bytes) in found.dump_inputs() {\n";
+    out += "        println!(\"{}: {:?}\", input, String::from_utf8_lossy(&bytes));\n";
+    out += "    }\n";
+    out += "}\n";
+
+    Ok(out)
+}
@@ -0,0 +1,88 @@
+use crate::r2_api::R2Api;
+use std::collections::HashMap;
+
+/// Suffixes of the portable implementations of glibc ifuncs, in order
+const GENERIC_SUFFIXES: [&str; 4] = ["_generic", "_sse2_unaligned", "_sse2", "_ia32"];
+
+/// How calls to GNU ifuncs are resolved, set with `RadiusOption::Ifunc`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum IfuncPolicy {
+    /// Bind to the portable implementation, eg. `__memcpy_sse2_unaligned`,
+    /// or resolve it if there is none, the default
+    #[default]
+    Generic,
+    /// Execute the resolver concretely and bind to the function it returns
+    Resolve,
+    /// Bind to a sim of the function if there is one, like an import
+    Sim,
+}
+
+/// An ifunc, a function chosen at load time by its resolver
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ifunc {
+    pub name: String,
+    pub resolver: u64,
+    /// The GOT slots of IRELATIVE relocations to the ifunc
+    pub slots: Vec<u64>,
+    /// The portable implementation of the function if there is one
+    pub generic: Option<u64>,
+}
+
+/// Find the ifuncs of the binary that are bound with relocations
+pub fn find_ifuncs(r2api: &mut R2Api) -> Vec<Ifunc> {
+    let relocations = r2api.get_relocations().unwrap_or_default();
+    if !relocations.iter().any(|r| r.is_ifunc) {
+        return vec![];
+    }
+
+    let symbols = r2api.get_symbols().unwrap_or_default();
+    let mut ifuncs: Vec<Ifunc> = vec![];
+    for symbol in symbols.iter().filter(|s| s.r#type == "IFUNC") {
+        if ifuncs.iter().any(|i| i.resolver == symbol.vaddr) {
+            continue;
+        }
+        let generic = GENERIC_SUFFIXES.iter().find_map(|suffix| {
+            let name = format!("__{}{}", symbol.realname, suffix);
+            symbols.iter().find(|s| s.realname == name).map(|s| s.vaddr)
+        });
+        ifuncs.push(Ifunc {
+            name: symbol.realname.to_owned(),
+            resolver: symbol.vaddr,
+            slots: vec![],
+            generic,
+        });
+    }
+
+    // the addend of irelative relocations is the resolver
+    let base = if r2api.info.bin.pic {
+        r2api.info.bin.baddr
+    } else {
+        0
+    };
+    let by_resolver: HashMap<u64, usize> = ifuncs
+        .iter()
+        .enumerate()
+        .map(|(i, f)| (f.resolver, i))
+        .collect();
+    for reloc in relocations.iter().filter(|r| r.is_ifunc) {
+        let resolver = if reloc.addend != 0 {
+            base.wrapping_add(reloc.addend as u64)
+        } else {
+            read_pointer(r2api, reloc.vaddr)
+        };
+        if let Some(i) = by_resolver.get(&resolver) {
+            ifuncs[*i].slots.push(reloc.vaddr);
+        }
+    }
+    ifuncs
+}
+
+/// Read a pointer from the binary at `addr`
+pub fn read_pointer(r2api: &mut R2Api, addr: u64) -> u64 {
+    let length = r2api.info.bin.bits as usize / 8;
+    let mut bytes = r2api.read(addr, length).unwrap_or_default();
+    if r2api.info.bin.endian != "big" {
+        bytes.reverse();
+    }
+    bytes.iter().fold(0, |v, b| (v << 8) | *b as u64)
+}
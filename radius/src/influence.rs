@@ -0,0 +1,101 @@
+use crate::state::State;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+
+/// Which input bytes can influence which branches, by branch address
+/// with the input sources and offsets its conditions depend on.
+/// Maps of several states are merged to get the map of a run
+///
+/// ## Example
+/// ```
+/// use radius2::influence::InfluenceMap;
+/// use radius2::radius::Radius;
+/// let mut radius = Radius::new("../tests/r100");
+/// let mut state = radius.call_state(0x004006fd);
+/// state.symbolize_memory("flag", 0x100000, 12);
+/// state.registers.set("rdi", radius2::vc(0x100000));
+/// let found = radius.run_until(state, 0x004007a1, &[0x00400790]).unwrap();
+///
+/// let map = InfluenceMap::new(&found);
+/// assert_eq!(map.potent_bytes()[0].1, 1);
+/// print!("{}", map.to_csv());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InfluenceMap {
+    pub branches: BTreeMap<u64, BTreeSet<(String, usize)>>,
+}
+
+impl InfluenceMap {
+    /// Get the influence of the inputs on the branches along the path of `state`
+    pub fn new(state: &State) -> Self {
+        let mut map = InfluenceMap::default();
+        for branch in &state.path {
+            // untainted conditions cannot depend on input
+            if branch.condition.get_taint() == 0 {
+                continue;
+            }
+            let positions = state.input_positions(&branch.condition);
+            if !positions.is_empty() {
+                map.branches
+                    .entry(branch.addr)
+                    .or_default()
+                    .extend(positions);
+            }
+        }
+        map
+    }
+
+    /// Add the influence found in another map
+    pub fn merge(&mut self, other: &InfluenceMap) {
+        for (addr, positions) in &other.branches {
+            self.branches
+                .entry(*addr)
+                .or_default()
+                .extend(positions.iter().cloned());
+        }
+    }
+
+    /// Get the input bytes with the number of branches each influences,
+    /// the most influential first
+    pub fn potent_bytes(&self) -> Vec<((String, usize), usize)> {
+        let mut counts: BTreeMap<(String, usize), usize> = BTreeMap::new();
+        for positions in self.branches.values() {
+            for position in positions {
+                *counts.entry(position.to_owned()).or_default() += 1;
+            }
+        }
+        let mut bytes: Vec<((String, usize), usize)> = counts.into_iter().collect();
+        bytes.sort_by_key(|b| Reverse(b.1));
+        bytes
+    }
+
+    /// One row for each branch and input byte it depends on
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("branch,source,offset\n");
+        for (addr, positions) in &self.branches {
+            for (source, offset) in positions {
+                csv += &format!("0x{:x},{},{}\n", addr, source, offset);
+            }
+        }
+        csv
+    }
+
+    /// Save the map as CSV if `path` ends with .csv, otherwise as JSON
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        if path.ends_with(".csv") {
+            fs::write(path, self.to_csv())
+        } else {
+            fs::write(path, serde_json::to_vec(self)?)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.branches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.branches.is_empty()
+    }
+}
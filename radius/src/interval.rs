@@ -0,0 +1,497 @@
+use crate::solver::BitVec;
+use crate::value::Value;
+use boolector::Btor;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+/// The unsigned values `lo`, `lo + stride`, ..., `hi` of a bitvector. Every
+/// value an expression can take is in its interval, ignoring constraints,
+/// so queries about values outside it are decided without the solver
+///
+/// ## Example
+/// ```
+/// use radius2::radius::Radius;
+/// use radius2::value::vc;
+/// let mut radius = Radius::new("../tests/r100");
+/// let state = radius.call_state(0x004006fd);
+/// let byte = state.symbolic_value("byte", 8).uext(&vc(8));
+/// let index = byte.and(&vc(0xfc)).add(&vc(0x1000));
+/// assert_eq!(index.interval().lo, 0x1000);
+/// assert_eq!(index.interval().hi, 0x10fc);
+/// assert_eq!(index.interval().stride, 4);
+/// assert_eq!(index.eq(&vc(0x1001)).interval().as_constant(), Some(0));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub lo: u64,
+    pub hi: u64,
+    /// Distance between the values, 0 if there is only one
+    pub stride: u64,
+    pub width: u32,
+}
+
+/// The binary operations that intervals are computed through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+    Eq,
+    Ult,
+    Slt,
+}
+
+// intervals of expressions by btor instance and node id. the weak refs
+// keep the instances allocated so a new one never reuses an address
+type Intervals = HashMap<usize, (Weak<Btor>, HashMap<i32, Interval>)>;
+
+thread_local! {
+    static INTERVALS: RefCell<Intervals> = RefCell::new(HashMap::new());
+}
+
+fn mask(width: u32) -> u64 {
+    if width >= 64 {
+        u64::MAX
+    } else {
+        (1 << width) - 1
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// all the bits up to the highest bit set in x
+fn fill(x: u64) -> u64 {
+    if x == 0 {
+        0
+    } else {
+        u64::MAX >> x.leading_zeros()
+    }
+}
+
+impl Interval {
+    pub fn new(lo: u64, hi: u64, stride: u64, width: u32) -> Self {
+        if lo >= hi {
+            return Interval::constant(lo, width);
+        }
+        let stride = stride.max(1);
+        Interval {
+            lo,
+            hi: lo + (hi - lo) / stride * stride,
+            stride,
+            width,
+        }
+    }
+
+    /// Every value of a `width` bit bitvector
+    pub fn top(width: u32) -> Self {
+        Interval::new(0, mask(width), 1, width)
+    }
+
+    pub fn constant(value: u64, width: u32) -> Self {
+        let value = value & mask(width);
+        Interval {
+            lo: value,
+            hi: value,
+            stride: 0,
+            width,
+        }
+    }
+
+    // the interval of a condition, None if it can be true or false
+    fn boolean(value: Option<bool>) -> Self {
+        match value {
+            Some(b) => Interval::constant(b as u64, 1),
+            None => Interval::top(1),
+        }
+    }
+
+    pub fn is_top(&self) -> bool {
+        self.lo == 0 && self.hi == mask(self.width) && self.stride <= 1
+    }
+
+    pub fn as_constant(&self) -> Option<u64> {
+        (self.lo == self.hi).then_some(self.lo)
+    }
+
+    pub fn contains(&self, value: u64) -> bool {
+        value >= self.lo
+            && value <= self.hi
+            && (self.stride == 0 || (value - self.lo) % self.stride == 0)
+    }
+
+    /// The number of values in the interval, saturating
+    pub fn size(&self) -> u64 {
+        match self.stride {
+            0 => 1,
+            s => ((self.hi - self.lo) / s).saturating_add(1),
+        }
+    }
+
+    /// The interval with values of `width` bits, zero extended or truncated
+    pub fn at(&self, width: u32) -> Self {
+        if width >= self.width || self.hi <= mask(width) {
+            Interval { width, ..*self }
+        } else {
+            Interval::top(width)
+        }
+    }
+
+    /// The smallest interval containing both
+    pub fn join(&self, other: &Interval) -> Self {
+        let stride = gcd(gcd(self.stride, other.stride), self.lo.abs_diff(other.lo));
+        let lo = self.lo.min(other.lo);
+        let hi = self.hi.max(other.hi);
+        Interval::new(lo, hi, stride, self.width)
+    }
+
+    // both are sound, so keep the one with fewer values
+    fn narrower(self, other: Interval) -> Self {
+        if other.size() < self.size() {
+            other
+        } else {
+            self
+        }
+    }
+
+    pub fn add(&self, other: &Interval) -> Self {
+        match self.hi.checked_add(other.hi) {
+            Some(hi) if hi <= mask(self.width) => Interval::new(
+                self.lo + other.lo,
+                hi,
+                gcd(self.stride, other.stride),
+                self.width,
+            ),
+            _ => Interval::top(self.width),
+        }
+    }
+
+    pub fn sub(&self, other: &Interval) -> Self {
+        if self.lo >= other.hi {
+            let stride = gcd(self.stride, other.stride);
+            Interval::new(self.lo - other.hi, self.hi - other.lo, stride, self.width)
+        } else {
+            Interval::top(self.width)
+        }
+    }
+
+    pub fn mul(&self, other: &Interval) -> Self {
+        let stride = match (self.as_constant(), other.as_constant()) {
+            (_, Some(c)) => self.stride.saturating_mul(c),
+            (Some(c), _) => other.stride.saturating_mul(c),
+            _ => 1,
+        };
+        match self.hi.checked_mul(other.hi) {
+            Some(hi) if hi <= mask(self.width) => {
+                Interval::new(self.lo * other.lo, hi, stride, self.width)
+            }
+            _ => Interval::top(self.width),
+        }
+    }
+
+    pub fn div(&self, other: &Interval) -> Self {
+        // division by zero is all ones
+        if other.lo == 0 {
+            return Interval::top(self.width);
+        }
+        Interval::new(self.lo / other.hi, self.hi / other.lo, 1, self.width)
+    }
+
+    pub fn rem(&self, other: &Interval) -> Self {
+        if other.lo == 0 {
+            Interval::top(self.width)
+        } else if self.hi < other.lo {
+            *self
+        } else {
+            Interval::new(0, self.hi.min(other.hi - 1), 1, self.width)
+        }
+    }
+
+    pub fn and(&self, other: &Interval) -> Self {
+        if let (Some(a), Some(b)) = (self.as_constant(), other.as_constant()) {
+            return Interval::constant(a & b, self.width);
+        }
+        // a constant mask clears the bits below its lowest one
+        let stride = match self.as_constant().or_else(|| other.as_constant()) {
+            Some(c) if c != 0 => 1 << c.trailing_zeros(),
+            _ => 1,
+        };
+        Interval::new(0, self.hi.min(other.hi), stride, self.width)
+    }
+
+    pub fn or(&self, other: &Interval) -> Self {
+        if let (Some(a), Some(b)) = (self.as_constant(), other.as_constant()) {
+            return Interval::constant(a | b, self.width);
+        }
+        let hi = fill(self.hi | other.hi) & mask(self.width);
+        Interval::new(self.lo.max(other.lo), hi, 1, self.width)
+    }
+
+    pub fn xor(&self, other: &Interval) -> Self {
+        if let (Some(a), Some(b)) = (self.as_constant(), other.as_constant()) {
+            return Interval::constant(a ^ b, self.width);
+        }
+        let hi = fill(self.hi | other.hi) & mask(self.width);
+        Interval::new(0, hi, 1, self.width)
+    }
+
+    /// Bitwise not
+    pub fn not(&self) -> Self {
+        let mask = mask(self.width);
+        Interval::new(mask - self.hi, mask - self.lo, self.stride, self.width)
+    }
+
+    pub fn neg(&self) -> Self {
+        match self.as_constant() {
+            Some(c) => Interval::constant(c.wrapping_neg(), self.width),
+            // -x is 2^width - x, and 2^width - lo fits if lo is not 0
+            None if self.lo > 0 => {
+                let mask = mask(self.width);
+                Interval::new(
+                    mask - self.hi + 1,
+                    mask - self.lo + 1,
+                    self.stride,
+                    self.width,
+                )
+            }
+            None => Interval::top(self.width),
+        }
+    }
+
+    pub fn shl(&self, bits: &Interval) -> Self {
+        let bits = match bits.as_constant() {
+            Some(b) if b < self.width as u64 => b as u32,
+            _ => return Interval::top(self.width),
+        };
+        if (self.hi as u128) << bits <= mask(self.width) as u128 {
+            Interval::new(
+                self.lo << bits,
+                self.hi << bits,
+                self.stride << bits,
+                self.width,
+            )
+        } else {
+            Interval::top(self.width)
+        }
+    }
+
+    pub fn shr(&self, bits: &Interval) -> Self {
+        let bits = match bits.as_constant() {
+            Some(b) if b < self.width as u64 => b as u32,
+            _ => return Interval::top(self.width),
+        };
+        // the stride is kept if shifting drops none of its bits
+        let stride = if self.stride.trailing_zeros() >= bits {
+            self.stride >> bits
+        } else {
+            1
+        };
+        Interval::new(self.lo >> bits, self.hi >> bits, stride, self.width)
+    }
+
+    pub fn eq(&self, other: &Interval) -> Self {
+        if let (Some(a), Some(b)) = (self.as_constant(), other.as_constant()) {
+            return Interval::constant((a == b) as u64, 1);
+        }
+        let disjoint = self.hi < other.lo || other.hi < self.lo;
+        // values on strides that never line up
+        let stride = gcd(self.stride, other.stride);
+        let misaligned = stride > 1 && self.lo.abs_diff(other.lo) % stride != 0;
+        Interval::boolean((disjoint || misaligned).then_some(false))
+    }
+
+    pub fn ult(&self, other: &Interval) -> Self {
+        if self.hi < other.lo {
+            Interval::boolean(Some(true))
+        } else if self.lo >= other.hi {
+            Interval::boolean(Some(false))
+        } else {
+            Interval::boolean(None)
+        }
+    }
+
+    pub fn slt(&self, other: &Interval) -> Self {
+        // values with the same sign are ordered like unsigned ones
+        let sign = 1 << (self.width - 1);
+        let negative = |i: &Interval| i.lo >= sign;
+        let positive = |i: &Interval| i.hi < sign;
+        if (positive(self) && positive(other)) || (negative(self) && negative(other)) {
+            self.ult(other)
+        } else if negative(self) && positive(other) {
+            Interval::boolean(Some(true))
+        } else if positive(self) && negative(other) {
+            Interval::boolean(Some(false))
+        } else {
+            Interval::boolean(None)
+        }
+    }
+
+    /// Whether the value is not zero, as a condition
+    pub fn is_nonzero(&self) -> Self {
+        if !self.contains(0) {
+            Interval::boolean(Some(true))
+        } else if self.as_constant() == Some(0) {
+            Interval::boolean(Some(false))
+        } else {
+            Interval::boolean(None)
+        }
+    }
+
+    pub fn sext(&self, width: u32) -> Self {
+        if self.hi < 1 << (self.width - 1) {
+            self.at(width)
+        } else {
+            Interval::top(width)
+        }
+    }
+
+    /// The bits `high` to `low`
+    pub fn slice(&self, high: u32, low: u32) -> Self {
+        self.shr(&Interval::constant(low as u64, 64))
+            .at(high - low + 1)
+    }
+
+    pub fn binary(&self, op: Op, other: &Interval) -> Self {
+        match op {
+            Op::Add => self.add(other),
+            Op::Sub => self.sub(other),
+            Op::Mul => self.mul(other),
+            Op::Div => self.div(other),
+            Op::Rem => self.rem(other),
+            Op::And => self.and(other),
+            Op::Or => self.or(other),
+            Op::Xor => self.xor(other),
+            Op::Shl => self.shl(other),
+            Op::Shr => self.shr(other),
+            Op::Eq => self.eq(other),
+            Op::Ult => self.ult(other),
+            Op::Slt => self.slt(other),
+        }
+    }
+}
+
+/// Get the interval of the bitvector, every value if none is known
+pub fn get(bv: &BitVec) -> Interval {
+    let width = bv.get_width();
+    if width > 64 {
+        return Interval::top(width);
+    }
+    if bv.is_const() {
+        if let Some(value) = bv.as_u64() {
+            return Interval::constant(value, width);
+        }
+    }
+
+    let btor = Arc::as_ptr(&bv.get_btor()) as usize;
+    let known = INTERVALS.with(|i| {
+        i.borrow()
+            .get(&btor)
+            .and_then(|(_, intervals)| intervals.get(&bv.get_id()).copied())
+    });
+    known.unwrap_or_else(|| Interval::top(width))
+}
+
+/// Set the interval of the bitvector, keeping the known one if it is narrower
+pub fn set(bv: &BitVec, interval: Interval) {
+    if interval.is_top() || bv.get_width() > 64 {
+        return;
+    }
+    let interval = interval.at(bv.get_width());
+    let btor = bv.get_btor();
+    let key = Arc::as_ptr(&btor) as usize;
+
+    INTERVALS.with(|i| {
+        let mut table = i.borrow_mut();
+        if !table.contains_key(&key) {
+            // drop the intervals of instances that are gone
+            table.retain(|_, (btor, _)| btor.strong_count() > 0);
+            table.insert(key, (Arc::downgrade(&btor), HashMap::new()));
+        }
+        let intervals = &mut table.get_mut(&key).unwrap().1;
+        let interval = match intervals.get(&bv.get_id()) {
+            Some(known) => known.narrower(interval),
+            None => interval,
+        };
+        intervals.insert(bv.get_id(), interval);
+    });
+}
+
+/// Get the interval of the value as `width` bits
+pub fn of(value: &Value, width: u32) -> Interval {
+    match value {
+        Value::Concrete(v, _) => Interval::constant(*v, width),
+        Value::Symbolic(bv, _) => get(bv).at(width),
+    }
+}
+
+/// Set the interval of the value if it is symbolic and return it
+pub fn track(value: Value, interval: Interval) -> Value {
+    if let Value::Symbolic(bv, _) = &value {
+        set(bv, interval);
+    }
+    value
+}
+
+/// The intervals of the operands of `op`, at the width it is done at.
+/// None if both are concrete
+pub fn operands(op: Op, lhs: &Value, rhs: &Value) -> Option<(Interval, Interval)> {
+    let width = match (lhs, rhs, op) {
+        (Value::Concrete(..), Value::Concrete(..), _) => return None,
+        // shifts of constants are done at 64 bits
+        (Value::Concrete(..), _, Op::Shl | Op::Shr) => 64,
+        (Value::Symbolic(a, _), _, Op::Shl | Op::Shr) => a.get_width(),
+        (Value::Symbolic(a, _), Value::Symbolic(b, _), _) => a.get_width().max(b.get_width()),
+        (Value::Symbolic(a, _), _, _) => a.get_width(),
+        (_, Value::Symbolic(b, _), _) => b.get_width(),
+    };
+    let rhs_width = match op {
+        Op::Shl | Op::Shr => 64,
+        _ => width,
+    };
+    Some((of(lhs, width), of(rhs, rhs_width)))
+}
+
+/// Set the interval of `result`, the result of `op` on `operands`
+pub fn apply(op: Op, operands: Option<(Interval, Interval)>, result: Value) -> Value {
+    match operands {
+        Some((lhs, rhs)) => track(result, lhs.binary(op, &rhs)),
+        None => result,
+    }
+}
+
+/// Set the interval of `result`, the result of `op` on the values
+pub fn binary(op: Op, lhs: &Value, rhs: &Value, result: Value) -> Value {
+    if result.is_symbolic() {
+        apply(op, operands(op, lhs, rhs), result)
+    } else {
+        result
+    }
+}
+
+/// Bitwise not of the bitvector, keeping its interval
+pub fn not(bv: &BitVec) -> BitVec {
+    let result = bv.not();
+    set(&result, get(bv).not());
+    result
+}
+
+/// The condition that the bitvector is not zero
+pub fn nonzero(bv: &BitVec) -> BitVec {
+    let zero = BitVec::zero(bv.get_btor(), bv.get_width());
+    let result = bv._eq(&zero).not();
+    set(&result, get(bv).is_nonzero());
+    result
+}
@@ -0,0 +1,133 @@
+use crate::state::State;
+use crate::value::Value;
+use std::collections::BTreeMap;
+
+/// Takes the state and returns the condition that must always hold
+pub type InvariantCheck = fn(&mut State) -> Value;
+
+/// A condition checked during exploration, after every instruction or
+/// only after the last instruction of each block
+#[derive(Debug, Clone)]
+pub struct Invariant {
+    pub name: String,
+    pub check: InvariantCheck,
+    pub per_block: bool,
+}
+
+/// A state that can violate an invariant, constrained to violate it
+#[derive(Clone)]
+pub struct InvariantViolation {
+    pub name: String,
+    /// Address of the instruction after which the invariant was violated
+    pub pc: u64,
+    pub state: State,
+}
+
+impl Invariant {
+    /// Check the invariant after the instruction at `pc`, reporting a
+    /// violating copy of the state and constraining `state` to satisfy it
+    pub fn check(
+        &self,
+        state: &mut State,
+        pc: u64,
+        block_end: bool,
+        violations: &mut Vec<InvariantViolation>,
+    ) {
+        if self.per_block && !block_end {
+            return;
+        }
+        let holds = (self.check)(state);
+        let violated = !holds.clone();
+        if !state.check(&violated) {
+            return;
+        }
+
+        // only the first violation at each address is reported
+        if !violations.iter().any(|v| v.pc == pc && v.name == self.name) {
+            let mut violating = state.duplicate();
+            violating.assert(&violated);
+            println!("warning: invariant {} violated after 0x{:x}", self.name, pc);
+            violations.push(InvariantViolation {
+                name: self.name.to_owned(),
+                pc,
+                state: violating,
+            });
+        }
+
+        if state.check(&holds) {
+            state.assert(&holds);
+        } else {
+            state.set_inactive();
+        }
+    }
+}
+
+/// A safety property that must hold whenever execution reaches `addr`,
+/// checked before the instruction there runs. Unlike `State::assert`,
+/// which constrains the state, a state that can violate it is reported
+#[derive(Debug, Clone)]
+pub struct Assertion {
+    pub name: String,
+    pub addr: u64,
+    pub check: InvariantCheck,
+}
+
+/// A reachable state that violates an assertion and the inputs reaching it
+#[derive(Clone)]
+pub struct Counterexample {
+    pub name: String,
+    pub pc: u64,
+    pub inputs: BTreeMap<String, Vec<u8>>,
+    pub state: State,
+}
+
+impl Assertion {
+    pub fn new(name: &str, addr: u64, check: InvariantCheck) -> Self {
+        Assertion {
+            name: name.to_owned(),
+            addr,
+            check,
+        }
+    }
+
+    /// Check the assertion, reporting a violating copy of the state and
+    /// constraining `state` to satisfy it
+    pub fn check(&self, state: &mut State, counterexamples: &mut Vec<Counterexample>) {
+        let holds = (self.check)(state);
+        let violated = !holds.clone();
+        if !state.check(&violated) {
+            return;
+        }
+
+        // only the first violation of each assertion is reported
+        if !counterexamples.iter().any(|c| c.name == self.name) {
+            let mut violating = state.duplicate();
+            violating.assert(&violated);
+            println!(
+                "warning: assertion {} violated at 0x{:x}",
+                self.name, self.addr
+            );
+            counterexamples.push(Counterexample {
+                name: self.name.to_owned(),
+                pc: self.addr,
+                inputs: violating.dump_inputs(),
+                state: violating,
+            });
+        }
+
+        if state.check(&holds) {
+            state.assert(&holds);
+        } else {
+            state.set_inactive();
+        }
+    }
+}
+
+impl Counterexample {
+    pub fn print(&self) {
+        println!("assertion {} violated at 0x{:08x}", self.name, self.pc);
+        for (name, bytes) in &self.inputs {
+            println!("{}: {:?}", name, String::from_utf8_lossy(bytes));
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use crate::loops::LoopSummary;
+use crate::r2_api::Instruction;
+use crate::reach::Reachability;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+
+/// Data derived from a binary that is stored on disk by file hash and
+/// load base so that later runs against the same binary can skip lifting
+/// and analysis
+///
+/// ## Example
+/// ```
+/// use radius2::knowledge::KnowledgeBase;
+/// let dir = std::env::temp_dir().join("radius2-doctest");
+/// let mut kb = KnowledgeBase::load(dir.to_str().unwrap(), "0123abcd");
+/// kb.opaque_predicates.insert(0x400cb0, 0x400cc4);
+/// kb.set("entry", &0x400500u64);
+/// kb.save().unwrap();
+///
+/// let kb = KnowledgeBase::load(dir.to_str().unwrap(), "0123abcd");
+/// assert_eq!(kb.opaque_predicates.get(&0x400cb0), Some(&0x400cc4));
+/// assert_eq!(kb.get::<u64>("entry"), Some(0x400500));
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KnowledgeBase {
+    /// Hash of the binary this knowledge is about
+    pub hash: String,
+    /// Results of radare2 queries, eg. function info and blocks
+    pub commands: HashMap<String, String>,
+    /// Instructions lifted by the processor, by the address they were fetched at
+    #[serde(default)]
+    pub blocks: BTreeMap<u64, Vec<Instruction>>,
+    /// CFG distances to the targets of `Radius::prune_unreachable`
+    #[serde(default)]
+    pub reachability: Vec<Reachability>,
+    /// Branches with symbolic conditions that only ever went to one target
+    #[serde(default)]
+    pub opaque_predicates: BTreeMap<u64, u64>,
+    /// Loop summaries by the address of the loop head
+    #[serde(default)]
+    pub summaries: BTreeMap<u64, LoopSummary>,
+    /// Other derived data by name
+    pub data: BTreeMap<String, Json>,
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+impl KnowledgeBase {
+    /// Load the knowledge for the binary with `hash` from `dir`,
+    /// or start an empty one if there is none
+    pub fn load(dir: &str, hash: &str) -> Self {
+        let dir = PathBuf::from(dir);
+        let path = dir.join(format!("{}.json", hash));
+
+        let mut kb = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice::<KnowledgeBase>(&data).ok())
+            .filter(|kb| kb.hash == hash)
+            .unwrap_or_default();
+
+        kb.hash = hash.to_owned();
+        kb.dir = dir;
+        kb
+    }
+
+    /// Write the knowledge to its directory
+    pub fn save(&self) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!("{}.json", self.hash));
+        fs::write(path, serde_json::to_vec(self)?)
+    }
+
+    /// Get the derived data stored as `key`
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.data
+            .get(key)
+            .and_then(|v| serde_json::from_value(v.to_owned()).ok())
+    }
+
+    /// Store derived data as `key`, replacing any previous value
+    pub fn set<T: Serialize>(&mut self, key: &str, value: &T) {
+        if let Ok(json) = serde_json::to_value(value) {
+            self.data.insert(key.to_owned(), json);
+        }
+    }
+
+    /// Check if there is derived data stored as `key`
+    pub fn contains(&self, key: &str) -> bool {
+        self.data.contains_key(key)
+    }
+}
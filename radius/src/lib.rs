@@ -5,26 +5,115 @@ extern crate boolector;
 extern crate r2pipe;
 extern crate serde_json;
 
+/// Calling conventions, thumb mode, and delay slots of architectures
+pub mod arch;
+/// Run the same job on a directory of binaries
+pub mod batch;
+/// Timeouts, step limits, and memory budgets of runs
+pub mod budget;
+/// Send run events to external observers
+pub mod bus;
+/// Drive symbolic execution with concrete inputs
+pub mod concolic;
+/// Check preconditions and postconditions of functions
+pub mod contract;
+/// Write the inputs of the paths explored as a fuzzing corpus
+pub mod corpus;
+/// Compare the coverage of runs
+pub mod coverage;
+/// Differences between the registers, memory, and constraints of states
+pub mod diff;
+/// Export coverage for Lighthouse and Cutter
+pub mod drcov;
+/// Run every model of small inputs and tabulate the outcomes
+pub mod enumerate;
+/// Dispatch exceptions to Windows SEH and VEH handlers
+pub mod exception;
+/// Attribute state explosions to the sites that fork
+pub mod explosion;
+/// Run concrete code natively with unicorn
+#[cfg(feature = "unicorn")]
+pub mod fastforward;
+/// Deduplicate and rank the bugs found
+pub mod findings;
+/// Choose which states to drop when there are too many
+pub mod gc;
+/// Search for several targets in one run
+pub mod goal;
+/// Generate harnesses that run functions with symbolic args
+pub mod harness;
+/// Bind GNU ifuncs to an implementation
+pub mod ifunc;
+/// Map which input bytes influence which branches
+pub mod influence;
+/// Ranges of symbolic values, to decide queries without the solver
+pub mod interval;
+/// Check invariants during exploration
+pub mod invariant;
+/// Derived data about binaries saved across runs
+pub mod knowledge;
+/// Summarize the memory effect of counted loops
+pub mod loops;
+/// Stashes of states, like the states found and avoided by a run
+pub mod manager;
 /// Memory used in a program state
 pub mod memory;
+/// Handle the imports of libraries by name pattern
+pub mod modules;
 mod operations;
+/// Explore states on several threads
+pub mod parallel;
+/// Prune thread interleavings with partial-order reduction
+pub mod por;
+/// Render symbolic expressions readably
+pub mod pretty;
+/// Catalog write-what-where primitives found during exploration
+pub mod primitives;
 /// Process the IL to execute instructions
 pub mod processor;
+/// Attribute execution time to target instructions
+pub mod profile;
 /// Interact with the radare2 instance
 pub mod r2_api;
 /// Start symbolic execution for a given binary
 pub mod radius;
+/// Find the code that can statically reach targets
+pub mod reach;
+/// Validate solutions concretely and refine the constraints
+pub mod refine;
 /// Registers and their values for a given state
 pub mod registers;
+/// Inspect and step a state interactively
+pub mod repl;
+/// Find ROP chains by executing gadgets symbolically
+pub mod rop;
+/// Serve symbolic execution jobs over HTTP
+pub mod server;
 /// Simulated libc functions and syscalls
 pub mod sims;
+/// Save states to disk and restore them
+pub mod snapshot;
 /// Utilities for using the SMT solver to evaluate symbolic values
 pub mod solver;
 /// A program state, containing the registers, memory, and solver context
 pub mod state;
+/// Choose the order states are explored in
+pub mod strategy;
+/// Read and constrain the standard streams of states
+pub mod stream;
 mod test;
+/// Symbolic messages of length-prefixed records
+pub mod tlv;
+/// Import execution traces recorded by external tools
+pub mod trace;
+/// Define C structs to access them in memory
+pub mod types;
 /// Asbstraction for concrete and symbolic values used during execution
 pub mod value;
+/// Trace the handlers of virtualized code
+pub mod vm;
+/// Callbacks on memory and register accesses
+pub mod watch;
 
 /// Start a symbolic execution run with `Radius`
 pub use crate::radius::{Radius, RadiusOption};
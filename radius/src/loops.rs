@@ -0,0 +1,208 @@
+use crate::sims::libc::{memcpy, memset, strcpy};
+use crate::state::State;
+use crate::value::{vc, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+const MAX_LEN: u64 = 8192;
+const CRC32_POLY: u64 = 0xedb88320;
+
+/// The memory effect of a counted loop, by the registers the loop uses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LoopKind {
+    /// Stores `value` to `count` bytes at `dst`
+    Memset {
+        dst: String,
+        value: String,
+        count: String,
+    },
+    /// Copies `count` bytes from `src` to `dst`
+    Memcpy {
+        dst: String,
+        src: String,
+        count: String,
+    },
+    /// Copies the string at `src` to `dst` including the null
+    Strcpy { dst: String, src: String },
+    /// Accumulates the reflected CRC-32 of `count` bytes at `buf` into `crc`
+    Crc32 {
+        crc: String,
+        buf: String,
+        count: String,
+    },
+}
+
+/// Replaces the iterations of a loop with a summary of its memory effect.
+/// When a state reaches the loop head the effect is applied at once, the
+/// pointer registers are advanced past the bytes, the count register is
+/// set to 0, and the state continues at `exit`
+///
+/// ## Example
+/// ```
+/// use radius2::loops::{LoopKind, LoopSummary};
+/// use radius2::radius::Radius;
+/// let mut radius = Radius::new("/bin/sh");
+/// let kind = LoopKind::Memcpy {
+///     dst: "rdi".to_owned(),
+///     src: "rsi".to_owned(),
+///     count: "rcx".to_owned(),
+/// };
+/// radius.summarize_loop(0x400cb0, LoopSummary::new(kind, 0x400cc4));
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoopSummary {
+    pub kind: LoopKind,
+    /// Address the loop exits to
+    pub exit: u64,
+}
+
+impl LoopSummary {
+    pub fn new(kind: LoopKind, exit: u64) -> Self {
+        LoopSummary { kind, exit }
+    }
+
+    /// Apply the effect of the whole loop to the state
+    pub fn apply(&self, state: &mut State) {
+        match &self.kind {
+            LoopKind::Memset { dst, value, count } => {
+                let args = [
+                    state.registers.get(dst),
+                    state.registers.get(value).slice(7, 0),
+                    state.registers.get(count),
+                ];
+                memset(state, &args);
+                advance(state, dst, &args[2]);
+                state.registers.set(count, vc(0));
+            }
+            LoopKind::Memcpy { dst, src, count } => {
+                let args = [
+                    state.registers.get(dst),
+                    state.registers.get(src),
+                    state.registers.get(count),
+                ];
+                memcpy(state, &args);
+                advance(state, dst, &args[2]);
+                advance(state, src, &args[2]);
+                state.registers.set(count, vc(0));
+            }
+            LoopKind::Strcpy { dst, src } => {
+                let args = [state.registers.get(dst), state.registers.get(src)];
+                let length = state.memory_strlen(&args[1], &vc(MAX_LEN)) + vc(1);
+                strcpy(state, &args);
+                advance(state, dst, &length);
+                advance(state, src, &length);
+            }
+            LoopKind::Crc32 { crc, buf, count } => {
+                let addr = state.registers.get(buf);
+                let length = state.registers.get(count);
+                let length = state.solver.evalcon_to_u64(&length).unwrap_or_default();
+                let mut value = state.registers.get(crc).slice(31, 0);
+                for i in 0..length {
+                    let byte = state.memory_read_value(&(addr.clone() + vc(i)), 1);
+                    value = crc32_byte(&value, &byte);
+                }
+                state.registers.set(crc, value.uext(&vc(32)));
+                advance(state, buf, &vc(length));
+                state.registers.set(count, vc(0));
+            }
+        }
+        state.registers.set_pc(vc(self.exit));
+    }
+}
+
+/// Bounds the iterations of the loops found in the cfgs of the functions
+/// r2 analyzes, see `RadiusOption::MaxLoopIters`. The loops of a function
+/// are found the first time a state jumps back in it, and the iterations
+/// of a loop are counted per state from the last time it entered the loop
+///
+/// ## Example
+/// ```
+/// use radius2::radius::{Radius, RadiusOption};
+/// let options = [RadiusOption::MaxLoopIters(16)];
+/// let mut radius = Radius::new_with_options(Some("../tests/r100"), &options);
+/// let mut state = radius.call_state(0x004006fd);
+/// state.symbolize_memory("flag", 0x100000, 12);
+/// state.registers.set("rdi", radius2::vc(0x100000));
+/// radius.run_until(state, 0x004007a1, &[0x00400790]);
+/// println!("{} states past the bound", radius.deferred().len());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoopBounds {
+    /// Iterations a state may make before it is deferred
+    pub max: usize,
+    /// The end of the body of each loop by the address of its head
+    pub heads: HashMap<u64, u64>,
+    // addresses of the blocks whose functions were searched for loops
+    searched: HashSet<u64>,
+}
+
+impl LoopBounds {
+    pub fn new(max: usize) -> Self {
+        LoopBounds {
+            max,
+            ..Default::default()
+        }
+    }
+
+    /// Count the iteration of the state if it came from `from` back to a
+    /// loop head, returning whether it went past the bound. `jump` tells
+    /// if the instruction at `from` was a jump, to find the loops of new
+    /// functions only on back edges
+    pub fn iterate(&mut self, state: &mut State, from: u64, jump: bool) -> bool {
+        let head = match state.registers.get_pc().as_u64() {
+            Some(head) => head,
+            None => return false,
+        };
+        if jump && head <= from && !self.searched.contains(&head) {
+            self.search(state, head);
+        }
+        let end = match self.heads.get(&head) {
+            Some(end) => *end,
+            None => return false,
+        };
+
+        // entering the loop from outside its body starts the count over
+        let key = format!("loop{:x}", head);
+        let iters = if from >= head && from < end {
+            let count = state.context.get(&key).and_then(|c| c[0].as_u64());
+            count.unwrap_or(0) + 1
+        } else {
+            0
+        };
+        state.context.insert(key, vec![vc(iters)]);
+        iters > self.max as u64
+    }
+
+    // find the back edges of the function containing addr, each loop
+    // spans from its head to the end of the furthest block jumping back
+    fn search(&mut self, state: &mut State, addr: u64) {
+        self.searched.insert(addr);
+        let blocks = state.r2api.get_blocks(addr).unwrap_or_default();
+        for block in &blocks {
+            self.searched.insert(block.addr);
+            let end = block.addr + block.size;
+            for target in [block.jump, block.fail] {
+                let back = target >= 0 && target as u64 <= block.addr;
+                if back && blocks.iter().any(|b| b.addr == target as u64) {
+                    let head = self.heads.entry(target as u64).or_insert(end);
+                    *head = end.max(*head);
+                }
+            }
+        }
+    }
+}
+
+fn advance(state: &mut State, reg: &str, length: &Value) {
+    let value = state.registers.get(reg);
+    state.registers.set(reg, value + length.to_owned());
+}
+
+// one byte of the bitwise reflected crc32
+fn crc32_byte(crc: &Value, byte: &Value) -> Value {
+    let mut crc = crc.to_owned() ^ byte.uext(&vc(24));
+    for _ in 0..8 {
+        let mask = vc(0) - (crc.to_owned() & vc(1));
+        crc = (crc >> vc(1)) ^ (mask & vc(CRC32_POLY));
+    }
+    crc.slice(31, 0)
+}
@@ -1,14 +1,22 @@
+use crate::bus::EventLevel;
+use crate::gc::GcPolicy;
+use crate::ifunc::IfuncPolicy;
+use crate::memory::{AddressPolicy, Uninit, POISON_BYTE};
+use crate::modules::ModulePolicy;
+use crate::processor::RunMode;
 use crate::processor::Word;
 use crate::r2_api::hex_encode;
 use crate::radius::{Radius, RadiusOption};
-use crate::processor::RunMode;
+use crate::solver::SolverKind;
+use crate::strategy::Strategy;
 
 use boolector::BV;
-use clap::{App, Arg};
+use clap::{App, Arg, SubCommand};
 use colored::*;
+use std::io::{self, Write};
 use std::path::Path;
-use std::time::Instant;
-use std::{fs, process};
+use std::time::{Duration, Instant};
+use std::{fs, process, thread};
 
 use crate::state::StateStatus;
 use crate::value::Value;
@@ -20,16 +28,64 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
+pub mod arch;
+pub mod batch;
+pub mod budget;
+pub mod bus;
+pub mod concolic;
+pub mod contract;
+pub mod corpus;
+pub mod coverage;
+pub mod diff;
+pub mod drcov;
+pub mod enumerate;
+pub mod exception;
+pub mod explosion;
+#[cfg(feature = "unicorn")]
+pub mod fastforward;
+pub mod findings;
+pub mod gc;
+pub mod goal;
+pub mod harness;
+pub mod ifunc;
+pub mod influence;
+pub mod interval;
+pub mod invariant;
+pub mod knowledge;
+pub mod loops;
+pub mod manager;
 pub mod memory;
+pub mod modules;
 pub mod operations;
+pub mod parallel;
+pub mod por;
+pub mod pretty;
+pub mod primitives;
 pub mod processor;
+pub mod profile;
 pub mod r2_api;
 pub mod radius;
+pub mod reach;
+pub mod refine;
 pub mod registers;
+pub mod repl;
+pub mod rop;
+pub mod server;
 pub mod sims;
+pub mod snapshot;
 pub mod solver;
 pub mod state;
+pub mod strategy;
+pub mod stream;
+pub mod tlv;
+pub mod trace;
+pub mod types;
 pub mod value;
+pub mod vm;
+pub mod watch;
+
+// number of instructions shown in the profile table
+const PROFILE_HOTSPOTS: usize = 20;
 
 macro_rules! occurs {
     ($m:expr, $s:expr) => {
@@ -53,6 +109,7 @@ pub struct JsonOutput {
 fn main() {
     let matches = App::new("radius2")
         .global_settings(&[clap::AppSettings::ColoredHelp])
+        .setting(clap::AppSettings::SubcommandsNegateReqs)
         .version(env!("CARGO_PKG_VERSION"))
         .author("Austin Emmitt (@alkalinesec) <aemmitt@nowsecure.com>")
         .about("A symbolic execution tool using r2 and boolector")
@@ -72,6 +129,185 @@ fn main() {
                 .multiple(true)
                 .help("Load libraries from path"),
         )
+        .arg(
+            Arg::with_name("base")
+                .long("base")
+                .takes_value(true)
+                .value_name("ADDR")
+                .help("Load PIE executables and shared objects at ADDR"),
+        )
+        .arg(
+            Arg::with_name("knowledge")
+                .long("knowledge")
+                .takes_value(true)
+                .value_name("DIR")
+                .help("Store derived data about the binary in DIR across runs"),
+        )
+        .arg(
+            Arg::with_name("record")
+                .long("record")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Log the r2 commands and their output to FILE"),
+        )
+        .arg(
+            Arg::with_name("replay")
+                .long("replay")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Serve r2 commands from a FILE made with --record instead of running r2"),
+        )
+        .arg(
+            Arg::with_name("coverage")
+                .long("coverage")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Save the covered blocks to FILE for covdiff"),
+        )
+        .arg(
+            Arg::with_name("drcov")
+                .long("drcov")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Save the covered blocks to FILE in drcov format for Lighthouse"),
+        )
+        .arg(
+            Arg::with_name("events")
+                .long("events")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Save every run event to FILE as JSON lines"),
+        )
+        .arg(
+            Arg::with_name("primitives")
+                .long("primitives")
+                .help("Print stores whose address and value depend on input"),
+        )
+        .arg(
+            Arg::with_name("queries")
+                .long("queries")
+                .value_names(&["DIR", "N"])
+                .help("Dump the N slowest solver queries to DIR as SMT-LIB"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_names(&["SOURCE", "FILE"])
+                .multiple(true)
+                .help("Prefer solutions close to the bytes of FILE for an input or symbol"),
+        )
+        .arg(
+            Arg::with_name("explosion")
+                .long("explosion")
+                .takes_value(true)
+                .value_name("N")
+                .help("Report the sites that fork the most once there are N states"),
+        )
+        .arg(
+            Arg::with_name("max_states")
+                .long("max-states")
+                .takes_value(true)
+                .value_name("N")
+                .help("Drop states once there are more than N"),
+        )
+        .arg(
+            Arg::with_name("max_loop_iters")
+                .long("max-loop-iters")
+                .takes_value(true)
+                .value_name("N")
+                .help("Defer states that iterate a loop more than N times"),
+        )
+        .arg(
+            Arg::with_name("query_timeout")
+                .long("query-timeout")
+                .takes_value(true)
+                .value_name("MS")
+                .help("Fork both ways at branches whose queries take over MS milliseconds"),
+        )
+        .arg(
+            Arg::with_name("gc")
+                .long("gc")
+                .takes_value(true)
+                .value_name("POLICY")
+                .possible_values(&["deepest", "constrained", "coverage"])
+                .help("Which states to drop once there are too many"),
+        )
+        .arg(
+            Arg::with_name("address_policy")
+                .long("address-policy")
+                .takes_value(true)
+                .value_name("POLICY")
+                .possible_values(&["evaluate", "min", "any", "range", "array"])
+                .help("How to load and store at symbolic addresses"),
+        )
+        .arg(
+            Arg::with_name("symbolic_stdin")
+                .long("symbolic-stdin")
+                .takes_value(true)
+                .value_name("N")
+                .help("Make stdin a symbolic stream of up to N bytes"),
+        )
+        .arg(
+            Arg::with_name("max_length")
+                .long("max-length")
+                .takes_value(true)
+                .value_name("N")
+                .help("Assume symbolic lengths of memory operations are at most N"),
+        )
+        .arg(
+            Arg::with_name("max_string")
+                .long("max-string")
+                .takes_value(true)
+                .value_name("N")
+                .help("Read strings of at most N bytes in string sims"),
+        )
+        .arg(
+            Arg::with_name("module")
+                .long("module")
+                .value_names(&["PATTERN", "POLICY"])
+                .multiple(true)
+                .help("Handle imports of libraries matching PATTERN with stub, sims, or execute"),
+        )
+        .arg(
+            Arg::with_name("ignore")
+                .long("ignore")
+                .takes_value(true)
+                .multiple(true)
+                .help("Stub imports matching the pattern"),
+        )
+        .arg(
+            Arg::with_name("solver")
+                .long("solver")
+                .takes_value(true)
+                .value_name("SOLVER")
+                .help(
+                    "Solve with boolector, or bitwuzla, cvc5, or an SMT-LIB solver command with the smtlib feature",
+                ),
+        )
+        .arg(
+            Arg::with_name("strategy")
+                .long("strategy")
+                .takes_value(true)
+                .value_name("STRATEGY")
+                .possible_values(&["visits", "dfs", "bfs", "random", "coverage"])
+                .help("Order to explore states in"),
+        )
+        .arg(
+            Arg::with_name("ifunc")
+                .long("ifunc")
+                .takes_value(true)
+                .value_name("POLICY")
+                .possible_values(&["generic", "resolve", "sim"])
+                .help("How to bind calls to GNU ifuncs"),
+        )
+        .arg(
+            Arg::with_name("uninit")
+                .long("uninit")
+                .takes_value(true)
+                .value_name("MODE")
+                .possible_values(&["zero", "poison", "symbolic"])
+                .help("Fill uninitialized registers and stack with zero, 0xcc, or symbols"),
+        )
         .arg(
             Arg::with_name("file")
                 .short("f")
@@ -103,11 +339,41 @@ fn main() {
                 .long("lazy")
                 .help("Evaluate symbolic PC values lazily"),
         )
+        .arg(
+            Arg::with_name("prune")
+                .long("prune")
+                .help("Drop states that can't statically reach a breakpoint"),
+        )
         .arg(
             Arg::with_name("crash")
                 .long("crash")
                 .help("Execution stops on invalid memory access"),
         )
+        .arg(
+            Arg::with_name("heap_check")
+                .long("heap-check")
+                .help("Execution stops on heap overflow, use after free and double free"),
+        )
+        .arg(
+            Arg::with_name("pc_control")
+                .long("pc-control")
+                .help("Execution stops when input controls the program counter"),
+        )
+        .arg(
+            Arg::with_name("anti_debug")
+                .long("anti-debug")
+                .help("Return clean values for anti-debugging checks"),
+        )
+        .arg(
+            Arg::with_name("exceptions")
+                .long("exceptions")
+                .help("Dispatch exceptions to SEH/VEH handlers (Windows)"),
+        )
+        .arg(
+            Arg::with_name("native")
+                .long("native")
+                .help("Run concrete code natively with unicorn (unicorn feature)"),
+        )
         .arg(
             Arg::with_name("selfmodify")
                 .short("M")
@@ -174,6 +440,11 @@ fn main() {
                 .takes_value(true)
                 .help("Address to begin execution at"),
         )
+        .arg(
+            Arg::with_name("snapshot")
+                .long("snapshot")
+                .help("Copy the memory of a dbg: target when it reaches the address"),
+        )
         .arg(
             Arg::with_name("threads")
                 .short("t")
@@ -301,8 +572,213 @@ fn main() {
                 .multiple(true)
                 .help("Evaluate ESIL expression after execution"),
         )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Serve symbolic execution jobs as JSON-RPC over HTTP")
+                .arg(
+                    Arg::with_name("port")
+                        .long("port")
+                        .takes_value(true)
+                        .default_value("8080")
+                        .help("Port to listen on"),
+                )
+                .arg(
+                    Arg::with_name("host")
+                        .long("host")
+                        .takes_value(true)
+                        .default_value("127.0.0.1")
+                        .help("Address to listen on"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("batch")
+                .about("Run a job on every binary in the --path directory")
+                .arg(
+                    Arg::with_name("job")
+                        .takes_value(true)
+                        .required(true)
+                        .help("JSON file with the params of a server solve request"),
+                )
+                .arg(
+                    Arg::with_name("workers")
+                        .short("w")
+                        .long("workers")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("Number of binaries to run at the same time"),
+                )
+                .arg(
+                    Arg::with_name("timeout")
+                        .long("timeout")
+                        .takes_value(true)
+                        .help("Time limit in seconds for each binary"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .short("j")
+                        .long("json")
+                        .help("Output the report as JSON"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("covdiff")
+                .about("Print the blocks newly covered and lost between two coverage files")
+                .arg(
+                    Arg::with_name("old")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Coverage of the earlier run"),
+                )
+                .arg(
+                    Arg::with_name("new")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Coverage of the later run"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .short("j")
+                        .long("json")
+                        .help("Output the diff as JSON"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("harness")
+                .about("Generate a Rust harness that runs a function with symbolic args to the -b address")
+                .arg(
+                    Arg::with_name("function")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Name or address of the function"),
+                )
+                .arg(
+                    Arg::with_name("prototype")
+                        .long("prototype")
+                        .takes_value(true)
+                        .help("C prototype of the function, from r2 if not set"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true)
+                        .help("File to write the harness to"),
+                ),
+        )
         .get_matches();
 
+    if let Some(serve) = matches.subcommand_matches("serve") {
+        let addr = format!(
+            "{}:{}",
+            serve.value_of("host").unwrap_or_default(),
+            serve.value_of("port").unwrap_or_default()
+        );
+        if let Err(e) = server::Server::new().serve(&addr) {
+            eprintln!("could not serve on {}: {}", addr, e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(batch) = matches.subcommand_matches("batch") {
+        let dir = matches.value_of("path").unwrap_or_default();
+        let job = batch.value_of("job").unwrap_or_default();
+        let params = fs::read(job)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string()));
+
+        let mut runner = match params {
+            Ok(params) => batch::Batch::new(params),
+            Err(e) => {
+                eprintln!("could not read job {}: {}", job, e);
+                process::exit(1);
+            }
+        };
+        runner.workers = batch
+            .value_of("workers")
+            .unwrap_or("1")
+            .parse()
+            .unwrap_or(1);
+        runner.timeout = batch
+            .value_of("timeout")
+            .and_then(|t| t.parse().ok())
+            .map(Duration::from_secs);
+
+        match runner.run(dir) {
+            Ok(report) if occurs!(batch, "json") => {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap())
+            }
+            Ok(report) => report.print(),
+            Err(e) => {
+                eprintln!("could not read {}: {}", dir, e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(covdiff) = matches.subcommand_matches("covdiff") {
+        let load = |name| {
+            let path = covdiff.value_of(name).unwrap_or_default();
+            coverage::Coverage::load(path).unwrap_or_else(|e| {
+                eprintln!("could not read coverage {}: {}", path, e);
+                process::exit(1);
+            })
+        };
+        let diff = load("new").diff(&load("old"));
+        if occurs!(covdiff, "json") {
+            println!("{}", serde_json::to_string_pretty(&diff).unwrap());
+        } else {
+            diff.print();
+        }
+        return;
+    }
+
+    if let Some(harness) = matches.subcommand_matches("harness") {
+        let path = matches.value_of("path").unwrap_or_default();
+        let function = harness.value_of("function").unwrap_or_default();
+        let mut radius = Radius::new(path);
+        let prototype = harness
+            .value_of("prototype")
+            .map(|p| p.to_owned())
+            .or_else(|| {
+                let addr = radius.get_address(function).ok()?;
+                let info = radius.r2api.get_function_info(addr).ok()?;
+                Some(info.signature)
+            });
+        let bits = radius.r2api.info.bin.bits;
+        let find = collect!(matches, "breakpoint")
+            .first()
+            .and_then(|b| radius.get_address(b).ok());
+        let avoid: Vec<u64> = collect!(matches, "avoid")
+            .iter()
+            .filter_map(|x| radius.get_address(x).ok())
+            .collect();
+        radius.close();
+
+        let source = prototype
+            .ok_or_else(|| format!("no prototype found for {}", function))
+            .and_then(|p| {
+                let find = find.ok_or("no address to find, set one with -b")?;
+                harness::generate_harness(path, function, &p, bits, find, &avoid)
+            });
+
+        match (source, harness.value_of("output")) {
+            (Ok(source), Some(output)) => {
+                if let Err(e) = fs::write(output, source) {
+                    eprintln!("could not write {}: {}", output, e);
+                    process::exit(1);
+                }
+            }
+            (Ok(source), None) => print!("{}", source),
+            (Err(e), _) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
     let libpaths: Vec<&str> = collect!(matches, "libs");
 
     let debug = occurs!(matches, "verbose") || occurs!(matches, "color");
@@ -329,18 +805,144 @@ fn main() {
         RadiusOption::Lazy(occurs!(matches, "lazy")),
         RadiusOption::Strict(occurs!(matches, "strict")),
         RadiusOption::SelfModify(occurs!(matches, "selfmodify")),
+        RadiusOption::FastForward(occurs!(matches, "native")),
         RadiusOption::ColorOutput(occurs!(matches, "color")),
         RadiusOption::Permissions(occurs!(matches, "crash")),
+        RadiusOption::Exceptions(occurs!(matches, "exceptions")),
+        RadiusOption::AntiDebug(occurs!(matches, "anti_debug")),
+        RadiusOption::HeapCheck(occurs!(matches, "heap_check")),
+        RadiusOption::CheckPcControl(occurs!(matches, "pc_control")),
         RadiusOption::Sims(!no_sims),
         RadiusOption::SimAll(all_sims),
         RadiusOption::LoadLibs(!libpaths.is_empty()),
         RadiusOption::LoadPlugins(plugins),
+        RadiusOption::Profile(profile),
+        RadiusOption::WritePrimitives(occurs!(matches, "primitives")),
     ];
 
     for lib in libpaths {
         options.push(RadiusOption::LibPath(lib.to_owned()));
     }
 
+    if let Some(dir) = matches.value_of("knowledge") {
+        options.push(RadiusOption::KnowledgeBase(dir.to_owned()));
+    }
+
+    let base = matches
+        .value_of("base")
+        .and_then(|b| match b.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => b.parse().ok(),
+        });
+    if let Some(base) = base {
+        options.push(RadiusOption::LoadBase(base));
+    }
+
+    if let Some(path) = matches.value_of("record") {
+        options.push(RadiusOption::Record(path.to_owned()));
+    }
+
+    if let Some(path) = matches.value_of("replay") {
+        options.push(RadiusOption::Replay(path.to_owned()));
+    }
+
+    if let Some(n) = matches.value_of("explosion").and_then(|n| n.parse().ok()) {
+        options.push(RadiusOption::ExplosionReport(n));
+    }
+
+    if let Some(n) = matches
+        .value_of("symbolic_stdin")
+        .and_then(|n| n.parse().ok())
+    {
+        options.push(RadiusOption::SymbolicStdin(n));
+    }
+
+    if let Some(n) = matches.value_of("max_states").and_then(|n| n.parse().ok()) {
+        options.push(RadiusOption::MaxStates(n));
+    }
+
+    if let Some(n) = matches
+        .value_of("max_loop_iters")
+        .and_then(|n| n.parse().ok())
+    {
+        options.push(RadiusOption::MaxLoopIters(n));
+    }
+
+    if let Some(ms) = matches
+        .value_of("query_timeout")
+        .and_then(|ms| ms.parse().ok())
+    {
+        options.push(RadiusOption::QueryTimeout(Duration::from_millis(ms)));
+    }
+
+    if let Some(policy) = matches.value_of("gc").and_then(GcPolicy::from_name) {
+        options.push(RadiusOption::GcPolicy(policy));
+    }
+
+    if let Some(policy) = matches
+        .value_of("address_policy")
+        .and_then(AddressPolicy::from_name)
+    {
+        options.push(RadiusOption::AddressPolicy(policy));
+    }
+
+    if let Some(n) = matches.value_of("max_length").and_then(|n| n.parse().ok()) {
+        options.push(RadiusOption::MaxLength(n));
+    }
+
+    if let Some(n) = matches.value_of("max_string").and_then(|n| n.parse().ok()) {
+        options.push(RadiusOption::MaxStringLength(n));
+    }
+
+    let modules: Vec<&str> = collect!(matches, "module");
+    for i in 0..modules.len() / 2 {
+        let policy = match modules[2 * i + 1] {
+            "stub" => ModulePolicy::Stub,
+            "sims" => ModulePolicy::Sims,
+            "execute" => ModulePolicy::Execute,
+            policy => {
+                println!("unknown module policy {}", policy);
+                continue;
+            }
+        };
+        options.push(RadiusOption::ModulePolicy(
+            modules[2 * i].to_owned(),
+            policy,
+        ));
+    }
+
+    for pattern in collect!(matches, "ignore") {
+        options.push(RadiusOption::IgnoreSymbol(pattern.to_owned()));
+    }
+
+    if let Some(solver) = matches.value_of("solver") {
+        options.push(RadiusOption::Solver(SolverKind::from_name(solver)));
+    }
+
+    if let Some(strategy) = matches.value_of("strategy").and_then(Strategy::from_name) {
+        options.push(RadiusOption::Strategy(strategy));
+    }
+
+    match matches.value_of("ifunc") {
+        Some("generic") => options.push(RadiusOption::Ifunc(IfuncPolicy::Generic)),
+        Some("resolve") => options.push(RadiusOption::Ifunc(IfuncPolicy::Resolve)),
+        Some("sim") => options.push(RadiusOption::Ifunc(IfuncPolicy::Sim)),
+        _ => {}
+    }
+
+    match matches.value_of("uninit") {
+        Some("zero") => options.push(RadiusOption::Uninit(Uninit::Zero)),
+        Some("poison") => options.push(RadiusOption::Uninit(Uninit::Poison(POISON_BYTE))),
+        Some("symbolic") => options.push(RadiusOption::Uninit(Uninit::Symbolic)),
+        _ => {}
+    }
+
+    let queries: Vec<&str> = collect!(matches, "queries");
+    if queries.len() == 2 {
+        let n = queries[1].parse().unwrap_or(0);
+        options.push(RadiusOption::QueryLog(n));
+    }
+
     let threads: usize = matches
         .value_of("threads")
         .unwrap_or_default()
@@ -354,11 +956,26 @@ fn main() {
 
     // just a guardrail cuz the error otherwise is vv unclear
     if path != "-" && !path.contains(':') && fs::metadata(path).is_err() {
-        println!("'{}' not found", path);
+        eprintln!("'{}' not found", path);
         process::exit(1);
     }
 
     let mut radius = Radius::new_with_options(matches.value_of("path"), &options);
+    // stream the events to the file while running so they don't pile up
+    let events = matches.value_of("events").map(|path| {
+        let file = fs::File::create(path).unwrap_or_else(|e| {
+            eprintln!("could not create {}: {}", path, e);
+            process::exit(1);
+        });
+        let events = radius.subscribe_level(EventLevel::Trace);
+        thread::spawn(move || -> io::Result<()> {
+            let mut writer = io::BufWriter::new(file);
+            for event in events {
+                writeln!(writer, "{}", event.to_json())?;
+            }
+            writer.flush()
+        })
+    });
 
     if !dir.exists() {
         fs::create_dir(&dir).unwrap();
@@ -433,8 +1050,16 @@ fn main() {
         }
     }
 
-    for bp in bps {
-        radius.breakpoint(bp);
+    for bp in &bps {
+        radius.breakpoint(*bp);
+    }
+
+    if occurs!(matches, "prune") {
+        // need to analyze to find the functions
+        if !analyzed {
+            radius.analyze(3);
+        }
+        radius.prune_unreachable(&bps);
     }
 
     radius.avoid(&avoid);
@@ -447,6 +1072,8 @@ fn main() {
         let addr = radius.get_address(address).unwrap_or(0);
         if path.starts_with("frida:") {
             radius.frida_state(addr)
+        } else if path.starts_with("dbg:") && occurs!(matches, "snapshot") {
+            radius.run_native_until(addr)
         } else if path.starts_with("gdb:") || path.starts_with("dbg:") {
             radius.debug_state(addr, &[])
         } else {
@@ -483,6 +1110,7 @@ fn main() {
         let sym_value = state.symbolic_value(sym_name, length);
         //symbol_types.insert(sym_name, symbols[3 * i + 2]);
         symbol_map.insert(sym_name, sym_value.as_bv().unwrap());
+        state.register_symbol(sym_name, &sym_value);
         state.context.insert(sym_name.to_owned(), vec![sym_value]);
 
         if sym_name.to_lowercase() == "stdin" {
@@ -556,6 +1184,8 @@ fn main() {
             let length = symbol_map[name].get_width() as usize;
             let value = Value::Symbolic(sym.clone(), 0);
             let bytes = state.unpack(&value, length / 8);
+            let source = if file == "0" { "stdin" } else { file };
+            state.declare_input_bytes(source, 0, &bytes);
             if let Ok(fd) = files[2 * i].parse() {
                 state.filesystem.fill(fd, &bytes);
             } else {
@@ -577,6 +1207,15 @@ fn main() {
         }
     }
 
+    // seed inputs with known good values
+    let seeds: Vec<&str> = collect!(matches, "seed");
+    for i in 0..seeds.len() / 2 {
+        match fs::read(seeds[2 * i + 1]) {
+            Ok(seed) => state.set_seed(seeds[2 * i], &seed),
+            Err(e) => println!("could not read seed {}: {}", seeds[2 * i + 1], e),
+        }
+    }
+
     // set provided address and register values
     let sets: Vec<&str> = collect!(matches, "set");
     for i in 0..matches.occurrences_of("set") as usize {
@@ -653,6 +1292,31 @@ fn main() {
             );
         }
 
+        for crash in &radius.processor.crashes {
+            if let StateStatus::Violation(addr, error) = crash.status {
+                if !do_json {
+                    println!("{} {:?} at 0x{:x}", "heap:".red(), error, addr);
+                }
+            }
+        }
+
+        for state in radius.unconstrained() {
+            if let Some(addr) = state.context["pc_control"][0].as_u64() {
+                if !do_json {
+                    println!("{} controlled at 0x{:x}", "pc:".red(), addr);
+                }
+            }
+        }
+
+        let deferred = radius.deferred().len();
+        if deferred > 0 && !do_json {
+            println!(
+                "{} {} states past the loop bound",
+                "deferred:".yellow(),
+                deferred
+            );
+        }
+
         if let Some(mut end_state) = result {
             // collect the ESIL strings to evaluate after running
             let constraints: Vec<&str> = collect!(matches, "constrain_after");
@@ -822,7 +1486,40 @@ fn main() {
 
     if profile {
         println!("total time:\t{}", start.elapsed().as_micros());
+        radius.print_profile(PROFILE_HOTSPOTS);
+    }
+
+    if queries.len() == 2 {
+        radius.print_query_stats(PROFILE_HOTSPOTS);
+        match radius.dump_queries(queries[0]) {
+            Ok(paths) => println!("dumped {} queries to {}", paths.len(), queries[0]),
+            Err(e) => println!("could not dump queries: {}", e),
+        }
+    }
+
+    if occurs!(matches, "primitives") {
+        radius.print_write_primitives();
+    }
+
+    if let Some(path) = matches.value_of("coverage") {
+        if let Err(e) = radius.coverage().save(path) {
+            println!("could not save coverage to {}: {}", path, e);
+        }
+    }
+
+    if let Some(path) = matches.value_of("drcov") {
+        if let Err(e) = radius.write_coverage(path) {
+            println!("could not save drcov coverage to {}: {}", path, e);
+        }
     }
 
     radius.close();
+
+    // dropping radius hangs up the bus so the writer can finish
+    drop(radius);
+    if let (Some(path), Some(writer)) = (matches.value_of("events"), events) {
+        if let Ok(Err(e)) = writer.join() {
+            eprintln!("could not save events to {}: {}", path, e);
+        }
+    }
 }
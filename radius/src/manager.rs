@@ -0,0 +1,151 @@
+use crate::state::{State, StateStatus};
+use std::collections::{BTreeMap, HashSet};
+
+/// The states still to explore
+pub const ACTIVE: &str = "active";
+/// The states that reached a target
+pub const FOUND: &str = "found";
+/// The states that reached an address to avoid
+pub const AVOIDED: &str = "avoided";
+/// The states that exited or could not go on
+pub const DEADENDED: &str = "deadended";
+/// The states that crashed or violated a contract
+pub const ERRORED: &str = "errored";
+/// The states dropped by `StateManager::prune`
+pub const PRUNED: &str = "pruned";
+
+/// Named stashes of states, like the states found and avoided by a run
+/// and the states left to explore, see `Radius::explore`
+///
+/// ## Example
+/// ```
+/// use radius2::manager::{StateManager, ACTIVE, AVOIDED, FOUND};
+/// use radius2::radius::Radius;
+/// let mut radius = Radius::new("../tests/r100");
+/// let mut state = radius.call_state(0x004006fd);
+/// state.symbolize_memory("flag", 0x100000, 12);
+/// state.registers.set("rdi", radius2::vc(0x100000));
+///
+/// let mut manager = StateManager::new(state);
+/// radius.explore(&mut manager, 0x004007a1, &[0x00400790], 1);
+/// assert_eq!(manager.len(FOUND), 1);
+/// assert!(manager.len(AVOIDED) > 0);
+/// // keep exploring the states left, or move them out of the way
+/// manager.move_states(ACTIVE, "later", |_| true);
+/// ```
+#[derive(Clone)]
+pub struct StateManager {
+    pub stashes: BTreeMap<String, Vec<State>>,
+    /// Keep the states that end in the avoided, deadended, and errored
+    /// stashes, on by default
+    pub keep_ended: bool,
+}
+
+impl StateManager {
+    /// A manager with the state in the active stash
+    pub fn new(state: State) -> Self {
+        let mut manager = StateManager {
+            stashes: BTreeMap::new(),
+            keep_ended: true,
+        };
+        manager.add(ACTIVE, state);
+        manager
+    }
+
+    /// Get the states in the stash, none if it does not exist
+    pub fn stash(&self, name: &str) -> &[State] {
+        self.stashes
+            .get(name)
+            .map(|s| s.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Get the states in the stash to change them, creating it if needed
+    pub fn stash_mut(&mut self, name: &str) -> &mut Vec<State> {
+        self.stashes.entry(name.to_owned()).or_default()
+    }
+
+    pub fn active(&self) -> &[State] {
+        self.stash(ACTIVE)
+    }
+
+    pub fn found(&self) -> &[State] {
+        self.stash(FOUND)
+    }
+
+    pub fn len(&self, name: &str) -> usize {
+        self.stash(name).len()
+    }
+
+    /// Whether the stash has no states
+    pub fn is_empty(&self, name: &str) -> bool {
+        self.stash(name).is_empty()
+    }
+
+    pub fn add(&mut self, name: &str, state: State) {
+        self.stash_mut(name).push(state);
+    }
+
+    /// Take every state out of the stash
+    pub fn take(&mut self, name: &str) -> Vec<State> {
+        self.stashes.remove(name).unwrap_or_default()
+    }
+
+    /// Move the states in `from` that `filter` is true for to `to`,
+    /// returns the number of states moved
+    pub fn move_states<F>(&mut self, from: &str, to: &str, mut filter: F) -> usize
+    where
+        F: FnMut(&mut State) -> bool,
+    {
+        let (mut moved, mut kept) = (vec![], vec![]);
+        for mut state in self.take(from) {
+            if filter(&mut state) {
+                moved.push(state);
+            } else {
+                kept.push(state);
+            }
+        }
+        let count = moved.len();
+        if !kept.is_empty() {
+            self.stash_mut(from).append(&mut kept);
+        }
+        if count > 0 {
+            self.stash_mut(to).append(&mut moved);
+        }
+        count
+    }
+
+    /// Move the states in the stash whose constraints are unsatisfiable
+    /// to the pruned stash, returns the number of states pruned
+    pub fn prune(&mut self, name: &str) -> usize {
+        self.move_states(name, PRUNED, |s| !s.is_sat())
+    }
+
+    /// Drop the states in the stash that `filter` is false for
+    pub fn filter<F>(&mut self, name: &str, mut filter: F)
+    where
+        F: FnMut(&mut State) -> bool,
+    {
+        self.stash_mut(name).retain_mut(|s| filter(s));
+    }
+
+    /// Add a state that stopped running to the stash for how it stopped
+    pub fn add_stopped(&mut self, state: State, avoid: &HashSet<u64>) {
+        let name = match state.status {
+            StateStatus::Active | StateStatus::PostMerge => ACTIVE,
+            StateStatus::Break => FOUND,
+            StateStatus::Crash(..) | StateStatus::Violation(..) => ERRORED,
+            _ if state
+                .registers
+                .get_pc()
+                .as_u64()
+                .map(|pc| avoid.contains(&pc))
+                == Some(true) =>
+            {
+                AVOIDED
+            }
+            _ => DEADENDED,
+        };
+        self.add(name, state);
+    }
+}
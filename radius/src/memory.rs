@@ -1,13 +1,20 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 //use ahash::AHashMap;
 //type HashMap<P, Q> = AHashMap<P, Q>;
 
+use crate::arch::Arch;
 use crate::r2_api::{Endian, R2Api, STACK_SIZE, STACK_START};
-use crate::solver::Solver;
+use crate::solver::{BitVec, Solver};
+use crate::trace::MemoryWrite;
 use crate::value::Value;
+use std::fmt;
 use std::mem;
 
 pub const READ_CACHE: usize = 256;
+/// Longest string the string operations read by default
+pub const MAX_STRING: u64 = 8192;
+// bytes of a string read at once while looking for its null
+const STRING_CHUNK: usize = 32;
 // const LEN_MAX: u64 = 65536;
 
 // one day I will make a reasonable heap impl
@@ -37,10 +44,100 @@ pub struct Memory {
     //heap_canary: Value,
     pub bits: u64,
     pub endian: Endian,
+    /// The mapped segments, call `index_segments` after changing them directly
     pub segs: Vec<MemorySegment>,
     pub blank: bool,
+    /// What unwritten stack memory holds, by default what r2 reads
+    pub uninit: Option<Uninit>,
+    /// How loads and stores at symbolic addresses are made
+    pub address_policy: AddressPolicy,
+    /// Ranges of executable memory that were written, the code there
+    /// is decoded from memory instead of the binary
+    pub code_writes: BTreeMap<u64, u64>,
+    /// Longest string `strlen` and `compare_strings` read
+    pub max_string: u64,
+    /// Writes made since they were last taken, kept while the state is recording
+    pub journal: Option<Vec<MemoryWrite>>,
+    /// The executable segments merged into ranges, see `index_segments`
+    exec_ranges: BTreeMap<u64, u64>,
 }
 
+/// What uninitialized registers and stack memory hold, set with
+/// `RadiusOption::Uninit` to surface harnesses relying on accidental zeros
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Uninit {
+    Zero,
+    /// Every byte is the pattern
+    Poison(u8),
+    /// Every byte is a fresh symbol named after its address or register
+    Symbolic,
+}
+
+/// Widest span of addresses `AddressPolicy::Range` and `Array` cover, wider
+/// symbolic addresses are evaluated like `AddressPolicy::Evaluate`
+pub const MAX_ADDRESS_RANGE: u64 = 0x1000;
+
+/// How loads and stores at symbolic addresses are made, set with
+/// `RadiusOption::AddressPolicy`
+///
+/// ## Example
+/// ```
+/// use radius2::memory::AddressPolicy;
+/// use radius2::radius::{Radius, RadiusOption};
+/// let options = [RadiusOption::AddressPolicy(AddressPolicy::Range)];
+/// let mut radius = Radius::new_with_options(Some("../tests/r100"), &options);
+/// let mut state = radius.call_state(0x004006fd);
+/// state.symbolize_memory("flag", 0x100000, 12);
+/// state.registers.set("rdi", radius2::vc(0x100000));
+/// let found = radius.run_until(state, 0x004007a1, &[0x00400790]);
+/// assert!(found.is_some());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum AddressPolicy {
+    /// Select between up to `EvalMax` values of the address, the default
+    #[default]
+    Evaluate,
+    /// Concretize the address to its least value
+    Min,
+    /// Concretize the address to any one value
+    Any,
+    /// Select between every address from the least to the greatest value,
+    /// eg. for table lookups like `sbox[input[i]]`
+    Range,
+    /// Read from a solver array of the memory in the range, writes are like `Range`
+    Array,
+}
+
+impl AddressPolicy {
+    /// Parse a policy name from the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "evaluate" => Some(AddressPolicy::Evaluate),
+            "min" => Some(AddressPolicy::Min),
+            "any" => Some(AddressPolicy::Any),
+            "range" => Some(AddressPolicy::Range),
+            "array" => Some(AddressPolicy::Array),
+            _ => None,
+        }
+    }
+}
+
+/// A heap bug caught by `RadiusOption::HeapCheck`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HeapError {
+    /// A write past the end of an allocation
+    Overflow,
+    /// An access to a freed allocation
+    UseAfterFree,
+    /// A free of an allocation that was already freed
+    DoubleFree,
+    /// A free of a heap address that is not the start of an allocation
+    InvalidFree,
+}
+
+/// Byte used for `Uninit::Poison` from the command line
+pub const POISON_BYTE: u8 = 0xcc;
+
 pub enum Permission {
     Read,
     Write,
@@ -58,6 +155,47 @@ pub struct MemorySegment {
     pub init: bool,
 }
 
+/// What a mapped memory region holds
+#[derive(Debug, Clone, PartialEq)]
+pub enum Backing {
+    /// A segment loaded from the named file or section
+    File(String),
+    Heap,
+    Stack,
+    /// Mapped with mmap
+    Mmap,
+}
+
+/// A mapped memory region as listed by `Memory::maps`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryMap {
+    pub addr: u64,
+    pub size: u64,
+    /// Permissions like "rw-"
+    pub perms: String,
+    pub backing: Backing,
+    /// True if the region was written since it was loaded
+    pub dirty: bool,
+}
+
+impl fmt::Display for MemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let backing = match &self.backing {
+            Backing::File(name) => format!("file {}", name),
+            backing => format!("{:?}", backing).to_lowercase(),
+        };
+        write!(
+            f,
+            "0x{:016x}-0x{:016x} {} {:<6} {}",
+            self.addr,
+            self.addr + self.size,
+            self.perms,
+            if self.dirty { "dirty" } else { "" },
+            backing
+        )
+    }
+}
+
 impl Memory {
     /// Create a new Memory struct to hold memory values for symbolic execution
     pub fn new(r2api: &mut R2Api, btor: Solver, blank: bool) -> Memory {
@@ -78,17 +216,12 @@ impl Memory {
 
         let endian = r2api.info.bin.endian.as_str();
 
-        let bin = &r2api.info.bin;
-        let bits = if bin.arch == "arm" && bin.bits == 16 {
-            32 // says "16" for 32 bit arm cuz thumb
-        } else {
-            bin.bits
-        };
+        let bits = Arch::from_info(&r2api.info.bin).bits;
 
         //let heap_canary = Value::Symbolic(
         //    btor.bv("heap_canary", HEAP_CANARY_SIZE as u32), 0);
 
-        Memory {
+        let mut memory = Memory {
             solver: btor,
             r2api: r2api.clone(),
             mem: BTreeMap::new(),
@@ -98,6 +231,22 @@ impl Memory {
             endian: Endian::from_string(endian),
             segs,
             blank,
+            uninit: None,
+            address_policy: AddressPolicy::Evaluate,
+            code_writes: BTreeMap::new(),
+            max_string: MAX_STRING,
+            journal: None,
+            exec_ranges: BTreeMap::new(),
+        };
+        memory.index_segments();
+        memory
+    }
+
+    /// Update the lookup of executable segments after changing `segs`
+    pub fn index_segments(&mut self) {
+        self.exec_ranges.clear();
+        for seg in self.segs.iter().filter(|s| s.exec) {
+            add_range(&mut self.exec_ranges, seg.addr, seg.addr + seg.size);
         }
     }
 
@@ -116,6 +265,71 @@ impl Memory {
         Value::Concrete(self.heap.alloc(len), 0)
     }
 
+    /// Get the address of the allocation containing `addr`
+    pub fn allocation(&self, addr: u64) -> Option<u64> {
+        self.heap
+            .chunks
+            .iter()
+            .find(|c| c.size > 0 && addr >= c.addr && addr < c.addr + c.size)
+            .map(|c| c.addr)
+    }
+
+    /// Get the address of the freed allocation containing `addr`
+    pub fn freed_allocation(&self, addr: u64) -> Option<u64> {
+        self.heap
+            .freed
+            .iter()
+            .find(|c| addr >= c.addr && addr < c.addr + c.size)
+            .map(|c| c.addr)
+    }
+
+    /// Get the size requested for the allocation at `addr`
+    pub fn allocation_size(&self, addr: u64) -> Option<u64> {
+        self.heap
+            .chunks
+            .iter()
+            .find(|c| c.size > 0 && c.addr == addr)
+            .map(|c| c.size - HEAP_CANARY_SIZE)
+    }
+
+    /// Keep freed allocations from being reused so accesses to them can be caught
+    pub fn set_quarantine(&mut self, quarantine: bool) {
+        self.heap.quarantine = quarantine;
+    }
+
+    /// Check an access of `length` bytes at `addr` against the heap allocations,
+    /// only writes can overflow since reading a little past an allocation is
+    /// common, and only into the bytes around an allocation
+    pub fn heap_error(&self, addr: u64, length: u64, write: bool) -> Option<HeapError> {
+        if !self.heap.contains(addr) {
+            None
+        } else if let Some(chunk) = self.heap.live(addr) {
+            let end = chunk.addr + chunk.size - HEAP_CANARY_SIZE;
+            if write && addr.saturating_add(length) > end {
+                Some(HeapError::Overflow)
+            } else {
+                None
+            }
+        } else if self.freed_allocation(addr).is_some() {
+            Some(HeapError::UseAfterFree)
+        } else if write && self.heap.adjacent(addr, length) {
+            Some(HeapError::Overflow)
+        } else {
+            None
+        }
+    }
+
+    /// Check a free of `addr`, freeing null is fine
+    pub fn free_error(&self, addr: u64) -> Option<HeapError> {
+        if addr == 0 || !self.heap.contains(addr) || self.allocation_size(addr).is_some() {
+            None
+        } else if self.heap.freed.iter().any(|c| c.addr == addr) {
+            Some(HeapError::DoubleFree)
+        } else {
+            Some(HeapError::InvalidFree)
+        }
+    }
+
     /// Free allocated memory
     pub fn free(&mut self, addr: &Value) -> Value {
         let address = addr.as_u64().unwrap();
@@ -132,22 +346,131 @@ impl Memory {
         self.free(&Value::Concrete(address, 0))
     }
 
+    /// Get the address and size of each heap chunk
+    pub fn heap_chunks(&self) -> Vec<(u64, u64)> {
+        self.heap.chunks.iter().map(|c| (c.addr, c.size)).collect()
+    }
+
+    /// Replace the heap chunks with the (address, size) pairs in `chunks`
+    pub fn set_heap_chunks(&mut self, chunks: &[(u64, u64)]) {
+        if !chunks.is_empty() {
+            self.heap.chunks = chunks
+                .iter()
+                .map(|(addr, size)| Chunk {
+                    addr: *addr,
+                    size: *size,
+                })
+                .collect();
+        }
+    }
+
     /// Check the permissions `perm` (r/w/x) of the memory at `addr`
     #[inline]
     pub fn check_permission(&self, addr: u64, length: u64, perm: char) -> bool {
-        for seg in &self.segs {
-            if addr >= seg.addr && addr + length <= seg.addr + seg.size {
-                match perm {
-                    'r' => return seg.read,
-                    'w' => return seg.write,
-                    'x' => return seg.exec,
-                    'i' => return seg.init,
-                    _ => return false, // uhhh shouldnt happen
-                }
+        let end = addr.saturating_add(length);
+        let mut pos = addr;
+        // accesses can span adjacent segments, each needs the permission
+        loop {
+            let seg = self
+                .segs
+                .iter()
+                .find(|s| pos >= s.addr && pos - s.addr < s.size);
+            let allowed = match (seg, perm) {
+                (Some(seg), 'r') => seg.read,
+                (Some(seg), 'w') => seg.write,
+                (Some(seg), 'x') => seg.exec,
+                (Some(seg), 'i') => seg.init,
+                _ => false, // unmapped, or uhhh shouldnt happen
+            };
+            if !allowed {
+                return false;
+            }
+            let seg = seg.unwrap();
+            pos = seg.addr + seg.size;
+            if pos >= end {
+                return true;
             }
         }
+    }
 
-        false
+    /// Remove `size` bytes at `addr` from the segments, splitting the
+    /// segments they are only part of
+    pub fn unmap_segments(&mut self, addr: u64, size: u64) {
+        let end = addr.saturating_add(size);
+        let mut segs = Vec::with_capacity(self.segs.len() + 1);
+        for seg in self.segs.drain(..) {
+            let seg_end = seg.addr + seg.size;
+            if seg_end <= addr || seg.addr >= end {
+                segs.push(seg);
+                continue;
+            }
+            if seg.addr < addr {
+                segs.push(MemorySegment {
+                    size: addr - seg.addr,
+                    ..seg.clone()
+                });
+            }
+            if seg_end > end {
+                segs.push(MemorySegment {
+                    addr: end,
+                    size: seg_end - end,
+                    ..seg
+                });
+            }
+        }
+        self.segs = segs;
+        self.index_segments();
+    }
+
+    /// Set the permissions of `size` bytes at `addr` like mprotect, the
+    /// segments keep their names. False if any of the bytes are unmapped
+    pub fn protect(&mut self, addr: u64, size: u64, perms: &str) -> bool {
+        let end = addr.saturating_add(size);
+        let mapped: u64 = self
+            .segs
+            .iter()
+            .map(|s| (s.addr + s.size).min(end).saturating_sub(s.addr.max(addr)))
+            .sum();
+        if mapped < size {
+            return false;
+        }
+
+        let protected: Vec<MemorySegment> = self
+            .segs
+            .iter()
+            .filter(|s| s.addr < end && s.addr + s.size > addr)
+            .map(|s| {
+                let start = s.addr.max(addr);
+                MemorySegment {
+                    name: s.name.to_owned(),
+                    addr: start,
+                    size: (s.addr + s.size).min(end) - start,
+                    read: perms.contains('r'),
+                    write: perms.contains('w'),
+                    exec: perms.contains('x'),
+                    init: s.init,
+                }
+            })
+            .collect();
+        self.unmap_segments(addr, size);
+        self.segs.extend(protected);
+        self.index_segments();
+        true
+    }
+
+    /// Mark `length` bytes at `addr` as written code, merging the ranges
+    /// they overlap or touch
+    pub fn mark_code(&mut self, addr: u64, length: u64) {
+        add_range(&mut self.code_writes, addr, addr.saturating_add(length));
+    }
+
+    /// Whether any of `length` bytes at `addr` are code that was written
+    pub fn code_written(&self, addr: u64, length: u64) -> bool {
+        self.code_writes
+            .range(..addr.saturating_add(length))
+            .next_back()
+            .map(|(_, end)| *end > addr)
+            .unwrap_or(false)
     }
 
     pub fn add_segment(&mut self, name: &str, addr: u64, size: u64, perms: &str) {
@@ -160,6 +483,9 @@ impl Memory {
             exec: perms.contains('x'),
             init: perms.contains('i'),
         });
+        if perms.contains('x') {
+            add_range(&mut self.exec_ranges, addr, addr + size);
+        }
     }
 
     pub fn add_heap(&mut self) {
@@ -246,8 +572,12 @@ impl Memory {
     pub fn read_sym(&mut self, address: &Value, len: usize, solver: &mut Solver) -> Value {
         match address {
             Value::Concrete(addr, _t) => self.read_value(*addr, len),
+            Value::Symbolic(addr, t) if self.address_policy == AddressPolicy::Array => {
+                let data = self.read_array(addr, *t, len, solver);
+                self.pack(&data)
+            }
             Value::Symbolic(addr, t) => {
-                let addrs = solver.evaluate_many(addr);
+                let addrs = self.symbolic_addresses(addr, solver);
                 let mut value = Value::Symbolic(solver.bvv(0, 64), 0);
                 for a in addrs {
                     let read_val = self.read_value(a, len);
@@ -266,7 +596,7 @@ impl Memory {
         match address {
             Value::Concrete(addr, _t) => self.write_value(*addr, value, len),
             Value::Symbolic(addr, t) => {
-                let addrs = solver.evaluate_many(addr);
+                let addrs = self.symbolic_addresses(addr, solver);
                 for a in addrs {
                     let read_val = self.read_value(a, len);
                     let bv = solver.bvv(a, addr.get_width());
@@ -292,8 +622,11 @@ impl Memory {
                 self.read(*addr, len, &mut data);
                 data
             }
+            Value::Symbolic(addr, t) if self.address_policy == AddressPolicy::Array => {
+                self.read_array(addr, *t, len, solver)
+            }
             Value::Symbolic(addr, t) => {
-                let addrs = solver.evaluate_many(addr);
+                let addrs = self.symbolic_addresses(addr, solver);
                 let mut values = Vec::with_capacity(256);
                 for a in addrs {
                     let mut read_vals = vec![Value::Concrete(0, 0); len];
@@ -336,7 +669,7 @@ impl Memory {
         let t = address.get_taint();
         match address {
             Value::Concrete(addr, _t) => addrs.push(*addr),
-            Value::Symbolic(addr, _t) => addrs.extend(solver.evaluate_many(addr)),
+            Value::Symbolic(addr, _t) => addrs.extend(self.symbolic_addresses(addr, solver)),
         };
 
         for addr in addrs {
@@ -353,6 +686,48 @@ impl Memory {
         }
     }
 
+    // the addresses a symbolic address may be under the policy, concretizing it if need be
+    fn symbolic_addresses(&self, addr: &BitVec, solver: &mut Solver) -> Vec<u64> {
+        match self.address_policy {
+            AddressPolicy::Evaluate => solver.evaluate_many(addr),
+            AddressPolicy::Min => {
+                let min = solver.min(addr);
+                let min_bv = solver.bvv(min, addr.get_width());
+                solver.assert_bv(&addr._eq(&min_bv));
+                vec![min]
+            }
+            AddressPolicy::Any => solver.evalcon(addr).into_iter().collect(),
+            AddressPolicy::Range | AddressPolicy::Array => {
+                let min = solver.min(addr);
+                let max = solver.max(addr);
+                if max - min < MAX_ADDRESS_RANGE {
+                    (min..=max).collect()
+                } else {
+                    solver.evaluate_many(addr)
+                }
+            }
+        }
+    }
+
+    // read `len` bytes at a symbolic address from an array of the bytes it may read
+    fn read_array(&mut self, addr: &BitVec, t: u64, len: usize, solver: &mut Solver) -> Vec<Value> {
+        let addrs = self.symbolic_addresses(addr, solver);
+        let positions: BTreeSet<u64> = addrs.iter().flat_map(|a| *a..a + len as u64).collect();
+
+        let mut array = solver.array(64, 8);
+        for position in positions {
+            let mut byte = [Value::Concrete(0, 0)];
+            self.read(position, 1, &mut byte);
+            let index = solver.bvv(position, 64);
+            array = array.write(&index, &solver.to_bv(&byte[0], 8));
+        }
+
+        let index = solver.to_bv(&Value::Symbolic(addr.to_owned(), t), 64);
+        (0..len as u64)
+            .map(|i| Value::Symbolic(array.read(&index.add(&solver.bvv(i, 64))), t))
+            .collect()
+    }
+
     pub fn memmove(&mut self, dst: &Value, src: &Value, length: &Value, solver: &mut Solver) {
         let data = self.read_sym_len(src, length, solver);
         self.write_sym_len(dst, &data, length, solver);
@@ -416,9 +791,86 @@ impl Memory {
         result
     }
 
+    /// Get the length of the string at `addr`, at most `length`. The length
+    /// is a chain of ites over the symbolic bytes before the first concrete
+    /// null, reading at most `max_string` bytes
     pub fn strlen(&mut self, addr: &Value, length: &Value, solver: &mut Solver) -> Value {
-        let end = self.search(addr, &Value::Concrete(0, 0), length, false, solver);
-        solver.conditional(&(end.eq(&Value::Concrete(0, 0))), length, &end.sub(addr))
+        let address = match addr {
+            Value::Concrete(address, _t) => *address,
+            Value::Symbolic(_, _) => {
+                let end = self.search(addr, &Value::Concrete(0, 0), length, false, solver);
+                return solver.conditional(
+                    &(end.eq(&Value::Concrete(0, 0))),
+                    length,
+                    &end.sub(addr),
+                );
+            }
+        };
+
+        let max = length.as_u64().unwrap_or_else(|| solver.max_value(length));
+        let limit = max.min(self.max_string);
+        let data = self.string_bytes(address, limit);
+
+        let mut result = if limit < max {
+            Value::Concrete(limit, 0)
+        } else {
+            length.to_owned()
+        };
+        for (i, byte) in data.iter().enumerate().rev() {
+            let null = byte.eq(&Value::Concrete(0, 0));
+            result = solver.conditional(&null, &Value::Concrete(i as u64, 0), &result);
+        }
+
+        if length.is_symbolic() {
+            result = solver.conditional(&result.ult(length), &result, length);
+        }
+        result
+    }
+
+    // the bytes at `addr` up to and including the first concrete null
+    fn string_bytes(&mut self, addr: u64, max: u64) -> Vec<Value> {
+        let mut data = Vec::with_capacity(STRING_CHUNK);
+        let mut chunk: [Value; STRING_CHUNK] = Default::default();
+        while (data.len() as u64) < max {
+            let n = (max - data.len() as u64).min(STRING_CHUNK as u64) as usize;
+            self.read(addr + data.len() as u64, n, &mut chunk[..n]);
+            for byte in &mut chunk[..n] {
+                let null = byte.as_u64() == Some(0);
+                data.push(mem::take(byte));
+                if null {
+                    return data;
+                }
+            }
+        }
+        data
+    }
+
+    /// Compare the strings at `addr1` and `addr2` like strncmp, up to
+    /// `length` bytes and at most `max_string` bytes. Unlike comparing
+    /// the strlens, the bytes are compared in a single chain of ites
+    pub fn compare_strings(
+        &mut self,
+        addr1: &Value,
+        addr2: &Value,
+        length: &Value,
+        solver: &mut Solver,
+    ) -> Value {
+        let (address1, address2) = match (addr1, addr2) {
+            (Value::Concrete(a1, _), Value::Concrete(a2, _)) => (*a1, *a2),
+            _ => {
+                let len1 = self.strlen(addr1, length, solver);
+                let len2 = self.strlen(addr2, length, solver);
+                let len = solver.conditional(&len1.ult(&len2), &len1, &len2);
+                return self.compare(addr1, addr2, &(len + Value::Concrete(1, 0)), solver);
+            }
+        };
+
+        let max = length.as_u64().unwrap_or_else(|| solver.max_value(length));
+        let limit = max.min(self.max_string);
+        let data1 = self.string_bytes(address1, limit);
+        let data2 = self.string_bytes(address2, limit);
+        let n = data1.len().min(data2.len());
+        first_difference(&data1[..n], &data2[..n], length, true, solver)
     }
 
     pub fn compare(
@@ -431,30 +883,7 @@ impl Memory {
         let len = solver.max_value(length);
         let data1 = self.read_sym_len(addr1, &Value::Concrete(len, length.get_taint()), solver);
         let data2 = self.read_sym_len(addr2, &Value::Concrete(len, length.get_taint()), solver);
-
-        let mut result = Value::Concrete(0, 0);
-
-        for ind in 0..len as usize {
-            let d1 = &data1[ind].uext(&Value::Concrete(8, 0));
-            let d2 = &data2[ind].uext(&Value::Concrete(8, 0));
-
-            let ind_val = Value::Concrete(ind as u64, 0);
-            let len_cond = ind_val.ult(length);
-
-            result = solver.conditional(
-                &(result.eq(&Value::Concrete(0, 0)) & len_cond),
-                &d1.sub(d2),
-                &result,
-            );
-
-            if let Value::Concrete(res, _t) = &result {
-                if *res != 0 {
-                    break;
-                }
-            }
-        }
-
-        result
+        first_difference(&data1, &data2, length, false, solver)
     }
 
     /// read a Value of `length` bytes from memory at `addr`
@@ -509,6 +938,18 @@ impl Memory {
                     vals.push(Value::Symbolic(self.solver.bv(&sym_name, 8), 0));
                 }
                 self.mem.entry(caddr).or_insert(vals)
+            } else if let Some(uninit) = self.uninit.filter(|_| in_stack(caddr)) {
+                let vals = (0..size)
+                    .map(|i| match uninit {
+                        Uninit::Zero => Value::Concrete(0, 0),
+                        Uninit::Poison(byte) => Value::Concrete(byte as u64, 0),
+                        Uninit::Symbolic => {
+                            let sym_name = format!("uninit_{:08x}", caddr + i);
+                            Value::Symbolic(self.solver.bv(&sym_name, 8), 0)
+                        }
+                    })
+                    .collect();
+                self.mem.entry(caddr).or_insert(vals)
             } else {
                 let bytes = self.r2api.read(caddr, READ_CACHE).unwrap();
                 let vals = bytes
@@ -526,6 +967,41 @@ impl Memory {
         }
     }
 
+    /// The `length` bytes at `addr` without caching or creating any, None
+    /// for bytes that would be new symbols when read, like unread memory
+    /// of blank states
+    pub fn peek(&mut self, addr: u64, length: usize) -> Vec<Option<Value>> {
+        let make_sym = self.blank && !self.check_permission(addr, length as u64, 'i');
+        let size = READ_CACHE as u64;
+        let mut data = Vec::with_capacity(length);
+
+        while data.len() < length {
+            let current = addr + data.len() as u64;
+            let caddr = current & !(size - 1);
+            let offset = (current - caddr) as usize;
+            let count = (READ_CACHE - offset).min(length - data.len());
+
+            if let Some(mem) = self.mem.get(&caddr) {
+                data.extend(mem[offset..offset + count].iter().cloned().map(Some));
+            } else if make_sym {
+                data.extend((0..count).map(|_| None));
+            } else if let Some(uninit) = self.uninit.filter(|_| in_stack(caddr)) {
+                let byte = match uninit {
+                    Uninit::Zero => Some(Value::Concrete(0, 0)),
+                    Uninit::Poison(byte) => Some(Value::Concrete(byte as u64, 0)),
+                    Uninit::Symbolic => None,
+                };
+                data.extend((0..count).map(|_| byte.clone()));
+            } else {
+                let bytes = self.r2api.read(current, count).unwrap_or_default();
+                data.extend(
+                    (0..count).map(|i| bytes.get(i).map(|b| Value::Concrete(*b as u64, 0))),
+                );
+            }
+        }
+        data
+    }
+
     pub fn prot_to_str(&self, prot: u64) -> String {
         let mut prot_str = String::from("");
 
@@ -582,6 +1058,23 @@ impl Memory {
     /// write `length` bytes to memory at `addr` from `data`
     pub fn write(&mut self, addr: u64, data: &mut [Value]) {
         let length = data.len();
+        // the old bytes are taken as they are replaced, so the journal
+        // doesn't make bytes the write itself would not
+        let new = self.journal.as_ref().map(|_| data.to_vec());
+        let mut old = new.as_ref().map(|_| Vec::with_capacity(length));
+
+        let end = addr.saturating_add(length as u64);
+        let exec: Vec<(u64, u64)> = self
+            .exec_ranges
+            .range(..end)
+            .rev()
+            .take_while(|(_, e)| **e > addr)
+            .map(|(s, e)| (addr.max(*s), end.min(*e)))
+            .collect();
+        for (start, stop) in exec {
+            add_range(&mut self.code_writes, start, stop);
+        }
+
         let size = READ_CACHE as u64;
         let mask = -1i64 as u64 ^ (size - 1);
         let not_mask = size - 1;
@@ -592,23 +1085,53 @@ impl Memory {
             let caddr = (addr & mask) + size * count;
             let mut offset = (addr & not_mask) * (count == 0) as u64;
 
+            // dont read if we are writing all
+            let whole = addr % size == 0 && length % READ_CACHE == 0;
+            let fresh = whole && !self.mem.contains_key(&caddr);
             let mem = if let Some(m) = self.mem.get_mut(&caddr) {
                 m
             } else {
                 let mut newmem = vec![Value::Concrete(0, 0); READ_CACHE];
-                // dont read if we are writing all
-                if addr % size != 0 || length % READ_CACHE != 0 {
+                if !whole {
                     self.read(caddr, READ_CACHE, &mut newmem);
                 }
                 self.mem.entry(caddr).or_insert(newmem)
             };
 
             while index < length && offset < size {
-                mem[offset as usize] = mem::take(&mut data[index]);
+                let byte = mem::take(&mut data[index]);
+                let prev = mem::replace(&mut mem[offset as usize], byte);
+                if let Some(old) = &mut old {
+                    old.push(if fresh { None } else { Some(prev) });
+                }
                 index += 1;
                 offset += 1;
             }
         }
+
+        if let (Some(journal), Some(old), Some(new)) = (&mut self.journal, old, new) {
+            journal.push(MemoryWrite { addr, old, new });
+        }
+    }
+
+    /// Undo a write at `addr` of the bytes `old` had, the chunks of bytes
+    /// that were not made before the write are dropped
+    pub fn restore(&mut self, addr: u64, old: &[Option<Value>]) {
+        let journal = self.journal.take();
+        let mut i = 0;
+        while i < old.len() {
+            let current = addr.wrapping_add(i as u64);
+            let count = old[i..].iter().take_while(|b| b.is_some()).count();
+            if count > 0 {
+                let mut data: Vec<Value> = old[i..i + count].iter().flatten().cloned().collect();
+                self.write(current, &mut data);
+                i += count;
+            } else {
+                self.mem.remove(&(current & !(READ_CACHE as u64 - 1)));
+                i += 1;
+            }
+        }
+        self.journal = journal;
     }
 
     // this sucks, we need to properly do error handling to do this right
@@ -623,15 +1146,21 @@ impl Memory {
     // jesus this got huge
     /// Pack the bytes in `data` into a Value according to the endianness of the target
     pub fn pack(&self, data: &[Value]) -> Value {
-        let new_data = data;
+        self.pack_endian(data, &self.endian)
+    }
+
+    /// Pack the bytes in `data` into a Value with the given endianness
+    pub fn pack_endian(&self, data: &[Value], endian: &Endian) -> Value {
+        let reversed: Vec<Value>;
+        let new_data = if *endian == Endian::Big {
+            reversed = data.iter().rev().cloned().collect();
+            &reversed[..]
+        } else {
+            data
+        };
         let length = new_data.len();
         let mut taint = 0;
 
-        if self.endian == Endian::Big {
-            let mut new_data = data.to_owned();
-            new_data.reverse();
-        }
-
         // if length > 64 bits use sym to cheat
         if length > 8 || new_data.iter().any(|x| x.is_symbolic()) {
             // this value isn't used, idk
@@ -679,6 +1208,11 @@ impl Memory {
 
     /// Unpack the Value into `length` bytes and store them in the `data` ref
     pub fn unpack(&self, value: &Value, length: usize, data: &mut [Value]) {
+        self.unpack_endian(value, length, data, &self.endian)
+    }
+
+    /// Unpack the Value into `length` bytes with the given endianness
+    pub fn unpack_endian(&self, value: &Value, length: usize, data: &mut [Value], endian: &Endian) {
         //let mut data: Vec<Value> = Vec::with_capacity(length);
         if length == 0 {
             return;
@@ -699,12 +1233,89 @@ impl Memory {
                 }
             }
         }
-        if self.endian == Endian::Big {
+        if *endian == Endian::Big {
             data.reverse();
         }
         //data
     }
 
+    /// List the mapped regions by address with their permissions, what
+    /// backs them, and if they were written
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("/bin/sh");
+    /// let mut state = radius.entry_state();
+    /// for map in state.memory.maps() {
+    ///     println!("{}", map);
+    /// }
+    /// ```
+    pub fn maps(&mut self) -> Vec<MemoryMap> {
+        let mut maps: Vec<MemoryMap> = self
+            .segs
+            .clone()
+            .iter()
+            .map(|seg| MemoryMap {
+                addr: seg.addr,
+                size: seg.size,
+                perms: format!(
+                    "{}{}{}",
+                    if seg.read { "r" } else { "-" },
+                    if seg.write { "w" } else { "-" },
+                    if seg.exec { "x" } else { "-" }
+                ),
+                backing: backing(&seg.name),
+                dirty: self.is_dirty(seg),
+            })
+            .collect();
+
+        maps.sort_by_key(|m| m.addr);
+        maps
+    }
+
+    /// Get the mapped region containing `addr`
+    pub fn map_at(&mut self, addr: u64) -> Option<MemoryMap> {
+        self.maps()
+            .into_iter()
+            .find(|m| addr >= m.addr && addr < m.addr + m.size)
+    }
+
+    /// Print the mapped regions
+    pub fn print_maps(&mut self) {
+        for map in self.maps() {
+            println!("{}", map);
+        }
+    }
+
+    // a region is dirty if a cached chunk differs from what backs it
+    fn is_dirty(&mut self, seg: &MemorySegment) -> bool {
+        let size = READ_CACHE as u64;
+        let start = seg.addr & !(size - 1);
+        let chunks: Vec<(u64, Vec<Value>)> = self
+            .mem
+            .range(start..seg.addr + seg.size)
+            .map(|(a, m)| (*a, m.to_owned()))
+            .collect();
+
+        let file = matches!(backing(&seg.name), Backing::File(_));
+        for (addr, chunk) in chunks {
+            let original = if file {
+                self.r2api.read(addr, READ_CACHE).unwrap_or_default()
+            } else {
+                vec![0; READ_CACHE]
+            };
+            let changed = chunk
+                .iter()
+                .zip(original.iter())
+                .any(|(v, b)| v.as_u64() != Some(*b as u64));
+            if changed {
+                return true;
+            }
+        }
+        false
+    }
+
     pub fn addresses(&self) -> Vec<u64> {
         self.mem.keys().cloned().collect::<Vec<u64>>()
     }
@@ -714,12 +1325,88 @@ impl Memory {
     }
 }
 
+fn in_stack(addr: u64) -> bool {
+    (STACK_START..STACK_START + STACK_SIZE).contains(&addr)
+}
+
+// what backs a segment, from its name
+fn backing(name: &str) -> Backing {
+    match name {
+        "heap" | "[heap]" => Backing::Heap,
+        "mmapped" => Backing::Mmap,
+        _ if name == "stack" || name.starts_with("[stack") => Backing::Stack,
+        _ => Backing::File(name.to_owned()),
+    }
+}
+
+// the difference of the first pair of bytes within `length` that differ,
+// or with `strings` that differ or end the string. the ites are chained
+// from the last byte so each byte is compared once
+fn first_difference(
+    data1: &[Value],
+    data2: &[Value],
+    length: &Value,
+    strings: bool,
+    solver: &Solver,
+) -> Value {
+    let zero = Value::Concrete(0, 0);
+    let mut stops = Vec::with_capacity(data1.len());
+    for (i, (b1, b2)) in data1.iter().zip(data2).enumerate() {
+        let mut stop = !b1.eq(b2);
+        if strings {
+            stop = stop | b1.eq(&zero);
+        }
+        if length.is_symbolic() {
+            stop = stop & Value::Concrete(i as u64, 0).ult(length);
+        }
+        // nothing after a byte that surely stops matters
+        let last = stop.as_u64() == Some(1);
+        stops.push(stop);
+        if last {
+            break;
+        }
+    }
+
+    let byte = Value::Concrete(8, 0);
+    let mut result = zero;
+    for (i, stop) in stops.iter().enumerate().rev() {
+        let diff = data1[i].uext(&byte).sub(&data2[i].uext(&byte));
+        result = solver.conditional(stop, &diff, &result);
+    }
+    result
+}
+
+// add start..end to the disjoint ranges, merging the ones it overlaps or touches
+fn add_range(ranges: &mut BTreeMap<u64, u64>, mut start: u64, mut end: u64) {
+    if start >= end {
+        return;
+    }
+
+    let touching: Vec<(u64, u64)> = ranges
+        .range(..=end)
+        .rev()
+        .take_while(|(_, e)| **e >= start)
+        .map(|(s, e)| (*s, *e))
+        .collect();
+
+    for (s, e) in touching {
+        ranges.remove(&s);
+        start = start.min(s);
+        end = end.max(e);
+    }
+    ranges.insert(start, end);
+}
+
 /// An extremely simple heap
 #[derive(Clone)]
 pub struct Heap {
     pub start: u64,
     pub size: u64,
     pub chunks: Vec<Chunk>,
+    /// Freed chunks that have not been reused
+    pub freed: Vec<Chunk>,
+    /// Never reuse freed chunks
+    pub quarantine: bool,
 }
 
 /// A chunk within the Heap
@@ -739,29 +1426,60 @@ impl Heap {
                 addr: start,
                 size: 0,
             }],
+            freed: vec![],
+            quarantine: false,
         }
     }
 
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.start && addr < self.start + self.size
+    }
+
+    /// Get the allocated chunk containing `addr`
+    pub fn live(&self, addr: u64) -> Option<&Chunk> {
+        self.chunks
+            .iter()
+            .find(|c| c.size > 0 && addr >= c.addr && addr < c.addr + c.size)
+    }
+
+    /// Whether an access of `length` bytes at `addr` outside of any chunk
+    /// runs into a live chunk or starts just past the end of one
+    pub fn adjacent(&self, addr: u64, length: u64) -> bool {
+        let end = addr.saturating_add(length);
+        self.chunks.iter().filter(|c| c.size > 0).any(|c| {
+            let past = c.addr + c.size;
+            (addr < c.addr && end > c.addr) || (addr >= past && addr < past + HEAP_CANARY_SIZE)
+        })
+    }
+
     pub fn alloc(&mut self, size: u64) -> u64 {
         let last = &self.chunks[self.chunks.len() - 1];
-        let addr = last.addr + last.size;
-        self.chunks.push(Chunk {
-            addr,
-            size: size + HEAP_CANARY_SIZE,
-        });
+        let mut addr = last.addr + last.size;
+        let size = size + HEAP_CANARY_SIZE;
+        if self.quarantine {
+            // past the quarantined chunks too
+            addr = self
+                .freed
+                .iter()
+                .map(|c| c.addr + c.size)
+                .fold(addr, u64::max);
+        } else {
+            // reused memory is no longer freed
+            self.freed
+                .retain(|c| c.addr + c.size <= addr || c.addr >= addr + size);
+        }
+        self.chunks.push(Chunk { addr, size });
         addr
     }
 
     pub fn free(&mut self, addr: u64) -> Option<u64> {
-        let last = &self.chunks[self.chunks.len() - 1];
-        if addr == last.addr {
-            self.chunks.pop();
-            Some(addr)
-        } else if let Some(rem) = self.chunks.iter().position(|x| x.addr == addr) {
-            self.chunks.remove(rem);
-            Some(addr)
-        } else {
-            None
-        }
+        // the zero size chunk at the start is not an allocation
+        let rem = self
+            .chunks
+            .iter()
+            .position(|x| x.addr == addr && x.size > 0)?;
+        let chunk = self.chunks.remove(rem);
+        self.freed.push(chunk);
+        Some(addr)
     }
 }
@@ -0,0 +1,84 @@
+/// How the imports of a library are handled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModulePolicy {
+    /// Never execute into the library, every import returns 0
+    Stub,
+    /// Use the sims of its imports, stubbing those without one
+    Sims,
+    /// Execute the code of the library loaded with `RadiusOption::LoadLibs`
+    /// instead of using sims
+    Execute,
+}
+
+/// Policies for libraries by name pattern and imports to ignore,
+/// patterns can use `*` to match any characters and ignore case
+///
+/// ## Example
+/// ```
+/// use radius2::modules::{ModulePolicies, ModulePolicy};
+/// let mut policies = ModulePolicies::default();
+/// policies.add_module("libcrypto*", ModulePolicy::Stub);
+/// policies.ignore("*printf");
+///
+/// assert_eq!(policies.policy("libcrypto.so.3", "EVP_EncryptInit"), Some(ModulePolicy::Stub));
+/// assert_eq!(policies.policy("libc.so.6", "fprintf"), Some(ModulePolicy::Stub));
+/// assert_eq!(policies.policy("libc.so.6", "strlen"), None);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModulePolicies {
+    /// The first matching pattern decides the policy of a library
+    pub modules: Vec<(String, ModulePolicy)>,
+    /// Imports that are always stubbed
+    pub ignored: Vec<String>,
+}
+
+impl ModulePolicies {
+    pub fn add_module(&mut self, pattern: &str, policy: ModulePolicy) {
+        self.modules.push((pattern.to_owned(), policy));
+    }
+
+    pub fn ignore(&mut self, pattern: &str) {
+        self.ignored.push(pattern.to_owned());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty() && self.ignored.is_empty()
+    }
+
+    /// Get the policy for the import `symbol` of the library `module`
+    pub fn policy(&self, module: &str, symbol: &str) -> Option<ModulePolicy> {
+        if self.ignored.iter().any(|p| pattern_matches(p, symbol)) {
+            return Some(ModulePolicy::Stub);
+        }
+        self.modules
+            .iter()
+            .find(|(p, _)| pattern_matches(p, module))
+            .map(|(_, policy)| *policy)
+    }
+}
+
+/// Match `name` against `pattern` where `*` matches any characters, ignoring case
+pub fn pattern_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let name = name.to_lowercase();
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == name;
+    }
+
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !name.starts_with(first) || !name[first.len()..].ends_with(last) {
+        return false;
+    }
+
+    // match the middle parts in order between the first and last
+    let mut rest = &name[first.len()..name.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        if let Some(index) = rest.find(part) {
+            rest = &rest[index + part.len()..];
+        } else {
+            return false;
+        }
+    }
+    true
+}
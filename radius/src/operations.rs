@@ -65,7 +65,9 @@ pub enum Operations {
     DoubleToFloat,
     FloatToDouble,
     FloatCompare,
+    FloatNotEqual,
     FloatLessThan,
+    FloatLessThanEqual,
     FloatAdd,
     FloatSubtract,
     FloatMultiply,
@@ -186,7 +188,9 @@ impl Operations {
             "F*" => Operations::FloatMultiply,
             "F/" => Operations::FloatDivide,
             "F==" => Operations::FloatCompare,
+            "F!=" => Operations::FloatNotEqual,
             "F<" => Operations::FloatLessThan,
+            "F<=" => Operations::FloatLessThanEqual,
             "NAN" => Operations::NaN,
             "-F" => Operations::FloatNegate,
             "SWAP" => Operations::Swap,
@@ -357,16 +361,25 @@ pub fn get_stack_taint(state: &mut State, n: usize) -> u64 {
     taint
 }
 
+// the solver has no floating point theory, so symbolic operands are
+// constrained to one value. unsat paths get 0 instead of panicking
+#[inline]
+fn pop_float_bits(state: &mut State) -> u64 {
+    let value = pop_value(state, false, false);
+    match &value {
+        Value::Concrete(val, _t) => *val,
+        Value::Symbolic(_val, _t) => state.solver.evalcon_to_u64(&value).unwrap_or_default(),
+    }
+}
+
 #[inline]
 pub fn pop_double(state: &mut State) -> f64 {
-    let value = pop_concrete(state, false, false);
-    f64::from_bits(value)
+    f64::from_bits(pop_float_bits(state))
 }
 
 #[inline]
 pub fn pop_float(state: &mut State) -> f32 {
-    let value = pop_concrete(state, false, false);
-    f32::from_bits(value as u32)
+    f32::from_bits(pop_float_bits(state) as u32)
 }
 
 #[inline]
@@ -386,6 +399,7 @@ pub fn do_equal(state: &mut State, reg: StackItem, value: Value, set_esil: bool)
         } else {
             state.registers.set_value(index, value.to_owned());
         }
+        state.watch_register(index);
 
         if set_esil {
             state.esil.last_sz = size;
@@ -408,8 +422,16 @@ macro_rules! binary_float_operation {
         let t = get_stack_taint($state, 1);
         let arg1 = pop_double($state);
         let arg2 = pop_double($state);
-        let value = Value::Concrete(f64::to_bits(arg1 $op arg2), t);
-        push_value($state, value);
+        push_value($state, Value::from_f64(arg1 $op arg2, t));
+    };
+}
+
+macro_rules! float_comparison {
+    ($state:expr, $op:tt) => {
+        let t = get_stack_taint($state, 2);
+        let arg1 = pop_double($state);
+        let arg2 = pop_double($state);
+        push_value($state, Value::Concrete((arg1 $op arg2) as u64, t));
     };
 }
 
@@ -783,7 +805,8 @@ pub fn do_operation(state: &mut State, operation: &Operations) {
         Operations::DoubleToInt => {
             let t = get_stack_taint(state, 1);
             let arg1 = pop_double(state);
-            push_value(state, Value::Concrete(arg1 as u64, t));
+            // truncate toward zero like cvttsd2si, negative doubles stay negative
+            push_value(state, Value::Concrete(arg1 as i64 as u64, t));
         }
         Operations::SignedToDouble => {
             let t = get_stack_taint(state, 1);
@@ -839,16 +862,16 @@ pub fn do_operation(state: &mut State, operation: &Operations) {
             binary_float_operation!(state, /);
         }
         Operations::FloatCompare => {
-            let t = get_stack_taint(state, 2);
-            let arg1 = pop_double(state);
-            let arg2 = pop_double(state);
-            push_value(state, Value::Concrete((arg1 - arg2 == 0.0) as u64, t));
+            float_comparison!(state, ==);
+        }
+        Operations::FloatNotEqual => {
+            float_comparison!(state, !=);
         }
         Operations::FloatLessThan => {
-            let t = get_stack_taint(state, 2);
-            let arg1 = pop_double(state);
-            let arg2 = pop_double(state);
-            push_value(state, Value::Concrete((arg1 < arg2) as u64, t));
+            float_comparison!(state, <);
+        }
+        Operations::FloatLessThanEqual => {
+            float_comparison!(state, <=);
         }
         Operations::NaN => {
             let t = get_stack_taint(state, 1);
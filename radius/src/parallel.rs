@@ -0,0 +1,215 @@
+use crate::bus::EventBus;
+use crate::processor::{Hook, RunMode};
+use crate::r2_api::R2Api;
+use crate::radius::{Radius, RadiusOption};
+use crate::sims::Sim;
+use crate::state::StateStatus;
+
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default)]
+struct Shared {
+    states: Vec<String>,
+    idle: usize,
+    found: Option<String>,
+    done: bool,
+    ended: WorkerResults,
+}
+
+/// What the workers collected on the way, handed back to the caller.
+/// Crashed states keep their status, which snapshots do not
+#[derive(Debug, Default)]
+pub struct WorkerResults {
+    pub crashes: Vec<(String, StateStatus)>,
+    pub unconstrained: Vec<String>,
+    pub steps: u64,
+}
+
+/// The states the workers of `Radius::run_until_parallel` share. States
+/// can't leave the thread that made them, so they are passed around
+/// serialized. Workers explore their own states and only give one up
+/// when another worker is idle
+#[derive(Debug)]
+pub struct WorkQueue {
+    workers: usize,
+    shared: Mutex<Shared>,
+}
+
+impl WorkQueue {
+    pub fn new(workers: usize) -> Self {
+        WorkQueue {
+            workers,
+            shared: Mutex::new(Shared::default()),
+        }
+    }
+
+    /// Share a state serialized with `State::serialize`
+    pub fn push(&self, data: String) {
+        self.shared.lock().unwrap().states.push(data);
+    }
+
+    /// Take a shared state, waiting until there is one. Returns None once
+    /// a state was found or every worker is waiting
+    pub fn take(&self) -> Option<String> {
+        self.shared.lock().unwrap().idle += 1;
+        loop {
+            {
+                let mut shared = self.shared.lock().unwrap();
+                if shared.done {
+                    return None;
+                } else if let Some(data) = shared.states.pop() {
+                    shared.idle -= 1;
+                    return Some(data);
+                } else if shared.idle == self.workers {
+                    // no worker has states left to give
+                    shared.done = true;
+                    return None;
+                }
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Whether a worker is waiting for a state to be shared
+    pub fn wants_work(&self) -> bool {
+        let shared = self.shared.lock().unwrap();
+        shared.idle > 0 && shared.states.is_empty()
+    }
+
+    /// Report the state that reached the target, stopping every worker
+    pub fn found(&self, data: String) {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.found.is_none() {
+            shared.found = Some(data);
+        }
+        shared.done = true;
+    }
+
+    /// Stop every worker, eg. when the run timed out
+    pub fn stop(&self) {
+        self.shared.lock().unwrap().done = true;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.shared.lock().unwrap().done
+    }
+
+    /// The serialized state that reached the target, if any
+    pub fn result(&self) -> Option<String> {
+        self.shared.lock().unwrap().found.to_owned()
+    }
+
+    /// Add the crashes and unconstrained states of a worker that stopped
+    pub fn collect(&self, radius: &mut Radius) {
+        let processor = &mut radius.processor;
+        let mut shared = self.shared.lock().unwrap();
+        for state in processor.crashes.drain(..) {
+            let status = state.status.to_owned();
+            shared.ended.crashes.push((state.serialize(), status));
+        }
+        for state in processor.unconstrained.drain(..) {
+            shared.ended.unconstrained.push(state.serialize());
+        }
+        shared.ended.steps += processor.steps;
+    }
+
+    /// Take what the workers collected, see `collect`
+    pub fn take_results(&self) -> WorkerResults {
+        std::mem::take(&mut self.shared.lock().unwrap().ended)
+    }
+}
+
+/// What a worker needs to make its own `Radius` for the same binary. The
+/// r2 session is shared, so workers only synchronize on r2 queries
+#[derive(Clone)]
+pub struct WorkerSetup {
+    pub r2api: R2Api,
+    pub options: Vec<RadiusOption>,
+    pub breakpoints: HashSet<u64>,
+    pub avoidpoints: HashSet<u64>,
+    pub hooks: HashMap<u64, Vec<Hook>>,
+    pub esil_hooks: HashMap<u64, Vec<String>>,
+    pub sims: HashMap<u64, Sim>,
+    pub skipped: HashMap<u64, u64>,
+    pub api_hashes: HashMap<u64, HashMap<u64, u64>>,
+    pub deadline: Option<Instant>,
+    pub bus: EventBus,
+}
+
+impl WorkerSetup {
+    /// Make the radius of the worker, in the thread it runs in
+    pub fn build(self) -> Radius {
+        let mut radius = Radius::new_with_r2api(self.r2api, &self.options);
+        radius.processor.breakpoints = self.breakpoints;
+        radius.processor.avoidpoints = self.avoidpoints;
+        radius.processor.hooks = self.hooks;
+        radius.processor.esil_hooks = self.esil_hooks;
+        radius.processor.sims.extend(self.sims);
+        radius.processor.skipped = self.skipped;
+        radius.processor.api_hashes = self.api_hashes;
+        radius.processor.bus = self.bus;
+        radius
+    }
+
+    /// Explore states from the queue until one reaches a breakpoint,
+    /// every worker runs out of states, or the deadline passes. States
+    /// are taken in the order of the configured strategy
+    pub fn explore(self, queue: &WorkQueue) {
+        let deadline = self.deadline;
+        let mut radius = self.build();
+        work(&mut radius, queue, deadline);
+        queue.collect(&mut radius);
+    }
+}
+
+// step the states of a worker until it is done
+fn work(radius: &mut Radius, queue: &WorkQueue, deadline: Option<Instant>) {
+    let mut states = radius.processor.strategy.queue();
+
+    loop {
+        if deadline.map(|d| Instant::now() > d) == Some(true) {
+            queue.stop();
+            return;
+        }
+
+        let state = if let Some(state) = states.pop() {
+            if queue.is_done() {
+                return;
+            }
+            Rc::try_unwrap(state).unwrap_or_else(|s| (*s).clone())
+        } else if let Some(data) = queue.take() {
+            match radius.load_state(&data) {
+                Ok(state) => state,
+                Err(e) => {
+                    println!("warning: could not load shared state: {}", e);
+                    continue;
+                }
+            }
+        } else {
+            return;
+        };
+
+        // runs until the state splits or one reaches a breakpoint
+        let mut new_states = radius.processor.run(state, RunMode::Parallel);
+        if new_states.len() == 1 {
+            queue.found(new_states.remove(0).serialize());
+            return;
+        }
+        for state in new_states {
+            states.push(Rc::new(state));
+        }
+
+        if states.len() > 1 && queue.wants_work() {
+            // give away the state the strategy would step last
+            let mut drained = states.drain();
+            queue.push(drained.remove(0).serialize());
+            for state in drained {
+                states.push(state);
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+/// A memory access made by a step of a thread
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Access {
+    pub addr: u64,
+    pub length: u64,
+    pub write: bool,
+}
+
+impl Access {
+    pub fn read(addr: u64, length: u64) -> Self {
+        Access {
+            addr,
+            length,
+            write: false,
+        }
+    }
+
+    pub fn write(addr: u64, length: u64) -> Self {
+        Access {
+            addr,
+            length,
+            write: true,
+        }
+    }
+
+    /// Two accesses conflict if they overlap and one of them is a write
+    pub fn conflicts(&self, other: &Access) -> bool {
+        (self.write || other.write)
+            && self.addr < other.addr.saturating_add(other.length)
+            && other.addr < self.addr.saturating_add(self.length)
+    }
+}
+
+/// The accesses of a step that a scheduler runs without switching threads
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Step {
+    pub accesses: Vec<Access>,
+}
+
+impl Step {
+    pub fn new(accesses: &[Access]) -> Self {
+        Step {
+            accesses: accesses.to_vec(),
+        }
+    }
+
+    /// Steps of different threads are dependent if any of their accesses
+    /// conflict, independent steps give the same state in either order
+    pub fn depends(&self, other: &Step) -> bool {
+        self.accesses
+            .iter()
+            .any(|a| other.accesses.iter().any(|b| a.conflicts(b)))
+    }
+}
+
+/// Get the schedules of the steps of each thread that have to be explored,
+/// one for each ordering of the dependent steps. Schedules that only differ
+/// in the order of independent steps end in the same state so all but one
+/// are pruned with sleep sets. A schedule is the thread index of each step
+///
+/// ## Example
+/// ```
+/// use radius2::por::{schedules, Access, Step};
+/// let a = vec![Step::new(&[Access::write(0x1000, 4)]), Step::new(&[Access::read(0x2000, 4)])];
+/// let b = vec![Step::new(&[Access::read(0x1000, 4)]), Step::new(&[Access::write(0x3000, 4)])];
+/// // only the order of the write and read of 0x1000 matters
+/// assert_eq!(schedules(&[a, b]).len(), 2);
+/// ```
+pub fn schedules(threads: &[Vec<Step>]) -> Vec<Vec<usize>> {
+    let mut positions = vec![0; threads.len()];
+    let mut schedule = vec![];
+    let mut found = vec![];
+    explore(threads, &mut positions, &[], &mut schedule, &mut found);
+    found
+}
+
+// depth first search where `sleep` holds the threads whose next step
+// was already explored from an equivalent schedule
+fn explore(
+    threads: &[Vec<Step>],
+    positions: &mut Vec<usize>,
+    sleep: &[usize],
+    schedule: &mut Vec<usize>,
+    found: &mut Vec<Vec<usize>>,
+) {
+    let enabled: Vec<usize> = (0..threads.len())
+        .filter(|t| positions[*t] < threads[*t].len())
+        .collect();
+
+    if enabled.is_empty() {
+        found.push(schedule.clone());
+        return;
+    }
+
+    let mut done: Vec<usize> = sleep.to_vec();
+    for thread in enabled {
+        if done.contains(&thread) {
+            continue;
+        }
+        let step = &threads[thread][positions[thread]];
+        let next_sleep: Vec<usize> = done
+            .iter()
+            .copied()
+            .filter(|t| !step.depends(&threads[*t][positions[*t]]))
+            .collect();
+
+        positions[thread] += 1;
+        schedule.push(thread);
+        explore(threads, positions, &next_sleep, schedule, found);
+        schedule.pop();
+        positions[thread] -= 1;
+
+        done.push(thread);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::snapshot::{parse_sexps, sort_width, tokenize, Sexp};
+use crate::value::Value;
+use std::collections::HashMap;
+
+// operators written infix, n-ary applications are joined by them
+const INFIX: [(&str, &str); 28] = [
+    ("bvadd", "+"),
+    ("bvsub", "-"),
+    ("bvmul", "*"),
+    ("bvudiv", "/"),
+    ("bvsdiv", "/s"),
+    ("bvurem", "%"),
+    ("bvsrem", "%s"),
+    ("and", "&&"),
+    ("or", "||"),
+    ("xor", "^^"),
+    ("=>", "=>"),
+    ("bvand", "&"),
+    ("bvor", "|"),
+    ("bvxor", "^"),
+    ("bvshl", "<<"),
+    ("bvlshr", ">>"),
+    ("bvashr", ">>s"),
+    ("=", "=="),
+    ("distinct", "!="),
+    ("bvcomp", "=="),
+    ("bvult", "<"),
+    ("bvule", "<="),
+    ("bvugt", ">"),
+    ("bvuge", ">="),
+    ("bvslt", "<s"),
+    ("bvsle", "<=s"),
+    ("bvsgt", ">s"),
+    ("bvsge", ">=s"),
+];
+
+/// Render a value as a readable expression like `(flag[7:0] + 0x1) == 0x41`.
+/// Definitions are inlined, constants are hex, slices of variables are
+/// `name[hi:lo]`, and the bytes of a variable concatenated in order are
+/// merged back into one slice. Signed operators end in `s`
+///
+/// ## Example
+/// ```
+/// use radius2::pretty;
+/// use radius2::radius::Radius;
+/// let mut radius = Radius::new("../tests/r100");
+/// let mut state = radius.call_state(0x004006fd);
+/// let flag = state.symbolic_value("flag", 64);
+/// println!("{}", pretty::expression(&flag.add(&radius2::vc(1))));
+/// assert_eq!(pretty::expression(&radius2::vc(0x41)), "0x41");
+/// ```
+pub fn expression(value: &Value) -> String {
+    match value.try_con() {
+        Value::Concrete(v, _) => format!("0x{:x}", v),
+        Value::Symbolic(bv, _) => smt(&format!("{:?}", bv)),
+    }
+}
+
+/// Render the SMT-LIB dump of a term as a readable expression, see
+/// `expression`. The dump is returned as is if it cannot be parsed
+pub fn smt(text: &str) -> String {
+    let sexps = match parse_sexps(&tokenize(text)) {
+        Ok(sexps) => sexps,
+        Err(_) => return text.trim().to_owned(),
+    };
+    let mut printer = Printer {
+        defs: HashMap::new(),
+        widths: HashMap::new(),
+    };
+    let mut result = None;
+
+    for sexp in &sexps {
+        let list = match sexp {
+            Sexp::List(list) => list,
+            _ => {
+                result = Some(sexp);
+                continue;
+            }
+        };
+        match list.first() {
+            Some(Sexp::Atom(cmd)) if cmd == "declare-fun" || cmd == "declare-const" => {
+                if let (Some(Sexp::Atom(name)), Ok(width)) = (list.get(1), sort_width(list.last()))
+                {
+                    printer.widths.insert(name.to_owned(), width);
+                    result = Some(&list[1]);
+                }
+            }
+            Some(Sexp::Atom(cmd)) if cmd == "define-fun" => {
+                if let (Some(Sexp::Atom(name)), Some(body)) = (list.get(1), list.get(4)) {
+                    printer.defs.insert(name.to_owned(), body.to_owned());
+                    result = Some(body);
+                }
+            }
+            Some(Sexp::Atom(cmd)) if cmd == "assert" => result = list.get(1),
+            Some(Sexp::Atom(cmd))
+                if cmd.starts_with("set-") || cmd == "check-sat" || cmd == "exit" => {}
+            _ => result = Some(sexp),
+        }
+    }
+
+    match result {
+        Some(sexp) => printer.term(sexp, true),
+        None => text.trim().to_owned(),
+    }
+}
+
+struct Printer {
+    defs: HashMap<String, Sexp>,
+    /// widths of the declared variables
+    widths: HashMap<String, u32>,
+}
+
+impl Printer {
+    // the term, parenthesized unless it is the whole expression or an operand
+    // that can't be split, like a variable or a function application
+    fn term(&mut self, sexp: &Sexp, top: bool) -> String {
+        let list = match sexp {
+            Sexp::Atom(name) => return self.atom(name, top),
+            Sexp::List(list) => list,
+        };
+        let (head, rest) = match list.split_first() {
+            Some(split) => split,
+            None => return "()".to_owned(),
+        };
+        let wrap = |s: String| if top { s } else { format!("({})", s) };
+
+        let op = match head {
+            Sexp::Atom(op) => op.as_str(),
+            Sexp::List(indexed) => return self.indexed(indexed, rest),
+        };
+        match (op, rest) {
+            ("let", [Sexp::List(bindings), body]) => {
+                let outer = self.defs.clone();
+                for binding in bindings {
+                    if let Sexp::List(pair) = binding {
+                        if let [Sexp::Atom(name), value] = pair.as_slice() {
+                            self.defs.insert(name.to_owned(), value.to_owned());
+                        }
+                    }
+                }
+                let body = self.term(body, top);
+                self.defs = outer;
+                body
+            }
+            ("_", [Sexp::Atom(value), _]) => {
+                let digits = value.trim_start_matches("bv");
+                match digits.parse::<u128>() {
+                    Ok(v) => format!("0x{:x}", v),
+                    Err(_) => digits.to_owned(),
+                }
+            }
+            // boolean conditions as bitvectors of width 1
+            ("ite", [cond, Sexp::Atom(t), Sexp::Atom(f)]) if t == "#b1" && f == "#b0" => {
+                self.term(cond, top)
+            }
+            ("ite", [cond, t, f]) => {
+                let (cond, t, f) = (
+                    self.term(cond, false),
+                    self.term(t, false),
+                    self.term(f, false),
+                );
+                wrap(format!("{} ? {} : {}", cond, t, f))
+            }
+            ("not", [arg]) | ("bvnot", [arg]) => format!("!{}", self.term(arg, false)),
+            ("bvneg", [arg]) => format!("-{}", self.term(arg, false)),
+            ("concat", _) => self.concat(rest, top),
+            _ => match INFIX.iter().find(|(name, _)| *name == op) {
+                Some((_, infix)) if rest.len() > 1 => {
+                    let args: Vec<String> = rest.iter().map(|a| self.term(a, false)).collect();
+                    wrap(args.join(&format!(" {} ", infix)))
+                }
+                _ => {
+                    let args: Vec<String> = rest.iter().map(|a| self.term(a, true)).collect();
+                    format!("{}({})", op, args.join(", "))
+                }
+            },
+        }
+    }
+
+    fn atom(&mut self, name: &str, top: bool) -> String {
+        if let Some(bits) = name.strip_prefix("#b") {
+            match u128::from_str_radix(bits, 2) {
+                Ok(v) => format!("0x{:x}", v),
+                Err(_) => name.to_owned(),
+            }
+        } else if let Some(hex) = name.strip_prefix("#x") {
+            let hex = hex.trim_start_matches('0');
+            format!("0x{}", if hex.is_empty() { "0" } else { hex })
+        } else if let Some(def) = self.defs.get(name).cloned() {
+            self.term(&def, top)
+        } else {
+            name.to_owned()
+        }
+    }
+
+    // indexed operators like ((_ extract 7 0) x)
+    fn indexed(&mut self, indexed: &[Sexp], rest: &[Sexp]) -> String {
+        let names: Vec<&str> = indexed
+            .iter()
+            .filter_map(|s| match s {
+                Sexp::Atom(a) => Some(a.as_str()),
+                _ => None,
+            })
+            .collect();
+        let arg = match rest.first() {
+            Some(arg) => self.term(arg, false),
+            None => return names.join(" "),
+        };
+        match names.as_slice() {
+            ["_", "extract", hi, lo] => format!("{}[{}:{}]", arg, hi, lo),
+            ["_", "zero_extend", n] => format!("uext({}, {})", arg, n),
+            ["_", "sign_extend", n] => format!("sext({}, {})", arg, n),
+            ["_", op, indices @ ..] => format!("{}({}, {})", op, arg, indices.join(", ")),
+            _ => format!("{}({})", names.join(" "), arg),
+        }
+    }
+
+    // slices of a variable, like ((_ extract 15 8) flag), as (flag, 15, 8)
+    fn slice(&self, sexp: &Sexp) -> Option<(String, u32, u32)> {
+        match sexp {
+            Sexp::Atom(name) => self.defs.get(name).and_then(|def| self.slice(def)),
+            Sexp::List(list) => match list.as_slice() {
+                [Sexp::List(indexed), Sexp::Atom(var)] if self.widths.contains_key(var) => {
+                    match indexed.as_slice() {
+                        [Sexp::Atom(u), Sexp::Atom(op), Sexp::Atom(hi), Sexp::Atom(lo)]
+                            if u == "_" && op == "extract" =>
+                        {
+                            Some((var.to_owned(), hi.parse().ok()?, lo.parse().ok()?))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+        }
+    }
+
+    // flatten nested concatenations and merge consecutive slices of a variable
+    fn concat(&mut self, args: &[Sexp], top: bool) -> String {
+        let mut flat = vec![];
+        let mut stack: Vec<&Sexp> = args.iter().rev().collect();
+        while let Some(arg) = stack.pop() {
+            match arg {
+                Sexp::List(list) if matches!(list.first(), Some(Sexp::Atom(op)) if op == "concat") =>
+                {
+                    stack.extend(list[1..].iter().rev());
+                }
+                _ => flat.push(arg),
+            }
+        }
+
+        let mut parts: Vec<String> = vec![];
+        let mut last: Option<(String, u32, u32)> = None;
+        for arg in flat {
+            match (self.slice(arg), &mut last) {
+                (Some((var, hi, lo)), Some((last_var, _, last_lo)))
+                    if var == *last_var && hi + 1 == *last_lo =>
+                {
+                    *last_lo = lo;
+                }
+                (slice, _) => {
+                    if let Some(done) = last.take() {
+                        parts.push(self.show_slice(done));
+                    }
+                    match slice {
+                        Some(slice) => last = Some(slice),
+                        None => parts.push(self.term(arg, false)),
+                    }
+                }
+            }
+        }
+        if let Some(done) = last.take() {
+            parts.push(self.show_slice(done));
+        }
+
+        if parts.len() == 1 || top {
+            parts.join(" ++ ")
+        } else {
+            format!("({})", parts.join(" ++ "))
+        }
+    }
+
+    fn show_slice(&self, (var, hi, lo): (String, u32, u32)) -> String {
+        if lo == 0 && self.widths.get(&var) == Some(&(hi + 1)) {
+            var
+        } else {
+            format!("{}[{}:{}]", var, hi, lo)
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use crate::solver::BitVec;
+use crate::state::State;
+use crate::value::Value;
+use std::collections::HashSet;
+
+/// A store whose address and value both depend on tainted input
+#[derive(Debug, Clone)]
+pub struct WritePrimitive {
+    /// Address of the storing instruction
+    pub pc: u64,
+    pub address: Value,
+    pub value: Value,
+    /// Number of bytes written
+    pub length: usize,
+    /// Lowest and highest address the store can write to
+    pub address_range: (u64, u64),
+    /// Lowest and highest value the store can write
+    pub value_range: (u64, u64),
+    /// Names of the taints the address and value depend on
+    pub taints: Vec<String>,
+    /// Path constraints of the state at the store
+    pub constraints: Vec<BitVec>,
+    /// Number of input bytes the address and value depend on
+    pub controlled: usize,
+    /// True if reaching the store depends on input
+    pub reachable: bool,
+}
+
+/// Catalog of write-what-where primitives found during exploration,
+/// keeping the first primitive found at each instruction
+///
+/// ## Example
+/// ```
+/// use radius2::radius::{Radius, RadiusOption};
+/// let mut radius = Radius::new_with_options(Some("../tests/r100"), &[RadiusOption::WritePrimitives(true)]);
+/// let state = radius.call_state(0x004006fd);
+/// radius.run_until(state, 0x00400844, &[0x00400855]);
+/// radius.print_write_primitives();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PrimitiveCatalog {
+    pub primitives: Vec<WritePrimitive>,
+    seen: HashSet<u64>,
+}
+
+impl PrimitiveCatalog {
+    pub fn new() -> Self {
+        PrimitiveCatalog::default()
+    }
+
+    /// Add the store of `value` to `address` if both are tainted
+    /// and no primitive was found at the current instruction yet
+    pub fn check(&mut self, state: &State, address: &Value, value: &Value, length: usize) {
+        if address.get_taint() == 0 || value.get_taint() == 0 {
+            return;
+        }
+
+        let pc = state.registers.get_pc().as_u64().unwrap_or_default();
+        if !self.seen.insert(pc) {
+            return;
+        }
+
+        let taint = address.get_taint() | value.get_taint();
+        let mut taints: Vec<String> = state
+            .taints
+            .iter()
+            .filter(|(_name, t)| taint & **t != 0)
+            .map(|(name, _t)| name.to_owned())
+            .collect();
+        taints.sort();
+
+        let mut positions = state.input_positions(address);
+        positions.extend(state.input_positions(value));
+        let controlled = positions.into_iter().collect::<HashSet<_>>().len();
+        let reachable = !state.input_positions(&state.path_predicate().0).is_empty();
+
+        let solver = &state.solver;
+        self.primitives.push(WritePrimitive {
+            pc,
+            address: address.to_owned(),
+            value: value.to_owned(),
+            length,
+            address_range: (solver.min_value(address), solver.max_value(address)),
+            value_range: (solver.min_value(value), solver.max_value(value)),
+            taints,
+            constraints: solver.assertions.clone(),
+            controlled,
+            reachable,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.primitives.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.primitives.is_empty()
+    }
+
+    /// Print a line for each primitive in the catalog
+    pub fn print(&self) {
+        println!(
+            "{:<12}{:>6}  {:<37}{:<37}{:>6}  taints",
+            "pc", "bytes", "address", "value", "cons"
+        );
+        for p in &self.primitives {
+            println!(
+                "{:<12}{:>6}  {:<37}{:<37}{:>6}  {}",
+                format!("0x{:08x}", p.pc),
+                p.length,
+                format!("0x{:x}-0x{:x}", p.address_range.0, p.address_range.1),
+                format!("0x{:x}-0x{:x}", p.value_range.0, p.value_range.1),
+                p.constraints.len(),
+                p.taints.join(",")
+            );
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.primitives.clear();
+        self.seen.clear();
+    }
+}
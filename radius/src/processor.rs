@@ -1,28 +1,106 @@
+use crate::arch::{Arch, ArchKind};
 use crate::operations::{
     do_operation, pop_concrete, pop_stack_value, pop_value, push_value, Operations, OPS,
 };
 use crate::r2_api::{hex_decode, CallingConvention, Instruction, Syscall};
+use crate::reach::Reachability;
 use crate::value::{vc, Value};
 
 use crate::state::{
     Event, EventContext, EventTrigger, ExecMode, StackItem, State, StateStatus, DO_EVENT_HOOKS,
 };
 
+use crate::budget::{resident_memory, StopReason, MEMORY_CHECK_STEPS};
+use crate::bus::{EventBus, EventLevel, PruneReason, RunEvent};
+use crate::contract::{self, Contract, Violations};
+use crate::corpus::Corpus;
+use crate::exception::{self, BREAKPOINT, EXCEPTION_RETURN};
+use crate::explosion::{ExplosionReport, ForkStats};
+#[cfg(feature = "unicorn")]
+use crate::fastforward::{self, NATIVE_RETRY, NATIVE_STEPS};
+use crate::findings::Finding;
+use crate::gc::{DropCallback, GcPolicy};
+use crate::goal::Goals;
+use crate::interval;
+use crate::invariant::{Assertion, Counterexample, Invariant, InvariantViolation};
+use crate::loops::{LoopBounds, LoopSummary};
+use crate::profile::Profile;
 use crate::sims::syscall::syscall;
 use crate::sims::{Sim, SimMethod};
+use crate::solver::SolverStats;
+use crate::strategy::{ExplorationStrategy, Strategy};
+use crate::vm::VmTracer;
+use boolector::SolverResult;
 
-use std::collections::BinaryHeap;
+use std::cell::RefCell;
 use std::mem;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use std::collections::BTreeMap;
 use std::collections::{HashMap, HashSet};
 
 const INSTR_NUM: usize = 64;
 // const COLOR: bool = true;
+// longest byte pattern of an opcode handler
+const MAX_OPCODE_LEN: usize = 16;
 const CALL_TYPE: i64 = 3;
 const RETN_TYPE: i64 = 5;
+// instruction types that end a block, for invariants checked per block
+pub const BLOCK_END_TYPES: [&str; 4] = ["jmp", "call", "ret", "trap"];
 // const NOP_TYPE: i64 = 8;
+// bytes read to disassemble a block of self-modifying code
+const SELFMODIFY_BYTES: usize = 256;
+// decoded versions of the code kept for each block of self-modifying code
+const SELFMODIFY_VERSIONS: usize = 8;
+
+// a symbolic pc that can be both of these can be pointed anywhere
+const PC_CONTROL_TARGETS: [u64; 2] = [0x4141414141414141, 0x4242424242424242];
+
+// fill the operands of the instruction into the esil of an override
+fn override_esil(esil: &str, opcode: &str) -> String {
+    let operands = opcode
+        .split_once(char::is_whitespace)
+        .map(|(_, ops)| ops)
+        .unwrap_or_default();
+    let mut esil = esil.to_owned();
+    for (i, operand) in operands.split(',').enumerate() {
+        esil = esil.replace(&format!("{{{}}}", i), operand.trim());
+    }
+    esil
+}
+
+// the instructions up to the end of the block, that are entirely in the data
+fn block_prefix(addr: u64, instrs: Vec<Instruction>, len: usize) -> Vec<Instruction> {
+    let end = addr + len as u64;
+    let mut block = vec![];
+    for instr in instrs {
+        let partial = instr.size == 0 || instr.offset + instr.size > end;
+        if partial && !block.is_empty() {
+            break;
+        }
+        let block_end = BLOCK_END_TYPES.iter().any(|t| instr.r#type.contains(t));
+        block.push(instr);
+        if block_end {
+            break;
+        }
+    }
+    block
+}
+
+// whether the symbolic pc can be pointed anywhere, like a return
+// address or call target overwritten with input
+fn pc_controlled(state: &mut State, pc: &Value) -> bool {
+    let width = pc.as_bv().map(|bv| bv.get_width()).unwrap_or(64);
+    let mask = if width >= 64 {
+        u64::MAX
+    } else {
+        (1 << width) - 1
+    };
+    PC_CONTROL_TARGETS
+        .iter()
+        .all(|t| state.check(&pc.eq(&vc(t & mask))))
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Word {
@@ -32,6 +110,36 @@ pub enum Word {
     Unknown(String),
 }
 
+// the next value produced by a policy, counters are kept per state
+fn policy_value(state: &mut State, name: &str, policy: InstructionPolicy, bits: u32) -> Value {
+    match policy {
+        InstructionPolicy::Fixed(value) => vc(value),
+        InstructionPolicy::Monotonic(start, step) => {
+            let count = state
+                .context
+                .get(name)
+                .and_then(|c| c[0].as_u64())
+                .map(|c| c.wrapping_add(step))
+                .unwrap_or(start);
+
+            state.context.insert(name.to_owned(), vec![vc(count)]);
+            vc(count)
+        }
+        InstructionPolicy::Symbolic => state.new_symbol(name, bits),
+    }
+}
+
+// cpuid results of a bare metal Intel CPU, the hypervisor bit is clear
+fn cpuid_values(leaf: u64) -> [u64; 4] {
+    match leaf {
+        // "GenuineIntel"
+        0 => [0x16, 0x756e6547, 0x6c65746e, 0x49656e69],
+        1 => [0x000906ea, 0x00100800, 0x7ffafbbf, 0xbfebfbff],
+        0x80000000 => [0x80000008, 0, 0, 0],
+        _ => [0, 0, 0, 0],
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum RunMode {
     Single,
@@ -41,30 +149,407 @@ pub enum RunMode {
 }
 
 pub type HookMethod = fn(&mut State) -> bool;
+pub type ChainHookMethod = fn(&mut State) -> HookAction;
+pub type ArgsHookMethod = fn(&mut State, &[Value]) -> bool;
+/// Takes an exit code and returns the condition it must satisfy
+pub type ExitGoal = fn(&Value) -> Value;
+/// Executes an instruction in place of its ESIL. The pc is set past the
+/// instruction before the handler runs, so handlers only set it to jump
+pub type OpcodeHandler = fn(&mut State, &Instruction);
+
+/// The instructions an `OpcodeHandler` executes
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpcodeMatch {
+    /// Instructions with the mnemonic, eg. a vendor specific op
+    Mnemonic(String),
+    /// Instructions whose bytes match the pattern of up to 16 bytes under the
+    /// mask, the instruction is the length of the pattern even if it can't
+    /// be disassembled
+    Bytes(Vec<u8>, Vec<u8>),
+}
+
+impl OpcodeMatch {
+    /// Get the size of the instruction if it matches, `bytes` are the
+    /// bytes in memory at the instruction
+    pub fn matches(&self, instr: &Instruction, bytes: &[u8]) -> Option<u64> {
+        match self {
+            OpcodeMatch::Mnemonic(mnemonic) => {
+                let m = instr.opcode.split_whitespace().next().unwrap_or_default();
+                Some(instr.size).filter(|_| m == mnemonic)
+            }
+            OpcodeMatch::Bytes(pattern, mask) => {
+                let matched = pattern.len() <= bytes.len()
+                    && pattern.iter().enumerate().all(|(i, p)| {
+                        let m = mask.get(i).copied().unwrap_or(0xff);
+                        bytes[i] & m == p & m
+                    });
+                Some(pattern.len() as u64).filter(|_| matched)
+            }
+        }
+    }
+}
+
+/// A block of self-modifying code disassembled from memory with its parsed
+/// ESIL, reused while memory holds the same bytes at its address
+#[derive(Debug, Clone)]
+pub struct DecodedBlock {
+    pub bytes: Vec<u8>,
+    pub instructions: Vec<(Instruction, Vec<Word>)>,
+}
+
+/// What a chained hook does with the rest of the hook chain
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookAction {
+    /// Run the next hooks and then the instruction or sim
+    Continue,
+    /// Run the next hooks but skip the instruction, a sim still runs
+    Skip,
+    /// Run the next hooks but skip the instruction or sim, returning
+    /// to the caller of a simmed function
+    Replace,
+    /// Skip the next hooks and the instruction or sim
+    Return,
+    /// Stop executing the state
+    Terminate,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum HookCallback {
+    /// Returns false to skip the instruction or sim
+    Simple(HookMethod),
+    Chained(ChainHookMethod),
+    /// Gets the args of the hooked function, returns false to skip
+    Args(ArgsHookMethod),
+}
+
+/// Where an arg of a hooked function is on entry
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgLocation {
+    Register(String),
+    /// Offset from the stack pointer
+    Stack(u64),
+}
+
+impl ArgLocation {
+    pub fn read(&self, state: &mut State) -> Value {
+        match self {
+            ArgLocation::Register(reg) => state.registers.get_with_alias(reg),
+            ArgLocation::Stack(offset) => {
+                let addr = state.registers.get_with_alias("SP") + vc(*offset);
+                let length = state.memory.bits as usize / 8;
+                state.memory_read_value(&addr, length)
+            }
+        }
+    }
+
+    pub fn write(&self, state: &mut State, value: &Value) {
+        match self {
+            ArgLocation::Register(reg) => state.registers.set_with_alias(reg, value.to_owned()),
+            ArgLocation::Stack(offset) => {
+                let addr = state.registers.get_with_alias("SP") + vc(*offset);
+                let length = state.memory.bits as usize / 8;
+                state.memory_write_value(&addr, value, length)
+            }
+        }
+    }
+}
+
+/// Restricts a hook to calls made from a function, by the code range
+/// [start, end) of the function and the return addresses on the call stack
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CallContext {
+    /// The current function was called directly from the range
+    Caller(u64, u64),
+    /// Any function on the call stack was called from the range
+    Stack(u64, u64),
+}
+
+impl CallContext {
+    /// Check if the call stack of the state matches the context
+    pub fn matches(&self, state: &State) -> bool {
+        match *self {
+            CallContext::Caller(start, end) => state
+                .backtrace
+                .last()
+                .map(|(_, ret)| *ret >= start && *ret < end)
+                .unwrap_or(false),
+            CallContext::Stack(start, end) => state
+                .backtrace
+                .iter()
+                .any(|(_, ret)| *ret >= start && *ret < end),
+        }
+    }
+}
+
+/// A hook on an address, hooks run from the highest priority to the
+/// lowest and then in the order they were added. A sim at the address
+/// runs at the end of the chain, in place of the instruction
+///
+/// ## Example
+/// ```
+/// use radius2::{Radius, State};
+/// use radius2::processor::Hook;
+/// let mut radius = Radius::new("/bin/sh");
+///
+/// fn large(state: &mut State) -> bool {
+///     let len = state.get_args()[2].to_owned();
+///     state.check(&len.ugt(&radius2::vc(0x100)))
+/// }
+/// fn callback(state: &mut State) -> bool {
+///     println!("third large memcpy");
+///     true
+/// }
+/// // fire once on the third call to memcpy with len > 0x100
+/// let memcpy = radius.get_address("sym.imp.memcpy").unwrap();
+/// radius.add_hook(memcpy, Hook::new(callback).when(large).after(2).times(1));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Hook {
+    pub priority: i32,
+    pub callback: HookCallback,
+    /// Only fire if this holds for the state
+    pub guard: Option<HookMethod>,
+    /// Number of guarded hits to let pass before firing
+    pub after: u64,
+    /// Stop firing after this many times
+    pub times: Option<u64>,
+    /// Only fire when called from this context
+    pub context: Option<CallContext>,
+    /// Identifies the hit counter of the hook in each state
+    pub id: usize,
+    /// Where the args of an `Args` callback are read from
+    pub args: Vec<ArgLocation>,
+}
+
+impl Hook {
+    pub fn new(callback: HookMethod) -> Self {
+        Hook {
+            priority: 0,
+            callback: HookCallback::Simple(callback),
+            guard: None,
+            after: 0,
+            times: None,
+            context: None,
+            id: 0,
+            args: vec![],
+        }
+    }
+
+    pub fn chained(priority: i32, callback: ChainHookMethod) -> Self {
+        Hook {
+            priority,
+            callback: HookCallback::Chained(callback),
+            ..Hook::new(|_| true)
+        }
+    }
+
+    /// A hook that gets the args of the function at the hooked address,
+    /// `Radius::add_hook` recovers where they are from the function info
+    pub fn with_args(callback: ArgsHookMethod) -> Self {
+        Hook {
+            callback: HookCallback::Args(callback),
+            ..Hook::new(|_| true)
+        }
+    }
+
+    /// Only fire the hook when `guard` returns true
+    pub fn when(mut self, guard: HookMethod) -> Self {
+        self.guard = Some(guard);
+        self
+    }
+
+    /// Let the first `n` hits pass without firing
+    pub fn after(mut self, n: u64) -> Self {
+        self.after = n;
+        self
+    }
+
+    /// Remove the hook after it has fired `n` times
+    pub fn times(mut self, n: u64) -> Self {
+        self.times = Some(n);
+        self
+    }
+
+    /// Only fire when the current function was called from the
+    /// function with the code range [start, end)
+    pub fn called_from(mut self, start: u64, end: u64) -> Self {
+        self.context = Some(CallContext::Caller(start, end));
+        self
+    }
+
+    /// Only fire when the function with the code range [start, end)
+    /// is anywhere on the call stack
+    pub fn called_within(mut self, start: u64, end: u64) -> Self {
+        self.context = Some(CallContext::Stack(start, end));
+        self
+    }
+
+    /// Set the priority of the hook in the chain
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    // check the guard and count the hit, hits are counted per state
+    fn fires(&self, state: &mut State) -> bool {
+        if let Some(context) = &self.context {
+            if !context.matches(state) {
+                return false;
+            }
+        }
+        if self.guard.is_none() && self.after == 0 && self.times.is_none() {
+            return true;
+        }
+        if let Some(guard) = self.guard {
+            if !guard(state) {
+                return false;
+            }
+        }
+
+        let key = format!("hook{}", self.id);
+        let hits = state
+            .context
+            .get(&key)
+            .and_then(|h| h[0].as_u64())
+            .unwrap_or(0);
+        state.context.insert(key, vec![vc(hits + 1)]);
+
+        hits >= self.after && self.times.map(|t| hits < self.after + t) != Some(false)
+    }
+
+    pub fn call(&self, state: &mut State) -> HookAction {
+        if !self.fires(state) {
+            return HookAction::Continue;
+        }
+        match self.callback {
+            HookCallback::Simple(callback) if callback(state) => HookAction::Continue,
+            HookCallback::Simple(_) => HookAction::Skip,
+            HookCallback::Chained(callback) => callback(state),
+            HookCallback::Args(callback) => {
+                let args: Vec<Value> = self.args.iter().map(|a| a.read(state)).collect();
+                if callback(state, &args) {
+                    HookAction::Continue
+                } else {
+                    HookAction::Skip
+                }
+            }
+        }
+    }
+}
+
+// keep the target of a two way branch while its symbolic condition only
+// ever allowed one, and forget it once both were taken
+fn record_branch(branches: &mut BTreeMap<u64, Option<u64>>, addr: u64, targets: &[u64]) {
+    let target = match targets {
+        [target] => Some(*target),
+        _ => None,
+    };
+    let entry = branches.entry(addr).or_insert(target);
+    if *entry != target {
+        *entry = None;
+    }
+}
+
+// add a hook after the hooks with the same or higher priority
+fn insert_hook(hooks: &mut Vec<Hook>, hook: Hook) {
+    let index = hooks
+        .iter()
+        .position(|h| h.priority < hook.priority)
+        .unwrap_or(hooks.len());
+    hooks.insert(index, hook);
+}
+
+/// How to model instructions that read timers, CPU info, or entropy
+/// (rdtsc, cpuid, rdrand) instead of using their lifted ESIL
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstructionPolicy {
+    /// Always produce this value, cpuid returns a bare metal Intel CPU
+    Fixed(u64),
+    /// Start at the first value and increase by the second each execution
+    Monotonic(u64, u64),
+    /// Produce a fresh symbol each execution
+    Symbolic,
+}
 
 #[derive(Clone)]
 pub struct Processor {
     pub instructions: BTreeMap<u64, InstructionEntry>,
-    pub hooks: HashMap<u64, Vec<HookMethod>>,
+    pub hooks: HashMap<u64, Vec<Hook>>,
+    /// Hooks run before every instruction
+    pub step_hooks: Vec<Hook>,
+    pub hook_count: usize, // ids for hook hit counters
+    pub instruction_hooks: HashMap<String, Vec<Hook>>,
+    pub policies: HashMap<String, InstructionPolicy>,
+    /// ESIL replacing the lifting of instructions with the mnemonic
+    pub esil_overrides: HashMap<String, String>,
     pub esil_hooks: HashMap<u64, Vec<String>>,
     pub sims: HashMap<u64, Sim>,
+    pub skipped: HashMap<u64, u64>, // return values of the calls skipped at each address
+    pub api_hashes: HashMap<u64, HashMap<u64, u64>>, // api addresses by name hash, for each resolver
     pub traps: HashMap<u64, SimMethod>,
     pub interrupts: HashMap<u64, SimMethod>,
     pub syscalls: HashMap<u64, Syscall>,
     pub breakpoints: HashSet<u64>,
     pub mergepoints: HashSet<u64>,
+    pub merge_calls: HashSet<u64>, // functions whose returns are merged
+    pub merge_leaf_calls: bool,
     pub avoidpoints: HashSet<u64>,
     pub visited: HashSet<u64>,
     pub merges: HashMap<u64, State>,
+    pub arch: Arch, // syscall conventions, thumb mode, and delay slots of the target
     pub crashes: Vec<State>,
+    pub check_pc_control: bool, // stop states whose pc the input controls
+    pub unconstrained: Vec<State>, // states stopped by check_pc_control
+    pub exit_goal: Option<ExitGoal>, // states exiting with a code satisfying this are found
+    pub return_goal: Option<ExitGoal>, // states returning a value satisfying this are found
+    pub contracts: HashMap<u64, Contract>,
+    pub loops: HashMap<u64, LoopSummary>, // loop heads whose iterations are summarized
+    pub lifted: Option<BTreeMap<u64, Vec<Instruction>>>, // r2 disassembly by fetch address, reused
+    pub branches: Option<BTreeMap<u64, Option<u64>>>, // the only target of one way symbolic branches
+    pub opcode_handlers: Vec<(OpcodeMatch, OpcodeHandler)>,
+    pub handled: HashMap<u64, (OpcodeHandler, u64)>, // handlers and sizes of fetched instructions
+    pub explosion_threshold: Option<usize>,          // report the fork sites above this many states
+    pub explosion: Option<ExplosionReport>,
+    pub forks: ForkStats,
+    pub violations: Violations, // returns that can violate a contract
+    pub checkpoint_coverage: HashMap<u64, HashSet<u64>>, // addresses executed after each checkpoint
+    pub vm: Option<VmTracer>,   // records the handlers of a vm dispatcher
+    pub invariants: Vec<Invariant>,
+    pub invariant_violations: Vec<InvariantViolation>,
+    pub assertions: HashMap<u64, Vec<Assertion>>, // properties checked when reaching each address
+    pub counterexamples: Vec<Counterexample>,
+    pub stop_at_counterexample: bool, // end the run at the first violated assertion
+    pub strategy: Strategy,           // order states are stepped in
+    pub exceptions: bool,             // dispatch exceptions to handlers
     pub selfmodify: bool,
+    pub modified_code: HashSet<u64>, // instructions decoded from written memory
+    pub decoded: HashMap<u64, Vec<DecodedBlock>>, // versions of the written code at each block
+    pub fast_forward: bool,          // run concrete code natively with unicorn
+    pub native_retry: u64,           // step to try running natively again at
     pub optimized: bool,
     pub debug: bool,
     pub lazy: bool,
     pub force: bool,
     pub color: bool,
-    pub topological: bool, // execute blocks in topological sort order
-    pub steps: u64,        // number of state steps
+    pub topological: bool,         // execute blocks in topological sort order
+    pub steps: u64,                // number of state steps
+    pub profile: Option<Profile>,  // time spent per instruction
+    pub timeout: Option<Duration>, // stop runs that take longer than this
+    pub max_steps: Option<u64>,    // stop runs that step more than this
+    pub max_memory: Option<u64>,   // stop runs once the process uses more bytes than this
+    pub budget_stop: Option<StopReason>, // the budget that stopped the last run
+    pub survivors: Vec<State>,     // states left when a budget stopped the last run
+    pub ended: Option<Vec<State>>, // collects the states that end, for a StateManager
+    pub unexplored: Option<Vec<State>>, // collects the states left when a run finds one
+    pub reachability: Option<Reachability>, // drop states that can't reach the targets
+    pub goals: Option<Goals>,      // targets retired as states satisfy them
+    pub max_states: Option<usize>, // shrink the frontier past this many states
+    pub loop_bounds: Option<LoopBounds>, // bounds the iterations of loops in the cfg
+    pub deferred: Vec<State>,      // states that went past the loop bound
+    pub gc_policy: GcPolicy,
+    pub on_drop: Vec<DropCallback>, // called with the states dropped to shrink
+    pub corpus: Option<Corpus>,     // writes the inputs of new blocks and ended paths
+    pub bus: EventBus,              // sends run events to subscribers
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -76,6 +561,8 @@ pub enum InstructionFlag {
     Merge,
     Avoid,
     Break,
+    Policy,
+    Handler,
 }
 
 #[derive(Debug, Clone)]
@@ -98,18 +585,55 @@ impl Processor {
         Processor {
             instructions: BTreeMap::new(),
             hooks: HashMap::new(),
+            step_hooks: vec![],
+            hook_count: 0,
+            instruction_hooks: HashMap::new(),
+            policies: HashMap::new(),
+            esil_overrides: HashMap::new(),
             esil_hooks: HashMap::new(),
             sims: HashMap::new(),
+            skipped: HashMap::new(),
+            api_hashes: HashMap::new(),
             traps: HashMap::new(),
             interrupts: HashMap::new(),
             syscalls: HashMap::new(),
             breakpoints: HashSet::new(),
             mergepoints: HashSet::new(),
+            merge_calls: HashSet::new(),
+            merge_leaf_calls: false,
             avoidpoints: HashSet::new(),
             visited: HashSet::new(),
             merges: HashMap::new(),
+            arch: Arch::default(),
             crashes: vec![],
+            check_pc_control: false,
+            unconstrained: vec![],
+            exit_goal: None,
+            return_goal: None,
+            contracts: HashMap::new(),
+            loops: HashMap::new(),
+            lifted: None,
+            branches: None,
+            opcode_handlers: vec![],
+            handled: HashMap::new(),
+            explosion_threshold: None,
+            explosion: None,
+            forks: ForkStats::default(),
+            violations: Rc::new(RefCell::new(vec![])),
+            checkpoint_coverage: HashMap::new(),
+            vm: None,
+            invariants: vec![],
+            invariant_violations: vec![],
+            assertions: HashMap::new(),
+            counterexamples: vec![],
+            stop_at_counterexample: false,
+            strategy: Strategy::default(),
+            exceptions: false,
             selfmodify,
+            modified_code: HashSet::new(),
+            decoded: HashMap::new(),
+            fast_forward: false,
+            native_retry: 0,
             optimized,
             debug,
             lazy,
@@ -117,9 +641,67 @@ impl Processor {
             topological,
             color,
             steps: 0, //states: vec!()
+            profile: None,
+            timeout: None,
+            max_steps: None,
+            max_memory: None,
+            budget_stop: None,
+            survivors: vec![],
+            ended: None,
+            unexplored: None,
+            reachability: None,
+            goals: None,
+            max_states: None,
+            loop_bounds: None,
+            deferred: vec![],
+            gc_policy: GcPolicy::default(),
+            on_drop: vec![],
+            corpus: None,
+            bus: EventBus::default(),
+        }
+    }
+
+    /// Add a hook to the chain at `addr` by its priority
+    pub fn add_hook(&mut self, addr: u64, mut hook: Hook) {
+        hook.id = self.hook_count;
+        self.hook_count += 1;
+        insert_hook(self.hooks.entry(addr).or_default(), hook);
+        // flags of instructions already fetched need to be updated
+        if let Some(entry) = self.instructions.get_mut(&addr) {
+            entry.flags.insert(InstructionFlag::Hook);
         }
     }
 
+    /// Add a hook that runs before every instruction
+    pub fn add_step_hook(&mut self, mut hook: Hook) {
+        hook.id = self.hook_count;
+        self.hook_count += 1;
+        insert_hook(&mut self.step_hooks, hook);
+    }
+
+    /// Add a hook to every instruction with `mnemonic`, the hits of
+    /// all these instructions are counted together
+    pub fn add_instruction_hook(&mut self, mnemonic: &str, mut hook: Hook) {
+        hook.id = self.hook_count;
+        self.hook_count += 1;
+        let hooks = self
+            .instruction_hooks
+            .entry(mnemonic.to_owned())
+            .or_default();
+        insert_hook(hooks, hook);
+    }
+
+    /// Lift instructions with `mnemonic` to the `esil` instead, dropping
+    /// those that were already lifted. `{0}`, `{1}`, ... are replaced
+    /// with the operands of the instruction as they are disassembled
+    pub fn override_esil(&mut self, mnemonic: &str, esil: &str) {
+        self.esil_overrides
+            .insert(mnemonic.to_owned(), esil.to_owned());
+        self.instructions.retain(|_, entry| {
+            entry.instruction.opcode.split_whitespace().next() != Some(mnemonic)
+        });
+    }
+
     pub fn tokenize(&self, state: &mut State, esil: &str) -> Vec<Word> {
         let mut tokens: Vec<Word> = Vec::with_capacity(128);
         let split_esil = esil.split(',');
@@ -234,15 +816,23 @@ impl Processor {
         let sys_num = state.solver.evalcon_to_u64(&sys_val).unwrap();
         //let pc = state.registers.get_pc().as_u64().unwrap();
 
-        if let Some(sys) = self.syscalls.get(&sys_num) {
-            let cc = state.r2api.get_syscall_cc().unwrap();
-            let mut args = Vec::with_capacity(8);
-            for arg in cc.args {
-                args.push(state.registers.get(arg.as_str()));
+        // without a convention the args are unknown, so fail the syscall
+        // like one the kernel does not have and keep going
+        let cc = match state.r2api.get_syscall_cc() {
+            Ok(cc) => cc,
+            Err(_) => {
+                let ret = syscall("nosys", state, &[]);
+                state.registers.set_with_alias("R0", ret);
+                return;
             }
-            let ret = syscall(sys.name.as_str(), state, &args);
-            state.registers.set(cc.ret.as_str(), ret);
-        }
+        };
+        // numbers missing from the syscall table fail like the kernel does
+        let name = self.syscalls.get(&sys_num).map(|s| s.name.as_str());
+        let name = name.unwrap_or("nosys");
+
+        let args = self.arch.syscall_args(state, &cc);
+        let ret = syscall(name, state, &args);
+        self.arch.set_syscall_return(state, &cc, ret);
     }
 
     // for one-off parsing of strings
@@ -289,6 +879,10 @@ impl Processor {
                         Operations::If => {
                             let arg1 = pop_value(state, false, false);
 
+                            if state.taint_log.is_some() {
+                                state.log_tainted_branch(arg1.get_taint());
+                            }
+
                             match (arg1, &state.esil.mode) {
                                 (Value::Concrete(val1, _t), ExecMode::Uncon) => {
                                     state.esil.mode = if val1 == 0 {
@@ -301,7 +895,7 @@ impl Processor {
                                     //println!("if {:?}", val1);
                                     state.esil.mode = ExecMode::If;
                                     state.esil.temp1 = state.stack.to_owned();
-                                    let cond_bv = val1._eq(&state.bvv(0, val1.get_width())).not();
+                                    let cond_bv = interval::nonzero(&val1);
 
                                     state.condition = Some(cond_bv);
                                 }
@@ -315,7 +909,8 @@ impl Processor {
                             ExecMode::NoExec => state.esil.mode = ExecMode::Exec,
                             ExecMode::If => {
                                 state.esil.mode = ExecMode::Else;
-                                state.condition = Some(state.condition.as_ref().unwrap().not());
+                                state.condition =
+                                    Some(interval::not(state.condition.as_ref().unwrap()));
                                 state.esil.temp2 = mem::take(&mut state.stack);
                                 state.stack = mem::take(&mut state.esil.temp1);
                             }
@@ -363,7 +958,7 @@ impl Processor {
                         Operations::GoTo => {
                             let n = pop_concrete(state, false, false);
                             if let Some(cond) = &state.condition.clone() {
-                                if !state.check(&Value::Symbolic(cond.not(), 0)) {
+                                if !state.check(&Value::Symbolic(interval::not(cond), 0)) {
                                     state.esil.mode = ExecMode::Uncon;
                                     word_index = n as usize;
                                 } else {
@@ -376,7 +971,7 @@ impl Processor {
                         }
                         Operations::Break => {
                             if let Some(cond) = &state.condition.clone() {
-                                if !state.check(&Value::Symbolic(cond.not(), 0)) {
+                                if !state.check(&Value::Symbolic(interval::not(cond), 0)) {
                                     state.esil.mode = ExecMode::Uncon;
                                     break;
                                 } else {
@@ -388,7 +983,15 @@ impl Processor {
                             }
                         }
                         Operations::Interrupt => {
-                            let _value = pop_value(state, false, false);
+                            let value = pop_value(state, false, false);
+
+                            // int3 and int 0x2d raise breakpoint exceptions
+                            let int = value.as_u64().unwrap_or_default();
+                            if self.exceptions && (int == 3 || int == 0x2d) {
+                                let pc = state.esil.prev_pc.as_u64().unwrap_or_default();
+                                exception::raise(state, BREAKPOINT, &[0], pc, 'x');
+                                break;
+                            }
                         }
                         Operations::Trap => {
                             let trap = pop_concrete(state, false, false);
@@ -404,6 +1007,12 @@ impl Processor {
                                 }
                                 let ret = trap_sim(state, &args);
                                 state.registers.set(cc.ret.as_str(), ret);
+                            } else if let Some(code) = exception::trap_code(trap) {
+                                if self.exceptions {
+                                    let pc = state.esil.prev_pc.as_u64().unwrap_or_default();
+                                    exception::raise(state, code, &[], pc, 'x');
+                                    break;
+                                }
                             }
                         }
                         Operations::Syscall => self.do_syscall(state),
@@ -506,26 +1115,9 @@ impl Processor {
     }
 
     pub fn get_args(&self, state: &mut State, cc: &CallingConvention) -> Vec<Value> {
-        let mut args = Vec::with_capacity(16);
-
-        if !cc.args.is_empty() {
-            for arg in &cc.args {
-                args.push(state.registers.get_with_alias(arg));
-            }
-        } else {
-            // read args from stack?
-            let mut sp = state.registers.get_with_alias("SP");
-            let length = state.memory.bits as usize / 8;
-
-            for _ in 0..8 {
-                // do 8 idk?
-                sp = sp + Value::Concrete(length as u64, 0);
-                let value = state.memory_read_value(&sp, length);
-                args.push(value);
-            }
-        }
-
-        args
+        // read 8 args from the stack if there are no register args
+        let count = if cc.args.is_empty() { 8 } else { cc.args.len() };
+        state.varargs(cc, 0).take(count).collect()
     }
 
     /**
@@ -545,8 +1137,28 @@ impl Processor {
         }
 
         let pc = instr.offset;
+
+        if let Some(summary) = self.loops.get(&pc) {
+            summary.apply(state);
+            return;
+        }
+
+        // keep the old registers to record what the instruction changes
+        let registers = if let Some(recording) = &mut state.recording {
+            recording.begin(pc);
+            Some(state.registers.values.clone())
+        } else {
+            None
+        };
+
         state.esil.prev_pc = vc(pc);
-        let new_pc = instr.offset.wrapping_add(instr.size);
+        // branches with a delay slot continue after the slot
+        let size = if self.arch.has_delay_slot(instr) {
+            2 * instr.size
+        } else {
+            instr.size
+        };
+        let new_pc = instr.offset.wrapping_add(size);
 
         state.esil.pcs.clear();
         if instr.jump != 0 {
@@ -568,17 +1180,45 @@ impl Processor {
             state.backtrace.push((instr.jump as u64, new_pc));
         }
 
+        if let Some(contract) = self.contracts.get(&pc) {
+            contract.enter(state);
+        }
+
         // skip executing this instruction
         let mut skip = false;
         let mut update = true;
-        if !new_flags.is_empty() {
-            if new_flags.contains(&InstructionFlag::Hook) {
-                let hooks = &self.hooks[&pc];
-                for hook in hooks {
-                    skip = !hook(state) || skip;
+        // a hook took the place of the sim
+        let mut replaced = false;
+        // a hook skipped the rest of the chain or stopped the state
+        let mut returned = false;
+        let mut terminated = false;
+        if !new_flags.is_empty() || !self.step_hooks.is_empty() {
+            if new_flags.contains(&InstructionFlag::Hook) || !self.step_hooks.is_empty() {
+                let hooks = self.hooks.get(&pc).into_iter().flatten();
+                for hook in self.step_hooks.iter().chain(hooks) {
+                    match hook.call(state) {
+                        HookAction::Continue => {}
+                        HookAction::Skip => skip = true,
+                        HookAction::Replace => {
+                            skip = true;
+                            replaced = true;
+                        }
+                        HookAction::Return => returned = true,
+                        HookAction::Terminate => {
+                            state.status = StateStatus::Inactive;
+                            returned = true;
+                            terminated = true;
+                            update = false;
+                        }
+                    }
+                    if returned {
+                        skip = true;
+                        replaced = true;
+                        break;
+                    }
                 }
             }
-            if new_flags.contains(&InstructionFlag::ESILHook) {
+            if !returned && new_flags.contains(&InstructionFlag::ESILHook) {
                 let esils = &self.esil_hooks[&pc];
                 for esil in esils {
                     self.parse_expression(state, esil);
@@ -589,12 +1229,29 @@ impl Processor {
             if state.registers.get_pc() != vc(pc) {
                 update = false; // hook changed pc dont update
             }
-            if new_flags.contains(&InstructionFlag::Sim) {
+            if new_flags.contains(&InstructionFlag::Sim) && replaced {
+                // a hook replaced the sim, return to the caller
+                if update {
+                    self.ret(state);
+                }
+                update = false;
+            } else if new_flags.contains(&InstructionFlag::Sim) {
                 let sim = &self.sims[&pc];
                 let cc = state.r2api.get_cc(pc).unwrap_or_default();
-                let args = self.get_args(state, &cc);
-
-                let ret = (sim.function)(state, &args);
+                let ret = if let Some(ret) = self.skipped.get(&pc) {
+                    vc(*ret)
+                } else if let Some(hashes) = self.api_hashes.get(&pc) {
+                    let args = self.get_args(state, &cc);
+                    let hash = args.first().and_then(|h| state.solver.evalcon_to_u64(h));
+                    let hash = hash.unwrap_or_default() & 0xffffffff;
+                    vc(hashes.get(&hash).copied().unwrap_or_default())
+                } else {
+                    let args = self.get_args(state, &cc);
+                    state.sim_cc = Some(cc.clone());
+                    let ret = (sim.function)(state, &args);
+                    state.sim_cc = None;
+                    ret
+                };
                 state.registers.set_with_alias(cc.ret.as_str(), ret);
 
                 // don't ret if sim changes the PC value
@@ -605,12 +1262,23 @@ impl Processor {
                 skip = true;
                 update = false;
             }
-            if new_flags.contains(&InstructionFlag::Break) {
+            if !skip && new_flags.contains(&InstructionFlag::Policy) {
+                self.apply_policy(state, instr);
+                skip = true;
+            }
+            if !skip && new_flags.contains(&InstructionFlag::Handler) {
+                let (handler, size) = self.handled[&pc];
+                state.registers.set_pc(vc(pc.wrapping_add(size)));
+                handler(state, instr);
+                skip = true;
+                update = false;
+            }
+            if !terminated && new_flags.contains(&InstructionFlag::Break) {
                 state.status = StateStatus::Break;
                 skip = true;
                 update = false;
             }
-            if new_flags.contains(&InstructionFlag::Merge) {
+            if !terminated && new_flags.contains(&InstructionFlag::Merge) {
                 state.status = StateStatus::Merge;
                 skip = true;
                 update = false;
@@ -637,7 +1305,16 @@ impl Processor {
                 if let Value::Concrete(v, _) = state.registers.get_pc() {
                     if !state.memory.check_permission(v, 1, 'x') {
                         // if it looks invalid
-                        if !self.breakpoints.is_empty() || !self.esil_hooks.is_empty() {
+                        if let Some(goal) = self.return_goal {
+                            // returned from the called function
+                            let condition = goal(&state.registers.get_with_alias("R0"));
+                            if state.check(&condition) {
+                                state.assert(&condition);
+                                state.status = StateStatus::Break;
+                            } else {
+                                state.status = StateStatus::Inactive;
+                            }
+                        } else if !self.breakpoints.is_empty() || !self.esil_hooks.is_empty() {
                             state.status = StateStatus::Inactive;
                         } else {
                             // break if there are no other breakpoints/hooks
@@ -648,6 +1325,68 @@ impl Processor {
             } else {
                 state.backtrace.pop();
             }
+            self.check_main_return(state, pc);
+            if !self.contracts.is_empty() {
+                contract::check_return(state, &self.contracts, &self.violations);
+            }
+        }
+
+        if let Some(old) = registers {
+            state.record_registers(&old);
+        }
+    }
+
+    // returning from main exits the process with the returned value
+    fn check_main_return(&self, state: &mut State, pc: u64) {
+        let main_return = state.context.get("main_return").map(|r| r[0].to_owned());
+        if main_return == Some(state.registers.get_pc()) {
+            let cc = state.r2api.get_cc(pc).unwrap_or_default();
+            let code = state.registers.get_with_alias(&cc.ret);
+            state.exit(&code);
+        }
+    }
+
+    // set the outputs of an instruction according to its policy
+    fn apply_policy(&self, state: &mut State, instr: &Instruction) {
+        let mut operands = instr.opcode.split_whitespace();
+        let mnemonic = operands.next().unwrap_or_default();
+        let policy = self.policies[mnemonic];
+
+        match mnemonic {
+            "rdtsc" | "rdtscp" => {
+                let count = policy_value(state, "rdtsc", policy, 64);
+                state.registers.set("eax", count.slice(31, 0));
+                state.registers.set("edx", count.slice(63, 32));
+                if mnemonic == "rdtscp" {
+                    state.registers.set("ecx", vc(0));
+                }
+            }
+            "rdrand" | "rdseed" => {
+                let dst = operands.next().unwrap_or_default();
+                if let Some(reg) = state.registers.get_register(dst) {
+                    let bits = reg.reg_info.size as u32;
+                    let value = policy_value(state, mnemonic, policy, bits);
+                    state.registers.set(dst, value);
+                    state.registers.set("cf", vc(1));
+                }
+            }
+            "cpuid" => {
+                let outputs = ["eax", "ebx", "ecx", "edx"];
+                if policy == InstructionPolicy::Symbolic {
+                    for reg in outputs {
+                        let value = state.new_symbol("cpuid", 32);
+                        state.registers.set(reg, value);
+                    }
+                } else {
+                    let leaf = state.registers.get("eax");
+                    let leaf = state.solver.evalcon_to_u64(&leaf).unwrap_or_default();
+                    let values = cpuid_values(leaf & 0xffffffff);
+                    for (reg, value) in outputs.iter().zip(values) {
+                        state.registers.set(reg, vc(value));
+                    }
+                }
+            }
+            _ => {}
         }
     }
 
@@ -666,11 +1405,14 @@ impl Processor {
     // and optimize if enabled. TODO this has become so convoluted, fix it
     pub fn fetch_instruction(&mut self, state: &mut State, pc_val: u64) {
         let has_instr = self.instructions.contains_key(&pc_val);
-        if self.selfmodify || !has_instr {
+        // code written by any state is decoded from the memory of this one
+        let from_memory = self.selfmodify
+            || self.modified_code.contains(&pc_val)
+            || state.memory.code_written(pc_val, MAX_OPCODE_LEN as u64);
+        if from_memory || !has_instr {
             let mut pc_tmp = pc_val;
-            let instrs = if self.selfmodify {
-                let data = state.memory_read_bytes(pc_val, 32);
-                // 1 at a time for selfmodify
+            let instrs = if from_memory {
+                let data = state.memory_read_bytes(pc_val, SELFMODIFY_BYTES);
                 // check to see if bytes changed
                 if has_instr {
                     let instr = &self.instructions[&pc_val];
@@ -681,24 +1423,56 @@ impl Processor {
                         return;
                     }
                 }
-                state
-                    .r2api
-                    .disassemble_bytes(pc_val, &data, 1)
-                    .unwrap_or_default()
+                self.decode_block(state, pc_val, &data)
+            } else if let Some(block) = self.lifted.as_ref().and_then(|l| l.get(&pc_val)) {
+                let block = block.to_owned();
+                self.parse_block(state, block)
             } else {
-                state
-                    .r2api
-                    .disassemble(pc_val, INSTR_NUM)
-                    .unwrap_or_default()
+                let block = if self.arch.kind == ArchKind::Arm {
+                    let bits = if self.arch.is_thumb(state) { 16 } else { 32 };
+                    state
+                        .r2api
+                        .disassemble_bits(pc_val, INSTR_NUM, bits)
+                        .unwrap_or_default()
+                } else {
+                    state
+                        .r2api
+                        .disassemble(pc_val, INSTR_NUM)
+                        .unwrap_or_default()
+                };
+                if let Some(lifted) = &mut self.lifted {
+                    lifted.insert(pc_val, block.clone());
+                }
+                self.parse_block(state, block)
             };
 
             let mut prev: Option<u64> = None;
-            for instr in instrs {
+            for (instr, words) in instrs {
                 let size = instr.size;
-                let words = self.tokenize(state, &instr.esil);
+                let mnemonic = instr.opcode.split_whitespace().next().unwrap_or_default();
+
+                // merge the states returning from the call before continuing
+                if instr.type_num == CALL_TYPE && self.is_merged_call(state, instr.jump as u64) {
+                    let ret = pc_tmp.wrapping_add(size);
+                    self.mergepoints.insert(ret);
+                    if let Some(entry) = self.instructions.get_mut(&ret) {
+                        entry.flags.insert(InstructionFlag::Merge);
+                    }
+                }
+
+                // hook every instruction with a hooked mnemonic, once
+                // even if it is fetched again
+                if let Some(hooks) = self.instruction_hooks.get(mnemonic) {
+                    let addr_hooks = self.hooks.entry(pc_tmp).or_default();
+                    for hook in hooks {
+                        if !addr_hooks.iter().any(|h| h.id == hook.id) {
+                            insert_hook(addr_hooks, hook.clone());
+                        }
+                    }
+                }
 
                 let mut flags = HashSet::new();
-                let mut opt = self.optimized && !self.selfmodify;
+                let mut opt = self.optimized && !from_memory;
                 if self.hooks.contains_key(&pc_tmp) {
                     flags.insert(InstructionFlag::Hook);
                 }
@@ -717,9 +1491,25 @@ impl Processor {
                 if self.sims.contains_key(&pc_tmp) {
                     flags.insert(InstructionFlag::Sim);
                 }
+                if self.policies.contains_key(mnemonic) {
+                    flags.insert(InstructionFlag::Policy);
+                }
+                let handler = if self.opcode_handlers.is_empty() {
+                    None
+                } else {
+                    // r2 gives a single byte for ops it can't disassemble
+                    let bytes = state.memory_read_bytes(pc_tmp, MAX_OPCODE_LEN);
+                    self.opcode_handlers
+                        .iter()
+                        .find_map(|(m, h)| m.matches(&instr, &bytes).map(|size| (*h, size)))
+                };
+                if let Some(handler) = handler {
+                    self.handled.insert(pc_tmp, handler);
+                    flags.insert(InstructionFlag::Handler);
+                }
 
                 // don't optimize if hooked / bp for accuracy
-                if !flags.is_empty() {
+                if !flags.is_empty() || !self.step_hooks.is_empty() {
                     opt = false;
                 }
 
@@ -735,14 +1525,108 @@ impl Processor {
                     }
                     prev = Some(pc_tmp);
                 }
+                if from_memory && !self.selfmodify {
+                    self.modified_code.insert(pc_tmp);
+                }
                 self.instructions.insert(pc_tmp, instr_entry);
                 pc_tmp = pc_tmp.wrapping_add(size);
             }
         }
     }
 
+    // tokenize the esil of the instructions, or of their overrides
+    fn parse_block(
+        &self,
+        state: &mut State,
+        block: Vec<Instruction>,
+    ) -> Vec<(Instruction, Vec<Word>)> {
+        block
+            .into_iter()
+            .map(|mut instr| {
+                let mnemonic = instr.opcode.split_whitespace().next().unwrap_or_default();
+                if let Some(esil) = self.esil_overrides.get(mnemonic) {
+                    instr.esil = override_esil(esil, &instr.opcode);
+                }
+                let words = self.tokenize(state, &instr.esil);
+                (instr, words)
+            })
+            .collect()
+    }
+
+    // disassemble and parse the block of written code at addr, reusing a
+    // version decoded before if memory holds its bytes again. each
+    // instruction is still checked against memory when it is reached
+    fn decode_block(
+        &mut self,
+        state: &mut State,
+        addr: u64,
+        data: &[u8],
+    ) -> Vec<(Instruction, Vec<Word>)> {
+        let versions = self.decoded.entry(addr).or_default();
+        if let Some(block) = versions.iter().find(|b| data.starts_with(&b.bytes)) {
+            return block.instructions.clone();
+        }
+
+        let instrs = state
+            .r2api
+            .disassemble_bytes(addr, data, INSTR_NUM)
+            .unwrap_or_default();
+        let block = block_prefix(addr, instrs, data.len());
+        let length: u64 = block.iter().map(|i| i.size).sum();
+        let instructions = self.parse_block(state, block);
+        if length == 0 {
+            return instructions;
+        }
+
+        // the code at addr was written over, the oldest version goes first
+        let versions = self.decoded.entry(addr).or_default();
+        if versions.len() >= SELFMODIFY_VERSIONS {
+            versions.remove(0);
+        }
+        versions.push(DecodedBlock {
+            bytes: data[..(length as usize).min(data.len())].to_vec(),
+            instructions: instructions.clone(),
+        });
+        instructions
+    }
+
+    // check if returns from the function at addr should be merged, leaf
+    // functions are added once from the analysis if merge_leaf_calls is set
+    fn is_merged_call(&mut self, state: &mut State, addr: u64) -> bool {
+        if self.merge_leaf_calls {
+            self.merge_leaf_calls = false;
+            let functions = state.r2api.get_functions().unwrap_or_default();
+            for function in functions {
+                if function.outdegree == 0 && !function.noreturn {
+                    self.merge_calls.insert(function.offset);
+                }
+            }
+        }
+        self.merge_calls.contains(&addr) && !self.sims.contains_key(&addr)
+    }
+
     pub fn execute_instruction(&mut self, state: &mut State, pc_val: u64) {
-        self.fetch_instruction(state, pc_val);
+        // a branch likely with a symbolic target runs its slot once the
+        // split states know whether they took it
+        if let Some((slot, fallthrough)) = state.esil.likely_slot.take() {
+            if pc_val != fallthrough {
+                self.execute_delay_slot(state, slot);
+                if state.status != StateStatus::Active {
+                    return;
+                }
+            }
+        }
+
+        if self.profile.is_some() {
+            let start = Instant::now();
+            self.fetch_instruction(state, pc_val);
+            let lift = start.elapsed();
+            if let Some(profile) = &mut self.profile {
+                profile.add_lift(pc_val, lift);
+            }
+        } else {
+            self.fetch_instruction(state, pc_val);
+        }
 
         // the hash lookup is done twice, needs fixing
         let instr = self.instructions.get(&pc_val).unwrap();
@@ -750,15 +1634,271 @@ impl Processor {
         if self.debug {
             self.print_instr(state, &instr.instruction);
         }
-        if state.strict && instr.instruction.disasm == "invalid" {
+        if state.strict
+            && instr.instruction.disasm == "invalid"
+            && !instr.flags.contains(&InstructionFlag::Handler)
+        {
             panic!("Executed invalid instruction");
         }
 
+        let delayed = self.arch.has_delay_slot(&instr.instruction)
+            && !instr.flags.contains(&InstructionFlag::Sim);
+        self.execute(state, &instr.instruction, &instr.flags, &instr.tokens);
+
+        let instr = &self.instructions[&pc_val];
+        if self.arch.kind == ArchKind::Arm {
+            if let Some(target) = state.registers.get_pc().as_u64() {
+                let next = self.arch.interwork(state, &instr.instruction, target);
+                if next != target {
+                    state.registers.set_pc(vc(next));
+                }
+            }
+        }
+        if delayed && state.status == StateStatus::Active {
+            let slot = pc_val.wrapping_add(instr.instruction.size);
+            if !self.arch.is_branch_likely(&instr.instruction) {
+                self.execute_delay_slot(state, slot);
+            } else {
+                // the slot of a branch likely is annulled if it is not taken
+                let fallthrough = slot.wrapping_add(instr.instruction.size);
+                match state.registers.get_pc().as_u64() {
+                    Some(target) if target == fallthrough => {}
+                    Some(_) => self.execute_delay_slot(state, slot),
+                    None => state.esil.likely_slot = Some((slot, fallthrough)),
+                }
+            }
+        }
+    }
+
+    // execute the instruction in the delay slot of a branch, keeping
+    // the target the branch already chose
+    fn execute_delay_slot(&mut self, state: &mut State, slot: u64) {
+        let target = state.registers.get_pc();
+        let pcs = state.esil.pcs.clone();
+        let prev_pc = state.esil.prev_pc.clone();
+
+        self.fetch_instruction(state, slot);
+        let instr = &self.instructions[&slot];
         self.execute(state, &instr.instruction, &instr.flags, &instr.tokens);
+
+        state.registers.set_pc(target);
+        state.esil.pcs = pcs;
+        state.esil.prev_pc = prev_pc;
+    }
+
+    // nothing that needs every instruction to go through radius is set
+    #[cfg(feature = "unicorn")]
+    fn can_run_natively(&self, state: &State) -> bool {
+        self.arch.kind == ArchKind::X86
+            && self.steps >= self.native_retry
+            && self.step_hooks.is_empty()
+            && self.instruction_hooks.is_empty()
+            && self.esil_overrides.is_empty()
+            && self.opcode_handlers.is_empty()
+            && self.invariants.is_empty()
+            && self.merge_calls.is_empty()
+            && !self.merge_leaf_calls
+            && self.return_goal.is_none()
+            && self.vm.is_none()
+            && self.profile.is_none()
+            && self.loop_bounds.is_none()
+            && self.reachability.is_none()
+            && self.goals.is_none()
+            && !(self.force || self.debug || self.selfmodify)
+            && !state.has_event_hooks
+            && !state.context.contains_key("checkpoints")
+    }
+
+    // whether the address has hooks, sims, or anything else radius handles
+    #[cfg(feature = "unicorn")]
+    fn is_native_stop(&self, addr: u64) -> bool {
+        self.hooks.contains_key(&addr)
+            || self.esil_hooks.contains_key(&addr)
+            || self.sims.contains_key(&addr)
+            || self.skipped.contains_key(&addr)
+            || self.api_hashes.contains_key(&addr)
+            || self.contracts.contains_key(&addr)
+            || self.loops.contains_key(&addr)
+            || self.assertions.contains_key(&addr)
+            || self.breakpoints.contains(&addr)
+            || self.mergepoints.contains(&addr)
+            || self.avoidpoints.contains(&addr)
+    }
+
+    /// Run the state natively with unicorn until it needs radius, eg.
+    /// it reads symbolic memory or reaches a hook. False if not a
+    /// single instruction could be run
+    #[cfg(feature = "unicorn")]
+    pub fn run_natively(&mut self, state: &mut State) -> bool {
+        let pc = state.registers.get_pc().as_u64().unwrap_or_default();
+        if !self.can_run_natively(state) || self.is_native_stop(pc) || !fastforward::can_run(state)
+        {
+            return false;
+        }
+        let stop = |addr| self.is_native_stop(addr);
+        match fastforward::run(state, &stop, NATIVE_STEPS) {
+            Some(executed) => {
+                self.steps += executed.len() as u64 - 1;
+                self.visited.extend(executed);
+                true
+            }
+            None => {
+                self.native_retry = self.steps + NATIVE_RETRY;
+                false
+            }
+        }
+    }
+
+    /// Without the unicorn feature every instruction goes through radius
+    #[cfg(not(feature = "unicorn"))]
+    pub fn run_natively(&mut self, _state: &mut State) -> bool {
+        false
     }
 
     /// Take single step with the state provided
     pub fn step(&mut self, state: &mut State) -> Vec<State> {
+        let pc = state.registers.get_pc().as_u64().unwrap_or_default();
+        let traced = self.bus.wants(EventLevel::Trace);
+        let before = traced.then(|| state.solver.stats.get());
+        let states = if self.profile.is_none() {
+            self.step_state(state)
+        } else {
+            let before = state.solver.stats.get();
+            let start = Instant::now();
+            let states = self.step_state(state);
+            let total = start.elapsed();
+
+            if let Some(profile) = &mut self.profile {
+                profile.add(pc, total, before, state.solver.stats.get());
+            }
+            states
+        };
+        if let Some(before) = before {
+            self.emit_step(state, pc, before);
+        }
+
+        if self.loop_bounds.is_some() {
+            self.bound_loops(state, states, pc)
+        } else {
+            states
+        }
+    }
+
+    // tell the subscribers about the sim at pc, if there is one
+    fn emit_sim(&mut self, pc: u64) {
+        let simulated = self
+            .instructions
+            .get(&pc)
+            .map(|e| e.flags.contains(&InstructionFlag::Sim))
+            .unwrap_or_default();
+        if let Some(sim) = self.sims.get(&pc).filter(|_| simulated) {
+            let name = sim.symbol.to_owned();
+            self.bus.emit(RunEvent::SimCalled { pc, name });
+        }
+    }
+
+    // tell the trace subscribers the instruction and its solver queries
+    fn emit_step(&mut self, state: &State, pc: u64, before: SolverStats) {
+        let after = state.solver.stats.get();
+        self.bus.emit(RunEvent::Executed { pc });
+        if after.queries > before.queries {
+            self.bus.emit(RunEvent::SolverQueries {
+                pc,
+                count: after.queries - before.queries,
+                time: after.time - before.time,
+            });
+        }
+    }
+
+    // defer the states that went past the loop bound instead of stepping them
+    fn bound_loops(&mut self, state: &mut State, states: Vec<State>, pc: u64) -> Vec<State> {
+        let jump = self
+            .instructions
+            .get(&pc)
+            .map(|e| e.instruction.r#type.contains("jmp"))
+            .unwrap_or_default();
+        let bounds = self.loop_bounds.as_mut().unwrap();
+
+        if state.status == StateStatus::Active && bounds.iterate(state, pc, jump) {
+            self.deferred.push(state.to_owned());
+            state.set_inactive();
+        }
+        let mut kept = vec![];
+        for mut new_state in states {
+            if bounds.iterate(&mut new_state, pc, jump) {
+                self.deferred.push(new_state);
+            } else {
+                kept.push(new_state);
+            }
+        }
+        kept
+    }
+
+    /// Print the `n` instructions that took the most time while profiling
+    pub fn print_profile(&self, n: usize) {
+        let profile = if let Some(profile) = &self.profile {
+            profile
+        } else {
+            return;
+        };
+
+        println!(
+            "{:<12} {:>8} {:>10} {:>10} {:>10} {:>10} {:>8}  instruction",
+            "address", "count", "total", "lift", "exec", "solver", "queries"
+        );
+        for (addr, p) in profile.hotspots(n) {
+            let disasm = if let Some(sim) = self.sims.get(&addr) {
+                format!("( simulated {} )", sim.symbol)
+            } else {
+                self.instructions
+                    .get(&addr)
+                    .map(|i| i.instruction.disasm.to_owned())
+                    .unwrap_or_default()
+            };
+            println!(
+                "0x{:010x} {:>8} {:>10} {:>10} {:>10} {:>10} {:>8}  {}",
+                addr,
+                p.count,
+                p.total.as_micros(),
+                p.lift.as_micros(),
+                p.exec().as_micros(),
+                p.solver.as_micros(),
+                p.queries,
+                disasm
+            );
+        }
+        println!(
+            "total profiled time (usec): {}",
+            profile.total().as_micros()
+        );
+    }
+
+    /// Drop the lifted instructions overlapping `length` bytes at `addr`
+    /// so they are fetched again after the code there is patched
+    pub fn invalidate(&mut self, addr: u64, length: u64) {
+        let start = addr.saturating_sub(MAX_OPCODE_LEN as u64);
+        let stale: Vec<u64> = self
+            .instructions
+            .range(start..addr + length)
+            .filter(|(pc, entry)| *pc + entry.instruction.size > addr)
+            .map(|(pc, _)| *pc)
+            .collect();
+
+        for pc in stale {
+            self.instructions.remove(&pc);
+            self.handled.remove(&pc);
+        }
+        if let Some(lifted) = &mut self.lifted {
+            let end = addr.saturating_add(length);
+            lifted.retain(|_, block| {
+                !block
+                    .iter()
+                    .any(|i| i.offset < end && i.offset + i.size > addr)
+            });
+        }
+    }
+
+    fn step_state(&mut self, state: &mut State) -> Vec<State> {
         self.steps += 1;
         state.visit();
 
@@ -766,7 +1906,51 @@ impl Processor {
         let pc_value = state.registers.get_pc();
 
         if let Some(pc_val) = pc_value.as_u64() {
-            self.execute_instruction(state, pc_val);
+            self.visited.insert(pc_val);
+            if let Some(ids) = state.context.get("checkpoints") {
+                for id in ids.iter().filter_map(|id| id.as_u64()) {
+                    if let Some(coverage) = self.checkpoint_coverage.get_mut(&id) {
+                        coverage.insert(pc_val);
+                    }
+                }
+            }
+            state.solver.set_address(pc_val);
+            if let Some(assertions) = self.assertions.get(&pc_val) {
+                for assertion in assertions {
+                    assertion.check(state, &mut self.counterexamples);
+                }
+            }
+            if let Some(vm) = &mut self.vm {
+                vm.enter(state, pc_val);
+            }
+            if self.exceptions && pc_val == EXCEPTION_RETURN {
+                // an exception handler returned
+                exception::resume(state);
+            } else if self.fast_forward && self.run_natively(state) {
+                // concrete code ran up to an instruction radius has to execute
+            } else {
+                self.execute_instruction(state, pc_val);
+                if self.bus.wants(EventLevel::Debug) {
+                    self.emit_sim(pc_val);
+                }
+            }
+            if let Some(vm) = &self.vm {
+                vm.exit(state, pc_val, self.instructions.get(&pc_val));
+            }
+            if !self.invariants.is_empty() {
+                let block_end = self
+                    .instructions
+                    .get(&pc_val)
+                    .map(|e| {
+                        BLOCK_END_TYPES
+                            .iter()
+                            .any(|t| e.instruction.r#type.contains(t))
+                    })
+                    .unwrap_or(true);
+                for invariant in &self.invariants {
+                    invariant.check(state, pc_val, block_end, &mut self.invariant_violations);
+                }
+            }
         } else {
             panic!("got an unexpected sym PC: {:?}", pc_value);
         }
@@ -785,6 +1969,16 @@ impl Processor {
                 println!("\nsymbolic PC: {:?}\n", pc_val);
             }
 
+            if self.check_pc_control && pc_controlled(state, &new_pc) {
+                let addr = pc_value.as_u64().unwrap_or_default();
+                state
+                    .context
+                    .insert("pc_control".to_owned(), vec![vc(addr), new_pc.to_owned()]);
+                self.unconstrained.push(state.to_owned());
+                state.set_inactive();
+                return vec![];
+            }
+
             if DO_EVENT_HOOKS && state.has_event_hooks {
                 state.do_hooked(
                     &Event::SymbolicExec(EventTrigger::Before),
@@ -793,14 +1987,33 @@ impl Processor {
             }
 
             if !self.lazy && !state.esil.pcs.is_empty() {
+                let two_way = state.esil.pcs.len() == 2;
                 // testing sat without modelgen is a bit faster than evaluating
+                let mut unknown = false;
                 state.esil.pcs = state
                     .esil
                     .pcs
                     .clone()
                     .into_iter()
-                    .filter(|x| state.check(&new_pc.eq(&Value::Concrete(*x, 0))))
+                    .filter(|x| {
+                        let target = new_pc.eq(&Value::Concrete(*x, 0));
+                        match state.solver.check_result(&target) {
+                            SolverResult::Sat => true,
+                            SolverResult::Unsat => false,
+                            // timed out, take the branch and verify it if reported
+                            SolverResult::Unknown => {
+                                unknown = true;
+                                true
+                            }
+                        }
+                    })
                     .collect();
+                state.unverified |= unknown;
+
+                if let (Some(branches), true) = (&mut self.branches, two_way) {
+                    let addr = pc_value.as_u64().unwrap_or_default();
+                    record_branch(branches, addr, &state.esil.pcs);
+                }
             } else if state.esil.pcs.is_empty() {
                 state.esil.pcs = state.evaluate_many(&pc_val);
             }
@@ -822,11 +2035,30 @@ impl Processor {
                 return states;
             }
 
+            // the jump target of the branch, to tell which way was taken
+            let addr = pc_value.as_u64().unwrap_or_default();
+            let jump = self
+                .instructions
+                .get(&addr)
+                .map(|i| i.instruction.jump as u64)
+                .unwrap_or_default();
+
+            // the other successor of two way branches
+            let pcs = state.esil.pcs.clone();
+            let other = |pc: u64| {
+                pcs.iter()
+                    .copied()
+                    .find(|p| *p != pc)
+                    .filter(|_| pcs.len() == 2)
+            };
+
             let last = state.esil.pcs.len() - 1;
             for new_pc_val in &state.esil.pcs[..last] {
                 let mut new_state = state.clone();
                 if let Some(pc_val) = new_pc.as_bv() {
                     let a = pc_val._eq(&new_state.bvv(*new_pc_val, pc_val.get_width()));
+                    let taken = *new_pc_val == jump;
+                    new_state.add_branch(addr, &a, *new_pc_val, taken, other(*new_pc_val));
                     new_state.solver.assert_bv(&a);
                 }
                 new_state.registers.set_pc(Value::Concrete(*new_pc_val, 0));
@@ -837,6 +2069,7 @@ impl Processor {
             if let Some(pc_val) = new_pc.as_bv() {
                 let pc_bv = pc_val;
                 let a = pc_bv._eq(&state.bvv(new_pc_val, pc_bv.get_width()));
+                state.add_branch(addr, &a, new_pc_val, new_pc_val == jump, other(new_pc_val));
                 state.solver.assert_bv(&a);
             }
             state.registers.set_pc(Value::Concrete(new_pc_val, 0));
@@ -864,9 +2097,9 @@ impl Processor {
 
     /// run the state until completion based on mode
     pub fn run(&mut self, state: State, mode: RunMode) -> Vec<State> {
-        // use binary heap as priority queue to prioritize states
-        // that have the lowest number of visits for the current PC
-        let mut states = BinaryHeap::new();
+        // by default a priority queue prioritizes states that
+        // have the lowest number of visits for the current PC
+        let mut states = self.strategy.queue();
         let mut results = vec![];
         states.push(Rc::new(state));
 
@@ -874,8 +2107,38 @@ impl Processor {
         // or until every state is at the breakpoint for multiple
         let split = mode == RunMode::Parallel;
         let step = mode == RunMode::Step;
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        let start_steps = self.steps;
+        self.budget_stop = None;
+        self.survivors.clear();
 
         loop {
+            let stop = if deadline.map(|d| Instant::now() > d) == Some(true) {
+                Some(StopReason::Timeout)
+            } else if self.max_steps.map(|m| self.steps - start_steps >= m) == Some(true) {
+                Some(StopReason::MaxSteps)
+            } else if self.over_memory() {
+                Some(StopReason::MaxMemory)
+            } else if self.stop_at_counterexample && !self.counterexamples.is_empty() {
+                Some(StopReason::Found)
+            } else {
+                None
+            };
+            if let Some(reason) = stop {
+                if reason == StopReason::Timeout && !self.bus.is_empty() {
+                    self.bus.emit(RunEvent::Timeout {
+                        states: states.len(),
+                    });
+                }
+                self.budget_stop = Some(reason);
+                self.survivors = states
+                    .drain()
+                    .into_iter()
+                    .map(|s| Rc::try_unwrap(s).unwrap_or_else(|s| (*s).clone()))
+                    .collect();
+                return results;
+            }
+
             //println!("{} states", states.len());
             if states.is_empty() {
                 if self.merges.is_empty() {
@@ -894,27 +2157,142 @@ impl Processor {
 
             match current_state.status {
                 StateStatus::Active | StateStatus::PostMerge => {
-                    let new_states = self.step(current_state);
+                    if let Some(reach) = &self.reachability {
+                        if reach.unreachable(current_state) {
+                            if self.bus.wants(EventLevel::Debug) {
+                                let pc = current_state.registers.get_pc().as_u64();
+                                self.bus.emit(RunEvent::Pruned {
+                                    pc: pc.unwrap_or_default(),
+                                    reason: PruneReason::Unreachable,
+                                });
+                            }
+                            continue;
+                        }
+                    }
+                    let pc = current_state.registers.get_pc().as_u64();
+                    let mut new_states = self.step(current_state);
+                    if !new_states.is_empty() && !self.bus.is_empty() {
+                        self.bus.emit(RunEvent::Forked {
+                            pc: pc.unwrap_or_default(),
+                            count: new_states.len() + 1,
+                        });
+                    }
+                    if let Some(corpus) = self.corpus.as_mut().filter(|_| !new_states.is_empty()) {
+                        // the paths of a fork are testcases if they cover a new block
+                        corpus.add_block(current_state);
+                        for state in &mut new_states {
+                            corpus.add_block(state);
+                        }
+                    }
+                    if let (Some(threshold), Some(pc)) = (self.explosion_threshold, pc) {
+                        if !new_states.is_empty() {
+                            self.forks.record(current_state, pc, new_states.len());
+                        }
+                        // the current state is pushed back too
+                        let count = states.len() + new_states.len() + 1;
+                        if self.explosion.is_none() && count > threshold {
+                            let report =
+                                ExplosionReport::new(&self.forks, current_state, count, self.steps);
+                            report.print();
+                            self.explosion = Some(report);
+                        }
+                    }
                     for state in new_states {
                         states.push(Rc::new(state));
                     }
                     states.push(current_rc);
+                    if let Some(max) = self.max_states {
+                        if states.len() > max {
+                            self.shrink(states.as_mut(), max);
+                        }
+                    }
                 }
                 StateStatus::Merge => {
                     self.merge(current_state.to_owned());
                 }
+                StateStatus::Break if self.goals.is_some() => {
+                    let pc = current_state
+                        .registers
+                        .get_pc()
+                        .as_u64()
+                        .unwrap_or_default();
+                    let goals = self.goals.as_mut().unwrap();
+                    if goals.check(current_state, pc) && !self.bus.is_empty() {
+                        self.bus.emit(RunEvent::TargetReached { pc });
+                    }
+                    if goals.is_done() {
+                        self.keep_unexplored(states.as_mut());
+                        return results;
+                    }
+                    // let states pass goals that are all retired
+                    if !goals.is_pending(pc) && self.breakpoints.remove(&pc) {
+                        if let Some(entry) = self.instructions.get_mut(&pc) {
+                            entry.flags.remove(&InstructionFlag::Break);
+                        }
+                    }
+                    let is_goal = goals.goals.iter().any(|g| g.addr == pc);
+                    if is_goal && !self.breakpoints.contains(&pc) {
+                        current_state.status = StateStatus::Active;
+                        states.push(current_rc);
+                    }
+                }
                 StateStatus::Break => {
-                    if current_state.is_sat() {
+                    if current_state.verify() {
+                        self.emit_state(current_state);
                         results.push(current_state.to_owned());
                         if mode != RunMode::Multiple {
+                            self.keep_unexplored(states.as_mut());
                             return results;
                         }
                     }
                 }
-                StateStatus::Crash(_addr, _len) => {
-                    self.crashes.push(current_state.to_owned());
+                StateStatus::Crash(addr, perm) => {
+                    if self.exceptions
+                        && exception::raise_access_violation(current_state, addr, perm)
+                    {
+                        states.push(current_rc);
+                    } else {
+                        if !current_state.unverified || current_state.verify() {
+                            self.crashes.push(current_state.to_owned());
+                        }
+                        self.emit_state(current_state);
+                    }
+                }
+                StateStatus::Violation(_addr, _error) => {
+                    if !current_state.unverified || current_state.verify() {
+                        self.crashes.push(current_state.to_owned());
+                    }
+                    self.emit_state(current_state);
+                }
+                StateStatus::Exit => {
+                    let code = current_state.exit_code();
+                    let found = match (self.exit_goal, code) {
+                        (Some(goal), Some(code)) => {
+                            let condition = goal(&code);
+                            let found = current_state.check(&condition);
+                            if found {
+                                current_state.assert(&condition);
+                            }
+                            found && (!current_state.unverified || current_state.verify())
+                        }
+                        _ => false,
+                    };
+                    if found {
+                        if !self.bus.is_empty() {
+                            let pc = current_state.registers.get_pc().as_u64();
+                            let pc = pc.unwrap_or_default();
+                            self.bus.emit(RunEvent::TargetReached { pc });
+                        }
+                        results.push(current_state.to_owned());
+                        if mode != RunMode::Multiple {
+                            self.keep_unexplored(states.as_mut());
+                            return results;
+                        }
+                    } else {
+                        self.emit_state(current_state);
+                    }
                 }
-                _ => {}
+                _ => self.emit_state(current_state),
             }
 
             // single step mode always returns states
@@ -927,6 +2305,77 @@ impl Processor {
         }
     }
 
+    // whether the process is past the memory budget, checked every few steps
+    fn over_memory(&self) -> bool {
+        match self.max_memory {
+            Some(max) if self.steps % MEMORY_CHECK_STEPS == 0 => {
+                resident_memory().map(|m| m > max).unwrap_or_default()
+            }
+            _ => false,
+        }
+    }
+
+    // keep the states left to explore when a run returns at a found one
+    fn keep_unexplored(&mut self, states: &mut dyn ExplorationStrategy) {
+        if let Some(unexplored) = &mut self.unexplored {
+            let states = states.drain().into_iter();
+            unexplored.extend(states.map(|s| Rc::try_unwrap(s).unwrap_or_else(|s| (*s).clone())));
+        }
+    }
+
+    // tell the subscribers how the state ended, if any are listening
+    fn emit_state(&mut self, state: &mut State) {
+        if let Some(ended) = self
+            .ended
+            .as_mut()
+            .filter(|_| state.status != StateStatus::Break)
+        {
+            ended.push(state.to_owned());
+        }
+        if let Some(corpus) = &mut self.corpus {
+            corpus.add(state);
+        }
+        if self.bus.is_empty() {
+            return;
+        }
+        let pc = state.registers.get_pc().as_u64().unwrap_or_default();
+        let event = match state.status {
+            StateStatus::Break => RunEvent::TargetReached { pc },
+            StateStatus::Crash(..) | StateStatus::Violation(..) => match Finding::from_crash(state)
+            {
+                Some(finding) => RunEvent::Finding(finding),
+                None => return,
+            },
+            _ => RunEvent::Died {
+                pc,
+                status: state.status.clone(),
+            },
+        };
+        self.bus.emit(event);
+    }
+
+    // drop states down to three quarters of the limit, so the
+    // frontier is not shrunk again on every step
+    fn shrink(&mut self, states: &mut dyn ExplorationStrategy, max: usize) {
+        let keep = max - max / 4;
+        let (kept, dropped) = self.gc_policy.shrink(states.drain(), keep);
+        for state in kept {
+            states.push(state);
+        }
+        for state in &dropped {
+            for callback in &self.on_drop {
+                callback(state);
+            }
+            if self.bus.wants(EventLevel::Debug) {
+                let pc = state.registers.get_pc().as_u64().unwrap_or_default();
+                self.bus.emit(RunEvent::Pruned {
+                    pc,
+                    reason: PruneReason::Shrunk,
+                });
+            }
+        }
+    }
+
     pub fn merge(&mut self, mut state: State) {
         let pc = state.registers.get_pc().as_u64().unwrap();
         if let Some(merge_state) = self.merges.get_mut(&pc) {
@@ -0,0 +1,71 @@
+use crate::solver::SolverStats;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Time spent on a single target instruction over a run
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InstructionProfile {
+    /// Number of times the instruction was stepped
+    pub count: u64,
+    /// Total time spent stepping the instruction
+    pub total: Duration,
+    /// Time spent disassembling and parsing the ESIL
+    pub lift: Duration,
+    /// Time spent in sat queries made while stepping
+    pub solver: Duration,
+    /// Number of sat queries made while stepping
+    pub queries: u64,
+}
+
+impl InstructionProfile {
+    /// Time spent executing the ESIL and building expressions
+    pub fn exec(&self) -> Duration {
+        self.total.saturating_sub(self.lift + self.solver)
+    }
+}
+
+/// Attributes the wall time of a run to the target instructions
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub instructions: HashMap<u64, InstructionProfile>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Profile::default()
+    }
+
+    /// Add a step of the instruction at `pc`, with the solver stats
+    /// from before and after the step
+    pub fn add(&mut self, pc: u64, total: Duration, before: SolverStats, after: SolverStats) {
+        let entry = self.instructions.entry(pc).or_default();
+        entry.count += 1;
+        entry.total += total;
+        entry.solver += after.time.saturating_sub(before.time);
+        entry.queries += after.queries.saturating_sub(before.queries);
+    }
+
+    /// Add time spent lifting the instruction at `pc`
+    pub fn add_lift(&mut self, pc: u64, lift: Duration) {
+        self.instructions.entry(pc).or_default().lift += lift;
+    }
+
+    /// Get the `n` instructions with the most total time
+    pub fn hotspots(&self, n: usize) -> Vec<(u64, InstructionProfile)> {
+        let mut hotspots: Vec<(u64, InstructionProfile)> =
+            self.instructions.iter().map(|(a, p)| (*a, *p)).collect();
+
+        hotspots.sort_by(|a, b| b.1.total.cmp(&a.1.total).then(a.0.cmp(&b.0)));
+        hotspots.truncate(n);
+        hotspots
+    }
+
+    /// Total time of all profiled steps
+    pub fn total(&self) -> Duration {
+        self.instructions.values().map(|p| p.total).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.instructions.clear();
+    }
+}
@@ -1,3 +1,4 @@
+use crate::arch::Arch;
 use r2pipe::{R2Pipe, R2PipeSpawnOptions};
 use serde::{Deserialize, Deserializer, Serialize};
 use std::sync::{Arc, Mutex};
@@ -6,13 +7,28 @@ use std::u8;
 //use ahash::AHashMap;
 //type HashMap<P, Q> = AHashMap<P, Q>;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::Write;
 use std::path::Path;
 use std::{thread, time};
 
 pub const STACK_START: u64 = 0xfff00000;
 pub const STACK_SIZE: u64 = 0x78000 * 2;
 
+// r2pipe only takes static args, so each distinct one is leaked just once
+static SPAWN_ARGS: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+fn static_arg(arg: &str) -> &'static str {
+    let mut args = SPAWN_ARGS.lock().unwrap();
+    if let Some(arg) = args.get(arg) {
+        return arg;
+    }
+    let arg: &'static str = Box::leak(arg.to_owned().into_boxed_str());
+    args.insert(arg);
+    arg
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Endian {
     Little,
@@ -32,6 +48,9 @@ pub enum Mode {
 pub struct CallingConvention {
     pub ret: String,
     pub args: Vec<String>,
+    /// Bytes of stack the caller reserves for the register arguments
+    #[serde(default)]
+    pub shadow: u64,
 }
 
 impl Default for CallingConvention {
@@ -44,8 +63,37 @@ impl Default for CallingConvention {
                 String::from("A2"),
                 // String::from("A3"),
             ],
+            shadow: 0,
+        }
+    }
+}
+
+impl CallingConvention {
+    pub fn new(args: &[&str], ret: &str, shadow: u64) -> Self {
+        CallingConvention {
+            args: args.iter().map(|a| a.to_string()).collect(),
+            ret: ret.to_string(),
+            shadow,
         }
     }
+
+    /// Offset from the stack pointer on entry of the arg at `index` that
+    /// is past the register args, for pointers of `ptr` bytes and a return
+    /// address of `ret` bytes on the stack
+    pub fn stack_offset(&self, index: usize, ptr: u64, ret: u64) -> u64 {
+        ret + self.shadow + index.saturating_sub(self.args.len()) as u64 * ptr
+    }
+
+    /// The standard calling convention of functions for the platform,
+    /// see `Arch::platform_cc`
+    pub fn platform(os: &str, arch: &str, bits: u64) -> Option<Self> {
+        Arch::new(arch, bits).platform_cc(os)
+    }
+
+    /// The calling convention of syscalls for the platform, see `Arch::syscall_cc`
+    pub fn syscall(os: &str, arch: &str, bits: u64) -> Option<Self> {
+        Arch::new(arch, bits).syscall_cc(os)
+    }
 }
 
 impl Endian {
@@ -87,6 +135,10 @@ pub struct Instruction {
 
     #[serde(default)]
     pub fail: i64,
+
+    /// Instructions in the delay slot of a branch, from `aoj`
+    #[serde(default)]
+    pub delay: i64,
 }
 
 fn invalid() -> String {
@@ -111,6 +163,30 @@ pub struct Segment {
     pub vaddr: u64,
 }
 
+/// A resource of a PE file as listed by `iRj`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Resource {
+    pub name: String,
+    #[serde(default)]
+    pub index: u64,
+    #[serde(default)]
+    pub r#type: String,
+    pub vaddr: u64,
+    pub size: u64,
+    #[serde(default)]
+    pub lang: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DebugMap {
+    pub name: String,
+    #[serde(default)]
+    pub file: String,
+    pub addr: u64,
+    pub addr_end: u64,
+    pub perm: String,
+}
+
 #[derive(Debug)]
 pub struct Permission {
     pub initialized: bool,
@@ -160,6 +236,12 @@ pub struct BinInfo {
     pub endian: String,
     pub os: String,
     pub nx: bool,
+    /// Position independent, eg. a PIE executable
+    #[serde(default)]
+    pub pic: bool,
+    /// Address the binary is loaded at
+    #[serde(default)]
+    pub baddr: u64,
 }
 
 impl Default for BinInfo {
@@ -172,6 +254,8 @@ impl Default for BinInfo {
             endian: "little".to_string(),
             os: "".to_string(),
             nx: false,
+            pic: false,
+            baddr: 0,
         }
     }
 }
@@ -210,6 +294,24 @@ pub struct SearchResult {
     pub data: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GadgetOp {
+    pub offset: u64,
+    pub size: u64,
+    pub opcode: String,
+    #[serde(default)]
+    pub r#type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Gadget {
+    pub opcodes: Vec<GadgetOp>,
+    #[serde(default)]
+    pub retaddr: u64,
+    #[serde(default)]
+    pub size: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CrossRef {
     pub addr: u64,
@@ -311,6 +413,10 @@ pub struct Import {
 
     #[serde(default)]
     pub plt: u64,
+
+    /// Library the import is from, only known for some formats
+    #[serde(default)]
+    pub libname: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -326,7 +432,7 @@ pub struct FridaImport {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Export {
-    name: String,
+    pub name: String,
     flagname: String,
     realname: String,
     ordinal: usize,
@@ -402,6 +508,8 @@ pub struct Relocation {
     pub r#type: String,
     pub demname: String,
     pub is_ifunc: bool,
+    #[serde(default)]
+    pub addend: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -507,23 +615,108 @@ pub fn hex_decode(data: &str) -> Vec<u8> {
     result
 }
 
+/// An r2 command and its output, one line of a recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct R2Exchange {
+    pub cmd: String,
+    pub output: String,
+}
+
+/// A recorded r2 session that serves the output of commands in place of r2
+#[derive(Debug, Default)]
+pub struct R2Replay {
+    exchanges: Vec<R2Exchange>,
+    position: usize,
+}
+
+impl R2Replay {
+    /// Load a recording made with `R2Api::new_recording`
+    pub fn load(path: &str) -> R2Result<Self> {
+        let data = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let exchanges = data
+            .lines()
+            .map(|line| r2_result(serde_json::from_str(line)))
+            .collect::<R2Result<Vec<R2Exchange>>>()?;
+        Ok(R2Replay {
+            exchanges,
+            position: 0,
+        })
+    }
+
+    /// The output of `cmd`, following the recording in order. Commands
+    /// issued out of the recorded order get the output of any recorded run
+    pub fn output(&mut self, cmd: &str) -> Option<String> {
+        let rest = &self.exchanges[self.position..];
+        if let Some(index) = rest.iter().position(|e| e.cmd == cmd) {
+            self.position += index + 1;
+            return Some(rest[index].output.to_owned());
+        }
+        self.exchanges
+            .iter()
+            .rev()
+            .find(|e| e.cmd == cmd)
+            .map(|e| e.output.to_owned())
+    }
+}
+
 // #[derive(DerefMut)]
 #[derive(Clone)]
 pub struct R2Api {
-    pub r2p: Arc<Mutex<R2Pipe>>,
+    /// The r2 session, `None` when replaying a recording
+    pub r2p: Option<Arc<Mutex<R2Pipe>>>,
     //pub instructions: HashMap<u64, Instruction>,
     //pub permissions: HashMap<u64, Permission>,
     pub info: Information,
     pub mode: Mode,
     do_cache: bool,
-    cache: HashMap<String, String>,
+    cache: Arc<Mutex<CommandCache>>,
+    recording: Option<Arc<Mutex<fs::File>>>,
+    replay: Option<Arc<Mutex<R2Replay>>>,
 }
 
 impl R2Api {
-    pub fn new<T: AsRef<str>>(filename: Option<T>, opts: Option<Vec<&'static str>>) -> R2Api {
+    pub fn new<T: AsRef<str>>(filename: Option<T>, opts: Option<Vec<&str>>) -> R2Api {
+        let r2pipe = R2Api::spawn(filename, opts).unwrap();
+        R2Api::with_session(Some(r2pipe), None, None)
+    }
+
+    /// Like `new`, also logging every command and its output to the
+    /// file at `path` to replay with `R2Api::replay`
+    pub fn new_recording<T: AsRef<str>>(
+        filename: Option<T>,
+        opts: Option<Vec<&str>>,
+        path: &str,
+    ) -> R2Result<R2Api> {
+        let file = fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        let r2pipe = R2Api::spawn(filename, opts)?;
+        Ok(R2Api::with_session(Some(r2pipe), Some(file), None))
+    }
+
+    /// An `R2Api` that serves commands from the recording at `path`
+    /// without running r2, so the same analysis can run where r2 is
+    /// not installed
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::r2_api::R2Api;
+    /// let path = std::env::temp_dir().join("r100.r2rec");
+    /// let path = path.to_str().unwrap();
+    /// let mut r2api = R2Api::new_recording(Some("../tests/r100"), None, path).unwrap();
+    /// let disasm = r2api.cmd("pi 1 @ 0x004006fd").unwrap();
+    /// r2api.close();
+    ///
+    /// let mut replay = R2Api::replay(path).unwrap();
+    /// assert_eq!(replay.cmd("pi 1 @ 0x004006fd").unwrap(), disasm);
+    /// ```
+    pub fn replay(path: &str) -> R2Result<R2Api> {
+        let replay = R2Replay::load(path)?;
+        Ok(R2Api::with_session(None, None, Some(replay)))
+    }
+
+    fn spawn<T: AsRef<str>>(filename: Option<T>, opts: Option<Vec<&str>>) -> R2Result<R2Pipe> {
         let options = &opts.as_ref().map(|o| R2PipeSpawnOptions {
             exepath: "r2".to_owned(),
-            args: o.to_owned(),
+            args: o.iter().map(|a| static_arg(a)).collect(),
         });
 
         let r2pipe = match (&filename, &opts) {
@@ -531,13 +724,22 @@ impl R2Api {
             (Some(name), _) => R2Pipe::spawn(name, options.to_owned()),
             // _ => Err(Error::NoSession),
         };
+        r2pipe.map_err(|e| format!("could not open r2: {:?}", e))
+    }
 
+    fn with_session(
+        r2pipe: Option<R2Pipe>,
+        recording: Option<fs::File>,
+        replay: Option<R2Replay>,
+    ) -> R2Api {
         let mut r2api = R2Api {
-            r2p: Arc::new(Mutex::new(r2pipe.unwrap())),
+            r2p: r2pipe.map(|r2p| Arc::new(Mutex::new(r2p))),
             info: Information::default(),
             mode: Mode::Default,
             do_cache: false,
-            cache: HashMap::new(),
+            cache: Arc::new(Mutex::new(CommandCache::default())),
+            recording: recording.map(|file| Arc::new(Mutex::new(file))),
+            replay: replay.map(|replay| Arc::new(Mutex::new(replay))),
         };
 
         r2api.info = r2api.get_info().unwrap();
@@ -565,17 +767,35 @@ impl R2Api {
     }
 
     pub fn cmd(&mut self, cmd: &str) -> R2Result<String> {
-        Ok(self.r2p.lock().unwrap().cmd(cmd).unwrap_or_default())
+        if let Some(replay) = &self.replay {
+            let output = replay.lock().unwrap().output(cmd);
+            return output.ok_or_else(|| format!("command {} is not in the recording", cmd));
+        }
+        let output = match &self.r2p {
+            Some(r2p) => r2p.lock().unwrap().cmd(cmd).unwrap_or_default(),
+            None => String::new(),
+        };
+        if let Some(recording) = &self.recording {
+            let exchange = R2Exchange {
+                cmd: cmd.to_owned(),
+                output,
+            };
+            let line = serde_json::to_string(&exchange).unwrap_or_default();
+            let _ = writeln!(recording.lock().unwrap(), "{}", line);
+            return Ok(exchange.output);
+        }
+        Ok(output)
     }
 
     // cached command
     pub fn ccmd(&mut self, cmd: &str) -> R2Result<String> {
         if self.do_cache {
-            if let Some(result) = self.cache.get(cmd) {
-                Ok(result.to_owned())
+            let cached = self.cache.lock().unwrap().results.get(cmd).cloned();
+            if let Some(result) = cached {
+                Ok(result)
             } else {
                 let result = self.cmd(cmd)?;
-                self.cache.insert(cmd.to_owned(), result.clone());
+                self.cache.lock().unwrap().insert(cmd, &result);
                 Ok(result)
             }
         } else {
@@ -583,6 +803,41 @@ impl R2Api {
         }
     }
 
+    /// Cache the results of queries starting with the given results,
+    /// which are shared by every clone of this `R2Api`
+    pub fn enable_cache(&mut self, cache: HashMap<String, String>) {
+        self.do_cache = true;
+        let mut command_cache = CommandCache::default();
+        for (cmd, result) in &cache {
+            command_cache.insert(cmd, result);
+        }
+        *self.cache.lock().unwrap() = command_cache;
+    }
+
+    /// Get the cached query results that don't depend on the analysis,
+    /// which are the ones worth keeping across runs
+    pub fn get_cache(&self) -> HashMap<String, String> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .results
+            .iter()
+            .filter(|(cmd, _)| !depends_on_analysis(cmd))
+            .map(|(cmd, result)| (cmd.to_owned(), result.to_owned()))
+            .collect()
+    }
+
+    /// Get a hash of the file contents to identify the binary
+    pub fn get_file_hash(&mut self) -> R2Result<String> {
+        let json = self.cmd("itj")?;
+        let hashes: HashMap<String, String> = r2_result(serde_json::from_str(json.as_str()))?;
+        hashes
+            .get("sha256")
+            .or_else(|| hashes.get("sha1"))
+            .or_else(|| hashes.get("md5"))
+            .cloned()
+            .ok_or_else(|| "no file hash".to_owned())
+    }
+
     pub fn get_info(&mut self) -> R2Result<Information> {
         let json = self.cmd("ij")?;
         Ok(serde_json::from_str(json.as_str()).unwrap())
@@ -598,9 +853,24 @@ impl R2Api {
         r2_result(serde_json::from_str(json.as_str()))
     }
 
+    /// Get the calling convention r2 found for the function at `pc`,
+    /// falling back to the platform convention if r2 has none
     pub fn get_cc(&mut self, pc: u64) -> R2Result<CallingConvention> {
         let json = self.cmd(format!("af @ {}; afcrj @ {}", pc, pc).as_str())?;
-        r2_result(serde_json::from_str(json.as_str()))
+        let cc: R2Result<CallingConvention> = r2_result(serde_json::from_str(json.as_str()));
+
+        let bin = &self.info.bin;
+        let platform = CallingConvention::platform(&bin.os, &bin.arch, bin.bits);
+        match (cc, platform) {
+            // r2 doesn't know about shadow space, so take it from the platform
+            (Ok(mut cc), Some(platform)) if !cc.args.is_empty() => {
+                cc.shadow = platform.shadow;
+                Ok(cc)
+            }
+            (Ok(cc), None) => Ok(cc),
+            (_, Some(platform)) => Ok(platform),
+            (Err(e), None) => Err(e),
+        }
     }
 
     pub fn get_shellcode(&mut self, cmd: &str) -> R2Result<Vec<u8>> {
@@ -608,135 +878,10 @@ impl R2Api {
         Ok(hex_decode(&result))
     }
 
-    /*
-        arch/ABI      arg1  arg2  arg3  arg4  arg5  arg6  arg7  Notes
-    ──────────────────────────────────────────────────────────────
-    alpha         a0    a1    a2    a3    a4    a5    -
-    arc           r0    r1    r2    r3    r4    r5    -
-    arm/OABI      a1    a2    a3    a4    v1    v2    v3
-    arm/EABI      r0    r1    r2    r3    r4    r5    r6
-    arm64         x0    x1    x2    x3    x4    x5    -
-    blackfin      R0    R1    R2    R3    R4    R5    -
-    i386          ebx   ecx   edx   esi   edi   ebp   -
-    ia64          out0  out1  out2  out3  out4  out5  -
-    m68k          d1    d2    d3    d4    d5    a0    -
-    microblaze    r5    r6    r7    r8    r9    r10   -
-    mips/o32      a0    a1    a2    a3    -     -     -     [1]
-    mips/n32,64   a0    a1    a2    a3    a4    a5    -
-    nios2         r4    r5    r6    r7    r8    r9    -
-    parisc        r26   r25   r24   r23   r22   r21   -
-    powerpc       r3    r4    r5    r6    r7    r8    r9
-    riscv         a0    a1    a2    a3    a4    a5    -
-    s390          r2    r3    r4    r5    r6    r7    -
-    s390x         r2    r3    r4    r5    r6    r7    -
-    superh        r4    r5    r6    r7    r0    r1    r2
-    sparc/32      o0    o1    o2    o3    o4    o5    -
-    sparc/64      o0    o1    o2    o3    o4    o5    -
-    tile          R00   R01   R02   R03   R04   R05   -
-    x86-64        rdi   rsi   rdx   r10   r8    r9    -
-    x32           rdi   rsi   rdx   r10   r8    r9    -
-    xtensa        a6    a3    a4    a5    a8    a9    -
-    */
-
     pub fn get_syscall_cc(&mut self) -> R2Result<CallingConvention> {
-        // this sucks, need a central place for arch stuff
-        match (self.info.bin.arch.as_str(), self.info.bin.bits) {
-            ("x86", 32) => Ok(CallingConvention {
-                args: vec![
-                    "ebx".to_string(),
-                    "ecx".to_string(),
-                    "edx".to_string(),
-                    "esi".to_string(),
-                    "edi".to_string(),
-                    "ebp".to_string(),
-                ],
-                ret: "eax".to_string(),
-            }),
-            ("x86", 64) => Ok(CallingConvention {
-                args: vec![
-                    "rdi".to_string(),
-                    "rsi".to_string(),
-                    "rdx".to_string(),
-                    "r10".to_string(),
-                    "r8".to_string(),
-                    "r9".to_string(),
-                ],
-                ret: "rax".to_string(),
-            }),
-            // 16 is thumb mode, need to handle better
-            ("arm", 16) | ("arm", 32) => Ok(CallingConvention {
-                args: vec![
-                    "r0".to_string(),
-                    "r1".to_string(),
-                    "r2".to_string(),
-                    "r3".to_string(),
-                    "r4".to_string(),
-                    "r5".to_string(),
-                    "r6".to_string(),
-                ],
-                ret: "r0".to_string(),
-            }),
-            ("arm", 64) => Ok(CallingConvention {
-                args: vec![
-                    "x0".to_string(),
-                    "x1".to_string(),
-                    "x2".to_string(),
-                    "x3".to_string(),
-                    "x4".to_string(),
-                    "x5".to_string(),
-                    "x6".to_string(),
-                    "x7".to_string(),
-                    "x8".to_string(), // supposedly xnu/ios can have up 9 args
-                ],
-                ret: "x0".to_string(),
-            }),
-            ("riscv", _) | ("mips", _) => Ok(CallingConvention {
-                args: vec![
-                    "a0".to_string(),
-                    "a1".to_string(),
-                    "a2".to_string(),
-                    "a3".to_string(),
-                    "a4".to_string(),
-                    "a5".to_string(),
-                ],
-                ret: "a0".to_string(),
-            }),
-            ("sparc", _) => Ok(CallingConvention {
-                args: vec![
-                    "o0".to_string(),
-                    "o1".to_string(),
-                    "o2".to_string(),
-                    "o3".to_string(),
-                    "o4".to_string(),
-                    "o5".to_string(),
-                ],
-                ret: "o0".to_string(),
-            }),
-            ("ppc", _) => Ok(CallingConvention {
-                args: vec![
-                    "r3".to_string(),
-                    "r4".to_string(),
-                    "r5".to_string(),
-                    "r6".to_string(),
-                    "r7".to_string(),
-                    "r8".to_string(),
-                    "r9".to_string(),
-                ],
-                ret: "r3".to_string(), // TODO errors are in r0
-            }),
-            ("xtensa", _) => Ok(CallingConvention {
-                args: vec![
-                    "a6".to_string(),
-                    "a3".to_string(),
-                    "a4".to_string(),
-                    "a5".to_string(),
-                    "a8".to_string(),
-                    "a9".to_string(),
-                ],
-                ret: "a2".to_string(),
-            }),
-            _ => Err("calling convention not found".to_owned()),
-        }
+        let bin = &self.info.bin;
+        CallingConvention::syscall(&bin.os, &bin.arch, bin.bits)
+            .ok_or_else(|| "calling convention not found".to_owned())
     }
 
     /*pub fn get_classes(&mut self) -> R2Result<Vec<ClassInfo>> {
@@ -768,18 +913,25 @@ impl R2Api {
         r2_result(serde_json::from_str(json.as_str()))
     }
 
+    pub fn get_resources(&mut self) -> R2Result<Vec<Resource>> {
+        let json = self.cmd("iRj")?;
+        r2_result(serde_json::from_str(json.as_str()))
+    }
+
     pub fn analyze(&mut self, n: usize) -> R2Result<String> {
+        // the functions and blocks cached before are stale now
+        self.cache.lock().unwrap().clear();
         // n = 14 automatically wins flareon
         self.cmd("a".repeat(n).as_str())
     }
 
     pub fn get_function_info(&mut self, addr: u64) -> R2Result<FunctionInfo> {
-        let json = self.cmd(format!("afij @ {}", addr).as_str())?;
+        let json = self.ccmd(format!("afij @ {}", addr).as_str())?;
         r2_result(serde_json::from_str(json.as_str()))
     }
 
     pub fn get_functions(&mut self) -> R2Result<Vec<FunctionInfo>> {
-        let json = self.cmd("aflj")?;
+        let json = self.ccmd("aflj")?;
         r2_result(serde_json::from_str(json.as_str()))
     }
 
@@ -803,6 +955,12 @@ impl R2Api {
         r2_result(serde_json::from_str(json.as_str()))
     }
 
+    /// Search for ROP gadgets containing `filter`, eg. "pop rdi"
+    pub fn search_gadgets(&mut self, filter: &str) -> R2Result<Vec<Gadget>> {
+        let json = self.ccmd(&format!("/Rj {}", filter))?;
+        r2_result(serde_json::from_str(json.as_str()))
+    }
+
     /// Gets all strings then filters, slower than search
     pub fn search_strings(&mut self, string: &str) -> R2Result<Vec<u64>> {
         let result = self.cmd(&format!("izz~[2]~{}", string))?;
@@ -816,7 +974,7 @@ impl R2Api {
 
     pub fn get_blocks(&mut self, addr: u64) -> R2Result<Vec<BasicBlock>> {
         let cmd = format!("af @ {}; afbj @ {}", addr, addr);
-        let json = self.cmd(cmd.as_str())?;
+        let json = self.ccmd(cmd.as_str())?;
         r2_result(serde_json::from_str(json.as_str()))
     }
 
@@ -894,10 +1052,23 @@ impl R2Api {
         .unwrap_or_default();
     }
 
+    pub fn get_debug_registers(&mut self) -> R2Result<HashMap<String, u64>> {
+        let json = self.cmd("drj")?;
+        r2_result(serde_json::from_str(json.as_str()))
+    }
+
+    pub fn get_debug_maps(&mut self) -> R2Result<Vec<DebugMap>> {
+        let json = self.cmd("dmj")?;
+        r2_result(serde_json::from_str(json.as_str()))
+    }
+
     pub fn init_vm(&mut self) {
         let _r = self.cmd(&format!("aei; aeim {} {}", STACK_START, STACK_SIZE));
     }
 
+    /// Set up the r2 vm with argc, argv and envp on its stack, states
+    /// get theirs from `Radius::entry_state_with`
+    #[deprecated(note = "use Radius::entry_state_with to set up argv and env")]
     pub fn init_entry(&mut self, args: &[String], vars: &[String]) {
         let argc = args.len();
         let argv = args.join(" ");
@@ -996,6 +1167,7 @@ impl R2Api {
                     ordinal: f.index,
                     plt: f.address,
                     bind: f.name.to_owned(),
+                    libname: f.module.to_owned(),
                 })
                 .collect())
         }
@@ -1008,8 +1180,24 @@ impl R2Api {
 
     pub fn disassemble(&mut self, addr: u64, num: usize) -> R2Result<Vec<Instruction>> {
         let cmd = format!("pdj {} @ {}", num, addr);
-        let json = self.cmd(cmd.as_str())?;
-        r2_result(serde_json::from_str(json.as_str()))
+        let json = self.ccmd(cmd.as_str())?;
+        let mut instrs: Vec<Instruction> = r2_result(serde_json::from_str(json.as_str()))?;
+        self.set_delays(&mut instrs);
+        Ok(instrs)
+    }
+
+    /// Disassemble with `bits` bits instead of the current mode, eg. 16 for thumb
+    pub fn disassemble_bits(
+        &mut self,
+        addr: u64,
+        num: usize,
+        bits: u64,
+    ) -> R2Result<Vec<Instruction>> {
+        let cmd = format!("pdj {} @ {} @b:{}", num, addr, bits);
+        let json = self.ccmd(cmd.as_str())?;
+        let mut instrs: Vec<Instruction> = r2_result(serde_json::from_str(json.as_str()))?;
+        self.set_delays(&mut instrs);
+        Ok(instrs)
     }
 
     pub fn disassemble_function(&mut self, addr: u64) -> R2Result<Vec<Instruction>> {
@@ -1028,7 +1216,32 @@ impl R2Api {
         let cmd = format!("wx {} @ {}; pij {} @ {}", hex_encode(data), addr, num, addr);
 
         let json = self.cmd(cmd.as_str())?;
-        r2_result(serde_json::from_str(json.as_str()))
+        let mut instrs: Vec<Instruction> = r2_result(serde_json::from_str(json.as_str()))?;
+        self.set_delays(&mut instrs);
+        Ok(instrs)
+    }
+
+    // pdj leaves out the delay slots of branches, so ask for the ops of
+    // the branches of archs that have them
+    fn set_delays(&mut self, instrs: &mut [Instruction]) {
+        if self.info.bin.arch != "mips" {
+            return;
+        }
+        for instr in instrs {
+            if !["jmp", "call", "ret"]
+                .iter()
+                .any(|t| instr.r#type.contains(t))
+            {
+                continue;
+            }
+            let json = self.ccmd(&format!("aoj @ {}", instr.offset));
+            let op = json
+                .ok()
+                .and_then(|j| serde_json::from_str::<serde_json::Value>(&j).ok());
+            instr.delay = op
+                .and_then(|op| op[0]["delay"].as_i64())
+                .unwrap_or_default();
+        }
     }
 
     pub fn assemble(&mut self, instruction: &str) -> R2Result<Vec<u8>> {
@@ -1046,10 +1259,25 @@ impl R2Api {
     pub fn write(&mut self, addr: u64, data: Vec<u8>) {
         let cmd = format!("wx {} @ {}", hex_encode(&data), addr);
         let _r = self.cmd(cmd.as_str());
+        self.invalidate(addr, data.len() as u64);
+    }
+
+    // drop the cached queries about code the write changed, those with an
+    // item like an instruction, block, or function overlapping the write
+    fn invalidate(&mut self, addr: u64, length: u64) {
+        let end = addr.saturating_add(length);
+        self.cache.lock().unwrap().invalidate(addr, end);
     }
 
     // get_address tries to be a bit smart, maybe a bad idea
     pub fn get_address(&mut self, symbol: &str) -> R2Result<u64> {
+        // "libc.so:strlen" is an export of a loaded library
+        if let Some((module, name)) = symbol.split_once(':') {
+            if self.mode != Mode::Frida && !module.is_empty() && !name.starts_with(':') {
+                return self.get_module_address(module, name);
+            }
+        }
+
         let mut val = "".to_owned();
         if self.mode == Mode::Frida {
             let cmd = format!(":isa {}", symbol);
@@ -1071,6 +1299,60 @@ impl R2Api {
         }
     }
 
+    /// Get the address of the export `symbol` of the loaded library `module`,
+    /// the file name of the library or its prefix before a dot, eg. `libc.so`
+    pub fn get_module_address(&mut self, module: &str, symbol: &str) -> R2Result<u64> {
+        let files = self.get_files()?;
+        let prefix = format!("{}.", module);
+        let file = files.iter().find(|f| {
+            let name = Path::new(&f.uri)
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            name == module || name.starts_with(&prefix)
+        });
+        let file = match file {
+            Some(file) => file,
+            None => return Err(format!("library {} is not loaded", module)),
+        };
+
+        let current = files.iter().find(|f| f.raised).map(|f| f.fd);
+        self.set_file_fd(file.fd);
+        let exports = self.get_exports();
+        if let Some(fd) = current {
+            self.set_file_fd(fd);
+        }
+        exports?
+            .iter()
+            .find(|e| e.name == symbol)
+            .map(|e| e.vaddr)
+            .ok_or(format!("{} does not export {}", module, symbol))
+    }
+
+    /// Get the names and addresses of the imports of the main file
+    /// and the exports of the libraries loaded with it
+    pub fn get_api_addresses(&mut self) -> R2Result<Vec<(String, u64)>> {
+        let files = self.get_files()?;
+        let current = files.iter().find(|f| f.raised).map(|f| f.fd);
+        let mut apis: Vec<(String, u64)> = self
+            .get_imports()?
+            .into_iter()
+            .map(|i| (i.name, i.plt))
+            .collect();
+        for file in &files {
+            if Some(file.fd) == current || file.uri.starts_with("null://") {
+                continue;
+            }
+            self.set_file_fd(file.fd);
+            let exports = self.get_exports().unwrap_or_default();
+            apis.extend(exports.into_iter().map(|e| (e.name, e.vaddr)));
+        }
+        if let Some(fd) = current {
+            self.set_file_fd(fd);
+        }
+        Ok(apis)
+    }
+
     pub fn get_files(&mut self) -> R2Result<Vec<File>> {
         let json = self.cmd("oj")?;
         r2_result(serde_json::from_str(json.as_str()))
@@ -1107,8 +1389,13 @@ impl R2Api {
 
     // load libraries, return list of full loaded paths
     pub fn load_libraries(&mut self, lib_paths: &[String]) -> R2Result<Vec<String>> {
+        let main = self.get_files()?.into_iter().find(|f| f.raised);
         let paths = self.load_library_helper(lib_paths, &[])?;
-        self.cmd("op 3").unwrap_or_default(); // usually the main module is 3 idk
+        if let Some(main) = main {
+            self.set_file_fd(main.fd);
+        } else {
+            self.cmd("op 3").unwrap_or_default(); // usually the main module is 3 idk
+        }
         Ok(paths)
     }
 
@@ -1185,9 +1472,107 @@ impl R2Api {
         Ok(full_paths)
     }
 
-    pub fn clear(&mut self) {}
+    pub fn clear(&mut self) {
+        self.cache.lock().unwrap().clear();
+    }
 
     pub fn close(&mut self) {
-        self.r2p.lock().unwrap().close();
+        if let Some(r2p) = &self.r2p {
+            r2p.lock().unwrap().close();
+        }
+    }
+}
+
+// whether the json has an object with an offset or addr and a size
+// overlapping start..end
+fn overlaps(json: &serde_json::Value, start: u64, end: u64) -> bool {
+    let mut found = false;
+    for_each_item(json, &mut |addr, size| {
+        found |= addr < end && addr.saturating_add(size) > start;
+    });
+    found
+}
+
+// call f with the address and size of each item with an offset and a size
+fn for_each_item(json: &serde_json::Value, f: &mut dyn FnMut(u64, u64)) {
+    match json {
+        serde_json::Value::Array(items) => items.iter().for_each(|i| for_each_item(i, f)),
+        serde_json::Value::Object(map) => {
+            let addr = map.get("offset").or_else(|| map.get("addr"));
+            let addr = addr.and_then(|a| a.as_u64());
+            let size = map.get("size").and_then(|s| s.as_u64());
+            if let (Some(addr), Some(size)) = (addr, size) {
+                f(addr, size);
+            }
+            map.values().for_each(|v| for_each_item(v, f));
+        }
+        _ => {}
+    }
+}
+
+// commands whose results change with the analysis, like function lists
+// and blocks, eg. "afij @ 0x1000" or "af @ 0x1000; afbj @ 0x1000"
+fn depends_on_analysis(cmd: &str) -> bool {
+    cmd.split(';').any(|c| c.trim_start().starts_with('a'))
+}
+
+/// Results of cached r2 commands, indexed by the addresses of the items
+/// in them so writes only look at the results they can change
+#[derive(Debug, Default)]
+struct CommandCache {
+    results: HashMap<String, String>,
+    /// The commands whose items span from each address to an end address
+    spans: BTreeMap<u64, Vec<(u64, String)>>,
+}
+
+impl CommandCache {
+    fn insert(&mut self, cmd: &str, result: &str) {
+        self.results.insert(cmd.to_owned(), result.to_owned());
+        let json = match serde_json::from_str::<serde_json::Value>(result) {
+            Ok(json) => json,
+            Err(_) => return,
+        };
+        let mut span: Option<(u64, u64)> = None;
+        for_each_item(&json, &mut |addr, size| {
+            let end = addr.saturating_add(size);
+            span = Some(match span {
+                Some((lo, hi)) => (lo.min(addr), hi.max(end)),
+                None => (addr, end),
+            });
+        });
+        if let Some((lo, hi)) = span {
+            self.spans.entry(lo).or_default().push((hi, cmd.to_owned()));
+        }
+    }
+
+    // drop the results with an item overlapping start..end
+    fn invalidate(&mut self, start: u64, end: u64) {
+        let results = &self.results;
+        let mut stale = vec![];
+        for commands in self.spans.range_mut(..end).map(|(_, c)| c) {
+            commands.retain(|(hi, cmd)| {
+                if *hi <= start {
+                    return true;
+                }
+                // the span is coarse, check the items themselves
+                let json = results.get(cmd).and_then(|r| serde_json::from_str(r).ok());
+                match json {
+                    Some(json) if !overlaps(&json, start, end) => true,
+                    _ => {
+                        stale.push(cmd.to_owned());
+                        false
+                    }
+                }
+            });
+        }
+        self.spans.retain(|_, commands| !commands.is_empty());
+        for cmd in stale {
+            self.results.remove(&cmd);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.results.clear();
+        self.spans.clear();
     }
 }
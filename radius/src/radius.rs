@@ -1,14 +1,74 @@
-use crate::processor::{HookMethod, Processor, RunMode};
-use crate::r2_api::{BasicBlock, FunctionInfo, Information, Instruction, R2Api, R2Result};
-use crate::state::State;
+use crate::arch::Arch;
+use crate::budget::{RunResult, StopReason};
+use crate::bus::{EventLevel, RunEvent};
+use crate::concolic::{self, ConcolicRun};
+use crate::contract::{Contract, Postcondition, Precondition, Violation};
+use crate::corpus::Corpus;
+use crate::coverage::Coverage;
+use crate::drcov::Drcov;
+use crate::enumerate::{self, EnumeratedRun, Enumeration, Outcome};
+use crate::explosion::ExplosionReport;
+use crate::findings::{Finding, Findings};
+use crate::gc::{DropCallback, GcPolicy};
+use crate::goal::{Goal, Goals};
+use crate::ifunc::{self, IfuncPolicy};
+use crate::invariant::{Assertion, Counterexample, Invariant, InvariantCheck, InvariantViolation};
+use crate::knowledge::KnowledgeBase;
+use crate::loops::{LoopBounds, LoopSummary};
+use crate::manager::{StateManager, ACTIVE, FOUND};
+use crate::memory::{AddressPolicy, Uninit, MAX_STRING};
+use crate::modules::{ModulePolicies, ModulePolicy};
+use crate::parallel::{WorkQueue, WorkerSetup};
+use crate::primitives::{PrimitiveCatalog, WritePrimitive};
+use crate::processor::{
+    ArgLocation, ArgsHookMethod, ChainHookMethod, ExitGoal, Hook, HookCallback, HookMethod,
+    InstructionFlag, InstructionPolicy, OpcodeHandler, OpcodeMatch, Processor, RunMode,
+};
+use crate::profile::Profile;
+use crate::r2_api::{
+    BasicBlock, FunctionInfo, Information, Instruction, R2Api, R2Result, Resource,
+};
+use crate::reach::Reachability;
+use crate::refine::{input_args, run_native, Divergence, Prediction, Validation};
+use crate::repl;
+use crate::solver::{QueryLog, SolverBackend, SolverKind};
+use crate::state::{State, StateStatus};
+use crate::strategy::Strategy;
+use crate::trace::{Trace, TraceBranch};
+use crate::types::TypeDefs;
+use crate::vm::VmTracer;
+use crate::watch::{
+    Access, MemoryWatch, MemoryWatchMethod, RegisterWatch, RegisterWatchMethod, Watchpoints,
+};
 //use crate::value::Value;
+use crate::sims::antidebug::{self, RDTSC_DELTA, RDTSC_START};
+use crate::sims::libc::set_main_return;
 use crate::sims::syscall::indirect;
-use crate::sims::{get_sims, zero, Sim, SimMethod};
+use crate::sims::windows::{self, ApiHash};
+use crate::sims::{get_sims, make_sim, zero, Sim, SimMethod};
 use crate::value::{vc, Value};
 
 // use std::collections::VecDeque;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::mem;
+use std::ops::Range;
+use std::path::Path;
+use std::process;
+use std::rc::Rc;
+use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// read debug maps in pieces to keep r2 output manageable
+const DEBUG_READ_SIZE: u64 = 0x10000;
+
+/// Size of the symbolic buffer each arg points to in `Radius::solve_return`
+pub const SOLVE_BUFFER: usize = 64;
 
 // use std::thread;
 
@@ -39,8 +99,12 @@ pub enum RadiusOption {
     EvalMax(usize),
     /// Radare2 argument, must be static
     R2Argument(&'static str),
-    /// Handle self-modifying code (poorly)
+    /// Check every instruction against memory (slow), code written to
+    /// executable memory is decoded from memory even without this
     SelfModify(bool),
+    /// Run stretches of concrete code natively with unicorn, needs the
+    /// `unicorn` feature
+    FastForward(bool),
     /// Load plugins
     LoadPlugins(bool),
     /// Load libraries
@@ -49,6 +113,123 @@ pub enum RadiusOption {
     ColorOutput(bool),
     /// Path to load library from
     LibPath(String),
+    /// Base address to load PIE executables and shared objects at
+    LoadBase(u64),
+    /// Dispatch exceptions to SEH/VEH handlers (Windows x86)
+    Exceptions(bool),
+    /// Return clean values for common anti-debugging checks
+    AntiDebug(bool),
+    /// Merge the return states of calls to functions that make no calls
+    MergeLeafCalls(bool),
+    /// How to model rdtsc and rdtscp
+    Rdtsc(InstructionPolicy),
+    /// How to model cpuid
+    Cpuid(InstructionPolicy),
+    /// How to model rdrand and rdseed
+    Rdrand(InstructionPolicy),
+    /// Directory to store derived data about binaries across runs
+    KnowledgeBase(String),
+    /// Log the r2 commands and their output to a file
+    Record(String),
+    /// Serve r2 commands from a file made with `Record` instead of running r2
+    Replay(String),
+    /// Attribute execution time to target instructions
+    Profile(bool),
+    /// Log solver queries, keeping the SMT-LIB of this many of the slowest
+    QueryLog(usize),
+    /// Catalog stores whose address and value both depend on input
+    WritePrimitives(bool),
+    /// Report the sites that forked the most once there are more states than this
+    ExplosionReport(usize),
+    /// What uninitialized registers and stack memory hold
+    Uninit(Uninit),
+    /// How to handle the imports of libraries matching the pattern
+    ModulePolicy(String, ModulePolicy),
+    /// Stub the imports matching the pattern
+    IgnoreSymbol(String),
+    /// Assume symbolic lengths of memory operations are at most this
+    MaxLength(u64),
+    /// Longest string the string sims read, 8192 bytes by default
+    MaxStringLength(u64),
+    /// Order to explore states in
+    Strategy(Strategy),
+    /// How to bind calls to GNU ifuncs
+    Ifunc(IfuncPolicy),
+    /// Solver that decides the satisfiability of branches
+    Solver(SolverKind),
+    /// Drop states once there are more than this many
+    MaxStates(usize),
+    /// Which states are dropped, see `MaxStates`
+    GcPolicy(GcPolicy),
+    /// Make stdin a symbolic stream of up to this many bytes
+    SymbolicStdin(usize),
+    /// Stop states at heap overflows, use after free, and double free
+    HeapCheck(bool),
+    /// How loads and stores at symbolic addresses are made
+    AddressPolicy(AddressPolicy),
+    /// Fork both ways at branches whose queries take longer than this
+    QueryTimeout(Duration),
+    /// Defer states that iterate a loop found in the cfg more than this many times
+    MaxLoopIters(usize),
+    /// Stop runs that take longer than this
+    Timeout(Duration),
+    /// Stop runs that step states more than this many times
+    MaxSteps(u64),
+    /// Stop runs once the process uses more than this many bytes of memory
+    MaxMemory(u64),
+    /// Stop states whose pc, call target, or return address the input
+    /// can point anywhere, see `Radius::unconstrained`
+    CheckPcControl(bool),
+}
+
+/// Concrete or symbolic bytes of an argv element for
+/// `Radius::entry_state_with`, of an environment variable, or of a file
+/// set with `Radius::set_file`
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimArg {
+    /// A concrete string
+    Concrete(String),
+    /// Concrete bytes, eg. the contents of a binary file
+    Bytes(Vec<u8>),
+    /// Symbolic bytes of the given length
+    Symbolic(usize),
+    /// Concrete and symbolic parts in order, eg. a known prefix
+    Mixed(Vec<SimArg>),
+}
+
+impl SimArg {
+    /// The bytes of the arg, symbolic bytes are the input `source`
+    pub fn bytes(&self, state: &mut State, source: &str) -> Vec<Value> {
+        let mut bytes = vec![];
+        self.push_bytes(state, source, &mut bytes);
+        bytes
+    }
+
+    fn push_bytes(&self, state: &mut State, source: &str, bytes: &mut Vec<Value>) {
+        match self {
+            SimArg::Concrete(s) => bytes.extend(s.bytes().map(|b| vc(b as u64))),
+            SimArg::Bytes(b) => bytes.extend(b.iter().map(|b| vc(*b as u64))),
+            SimArg::Symbolic(n) => bytes.extend(state.input_bytes(source, bytes.len(), *n)),
+            SimArg::Mixed(parts) => {
+                for part in parts {
+                    part.push_bytes(state, source, bytes);
+                }
+            }
+        }
+    }
+}
+
+/// The data of a registry value set with `Radius::set_registry`
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegistryValue {
+    /// A REG_SZ string, read with its null terminator
+    String(String),
+    /// A REG_DWORD
+    Dword(u32),
+    /// REG_BINARY bytes
+    Binary(Vec<u8>),
+    /// Symbolic REG_BINARY bytes of the given length
+    Symbolic(usize),
 }
 
 /// Main Radius struct that coordinates and configures
@@ -77,6 +258,46 @@ pub struct Radius {
     pub debug: bool,
     /// Panic on invalid instructions
     pub strict: bool,
+    /// Defeat anti-debugging checks
+    pub anti_debug: bool,
+    /// Check the heap accesses of new states
+    pub heap_check: bool,
+    /// Environment variables added to the envp of states
+    pub env: Vec<(String, SimArg)>,
+    /// Files added to the filesystem of states
+    pub files: Vec<(String, SimArg)>,
+    /// Registry keys, value names, and values added to the registry of states
+    pub registry: Vec<(String, String, RegistryValue)>,
+    /// Derived data about the binary saved across runs
+    pub knowledge: Option<KnowledgeBase>,
+    /// Log of the solver queries of all states
+    pub query_log: Option<Rc<RefCell<QueryLog>>>,
+    /// Write-what-where primitives found by all states
+    pub primitives: Option<Rc<RefCell<PrimitiveCatalog>>>,
+    /// What uninitialized registers and stack memory of new states hold
+    pub uninit: Option<Uninit>,
+    /// States saved with `checkpoint` to re-explore from after a patch
+    pub checkpoints: Vec<State>,
+    /// Largest length assumed for symbolic length memory operations
+    pub max_length: Option<u64>,
+    /// Longest string the string sims read
+    pub max_string: u64,
+    /// Struct definitions shared by all states
+    pub types: Rc<RefCell<TypeDefs>>,
+    /// Memory and register watchpoints shared by all states
+    pub watchpoints: Rc<RefCell<Watchpoints>>,
+    /// Decides the satisfiability queries of all states
+    pub backend: Rc<dyn SolverBackend>,
+    /// Maximum length of the symbolic stdin stream of new states
+    pub stdin_stream: Option<usize>,
+    /// How new states load and store at symbolic addresses
+    pub address_policy: AddressPolicy,
+    /// Paths of the libraries loaded with `RadiusOption::LoadLibs`
+    pub libraries: Vec<String>,
+    /// Time limit of the branch queries of new states, see `State::verify`
+    pub query_timeout: Option<Duration>,
+    /// The options radius was made with, for the workers of parallel runs
+    options: Vec<RadiusOption>,
 }
 
 impl Radius {
@@ -108,9 +329,47 @@ impl Radius {
     ///   let mut radius = Radius::new_with_options(Some("/bin/sh"), &options);
     /// ```
     pub fn new_with_options<T: AsRef<str>>(filename: Option<T>, options: &[RadiusOption]) -> Self {
+        Radius::setup(None, filename, options)
+    }
+
+    /// Create a new Radius instance sharing the r2 session of `r2api`,
+    /// eg. the `r2api` of another instance for the same binary
+    pub fn new_with_r2api(r2api: R2Api, options: &[RadiusOption]) -> Self {
+        Radius::setup(Some(r2api), None::<&str>, options)
+    }
+
+    fn setup<T: AsRef<str>>(
+        session: Option<R2Api>,
+        filename: Option<T>,
+        options: &[RadiusOption],
+    ) -> Self {
+        let fresh = session.is_none();
         let mut argv = vec!["-2"];
         let mut eval_max = 256;
         let mut paths = vec![];
+        let mut policies = HashMap::new();
+        let mut knowledge_dir = None;
+        let mut record = None;
+        let mut replay = None;
+        let mut query_log = None;
+        let mut explosion_threshold = None;
+        let mut uninit = None;
+        let mut module_policies = ModulePolicies::default();
+        let mut max_length = None;
+        let mut max_string = MAX_STRING;
+        let mut strategy = Strategy::default();
+        let mut base = None;
+        let mut ifunc_policy = IfuncPolicy::default();
+        let mut solver = SolverKind::default();
+        let mut max_states = None;
+        let mut gc_policy = GcPolicy::default();
+        let mut address_policy = AddressPolicy::default();
+        let mut stdin_stream = None;
+        let mut query_timeout = None;
+        let mut max_loop_iters = None;
+        let mut timeout = None;
+        let mut max_steps = None;
+        let mut max_memory = None;
         for o in options {
             if let RadiusOption::R2Argument(arg) = o {
                 argv.push(*arg);
@@ -118,12 +377,71 @@ impl Radius {
                 eval_max = *m;
             } else if let RadiusOption::LibPath(p) = o {
                 paths.push(p.to_owned());
+            } else if let RadiusOption::Rdtsc(p) = o {
+                policies.insert("rdtsc".to_owned(), *p);
+                policies.insert("rdtscp".to_owned(), *p);
+            } else if let RadiusOption::Cpuid(p) = o {
+                policies.insert("cpuid".to_owned(), *p);
+            } else if let RadiusOption::Rdrand(p) = o {
+                policies.insert("rdrand".to_owned(), *p);
+                policies.insert("rdseed".to_owned(), *p);
+            } else if let RadiusOption::KnowledgeBase(dir) = o {
+                knowledge_dir = Some(dir.to_owned());
+            } else if let RadiusOption::Record(path) = o {
+                record = Some(path.to_owned());
+            } else if let RadiusOption::Replay(path) = o {
+                replay = Some(path.to_owned());
+            } else if let RadiusOption::QueryLog(n) = o {
+                query_log = Some(Rc::new(RefCell::new(QueryLog::new(*n))));
+            } else if let RadiusOption::ExplosionReport(n) = o {
+                explosion_threshold = Some(*n);
+            } else if let RadiusOption::Uninit(u) = o {
+                uninit = Some(*u);
+            } else if let RadiusOption::ModulePolicy(pattern, policy) = o {
+                module_policies.add_module(pattern, *policy);
+            } else if let RadiusOption::IgnoreSymbol(pattern) = o {
+                module_policies.ignore(pattern);
+            } else if let RadiusOption::MaxLength(n) = o {
+                max_length = Some(*n);
+            } else if let RadiusOption::MaxStringLength(n) = o {
+                max_string = *n;
+            } else if let RadiusOption::Strategy(s) = o {
+                strategy = *s;
+            } else if let RadiusOption::LoadBase(b) = o {
+                base = Some(*b);
+            } else if let RadiusOption::Ifunc(p) = o {
+                ifunc_policy = *p;
+            } else if let RadiusOption::Solver(kind) = o {
+                solver = kind.to_owned();
+            } else if let RadiusOption::MaxStates(n) = o {
+                max_states = Some(*n);
+            } else if let RadiusOption::GcPolicy(p) = o {
+                gc_policy = *p;
+            } else if let RadiusOption::SymbolicStdin(n) = o {
+                stdin_stream = Some(*n);
+            } else if let RadiusOption::AddressPolicy(p) = o {
+                address_policy = *p;
+            } else if let RadiusOption::QueryTimeout(t) = o {
+                query_timeout = Some(*t);
+            } else if let RadiusOption::MaxLoopIters(n) = o {
+                max_loop_iters = Some(*n);
+            } else if let RadiusOption::Timeout(t) = o {
+                timeout = Some(*t);
+            } else if let RadiusOption::MaxSteps(n) = o {
+                max_steps = Some(*n);
+            } else if let RadiusOption::MaxMemory(n) = o {
+                max_memory = Some(*n);
             }
         }
 
         let debug = options.contains(&RadiusOption::Debug(true));
         let color = options.contains(&RadiusOption::ColorOutput(true));
         let use_sims = !options.contains(&RadiusOption::Sims(false));
+        let primitives = if options.contains(&RadiusOption::WritePrimitives(true)) {
+            Some(Rc::new(RefCell::new(PrimitiveCatalog::new())))
+        } else {
+            None
+        };
 
         if !options.contains(&RadiusOption::LoadPlugins(true)) {
             argv.push("-NN");
@@ -139,16 +457,46 @@ impl Radius {
             argv.push("-e asm.functions=false");
         }
 
+        // relocate position independent binaries so memory holds the
+        // same pointers the loader would write at the base
+        let shared = filename.as_ref().map(|f| is_shared_object(f.as_ref()));
+        if base.is_some() || shared == Some(true) {
+            argv.push("-e bin.relocs.apply=true");
+        }
+        let base_arg;
+        if let Some(base) = base {
+            base_arg = format!("-B0x{:x}", base);
+            argv.push(&base_arg);
+        }
+
         let args = if !argv.is_empty() || filename.is_none() {
             Some(argv)
         } else {
             None
         };
 
-        let mut r2api = R2Api::new(filename, args);
+        let mut r2api = if let Some(r2api) = session {
+            r2api
+        } else if let Some(path) = &replay {
+            R2Api::replay(path).unwrap()
+        } else if let Some(path) = &record {
+            R2Api::new_recording(filename, args, path).unwrap()
+        } else {
+            R2Api::new(filename, args)
+        };
         r2api.set_option("io.cache", "true").unwrap();
         // r2api.cmd("eco darkda").unwrap(); // i like darkda
 
+        // serve r2 queries from the knowledge of previous runs, addresses
+        // depend on the load base so it is part of the key
+        let knowledge = knowledge_dir.and_then(|dir| {
+            let hash = r2api.get_file_hash().ok()?;
+            let key = format!("{}-{:x}", hash, r2api.info.bin.baddr);
+            let kb = KnowledgeBase::load(&dir, &key);
+            r2api.enable_cache(kb.commands.clone());
+            Some(kb)
+        });
+
         let arch = &r2api.info.bin.arch;
 
         // don't optimize dalvik & arm
@@ -159,13 +507,44 @@ impl Radius {
         let lazy = !options.contains(&RadiusOption::Lazy(false));
         let force = options.contains(&RadiusOption::Force(true));
         let topo = options.contains(&RadiusOption::Topological(true));
-        let check = options.contains(&RadiusOption::Permissions(true));
+        let exceptions = options.contains(&RadiusOption::Exceptions(true));
+        // access violations can only be raised if permissions are checked
+        let check = options.contains(&RadiusOption::Permissions(true)) || exceptions;
         let sim_all = options.contains(&RadiusOption::SimAll(true));
         let selfmod = options.contains(&RadiusOption::SelfModify(true));
         let strict = options.contains(&RadiusOption::Strict(true));
+        let anti_debug = options.contains(&RadiusOption::AntiDebug(true));
+        let heap_check = options.contains(&RadiusOption::HeapCheck(true));
 
+        let types = Rc::new(RefCell::new(TypeDefs::new(r2api.info.bin.bits / 8)));
         let mut processor = Processor::new(selfmod, opt, debug, lazy, force, topo, color);
         let processors = Arc::new(Mutex::new(vec![]));
+        processor.exceptions = exceptions;
+        processor.fast_forward = options.contains(&RadiusOption::FastForward(true));
+        if processor.fast_forward && cfg!(not(feature = "unicorn")) {
+            println!("radius2 was built without the unicorn feature, running everything with esil");
+        }
+        processor.arch = Arch::from_info(&r2api.info.bin);
+        processor.check_pc_control = options.contains(&RadiusOption::CheckPcControl(true));
+        processor.policies = policies;
+        processor.merge_leaf_calls = options.contains(&RadiusOption::MergeLeafCalls(true));
+        processor.explosion_threshold = explosion_threshold;
+        processor.strategy = strategy;
+        processor.max_states = max_states;
+        processor.loop_bounds = max_loop_iters.map(LoopBounds::new);
+        processor.timeout = timeout;
+        processor.max_steps = max_steps;
+        processor.max_memory = max_memory;
+        processor.gc_policy = gc_policy;
+        if options.contains(&RadiusOption::Profile(true)) {
+            processor.profile = Some(Profile::new());
+        }
+        if let Some(kb) = &knowledge {
+            processor.lifted = Some(kb.blocks.clone());
+            let opaque = kb.opaque_predicates.iter().map(|(a, t)| (*a, Some(*t)));
+            processor.branches = Some(opaque.collect());
+            processor.loops.extend(kb.summaries.clone());
+        }
 
         if !options.contains(&RadiusOption::Syscalls(false)) {
             let syscalls = r2api.get_syscalls().unwrap();
@@ -177,7 +556,8 @@ impl Radius {
             }
         }
 
-        let _libs = if options.contains(&RadiusOption::LoadLibs(true)) {
+        // a shared session has the libraries loaded already
+        let libraries = if fresh && options.contains(&RadiusOption::LoadLibs(true)) {
             r2api.load_libraries(&paths).unwrap()
         } else {
             vec![]
@@ -185,10 +565,39 @@ impl Radius {
 
         // this is weird, idk
         if use_sims {
-            Radius::register_sims(&mut r2api, &mut processor, sim_all);
+            // the first sim registered for a symbol wins
+            let mut sims = if anti_debug {
+                antidebug::get_sims()
+            } else {
+                vec![]
+            };
+            if r2api.info.bin.os == "windows" {
+                sims.extend(windows::get_sims());
+            }
+            sims.extend(get_sims());
+            Radius::register_sims(&mut r2api, &mut processor, &sims, sim_all, &module_policies);
+        } else if !module_policies.is_empty() {
+            // only stub with the policies
+            Radius::register_sims(&mut r2api, &mut processor, &[], false, &module_policies);
+        }
+
+        if anti_debug {
+            // small timing deltas and no hypervisor, unless set explicitly
+            let rdtsc = InstructionPolicy::Monotonic(RDTSC_START, RDTSC_DELTA);
+            let policies = [
+                ("rdtsc", rdtsc),
+                ("rdtscp", rdtsc),
+                ("cpuid", InstructionPolicy::Fixed(0)),
+            ];
+            for (mnemonic, policy) in policies {
+                processor
+                    .policies
+                    .entry(mnemonic.to_owned())
+                    .or_insert(policy);
+            }
         }
 
-        Radius {
+        let mut radius = Radius {
             r2api,
             processor,
             processors,
@@ -196,9 +605,67 @@ impl Radius {
             check,
             debug,
             strict,
+            anti_debug,
+            heap_check,
+            env: vec![],
+            files: vec![],
+            registry: vec![],
+            knowledge,
+            query_log,
+            primitives,
+            uninit,
+            checkpoints: vec![],
+            max_length,
+            max_string,
+            types,
+            watchpoints: Rc::new(RefCell::new(Watchpoints::default())),
+            backend: solver.backend(),
+            stdin_stream,
+            address_policy,
+            libraries,
+            query_timeout,
+            options: options.to_vec(),
+        };
+        if fresh {
+            let sims = if use_sims { get_sims() } else { vec![] };
+            radius.bind_ifuncs(ifunc_policy, &sims);
+        }
+        radius
+    }
+
+    // write the implementation each ifunc is bound to into its got slots,
+    // so calls don't go through the resolver probing cpu features
+    fn bind_ifuncs(&mut self, policy: IfuncPolicy, sims: &[Sim]) {
+        for func in ifunc::find_ifuncs(&mut self.r2api) {
+            let sim = sims.iter().find(|s| s.symbol == func.name);
+            let target = match (policy, func.generic, sim) {
+                (IfuncPolicy::Sim, generic, Some(sim)) => {
+                    let addr = generic.unwrap_or(func.resolver);
+                    self.processor.sims.insert(addr, sim.to_owned());
+                    Some(addr)
+                }
+                (IfuncPolicy::Resolve, _, _) | (_, None, _) => self.resolve_ifunc(func.resolver),
+                (_, Some(generic), _) => Some(generic),
+            };
+            if let Some(target) = target {
+                let ptr = self.r2api.info.bin.bits / 8;
+                for slot in &func.slots {
+                    let cmd = format!("wv{} {} @ {}", ptr, target, slot);
+                    self.r2api.cmd(&cmd).unwrap_or_default();
+                }
+            }
         }
     }
 
+    // run the resolver of an ifunc and get the address it returns
+    fn resolve_ifunc(&mut self, resolver: u64) -> Option<u64> {
+        let cc = self.r2api.get_cc(resolver).ok()?;
+        let state = self.call_state(resolver);
+        let mut end = self.call_address(resolver, state, vec![])?;
+        let target = end.registers.get(&cc.ret);
+        end.solver.evalcon_to_u64(&target)
+    }
+
     /// Initialized state at the provided function address with an initialized stack
     /// (if applicable)
     ///
@@ -263,6 +730,98 @@ impl Radius {
         state
     }
 
+    /// Initialize state from the current context of a running debug session,
+    /// snapshotting the registers and all readable mapped memory of the target
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// use radius2::Radius;
+    /// let mut radius = Radius::new("dbg:///bin/ls");
+    /// radius.cmd("db main; dc").unwrap();
+    /// let mut state = radius.state_from_debugger();
+    /// ```
+    pub fn state_from_debugger(&mut self) -> State {
+        self.snapshot_debugger(false)
+    }
+
+    // snapshot the debugged process, copying only the maps of the binary
+    // itself, the stack and the heap if `own` is set. the rest of the
+    // memory is still mapped and read from the process when it is used
+    fn snapshot_debugger(&mut self, own: bool) -> State {
+        let file = self.r2api.info.core.file.to_owned();
+        let target = Path::new(file.trim_start_matches("dbg://")).file_name();
+
+        // disable the cache so reads come from the live process
+        self.r2api.set_option("io.cache", "false").unwrap();
+        let mut state = self.init_state();
+
+        let regs = self.r2api.get_debug_registers().unwrap_or_default();
+        for (reg, value) in regs {
+            if state.registers.regs.contains_key(&reg) {
+                state.registers.set(&reg, vc(value));
+            }
+        }
+
+        // the debug maps replace the static segments of the binary
+        let maps = self.r2api.get_debug_maps().unwrap_or_default();
+        if !maps.is_empty() {
+            state.memory.segs.clear();
+            state.memory.index_segments();
+        }
+
+        for map in maps {
+            let size = map.addr_end - map.addr;
+            let perms = format!("{}i", map.perm);
+            state.memory.add_segment(&map.name, map.addr, size, &perms);
+
+            let copied = !own
+                || map.name == "[stack]"
+                || map.name == "[heap]"
+                || Path::new(&map.file).file_name() == target
+                || Path::new(&map.name).file_name() == target;
+
+            if !copied || !map.perm.contains('r') {
+                continue;
+            }
+
+            let mut addr = map.addr;
+            while addr < map.addr_end {
+                let len = (map.addr_end - addr).min(DEBUG_READ_SIZE);
+                let bytes = self.r2api.read(addr, len as usize).unwrap_or_default();
+                let mut data: Vec<Value> = bytes.iter().map(|b| vc(*b as u64)).collect();
+                state.memory.write(addr, &mut data);
+                addr += len;
+            }
+        }
+
+        // the snapshot is the code r2 disassembles, not written code
+        state.memory.code_writes.clear();
+        state.memory.add_heap();
+        self.r2api.set_option("io.cache", "true").unwrap();
+        state
+    }
+
+    /// Run the debugged process natively until it reaches `addr` and
+    /// snapshot it like `state_from_debugger`, to go symbolic from a
+    /// realistic heap and globals. Only the maps of the binary, the stack,
+    /// and the heap are copied, libraries are read when they are used
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// use radius2::Radius;
+    /// let mut radius = Radius::new("dbg:///bin/ls");
+    /// let main = radius.get_address("main").unwrap();
+    /// let mut state = radius.run_native_until(main);
+    /// ```
+    pub fn run_native_until(&mut self, addr: u64) -> State {
+        if let Err(e) = self.r2api.breakpoint(addr).and_then(|_| self.r2api.cont()) {
+            println!("warning: could not run to 0x{:x}: {}", addr, e);
+        }
+        self.snapshot_debugger(true)
+    }
+
     /// Initialized state at the program entry point (the first if multiple).
     ///
     /// ## Example
@@ -273,6 +832,25 @@ impl Radius {
     /// let mut state = radius.entry_state();
     /// ```
     pub fn entry_state(&mut self) -> State {
+        self.entry_state_with(&[], &[])
+    }
+
+    /// Initialized state at the program entry point with the argv `args`
+    /// and the environment `env` followed by the variables set with
+    /// `set_env`. Symbolic bytes of argv\[i\] are the input "argv\[i\]"
+    /// and those of a variable are the input "env:KEY"
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use radius2::radius::{Radius, SimArg};
+    /// let mut radius = Radius::new("/bin/sh");
+    /// let args = [SimArg::Concrete("sh".to_owned()), SimArg::Symbolic(32)];
+    /// let env = [("HOME", SimArg::Concrete("/root".to_owned()))];
+    /// let mut state = radius.entry_state_with(&args, &env);
+    /// assert_eq!(state.dump_inputs()["argv[1]"].len(), 32);
+    /// ```
+    pub fn entry_state_with(&mut self, args: &[SimArg], env: &[(&str, SimArg)]) -> State {
         // get the entrypoint
         let entrypoints = self.r2api.get_entrypoints().unwrap_or_default();
         if !entrypoints.is_empty() {
@@ -284,12 +862,133 @@ impl Radius {
         state.memory.add_heap();
         state.memory.add_std_streams();
 
+        // variables passed in env take the place of those set with set_env
+        let set = self
+            .env
+            .iter()
+            .filter(|(k, _)| env.iter().all(|(key, _)| key != k));
+        let vars: Vec<(&str, &SimArg)> = env
+            .iter()
+            .map(|(k, v)| (*k, v))
+            .chain(set.map(|(k, v)| (k.as_str(), v)))
+            .collect();
+
+        let mut envp = vec![];
+        for (key, value) in vars {
+            let mut bytes: Vec<Value> = format!("{}=", key).bytes().map(|b| vc(b as u64)).collect();
+            bytes.extend(value.bytes(&mut state, &format!("env:{}", key)));
+            envp.push(pack_string(&state, &bytes));
+        }
+        if !args.is_empty() {
+            let mut argv = vec![];
+            for (i, arg) in args.iter().enumerate() {
+                let bytes = arg.bytes(&mut state, &format!("argv[{}]", i));
+                argv.push(pack_string(&state, &bytes));
+            }
+            self.set_argv_env(&mut state, &argv, &envp);
+        } else if !envp.is_empty() {
+            // keep argc and argv as they are
+            self.set_envp(&mut state, &envp);
+        }
+
         let start_main_reloc = self.r2api.get_address("reloc.__libc_start_main").unwrap();
 
         self.hook(start_main_reloc, __libc_start_main);
         state
     }
 
+    /// Set an environment variable for the target program. Variables are
+    /// placed in the envp of states made by `entry_state` and
+    /// `entry_state_with`, symbolic values are the input "env:KEY"
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::{Radius, SimArg};
+    /// let mut radius = Radius::new("/bin/sh");
+    /// radius.set_env("LICENSE_KEY", SimArg::Symbolic(32));
+    /// radius.set_env("HOME", SimArg::Concrete("/root".to_owned()));
+    /// let mut state = radius.entry_state();
+    /// ```
+    pub fn set_env(&mut self, key: &str, value: SimArg) {
+        self.env.retain(|(k, _)| k != key);
+        self.env.push((key.to_owned(), value));
+    }
+
+    /// Add a file at `path` to the filesystem of new states, so opening
+    /// it with open or fopen and reading it gets `content`. Symbolic
+    /// contents are the input `file:PATH`
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::{Radius, SimArg};
+    /// let mut radius = Radius::new("/bin/sh");
+    /// radius.set_file("/flag.txt", SimArg::Symbolic(32));
+    /// radius.set_file("/etc/hostname", SimArg::Bytes(b"box\n".to_vec()));
+    /// let mut state = radius.entry_state();
+    /// ```
+    pub fn set_file(&mut self, path: &str, content: SimArg) {
+        self.files.retain(|(p, _)| p != path);
+        self.files.push((path.to_owned(), content));
+    }
+
+    // add the files set with set_file to the filesystem of the state
+    fn add_files(&self, state: &mut State) {
+        for (path, content) in &self.files {
+            let data = content.bytes(state, &format!("file:{}", path));
+            state.filesystem.add_file(path, &data);
+        }
+    }
+
+    /// Set the value `name` of the registry `key` of new states, for the
+    /// Reg* sims of Windows targets. Symbolic values are the input
+    /// `registry:KEY\NAME`
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::{Radius, RegistryValue};
+    /// let mut radius = Radius::new("/bin/sh");
+    /// let key = r"HKLM\SOFTWARE\Microsoft\Windows NT\CurrentVersion";
+    /// radius.set_registry(key, "ProductName", RegistryValue::String("Windows 10 Pro".to_owned()));
+    /// radius.set_registry(r"HKCU\Software\Implant", "Config", RegistryValue::Symbolic(16));
+    /// let mut state = radius.entry_state();
+    /// ```
+    pub fn set_registry(&mut self, key: &str, name: &str, value: RegistryValue) {
+        let path = windows::registry_path(key);
+        self.registry
+            .retain(|(k, n, _)| windows::registry_path(k) != path || !n.eq_ignore_ascii_case(name));
+        self.registry.push((key.to_owned(), name.to_owned(), value));
+    }
+
+    // add the registry values set with set_registry to the state
+    fn add_registry(&self, state: &mut State) {
+        for (key, name, value) in &self.registry {
+            let bytes = |b: &[u8]| b.iter().map(|b| vc(*b as u64)).collect::<Vec<Value>>();
+            let (kind, data) = match value {
+                RegistryValue::String(s) => {
+                    let mut data = bytes(s.as_bytes());
+                    data.push(vc(0));
+                    (windows::REG_SZ, data)
+                }
+                RegistryValue::Dword(d) => (windows::REG_DWORD, bytes(&d.to_le_bytes())),
+                RegistryValue::Binary(b) => (windows::REG_BINARY, bytes(b)),
+                RegistryValue::Symbolic(n) => {
+                    let source = format!("registry:{}\\{}", key, name);
+                    (windows::REG_BINARY, state.input_bytes(&source, 0, *n))
+                }
+            };
+            windows::set_registry_value(state, key, name, kind, &data);
+        }
+    }
+
+    /// Set only the envp of the state to `env`, leaving argc and argv
+    /// untouched. The array is allocated on the heap
+    pub fn set_envp(&mut self, state: &mut State, env: &[Value]) {
+        let ptrlen = state.memory.bits / 8;
+        let array = state.memory.alloc(&vc((env.len() as u64 + 1) * ptrlen));
+        write_strings(state, "env", &env, vc(array));
+        state.registers.set_with_alias("A2", vc(array));
+    }
+
     /// Set argv and env with arrays of values
     pub fn set_argv_env(&mut self, state: &mut State, args: &[Value], env: &[Value]) {
         // we write args to both regs and stack
@@ -303,58 +1002,81 @@ impl Radius {
         let types = ["argv", "env"];
         let mut current = sp + Value::Concrete(ptrlen as u64, 0);
         for (i, strings) in [args, env].iter().enumerate() {
-            state
-                .context
-                .insert(types[i].to_owned(), vec![current.clone()]);
             let alias = format!("A{}", i + 1);
             state.registers.set_with_alias(&alias, current.clone());
-            for string in strings.iter() {
-                let addr = state
-                    .memory
-                    .alloc(&Value::Concrete((string.size() / 8) as u64 + 1, 0));
-
-                state.memory_write_value(
-                    &Value::Concrete(addr, 0),
-                    string,
-                    string.size() as usize / 8,
-                );
-
-                state.memory.write_value(
-                    addr + (string.size() / 8) as u64,
-                    &Value::Concrete(0, 0),
-                    1,
-                );
-
-                state.memory_write_value(&current, &Value::Concrete(addr, 0), ptrlen);
-                current = current + Value::Concrete(ptrlen as u64, 0);
-            }
-            state.memory_write_value(&current, &Value::Concrete(0, 0), ptrlen);
-            current = current + Value::Concrete(ptrlen as u64, 0);
+            current = write_strings(state, types[i], strings, current);
         }
     }
 
     /// A default initial state
     pub fn init_state(&mut self) -> State {
-        State::new(
+        let mut state = State::new(
             &mut self.r2api,
             self.eval_max,
             self.debug,
             false,
             self.check,
             self.strict,
-        )
+        );
+        if state.info.bin.bintype == "pe" {
+            windows::setup_environment(&mut state);
+        }
+        if self.anti_debug {
+            antidebug::add_clean_files(&mut state);
+        }
+        state.solver.log = self.query_log.clone();
+        state.solver.backend = self.backend.clone();
+        state.primitives = self.primitives.clone();
+        state.types = self.types.clone();
+        state.watchpoints = self.watchpoints.clone();
+        state.max_length = self.max_length;
+        state.set_heap_check(self.heap_check);
+        state.memory.address_policy = self.address_policy;
+        state.memory.max_string = self.max_string;
+        state.solver.set_timeout(self.query_timeout);
+        self.add_files(&mut state);
+        self.add_registry(&mut state);
+        if let Some(max_length) = self.stdin_stream {
+            state.symbolic_stream(0, max_length);
+        }
+        if let Some(uninit) = self.uninit {
+            state.set_uninit(uninit);
+        }
+        state
     }
 
     /// A "blank" state with uninitialized values set to be symbolic
     pub fn blank_state(&mut self) -> State {
-        State::new(
+        let mut state = State::new(
             &mut self.r2api,
             self.eval_max,
             self.debug,
             true,
             self.check,
             self.strict,
-        )
+        );
+        if state.info.bin.bintype == "pe" {
+            windows::setup_environment(&mut state);
+        }
+        if self.anti_debug {
+            antidebug::add_clean_files(&mut state);
+        }
+        state.solver.log = self.query_log.clone();
+        state.solver.backend = self.backend.clone();
+        state.primitives = self.primitives.clone();
+        state.types = self.types.clone();
+        state.watchpoints = self.watchpoints.clone();
+        state.max_length = self.max_length;
+        state.set_heap_check(self.heap_check);
+        state.memory.address_policy = self.address_policy;
+        state.memory.max_string = self.max_string;
+        state.solver.set_timeout(self.query_timeout);
+        self.add_files(&mut state);
+        self.add_registry(&mut state);
+        if let Some(max_length) = self.stdin_stream {
+            state.symbolic_stream(0, max_length);
+        }
+        state
     }
 
     /// A blank state except for PC and SP
@@ -393,114 +1115,839 @@ impl Radius {
     /// radius.hook(0x400cb0, callback);
     /// ```
     pub fn hook(&mut self, addr: u64, hook_callback: HookMethod) {
-        self.processor
-            .hooks
-            .entry(addr)
-            .or_insert(vec![])
-            .push(hook_callback);
+        self.processor.add_hook(addr, Hook::new(hook_callback));
     }
 
-    /// Hook an address with an esil expression. The instruction
-    /// at the address is skipped if the last value on the stack is nonzero
+    /// Hook an address with a callback that decides what happens with the
+    /// rest of the chain. Hooks with higher priorities run first, `hook`
+    /// adds hooks with priority 0, and a sim at the address runs last
     ///
     /// ## Arguments
     /// * `addr` - the address to hook
-    /// * `esil` - the ESIL expression to evaluate
-    pub fn esil_hook(&mut self, addr: u64, esil: &str) {
+    /// * `priority` - the priority of the hook in the chain
+    /// * `hook_callback` - the function to call once the address is reached
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::{Radius, State, vc};
+    /// use radius2::processor::HookAction;
+    /// let mut radius = Radius::new("/bin/sh");
+    ///
+    /// fn log(state: &mut State) -> HookAction {
+    ///     println!("reached 0x400cb0");
+    ///     HookAction::Continue
+    /// }
+    /// fn replace(state: &mut State) -> HookAction {
+    ///     state.registers.set("rax", vc(0x1337));
+    ///     HookAction::Replace // skip the instruction or sim
+    /// }
+    /// radius.hook_chained(0x400cb0, 10, log);
+    /// radius.hook_chained(0x400cb0, 0, replace);
+    /// ```
+    pub fn hook_chained(&mut self, addr: u64, priority: i32, hook_callback: ChainHookMethod) {
         self.processor
-            .esil_hooks
-            .entry(addr)
-            .or_insert(vec![])
-            .push(esil.to_owned());
+            .add_hook(addr, Hook::chained(priority, hook_callback));
     }
 
-    /// Hook a symbol with a callback that is passed each state that reaches it
-    pub fn hook_symbol(&mut self, sym: &str, hook_callback: HookMethod) {
-        let addr = self.get_address(sym).unwrap();
-        self.hook(addr, hook_callback);
+    /// Hook a function with a callback that is passed its args, recovered
+    /// from the analysis of the function so internal functions can be hooked
+    /// like imports. The return value of the callback specifies whether the
+    /// function should be executed or skipped
+    ///
+    /// ## Arguments
+    /// * `addr` - the address of the function to hook
+    /// * `hook_callback` - the function to call with the args
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::{Radius, State, Value};
+    /// let mut radius = Radius::new("../tests/r100");
+    ///
+    /// fn check(state: &mut State, args: &[Value]) -> bool {
+    ///     println!("check({:?})", args);
+    ///     true
+    /// }
+    /// radius.hook_args(0x004006fd, check);
+    /// ```
+    pub fn hook_args(&mut self, addr: u64, hook_callback: ArgsHookMethod) {
+        self.add_hook(addr, Hook::with_args(hook_callback));
     }
 
-    // internal method to register import sims
-    fn register_sims(r2api: &mut R2Api, processor: &mut Processor, sim_all: bool) {
-        let sims = get_sims();
-        let files = r2api.get_files().unwrap();
-
-        for file in files {
-            if file.uri.starts_with("null://") {
-                continue;
+    /// Add a `Hook` to an address, with a guard, hit limits, or priority
+    pub fn add_hook(&mut self, addr: u64, mut hook: Hook) {
+        if let HookCallback::Args(_) = hook.callback {
+            if hook.args.is_empty() {
+                hook.args = self.function_args(addr);
             }
+        }
+        self.processor.add_hook(addr, hook);
+    }
 
-            r2api.set_file_fd(file.fd);
-            let symbols = r2api.get_imports().unwrap();
-            let mut symmap: HashMap<String, u64> = HashMap::new();
-
-            for symbol in symbols {
-                symmap.insert(symbol.name, symbol.plt);
-            }
+    /// Get the locations of the args of the function at `addr` on entry.
+    /// Register and stack args found by the analysis are used, otherwise
+    /// the number of args is placed with the calling convention. Without
+    /// a function at `addr` every register arg of the convention is used
+    pub fn function_args(&mut self, addr: u64) -> Vec<ArgLocation> {
+        let cc = match self.r2api.get_cc(addr) {
+            Ok(cc) => cc,
+            Err(_) => return vec![],
+        };
+        let info = self
+            .r2api
+            .get_functions()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|f| f.offset == addr);
+
+        let info = if let Some(info) = info {
+            info
+        } else {
+            // not analyzed, use every register arg
+            return cc.args.into_iter().map(ArgLocation::Register).collect();
+        };
 
-            // TODO expand this to handle other symbols
-            for sim in &sims {
-                let addropt = symmap.remove(&sim.symbol);
-                if let Some(addr) = addropt {
-                    processor.sims.insert(addr, sim.to_owned());
-                }
-            }
+        let bin = &self.r2api.info.bin;
+        let ptr = if bin.bits < 32 { 4 } else { bin.bits / 8 };
+        let ret = if Arch::from_info(bin).pushes_return() {
+            ptr
+        } else {
+            0
+        };
 
-            if sim_all {
-                for name in symmap.keys() {
-                    // we are gonna go with zero by default
-                    processor.sims.insert(
-                        symmap[name],
-                        Sim {
-                            symbol: name.to_owned(),
-                            function: zero,
-                            arguments: 0,
-                        },
-                    );
+        let mut regs: Vec<String> = info
+            .regvars
+            .iter()
+            .map(|var| var.r#ref.base.to_owned())
+            .filter(|reg| cc.args.contains(reg))
+            .collect();
+        regs.sort_by_key(|reg| cc.args.iter().position(|arg| arg == reg));
+        regs.dedup();
+
+        // bp points to the saved bp that is pushed on entry
+        let mut offsets: Vec<u64> = info
+            .bpvars
+            .iter()
+            .filter(|var| var.kind == "arg")
+            .map(|var| var.r#ref.offset.wrapping_sub(ptr))
+            .chain(
+                info.spvars
+                    .iter()
+                    .filter(|var| var.kind == "arg")
+                    .map(|var| var.r#ref.offset),
+            )
+            .collect();
+        offsets.sort_unstable();
+        offsets.dedup();
+
+        let mut args: Vec<ArgLocation> = regs.into_iter().map(ArgLocation::Register).collect();
+        args.extend(offsets.into_iter().map(ArgLocation::Stack));
+
+        if args.is_empty() {
+            for index in 0..info.nargs as usize {
+                if let Some(reg) = cc.args.get(index) {
+                    args.push(ArgLocation::Register(reg.to_owned()));
+                } else {
+                    args.push(ArgLocation::Stack(cc.stack_offset(index, ptr, ret)));
                 }
             }
         }
+        args
+    }
 
-        // back to main file
-        r2api.set_file_fd(3);
+    /// Hook the function `sym` only when it is called from the function
+    /// `caller`, using the call stack of each state. Returning false
+    /// from the callback replaces a sim of `sym` for these calls
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::{Radius, State, vc};
+    /// let mut radius = Radius::new("../tests/r100");
+    ///
+    /// fn callback(state: &mut State) -> bool {
+    ///     state.registers.set_with_alias("A0", vc(0));
+    ///     true
+    /// }
+    /// radius.hook_caller("sym.imp.fgets", "main", callback).unwrap();
+    /// ```
+    pub fn hook_caller(
+        &mut self,
+        sym: &str,
+        caller: &str,
+        hook_callback: HookMethod,
+    ) -> R2Result<()> {
+        let addr = self.get_address(sym)?;
+        let (start, end) = self.function_range(caller)?;
+        self.add_hook(addr, Hook::new(hook_callback).called_from(start, end));
+        Ok(())
     }
 
-    /// Register a trap to call the provided `SimMethod`
-    pub fn trap(&mut self, trap_num: u64, sim: SimMethod) {
-        self.processor.traps.insert(trap_num, sim);
+    /// Get the code range [start, end) of the function `sym`
+    pub fn function_range(&mut self, sym: &str) -> R2Result<(u64, u64)> {
+        let addr = self.get_address(sym)?;
+        self.r2api
+            .get_functions()?
+            .iter()
+            .find(|f| f.offset == addr)
+            .map(|f| (f.minbound, f.maxbound))
+            .ok_or_else(|| format!("no function at {}", sym))
     }
 
-    /// Register a `SimMethod` for the provided function address
+    /// Check that the function at `addr` satisfies `post` when it returns
+    /// from an entry that satisfies `pre`. Returns that can violate the
+    /// postcondition are added to the violations with a counterexample
     ///
     /// ## Arguments
-    /// * `addr` - address of the function to simulate (usually the PLT address)
-    /// * `sim` - Sim struct containing the function name, implementation, and arg count
+    /// * `addr` - the address of the function
+    /// * `pre` - takes the args and returns the condition assumed on entry
+    /// * `post` - takes the args and return value and returns the condition to check
     ///
     /// ## Example
     /// ```
-    /// use radius2::{Radius, State, Sim, Value, vc};
-    /// let mut radius = Radius::new("/bin/sh");
-    /// let scanf = radius.get_address("__isoc99_scanf").unwrap();
+    /// use radius2::radius::Radius;
+    /// use radius2::value::vc;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.symbolize_memory("flag", 0x100000, 12);
+    /// state.registers.set("rdi", vc(0x100000));
     ///
-    /// fn scanf_sim(state: &mut State, args: &[Value]) -> Value {
-    ///     state.memory_write_value(&args[1], &vc(42), 8);
-    ///     vc(1)
-    /// }
+    /// // the check should fail for any input that is not the flag
+    /// radius.add_contract(0x004006fd, |_, args| args[0].eq(&vc(0x100000)), |_, _, ret| {
+    ///     !ret.slice(31, 0).eq(&vc(0))
+    /// });
+    /// radius.run(state, 1);
+    /// let violations = radius.violations();
+    /// assert_eq!(violations[0].inputs["flag"], b"Code_Talkers");
+    /// ```
+    pub fn add_contract(&mut self, addr: u64, pre: Precondition, post: Postcondition) {
+        let args = self.function_args(addr);
+        let contract = Contract {
+            addr,
+            args,
+            pre,
+            post,
+        };
+        self.processor.contracts.insert(addr, contract);
+    }
+
+    /// Add an invariant, a condition that must hold after every instruction.
+    /// Copies of states that can violate it are kept, constrained to
+    /// violate it, and the states continue constrained to satisfy it
     ///
-    /// radius.simulate(scanf, Sim{
-    ///     symbol: "scanf".to_owned(),
-    ///     function: scanf_sim,
-    ///     arguments: 2
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// use radius2::vc;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.symbolize_memory("flag", 0x100000, 12);
+    /// state.registers.set("rdi", vc(0x100000));
+    ///
+    /// // the stack pointer stays in the stack
+    /// radius.add_invariant("stack", |state| {
+    ///     let sp = state.registers.get_with_alias("SP");
+    ///     sp.ult(&vc(0xfff00000 + 0xf0000)) & sp.ugte(&vc(0xfff00000))
     /// });
+    /// radius.run_until(state, 0x004007a1, &[0x00400790]);
+    /// assert!(radius.invariant_violations().is_empty());
     /// ```
-    pub fn simulate(&mut self, addr: u64, sim: Sim) {
-        self.processor.sims.insert(addr, sim);
+    pub fn add_invariant(&mut self, name: &str, check: InvariantCheck) {
+        self.processor.invariants.push(Invariant {
+            name: name.to_owned(),
+            check,
+            per_block: false,
+        });
+    }
+
+    /// Add an invariant only checked after the last instruction of each
+    /// block, which is cheaper than after every instruction
+    pub fn add_block_invariant(&mut self, name: &str, check: InvariantCheck) {
+        self.processor.invariants.push(Invariant {
+            name: name.to_owned(),
+            check,
+            per_block: true,
+        });
+    }
+
+    /// Get the states that violated an invariant
+    pub fn invariant_violations(&self) -> &[InvariantViolation] {
+        &self.processor.invariant_violations
+    }
+
+    /// Add an assertion, checked whenever a state reaches its address
+    pub fn add_assertion(&mut self, assertion: Assertion) {
+        self.processor
+            .assertions
+            .entry(assertion.addr)
+            .or_default()
+            .push(assertion);
+    }
+
+    /// Get the violations of the assertions found so far
+    pub fn counterexamples(&self) -> &[Counterexample] {
+        &self.processor.counterexamples
+    }
+
+    /// Explore every path from `state` until a state reaching one of the
+    /// `assertions` can violate it, returning it with the inputs reaching it.
+    /// None means no explored path violates them
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::invariant::Assertion;
+    /// use radius2::radius::Radius;
+    /// use radius2::vc;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.symbolize_memory("flag", 0x100000, 12);
+    /// state.registers.set("rdi", vc(0x100000));
+    /// radius.avoid(&[0x00400790]);
+    ///
+    /// // the flag is never accepted, which one flag violates
+    /// let rejected = Assertion::new("rejected", 0x004007a1, |_| vc(0));
+    /// let counterexample = radius.verify(state, &[rejected]).unwrap();
+    /// assert_eq!(counterexample.inputs["flag"], b"Code_Talkers");
+    /// ```
+    pub fn verify(&mut self, state: State, assertions: &[Assertion]) -> Option<Counterexample> {
+        for assertion in assertions {
+            self.add_assertion(assertion.to_owned());
+        }
+        self.processor.counterexamples.clear();
+        self.processor.stop_at_counterexample = true;
+        self.processor.run(state, RunMode::Multiple);
+        self.processor.stop_at_counterexample = false;
+        self.processor.counterexamples.first().cloned()
+    }
+
+    /// Get the returns that violated a contract
+    pub fn violations(&self) -> Vec<Violation> {
+        self.processor.violations.borrow().clone()
+    }
+
+    /// Run the solved inputs of the state natively and report how the binary
+    /// diverged from the state, eg. from an unsound sim or environment. The
+    /// args, environment, and stdin of the inputs are passed to the binary,
+    /// and the contents the state has for `input_files` are written to those
+    /// paths first. If the state did not exit, the binary is debugged to
+    /// check that it reaches the address the state stopped at
+    ///
+    /// ## Example
+    /// ```no_run
+    /// use radius2::radius::{Radius, RadiusOption};
+    /// let options = [RadiusOption::SymbolicStdin(32)];
+    /// let mut radius = Radius::new_with_options(Some("../tests/r100"), &options);
+    /// let state = radius.entry_state();
+    /// let mut found = radius.run_until(state, 0x004007a1, &[0x00400790]).unwrap();
+    /// let validation = radius.validate(&mut found, &[]);
+    /// for divergence in &validation.divergences {
+    ///     println!("diverged: {:?}", divergence);
+    /// }
+    /// ```
+    pub fn validate(&mut self, state: &mut State, input_files: &[&str]) -> Validation {
+        let mut validation = Validation::default();
+        if !state.is_sat() {
+            println!("warning: can't validate an unsat state");
+            return validation;
+        }
+        let inputs = state.dump_inputs();
+        let prediction = Prediction::new(state, &inputs, input_files);
+        for (path, content) in &prediction.files {
+            if let Err(e) = fs::write(path, content) {
+                println!("warning: could not write {}: {}", path, e);
+            }
+        }
+
+        let path = self.r2api.info.core.file.to_owned();
+        match run_native(&path, &inputs) {
+            Ok((code, stdout)) => validation.divergences = prediction.compare(code, &stdout),
+            Err(e) => println!("warning: could not run {}: {}", path, e),
+        }
+        if let Some(addr) = prediction.stopped {
+            if !self.reaches_natively(&path, &inputs, addr) {
+                validation.divergences.push(Divergence::Unreached(addr));
+            }
+        }
+        validation.inputs = inputs;
+        validation
+    }
+
+    // debug the binary with the inputs, checking that it hits addr
+    fn reaches_natively(&self, path: &str, inputs: &BTreeMap<String, Vec<u8>>, addr: u64) -> bool {
+        let stdin = env::temp_dir().join(format!("radius-stdin-{}", process::id()));
+        let stdin_bytes = inputs.get("stdin").cloned().unwrap_or_default();
+        if fs::write(&stdin, stdin_bytes).is_err() {
+            return false;
+        }
+
+        // a rarun2 profile passes the args and stdin to the debuggee, its
+        // path stays the same so spawning r2 doesn't take new args each time
+        let profile = env::temp_dir().join(format!("radius-{}.rr2", process::id()));
+        let mut directives = format!("stdin={}\n", stdin.display());
+        for (i, arg) in input_args(inputs).iter().enumerate() {
+            directives += &format!("arg{}={}\n", i + 1, arg);
+        }
+        if fs::write(&profile, directives).is_err() {
+            return false;
+        }
+        let profile_path = profile.to_string_lossy();
+        let opts = vec!["-d", "-r", &profile_path];
+
+        let mut debugger = R2Api::new(Some(path), Some(opts));
+        // the debuggee may be loaded somewhere else
+        let target = addr
+            .wrapping_sub(self.r2api.info.bin.baddr)
+            .wrapping_add(debugger.info.bin.baddr);
+        debugger.breakpoint(target).unwrap_or_default();
+        debugger.cont().unwrap_or_default();
+        let pc = debugger.cmd("dr?PC").unwrap_or_default();
+        let pc = u64::from_str_radix(pc.trim().trim_start_matches("0x"), 16).ok();
+        debugger.close();
+        let _ = fs::remove_file(&stdin);
+        let _ = fs::remove_file(&profile);
+        pc == Some(target)
+    }
+
+    /// Get the states deferred at the bound of `RadiusOption::MaxLoopIters`,
+    /// which can be run further after raising the bound
+    pub fn deferred(&self) -> &[State] {
+        &self.processor.deferred
+    }
+
+    /// Get the states stopped by `RadiusOption::CheckPcControl`, whose
+    /// symbolic pc can be solved for any target address
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::{Radius, RadiusOption};
+    /// let options = [RadiusOption::CheckPcControl(true)];
+    /// let mut radius = Radius::new_with_options(Some("../tests/r100"), &options);
+    /// let state = radius.call_state(0x004006fd);
+    /// radius.run_until(state, 0x004007a1, &[0x00400790]);
+    /// for state in radius.unconstrained() {
+    ///     println!("{:?}", state.context["pc_control"]);
+    /// }
+    /// ```
+    pub fn unconstrained(&self) -> &[State] {
+        &self.processor.unconstrained
+    }
+
+    /// Get the crashes, controlled pcs, write primitives, and contract
+    /// violations found so far as findings, with duplicate reports merged
+    pub fn findings(&mut self) -> Findings {
+        let mut findings = Findings::new();
+        for state in &mut self.processor.crashes {
+            if let Some(finding) = Finding::from_crash(state) {
+                findings.add(finding);
+            }
+        }
+        for state in &mut self.processor.unconstrained {
+            if let Some(finding) = Finding::from_pc_control(state) {
+                findings.add(finding);
+            }
+        }
+        for primitive in self.write_primitives() {
+            findings.add(Finding::from_primitive(&primitive));
+        }
+        for violation in self.violations() {
+            findings.add(Finding::from_violation(&violation));
+        }
+        findings
+    }
+
+    /// Print a line for each contract violation with its counterexample
+    pub fn print_violations(&self) {
+        for v in self.processor.violations.borrow().iter() {
+            let inputs: Vec<String> = v
+                .inputs
+                .iter()
+                .map(|(name, bytes)| format!("{}={:?}", name, String::from_utf8_lossy(bytes)))
+                .collect();
+            println!(
+                "0x{:08x} -> 0x{:08x}  args {:x?}  ret 0x{:x}  {}",
+                v.addr,
+                v.pc,
+                v.args,
+                v.ret,
+                inputs.join(" ")
+            );
+        }
+    }
+
+    /// Replace the iterations of the loop with the head at `addr` with
+    /// `summary`, applying its memory effect at once
+    pub fn summarize_loop(&mut self, addr: u64, summary: LoopSummary) {
+        self.processor.loops.insert(addr, summary);
+    }
+
+    /// Hook an address with an esil expression. The instruction
+    /// at the address is skipped if the last value on the stack is nonzero
+    ///
+    /// ## Arguments
+    /// * `addr` - the address to hook
+    /// * `esil` - the ESIL expression to evaluate
+    pub fn esil_hook(&mut self, addr: u64, esil: &str) {
+        self.processor
+            .esil_hooks
+            .entry(addr)
+            .or_insert(vec![])
+            .push(esil.to_owned());
+    }
+
+    /// Hook a symbol with a callback that is passed each state that reaches it
+    pub fn hook_symbol(&mut self, sym: &str, hook_callback: HookMethod) {
+        let addr = self.get_address(sym).unwrap();
+        self.hook(addr, hook_callback);
+    }
+
+    /// Hook every instruction with a callback that is passed the `State`,
+    /// before the hooks of the address
+    pub fn hook_all(&mut self, hook_callback: HookMethod) {
+        self.processor.add_step_hook(Hook::new(hook_callback));
+    }
+
+    /// Call `callback` with the address and value of each read of memory
+    /// in `range`, including the reads of sims
+    pub fn hook_mem_read(&mut self, range: Range<u64>, callback: MemoryWatchMethod) {
+        self.watchpoints.borrow_mut().memory.push(MemoryWatch {
+            range,
+            access: Access::Read,
+            callback,
+        });
+    }
+
+    /// Call `callback` with the address and value of each write of memory
+    /// in `range`, including the writes of sims
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::{Radius, State, Value, vc};
+    /// let mut radius = Radius::new("../tests/r100");
+    ///
+    /// fn callback(state: &mut State, addr: &Value, value: &Value) {
+    ///     let pc = state.registers.get_pc();
+    ///     println!("{:?} wrote {:?} to {:?}", pc, value, addr);
+    /// }
+    /// radius.hook_mem_write(0x100000..0x100100, callback);
+    /// ```
+    pub fn hook_mem_write(&mut self, range: Range<u64>, callback: MemoryWatchMethod) {
+        self.watchpoints.borrow_mut().memory.push(MemoryWatch {
+            range,
+            access: Access::Write,
+            callback,
+        });
+    }
+
+    /// Call `callback` with the name and value of each register written by
+    /// an instruction that overlaps `register`
+    pub fn hook_register(&mut self, register: &str, callback: RegisterWatchMethod) {
+        self.watchpoints.borrow_mut().registers.push(RegisterWatch {
+            register: register.to_owned(),
+            callback,
+        });
+    }
+
+    /// Hook every instruction with the given mnemonic, this must be
+    /// done before the instructions are first executed
+    ///
+    /// ## Arguments
+    /// * `mnemonic` - the instruction mnemonic, eg. `rdtsc`
+    /// * `hook_callback` - the function to call once an instruction is reached
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::{Radius, State, vc};
+    /// let mut radius = Radius::new("/bin/sh");
+    ///
+    /// fn callback(state: &mut State) -> bool {
+    ///     state.registers.set("eax", vc(0));
+    ///     false // skip the instruction
+    /// }
+    /// radius.hook_instruction("rdrand", callback);
+    /// ```
+    pub fn hook_instruction(&mut self, mnemonic: &str, hook_callback: HookMethod) {
+        self.processor
+            .add_instruction_hook(mnemonic, Hook::new(hook_callback));
+    }
+
+    /// Execute instructions that ESIL can't lift with `handler`, matched by
+    /// mnemonic or byte pattern. This must be done before the instructions
+    /// are first executed
+    ///
+    /// ## Arguments
+    /// * `opcode` - the instructions to handle
+    /// * `handler` - the function that executes a matched instruction
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::{Radius, State, vc};
+    /// use radius2::processor::OpcodeMatch;
+    /// use radius2::r2_api::Instruction;
+    /// let mut radius = Radius::new("/bin/sh");
+    ///
+    /// // a made up 4 byte op 0f 0b xx yy that sets rax to yyxx
+    /// fn handler(state: &mut State, instr: &Instruction) {
+    ///     let bytes = radius2::r2_api::hex_decode(&instr.bytes);
+    ///     state.registers.set("rax", vc(bytes[2] as u64 | (bytes[3] as u64) << 8));
+    /// }
+    /// let opcode = OpcodeMatch::Bytes(vec![0x0f, 0x0b, 0, 0], vec![0xff, 0xff, 0, 0]);
+    /// radius.handle_opcode(opcode, handler);
+    /// ```
+    pub fn handle_opcode(&mut self, opcode: OpcodeMatch, handler: OpcodeHandler) {
+        self.processor.opcode_handlers.push((opcode, handler));
+    }
+
+    /// Lift every instruction with `mnemonic` to `esil` instead of the
+    /// ESIL from r2, to work around lifting bugs. `{0}`, `{1}`, ... are
+    /// replaced with the operands of each instruction, so operands
+    /// should be registers or immediates
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::Radius;
+    /// let mut radius = Radius::new("/bin/sh");
+    /// // bt reg, reg sets the carry flag to the bit
+    /// radius.override_esil("bt", "1,{1},{0},>>,&,cf,:=");
+    /// ```
+    pub fn override_esil(&mut self, mnemonic: &str, esil: &str) {
+        self.processor.override_esil(mnemonic, esil);
+    }
+
+    // internal method to register import sims
+    fn register_sims(
+        r2api: &mut R2Api,
+        processor: &mut Processor,
+        sims: &[Sim],
+        sim_all: bool,
+        policies: &ModulePolicies,
+    ) {
+        let files = r2api.get_files().unwrap();
+
+        // imports without a library name are attributed to the loaded library exporting them
+        let mut exporters: HashMap<String, String> = HashMap::new();
+        if !policies.is_empty() {
+            let main = r2api.info.core.file.to_owned();
+            for file in files
+                .iter()
+                .filter(|f| f.uri != main && !f.uri.starts_with("null://"))
+            {
+                r2api.set_file_fd(file.fd);
+                let module = Path::new(&file.uri)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                for export in r2api.get_exports().unwrap_or_default() {
+                    exporters
+                        .entry(export.name)
+                        .or_insert_with(|| module.clone());
+                }
+            }
+        }
+
+        for file in files {
+            if file.uri.starts_with("null://") {
+                continue;
+            }
+
+            r2api.set_file_fd(file.fd);
+            let symbols = r2api.get_imports().unwrap();
+            let mut symmap: HashMap<String, u64> = HashMap::new();
+            let mut stubs = vec![];
+
+            for symbol in symbols {
+                let module = if symbol.libname.is_empty() {
+                    exporters.get(&symbol.name).cloned().unwrap_or_default()
+                } else {
+                    symbol.libname.to_owned()
+                };
+                match policies.policy(&module, &symbol.name) {
+                    Some(ModulePolicy::Stub) => stubs.push((symbol.name, symbol.plt)),
+                    Some(ModulePolicy::Sims) => {
+                        if !sims.iter().any(|s| s.symbol == symbol.name) {
+                            stubs.push((symbol.name.to_owned(), symbol.plt));
+                        }
+                        symmap.insert(symbol.name, symbol.plt);
+                    }
+                    Some(ModulePolicy::Execute) => {}
+                    None => {
+                        symmap.insert(symbol.name, symbol.plt);
+                    }
+                }
+            }
+
+            for (name, addr) in stubs {
+                processor.sims.insert(
+                    addr,
+                    Sim {
+                        symbol: name,
+                        function: zero,
+                        arguments: 0,
+                    },
+                );
+            }
+
+            // TODO expand this to handle other symbols
+            for sim in sims {
+                let addropt = symmap.remove(&sim.symbol);
+                if let Some(addr) = addropt {
+                    processor.sims.insert(addr, sim.to_owned());
+                }
+            }
+
+            if sim_all {
+                for name in symmap.keys() {
+                    // we are gonna go with zero by default
+                    processor.sims.insert(
+                        symmap[name],
+                        Sim {
+                            symbol: name.to_owned(),
+                            function: zero,
+                            arguments: 0,
+                        },
+                    );
+                }
+            }
+        }
+
+        // back to main file
+        r2api.set_file_fd(3);
+    }
+
+    /// Register a trap to call the provided `SimMethod`
+    pub fn trap(&mut self, trap_num: u64, sim: SimMethod) {
+        self.processor.traps.insert(trap_num, sim);
+    }
+
+    /// Register a `SimMethod` for the provided function address
+    ///
+    /// ## Arguments
+    /// * `addr` - address of the function to simulate (usually the PLT address)
+    /// * `sim` - Sim struct containing the function name, implementation, and arg count
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::{Radius, State, Sim, Value, vc};
+    /// let mut radius = Radius::new("/bin/sh");
+    /// let scanf = radius.get_address("__isoc99_scanf").unwrap();
+    ///
+    /// fn scanf_sim(state: &mut State, args: &[Value]) -> Value {
+    ///     state.memory_write_value(&args[1], &vc(42), 8);
+    ///     vc(1)
+    /// }
+    ///
+    /// radius.simulate(scanf, Sim{
+    ///     symbol: "scanf".to_owned(),
+    ///     function: scanf_sim,
+    ///     arguments: 2
+    /// });
+    /// ```
+    pub fn simulate(&mut self, addr: u64, sim: Sim) {
+        self.processor.sims.insert(addr, sim);
+    }
+
+    /// Replace the function at `addr` with a summary, which gets the args
+    /// of the calling convention and returns the value set as the return
+    /// value before returning to the caller, like a sim of an import
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::{Radius, vc};
+    /// let mut radius = Radius::new("../tests/r100");
+    /// radius.summarize(0x004006fd, |state, args| {
+    ///     let len = state.memory_strlen(&args[0], &vc(12));
+    ///     len.eq(&vc(12))
+    /// });
+    /// ```
+    pub fn summarize(&mut self, addr: u64, summary: SimMethod) {
+        let symbol = self
+            .r2api
+            .get_flag(addr)
+            .map(|f| f.trim().to_owned())
+            .ok()
+            .filter(|f| !f.is_empty())
+            .unwrap_or_else(|| format!("fcn.{:08x}", addr));
+        let arguments = self.function_args(addr).len();
+        self.simulate(addr, make_sim(&symbol, summary, arguments));
+    }
+
+    /// Resolve the API hashes given to the resolver function at `addr`, the
+    /// routine of shellcode that walks the export tables of the loaded modules
+    /// for the function whose name hashes to its first arg. Calls return the
+    /// address of the import or library export with that hash, or 0
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::Radius;
+    /// use radius2::sims::windows::ApiHash;
+    /// let mut radius = Radius::new("/bin/sh");
+    /// for resolver in radius.find_api_resolvers() {
+    ///     radius.resolve_api_hashes(resolver, ApiHash::Ror13);
+    /// }
+    /// ```
+    pub fn resolve_api_hashes(&mut self, addr: u64, hash: ApiHash) {
+        let apis = self.r2api.get_api_addresses().unwrap_or_default();
+        let hashes = apis
+            .iter()
+            .map(|(name, api)| (hash.hash(name) as u64, *api))
+            .collect();
+        self.summarize(addr, zero);
+        self.processor.api_hashes.insert(addr, hashes);
+    }
+
+    /// Find the functions that look like API hash resolvers, the ones that
+    /// get the PEB to walk the loaded modules and rotate to hash names
+    pub fn find_api_resolvers(&mut self) -> Vec<u64> {
+        let functions = self.r2api.get_functions().unwrap_or_default();
+        let mut resolvers = vec![];
+        for function in functions {
+            let instrs = self
+                .r2api
+                .disassemble_function(function.offset)
+                .unwrap_or_default();
+            let peb = instrs
+                .iter()
+                .any(|i| i.opcode.contains("fs:[0x30]") || i.opcode.contains("gs:[0x60]"));
+            let rotates = instrs
+                .iter()
+                .any(|i| i.opcode.starts_with("ror ") || i.opcode.starts_with("rol "));
+            if peb && rotates {
+                resolvers.push(function.offset);
+            }
+        }
+        resolvers
+    }
+
+    /// Skip calls to the function at `addr`, returning `ret` to the caller
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// radius.skip_call(0x004006fd, 0);
+    /// ```
+    pub fn skip_call(&mut self, addr: u64, ret: u64) {
+        self.summarize(addr, zero);
+        self.processor.skipped.insert(addr, ret);
     }
 
     /// Add a breakpoint at the provided address.
     /// This is where execution will stop after `run` is called
     pub fn breakpoint(&mut self, addr: u64) {
         self.processor.breakpoints.insert(addr);
+        if let Some(entry) = self.processor.instructions.get_mut(&addr) {
+            entry.flags.insert(InstructionFlag::Break);
+        }
+    }
+
+    /// Remove every breakpoint and address to avoid, eg. between
+    /// jobs run with the same instance
+    pub fn clear_breakpoints(&mut self) {
+        let breakpoints = mem::take(&mut self.processor.breakpoints);
+        let avoidpoints = mem::take(&mut self.processor.avoidpoints);
+        for addr in breakpoints.iter().chain(&avoidpoints) {
+            if let Some(entry) = self.processor.instructions.get_mut(addr) {
+                entry.flags.remove(&InstructionFlag::Break);
+                entry.flags.remove(&InstructionFlag::Avoid);
+            }
+        }
     }
 
     /// Add a mergepoint, an address where many states will be combined
@@ -509,27 +1956,228 @@ impl Radius {
         self.processor.mergepoints.insert(addr);
     }
 
-    /// Add addresses that will be avoided during execution. Any
-    /// `State` that reaches these addresses will be marked inactive
-    ///
-    /// ## Arguments
-    /// * `addrs` - slice of addresses to avoid during execution
-    pub fn avoid(&mut self, addrs: &[u64]) {
-        for addr in addrs {
-            self.processor.avoidpoints.insert(*addr);
+    /// Add mergepoints at the join points of the function containing
+    /// `addr`, the blocks reached by two or more forward edges, and
+    /// return their addresses. Loop heads are not join points
+    pub fn merge_joins(&mut self, addr: u64) -> Vec<u64> {
+        let blocks = self.r2api.get_blocks(addr).unwrap_or_default();
+        let mut preds: HashMap<u64, usize> = HashMap::new();
+        for block in &blocks {
+            for target in [block.jump, block.fail] {
+                if target > block.addr as i64 {
+                    *preds.entry(target as u64).or_default() += 1;
+                }
+            }
+        }
+        let mut joins: Vec<u64> = preds
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(join, _)| join)
+            .collect();
+        joins.sort_unstable();
+        for join in &joins {
+            self.mergepoint(*join);
+        }
+        joins
+    }
+
+    /// Like `run_until` but states are merged at the join points of the
+    /// function the state starts in, see `merge_joins`. Merged states have
+    /// ite values for the registers and memory that differ, which keeps
+    /// functions with many small branches from exploding into many states
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.symbolize_memory("flag", 0x100000, 12);
+    /// state.registers.set("rdi", radius2::vc(0x100000));
+    /// let found = radius.run_merging(state, 0x004007a1, &[0x00400790]);
+    /// assert!(found.is_some());
+    /// ```
+    pub fn run_merging(&mut self, state: State, target: u64, avoid: &[u64]) -> Option<State> {
+        if let Some(pc) = state.registers.get_pc().as_u64() {
+            self.merge_joins(pc);
+        }
+        self.run_until(state, target, avoid)
+    }
+
+    /// Merge the states returning from calls to the function at `addr`
+    /// into a single state before the caller continues. This contains
+    /// the state explosion from small functions with many paths
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("/bin/sh");
+    /// radius.merge_call(0x400cb0);
+    /// ```
+    pub fn merge_call(&mut self, addr: u64) {
+        self.processor.merge_calls.insert(addr);
+    }
+
+    /// Add addresses that will be avoided during execution. Any
+    /// `State` that reaches these addresses will be marked inactive
+    ///
+    /// ## Arguments
+    /// * `addrs` - slice of addresses to avoid during execution
+    pub fn avoid(&mut self, addrs: &[u64]) {
+        for addr in addrs {
+            self.processor.avoidpoints.insert(*addr);
+            if let Some(entry) = self.processor.instructions.get_mut(addr) {
+                entry.flags.insert(InstructionFlag::Avoid);
+            }
+        }
+    }
+
+    /// Call `callback` with each state dropped because there were more
+    /// than `RadiusOption::MaxStates`, eg. to log or save it
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::{Radius, RadiusOption};
+    /// use radius2::state::State;
+    /// let options = [RadiusOption::MaxStates(4)];
+    /// let mut radius = Radius::new_with_options(Some("../tests/r100"), &options);
+    /// radius.on_drop(|state: &State| {
+    ///     println!("dropped state at {:?}", state.registers.get_pc().as_u64());
+    /// });
+    /// ```
+    pub fn on_drop(&mut self, callback: DropCallback) {
+        self.processor.on_drop.push(callback);
+    }
+
+    /// Drop states that can't reach any of the `targets` over the call
+    /// graph and control flow graphs, before they are stepped. Analyze
+    /// the binary first so r2 knows its functions
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// radius.analyze(3);
+    /// radius.prune_unreachable(&[0x004007a1]);
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.symbolize_memory("flag", 0x100000, 12);
+    /// state.registers.set("rdi", radius2::vc(0x100000));
+    /// let found = radius.run_until(state, 0x004007a1, &[]);
+    /// assert!(found.is_some());
+    /// ```
+    pub fn prune_unreachable(&mut self, targets: &[u64]) {
+        let known = self.knowledge.as_ref().and_then(|kb| {
+            let reach = kb.reachability.iter().find(|r| r.targets == targets);
+            reach.cloned()
+        });
+        let reach = known.unwrap_or_else(|| Reachability::new(&mut self.r2api, targets));
+        if let Some(kb) = &mut self.knowledge {
+            kb.reachability.retain(|r| r.targets != targets);
+            kb.reachability.push(reach.clone());
+        }
+        self.processor.reachability = Some(reach);
+    }
+
+    /// Get a receiver of the events of runs from now on, see `EventBus`
+    pub fn subscribe(&mut self) -> Receiver<RunEvent> {
+        self.processor.bus.subscribe()
+    }
+
+    /// Get a receiver of the events at or above `level`, `EventLevel::Trace`
+    /// includes every instruction executed and the solver queries it made
+    pub fn subscribe_level(&mut self, level: EventLevel) -> Receiver<RunEvent> {
+        self.processor.bus.subscribe_level(level)
+    }
+
+    /// Stop runs that take longer than `timeout`, returning the states
+    /// found so far. `None` lets runs take as long as they need
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.processor.timeout = timeout;
+    }
+
+    /// Get total number of steps from all processors
+    pub fn get_steps(&self) -> u64 {
+        self.processor.steps
+            + self
+                .processors
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|p| p.steps)
+                .sum::<u64>()
+    }
+
+    /// Get the time spent on each instruction, if profiling is enabled
+    /// with `RadiusOption::Profile`
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::{Radius, RadiusOption};
+    /// let mut radius = Radius::new_with_options(Some("../tests/r100"), &[RadiusOption::Profile(true)]);
+    /// let state = radius.call_state(0x004006fd);
+    /// radius.run_until(state, 0x00400844, &[0x00400855]);
+    /// let hotspots = radius.get_profile().unwrap().hotspots(10);
+    /// radius.print_profile(10);
+    /// ```
+    pub fn get_profile(&self) -> Option<&Profile> {
+        self.processor.profile.as_ref()
+    }
+
+    /// Print a table of the `n` instructions that took the most time
+    pub fn print_profile(&self, n: usize) {
+        self.processor.print_profile(n)
+    }
+
+    /// Print the solver query stats for the `n` addresses with the most
+    /// solver time, if enabled with `RadiusOption::QueryLog`
+    pub fn print_query_stats(&self, n: usize) {
+        if let Some(log) = &self.query_log {
+            log.borrow().print_stats(n);
+        }
+    }
+
+    /// Write the SMT-LIB of the slowest solver queries to `dir`
+    pub fn dump_queries(&self, dir: &str) -> std::io::Result<Vec<String>> {
+        if let Some(log) = &self.query_log {
+            log.borrow().dump(dir)
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    /// Get the report made when the number of states first exceeded the
+    /// threshold set with `RadiusOption::ExplosionReport`
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::{Radius, RadiusOption};
+    /// let mut radius = Radius::new_with_options(Some("../tests/r100"), &[RadiusOption::ExplosionReport(4)]);
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.symbolize_memory("flag", 0x100000, 12);
+    /// state.registers.set("rdi", radius2::vc(0x100000));
+    /// radius.run_all(state);
+    /// if let Some(report) = radius.explosion_report() {
+    ///     println!("{} forks at 0x{:x}", report.sites[0].forks, report.sites[0].addr);
+    /// }
+    /// ```
+    pub fn explosion_report(&self) -> Option<&ExplosionReport> {
+        self.processor.explosion.as_ref()
+    }
+
+    /// Get the write-what-where primitives found so far, if enabled
+    /// with `RadiusOption::WritePrimitives`
+    pub fn write_primitives(&self) -> Vec<WritePrimitive> {
+        if let Some(catalog) = &self.primitives {
+            catalog.borrow().primitives.clone()
+        } else {
+            vec![]
         }
     }
 
-    /// Get total number of steps from all processors
-    pub fn get_steps(&self) -> u64 {
-        self.processor.steps
-            + self
-                .processors
-                .lock()
-                .unwrap()
-                .iter()
-                .map(|p| p.steps)
-                .sum::<u64>()
+    /// Print the catalog of write-what-where primitives
+    pub fn print_write_primitives(&self) {
+        if let Some(catalog) = &self.primitives {
+            catalog.borrow().print();
+        }
     }
 
     /// Execute function and return the resulting state
@@ -545,16 +2193,307 @@ impl Radius {
         self.processor.run(state, RunMode::Single).pop()
     }
 
-    /// Simple way to execute until a given target address while avoiding a vec of other addrs
+    /// Add the struct, union, and typedef definitions in the C `source`,
+    /// returning the names defined
+    pub fn define_types(&mut self, source: &str) -> Result<Vec<String>, String> {
+        self.types.borrow_mut().define(source)
+    }
+
+    /// Restore a state serialized with `State::serialize`
+    pub fn load_state(&mut self, data: &str) -> Result<State, String> {
+        let mut state = self.init_state();
+        state.deserialize(data)?;
+        Ok(state)
+    }
+
+    /// Continue running a state, eg. one restored with `load_state`,
+    /// until a breakpoint or the end of the program
+    pub fn run_from(&mut self, state: State) -> Option<State> {
+        self.processor.run(state, RunMode::Single).pop()
+    }
+
+    /// Simple way to execute until a given target address while avoiding a vec of other addrs.
+    /// Use `explore` to keep the other states of the run
     ///
     /// ## Arguments
     /// * `state` - the program state to begin running from
     /// * `target` - the goal address where execution should stop and return the result state
     /// * `avoid` - slice of addresses to avoid, states that reach them will be marked inactive
     pub fn run_until(&mut self, state: State, target: u64, avoid: &[u64]) -> Option<State> {
+        let mut manager = StateManager::new(state);
+        manager.keep_ended = false;
+        self.explore(&mut manager, target, avoid, 1);
+        manager.take(FOUND).pop()
+    }
+
+    /// Run the active states of the manager until `count` states reach
+    /// `target` or none are left. States are sorted into the stashes of
+    /// the manager as they stop, see `StateManager`. Returns the number
+    /// of states found
+    pub fn explore(
+        &mut self,
+        manager: &mut StateManager,
+        target: u64,
+        avoid: &[u64],
+        count: usize,
+    ) -> usize {
         self.breakpoint(target);
         self.avoid(avoid);
-        self.processor.run(state, RunMode::Single).pop()
+        self.processor.unexplored = Some(vec![]);
+        self.processor.ended = manager.keep_ended.then(Vec::new);
+
+        let mut found = 0;
+        while found < count {
+            let state = match manager.stash_mut(ACTIVE).pop() {
+                Some(state) => state,
+                None => break,
+            };
+            let results = self.processor.run(state, RunMode::Single);
+            found += results.len();
+            manager.stash_mut(FOUND).extend(results);
+
+            let mut stopped = self
+                .processor
+                .unexplored
+                .as_mut()
+                .map(mem::take)
+                .unwrap_or_default();
+            if let Some(ended) = &mut self.processor.ended {
+                stopped.append(ended);
+            }
+            for state in stopped {
+                manager.add_stopped(state, &self.processor.avoidpoints);
+            }
+            if self.processor.budget_stop.is_some() {
+                manager
+                    .stash_mut(ACTIVE)
+                    .append(&mut self.processor.survivors);
+                break;
+            }
+        }
+        self.processor.unexplored = None;
+        self.processor.ended = None;
+        found
+    }
+
+    /// Inspect the registers, memory, and constraints of the state and
+    /// step it with commands read from stdin, type `help` for the list.
+    /// Returns the current state when quit, so a script can go on running
+    /// it, the other states it forked are dropped
+    ///
+    /// ## Example
+    /// ```no_run
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let state = radius.call_state(0x004006fd);
+    /// let state = radius.interact(state);
+    /// let result = radius.run_until(state, 0x004007a1, &[0x00400790]);
+    /// ```
+    pub fn interact(&mut self, state: State) -> State {
+        let stdin = io::stdin();
+        repl::interact(&mut self.processor, state, stdin.lock(), &mut io::stdout())
+    }
+
+    /// Execute until a state satisfies each of the `goals`, exploring for
+    /// all of them at once. Goals are retired as they are satisfied and
+    /// the states that satisfied them keep exploring for the rest.
+    /// Returns the state that satisfied each goal, by index
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::goal::Goal;
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.symbolize_memory("flag", 0x100000, 12);
+    /// state.registers.set("rdi", radius2::vc(0x100000));
+    /// let goals = [Goal::new(0x004007a1), Goal::new(0x00400790)];
+    /// let mut found = radius.run_until_goals(state, &goals, &[]);
+    /// assert_eq!(found.len(), 2);
+    /// assert_eq!(found.get_mut(&0).unwrap().dump_inputs()["flag"], b"Code_Talkers");
+    /// ```
+    pub fn run_until_goals(
+        &mut self,
+        state: State,
+        goals: &[Goal],
+        avoid: &[u64],
+    ) -> BTreeMap<usize, State> {
+        for goal in goals {
+            self.breakpoint(goal.addr);
+        }
+        self.avoid(avoid);
+        self.processor.goals = Some(Goals::new(goals));
+        self.processor.run(state, RunMode::Single);
+        self.processor.goals.take().unwrap_or_default().found
+    }
+
+    /// Like `run_until`, but explores states on `threads` threads at once.
+    /// Each thread steps its own states, sharing the r2 session, and gives
+    /// one to a thread that runs out, in the order of the configured
+    /// `Strategy`. States are moved between threads with `State::serialize`,
+    /// so their hooks, ioctl handlers, solver levels, and the constraints of
+    /// their path branches are not kept, see `Snapshot`, and states kept by
+    /// a budget are dropped. The crashes and unconstrained states of the
+    /// threads are added to the processor
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.symbolize_memory("flag", 0x100000, 12);
+    /// state.registers.set("rdi", radius2::vc(0x100000));
+    /// let mut found = radius
+    ///     .run_until_parallel(state, 0x004007a1, &[0x00400790], 4)
+    ///     .unwrap();
+    /// assert_eq!(found.dump_inputs()["flag"], b"Code_Talkers");
+    /// ```
+    pub fn run_until_parallel(
+        &mut self,
+        state: State,
+        target: u64,
+        avoid: &[u64],
+        threads: usize,
+    ) -> Option<State> {
+        self.breakpoint(target);
+        self.avoid(avoid);
+        self.run_parallel(state, threads)
+    }
+
+    // explore on worker threads until a state reaches a breakpoint
+    fn run_parallel(&mut self, state: State, threads: usize) -> Option<State> {
+        if threads < 2 {
+            return self.processor.run(state, RunMode::Single).pop();
+        }
+
+        let queue = Arc::new(WorkQueue::new(threads));
+        queue.push(state.serialize());
+        let setup = WorkerSetup {
+            r2api: self.r2api.clone(),
+            options: self.options.clone(),
+            breakpoints: self.processor.breakpoints.clone(),
+            avoidpoints: self.processor.avoidpoints.clone(),
+            hooks: self.processor.hooks.clone(),
+            esil_hooks: self.processor.esil_hooks.clone(),
+            sims: self.processor.sims.clone(),
+            skipped: self.processor.skipped.clone(),
+            api_hashes: self.processor.api_hashes.clone(),
+            deadline: self.processor.timeout.map(|t| Instant::now() + t),
+            bus: self.processor.bus.clone(),
+        };
+
+        let mut handles = vec![];
+        for _ in 0..threads {
+            let setup = setup.clone();
+            let queue = queue.clone();
+            handles.push(thread::spawn(move || setup.explore(&queue)));
+        }
+        for handle in handles {
+            if handle.join().is_err() {
+                println!("warning: worker thread panicked");
+            }
+        }
+
+        let results = queue.take_results();
+        for (data, status) in results.crashes {
+            if let Ok(mut crash) = self.load_state(&data) {
+                crash.status = status;
+                self.processor.crashes.push(crash);
+            }
+        }
+        for data in results.unconstrained {
+            if let Ok(state) = self.load_state(&data) {
+                self.processor.unconstrained.push(state);
+            }
+        }
+        self.processor.steps += results.steps;
+
+        let data = queue.result()?;
+        self.load_state(&data).ok()
+    }
+
+    /// Execute until a state exits, by calling exit or returning from main,
+    /// with an exit code that can satisfy `goal`. The resulting state has
+    /// the goal asserted on its exit code
+    ///
+    /// ## Arguments
+    /// * `state` - the program state to begin running from
+    /// * `goal` - takes the exit code and returns the condition to satisfy
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// use radius2::value::vc;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let state = radius.entry_state();
+    /// let success = radius.run_until_exit(state, |code| code.eq(&vc(0)));
+    /// ```
+    pub fn run_until_exit(&mut self, state: State, goal: ExitGoal) -> Option<State> {
+        self.processor.exit_goal = Some(goal);
+        let result = self.processor.run(state, RunMode::Single).pop();
+        self.processor.exit_goal = None;
+        result
+    }
+
+    /// Call the function at `addr` with each arg pointing to its own symbolic
+    /// buffer of `SOLVE_BUFFER` bytes, and solve for buffers that make it
+    /// return a value satisfying `goal`. Returns the buffers one after another
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let buffers = radius.solve_return(0x004006fd, |ret| ret.eq(&radius2::vc(0)));
+    /// assert_eq!(&buffers.unwrap()[..12], b"Code_Talkers");
+    /// ```
+    pub fn solve_return(&mut self, addr: u64, goal: ExitGoal) -> Option<Vec<u8>> {
+        let args = self.function_args(addr);
+        let mut state = self.call_state(addr);
+        for (i, arg) in args.iter().enumerate() {
+            let buffer = state.memory_alloc(&vc(SOLVE_BUFFER as u64));
+            let name = format!("arg{}", i);
+            state.symbolize_memory(&name, buffer.as_u64()?, SOLVE_BUFFER);
+            arg.write(&mut state, &buffer);
+        }
+
+        self.processor.return_goal = Some(goal);
+        let result = self.processor.run(state, RunMode::Single).pop();
+        self.processor.return_goal = None;
+
+        let inputs = result?.dump_inputs();
+        let mut buffers = vec![];
+        for i in 0..args.len() {
+            buffers.extend(inputs.get(&format!("arg{}", i))?);
+        }
+        Some(buffers)
+    }
+
+    /// Run until `target`, or until every state ends without one, within the
+    /// budgets of `RadiusOption::Timeout`, `MaxSteps`, and `MaxMemory`.
+    /// The result tells why the run stopped and has the states left to
+    /// explore if a budget stopped it, see `RunResult`
+    pub fn run_budgeted(&mut self, state: State, target: Option<u64>, avoid: &[u64]) -> RunResult {
+        self.avoid(avoid);
+        let mode = if let Some(target) = target {
+            self.breakpoint(target);
+            RunMode::Single
+        } else {
+            RunMode::Multiple
+        };
+
+        let steps = self.processor.steps;
+        let found = self.processor.run(state, mode);
+        let reason = match self.processor.budget_stop {
+            Some(reason) => reason,
+            None if found.is_empty() => StopReason::Exhausted,
+            None => StopReason::Found,
+        };
+        RunResult {
+            reason,
+            found,
+            survivors: mem::take(&mut self.processor.survivors),
+            steps: self.processor.steps - steps,
+        }
     }
 
     /// Execute until every state has reached an end and return active states
@@ -562,11 +2501,274 @@ impl Radius {
         self.processor.run(state, RunMode::Multiple)
     }
 
+    /// Explore every path from the state like `run_all`, writing the
+    /// inputs of each path that reaches a new block or ends to `out_dir`
+    /// as a fuzzing corpus, eg. `stdin/id_000000`. Returns the number
+    /// of testcases written
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// let flag = state.symbolic_value("flag", 12 * 8);
+    /// let bytes = state.unpack(&flag, 12);
+    /// state.declare_input_bytes("flag", 0, &bytes);
+    /// state.memory_write_value(&radius2::vc(0x100000), &flag, 12);
+    /// state.registers.set("rdi", radius2::vc(0x100000));
+    /// radius.breakpoint(0x004007a1);
+    /// let dir = std::env::temp_dir().join("r100_corpus");
+    /// let count = radius.explore_and_dump(state, dir.to_str().unwrap()).unwrap();
+    /// assert!(count > 1);
+    /// assert!(dir.join("flag/id_000001").exists());
+    /// ```
+    pub fn explore_and_dump(&mut self, mut state: State, out_dir: &str) -> R2Result<usize> {
+        let mut corpus = Corpus::new(out_dir)?;
+        corpus.add_block(&mut state);
+        self.processor.corpus = Some(corpus);
+        self.run_all(state);
+        let corpus = self.processor.corpus.take().unwrap();
+        Ok(corpus.count)
+    }
+
+    /// Run every model of the input bytes and symbols of the state, up to
+    /// `limit` of them, and get the outcome of each. The symbols are replaced
+    /// with their values so each run is nearly concrete, which for small
+    /// inputs like a PIN is often faster than a symbolic search. Set the
+    /// breakpoints and avoided addresses that end runs beforehand
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// use radius2::enumerate::Outcome;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.symbolize_memory("flag", 0x100000, 1);
+    /// state.memory_write_string(0x100001, "ode_Talkers");
+    /// state.registers.set("rdi", radius2::vc(0x100000));
+    /// radius.breakpoint(0x004007a1);
+    /// radius.avoid(&[0x00400790]);
+    ///
+    /// let enumeration = radius.enumerate(state, 256);
+    /// enumeration.print();
+    /// let found = enumeration.with_outcome(&Outcome::Break(0x004007a1));
+    /// assert_eq!(found[0].values["flag[0]"], b'C' as u64);
+    /// ```
+    pub fn enumerate(&mut self, mut state: State, limit: usize) -> Enumeration {
+        let symbols = enumerate::input_symbols(&state);
+        let (models, complete) = enumerate::models(&mut state, &symbols, limit);
+
+        // exits are outcomes too
+        let exit_goal = self.processor.exit_goal.replace(|_| vc(1));
+        let mut runs = Vec::with_capacity(models.len());
+        for values in models {
+            let mut run = state.duplicate();
+            enumerate::concretize(&mut run, &symbols, &values);
+
+            let crashes = self.processor.crashes.len();
+            let result = self.processor.run(run, RunMode::Single).pop();
+            let outcome = match result {
+                Some(end) if end.status == StateStatus::Exit => {
+                    let code = end.exit_code().and_then(|c| c.as_u64());
+                    Outcome::Exit(code.unwrap_or_default())
+                }
+                Some(end) => Outcome::Break(end.registers.get_pc().as_u64().unwrap_or_default()),
+                None => match self.processor.crashes.get(crashes).map(|c| &c.status) {
+                    Some(StateStatus::Crash(addr, _)) | Some(StateStatus::Violation(addr, _)) => {
+                        Outcome::Crash(*addr)
+                    }
+                    _ => Outcome::Ended,
+                },
+            };
+            runs.push(EnumeratedRun { values, outcome });
+        }
+        self.processor.exit_goal = exit_goal;
+        Enumeration { runs, complete }
+    }
+
+    /// Add the addresses covered by a recorded `Trace` to the coverage map
+    pub fn seed_coverage(&mut self, trace: &Trace) {
+        self.processor.visited.extend(trace.coverage());
+    }
+
+    /// Get the blocks covered so far with the functions they are in,
+    /// save it to diff the coverage of later runs against it
+    pub fn coverage(&mut self) -> Coverage {
+        let mut visited: Vec<u64> = self.processor.visited.iter().copied().collect();
+        visited.sort_unstable();
+        let functions = self.r2api.get_functions().unwrap_or_default();
+
+        let mut blocks = BTreeMap::new();
+        let mut attributed = HashSet::new();
+        for function in functions {
+            let start = visited.partition_point(|a| *a < function.minbound);
+            let end = visited.partition_point(|a| *a < function.maxbound);
+            if start == end {
+                continue;
+            }
+            attributed.extend(visited[start..end].iter().copied());
+            for block in self.r2api.get_blocks(function.offset).unwrap_or_default() {
+                if self.processor.visited.contains(&block.addr) {
+                    blocks.insert(block.addr, function.name.to_owned());
+                }
+            }
+        }
+        for addr in visited {
+            if !attributed.contains(&addr) {
+                blocks.insert(addr, String::new());
+            }
+        }
+        Coverage { blocks }
+    }
+
+    /// Write the instructions executed by every state so far to `path`
+    /// in drcov format, to load into Lighthouse or Cutter
+    pub fn write_coverage(&mut self, path: &str) -> std::io::Result<()> {
+        let addrs: Vec<u64> = self.processor.visited.iter().copied().collect();
+        Drcov::new(&mut self.r2api, &self.processor, &addrs).save(path)
+    }
+
+    /// Write the instructions executed by `state` to `path` in drcov format
+    pub fn write_state_coverage(&mut self, state: &State, path: &str) -> std::io::Result<()> {
+        let addrs: Vec<u64> = state.visits.keys().copied().collect();
+        Drcov::new(&mut self.r2api, &self.processor, &addrs).save(path)
+    }
+
+    /// Execute the state along a recorded `Trace`, taking the successor that
+    /// matches the trace whenever the state forks. The resulting state holds
+    /// the path constraints of the traced execution, or `None` if it diverged
+    ///
+    /// ## Arguments
+    /// * `state` - the program state to begin replaying from
+    /// * `trace` - the recorded addresses to follow
+    pub fn follow_trace(&mut self, state: State, trace: &Trace) -> Option<State> {
+        self.replay_trace(state, trace, &mut vec![])
+    }
+
+    /// Replay a recorded `Trace` and return the branches along it whose
+    /// direction depended on symbolic input, along with the final state
+    ///
+    /// ## Arguments
+    /// * `state` - the program state to begin replaying from
+    /// * `trace` - the recorded addresses to follow
+    pub fn trace_branches(
+        &mut self,
+        state: State,
+        trace: &Trace,
+    ) -> (Option<State>, Vec<TraceBranch>) {
+        let mut branches = vec![];
+        let result = self.replay_trace(state, trace, &mut branches);
+        (result, branches)
+    }
+
+    fn replay_trace(
+        &mut self,
+        state: State,
+        trace: &Trace,
+        branches: &mut Vec<TraceBranch>,
+    ) -> Option<State> {
+        let mut index = 0;
+        let mut current = state;
+
+        loop {
+            let pc = current.registers.get_pc().as_u64();
+            if pc.is_some() && trace.addrs.get(index) == pc.as_ref() {
+                index += 1;
+            }
+
+            if index == trace.len() || current.status != StateStatus::Active {
+                return Some(current);
+            }
+
+            let mut states = self.processor.run(current, RunMode::Step);
+            current = if states.len() > 1 {
+                let pcs: Vec<u64> = states
+                    .iter()
+                    .filter_map(|s| s.registers.get_pc().as_u64())
+                    .collect();
+
+                // find the first state that shows up next in the trace
+                let (offset, pos) =
+                    trace.addrs[index..].iter().enumerate().find_map(|(i, a)| {
+                        states
+                            .iter()
+                            .position(|s| s.registers.get_pc().as_u64() == Some(*a))
+                            .map(|pos| (i, pos))
+                    })?;
+
+                let taken = trace.addrs[index + offset];
+                branches.push(TraceBranch {
+                    addr: pc.unwrap_or_default(),
+                    taken,
+                    alternatives: pcs.into_iter().filter(|p| *p != taken).collect(),
+                    index: index + offset,
+                });
+                states.swap_remove(pos)
+            } else {
+                states.pop()?
+            };
+        }
+    }
+
+    /// Execute the state along the path that the concrete inputs in `seed`
+    /// take, while the symbolic inputs collect the constraints of the path.
+    /// Returns the path and the inputs that flip each branch on it, which
+    /// can be run as seeds in turn, or `None` if no successor of a fork
+    /// matches the seed. Inputs missing from the seed stay symbolic
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// use std::collections::BTreeMap;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.symbolize_memory("flag", 0x100000, 12);
+    /// state.registers.set("rdi", radius2::vc(0x100000));
+    /// radius.breakpoint(0x004007a1);
+    /// radius.avoid(&[0x00400790]);
+    ///
+    /// let seed = BTreeMap::from([("flag".to_owned(), b"Code_Talkerz".to_vec())]);
+    /// let run = radius.run_concolic(state, &seed).unwrap();
+    /// assert!(!run.flips.is_empty());
+    /// ```
+    pub fn run_concolic(
+        &mut self,
+        mut state: State,
+        seed: &BTreeMap<String, Vec<u8>>,
+    ) -> Option<ConcolicRun> {
+        for (source, bytes) in seed {
+            state.set_seed(source, bytes);
+        }
+
+        let mut current = state;
+        while current.status == StateStatus::Active {
+            let condition = concolic::seed_condition(&current, seed);
+            let mut states = self.processor.run(current, RunMode::Step);
+            current = if states.len() > 1 {
+                // the successor whose path the seed satisfies
+                let pos = states
+                    .iter_mut()
+                    .position(|s| s.solver.check_sat(&condition))?;
+                states.swap_remove(pos)
+            } else {
+                states.pop()?
+            };
+        }
+
+        let flips = concolic::flips(&mut current);
+        Some(ConcolicRun {
+            path: current.path.clone(),
+            state: current,
+            flips,
+        })
+    }
+
     /// Main run method, start or continue a symbolic execution
     ///
     /// ## Arguments
     /// * `state` - the program state to begin executing from
-    /// * `threads` - number of threads (currently unused)
+    /// * `threads` - number of threads (currently unused), use
+    ///   `run_until_parallel` to explore on worker threads
     ///
     ///  ## Example
     /// ```
@@ -576,7 +2778,8 @@ impl Radius {
     /// let new_state = radius.run(state, 1);
     /// ```
     pub fn run(&mut self, state: State, _threads: usize) -> Option<State> {
-        // we are gonna scrap threads for now cuz theyre currently useless.
+        // worker states go through serialize, which doesn't keep every
+        // part of a state yet, so running on threads is opt in
         self.processor.run(state, RunMode::Single).pop()
     }
 
@@ -590,11 +2793,38 @@ impl Radius {
         self.r2api.get_info()
     }
 
-    /// Get address of symbol
+    /// Get address of symbol, or of an export of a loaded library as `library:symbol`
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::{Radius, RadiusOption};
+    /// let options = [
+    ///     RadiusOption::LoadLibs(true),
+    ///     RadiusOption::LibPath("/usr/lib/x86_64-linux-gnu".to_owned()),
+    /// ];
+    /// let mut radius = Radius::new_with_options(Some("/bin/ls"), &options);
+    /// let strlen = radius.get_address("libc.so:strlen").unwrap();
+    /// assert!(radius.libraries.iter().any(|l| l.contains("libc.so")));
+    /// assert_ne!(strlen, 0);
+    /// ```
     pub fn get_address<T: AsRef<str>>(&mut self, symbol: T) -> R2Result<u64> {
         self.r2api.get_address(symbol.as_ref())
     }
 
+    /// Get the address the binary is loaded at, which is the slide of
+    /// position independent binaries, see `RadiusOption::LoadBase`
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::{Radius, RadiusOption};
+    /// let options = [RadiusOption::LoadBase(0x555555554000)];
+    /// let mut radius = Radius::new_with_options(Some("/bin/ls"), &options);
+    /// assert_eq!(radius.load_base(), 0x555555554000);
+    /// ```
+    pub fn load_base(&self) -> u64 {
+        self.r2api.info.bin.baddr
+    }
+
     /// Get all functions
     pub fn get_functions(&mut self) -> R2Result<Vec<FunctionInfo>> {
         self.r2api.get_functions()
@@ -632,7 +2862,75 @@ impl Radius {
 
     /// Patch binary
     pub fn write(&mut self, address: u64, data: Vec<u8>) {
-        self.r2api.write(address, data)
+        let length = data.len() as u64;
+        self.r2api.write(address, data);
+        self.processor.invalidate(address, length);
+    }
+
+    /// Record the handlers that the vm dispatcher at `dispatcher` jumps to,
+    /// with `operands` bytes of bytecode at the virtual pc register `vpc`.
+    /// Without a dispatcher the first indirect jump with many targets is used,
+    /// read the handlers of a state with `VmTrace::new`
+    pub fn trace_vm(&mut self, dispatcher: Option<u64>, vpc: Option<&str>, operands: usize) {
+        self.processor.vm = Some(VmTracer::new(dispatcher, vpc, operands));
+    }
+
+    /// Save a copy of the state to re-explore from after a `patch`,
+    /// the addresses executed by the state and its successors are recorded
+    pub fn checkpoint(&mut self, state: &mut State) {
+        let id = self.checkpoints.len() as u64;
+        state
+            .context
+            .entry("checkpoints".to_owned())
+            .or_default()
+            .push(vc(id));
+        self.processor
+            .checkpoint_coverage
+            .insert(id, HashSet::new());
+        self.checkpoints.push(state.duplicate());
+    }
+
+    /// Patch the binary and drop the cached instructions at the patched bytes.
+    /// Returns copies of the checkpoints that did not execute the patched
+    /// bytes themselves but whose successors did, with the patch applied,
+    /// so only the affected paths are explored again
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// radius.checkpoint(&mut state);
+    /// radius.run_until(state, 0x004007a1, &[0x00400790]);
+    ///
+    /// // always jump past the failure
+    /// let states = radius.patch(0x0040078e, vec![0xeb, 0x07]);
+    /// assert_eq!(states.len(), 1);
+    /// ```
+    pub fn patch(&mut self, address: u64, data: Vec<u8>) -> Vec<State> {
+        let length = data.len() as u64;
+        let patched = |addr: &u64| *addr >= address && *addr < address + length;
+        let values: Vec<Value> = data.iter().map(|b| vc(*b as u64)).collect();
+
+        self.r2api.write(address, data);
+        self.processor.invalidate(address, length);
+        self.processors.lock().unwrap().clear();
+
+        let mut states = vec![];
+        for (id, checkpoint) in self.checkpoints.iter_mut().enumerate() {
+            let crossed = self
+                .processor
+                .checkpoint_coverage
+                .get(&(id as u64))
+                .map(|coverage| coverage.iter().any(patched))
+                .unwrap_or_default();
+
+            checkpoint.memory.write(address, &mut values.clone());
+            if crossed && !checkpoint.visits.keys().any(patched) {
+                states.push(checkpoint.duplicate());
+            }
+        }
+        states
     }
 
     /// Write string to binary / real memory
@@ -641,6 +2939,76 @@ impl Radius {
             .write(address, string.chars().map(|c| c as u8).collect::<Vec<_>>())
     }
 
+    /// Read the bytes of the binary file as it is on disk
+    pub fn file_bytes(&mut self) -> R2Result<Vec<u8>> {
+        fs::read(&self.r2api.info.core.file).map_err(|e| e.to_string())
+    }
+
+    /// Get the resources of a PE binary
+    pub fn resources(&mut self) -> R2Result<Vec<Resource>> {
+        self.r2api.get_resources()
+    }
+
+    /// Read the data of a resource listed by `resources`
+    pub fn resource_bytes(&mut self, resource: &Resource) -> R2Result<Vec<u8>> {
+        self.r2api.read(resource.vaddr, resource.size as usize)
+    }
+
+    /// Read the raw bytes of the section `name` from the file,
+    /// these can differ from memory for sections that are unpacked
+    pub fn section_bytes(&mut self, name: &str) -> R2Result<Vec<u8>> {
+        let segments = self.r2api.get_segments()?;
+        let section = segments
+            .iter()
+            .find(|s| s.name == name)
+            .ok_or_else(|| format!("no section {}", name))?;
+
+        let bytes = self.file_bytes()?;
+        let start = (section.paddr as usize).min(bytes.len());
+        let end = (start + section.size as usize).min(bytes.len());
+        Ok(bytes[start..end].to_vec())
+    }
+
+    /// Get the overlay, the data appended to the binary after its
+    /// last section that is not loaded into memory. Empty if there is none
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// assert!(radius.overlay().unwrap().is_empty());
+    /// ```
+    pub fn overlay(&mut self) -> R2Result<Vec<u8>> {
+        let segments = self.r2api.get_segments()?;
+        let bytes = self.file_bytes()?;
+        let end = segments
+            .iter()
+            .map(|s| (s.paddr + s.size) as usize)
+            .chain(elf_headers_end(&bytes))
+            .max()
+            .unwrap_or_default();
+
+        Ok(bytes.get(end..).unwrap_or_default().to_vec())
+    }
+
+    /// Add the bytes of the binary to the filesystem of the state at `path`,
+    /// so a program that opens its own file to read its resources
+    /// or overlay reads the real data
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// radius.add_binary_file(&mut state, "/proc/self/exe").unwrap();
+    /// ```
+    pub fn add_binary_file(&mut self, state: &mut State, path: &str) -> R2Result<()> {
+        let bytes = self.file_bytes()?;
+        let data: Vec<Value> = bytes.iter().map(|b| vc(*b as u64)).collect();
+        state.filesystem.add_file(path, &data);
+        Ok(())
+    }
+
     /// Set radare2 option, equivalent to "e `key`=`value`"
     pub fn set_option(&mut self, key: &str, value: &str) {
         self.r2api.set_option(key, value).unwrap();
@@ -658,9 +3026,43 @@ impl Radius {
 
     /// close r2
     pub fn close(&mut self) {
+        if let Err(e) = self.save_knowledge() {
+            println!("could not save knowledge base: {}", e);
+        }
         self.r2api.close()
     }
 
+    /// Save the knowledge base with the r2 queries, lifted instructions,
+    /// opaque predicates, and loop summaries so far, this is done
+    /// automatically on `close`
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::{Radius, RadiusOption};
+    /// let dir = std::env::temp_dir().join("radius2-kb");
+    /// let options = [RadiusOption::KnowledgeBase(dir.to_str().unwrap().to_owned())];
+    /// let mut radius = Radius::new_with_options(Some("/bin/sh"), &options);
+    /// let functions = radius.get_functions().unwrap();
+    /// radius.save_knowledge().unwrap();
+    /// ```
+    pub fn save_knowledge(&mut self) -> std::io::Result<()> {
+        if let Some(kb) = &mut self.knowledge {
+            kb.commands = self.r2api.get_cache();
+            if let Some(lifted) = &self.processor.lifted {
+                kb.blocks = lifted.to_owned();
+            }
+            if let Some(branches) = &self.processor.branches {
+                let opaque = branches.iter().filter_map(|(a, t)| t.map(|t| (*a, t)));
+                kb.opaque_predicates = opaque.collect();
+            }
+            let summaries = self.processor.loops.iter().map(|(a, s)| (*a, s.to_owned()));
+            kb.summaries = summaries.collect();
+            kb.save()
+        } else {
+            Ok(())
+        }
+    }
+
     // clear cached data from r2api and processors
     pub fn clear(&mut self) {
         self.r2api.clear();
@@ -668,6 +3070,44 @@ impl Radius {
     }
 }
 
+// the end of the section header table of an elf, which comes after the sections
+fn elf_headers_end(bytes: &[u8]) -> Option<usize> {
+    if bytes.len() < 0x40 || &bytes[..4] != b"\x7fELF" {
+        return None;
+    }
+    let big = bytes[5] == 2;
+    let read = |offset: usize, length: usize| {
+        let mut field = bytes[offset..offset + length].to_vec();
+        if !big {
+            field.reverse();
+        }
+        field.iter().fold(0usize, |v, b| (v << 8) | *b as usize)
+    };
+
+    // offsets of e_shoff, e_shentsize and e_shnum for 32 and 64 bit
+    let (shoff, shentsize) = if bytes[4] == 2 {
+        (read(0x28, 8), 0x3a)
+    } else {
+        (read(0x20, 4), 0x2e)
+    };
+    Some(shoff + read(shentsize, 2) * read(shentsize + 2, 2))
+}
+
+// if the file is an elf executable or library with type ET_DYN
+fn is_shared_object(path: &str) -> bool {
+    let mut header = [0u8; 18];
+    let read = fs::File::open(path).and_then(|mut f| f.read_exact(&mut header));
+    if read.is_err() || &header[..4] != b"\x7fELF" {
+        return false;
+    }
+    let e_type = if header[5] == 2 {
+        [header[16], header[17]]
+    } else {
+        [header[17], header[16]]
+    };
+    e_type == [0, 3]
+}
+
 pub fn __libc_start_main(state: &mut State) -> bool {
     let mut args = state.get_args();
     let main = args.remove(0);
@@ -677,8 +3117,52 @@ pub fn __libc_start_main(state: &mut State) -> bool {
     // so until then
 
     // go to main
+    set_main_return(state);
     state.registers.set_with_alias("PC", main);
     state.set_args(args);
 
+    // envp as set up by set_argv_env
+    if let Some(env) = state.context.get("env") {
+        let env = env[0].to_owned();
+        state.registers.set_with_alias("A2", env);
+    }
+
     false
 }
+
+// write the null terminated array of pointers to `strings` at `current`,
+// recording it in the context as `name`. Returns the address after it
+fn write_strings(state: &mut State, name: &str, strings: &[Value], mut current: Value) -> Value {
+    let ptrlen = (state.memory.bits / 8) as usize;
+    state.context.insert(name.to_owned(), vec![current.clone()]);
+    for (j, string) in strings.iter().enumerate() {
+        state.declare_input(&format!("{}[{}]", name, j), 0, string);
+        let addr = state
+            .memory
+            .alloc(&Value::Concrete((string.size() / 8) as u64 + 1, 0));
+
+        state.memory_write_value(
+            &Value::Concrete(addr, 0),
+            string,
+            string.size() as usize / 8,
+        );
+
+        state
+            .memory
+            .write_value(addr + (string.size() / 8) as u64, &Value::Concrete(0, 0), 1);
+
+        state.memory_write_value(&current, &Value::Concrete(addr, 0), ptrlen);
+        current = current + Value::Concrete(ptrlen as u64, 0);
+    }
+    state.memory_write_value(&current, &Value::Concrete(0, 0), ptrlen);
+    current + Value::Concrete(ptrlen as u64, 0)
+}
+
+// pack the bytes of a string, an empty string is a single null byte
+fn pack_string(state: &State, bytes: &[Value]) -> Value {
+    if bytes.is_empty() {
+        state.pack(&[vc(0)])
+    } else {
+        state.pack(bytes)
+    }
+}
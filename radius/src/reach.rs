@@ -0,0 +1,202 @@
+use crate::r2_api::{BasicBlock, Instruction, R2Api};
+use crate::state::State;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Instruction types whose successors r2 can't know
+const INDIRECT_TYPES: [&str; 8] = [
+    "ucall", "rcall", "icall", "ircall", "ujmp", "rjmp", "ijmp", "irjmp",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Block {
+    end: u64,
+    successors: Vec<u64>,
+    /// calls or tail calls to these addresses
+    calls: Vec<u64>,
+    function: u64,
+    reaches: bool,
+    /// fewest blocks to go through to get to a target
+    distance: Option<u64>,
+}
+
+/// The blocks from which the targets can be reached over the call graph
+/// and control flow graphs r2 recovers, and how far they are from them.
+/// Blocks with indirect calls or jumps are assumed to reach them, code
+/// outside of functions is unknown
+///
+/// ## Example
+/// ```
+/// use radius2::radius::Radius;
+/// use radius2::reach::Reachability;
+/// let mut radius = Radius::new("../tests/r100");
+/// radius.analyze(3);
+/// let reach = Reachability::new(&mut radius.r2api, &[0x004007a1]);
+/// assert_eq!(reach.reaches(0x004006fd), Some(true));
+/// assert_eq!(reach.reaches(0x00400790), Some(false));
+/// assert!(reach.distance(0x004006fd) > Some(0));
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Reachability {
+    pub targets: Vec<u64>,
+    blocks: BTreeMap<u64, Block>,
+}
+
+impl Reachability {
+    /// Analyze the functions r2 found, see `Radius::analyze`
+    pub fn new(r2api: &mut R2Api, targets: &[u64]) -> Self {
+        let mut blocks = BTreeMap::new();
+        for function in r2api.get_functions().unwrap_or_default() {
+            let bbs = r2api.get_blocks(function.offset).unwrap_or_default();
+            let instructions = r2api
+                .disassemble_function(function.offset)
+                .unwrap_or_default();
+            for bb in &bbs {
+                let block = Reachability::block(function.offset, bb, &instructions, targets);
+                blocks.insert(bb.addr, block);
+            }
+        }
+
+        let mut reach = Reachability {
+            targets: targets.to_vec(),
+            blocks,
+        };
+        reach.propagate();
+        reach.measure();
+        reach
+    }
+
+    fn block(
+        function: u64,
+        bb: &BasicBlock,
+        instructions: &[Instruction],
+        targets: &[u64],
+    ) -> Block {
+        let end = bb.addr + bb.size;
+        let mut block = Block {
+            end,
+            successors: [bb.jump, bb.fail]
+                .iter()
+                .filter(|a| **a > 0)
+                .map(|a| *a as u64)
+                .collect(),
+            calls: vec![],
+            function,
+            reaches: targets.iter().any(|t| (bb.addr..end).contains(t)),
+            distance: None,
+        };
+
+        let ops = instructions
+            .iter()
+            .filter(|i| (bb.addr..end).contains(&i.offset));
+        for op in ops {
+            if INDIRECT_TYPES.contains(&op.r#type.as_str()) {
+                block.reaches = true;
+            } else if op.r#type == "call" || (op.r#type == "jmp" && op.jump > 0) {
+                block.calls.push(op.jump as u64);
+            }
+        }
+        block
+    }
+
+    // mark the blocks that can get to a reaching block or call a
+    // function that has one, until nothing changes
+    fn propagate(&mut self) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            let functions: HashSet<u64> = self
+                .blocks
+                .values()
+                .filter(|b| b.reaches)
+                .map(|b| b.function)
+                .collect();
+            let reaching: HashMap<u64, bool> = self
+                .blocks
+                .iter()
+                .map(|(addr, b)| (*addr, b.reaches))
+                .collect();
+
+            for block in self.blocks.values_mut().filter(|b| !b.reaches) {
+                let successor = block
+                    .successors
+                    .iter()
+                    .any(|s| reaching.get(s) == Some(&true));
+                // entering a function with a reaching block is enough
+                let call = block.calls.iter().any(|c| functions.contains(c));
+                if successor || call {
+                    block.reaches = true;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    // set the distances of the blocks to the targets, a call counts as
+    // one block on top of the distance of the function it enters
+    fn measure(&mut self) {
+        for (addr, block) in self.blocks.iter_mut() {
+            let target = self.targets.iter().any(|t| (*addr..block.end).contains(t));
+            block.distance = if target { Some(0) } else { None };
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            let distances: HashMap<u64, u64> = self
+                .blocks
+                .iter()
+                .filter_map(|(addr, b)| b.distance.map(|d| (*addr, d)))
+                .collect();
+            for block in self.blocks.values_mut().filter(|b| b.reaches) {
+                let nearest = block
+                    .successors
+                    .iter()
+                    .chain(&block.calls)
+                    .filter_map(|a| distances.get(a))
+                    .min()
+                    .map(|d| d + 1);
+                if nearest.is_some() && (block.distance.is_none() || nearest < block.distance) {
+                    block.distance = nearest;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    /// Whether a target can be reached from `addr`, without returning,
+    /// or None if it is not in an analyzed block
+    pub fn reaches(&self, addr: u64) -> Option<bool> {
+        let (_, block) = self.blocks.range(..=addr).next_back()?;
+        if addr < block.end {
+            Some(block.reaches)
+        } else {
+            None
+        }
+    }
+
+    /// The fewest blocks from `addr` to a target, counting a call as one
+    /// block plus the distance within the called function. None if it is
+    /// not in an analyzed block or there is no known path
+    pub fn distance(&self, addr: u64) -> Option<u64> {
+        let (_, block) = self.blocks.range(..=addr).next_back()?;
+        if addr < block.end {
+            block.distance
+        } else {
+            None
+        }
+    }
+
+    /// Whether the state can't reach a target, from its pc or from any
+    /// of the return addresses on its backtrace
+    pub fn unreachable(&self, state: &State) -> bool {
+        let pc = match state.registers.get_pc().as_u64() {
+            Some(pc) => pc,
+            None => return false,
+        };
+        let returns = state.backtrace.iter().map(|(_, ret)| *ret);
+        !std::iter::once(pc)
+            .chain(returns)
+            .any(|addr| self.reaches(addr) != Some(false))
+    }
+}
@@ -0,0 +1,251 @@
+use crate::state::{State, StateStatus};
+use crate::trace::Trace;
+use crate::value::{vc, Value};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// The outcome of running a proposed input concretely
+#[derive(Debug, Clone)]
+pub enum Replay {
+    /// The input behaved as predicted
+    Valid,
+    /// The input did not behave as predicted, with the trace
+    /// of the concrete run if one was recorded
+    Diverged(Option<Trace>),
+}
+
+/// Propose inputs from the state and check each with `validate`, usually a
+/// fast concrete run of the binary. Inputs that diverge from the symbolic
+/// prediction are excluded and a new one is proposed, up to `tries` times.
+/// With a trace of the diverging run only the input bytes of the first
+/// branch that went another way are excluded, otherwise the whole input
+///
+/// ## Example
+/// ```no_run
+/// use radius2::radius::Radius;
+/// use radius2::refine::{refine, Replay};
+/// use std::collections::BTreeMap;
+/// use std::io::Write;
+/// use std::process::{Command, Stdio};
+///
+/// // run the binary and check that it prints the success message
+/// fn validate(inputs: &BTreeMap<String, Vec<u8>>) -> Replay {
+///     let mut child = Command::new("../tests/r100")
+///         .stdin(Stdio::piped())
+///         .stdout(Stdio::piped())
+///         .spawn()
+///         .unwrap();
+///     child.stdin.take().unwrap().write_all(&inputs["flag"]).unwrap();
+///     let output = child.wait_with_output().unwrap();
+///     if String::from_utf8_lossy(&output.stdout).contains("Nice!") {
+///         Replay::Valid
+///     } else {
+///         Replay::Diverged(None)
+///     }
+/// }
+///
+/// let mut radius = Radius::new("../tests/r100");
+/// let mut state = radius.call_state(0x004006fd);
+/// state.symbolize_memory("flag", 0x100000, 12);
+/// state.registers.set("rdi", radius2::vc(0x100000));
+/// let mut found = radius.run_until(state, 0x004007a1, &[0x00400790]).unwrap();
+/// let flag = refine(&mut found, 8, validate);
+/// ```
+pub fn refine<F>(
+    state: &mut State,
+    tries: usize,
+    mut validate: F,
+) -> Option<BTreeMap<String, Vec<u8>>>
+where
+    F: FnMut(&BTreeMap<String, Vec<u8>>) -> Replay,
+{
+    for _ in 0..tries {
+        if !state.is_sat() {
+            return None;
+        }
+        let inputs = state.dump_inputs();
+        match validate(&inputs) {
+            Replay::Valid => return Some(inputs),
+            Replay::Diverged(trace) => exclude(state, &inputs, trace.as_ref()),
+        }
+    }
+    None
+}
+
+// constrain the state so the diverging input is not proposed again
+fn exclude(state: &mut State, inputs: &BTreeMap<String, Vec<u8>>, trace: Option<&Trace>) {
+    let mut positions = vec![];
+    if let Some(trace) = trace {
+        // the first branch whose predicted successor the concrete run never hit
+        let coverage = trace.coverage();
+        let branch = state.path.iter().find(|b| !coverage.contains(&b.target));
+        if let Some(branch) = branch {
+            println!(
+                "warning: concrete run diverged at 0x{:x}, sim or model may be unsound",
+                branch.addr
+            );
+            positions = state.input_positions(&branch.condition);
+        }
+    }
+    if positions.is_empty() {
+        for (source, bytes) in inputs {
+            positions.extend((0..bytes.len()).map(|i| (source.to_owned(), i)));
+        }
+    }
+
+    let mut differs: Option<Value> = None;
+    for (source, i) in positions {
+        let byte = state.inputs[&source][i].to_owned();
+        let value = inputs[&source][i] as u64;
+        let cond = !byte.eq(&vc(value));
+        differs = Some(if let Some(d) = differs {
+            d.or(&cond)
+        } else {
+            cond
+        });
+    }
+    if let Some(differs) = differs {
+        state.assert(&differs);
+    }
+}
+
+/// A way the native run of a solved input differed from the state
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    /// The state exited with the first code, the binary with the second
+    ExitCode(u64, Option<i32>),
+    /// The binary did not print the stdout of the state, the first,
+    /// it printed the second
+    Stdout(Vec<u8>, Vec<u8>),
+    /// The binary never reached the address the state stopped at
+    Unreached(u64),
+}
+
+/// The solved inputs of a state and how running them natively
+/// differed from the state, see `Radius::validate`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Validation {
+    pub inputs: BTreeMap<String, Vec<u8>>,
+    pub divergences: Vec<Divergence>,
+}
+
+impl Validation {
+    /// Whether the native run behaved as the state predicted
+    pub fn is_valid(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// What a state predicts a native run of its inputs does, evaluated with
+/// the inputs fixed so the exit code and stdout agree with them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prediction {
+    pub exit_code: Option<u64>,
+    pub stdout: Vec<u8>,
+    /// The address the state stopped at if it did not exit
+    pub stopped: Option<u64>,
+    /// Contents of the files of the state by path
+    pub files: BTreeMap<String, Vec<u8>>,
+}
+
+impl Prediction {
+    /// Predict the run of the inputs, with the contents of the files at `paths`
+    pub fn new(state: &mut State, inputs: &BTreeMap<String, Vec<u8>>, paths: &[&str]) -> Self {
+        let mut state = state.duplicate();
+        for (source, bytes) in inputs {
+            for (byte, value) in state.inputs[source].clone().iter().zip(bytes) {
+                state.assert(&byte.eq(&vc(*value as u64)));
+            }
+        }
+        let exit_code = if state.status == StateStatus::Exit {
+            let code = state.exit_code().unwrap_or_else(|| vc(0));
+            state.solver.evalcon_to_u64(&code)
+        } else {
+            None
+        };
+        let stopped = if exit_code.is_none() {
+            state.registers.get_pc().as_u64()
+        } else {
+            None
+        };
+        let mut files = BTreeMap::new();
+        for path in paths {
+            if let Some(fd) = state.filesystem.getfd(path) {
+                files.insert(path.to_string(), state.dump_file_bytes(fd));
+            }
+        }
+        Prediction {
+            exit_code,
+            stdout: state.dump_file_bytes(1),
+            stopped,
+            files,
+        }
+    }
+
+    /// Compare the exit code and stdout of a native run to the prediction,
+    /// a state that did not exit only predicts the start of stdout
+    pub fn compare(&self, exit_code: Option<i32>, stdout: &[u8]) -> Vec<Divergence> {
+        let mut divergences = vec![];
+        if let Some(code) = self.exit_code {
+            if exit_code.map(|c| c as u8 as u64) != Some(code & 0xff) {
+                divergences.push(Divergence::ExitCode(code, exit_code));
+            }
+            if stdout != self.stdout {
+                divergences.push(Divergence::Stdout(self.stdout.clone(), stdout.to_vec()));
+            }
+        } else if !stdout.starts_with(&self.stdout) {
+            divergences.push(Divergence::Stdout(self.stdout.clone(), stdout.to_vec()));
+        }
+        divergences
+    }
+}
+
+// the strings of the inputs "argv[i]" or "env[i]" in order, up to their nulls
+fn input_strings(inputs: &BTreeMap<String, Vec<u8>>, kind: &str, start: usize) -> Vec<String> {
+    (start..)
+        .map_while(|i| inputs.get(&format!("{}[{}]", kind, i)))
+        .map(|bytes| {
+            let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).into_owned()
+        })
+        .collect()
+}
+
+/// The args of the binary solved in the inputs, without argv\[0\]
+pub fn input_args(inputs: &BTreeMap<String, Vec<u8>>) -> Vec<String> {
+    input_strings(inputs, "argv", 1)
+}
+
+/// Run the binary at `path` natively with the args, environment, and stdin
+/// solved in the inputs, returning its exit code and stdout
+pub fn run_native(
+    path: &str,
+    inputs: &BTreeMap<String, Vec<u8>>,
+) -> std::io::Result<(Option<i32>, Vec<u8>)> {
+    let mut command = Command::new(path);
+    command
+        .args(input_args(inputs))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+
+    let env = input_strings(inputs, "env", 0);
+    if !env.is_empty() {
+        command.env_clear();
+        for var in &env {
+            if let Some((key, value)) = var.split_once('=') {
+                command.env(key, value);
+            }
+        }
+    }
+
+    let mut child = command.spawn()?;
+    let stdin = inputs.get("stdin").cloned().unwrap_or_default();
+    if let Some(mut pipe) = child.stdin.take() {
+        // the binary may exit without reading all of it
+        let _ = pipe.write_all(&stdin);
+    }
+    let output = child.wait_with_output()?;
+    Ok((output.status.code(), output.stdout))
+}
@@ -1,3 +1,4 @@
+use crate::memory::Uninit;
 use crate::r2_api::{AliasInfo, R2Api, RegisterInfo};
 use crate::solver::Solver;
 use crate::value::{vc, Value};
@@ -115,6 +116,41 @@ impl Registers {
         registers
     }
 
+    /// Set the general purpose registers other than the pc, stack and frame
+    /// pointers to the uninitialized value `uninit`
+    pub fn fill_uninit(&mut self, uninit: Uninit) {
+        let keep: Vec<usize> = ["PC", "SP", "BP"]
+            .iter()
+            .filter_map(|alias| self.aliases.get(*alias))
+            .filter_map(|alias| self.regs.get(&alias.reg))
+            .map(|reg| reg.value_index)
+            .collect();
+
+        for reg in &self.indexes {
+            let size = reg.reg_info.size;
+            if reg.reg_info.type_str != "gpr"
+                || size != reg.bounds.size
+                || size > 64
+                || reg.reg_info.offset == -1i64 as u64
+                || keep.contains(&reg.value_index)
+            {
+                continue;
+            }
+
+            self.values[reg.value_index] = match uninit {
+                Uninit::Zero => vc(0),
+                Uninit::Poison(byte) => {
+                    let pattern = u64::from_ne_bytes([byte; 8]);
+                    vc(pattern).slice(size - 1, 0)
+                }
+                Uninit::Symbolic => {
+                    let sym_name = format!("uninit_{}", reg.reg_info.name);
+                    Value::Symbolic(self.solver.bv(&sym_name, size as u32), 0)
+                }
+            };
+        }
+    }
+
     /// Get the value of the register `reg`
     #[inline]
     pub fn get(&self, reg: &str) -> Value {
@@ -127,6 +163,18 @@ impl Registers {
         self.set_value(self.regs[reg].index, value)
     }
 
+    /// Get the `width` bits of the register `reg` starting at bit `low`, eg. a flag
+    pub fn get_bits(&self, reg: &str, low: u64, width: u64) -> Value {
+        self.get(reg).slice(low + width - 1, low)
+    }
+
+    /// Set the `width` bits of the register `reg` starting at bit `low`
+    /// to the low bits of `value`, leaving the other bits as they were
+    pub fn set_bits(&mut self, reg: &str, low: u64, width: u64, value: &Value) {
+        let new = self.get(reg).insert_bits(low, width, value);
+        self.set(reg, new)
+    }
+
     #[inline]
     pub fn get_register(&self, reg: &str) -> Option<&Register> {
         self.regs.get(reg)
@@ -182,11 +230,17 @@ impl Registers {
     }
 
     pub fn get_value(&self, index: usize) -> Value {
+        let register = &self.indexes[index];
+        self.extract(index, &self.values[register.value_index])
+    }
+
+    /// Get the value of the register at `index` from `value`,
+    /// the value of the whole register it is part of
+    pub fn extract(&self, index: usize, value: &Value) -> Value {
         let register = &self.indexes[index];
         if register.reg_info.offset == -1i64 as u64 {
             return Value::Concrete(0, 0); // this is a zero register
         }
-        let value = &self.values[register.value_index];
         if register.reg_info.size == register.bounds.size {
             value.to_owned()
         } else {
@@ -0,0 +1,214 @@
+use crate::pretty;
+use crate::processor::Processor;
+use crate::state::{State, StateStatus};
+use crate::value::Value;
+use std::io::{self, BufRead, Write};
+
+/// Bytes shown by `x` if no length is given
+pub const HEXDUMP_LENGTH: usize = 64;
+
+/// Most bytes shown by `x`
+pub const MAX_HEXDUMP_LENGTH: usize = 0x1000;
+
+const HELP: &str = "\
+regs              print the general purpose registers
+r <reg>           print a register and a value it can have
+x <addr> [len]    hexdump memory, addr can be a register
+c                 print the constraints
+s [n]             step n instructions, states that fork off are kept
+states            list the states, the current one is marked with *
+switch <i>        continue with another state
+bt                print the backtrace
+maps              print the memory maps
+q                 quit and return the current state, dropping the others";
+
+/// Inspect the registers, memory, and constraints of a state and step it,
+/// reading commands from `input` until `q` or the end of the input.
+/// Returns the current state, the others it forked are dropped with a
+/// warning, see `Radius::interact`
+pub fn interact<R: BufRead, W: Write>(
+    processor: &mut Processor,
+    state: State,
+    input: R,
+    output: &mut W,
+) -> State {
+    let mut repl = Repl {
+        processor,
+        states: vec![state],
+        current: 0,
+    };
+    let _ = repl.run(input, output);
+    if repl.states.len() > 1 {
+        let _ = writeln!(
+            output,
+            "dropping {} other states, switch to one to keep it",
+            repl.states.len() - 1
+        );
+    }
+    repl.states.swap_remove(repl.current)
+}
+
+struct Repl<'a> {
+    processor: &'a mut Processor,
+    states: Vec<State>,
+    current: usize,
+}
+
+impl Repl<'_> {
+    fn run<R: BufRead, W: Write>(&mut self, input: R, output: &mut W) -> io::Result<()> {
+        self.location(output)?;
+        write!(output, "> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if let Some((command, args)) = words.split_first() {
+                if !self.command(command, args, output)? {
+                    return Ok(());
+                }
+            }
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+
+    // run the command, false if it was quit
+    fn command<W: Write>(
+        &mut self,
+        command: &str,
+        args: &[&str],
+        output: &mut W,
+    ) -> io::Result<bool> {
+        let state = &mut self.states[self.current];
+        match (command, args) {
+            ("q", _) | ("quit", _) => return Ok(false),
+            ("regs", _) => {
+                let names: Vec<String> = state
+                    .registers
+                    .indexes
+                    .iter()
+                    .filter(|r| r.reg_info.type_str == "gpr")
+                    .map(|r| r.reg_info.name.to_owned())
+                    .collect();
+                for name in names {
+                    let value = state.registers.get(&name);
+                    writeln!(output, "{:<8} {}", name, pretty::expression(&value))?;
+                }
+            }
+            ("r", [reg]) if state.registers.get_register(reg).is_some() => {
+                let value = state.registers.get(reg);
+                writeln!(output, "{} = {}", reg, pretty::expression(&value))?;
+                if value.is_symbolic() {
+                    match state.eval(&value).and_then(|v| v.as_u64()) {
+                        Some(v) => writeln!(output, "  can be 0x{:x}", v)?,
+                        None => writeln!(output, "  has no satisfiable value")?,
+                    }
+                }
+            }
+            ("x", [addr, ..]) => match address(state, addr) {
+                Some(addr) => {
+                    let length = args.get(1).and_then(|l| parse_u64(l));
+                    let length = length.unwrap_or(HEXDUMP_LENGTH as u64);
+                    if length > MAX_HEXDUMP_LENGTH as u64 {
+                        writeln!(output, "showing the first 0x{:x} bytes", MAX_HEXDUMP_LENGTH)?;
+                    }
+                    let length = length.min(MAX_HEXDUMP_LENGTH as u64) as usize;
+                    write!(output, "{}", state.hexdump(addr, length))?;
+                }
+                None => writeln!(output, "bad address {}", addr)?,
+            },
+            ("c", _) => {
+                for assertion in &state.solver.assertions {
+                    let value = Value::Symbolic(assertion.to_owned(), 0);
+                    writeln!(output, "{}", pretty::expression(&value))?;
+                }
+            }
+            ("s", _) => {
+                let steps = args.first().and_then(|n| parse_u64(n)).unwrap_or(1);
+                self.step(steps, output)?;
+            }
+            ("states", _) => {
+                for (i, state) in self.states.iter().enumerate() {
+                    let pc = state.registers.get_pc().as_u64().unwrap_or_default();
+                    let mark = if i == self.current { "*" } else { " " };
+                    writeln!(output, "{}{} 0x{:08x} {:?}", mark, i, pc, state.status)?;
+                }
+            }
+            ("switch", [i]) => match i.parse::<usize>() {
+                Ok(i) if i < self.states.len() => {
+                    self.current = i;
+                    self.location(output)?;
+                }
+                _ => writeln!(output, "no state {}", i)?,
+            },
+            ("bt", _) => {
+                for (i, bt) in state.backtrace.iter().rev().enumerate() {
+                    let name = state.r2api.get_flag(bt.1).unwrap_or_default();
+                    writeln!(output, "#{} 0x{:08x} ({})", i, bt.1, name.trim())?;
+                }
+            }
+            ("maps", _) => {
+                for map in state.memory.maps() {
+                    writeln!(output, "{}", map)?;
+                }
+            }
+            _ => writeln!(output, "{}", HELP)?,
+        }
+        Ok(true)
+    }
+
+    // step the current state, the states it forks are added to the list
+    fn step<W: Write>(&mut self, steps: u64, output: &mut W) -> io::Result<()> {
+        for _ in 0..steps {
+            let state = &mut self.states[self.current];
+            if state.status != StateStatus::Active {
+                writeln!(output, "state is {:?}", state.status)?;
+                break;
+            }
+            let forks = self.processor.step(state);
+            if !forks.is_empty() {
+                let first = self.states.len();
+                writeln!(
+                    output,
+                    "forked states {}..{}",
+                    first,
+                    first + forks.len() - 1
+                )?;
+                self.states.extend(forks);
+            }
+            self.location(output)?;
+        }
+        Ok(())
+    }
+
+    // print the instruction at the pc of the current state
+    fn location<W: Write>(&mut self, output: &mut W) -> io::Result<()> {
+        let state = &mut self.states[self.current];
+        match state.registers.get_pc().as_u64() {
+            Some(pc) => {
+                let instr = state.r2api.disassemble(pc, 1).ok();
+                let disasm = instr.and_then(|i| i.first().map(|i| i.disasm.to_owned()));
+                writeln!(output, "0x{:08x}      {}", pc, disasm.unwrap_or_default())
+            }
+            None => writeln!(output, "symbolic pc"),
+        }
+    }
+}
+
+// a register or a hex or decimal number, registers have to be concrete
+fn address(state: &mut State, arg: &str) -> Option<u64> {
+    if state.registers.get_register(arg).is_some() {
+        let value = state.registers.get(arg);
+        state.solver.evalcon_to_u64(&value)
+    } else {
+        parse_u64(arg)
+    }
+}
+
+fn parse_u64(arg: &str) -> Option<u64> {
+    match arg.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => arg.parse().ok(),
+    }
+}
@@ -0,0 +1,154 @@
+use crate::r2_api::Gadget;
+use crate::radius::Radius;
+use crate::state::State;
+use crate::value::vc;
+use std::collections::BTreeMap;
+
+/// Candidate gadgets tried for each register before backtracking
+pub const MAX_CANDIDATES: usize = 16;
+
+// instructions that gadgets must not contain
+const UNSAFE_TYPES: [&str; 10] = [
+    "call", "ucall", "rcall", "jmp", "ujmp", "rjmp", "cjmp", "ret", "store", "trap",
+];
+
+/// A chain of gadgets and the inputs that run it
+#[derive(Debug, Clone, PartialEq)]
+pub struct RopChain {
+    /// Addresses of the gadgets in the order they run
+    pub gadgets: Vec<u64>,
+    /// The inputs of the state that run the chain, see `State::dump_inputs`
+    pub inputs: BTreeMap<String, Vec<u8>>,
+}
+
+/// Find a chain of `pop` gadgets that sets each register in `goals` to
+/// its value and then returns to `target`, from a `state` whose pc and
+/// stack the input controls, like the states `RadiusOption::CheckPcControl`
+/// stops. Each candidate chain is executed symbolically from the state and
+/// the inputs are solved from the constraints, so gadgets with side effects
+/// on other registers also work. When no gadget for a register works with
+/// the ones chosen before, the search backtracks to try other candidates.
+/// Gadgets ending in `ret` are used, so this is for x86
+///
+/// ## Example
+/// ```
+/// use radius2::radius::Radius;
+/// use radius2::rop::find_chain;
+/// let mut radius = Radius::new("/bin/sh");
+/// if let Some(state) = radius.processor.unconstrained.pop() {
+///     if let Some(chain) = find_chain(&mut radius, &state, &[("rdi", 0x1337)], 0x400cb0) {
+///         println!("inputs: {:?}", chain.inputs);
+///     }
+/// }
+/// ```
+pub fn find_chain(
+    radius: &mut Radius,
+    state: &State,
+    goals: &[(&str, u64)],
+    target: u64,
+) -> Option<RopChain> {
+    let candidates: Vec<Vec<Gadget>> = goals
+        .iter()
+        .map(|(reg, _)| {
+            let filter = format!("pop {}", reg);
+            let gadgets = radius.r2api.search_gadgets(&filter).unwrap_or_default();
+            gadgets
+                .into_iter()
+                .filter(is_simple)
+                .take(MAX_CANDIDATES)
+                .collect()
+        })
+        .collect();
+
+    let mut chain = vec![];
+    search(radius, state, goals, &candidates, &mut chain)?;
+    execute_chain(radius, state, &chain, goals, Some(target))
+}
+
+// extend the chain with a gadget for each of the remaining goals,
+// trying the next candidate when the later goals can't be met
+fn search(
+    radius: &mut Radius,
+    state: &State,
+    goals: &[(&str, u64)],
+    candidates: &[Vec<Gadget>],
+    chain: &mut Vec<Gadget>,
+) -> Option<()> {
+    let i = chain.len();
+    if i == goals.len() {
+        return Some(());
+    }
+    for gadget in &candidates[i] {
+        chain.push(gadget.to_owned());
+        if execute_chain(radius, state, chain, &goals[..=i], None).is_some()
+            && search(radius, state, goals, candidates, chain).is_some()
+        {
+            return Some(());
+        }
+        chain.pop();
+    }
+    None
+}
+
+// gadgets that only end in a plain ret and do not write memory
+fn is_simple(gadget: &Gadget) -> bool {
+    if let Some((last, body)) = gadget.opcodes.split_last() {
+        last.opcode == "ret"
+            && body
+                .iter()
+                .all(|op| !UNSAFE_TYPES.contains(&op.r#type.as_str()))
+    } else {
+        false
+    }
+}
+
+// run the gadgets from the controlled state and solve its inputs
+fn execute_chain(
+    radius: &mut Radius,
+    state: &State,
+    chain: &[Gadget],
+    goals: &[(&str, u64)],
+    target: Option<u64>,
+) -> Option<RopChain> {
+    let mut state = state.to_owned();
+    let gadgets: Vec<u64> = chain.iter().map(|g| g.opcodes[0].offset).collect();
+
+    // the controlled pc goes to the first gadget
+    if let Some(first) = gadgets.first().copied().or(target) {
+        let pc = state.registers.get_pc();
+        state.assert(&pc.eq(&vc(first)));
+        state.registers.set_pc(vc(first));
+    }
+    for (i, gadget) in chain.iter().enumerate() {
+        let (_ret, body) = gadget.opcodes.split_last()?;
+        for op in body {
+            radius.processor.execute_instruction(&mut state, op.offset);
+        }
+        pop_return(&mut state, gadgets.get(i + 1).copied().or(target));
+    }
+
+    for (reg, value) in goals {
+        if !state.registers.regs.contains_key(*reg) {
+            return None;
+        }
+        let current = state.registers.get(reg);
+        state.assert(&current.eq(&vc(*value)));
+    }
+    if !state.is_sat() {
+        return None;
+    }
+
+    let inputs = state.dump_inputs();
+    Some(RopChain { gadgets, inputs })
+}
+// pop the return address off the stack and constrain it to `next`
+fn pop_return(state: &mut State, next: Option<u64>) {
+    let ptr = state.memory.bits / 8;
+    let sp = state.registers.get_with_alias("SP");
+    let addr = state.memory_read_value(&sp, ptr as usize);
+    if let Some(next) = next {
+        state.assert(&addr.eq(&vc(next)));
+        state.registers.set_pc(vc(next));
+    }
+    state.registers.set_with_alias("SP", sp + vc(ptr));
+}
@@ -0,0 +1,510 @@
+use crate::manager::{StateManager, FOUND};
+use crate::radius::{Radius, RadiusOption};
+use crate::solver::BitVec;
+use crate::state::State;
+use crate::value::Value;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as Json};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+/// Largest request body or WebSocket frame the server reads
+pub const MAX_MESSAGE: usize = 16 << 20;
+
+/// A JSON-RPC request sent to the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Request {
+    #[serde(default)]
+    pub id: Json,
+    pub method: String,
+    #[serde(default)]
+    pub params: Json,
+}
+
+/// A symbolic input declared in a request, placed in the register or at
+/// the address given by `at`, or filled into stdin if `at` is "stdin"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SymbolSpec {
+    pub name: String,
+    pub bits: u32,
+    #[serde(default)]
+    pub at: String,
+}
+
+/// Serves load/solve/explore operations as JSON-RPC over HTTP or a
+/// WebSocket so that symbolic jobs can be run by non-Rust clients
+///
+/// ## Example
+/// ```no_run
+/// use radius2::server::Server;
+/// let mut server = Server::new();
+/// server.serve("127.0.0.1:8080").unwrap();
+/// ```
+#[derive(Default)]
+pub struct Server {
+    sessions: HashMap<u64, Radius>,
+    next_session: u64,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Server::default()
+    }
+
+    /// Listen for requests on `addr` forever, handling one at a time
+    pub fn serve(&mut self, addr: &str) -> std::io::Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        for stream in listener.incoming() {
+            if let Err(e) = self.handle(stream?) {
+                println!("error handling request: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, mut stream: TcpStream) -> std::io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+
+        let mut length = 0;
+        let mut websocket_key = None;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header)?;
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+            let lower = header.to_lowercase();
+            if let Some(len) = lower.strip_prefix("content-length:") {
+                length = len.trim().parse().unwrap_or(0);
+            } else if lower.starts_with("sec-websocket-key:") {
+                websocket_key = header.split_once(':').map(|(_, key)| key.trim().to_owned());
+            }
+        }
+
+        if let Some(key) = websocket_key.filter(|_| line.starts_with("GET")) {
+            return self.websocket(&mut stream, &mut reader, &key);
+        }
+        if !line.starts_with("POST") {
+            let body = "radius2 server, POST JSON-RPC requests to / or connect a WebSocket";
+            return write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        }
+
+        if length > MAX_MESSAGE {
+            return write!(
+                stream,
+                "HTTP/1.1 413 Payload Too Large\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            );
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+
+        let response = match serde_json::from_slice::<Request>(&body) {
+            Ok(request) if request.method == "explore" => {
+                // results are streamed one state event per chunk
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nTransfer-Encoding: chunked\r\n\r\n"
+                )?;
+                self.explore(&request, &mut |event| {
+                    let data = format!("{}\n", event);
+                    write!(stream, "{:x}\r\n{}\r\n", data.len(), data)?;
+                    stream.flush()
+                })?;
+                return write!(stream, "0\r\n\r\n");
+            }
+            Ok(request) => self.response(&request),
+            Err(e) => error_response(&Json::Null, &e.to_string()),
+        };
+
+        let data = response.to_string();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            data.len(),
+            data
+        )
+    }
+
+    // every text message is a request, explore results are sent one
+    // state event per message and the other results in one message
+    fn websocket(
+        &mut self,
+        stream: &mut TcpStream,
+        reader: &mut BufReader<TcpStream>,
+        key: &str,
+    ) -> std::io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            websocket_accept(key)
+        )?;
+
+        loop {
+            let (opcode, payload) = match read_frame(reader) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => return Ok(()),
+                // close with 1009, the message is too big to process
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    return write_frame(stream, OP_CLOSE, &1009u16.to_be_bytes());
+                }
+                Err(e) => return Err(e),
+            };
+            match opcode {
+                OP_CLOSE => return write_frame(stream, OP_CLOSE, &[]),
+                OP_PING => write_frame(stream, OP_PONG, &payload)?,
+                OP_TEXT => match serde_json::from_slice::<Request>(&payload) {
+                    Ok(request) if request.method == "explore" => {
+                        self.explore(&request, &mut |event| {
+                            write_frame(stream, OP_TEXT, event.to_string().as_bytes())
+                        })?;
+                    }
+                    Ok(request) => {
+                        let response = self.response(&request).to_string();
+                        write_frame(stream, OP_TEXT, response.as_bytes())?;
+                    }
+                    Err(e) => {
+                        let response = error_response(&Json::Null, &e.to_string()).to_string();
+                        write_frame(stream, OP_TEXT, response.as_bytes())?;
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn response(&mut self, request: &Request) -> Json {
+        match self.dispatch(request) {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": request.id, "result": result}),
+            Err(e) => error_response(&request.id, &e),
+        }
+    }
+
+    /// Perform the operation for a single request and return its result
+    pub fn dispatch(&mut self, request: &Request) -> Result<Json, String> {
+        let params = &request.params;
+        match request.method.as_str() {
+            "load" => {
+                let path = params["path"].as_str().ok_or("missing path")?;
+                let options = load_options(params);
+                let radius = Radius::new_with_options(Some(path), &options);
+                let session = self.next_session;
+                self.next_session += 1;
+                self.sessions.insert(session, radius);
+                Ok(json!({ "session": session }))
+            }
+            "close" => {
+                let session = params["session"].as_u64().ok_or("missing session")?;
+                let mut radius = self.sessions.remove(&session).ok_or("no such session")?;
+                radius.close();
+                Ok(Json::Bool(true))
+            }
+            "cmd" => {
+                let radius = self.session(params)?;
+                let cmd = params["cmd"].as_str().ok_or("missing cmd")?;
+                radius.cmd(cmd).map(Json::String)
+            }
+            "solve" => {
+                let radius = self.session(params)?;
+                let (state, symbols) = setup_state(radius, params)?;
+                let target = get_address(radius, &params["target"])?;
+                let avoid = get_addresses(radius, &params["avoid"])?;
+                let result = radius.run_until(state, target, &avoid);
+                radius.clear_breakpoints();
+                if let Some(mut end_state) = result {
+                    Ok(state_event(&mut end_state, &symbols))
+                } else {
+                    Ok(Json::Null)
+                }
+            }
+            _ => Err(format!("unknown method {}", request.method)),
+        }
+    }
+
+    fn session(&mut self, params: &Json) -> Result<&mut Radius, String> {
+        let session = params["session"].as_u64().ok_or("missing session")?;
+        self.sessions
+            .get_mut(&session)
+            .ok_or_else(|| "no such session".to_owned())
+    }
+
+    // run the states to the breakpoints and emit each one as it reaches
+    // one, the breakpoints and addresses to avoid are cleared after
+    fn explore(
+        &mut self,
+        request: &Request,
+        emit: &mut dyn FnMut(&Json) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        let params = &request.params;
+        let radius = match self.session(params) {
+            Ok(radius) => radius,
+            Err(e) => return emit(&error_response(&request.id, &e)),
+        };
+        let job = setup_state(radius, params).and_then(|(state, symbols)| {
+            let breakpoints = get_addresses(radius, &params["breakpoints"])?;
+            let avoid = get_addresses(radius, &params["avoid"])?;
+            Ok((state, symbols, breakpoints, avoid))
+        });
+        let (state, symbols, breakpoints, avoid) = match job {
+            Ok(job) => job,
+            Err(e) => return emit(&error_response(&request.id, &e)),
+        };
+        let target = match breakpoints.first() {
+            Some(target) => *target,
+            None => return emit(&error_response(&request.id, "missing breakpoints")),
+        };
+        for bp in &breakpoints {
+            radius.breakpoint(*bp);
+        }
+
+        let mut manager = StateManager::new(state);
+        manager.keep_ended = false;
+        let mut result = Ok(());
+        while result.is_ok() && radius.explore(&mut manager, target, &avoid, 1) > 0 {
+            for mut state in manager.take(FOUND) {
+                let event = state_event(&mut state, &symbols);
+                result = result.and_then(|_| {
+                    emit(&json!({"jsonrpc": "2.0", "id": request.id, "result": event}))
+                });
+            }
+        }
+        radius.clear_breakpoints();
+        result
+    }
+}
+
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The Sec-WebSocket-Accept of the handshake answering `key`
+pub fn websocket_accept(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+// the opcode and unmasked payload of the next frame, None once closed and
+// an InvalidData error if it is longer than MAX_MESSAGE. fragmented
+// messages are not supported
+fn read_frame<R: Read>(reader: &mut R) -> std::io::Result<Option<(u8, Vec<u8>)>> {
+    let mut head = [0; 2];
+    if reader.read_exact(&mut head).is_err() {
+        return Ok(None);
+    }
+    let length = match head[1] & 0x7f {
+        126 => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as usize
+        }
+        127 => {
+            let mut len = [0; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len).min(MAX_MESSAGE as u64 + 1) as usize
+        }
+        len => len as usize,
+    };
+    if length > MAX_MESSAGE {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "frame too large",
+        ));
+    }
+    let mut mask = [0; 4];
+    if head[1] & 0x80 != 0 {
+        reader.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0; length];
+    reader.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok(Some((head[0] & 0xf, payload)))
+}
+
+fn write_frame<W: Write>(writer: &mut W, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= 0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+pub(crate) fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (word, bytes) in w.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, h) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
+
+pub(crate) fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// the options of a "load" request
+pub(crate) fn load_options(params: &Json) -> Vec<RadiusOption> {
+    vec![
+        RadiusOption::Sims(!params["no_sims"].as_bool().unwrap_or(false)),
+        RadiusOption::Lazy(params["lazy"].as_bool().unwrap_or(true)),
+    ]
+}
+
+fn error_response(id: &Json, message: &str) -> Json {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": -32000, "message": message }
+    })
+}
+
+pub(crate) fn get_address(radius: &mut Radius, value: &Json) -> Result<u64, String> {
+    if let Some(addr) = value.as_u64() {
+        Ok(addr)
+    } else if let Some(sym) = value.as_str() {
+        radius.get_address(sym)
+    } else {
+        Err(format!("bad address {}", value))
+    }
+}
+
+pub(crate) fn get_addresses(radius: &mut Radius, value: &Json) -> Result<Vec<u64>, String> {
+    let mut addrs = vec![];
+    for addr in value.as_array().unwrap_or(&vec![]) {
+        addrs.push(get_address(radius, addr)?);
+    }
+    Ok(addrs)
+}
+
+// create the initial state from "address" and place the declared "symbols"
+pub(crate) fn setup_state(
+    radius: &mut Radius,
+    params: &Json,
+) -> Result<(State, HashMap<String, BitVec>), String> {
+    let mut state = if params["address"].is_null() {
+        radius.entry_state()
+    } else {
+        let addr = get_address(radius, &params["address"])?;
+        radius.call_state(addr)
+    };
+
+    let specs: Vec<SymbolSpec> =
+        serde_json::from_value(params["symbols"].clone()).unwrap_or_default();
+
+    let mut symbols = HashMap::new();
+    for spec in specs {
+        let value = state.symbolic_value(&spec.name, spec.bits);
+        state.register_symbol(&spec.name, &value);
+        if spec.at == "stdin" {
+            let bytes = state.unpack(&value, spec.bits as usize / 8);
+            state.filesystem.fill(0, &bytes);
+        } else if state.registers.regs.contains_key(&spec.at) {
+            state.registers.set(&spec.at, value.clone());
+        } else if !spec.at.is_empty() {
+            let addr = radius.get_address(&spec.at)?;
+            state.memory_write_value(&Value::Concrete(addr, 0), &value, spec.bits as usize / 8);
+        }
+        let bv = value
+            .as_bv()
+            .ok_or_else(|| format!("symbol {} has no symbolic bits", spec.name))?;
+        symbols.insert(spec.name, bv);
+    }
+
+    Ok((state, symbols))
+}
+
+// the pc, status, and symbol solutions of a state
+pub(crate) fn state_event(state: &mut State, symbols: &HashMap<String, BitVec>) -> Json {
+    let mut solutions = HashMap::new();
+    for (name, bv) in symbols {
+        let solution = state
+            .translate(bv)
+            .and_then(|bv| state.solver.eval_to_bv(&Value::Symbolic(bv, 0)))
+            .and_then(|bv| state.solver.hex_solution(&bv));
+        solutions.insert(name.to_owned(), solution);
+    }
+
+    json!({
+        "pc": state.registers.get_pc().as_u64(),
+        "status": format!("{:?}", state.status),
+        "symbols": solutions
+    })
+}
@@ -0,0 +1,75 @@
+use crate::sims::{make_sim, Sim};
+use crate::state::State;
+use crate::value::{vc, Value};
+
+/// Cycles between consecutive rdtsc reads, small enough to pass timing checks
+pub const RDTSC_DELTA: u64 = 0x40;
+pub const RDTSC_START: u64 = 0x1d3c2b1a0000;
+
+const STATUS_SUCCESS: u64 = 0;
+const STATUS_PORT_NOT_SET: u64 = 0xc0000353;
+
+// NtQueryInformationProcess classes used to detect debuggers
+const PROCESS_DEBUG_PORT: u64 = 7;
+const PROCESS_DEBUG_OBJECT_HANDLE: u64 = 0x1e;
+const PROCESS_DEBUG_FLAGS: u64 = 0x1f;
+
+/// Sims for debugger checks that report no debugger is attached
+pub fn get_sims() -> Vec<Sim> {
+    vec![
+        make_sim("IsDebuggerPresent", is_debugger_present, 0),
+        make_sim(
+            "CheckRemoteDebuggerPresent",
+            check_remote_debugger_present,
+            2,
+        ),
+        make_sim("NtQueryInformationProcess", nt_query_information_process, 5),
+        make_sim("ZwQueryInformationProcess", nt_query_information_process, 5),
+    ]
+}
+
+/// Add files that are read to detect tracers with clean contents
+pub fn add_clean_files(state: &mut State) {
+    let name = state.info.core.file.rsplit('/').next().unwrap_or_default();
+    let status = format!(
+        "Name:\t{}\nState:\tR (running)\nTgid:\t{}\nPid:\t{}\nPPid:\t1\n\
+        TracerPid:\t0\nUid:\t0\t0\t0\t0\nGid:\t0\t0\t0\t0\n",
+        name, state.pid, state.pid
+    );
+
+    let data: Vec<Value> = status.bytes().map(|b| vc(b as u64)).collect();
+    state.filesystem.add_file("/proc/self/status", &data);
+    let path = format!("/proc/{}/status", state.pid);
+    state.filesystem.add_file(&path, &data);
+}
+
+pub fn is_debugger_present(_state: &mut State, _args: &[Value]) -> Value {
+    vc(0)
+}
+
+pub fn check_remote_debugger_present(state: &mut State, args: &[Value]) -> Value {
+    state.memory_write_value(&args[1], &vc(0), 4);
+    vc(1)
+}
+
+pub fn nt_query_information_process(state: &mut State, args: &[Value]) -> Value {
+    let class = state.solver.evalcon_to_u64(&args[1]).unwrap_or_default();
+    let ptr = state.memory.bits as usize / 8;
+
+    match class {
+        PROCESS_DEBUG_PORT => {
+            state.memory_write_value(&args[2], &vc(0), ptr);
+            vc(STATUS_SUCCESS)
+        }
+        PROCESS_DEBUG_OBJECT_HANDLE => {
+            state.memory_write_value(&args[2], &vc(0), ptr);
+            vc(STATUS_PORT_NOT_SET)
+        }
+        PROCESS_DEBUG_FLAGS => {
+            // NoDebugInherit is set when there is no debugger
+            state.memory_write_value(&args[2], &vc(1), 4);
+            vc(STATUS_SUCCESS)
+        }
+        _ => vc(STATUS_SUCCESS),
+    }
+}
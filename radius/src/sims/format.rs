@@ -2,6 +2,8 @@ use crate::state::State;
 use crate::value::{vc, Value};
 
 const MAXLEN: usize = 8192;
+// number of stack args read for format strings
+const VARARGS: usize = 16;
 const FORMATS: [char; 19] = [
     'd', 'i', 'u', 'o', 'x', 'X', 'f', 'F', 'e', 'E', 'g', 'G', 'a', 'A', 'c', 's', 'p', 'n', '%',
 ];
@@ -11,6 +13,21 @@ const NONUINTS: [char; 15] = [
     'd', 'i', 'f', 'F', 'e', 'E', 'g', 'G', 'a', 'A', 'c', 's', 'n', '%', 'p',
 ];
 
+// the sim args followed by the variadic args passed on the stack, using
+// the calling convention the processor resolved for the sim
+pub fn with_varargs(state: &mut State, args: &[Value]) -> Vec<Value> {
+    let cc = match state.sim_cc.clone() {
+        Some(cc) => cc,
+        None => {
+            let pc = state.registers.get_pc().as_u64().unwrap_or_default();
+            state.r2api.get_cc(pc).unwrap_or_default()
+        }
+    };
+    let mut all = args.to_vec();
+    all.extend(state.varargs(&cc, args.len()).take(VARARGS));
+    all
+}
+
 // the value returned is the formatted string
 pub fn format(state: &mut State, args: &[Value]) -> Vec<Value> {
     let length = state.memory_strlen(&args[0], &vc(MAXLEN as u64));
@@ -1,4 +1,7 @@
+use crate::sims::net::SimSocket;
+use crate::sims::SimMethod;
 use crate::value::Value;
+use std::collections::HashMap;
 use std::fs;
 // use std::io;
 use std::time::SystemTime;
@@ -44,11 +47,37 @@ pub struct SimFile {
     pub mode: FileMode,
     pub content: Vec<Value>,
     pub metadata: Option<fs::Metadata>,
+    pub pipe: Option<usize>, // read end that writes go to
+}
+
+/// A call that controls a file instead of reading or writing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ControlCall {
+    Ioctl,
+    Fcntl,
+}
+
+/// Matches any file path or any request of a control handler
+pub const ANY_PATH: &str = "*";
+pub const ANY_REQUEST: u64 = u64::MAX;
+
+/// A file that grows by fresh symbolic bytes as it is read past its end
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SymbolicStream {
+    pub max_length: usize,
+    /// Constrain the bytes to printable characters
+    pub printable: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct SimFilesytem {
     pub files: Vec<SimFile>,
+    /// handlers for ioctl and fcntl requests by file path and request
+    pub handlers: HashMap<(ControlCall, String, u64), SimMethod>,
+    /// files that are symbolic streams, by fd
+    pub streams: HashMap<usize, SymbolicStream>,
+    /// sockets by fd, see `SimSocket`
+    pub sockets: HashMap<usize, SimSocket>,
 }
 
 impl Default for SimFilesytem {
@@ -60,7 +89,12 @@ impl Default for SimFilesytem {
 impl SimFilesytem {
     pub fn new() -> Self {
         let files = SimFilesytem::get_stdio();
-        SimFilesytem { files }
+        SimFilesytem {
+            files,
+            handlers: HashMap::new(),
+            streams: HashMap::new(),
+            sockets: HashMap::new(),
+        }
     }
 
     pub fn open(&mut self, path: &str, mode: FileMode) -> Option<usize> {
@@ -88,6 +122,7 @@ impl SimFilesytem {
                 mode,
                 content,
                 metadata: Some(metadata),
+                pipe: None,
             };
 
             self.files.push(file);
@@ -113,6 +148,14 @@ impl SimFilesytem {
         }
     }
 
+    /// Get the number of bytes left to read from the file
+    pub fn remaining(&self, fd: usize) -> usize {
+        self.files
+            .get(fd)
+            .map(|f| f.content.len().saturating_sub(f.position))
+            .unwrap_or_default()
+    }
+
     pub fn fill(&mut self, fd: usize, data: &[Value]) {
         self.files[fd].content.extend(data.to_owned());
     }
@@ -122,12 +165,28 @@ impl SimFilesytem {
     }
 
     pub fn write(&mut self, fd: usize, data: Vec<Value>) {
-        if let Some(file) = &mut self.files.get_mut(fd) {
+        let pipe = self.files.get(fd).and_then(|f| f.pipe);
+        if let Some(socket) = self.sockets.get_mut(&fd) {
+            socket.sent.extend(data);
+        } else if let Some(read_fd) = pipe {
+            self.files[fd].position += data.len();
+            self.fill(read_fd, &data);
+        } else if let Some(file) = &mut self.files.get_mut(fd) {
             file.position += data.len();
             file.content.extend(data);
         }
     }
 
+    /// Create a pipe and return the read and write fds
+    pub fn pipe(&mut self) -> (usize, usize) {
+        let read_fd = self.files.len();
+        self.touch(&format!("pipe:[{}]", read_fd), FileMode::Read);
+        let write_fd = self.files.len();
+        self.touch(&format!("pipe:[{}]:w", read_fd), FileMode::Write);
+        self.files[write_fd].pipe = Some(read_fd);
+        (read_fd, write_fd)
+    }
+
     pub fn seek(&mut self, fd: usize, pos: usize) {
         if let Some(file) = &mut self.files.get_mut(fd) {
             file.position = pos;
@@ -214,11 +273,30 @@ impl SimFilesytem {
             mode,
             content: vec![],
             metadata: None,
+            pipe: None,
         };
 
         self.files.push(file);
     }
 
+    /// Make a new fd for the file of `fd`, writes to either go to the same
+    /// place but each reads from its own position
+    pub fn dup(&mut self, fd: usize) -> Option<usize> {
+        let new_fd = self.files.len();
+        let mut file = self.files.get(fd)?.clone();
+        file.fd = new_fd;
+        file.pipe = file.pipe.or(Some(fd));
+        self.files.push(file);
+
+        if let Some(stream) = self.streams.get(&fd).copied() {
+            self.streams.insert(new_fd, stream);
+        }
+        if let Some(socket) = self.sockets.get(&fd).cloned() {
+            self.sockets.insert(new_fd, socket);
+        }
+        Some(new_fd)
+    }
+
     pub fn close(&mut self, fd: usize) {
         self.seek(fd, 0); // uhh just go to 0 for now
     }
@@ -231,6 +309,7 @@ impl SimFilesytem {
             mode: FileMode::Read,
             content: data.to_owned(),
             metadata: None,
+            pipe: None,
         });
     }
 
@@ -241,6 +320,47 @@ impl SimFilesytem {
         }
     }
 
+    /// Handle `request` on the file at `path` with `handler`, which is
+    /// passed the args of the call. Use `ANY_PATH` or `ANY_REQUEST` to
+    /// handle requests on every file or every request on a file
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::{Radius, State, Value, vc};
+    /// use radius2::sims::fs::ControlCall;
+    /// let mut radius = Radius::new("/bin/sh");
+    /// let mut state = radius.entry_state();
+    ///
+    /// // TIOCGWINSZ reports an 80x24 terminal
+    /// fn winsize(state: &mut State, args: &[Value]) -> Value {
+    ///     state.memory_write_value(&args[2], &vc(0x00500018), 4);
+    ///     vc(0)
+    /// }
+    /// state.filesystem.add_handler(ControlCall::Ioctl, "STDOUT", 0x5413, winsize);
+    /// ```
+    pub fn add_handler(&mut self, call: ControlCall, path: &str, request: u64, handler: SimMethod) {
+        self.handlers
+            .insert((call, path.to_owned(), request), handler);
+    }
+
+    /// Get the handler for `request` on the file `fd`
+    pub fn get_handler(&self, call: ControlCall, fd: usize, request: u64) -> Option<SimMethod> {
+        let path = self
+            .files
+            .get(fd)
+            .map(|f| f.path.as_str())
+            .unwrap_or_default();
+        let keys = [
+            (call, path, request),
+            (call, ANY_PATH, request),
+            (call, path, ANY_REQUEST),
+            (call, ANY_PATH, ANY_REQUEST),
+        ];
+        keys.iter()
+            .find_map(|(c, p, r)| self.handlers.get(&(*c, p.to_string(), *r)))
+            .copied()
+    }
+
     pub fn get_stdio() -> Vec<SimFile> {
         vec![
             SimFile {
@@ -250,6 +370,7 @@ impl SimFilesytem {
                 mode: FileMode::Read,
                 content: Vec::with_capacity(256),
                 metadata: None,
+                pipe: None,
             },
             SimFile {
                 path: "STDOUT".to_owned(),
@@ -258,6 +379,7 @@ impl SimFilesytem {
                 mode: FileMode::Write,
                 content: Vec::with_capacity(256),
                 metadata: None,
+                pipe: None,
             },
             SimFile {
                 path: "STDERR".to_owned(),
@@ -266,6 +388,7 @@ impl SimFilesytem {
                 mode: FileMode::Write,
                 content: Vec::with_capacity(256),
                 metadata: None,
+                pipe: None,
             },
         ]
     }
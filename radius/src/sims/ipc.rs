@@ -0,0 +1,188 @@
+use crate::sims::libc;
+use crate::state::State;
+use crate::value::{vc, Value};
+
+// these models never block, so single-threaded execution keeps going
+// past synchronization with processes or threads that do not exist.
+// the sys_ functions return -errno like the kernel, the others are the
+// libc functions that return -1 and set errno
+
+const IPC_PRIVATE: u64 = 0;
+const EAGAIN: i64 = 11;
+const EINVAL: i64 = 22;
+
+const FUTEX_WAIT: u64 = 0;
+const FUTEX_WAKE: u64 = 1;
+const FUTEX_WAIT_BITSET: u64 = 9;
+const FUTEX_WAKE_BITSET: u64 = 10;
+// op without the private and clock flags
+const FUTEX_CMD_MASK: u64 = 0x7f;
+
+/// A wait on a futex or semaphore that returned instead of blocking
+#[derive(Debug, Clone, PartialEq)]
+pub struct IpcEvent {
+    pub pc: u64,
+    /// Address of the futex or semaphore
+    pub addr: u64,
+}
+
+/// Get the waits that would have blocked, see `IpcEvent`
+pub fn waits(state: &State) -> Vec<IpcEvent> {
+    let waits = state.context.get("ipc_waits").cloned().unwrap_or_default();
+    waits
+        .chunks(2)
+        .map(|w| IpcEvent {
+            pc: w[0].as_u64().unwrap_or_default(),
+            addr: w.get(1).and_then(|a| a.as_u64()).unwrap_or_default(),
+        })
+        .collect()
+}
+
+fn record_wait(state: &mut State, addr: u64) {
+    let pc = state.registers.get_pc();
+    let waits = state.context.entry("ipc_waits".to_owned()).or_default();
+    waits.push(pc);
+    waits.push(vc(addr));
+}
+
+// shared memory segments are kept in the context as [key, size, addr]
+fn segments(state: &State) -> Vec<Value> {
+    state.context.get("shm").cloned().unwrap_or_default()
+}
+
+pub fn shmget(state: &mut State, args: &[Value]) -> Value {
+    let key = state.solver.evalcon_to_u64(&args[0]).unwrap_or_default();
+    let size = state.solver.evalcon_to_u64(&args[1]).unwrap_or_default();
+
+    let shm = segments(state);
+    if key != IPC_PRIVATE {
+        if let Some(id) = shm.chunks(3).position(|s| s[0].as_u64() == Some(key)) {
+            return vc(id as u64);
+        }
+    }
+
+    let id = shm.len() / 3;
+    let entry = state.context.entry("shm".to_owned()).or_default();
+    entry.extend(vec![vc(key), vc(size), vc(0)]);
+    vc(id as u64)
+}
+
+pub fn shmat(state: &mut State, args: &[Value]) -> Value {
+    // (void *) -1 on errors
+    let addr = sys_shmat(state, args);
+    libc::syscall_result(state, addr)
+}
+
+pub fn sys_shmat(state: &mut State, args: &[Value]) -> Value {
+    let id = state.solver.evalcon_to_u64(&args[0]).unwrap_or_default() as usize;
+    let shm = segments(state);
+    if id * 3 >= shm.len() {
+        return Value::Concrete(-EINVAL as u64, 0);
+    }
+
+    // every attach of a segment gets the same memory
+    if let Some(addr) = shm[id * 3 + 2].as_u64().filter(|a| *a != 0) {
+        return vc(addr);
+    }
+    let size = shm[id * 3 + 1].to_owned();
+    let addr = state.memory_alloc(&size);
+    let bytes = vec![vc(0); size.as_u64().unwrap_or_default() as usize];
+    state.memory_write(&addr, &bytes, &size);
+    if let Some(entry) = state.context.get_mut("shm") {
+        entry[id * 3 + 2] = addr.to_owned();
+    }
+    addr
+}
+
+pub fn shmdt(_state: &mut State, _args: &[Value]) -> Value {
+    vc(0)
+}
+
+// removed segments stay attached until detached, like linux
+pub fn shmctl(_state: &mut State, _args: &[Value]) -> Value {
+    vc(0)
+}
+
+pub fn sys_futex(state: &mut State, args: &[Value]) -> Value {
+    let addr = state.solver.evalcon_to_u64(&args[0]).unwrap_or_default();
+    let op = state.solver.evalcon_to_u64(&args[1]).unwrap_or_default() & FUTEX_CMD_MASK;
+
+    match op {
+        FUTEX_WAIT | FUTEX_WAIT_BITSET => {
+            let value = state.memory_read_value(&args[0], 4);
+            let expected = args[2].slice(31, 0);
+            if let (Some(v), Some(e)) = (value.as_u64(), expected.as_u64()) {
+                if v != e {
+                    return Value::Concrete(-EAGAIN as u64, 0);
+                }
+            }
+            // return as if woken up by another thread
+            record_wait(state, addr);
+            vc(0)
+        }
+        // no one else is waiting
+        FUTEX_WAKE | FUTEX_WAKE_BITSET => vc(0),
+        _ => vc(0),
+    }
+}
+
+pub fn semget(state: &mut State, args: &[Value]) -> Value {
+    let key = state.solver.evalcon_to_u64(&args[0]).unwrap_or_default();
+    let sems = state.context.entry("sem".to_owned()).or_default();
+    if key != IPC_PRIVATE {
+        if let Some(id) = sems.iter().position(|k| k.as_u64() == Some(key)) {
+            return vc(id as u64);
+        }
+    }
+    sems.push(vc(key));
+    vc(sems.len() as u64 - 1)
+}
+
+pub fn semop(_state: &mut State, _args: &[Value]) -> Value {
+    vc(0)
+}
+
+pub fn semctl(_state: &mut State, _args: &[Value]) -> Value {
+    vc(0)
+}
+
+// posix semaphores keep their count in the first 4 bytes of the sem_t
+
+pub fn sem_init(state: &mut State, args: &[Value]) -> Value {
+    state.memory_write_value(&args[0], &args[2].slice(31, 0), 4);
+    vc(0)
+}
+
+pub fn sem_post(state: &mut State, args: &[Value]) -> Value {
+    let count = state.memory_read_value(&args[0], 4);
+    state.memory_write_value(&args[0], &count.add(&vc(1)).slice(31, 0), 4);
+    vc(0)
+}
+
+pub fn sem_wait(state: &mut State, args: &[Value]) -> Value {
+    let addr = state.solver.evalcon_to_u64(&args[0]).unwrap_or_default();
+    let count = state.memory_read_value(&args[0], 4);
+    if count.as_u64() == Some(0) {
+        // return as if posted by another thread
+        record_wait(state, addr);
+    } else {
+        state.memory_write_value(&args[0], &count.sub(&vc(1)).slice(31, 0), 4);
+    }
+    vc(0)
+}
+
+pub fn sem_trywait(state: &mut State, args: &[Value]) -> Value {
+    let count = state.memory_read_value(&args[0], 4);
+    if count.as_u64() == Some(0) {
+        libc::set_errno(state, EAGAIN);
+        Value::Concrete(-1i64 as u64, 0)
+    } else {
+        sem_wait(state, args)
+    }
+}
+
+pub fn sem_getvalue(state: &mut State, args: &[Value]) -> Value {
+    let count = state.memory_read_value(&args[0], 4);
+    state.memory_write_value(&args[1], &count, 4);
+    vc(0)
+}
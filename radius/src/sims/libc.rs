@@ -2,7 +2,6 @@ use crate::sims::format;
 use crate::sims::syscall;
 use crate::state::State;
 use crate::value::{vc, Value};
-use rand::Rng;
 
 const MAX_LEN: u64 = 8192;
 
@@ -36,8 +35,7 @@ fn readline(state: &mut State, args: &[Value]) -> Value {
 
     loop {
         let c = state
-            .filesystem
-            .read(fd, 1)
+            .read_file(fd, 1)
             .get(0)
             .unwrap_or(&vc(-1i64 as u64))
             .to_owned();
@@ -62,23 +60,24 @@ fn readline(state: &mut State, args: &[Value]) -> Value {
 
 pub fn getchar(state: &mut State, _args: &[Value]) -> Value {
     state
-        .filesystem
-        .read(0, 1)
+        .read_file(0, 1)
         .get(0)
         .unwrap_or(&vc(-1i64 as u64))
         .to_owned()
 }
 
 pub fn fprintf(state: &mut State, args: &[Value]) -> Value {
+    let args = &format::with_varargs(state, args);
     let fd = fileno(state, &args[0..1]);
     let fdn = state.solver.evalcon_to_u64(&fd).unwrap_or(1);
-    let formatted = format::format(state, args);
+    let formatted = format::format(state, &args[1..]);
     let ret = vc(formatted.len() as u64);
     state.filesystem.write(fdn as usize, formatted);
     ret
 }
 
 pub fn sprintf(state: &mut State, args: &[Value]) -> Value {
+    let args = &format::with_varargs(state, args);
     let formatted = format::format(state, &args[1..]);
     let ret = vc(formatted.len() as u64);
     state.memory_write(&args[0], &formatted, &vc(formatted.len() as u64));
@@ -86,6 +85,7 @@ pub fn sprintf(state: &mut State, args: &[Value]) -> Value {
 }
 
 pub fn printf(state: &mut State, args: &[Value]) -> Value {
+    let args = &format::with_varargs(state, args);
     let formatted = format::format(state, args);
     let ret = vc(formatted.len() as u64);
     state.filesystem.write(1, formatted);
@@ -93,14 +93,17 @@ pub fn printf(state: &mut State, args: &[Value]) -> Value {
 }
 
 pub fn scanf(state: &mut State, args: &[Value]) -> Value {
+    let mut args = format::with_varargs(state, args);
     let buf = state.memory_alloc(&vc(MAX_LEN));
     gets(state, &[buf.clone()]);
-    let result = format::scan(state, &[&[buf.clone()], args].concat());
+    args.insert(0, buf.clone());
+    let result = format::scan(state, &args);
     state.memory_free(&buf);
     result
 }
 
 pub fn sscanf(state: &mut State, args: &[Value]) -> Value {
+    let args = &format::with_varargs(state, args);
     format::scan(state, args)
 }
 
@@ -258,12 +261,13 @@ pub fn strncat(state: &mut State, args: &[Value]) -> Value {
 
 pub fn memset(state: &mut State, args: &[Value]) -> Value {
     let mut data = vec![];
-    let length = state.solver.max_value(&args[2]) & 0xffffffff;
+    let count = state.bound_length(&args[2]);
+    let length = state.solver.max_value(&count) & 0xffffffff;
     for _ in 0..length {
         data.push(args[1].to_owned());
     }
 
-    state.memory_write(&args[0], &data, &args[2]);
+    state.memory_write(&args[0], &data, &count);
     args[0].to_owned()
 }
 
@@ -300,19 +304,11 @@ pub fn memcmp(state: &mut State, args: &[Value]) -> Value {
 }
 
 pub fn strcmp(state: &mut State, args: &[Value]) -> Value {
-    let len1 = state.memory_strlen(&args[0], &vc(MAX_LEN));
-    let len2 = state.memory_strlen(&args[1], &vc(MAX_LEN));
-    let length = state.cond(&(len1.ult(&len2)), &len1, &len2) + vc(1);
-
-    state.memory_compare(&args[0], &args[1], &length)
+    state.memory_strcmp(&args[0], &args[1], &vc(MAX_LEN))
 }
 
 pub fn strncmp(state: &mut State, args: &[Value]) -> Value {
-    let len1 = state.memory_strlen(&args[0], &args[2]);
-    let len2 = state.memory_strlen(&args[1], &args[2]);
-    let length = state.cond(&(len1.ult(&len2)), &len1, &len2) + vc(1);
-
-    state.memory_compare(&args[0], &args[1], &length)
+    state.memory_strcmp(&args[0], &args[1], &args[2])
 }
 
 // TODO properly handle sym slens
@@ -346,11 +342,30 @@ pub fn malloc(state: &mut State, args: &[Value]) -> Value {
 }
 
 pub fn realloc(state: &mut State, args: &[Value]) -> Value {
-    malloc(state, &args[1..])
+    let addr = state.solver.evalcon_to_u64(&args[0]).unwrap_or_default();
+    if addr == 0 {
+        return malloc(state, &args[1..]);
+    } else if args[1].as_u64() == Some(0) {
+        state.memory_free(&args[0]);
+        return vc(0);
+    }
+
+    // copy what fits of the old allocation and free it
+    let new = state.memory_alloc(&args[1]);
+    let old_size = state.memory.allocation_size(addr).unwrap_or_default();
+    let length = state.cond(&args[1].ult(&vc(old_size)), &args[1], &vc(old_size));
+    state.memory_move(&new, &args[0], &length);
+    state.memory_free(&args[0]);
+    new
 }
 
 pub fn calloc(state: &mut State, args: &[Value]) -> Value {
-    state.memory_alloc(&args[0].mul(&args[1]))
+    let length = args[0].mul(&args[1]);
+    let addr = state.memory_alloc(&length);
+    // freed memory may be reused so it has to be cleared
+    let size = state.solver.max_value(&length).min(MAX_LEN) as usize;
+    state.memory_write(&addr, &vec![vc(0); size], &length);
+    addr
 }
 
 pub fn free(state: &mut State, args: &[Value]) -> Value {
@@ -366,6 +381,10 @@ pub fn munmap(state: &mut State, args: &[Value]) -> Value {
     syscall::munmap(state, args)
 }
 
+pub fn mprotect(state: &mut State, args: &[Value]) -> Value {
+    syscall::mprotect(state, args)
+}
+
 pub fn c_syscall(state: &mut State, args: &[Value]) -> Value {
     syscall::syscall("indirect_syscall", state, args)
 }
@@ -383,17 +402,28 @@ pub fn __libc_start_main(state: &mut State, args: &[Value]) -> Value {
     // so until then
 
     // go to main
+    set_main_return(state);
     state.registers.set_with_alias("PC", main);
     state.registers.set_with_alias("A0", argc);
     state.registers.set_with_alias("A1", argv);
 
-    // TODO set env
-    state.registers.set_with_alias("A2", vc(0));
+    // envp as set up by set_argv_env
+    let env = state.context.get("env").map(|e| e[0].to_owned());
+    state
+        .registers
+        .set_with_alias("A2", env.unwrap_or_else(|| vc(0)));
 
     // uh in case we are overwriting A0
     args[1].to_owned()
 }
 
+/// Record where main returns to so that returning from main exits,
+/// call this before jumping to main from `__libc_start_main`
+pub fn set_main_return(state: &mut State) {
+    let ret = state.return_address();
+    state.context.insert("main_return".to_owned(), vec![ret]);
+}
+
 /*
 type = struct _IO_FILE {
 /*    0      |     4 */    int _flags;
@@ -452,6 +482,11 @@ pub fn fileno(state: &mut State, args: &[Value]) -> Value {
 pub fn fopen(state: &mut State, args: &[Value]) -> Value {
     // we are reaching levels of jank code previously undreamt
     let fd = syscall::open(state, args);
+    file_struct(state, fd)
+}
+
+/// Allocate a FILE struct for the file descriptor `fd`
+pub fn file_struct(state: &mut State, fd: Value) -> Value {
     let file_struct = state.memory.alloc(&vc(216));
 
     let fd_addr = if state.info.bin.os == "darwin" {
@@ -605,8 +640,7 @@ pub fn zero(_state: &mut State, _args: &[Value]) -> Value {
 }
 
 pub fn rand(state: &mut State, _args: &[Value]) -> Value {
-    let r = rand::thread_rng().gen::<u64>();
-    let rand = state.symbolic_value(&format!("rand_{}", r), 64);
+    let rand = state.new_symbol("rand", 64);
 
     let rand_vec = &mut state
         .context
@@ -815,11 +849,35 @@ pub fn sleep(_state: &mut State, _args: &[Value]) -> Value {
 }
 
 pub fn __errno_location(state: &mut State, _args: &[Value]) -> Value {
+    if let Some(addr) = state.context.get("errno").and_then(|e| e.first()) {
+        return addr.to_owned();
+    }
     let addr = state.memory_alloc(&vc(8));
     state.memory_write_value(&addr, &vc(0), 8);
+    state
+        .context
+        .insert("errno".to_owned(), vec![addr.to_owned()]);
     addr
 }
 
+/// Set errno, which is kept where `__errno_location` points
+pub fn set_errno(state: &mut State, errno: i64) {
+    let addr = __errno_location(state, &[]);
+    state.memory_write_value(&addr, &vc(errno as u64), 4);
+}
+
+/// Get the libc return value of a syscall that returned `ret`, errors
+/// from -4095 to -1 set errno and return -1
+pub fn syscall_result(state: &mut State, ret: Value) -> Value {
+    match ret.as_u64().map(|r| r as i64) {
+        Some(err) if (-4095..0).contains(&err) => {
+            set_errno(state, -err);
+            Value::Concrete(-1i64 as u64, 0)
+        }
+        _ => ret,
+    }
+}
+
 pub fn open(state: &mut State, args: &[Value]) -> Value {
     syscall::open(state, args)
 }
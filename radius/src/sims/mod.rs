@@ -1,10 +1,15 @@
 use crate::state::State;
 use crate::value::Value;
 
+pub mod antidebug;
 pub mod format;
 pub mod fs;
+pub mod ipc;
 pub mod libc;
+pub mod net;
+pub mod process;
 pub mod syscall;
+pub mod windows;
 
 pub type SimMethod = fn(&mut State, &[Value]) -> Value;
 
@@ -74,7 +79,7 @@ pub fn get_sims() -> Vec<Sim> {
         make_sim("ferror", libc::zero, 1),
         make_sim("fseek", libc::fseek, 2),
         make_sim("ftell", libc::ftell, 1),
-        make_sim("fclose", libc::fgets, 1),
+        make_sim("fclose", libc::fclose, 1),
         make_sim("strlen", libc::strlen, 1),
         make_sim("strnlen", libc::strnlen, 2),
         make_sim("strstr", libc::strstr, 2),
@@ -113,6 +118,7 @@ pub fn get_sims() -> Vec<Sim> {
         make_sim("free", libc::strnlen, 1),
         make_sim("mmap", libc::mmap, 4),
         make_sim("munmap", libc::munmap, 2),
+        make_sim("mprotect", libc::mprotect, 3),
         make_sim("brk", libc::brk, 1),
         make_sim("sbrk", libc::sbrk, 1),
         make_sim("atoi", libc::atoi, 1),
@@ -149,13 +155,48 @@ pub fn get_sims() -> Vec<Sim> {
         make_sim("sleep", libc::sleep, 1),
         make_sim("getpid", libc::getpid, 0),
         make_sim("fork", libc::fork, 0),
+        make_sim("popen", process::popen, 2),
+        make_sim("pclose", process::pclose, 1),
+        make_sim("system", process::system, 1),
+        make_sim("pipe", syscall::pipe, 1),
+        make_sim("pipe2", syscall::pipe, 2),
+        make_sim("socket", net::socket, 3),
+        make_sim("connect", net::connect, 3),
+        make_sim("bind", net::bind, 3),
+        make_sim("listen", zero, 2),
+        make_sim("accept", net::accept, 3),
+        make_sim("accept4", net::accept, 4),
+        make_sim("recv", net::recv, 4),
+        make_sim("recvfrom", net::recv, 6),
+        make_sim("send", net::send, 4),
+        make_sim("sendto", net::sendto, 6),
+        make_sim("setsockopt", zero, 5),
+        make_sim("getsockopt", zero, 5),
+        make_sim("shutdown", zero, 2),
+        make_sim("shmget", ipc::shmget, 3),
+        make_sim("shmat", ipc::shmat, 3),
+        make_sim("shmdt", ipc::shmdt, 1),
+        make_sim("shmctl", ipc::shmctl, 3),
+        make_sim("semget", ipc::semget, 3),
+        make_sim("semop", ipc::semop, 3),
+        make_sim("semtimedop", ipc::semop, 4),
+        make_sim("semctl", ipc::semctl, 3),
+        make_sim("sem_init", ipc::sem_init, 3),
+        make_sim("sem_destroy", zero, 1),
+        make_sim("sem_post", ipc::sem_post, 1),
+        make_sim("sem_wait", ipc::sem_wait, 1),
+        make_sim("sem_timedwait", ipc::sem_wait, 2),
+        make_sim("sem_trywait", ipc::sem_trywait, 1),
+        make_sim("sem_getvalue", ipc::sem_getvalue, 2),
+        make_sim("ioctl", syscall::ioctl, 3),
+        make_sim("fcntl", syscall::fcntl, 3),
         make_sim("ptrace", libc::ptrace, 0),
         make_sim("syscall", libc::c_syscall, 0),
         make_sim("getenv", libc::getenv, 1),
+        make_sim("secure_getenv", libc::getenv, 1),
         make_sim("abort", libc::exit, 1),
         make_sim("exit", libc::exit, 1),
         make_sim("xexit", libc::exit, 1),
-        make_sim("ioctl", error, 1),
         make_sim("sysctl", zero, 1),
         make_sim("setbuf", zero, 2),
         make_sim("rand", libc::rand, 0),
@@ -167,5 +208,7 @@ pub fn get_sims() -> Vec<Sim> {
         make_sim("__libc_start_main", libc::__libc_start_main, 5),
         make_sim("__cfi_slowpath", zero, 0),
         make_sim("__errno_location", libc::__errno_location, 0),
+        make_sim("_strdup", libc::strdup, 1),
+        make_sim("_exit", libc::exit, 1),
     ]
 }
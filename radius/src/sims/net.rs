@@ -0,0 +1,193 @@
+use crate::sims::fs::{FileMode, SymbolicStream};
+use crate::sims::syscall;
+use crate::state::State;
+use crate::value::{vc, Value};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+
+/// Number of symbolic bytes a connection receives if no data was added
+pub const RECV_LENGTH: usize = 1024;
+
+const AF_UNIX: u64 = 1;
+const AF_INET: u64 = 2;
+const AF_INET6: u64 = 10;
+// type without the nonblock and cloexec flags
+const SOCK_TYPE_MASK: u64 = 0xf;
+const ENOTSOCK: i64 = 88;
+
+/// A socket, the data it receives is read from its file like a pipe
+/// and the data it sends is kept here
+#[derive(Debug, Clone)]
+pub struct SimSocket {
+    pub fd: usize,
+    pub domain: u64,
+    pub kind: u64,
+    /// The address connected to, or bound to if listening
+    pub address: Option<String>,
+    pub sent: Vec<Value>,
+}
+
+/// The path of the file holding the data received from `address`, add
+/// a file at this path to give connections concrete data to receive.
+/// Connections accepted on a socket bound to `address` receive it too
+///
+/// ## Example
+/// ```
+/// use radius2::radius::Radius;
+/// use radius2::sims::net::socket_path;
+/// let mut radius = Radius::new("/bin/sh");
+/// let mut state = radius.entry_state();
+/// let response = radius2::value::byte_values("HTTP/1.1 200 OK\r\n\r\n");
+/// state.filesystem.add_file(&socket_path("10.0.0.1:80"), &response);
+/// ```
+pub fn socket_path(address: &str) -> String {
+    format!("socket:{}", address)
+}
+
+/// Get the data the state sent to `address`, over every socket connected to it
+pub fn sent(state: &State, address: &str) -> Vec<Value> {
+    let mut sockets: Vec<&SimSocket> = state
+        .filesystem
+        .sockets
+        .values()
+        .filter(|s| s.address.as_deref() == Some(address))
+        .collect();
+    sockets.sort_by_key(|s| s.fd);
+    sockets.iter().flat_map(|s| s.sent.to_owned()).collect()
+}
+
+// read a sockaddr as an address like 10.0.0.1:80, [::1]:80, or a unix path
+fn read_address(state: &mut State, addr: &Value) -> String {
+    let addr = state.solver.evalcon_to_u64(addr).unwrap_or_default();
+    let family = state.memory_read_value(&vc(addr), 2);
+    let family = state.solver.evalcon_to_u64(&family).unwrap_or_default();
+    match family {
+        AF_INET => {
+            let b = state.memory_read_bytes(addr + 2, 6);
+            let ip = Ipv4Addr::new(b[2], b[3], b[4], b[5]);
+            SocketAddrV4::new(ip, u16::from_be_bytes([b[0], b[1]])).to_string()
+        }
+        AF_INET6 => {
+            let b = state.memory_read_bytes(addr + 2, 22);
+            let mut ip = [0u8; 16];
+            ip.copy_from_slice(&b[6..22]);
+            let port = u16::from_be_bytes([b[0], b[1]]);
+            SocketAddrV6::new(Ipv6Addr::from(ip), port, 0, 0).to_string()
+        }
+        AF_UNIX => state.memory_read_cstring(addr + 2),
+        _ => format!("family:{}", family),
+    }
+}
+
+fn new_socket(state: &mut State, domain: u64, kind: u64) -> usize {
+    let fd = state.filesystem.files.len();
+    state
+        .filesystem
+        .touch(&format!("socket:[{}]", fd), FileMode::Read);
+    let socket = SimSocket {
+        fd,
+        domain,
+        kind,
+        address: None,
+        sent: vec![],
+    };
+    state.filesystem.sockets.insert(fd, socket);
+    fd
+}
+
+// connect the socket, it receives the data added for the address
+// or symbolic bytes. connections to one address receive the same data
+fn connect_socket(state: &mut State, fd: usize, address: &str) {
+    let path = socket_path(address);
+    let scoped = state.in_scope(&path);
+    let fs = &mut state.filesystem;
+    let added = fs
+        .files
+        .iter()
+        .find(|f| f.path == path && !fs.sockets.contains_key(&f.fd))
+        .map(|f| f.content.to_owned());
+
+    // connections out of scope receive nothing
+    if added.is_none() && scoped {
+        let stream = SymbolicStream {
+            max_length: RECV_LENGTH,
+            printable: false,
+        };
+        fs.streams.insert(fd, stream);
+    }
+    let file = &mut fs.files[fd];
+    file.path = path;
+    file.position = 0;
+    file.content = added.unwrap_or_default();
+    if let Some(socket) = fs.sockets.get_mut(&fd) {
+        socket.address = Some(address.to_owned());
+    }
+}
+
+// get the fd of the socket, None if it is not one
+fn socket_fd(state: &mut State, fd: &Value) -> Option<usize> {
+    let fd = state.solver.evalcon_to_u64(fd).unwrap_or_default() as usize;
+    state.filesystem.sockets.contains_key(&fd).then_some(fd)
+}
+
+pub fn socket(state: &mut State, args: &[Value]) -> Value {
+    let domain = state.solver.evalcon_to_u64(&args[0]).unwrap_or_default();
+    let kind = state.solver.evalcon_to_u64(&args[1]).unwrap_or_default() & SOCK_TYPE_MASK;
+    vc(new_socket(state, domain, kind) as u64)
+}
+
+pub fn connect(state: &mut State, args: &[Value]) -> Value {
+    if let Some(fd) = socket_fd(state, &args[0]) {
+        let address = read_address(state, &args[1]);
+        connect_socket(state, fd, &address);
+        vc(0)
+    } else {
+        Value::Concrete(-ENOTSOCK as u64, 0)
+    }
+}
+
+// a bound socket receives like one connected to its address
+pub fn bind(state: &mut State, args: &[Value]) -> Value {
+    if let Some(fd) = socket_fd(state, &args[0]) {
+        let address = read_address(state, &args[1]);
+        connect_socket(state, fd, &address);
+        vc(0)
+    } else {
+        Value::Concrete(-ENOTSOCK as u64, 0)
+    }
+}
+
+// there is always a client waiting, the peer address is left unset
+pub fn accept(state: &mut State, args: &[Value]) -> Value {
+    let listening = socket_fd(state, &args[0]).and_then(|fd| state.filesystem.sockets.get(&fd));
+    if let Some(listening) = listening.cloned() {
+        let fd = new_socket(state, listening.domain, listening.kind);
+        let address = listening.address.unwrap_or_default();
+        connect_socket(state, fd, &address);
+        vc(fd as u64)
+    } else {
+        Value::Concrete(-ENOTSOCK as u64, 0)
+    }
+}
+
+pub fn recv(state: &mut State, args: &[Value]) -> Value {
+    syscall::read(state, &args[0..3])
+}
+
+pub fn send(state: &mut State, args: &[Value]) -> Value {
+    syscall::write(state, &args[0..3])
+}
+
+// sending to an address connects a socket that is not connected yet
+pub fn sendto(state: &mut State, args: &[Value]) -> Value {
+    let fd = socket_fd(state, &args[0]);
+    let unconnected = fd
+        .and_then(|fd| state.filesystem.sockets.get(&fd))
+        .map(|s| s.address.is_none())
+        .unwrap_or_default();
+    let dest = args.get(4).filter(|a| a.as_u64() != Some(0));
+    if let (true, Some(dest)) = (unconnected, dest) {
+        let address = read_address(state, dest);
+        connect_socket(state, fd.unwrap(), &address);
+    }
+    send(state, args)
+}
@@ -0,0 +1,105 @@
+use crate::sims::fs::FileMode;
+use crate::sims::libc::{file_struct, fileno};
+use crate::sims::syscall;
+use crate::state::State;
+use crate::value::{vc, Value};
+
+/// Number of symbolic bytes a command outputs if no output was added
+pub const COMMAND_OUTPUT_LEN: usize = 64;
+const MAX_LEN: u64 = 8192;
+
+/// The path of the file holding the output of `command`, add a file at
+/// this path to give a command concrete or structured output
+///
+/// ## Example
+/// ```
+/// use radius2::radius::Radius;
+/// use radius2::sims::process::output_path;
+/// let mut radius = Radius::new("/bin/sh");
+/// let mut state = radius.entry_state();
+/// let output = radius2::value::byte_values("Linux\n");
+/// state.filesystem.add_file(&output_path("uname"), &output);
+/// ```
+pub fn output_path(command: &str) -> String {
+    format!("popen:{}", command)
+}
+
+/// Get the commands run by the state, and whether they depend on input
+pub fn commands(state: &mut State) -> Vec<(String, bool)> {
+    let commands = state.context.get("commands").cloned().unwrap_or_default();
+    commands
+        .iter()
+        .map(|c| {
+            let bytes = state.unpack(c, c.size() as usize / 8);
+            let tainted = bytes.iter().any(|b| b.is_symbolic() || b.get_taint() != 0);
+            (concrete_string(state, &bytes), tainted)
+        })
+        .collect()
+}
+
+fn concrete_string(state: &mut State, bytes: &[Value]) -> String {
+    bytes
+        .iter()
+        .map(|b| state.solver.eval_to_u64(b).unwrap_or_default() as u8 as char)
+        .collect()
+}
+
+// read the command string, record it and return its concrete value
+fn record_command(state: &mut State, ptr: &Value) -> String {
+    let length = state.memory_strlen(ptr, &vc(MAX_LEN));
+    let bytes = state.memory_read(ptr, &length);
+
+    if bytes.is_empty() {
+        return String::new();
+    }
+
+    let tainted = bytes.iter().any(|b| b.is_symbolic() || b.get_taint() != 0);
+    let command = concrete_string(state, &bytes);
+    if tainted {
+        println!("warning: command \"{}\" depends on input", command);
+    }
+
+    let value = state.pack(&bytes);
+    state
+        .context
+        .entry("commands".to_owned())
+        .or_default()
+        .push(value);
+    command
+}
+
+pub fn popen(state: &mut State, args: &[Value]) -> Value {
+    let command = record_command(state, &args[0]);
+    // the first byte of the mode string
+    let mode = state.memory_read_value(&args[1], 1);
+    let mode = state.solver.evalcon_to_u64(&mode).unwrap_or_default() as u8;
+
+    let path = output_path(&command);
+    let fd = if let Some(fd) = state.filesystem.getfd(&path) {
+        fd
+    } else if mode == b'w' {
+        // the input of the command is written to this file
+        state.filesystem.touch(&path, FileMode::Write);
+        state.filesystem.getfd(&path).unwrap()
+    } else {
+        let output = state.sim_input(&path, 0, COMMAND_OUTPUT_LEN);
+        state.filesystem.add_file(&path, &output);
+        state.filesystem.getfd(&path).unwrap()
+    };
+
+    file_struct(state, vc(fd as u64))
+}
+
+pub fn pclose(state: &mut State, args: &[Value]) -> Value {
+    let fd = fileno(state, &args[0..1]);
+    syscall::close(state, &[fd]);
+    vc(0) // exit status of the command
+}
+
+pub fn system(state: &mut State, args: &[Value]) -> Value {
+    if args[0].as_u64() == Some(0) {
+        return vc(1); // a shell is available
+    }
+    record_command(state, &args[0]);
+    vc(0)
+}
@@ -0,0 +1,657 @@
+use crate::sims::{make_sim, Sim};
+use crate::solver::Solver;
+use crate::state::State;
+use crate::value::{vc, Value};
+
+use std::collections::BTreeMap;
+use std::mem;
+
+/// The exception handlers and registry of a Windows process
+#[derive(Debug, Clone, Default)]
+pub struct WindowsState {
+    /// Vectored exception handlers in call order
+    pub vectored_handlers: Vec<u64>,
+    /// Filter set with `SetUnhandledExceptionFilter`
+    pub unhandled_filter: u64,
+    /// Registry values by normalized `KEY\NAME`, as their type and data
+    pub registry: BTreeMap<String, (u64, Vec<Value>)>,
+    /// Paths of the open registry keys by handle
+    pub open_keys: BTreeMap<u64, String>,
+}
+
+impl WindowsState {
+    /// Translate the registry data into another solver
+    pub fn translate(&self, solver: &Solver) -> Self {
+        let registry = self
+            .registry
+            .iter()
+            .map(|(key, (kind, data))| {
+                let data = data.iter().map(|v| solver.translate_value(v)).collect();
+                (key.to_owned(), (*kind, data))
+            })
+            .collect();
+
+        WindowsState {
+            registry,
+            ..self.clone()
+        }
+    }
+}
+
+pub fn get_sims() -> Vec<Sim> {
+    vec![
+        make_sim(
+            "AddVectoredExceptionHandler",
+            add_vectored_exception_handler,
+            2,
+        ),
+        make_sim(
+            "RemoveVectoredExceptionHandler",
+            remove_vectored_exception_handler,
+            1,
+        ),
+        make_sim(
+            "SetUnhandledExceptionFilter",
+            set_unhandled_exception_filter,
+            1,
+        ),
+        make_sim("GetModuleHandleA", get_module_handle, 1),
+        make_sim("GetModuleHandleW", get_module_handle, 1),
+        make_sim("GetStdHandle", get_std_handle, 1),
+        make_sim("CreateFileA", create_file, 7),
+        make_sim("ReadFile", read_file, 5),
+        make_sim("WriteFile", write_file, 5),
+        make_sim("CloseHandle", close_handle, 1),
+        make_sim("VirtualAlloc", virtual_alloc, 4),
+        make_sim("VirtualFree", virtual_free, 3),
+        make_sim("VirtualProtect", virtual_protect, 4),
+        make_sim("GetProcessHeap", get_process_heap, 0),
+        make_sim("HeapAlloc", heap_alloc, 3),
+        make_sim("HeapFree", heap_free, 3),
+        make_sim("GetLastError", get_last_error, 0),
+        make_sim("SetLastError", set_last_error, 1),
+        make_sim("GetTickCount", get_tick_count, 0),
+        make_sim("Sleep", sleep, 1),
+        make_sim("ExitProcess", exit_process, 1),
+        make_sim("RegOpenKeyA", reg_open_key, 3),
+        make_sim("RegOpenKeyExA", reg_open_key_ex, 5),
+        make_sim("RegQueryValueExA", reg_query_value_ex, 6),
+        make_sim("RegSetValueExA", reg_set_value_ex, 6),
+        make_sim("RegCloseKey", reg_close_key, 1),
+        make_sim("lstrlenA", lstrlen, 1),
+        make_sim("lstrcmpA", lstrcmp, 2),
+        make_sim("lstrcmpiA", lstrcmpi, 2),
+        make_sim("lstrcpyA", lstrcpy, 2),
+        make_sim("lstrcatA", lstrcat, 2),
+        make_sim("_stricmp", stricmp, 2),
+        make_sim("_strcmpi", stricmp, 2),
+        make_sim("stricmp", stricmp, 2),
+    ]
+}
+
+pub fn add_vectored_exception_handler(state: &mut State, args: &[Value]) -> Value {
+    let first = state.solver.evalcon_to_u64(&args[0]).unwrap_or_default();
+    let handler = state.solver.evalcon_to_u64(&args[1]).unwrap_or_default();
+
+    let handlers = &mut state.windows.vectored_handlers;
+    if first != 0 {
+        handlers.insert(0, handler);
+    } else {
+        handlers.push(handler);
+    }
+
+    // use the handler address as the handle
+    vc(handler)
+}
+
+pub fn remove_vectored_exception_handler(state: &mut State, args: &[Value]) -> Value {
+    let handle = state.solver.evalcon_to_u64(&args[0]);
+    let handlers = &mut state.windows.vectored_handlers;
+    if let Some(index) = handlers.iter().position(|h| Some(*h) == handle) {
+        handlers.remove(index);
+        vc(1)
+    } else {
+        vc(0)
+    }
+}
+
+pub fn set_unhandled_exception_filter(state: &mut State, args: &[Value]) -> Value {
+    let filter = state.solver.evalcon_to_u64(&args[0]).unwrap_or_default();
+    vc(mem::replace(&mut state.windows.unhandled_filter, filter))
+}
+
+/// Handles of files are the fd past this, so they are never null
+pub const HANDLE_BASE: u64 = 0x100;
+const INVALID_HANDLE_VALUE: u64 = u64::MAX;
+const STD_INPUT_HANDLE: u64 = -10i64 as u64;
+const STD_ERROR_HANDLE: u64 = -12i64 as u64;
+const PROCESS_HEAP: u64 = 0x150000;
+const MAX_NAME: u64 = 260;
+// PAGE_EXECUTE, PAGE_EXECUTE_READ, PAGE_EXECUTE_READWRITE, PAGE_EXECUTE_WRITECOPY
+const PAGE_EXECUTE_ANY: u64 = 0xf0;
+
+// the predefined root keys of the registry, with their abbreviations
+const ROOT_KEYS: [(u64, &str, &str); 5] = [
+    (0x80000000, "hkey_classes_root", "hkcr"),
+    (0x80000001, "hkey_current_user", "hkcu"),
+    (0x80000002, "hkey_local_machine", "hklm"),
+    (0x80000003, "hkey_users", "hku"),
+    (0x80000005, "hkey_current_config", "hkcc"),
+];
+const REGKEY_BASE: u64 = 0x2000;
+const ERROR_FILE_NOT_FOUND: u64 = 2;
+const ERROR_INVALID_HANDLE: u64 = 6;
+const ERROR_MORE_DATA: u64 = 234;
+
+pub const REG_SZ: u64 = 1;
+pub const REG_BINARY: u64 = 3;
+pub const REG_DWORD: u64 = 4;
+
+// addresses of the TEB and PEB, just below the user address space limit
+const TEB_32: u64 = 0x7ffde000;
+const PEB_32: u64 = 0x7ffdf000;
+const TEB_64: u64 = 0x7ff_fffde000;
+const PEB_64: u64 = 0x7ff_fffdf000;
+const TEB_SIZE: u64 = 0x2000;
+// offsets of the module lists in the loader data
+const LDR_LISTS_32: [u64; 3] = [0x0c, 0x14, 0x1c];
+const LDR_LISTS_64: [u64; 3] = [0x10, 0x20, 0x30];
+
+// stdcall functions pop their args on 32 bit x86, so move the return
+// address up over the args before the sim returns
+fn stdcall(state: &mut State, args: usize) {
+    if state.memory.bits != 32 || args == 0 {
+        return;
+    }
+    let sp = state.registers.get_with_alias("SP");
+    let ret = state.memory_read_value(&sp, 4);
+    let new_sp = sp.add(&vc(4 * args as u64));
+    state.memory_write_value(&new_sp, &ret, 4);
+    state.registers.set_with_alias("SP", new_sp);
+}
+
+fn ptr_size(state: &State) -> usize {
+    state.memory.bits as usize / 8
+}
+
+fn handle_fd(state: &mut State, handle: &Value) -> Option<usize> {
+    let handle = state.solver.evalcon_to_u64(handle)?;
+    // the pseudo handles of GetStdHandle are -10, -11 and -12, sign
+    // extended from 32 bits on 32 bit targets
+    let handle = if ptr_size(state) == 4 {
+        handle as u32 as i32 as u64
+    } else {
+        handle
+    };
+    match handle {
+        STD_ERROR_HANDLE..=STD_INPUT_HANDLE => Some((STD_INPUT_HANDLE - handle) as usize),
+        _ => handle.checked_sub(HANDLE_BASE).map(|fd| fd as usize),
+    }
+}
+
+// names may be ascii or utf-16, where every other byte is zero
+fn read_name(state: &mut State, addr: u64) -> String {
+    let raw = state.memory_read_string(addr, 2 * MAX_NAME as usize);
+    let wide = raw.as_bytes().get(1) == Some(&0);
+    raw.chars()
+        .step_by(if wide { 2 } else { 1 })
+        .take_while(|c| *c != '\0')
+        .collect()
+}
+
+/// Set up the TEB and PEB of the main thread and point fs (32 bit)
+/// or gs (64 bit) at the TEB, for PE targets
+pub fn setup_environment(state: &mut State) {
+    let bits = state.memory.bits;
+    let (teb, peb, segment) = if bits == 64 {
+        (TEB_64, PEB_64, "gs")
+    } else {
+        (TEB_32, PEB_32, "fs")
+    };
+    state.memory.add_segment("teb", teb, TEB_SIZE, "rw--");
+
+    let ptr = ptr_size(state);
+    let p = ptr as u64;
+    let stack = state
+        .registers
+        .get_with_alias("SP")
+        .as_u64()
+        .unwrap_or_default();
+    let ldr = peb + 0x800;
+    let base = state.info.bin.baddr;
+
+    // ExceptionList, StackBase, StackLimit, Self, ClientId, Peb
+    let exceptions = if bits == 64 { 0 } else { 0xffffffff };
+    let pid = state.pid;
+    let teb_fields = [
+        (0, exceptions),
+        (p, stack),
+        (2 * p, stack.saturating_sub(0x100000)),
+        (6 * p, teb),
+        (8 * p, pid),
+        (9 * p, pid),
+        (12 * p, peb),
+    ];
+    for (offset, value) in teb_fields {
+        state.memory.write_value(teb + offset, &vc(value), ptr);
+    }
+
+    // BeingDebugged is 0, ImageBaseAddress, Ldr, ProcessHeap
+    let peb_fields = [(2 * p, base), (3 * p, ldr), (6 * p, PROCESS_HEAP)];
+    for (offset, value) in peb_fields {
+        state.memory.write_value(peb + offset, &vc(value), ptr);
+    }
+    state.memory.write_value(peb + 2, &vc(0), 1);
+
+    // the module lists of the loader data are empty, each entry points to itself
+    let lists = if bits == 64 {
+        LDR_LISTS_64
+    } else {
+        LDR_LISTS_32
+    };
+    for offset in lists {
+        let list = ldr + offset;
+        state.memory.write_value(list, &vc(list), ptr);
+        state.memory.write_value(list + p, &vc(list), ptr);
+    }
+
+    if state.registers.regs.contains_key(segment) {
+        state.registers.set(segment, vc(teb));
+    }
+}
+
+pub fn get_module_handle(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 1);
+    let name = state.solver.evalcon_to_u64(&args[0]).unwrap_or_default();
+    if name == 0 {
+        return vc(state.info.bin.baddr);
+    }
+
+    let name = read_name(state, name);
+    let main = state
+        .info
+        .core
+        .file
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default();
+    if name.eq_ignore_ascii_case(main) {
+        vc(state.info.bin.baddr)
+    } else {
+        vc(0)
+    }
+}
+
+pub fn get_std_handle(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 1);
+    // the pseudo handles themselves are used as the handles
+    args[0].to_owned()
+}
+
+pub fn create_file(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 7);
+    let addr = state.solver.evalcon_to_u64(&args[0]).unwrap_or_default();
+    let path = state.memory_read_cstring(addr);
+    let fd = state
+        .filesystem
+        .getfd(&path)
+        .or_else(|| state.filesystem.open(&path, super::fs::FileMode::Read));
+    match fd {
+        Some(fd) => vc(HANDLE_BASE + fd as u64),
+        None => vc(INVALID_HANDLE_VALUE),
+    }
+}
+
+pub fn read_file(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 5);
+    let fd = match handle_fd(state, &args[0]) {
+        Some(fd) => fd,
+        None => return vc(0),
+    };
+    let length = state.solver.max_value(&args[2]) & 0xffffffff;
+    let data = state.read_file(fd, length as usize);
+    let read = vc(data.len() as u64);
+    state.memory_write(&args[1], &data, &read);
+    if args[3].as_u64() != Some(0) {
+        state.memory_write_value(&args[3], &read, 4);
+    }
+    vc(1)
+}
+
+pub fn write_file(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 5);
+    let fd = match handle_fd(state, &args[0]) {
+        Some(fd) => fd,
+        None => return vc(0),
+    };
+    let data = state.memory_read(&args[1], &args[2]);
+    state.filesystem.write(fd, data);
+    if args[3].as_u64() != Some(0) {
+        state.memory_write_value(&args[3], &args[2], 4);
+    }
+    vc(1)
+}
+
+pub fn close_handle(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 1);
+    if let Some(fd) = handle_fd(state, &args[0]) {
+        state.filesystem.close(fd);
+    }
+    vc(1)
+}
+
+pub fn virtual_alloc(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 4);
+    let addr = state.memory_alloc(&args[1]);
+    mark_executable(state, &addr, &args[1], &args[3]);
+    addr
+}
+
+// code written to memory that is made executable isn't in the binary
+fn mark_executable(state: &mut State, addr: &Value, size: &Value, protect: &Value) {
+    let protect = state.solver.evalcon_to_u64(protect).unwrap_or_default();
+    if protect & PAGE_EXECUTE_ANY != 0 {
+        let addr = state.solver.evalcon_to_u64(addr).unwrap_or_default();
+        let size = state.solver.evalcon_to_u64(size).unwrap_or_default();
+        if !state.memory.check_permission(addr, size, 'x') {
+            state.memory.mark_code(addr, size);
+        }
+    }
+}
+
+pub fn virtual_free(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 3);
+    state.memory_free(&args[0]);
+    vc(1)
+}
+
+pub fn virtual_protect(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 4);
+    mark_executable(state, &args[0], &args[1], &args[2]);
+    // PAGE_EXECUTE_READWRITE
+    if args[3].as_u64() != Some(0) {
+        state.memory_write_value(&args[3], &vc(0x40), 4);
+    }
+    vc(1)
+}
+
+pub fn get_process_heap(_state: &mut State, _args: &[Value]) -> Value {
+    vc(PROCESS_HEAP)
+}
+
+pub fn heap_alloc(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 3);
+    state.memory_alloc(&args[2])
+}
+
+pub fn heap_free(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 3);
+    state.memory_free(&args[2]);
+    vc(1)
+}
+
+pub fn get_last_error(state: &mut State, _args: &[Value]) -> Value {
+    state
+        .context
+        .get("last_error")
+        .map(|e| e[0].to_owned())
+        .unwrap_or_else(|| vc(0))
+}
+
+pub fn set_last_error(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 1);
+    state
+        .context
+        .insert("last_error".to_owned(), vec![args[0].to_owned()]);
+    vc(0)
+}
+
+pub fn get_tick_count(state: &mut State, _args: &[Value]) -> Value {
+    // advance a little on each call like a real clock
+    let count = state
+        .context
+        .get("tick_count")
+        .and_then(|c| c[0].as_u64())
+        .map(|c| c + 16)
+        .unwrap_or(0x100000);
+    state
+        .context
+        .insert("tick_count".to_owned(), vec![vc(count)]);
+    vc(count)
+}
+
+pub fn sleep(state: &mut State, _args: &[Value]) -> Value {
+    stdcall(state, 1);
+    vc(0)
+}
+
+pub fn exit_process(state: &mut State, args: &[Value]) -> Value {
+    super::libc::exit(state, args)
+}
+
+pub fn lstrlen(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 1);
+    super::libc::strlen(state, args)
+}
+
+pub fn lstrcmp(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 2);
+    super::libc::strcmp(state, args)
+}
+
+pub fn lstrcmpi(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 2);
+    stricmp(state, args)
+}
+
+pub fn lstrcpy(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 2);
+    super::libc::strcpy(state, args)
+}
+
+pub fn lstrcat(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 2);
+    super::libc::strcat(state, args)
+}
+
+// the lower case of a symbolic character
+fn lower(state: &State, c: &Value) -> Value {
+    let upper = c.ugte(&vc('A' as u64)).and(&c.ulte(&vc('Z' as u64)));
+    state.cond(&upper, &c.add(&vc(0x20)), c)
+}
+
+/// The case insensitive strcmp of the msvc crt, `_stricmp`
+pub fn stricmp(state: &mut State, args: &[Value]) -> Value {
+    let len1 = state.memory_strlen(&args[0], &vc(MAX_NAME));
+    let len2 = state.memory_strlen(&args[1], &vc(MAX_NAME));
+    let max1 = state.solver.max_value(&len1);
+    let max2 = state.solver.max_value(&len2);
+    let length = max1.max(max2) + 1;
+
+    let s1 = state.memory_read(&args[0], &vc(length));
+    let s2 = state.memory_read(&args[1], &vc(length));
+
+    // from the end, equal chars defer to the rest unless both strings end
+    let mut result = vc(0);
+    for (c1, c2) in s1.iter().zip(&s2).rev() {
+        let (l1, l2) = (lower(state, c1), lower(state, c2));
+        let rest = state.cond(&l1.eq(&vc(0)), &vc(0), &result);
+        result = state.cond(&l1.eq(&l2), &rest, &l1.sub(&l2));
+    }
+    result
+}
+
+/// Normalize the path of a registry key, which is case insensitive
+/// and may start with an abbreviated root key like `HKLM`
+pub fn registry_path(key: &str) -> String {
+    let key = key.trim_matches('\\').to_ascii_lowercase();
+    let (root, rest) = key.split_once('\\').unwrap_or((&key, ""));
+    let root = ROOT_KEYS
+        .iter()
+        .find(|r| r.2 == root)
+        .map(|r| r.1)
+        .unwrap_or(root);
+    if rest.is_empty() {
+        root.to_owned()
+    } else {
+        format!("{}\\{}", root, rest)
+    }
+}
+
+fn value_key(path: &str, name: &str) -> String {
+    format!("{}\\{}", path, name.to_ascii_lowercase())
+}
+
+/// Set the value `name` of the registry `key` of the state to `data` of type `kind`
+pub fn set_registry_value(state: &mut State, key: &str, name: &str, kind: u64, data: &[Value]) {
+    let key = value_key(&registry_path(key), name);
+    state.windows.registry.insert(key, (kind, data.to_vec()));
+}
+
+fn key_path(state: &mut State, handle: &Value) -> Option<String> {
+    let handle = state.solver.evalcon_to_u64(handle)?;
+    // root keys are sign extended on 64 bit
+    if let Some(root) = ROOT_KEYS.iter().find(|r| r.0 == handle & 0xffffffff) {
+        return Some(root.1.to_owned());
+    }
+    state.windows.open_keys.get(&handle).cloned()
+}
+
+fn open_key(state: &mut State, parent: &Value, subkey: &Value, result: &Value) -> Value {
+    let parent = match key_path(state, parent) {
+        Some(parent) => parent,
+        None => return vc(ERROR_INVALID_HANDLE),
+    };
+    let addr = state.solver.evalcon_to_u64(subkey).unwrap_or_default();
+    let path = if addr == 0 {
+        parent
+    } else {
+        let subkey = read_name(state, addr);
+        registry_path(&format!("{}\\{}", parent, subkey))
+    };
+
+    // a key exists if it or one of its subkeys has a value
+    let prefix = format!("{}\\", path);
+    let registry = &state.windows.registry;
+    if !registry.keys().any(|k| k.starts_with(&prefix)) {
+        return vc(ERROR_FILE_NOT_FOUND);
+    }
+
+    let open_keys = &mut state.windows.open_keys;
+    let handle = open_keys
+        .keys()
+        .next_back()
+        .map(|h| h + 1)
+        .unwrap_or(REGKEY_BASE);
+    open_keys.insert(handle, path);
+    let ptr = ptr_size(state);
+    state.memory_write_value(result, &vc(handle), ptr);
+    vc(0)
+}
+
+pub fn reg_open_key(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 3);
+    open_key(state, &args[0], &args[1], &args[2])
+}
+
+pub fn reg_open_key_ex(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 5);
+    open_key(state, &args[0], &args[1], &args[4])
+}
+
+pub fn reg_query_value_ex(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 6);
+    let path = match key_path(state, &args[0]) {
+        Some(path) => path,
+        None => return vc(ERROR_INVALID_HANDLE),
+    };
+    // a null name is the default value of the key
+    let addr = state.solver.evalcon_to_u64(&args[1]).unwrap_or_default();
+    let name = if addr == 0 {
+        String::new()
+    } else {
+        read_name(state, addr)
+    };
+    let (kind, data) = match state.windows.registry.get(&value_key(&path, &name)) {
+        Some(value) => value.to_owned(),
+        None => return vc(ERROR_FILE_NOT_FOUND),
+    };
+
+    if args[3].as_u64() != Some(0) {
+        state.memory_write_value(&args[3], &vc(kind), 4);
+    }
+    if args[5].as_u64() == Some(0) {
+        return vc(0);
+    }
+
+    // the size of the buffer is updated to the size of the data
+    let size = state.memory_read_value(&args[5], 4);
+    let size = state.solver.evalcon_to_u64(&size).unwrap_or_default();
+    let length = vc(data.len() as u64);
+    state.memory_write_value(&args[5], &length, 4);
+    if args[4].as_u64() == Some(0) {
+        vc(0)
+    } else if size < data.len() as u64 {
+        vc(ERROR_MORE_DATA)
+    } else {
+        state.memory_write(&args[4], &data, &length);
+        vc(0)
+    }
+}
+
+pub fn reg_set_value_ex(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 6);
+    let path = match key_path(state, &args[0]) {
+        Some(path) => path,
+        None => return vc(ERROR_INVALID_HANDLE),
+    };
+    let addr = state.solver.evalcon_to_u64(&args[1]).unwrap_or_default();
+    let name = if addr == 0 {
+        String::new()
+    } else {
+        read_name(state, addr)
+    };
+    let kind = state.solver.evalcon_to_u64(&args[3]).unwrap_or(REG_BINARY);
+    let data = state.memory_read(&args[4], &args[5]);
+    set_registry_value(state, &path, &name, kind, &data);
+    vc(0)
+}
+
+pub fn reg_close_key(state: &mut State, args: &[Value]) -> Value {
+    stdcall(state, 1);
+    if let Some(handle) = state.solver.evalcon_to_u64(&args[0]) {
+        state.windows.open_keys.remove(&handle);
+    }
+    vc(0)
+}
+
+/// How shellcode hashes the names of the functions it resolves, see
+/// `Radius::resolve_api_hashes`
+#[derive(Debug, Clone, Copy)]
+pub enum ApiHash {
+    /// Rotate right by 13 and add each byte, as in many shellcodes
+    Ror13,
+    /// Multiply by 33 and add each byte, starting at 5381
+    Djb2,
+    /// 32 bit FNV-1a
+    Fnv1a,
+    /// Hash names with the function
+    Custom(fn(&str) -> u32),
+}
+
+impl ApiHash {
+    pub fn hash(&self, name: &str) -> u32 {
+        match self {
+            ApiHash::Ror13 => name
+                .bytes()
+                .fold(0u32, |h, c| h.rotate_right(13).wrapping_add(c as u32)),
+            ApiHash::Djb2 => name
+                .bytes()
+                .fold(5381u32, |h, c| h.wrapping_mul(33).wrapping_add(c as u32)),
+            ApiHash::Fnv1a => name.bytes().fold(0x811c9dc5u32, |h, c| {
+                (h ^ c as u32).wrapping_mul(0x01000193)
+            }),
+            ApiHash::Custom(hash) => hash(name),
+        }
+    }
+}
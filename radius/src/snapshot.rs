@@ -0,0 +1,710 @@
+use crate::exception::Dispatch;
+use crate::memory::MemorySegment;
+use crate::sims::fs::{FileMode, SimFile, SymbolicStream};
+use crate::sims::net::SimSocket;
+use crate::sims::windows::WindowsState;
+use crate::solver::{BitVec, Solver};
+use crate::state::{PathBranch, State};
+use crate::value::Value;
+use boolector::BV;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+/// A value in a snapshot, symbolic values are kept as SMT-LIB
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SnapshotValue {
+    Concrete(u64, u64),
+    Symbolic(String, u64),
+}
+
+impl SnapshotValue {
+    pub fn new(value: &Value) -> Self {
+        match value {
+            Value::Concrete(v, t) => SnapshotValue::Concrete(*v, *t),
+            Value::Symbolic(bv, t) => SnapshotValue::Symbolic(format!("{:?}", bv), *t),
+        }
+    }
+}
+
+/// A memory segment in a snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotSegment {
+    pub name: String,
+    pub addr: u64,
+    pub size: u64,
+    pub perms: String,
+    pub init: bool,
+}
+
+/// A simulated file in a snapshot, the mode is "r", "w", or "a"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    pub path: String,
+    pub fd: usize,
+    pub position: usize,
+    pub mode: String,
+    pub content: Vec<SnapshotValue>,
+    /// The file was opened from disk, its metadata is read again on restore
+    pub on_disk: bool,
+    pub pipe: Option<usize>,
+}
+
+/// A simulated socket in a snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotSocket {
+    pub fd: usize,
+    pub domain: u64,
+    pub kind: u64,
+    pub address: Option<String>,
+    pub sent: Vec<SnapshotValue>,
+}
+
+/// The simulated filesystem in a snapshot, without the ioctl and fcntl
+/// handlers, which are code
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotFilesystem {
+    pub files: Vec<SnapshotFile>,
+    /// Max length and printable flag of the symbolic streams by fd
+    pub streams: BTreeMap<usize, (usize, bool)>,
+    pub sockets: Vec<SnapshotSocket>,
+}
+
+/// The exception handlers and registry of a Windows process in a snapshot
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotWindows {
+    pub vectored_handlers: Vec<u64>,
+    pub unhandled_filter: u64,
+    pub registry: BTreeMap<String, (u64, Vec<SnapshotValue>)>,
+    pub open_keys: BTreeMap<u64, String>,
+    pub exception: Option<Dispatch>,
+}
+
+/// The registers, memory, constraints, and context of a state that can be
+/// saved to disk and restored into a fresh state of the same binary.
+/// Symbolic values and constraints are stored as SMT-LIB and parsed back
+/// into the solver of the restored state, variables are told apart by
+/// their btor node id so distinct ones with the same name stay distinct.
+/// The simulated filesystem is kept, its ioctl and fcntl handlers, the
+/// solver push levels, and hooks are not
+///
+/// ## Example
+/// ```
+/// use radius2::radius::Radius;
+/// let mut radius = Radius::new("../tests/r100");
+/// let mut state = radius.call_state(0x004006fd);
+/// let flag = state.symbolic_value("flag", 64);
+/// state.registers.set("rdi", radius2::vc(0x100000));
+/// state.memory_write_value(&radius2::vc(0x100000), &flag, 8);
+/// let data = state.serialize();
+///
+/// let mut restored = radius.load_state(&data).unwrap();
+/// let value = restored.memory_read_value(&radius2::vc(0x100000), 8);
+/// assert!(value.is_symbolic());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub registers: Vec<SnapshotValue>,
+    pub memory: BTreeMap<u64, Vec<SnapshotValue>>,
+    pub segments: Vec<SnapshotSegment>,
+    /// Address and size of each heap chunk
+    pub heap: Vec<(u64, u64)>,
+    pub constraints: Vec<String>,
+    pub context: BTreeMap<String, Vec<SnapshotValue>>,
+    pub symbols: BTreeMap<String, SnapshotValue>,
+    pub inputs: BTreeMap<String, Vec<SnapshotValue>>,
+    pub taints: BTreeMap<String, u64>,
+    pub visits: BTreeMap<u64, usize>,
+    pub path: Vec<(u64, SnapshotValue, u64, bool)>,
+    pub backtrace: Vec<(u64, u64)>,
+    #[serde(default)]
+    pub filesystem: SnapshotFilesystem,
+    #[serde(default)]
+    pub windows: SnapshotWindows,
+}
+
+impl Snapshot {
+    pub fn new(state: &State) -> Self {
+        // name the variables in the dumps by node id
+        state.solver.with_node_ids(|| Snapshot::dump(state))
+    }
+
+    fn dump(state: &State) -> Self {
+        let values = |values: &[Value]| values.iter().map(SnapshotValue::new).collect();
+        let fs = &state.filesystem;
+        Snapshot {
+            registers: values(&state.registers.values),
+            memory: state
+                .memory
+                .mem
+                .iter()
+                .map(|(addr, chunk)| (*addr, values(chunk)))
+                .collect(),
+            segments: state
+                .memory
+                .segs
+                .iter()
+                .map(|seg| SnapshotSegment {
+                    name: seg.name.to_owned(),
+                    addr: seg.addr,
+                    size: seg.size,
+                    perms: format!(
+                        "{}{}{}",
+                        if seg.read { "r" } else { "-" },
+                        if seg.write { "w" } else { "-" },
+                        if seg.exec { "x" } else { "-" }
+                    ),
+                    init: seg.init,
+                })
+                .collect(),
+            heap: state.memory.heap_chunks(),
+            constraints: state
+                .solver
+                .assertions
+                .iter()
+                .map(|bv| format!("{:?}", bv))
+                .collect(),
+            context: state
+                .context
+                .iter()
+                .map(|(key, vals)| (key.to_owned(), values(vals)))
+                .collect(),
+            symbols: state
+                .symbols
+                .iter()
+                .map(|(name, val)| (name.to_owned(), SnapshotValue::new(val)))
+                .collect(),
+            inputs: state
+                .inputs
+                .iter()
+                .map(|(name, vals)| (name.to_owned(), values(vals)))
+                .collect(),
+            taints: state
+                .taints
+                .iter()
+                .map(|(k, v)| (k.to_owned(), *v))
+                .collect(),
+            visits: state.visits.iter().map(|(k, v)| (*k, *v)).collect(),
+            path: state
+                .path
+                .iter()
+                .map(|b| (b.addr, SnapshotValue::new(&b.condition), b.target, b.taken))
+                .collect(),
+            backtrace: state.backtrace.clone(),
+            filesystem: SnapshotFilesystem {
+                files: fs
+                    .files
+                    .iter()
+                    .map(|file| SnapshotFile {
+                        path: file.path.to_owned(),
+                        fd: file.fd,
+                        position: file.position,
+                        mode: match file.mode {
+                            FileMode::Read => "r",
+                            FileMode::Write => "w",
+                            FileMode::Append => "a",
+                        }
+                        .to_owned(),
+                        content: values(&file.content),
+                        on_disk: file.metadata.is_some(),
+                        pipe: file.pipe,
+                    })
+                    .collect(),
+                streams: fs
+                    .streams
+                    .iter()
+                    .map(|(fd, stream)| (*fd, (stream.max_length, stream.printable)))
+                    .collect(),
+                sockets: fs
+                    .sockets
+                    .values()
+                    .map(|socket| SnapshotSocket {
+                        fd: socket.fd,
+                        domain: socket.domain,
+                        kind: socket.kind,
+                        address: socket.address.to_owned(),
+                        sent: values(&socket.sent),
+                    })
+                    .collect(),
+            },
+            windows: SnapshotWindows {
+                vectored_handlers: state.windows.vectored_handlers.clone(),
+                unhandled_filter: state.windows.unhandled_filter,
+                registry: state
+                    .windows
+                    .registry
+                    .iter()
+                    .map(|(key, (kind, data))| (key.to_owned(), (*kind, values(data))))
+                    .collect(),
+                open_keys: state.windows.open_keys.clone(),
+                exception: state.exception,
+            },
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn from_json(data: &str) -> Result<Self, String> {
+        serde_json::from_str(data).map_err(|e| format!("bad snapshot: {}", e))
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Snapshot::from_json(&data)
+    }
+
+    /// Restore the snapshot into `state`, which should be a fresh state
+    /// of the same binary with no symbols of its own
+    pub fn restore(&self, state: &mut State) -> Result<(), String> {
+        if self.registers.len() != state.registers.values.len() {
+            return Err(format!(
+                "snapshot has {} registers, state has {}",
+                self.registers.len(),
+                state.registers.values.len()
+            ));
+        }
+
+        let mut parser = SmtParser::new(&state.solver);
+        let mut values = |vals: &[SnapshotValue]| -> Result<Vec<Value>, String> {
+            vals.iter().map(|v| parser.value(v)).collect()
+        };
+
+        state.registers.values = values(&self.registers)?;
+        let mut memory = BTreeMap::new();
+        for (addr, chunk) in &self.memory {
+            memory.insert(*addr, values(chunk)?);
+        }
+        state.memory.mem = memory;
+
+        let mut context = HashMap::new();
+        for (key, vals) in &self.context {
+            context.insert(key.to_owned(), values(vals)?);
+        }
+        state.context = context;
+
+        let mut inputs = BTreeMap::new();
+        for (name, vals) in &self.inputs {
+            inputs.insert(name.to_owned(), values(vals)?);
+        }
+        state.inputs = inputs;
+
+        let mut files = vec![];
+        for file in &self.filesystem.files {
+            files.push(SimFile {
+                path: file.path.to_owned(),
+                fd: file.fd,
+                position: file.position,
+                mode: match file.mode.as_str() {
+                    "w" => FileMode::Write,
+                    "a" => FileMode::Append,
+                    _ => FileMode::Read,
+                },
+                content: values(&file.content)?,
+                metadata: if file.on_disk {
+                    fs::metadata(&file.path).ok()
+                } else {
+                    None
+                },
+                pipe: file.pipe,
+            });
+        }
+        // snapshots without files keep the stdio of the fresh state
+        if !files.is_empty() {
+            state.filesystem.files = files;
+        }
+        state.filesystem.streams = self
+            .filesystem
+            .streams
+            .iter()
+            .map(|(fd, (max_length, printable))| {
+                let stream = SymbolicStream {
+                    max_length: *max_length,
+                    printable: *printable,
+                };
+                (*fd, stream)
+            })
+            .collect();
+        let mut sockets = HashMap::new();
+        for socket in &self.filesystem.sockets {
+            let sim = SimSocket {
+                fd: socket.fd,
+                domain: socket.domain,
+                kind: socket.kind,
+                address: socket.address.to_owned(),
+                sent: values(&socket.sent)?,
+            };
+            sockets.insert(socket.fd, sim);
+        }
+        state.filesystem.sockets = sockets;
+
+        let mut registry = BTreeMap::new();
+        for (key, (kind, data)) in &self.windows.registry {
+            registry.insert(key.to_owned(), (*kind, values(data)?));
+        }
+        state.windows = WindowsState {
+            vectored_handlers: self.windows.vectored_handlers.clone(),
+            unhandled_filter: self.windows.unhandled_filter,
+            registry,
+            open_keys: self.windows.open_keys.clone(),
+        };
+        state.exception = self.windows.exception;
+
+        state.symbols.clear();
+        state.symbol_counts.clear();
+        for (name, val) in &self.symbols {
+            let value = values(&[val.to_owned()])?.remove(0);
+            state.register_symbol(name, &value);
+        }
+
+        let mut path = vec![];
+        for (addr, condition, target, taken) in &self.path {
+            path.push(PathBranch {
+                addr: *addr,
+                condition: values(&[condition.to_owned()])?.remove(0),
+                target: *target,
+                taken: *taken,
+                // the constraints are restored without their order
+                other: None,
+                step: None,
+                constraints: 0,
+            });
+        }
+        state.path = path;
+
+        state.solver.reset();
+        for constraint in &self.constraints {
+            let bv = parser.parse(constraint)?;
+            state.solver.assert_bv(&bv);
+        }
+
+        state.memory.segs = self
+            .segments
+            .iter()
+            .map(|seg| MemorySegment {
+                name: seg.name.to_owned(),
+                addr: seg.addr,
+                size: seg.size,
+                read: seg.perms.contains('r'),
+                write: seg.perms.contains('w'),
+                exec: seg.perms.contains('x'),
+                init: seg.init,
+            })
+            .collect();
+        state.memory.index_segments();
+        state.memory.set_heap_chunks(&self.heap);
+        state.taints = self
+            .taints
+            .iter()
+            .map(|(k, v)| (k.to_owned(), *v))
+            .collect();
+        state.visits = self.visits.iter().map(|(k, v)| (*k, *v)).collect();
+        state.backtrace = self.backtrace.clone();
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+/// Parser for the QF_BV SMT-LIB that boolector dumps terms as,
+/// booleans are parsed as bitvectors of width 1
+pub struct SmtParser {
+    solver: Solver,
+    /// declared variables by their dumped name, which ends in the btor node
+    /// id in snapshots so distinct variables with the same name are kept apart
+    vars: HashMap<String, BitVec>,
+}
+
+impl SmtParser {
+    pub fn new(solver: &Solver) -> Self {
+        SmtParser {
+            solver: solver.clone(),
+            vars: HashMap::new(),
+        }
+    }
+
+    pub fn value(&mut self, value: &SnapshotValue) -> Result<Value, String> {
+        match value {
+            SnapshotValue::Concrete(v, t) => Ok(Value::Concrete(*v, *t)),
+            SnapshotValue::Symbolic(smt, t) => Ok(Value::Symbolic(self.parse(smt)?, *t)),
+        }
+    }
+
+    /// Parse the dump of a term, which is the last term, definition,
+    /// or assertion in the dump, or the last variable if there are none
+    pub fn parse(&mut self, text: &str) -> Result<BitVec, String> {
+        let sexps = parse_sexps(&tokenize(text))?;
+        let mut defs = HashMap::new();
+        let mut result = None;
+        let mut last_var = None;
+
+        for sexp in &sexps {
+            let list = match sexp {
+                Sexp::List(list) => list,
+                _ => {
+                    result = Some(self.term(sexp, &defs)?);
+                    continue;
+                }
+            };
+            match list.first() {
+                Some(Sexp::Atom(cmd)) if cmd == "declare-fun" || cmd == "declare-const" => {
+                    let name = atom(list.get(1))?;
+                    let width = sort_width(list.last())?;
+                    if !self.vars.contains_key(&name) {
+                        let bv = self.solver.bv(var_name(&name), width);
+                        self.vars.insert(name.to_owned(), bv);
+                    }
+                    last_var = Some(name);
+                }
+                Some(Sexp::Atom(cmd)) if cmd == "define-fun" => {
+                    let name = atom(list.get(1))?;
+                    let body = list.get(4).ok_or("bad define-fun")?;
+                    let bv = self.term(body, &defs)?;
+                    defs.insert(name, bv.to_owned());
+                    result = Some(bv);
+                }
+                Some(Sexp::Atom(cmd)) if cmd == "assert" => {
+                    let body = list.get(1).ok_or("bad assert")?;
+                    result = Some(self.term(body, &defs)?);
+                }
+                Some(Sexp::Atom(cmd))
+                    if cmd.starts_with("set-") || cmd == "check-sat" || cmd == "exit" => {}
+                _ => result = Some(self.term(sexp, &defs)?),
+            }
+        }
+
+        result
+            .or_else(|| last_var.and_then(|name| self.vars.get(&name).cloned()))
+            .ok_or_else(|| format!("no term in {}", text))
+    }
+
+    fn term(&mut self, sexp: &Sexp, defs: &HashMap<String, BitVec>) -> Result<BitVec, String> {
+        let list = match sexp {
+            Sexp::Atom(name) => return self.atom(name, defs),
+            Sexp::List(list) => list,
+        };
+        let (head, rest) = list.split_first().ok_or("empty term")?;
+
+        match head {
+            Sexp::Atom(op) if op == "let" => {
+                let mut inner = defs.clone();
+                if let Some(Sexp::List(bindings)) = rest.first() {
+                    for binding in bindings {
+                        if let Sexp::List(pair) = binding {
+                            let name = atom(pair.first())?;
+                            let bv = self.term(pair.get(1).ok_or("bad let")?, defs)?;
+                            inner.insert(name, bv);
+                        }
+                    }
+                }
+                self.term(rest.get(1).ok_or("bad let")?, &inner)
+            }
+            Sexp::Atom(op) if op == "_" => {
+                // (_ bvN width) constants
+                let value = atom(rest.first())?;
+                let width = atom(rest.get(1))?.parse().map_err(|_| "bad width")?;
+                let digits = value.trim_start_matches("bv");
+                Ok(BV::from_dec_str(self.solver.btor.clone(), digits, width))
+            }
+            Sexp::Atom(op) => {
+                let mut args = vec![];
+                for arg in rest {
+                    args.push(self.term(arg, defs)?);
+                }
+                self.apply(op, &args)
+            }
+            Sexp::List(indexed) => {
+                // indexed operators like ((_ extract 7 0) x)
+                let op = atom(indexed.get(1))?;
+                let mut indices = vec![];
+                for index in &indexed[2..] {
+                    indices.push(atom(Some(index))?.parse().map_err(|_| "bad index")?);
+                }
+                let arg = self.term(rest.first().ok_or("missing operand")?, defs)?;
+                let index = |i: usize| indices.get(i).copied().ok_or("missing index");
+                match op.as_str() {
+                    "extract" => Ok(arg.slice(index(0)?, index(1)?)),
+                    "zero_extend" => Ok(arg.uext(index(0)?)),
+                    "sign_extend" => Ok(arg.sext(index(0)?)),
+                    "repeat" => Ok(arg.repeat(index(0)?)),
+                    "rotate_left" => {
+                        Ok(arg.rol(&self.solver.bvv(index(0)? as u64, arg.get_width())))
+                    }
+                    "rotate_right" => {
+                        Ok(arg.ror(&self.solver.bvv(index(0)? as u64, arg.get_width())))
+                    }
+                    _ => Err(format!("unsupported operator {}", op)),
+                }
+            }
+        }
+    }
+
+    fn atom(&self, name: &str, defs: &HashMap<String, BitVec>) -> Result<BitVec, String> {
+        let btor = self.solver.btor.clone();
+        if let Some(bits) = name.strip_prefix("#b") {
+            Ok(BV::from_binary_str(btor, bits))
+        } else if let Some(hex) = name.strip_prefix("#x") {
+            let mut bits = String::new();
+            for c in hex.chars() {
+                let digit = c.to_digit(16).ok_or("bad hex constant")?;
+                bits += &format!("{:04b}", digit);
+            }
+            Ok(BV::from_binary_str(btor, &bits))
+        } else if name == "true" {
+            Ok(self.solver.bvv(1, 1))
+        } else if name == "false" {
+            Ok(self.solver.bvv(0, 1))
+        } else {
+            defs.get(name)
+                .or_else(|| self.vars.get(name))
+                .cloned()
+                .ok_or_else(|| format!("unknown symbol {}", name))
+        }
+    }
+
+    fn apply(&self, op: &str, args: &[BitVec]) -> Result<BitVec, String> {
+        let arg = |i: usize| args.get(i).ok_or(format!("missing operand of {}", op));
+        let result = match op {
+            "not" | "bvnot" => arg(0)?.not(),
+            "bvneg" => arg(0)?.neg(),
+            "bvredor" => arg(0)?.redor(),
+            "bvredand" => arg(0)?.redand(),
+            "ite" => arg(0)?.cond_bv(arg(1)?, arg(2)?),
+            "=" | "bvcomp" => arg(0)?._eq(arg(1)?),
+            "distinct" => arg(0)?._ne(arg(1)?),
+            "=>" => arg(0)?.implies(arg(1)?),
+            _ => {
+                let binary: fn(&BitVec, &BitVec) -> BitVec = match op {
+                    "and" | "bvand" => BV::and,
+                    "or" | "bvor" => BV::or,
+                    "xor" | "bvxor" => BV::xor,
+                    "bvnand" => BV::nand,
+                    "bvnor" => BV::nor,
+                    "bvxnor" => BV::xnor,
+                    "bvadd" => BV::add,
+                    "bvsub" => BV::sub,
+                    "bvmul" => BV::mul,
+                    "bvudiv" => BV::udiv,
+                    "bvurem" => BV::urem,
+                    "bvsdiv" => BV::sdiv,
+                    "bvsrem" => BV::srem,
+                    "bvsmod" => BV::smod,
+                    "bvshl" => BV::sll,
+                    "bvlshr" => BV::srl,
+                    "bvashr" => BV::sra,
+                    "bvult" => BV::ult,
+                    "bvule" => BV::ulte,
+                    "bvugt" => BV::ugt,
+                    "bvuge" => BV::ugte,
+                    "bvslt" => BV::slt,
+                    "bvsle" => BV::slte,
+                    "bvsgt" => BV::sgt,
+                    "bvsge" => BV::sgte,
+                    "concat" => BV::concat,
+                    _ => return Err(format!("unsupported operator {}", op)),
+                };
+                // fold n-ary applications from the left
+                let mut result = binary(arg(0)?, arg(1)?);
+                for other in &args[2..] {
+                    result = binary(&result, other);
+                }
+                result
+            }
+        };
+        Ok(result)
+    }
+}
+
+// the name of a variable dumped as `name@id` by `Solver::with_node_ids`
+fn var_name(dumped: &str) -> &str {
+    match dumped.rsplit_once('@') {
+        Some((name, id)) if id.parse::<i32>().is_ok() => name,
+        _ => dumped,
+    }
+}
+
+fn atom(sexp: Option<&Sexp>) -> Result<String, String> {
+    match sexp {
+        Some(Sexp::Atom(a)) => Ok(a.to_owned()),
+        _ => Err("expected an atom".to_owned()),
+    }
+}
+
+// width of a sort, Bool is a single bit
+pub(crate) fn sort_width(sexp: Option<&Sexp>) -> Result<u32, String> {
+    match sexp {
+        Some(Sexp::Atom(sort)) if sort == "Bool" => Ok(1),
+        Some(Sexp::List(sort)) => atom(sort.get(2))?
+            .parse()
+            .map_err(|_| "bad sort".to_owned()),
+        _ => Err("unsupported sort".to_owned()),
+    }
+}
+
+pub(crate) fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | ')' => tokens.push(c.to_string()),
+            ';' => while chars.next().map(|c| c != '\n').unwrap_or(false) {},
+            '|' => {
+                // quoted symbols drop their bars
+                let mut token = String::new();
+                for c in chars.by_ref() {
+                    if c == '|' {
+                        break;
+                    }
+                    token.push(c);
+                }
+                tokens.push(token);
+            }
+            c if c.is_whitespace() => {}
+            _ => {
+                let mut token = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+    }
+    tokens
+}
+
+pub(crate) fn parse_sexps(tokens: &[String]) -> Result<Vec<Sexp>, String> {
+    let mut stack: Vec<Vec<Sexp>> = vec![vec![]];
+    for token in tokens {
+        match token.as_str() {
+            "(" => stack.push(vec![]),
+            ")" => {
+                let list = stack.pop().ok_or("unbalanced parentheses")?;
+                stack
+                    .last_mut()
+                    .ok_or("unbalanced parentheses")?
+                    .push(Sexp::List(list));
+            }
+            _ => stack
+                .last_mut()
+                .ok_or("unbalanced parentheses")?
+                .push(Sexp::Atom(token.to_owned())),
+        }
+    }
+    if stack.len() != 1 {
+        return Err("unbalanced parentheses".to_owned());
+    }
+    Ok(stack.pop().unwrap_or_default())
+}
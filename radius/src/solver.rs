@@ -1,12 +1,520 @@
+use crate::interval;
+#[cfg(feature = "smtlib")]
+use crate::snapshot::{parse_sexps, sort_width, tokenize, Sexp};
 use crate::value::Value;
 use boolector::option::{BtorOption, ModelGen, NumberFormat};
-use boolector::{Btor, SolverResult, BV};
-use std::cmp::Ordering;
+use boolector::{Array, Btor, SolverResult, BV};
+use std::cell::{Cell, RefCell};
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "smtlib")]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(feature = "smtlib")]
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
 
 const EVAL_MAX: usize = 256;
 
 pub type BitVec = BV<Arc<Btor>>;
+pub type BitArray = Array<Arc<Btor>>;
+
+/// The number of sat queries made and the time spent on them
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SolverStats {
+    pub queries: u64,
+    pub time: Duration,
+    /// Queries decided by the intervals of values, without the solver
+    pub skipped: u64,
+}
+
+/// A single sat query made by the solver
+#[derive(Debug, Clone)]
+pub struct SolverQuery {
+    /// Address of the instruction being executed when the query was made
+    pub addr: u64,
+    /// Number of assertions in the query
+    pub assertions: usize,
+    /// Number of conditions assumed for the query only
+    pub assumptions: usize,
+    pub sat: bool,
+    pub time: Duration,
+    /// SMT-LIB of the query with its assumptions asserted, only kept for
+    /// the slowest queries
+    pub smt: Option<String>,
+}
+
+/// Log of the sat queries, counting them and their time per address and
+/// keeping the SMT-LIB of the slowest ones, assumptions included
+///
+/// ## Example
+/// ```
+/// use radius2::radius::{Radius, RadiusOption};
+/// let mut radius = Radius::new_with_options(Some("../tests/r100"), &[RadiusOption::QueryLog(4)]);
+/// let state = radius.call_state(0x004006fd);
+/// radius.run_until(state, 0x00400844, &[0x00400855]);
+/// radius.print_query_stats(10);
+/// let files = radius.dump_queries("/tmp/r100-queries").unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct QueryLog {
+    /// The slowest queries, slowest first, at most `keep` of them
+    pub queries: Vec<SolverQuery>,
+    /// Number of slowest queries to keep
+    pub keep: usize,
+    /// Address of the instruction currently being executed
+    pub addr: u64,
+    /// Total time and count of the queries made at each address
+    pub addresses: HashMap<u64, (Duration, usize)>,
+    /// Number of queries made, how many were sat, and their total time
+    pub count: usize,
+    pub sat: usize,
+    pub time: Duration,
+}
+
+impl QueryLog {
+    pub fn new(keep: usize) -> Self {
+        QueryLog {
+            keep,
+            ..QueryLog::default()
+        }
+    }
+
+    /// Check if a query taking `time` is one of the slowest so far
+    pub fn is_slow(&self, time: Duration) -> bool {
+        self.keep > 0
+            && (self.queries.len() < self.keep
+                || self.queries.last().map(|q| time > q.time) == Some(true))
+    }
+
+    /// Add a query to the log, it is only kept if it has its SMT-LIB
+    /// and is one of the slowest
+    pub fn add(&mut self, query: SolverQuery) {
+        self.count += 1;
+        self.sat += query.sat as usize;
+        self.time += query.time;
+        let total = self.addresses.entry(query.addr).or_default();
+        total.0 += query.time;
+        total.1 += 1;
+
+        if query.smt.is_some() && self.is_slow(query.time) {
+            let index = self.queries.partition_point(|q| q.time >= query.time);
+            self.queries.insert(index, query);
+            self.queries.truncate(self.keep);
+        }
+    }
+
+    /// Get the slowest queries, slowest first
+    pub fn slowest(&self, n: usize) -> Vec<&SolverQuery> {
+        self.queries.iter().take(n).collect()
+    }
+
+    /// Total time and count of the queries made at each address, slowest first
+    pub fn by_address(&self) -> Vec<(u64, Duration, usize)> {
+        let mut totals: Vec<(u64, Duration, usize)> = self
+            .addresses
+            .iter()
+            .map(|(addr, (time, count))| (*addr, *time, *count))
+            .collect();
+        totals.sort_by_key(|t| (Reverse(t.1), t.0));
+        totals
+    }
+
+    /// Write the SMT-LIB of the slowest queries to `dir` and return the paths
+    pub fn dump(&self, dir: &str) -> std::io::Result<Vec<String>> {
+        fs::create_dir_all(dir)?;
+        let mut paths = vec![];
+        for (i, query) in self.queries.iter().enumerate() {
+            if let Some(smt) = &query.smt {
+                let name = format!("query_{}_{:x}.smt2", i, query.addr);
+                let path = Path::new(dir).join(name);
+                let header = format!(
+                    "; address 0x{:x}, {} assertions, {} assumptions, {}, {} usec\n",
+                    query.addr,
+                    query.assertions,
+                    query.assumptions,
+                    if query.sat { "sat" } else { "unsat" },
+                    query.time.as_micros()
+                );
+                fs::write(&path, header + smt)?;
+                paths.push(path.to_string_lossy().to_string());
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Print the number of queries, their total time and the slowest addresses
+    pub fn print_stats(&self, n: usize) {
+        println!(
+            "queries:\t{} ({} sat, {} unsat)\nsolver time:\t{}",
+            self.count,
+            self.sat,
+            self.count - self.sat,
+            self.time.as_micros()
+        );
+        println!("{:<12} {:>8} {:>10}", "address", "queries", "time");
+        for (addr, time, count) in self.by_address().iter().take(n) {
+            println!("0x{:010x} {:>8} {:>10}", addr, count, time.as_micros());
+        }
+    }
+}
+
+/// Makes the queries of a `Solver`: decides whether the assertions of its
+/// btor context are satisfiable under some assumptions and gives the
+/// values of terms in the model found
+pub trait SolverBackend: Debug {
+    fn name(&self) -> &str;
+    /// Check the assertions of the context of `solver` along with the
+    /// `assumptions`, which only hold for this query. The values of the
+    /// model are only needed after if `model` is set
+    fn check(&self, solver: &Solver, assumptions: &[BitVec], model: bool) -> SolverResult;
+    /// Get the value of `bv` in the model of the last sat check as a
+    /// binary string
+    fn value(&self, solver: &Solver, bv: &BitVec) -> Option<String>;
+    /// Check if an assumption of the last unsat check is part of the
+    /// reason it is unsat, backends that can't tell say they all are
+    fn failed(&self, assumption: &BitVec) -> bool;
+}
+
+/// The boolector context itself, the default backend
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BoolectorBackend;
+
+impl SolverBackend for BoolectorBackend {
+    fn name(&self) -> &str {
+        "boolector"
+    }
+
+    fn check(&self, solver: &Solver, assumptions: &[BitVec], _model: bool) -> SolverResult {
+        for cond in assumptions {
+            cond.assume();
+        }
+        solver.btor.sat()
+    }
+
+    fn value(&self, _solver: &Solver, bv: &BitVec) -> Option<String> {
+        let solution = bv.get_a_solution().disambiguate();
+        Some(solution.as_01x_str().to_owned())
+    }
+
+    fn failed(&self, assumption: &BitVec) -> bool {
+        assumption.is_failed_assumption()
+    }
+}
+
+// a running solver process that queries are written to
+#[cfg(feature = "smtlib")]
+#[derive(Debug)]
+struct SmtProcess {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+#[cfg(feature = "smtlib")]
+impl SmtProcess {
+    fn spawn(command: &str, args: &[String]) -> Option<Self> {
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+        let mut stdin = child.stdin.take()?;
+        let stdout = BufReader::new(child.stdout.take()?);
+        stdin
+            .write_all(b"(set-option :produce-models true)\n(set-logic QF_AUFBV)\n")
+            .ok()?;
+        Some(SmtProcess {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    // read a response, which can span lines until its parentheses close
+    fn response(&mut self) -> Option<String> {
+        let mut response = String::new();
+        let mut depth = 0i64;
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            let mut quoted = false;
+            for c in line.chars() {
+                match c {
+                    '|' => quoted = !quoted,
+                    '(' if !quoted => depth += 1,
+                    ')' if !quoted => depth -= 1,
+                    _ => {}
+                }
+            }
+            response += &line;
+            if depth <= 0 && !response.trim().is_empty() {
+                return Some(response);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "smtlib")]
+impl Drop for SmtProcess {
+    fn drop(&mut self) {
+        let _ = self.stdin.write_all(b"(exit)\n");
+        let _ = self.child.wait();
+    }
+}
+
+/// An external solver reading SMT-LIB from stdin, eg. bitwuzla or cvc5.
+/// One process is started and every query is made in a scope of its
+/// own, the values of the variables are read back to build models. If
+/// the solver can't be run or gives up boolector is used from then on
+#[cfg(feature = "smtlib")]
+#[derive(Debug)]
+pub struct SmtLibBackend {
+    pub command: String,
+    pub args: Vec<String>,
+    process: RefCell<Option<SmtProcess>>,
+    /// binary values of the variables in the last model, by dumped name
+    model: RefCell<HashMap<String, String>>,
+    /// the solver failed and boolector makes the queries
+    fallback: Cell<bool>,
+}
+
+#[cfg(feature = "smtlib")]
+impl SmtLibBackend {
+    pub fn new(command: &str, args: &[&str]) -> Self {
+        SmtLibBackend {
+            command: command.to_owned(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            process: RefCell::new(None),
+            model: RefCell::new(HashMap::new()),
+            fallback: Cell::new(false),
+        }
+    }
+
+    fn query(&self, solver: &Solver, assumptions: &[BitVec], model: bool) -> Option<SolverResult> {
+        // the assumptions are asserted in a level of their own to dump them
+        solver.btor.push(1);
+        for cond in assumptions {
+            cond.assert();
+        }
+        let smt = solver.with_node_ids(|| solver.btor.print_constraints());
+        solver.btor.pop(1);
+
+        let mut process = self.process.borrow_mut();
+        if process.is_none() {
+            *process = SmtProcess::spawn(&self.command, &self.args);
+        }
+        let process = process.as_mut()?;
+
+        let mut script = String::from("(push 1)\n");
+        let mut widths = HashMap::new();
+        for line in smt.lines() {
+            let command = line.trim_start();
+            let skip = ["(set-", "(check-sat", "(exit", "(get-"];
+            if skip.iter().any(|s| command.starts_with(s)) {
+                continue;
+            }
+            if command.starts_with("(declare-fun") {
+                if let Ok(sexps) = parse_sexps(&tokenize(command)) {
+                    if let Some(Sexp::List(decl)) = sexps.first() {
+                        if let (Some(Sexp::Atom(name)), Ok(width)) =
+                            (decl.get(1), sort_width(decl.last()))
+                        {
+                            widths.insert(name.to_owned(), width);
+                        }
+                    }
+                }
+            }
+            script += line;
+            script += "\n";
+        }
+        script += "(check-sat)\n";
+        process.stdin.write_all(script.as_bytes()).ok()?;
+        process.stdin.flush().ok()?;
+
+        let result = match process.response()?.trim() {
+            "sat" => SolverResult::Sat,
+            "unsat" => SolverResult::Unsat,
+            "unknown" => SolverResult::Unknown,
+            _ => return None,
+        };
+
+        self.model.borrow_mut().clear();
+        if result == SolverResult::Sat && model && !widths.is_empty() {
+            let names: Vec<String> = widths.keys().map(|n| format!("|{}|", n)).collect();
+            let get = format!("(get-value ({}))\n", names.join(" "));
+            process.stdin.write_all(get.as_bytes()).ok()?;
+            process.stdin.flush().ok()?;
+            let values = parse_sexps(&tokenize(&process.response()?)).ok()?;
+            let mut model = self.model.borrow_mut();
+            for pair in values.iter().flat_map(|v| match v {
+                Sexp::List(pairs) => pairs.as_slice(),
+                _ => &[],
+            }) {
+                if let Sexp::List(pair) = pair {
+                    if let [Sexp::Atom(name), value] = pair.as_slice() {
+                        let width = widths.get(name).copied().unwrap_or(64);
+                        if let Some(bits) = binary_value(value, width) {
+                            model.insert(name.to_owned(), bits);
+                        }
+                    }
+                }
+            }
+        }
+
+        process.stdin.write_all(b"(pop 1)\n").ok()?;
+        Some(result)
+    }
+}
+
+#[cfg(feature = "smtlib")]
+impl SolverBackend for SmtLibBackend {
+    fn name(&self) -> &str {
+        &self.command
+    }
+
+    fn check(&self, solver: &Solver, assumptions: &[BitVec], model: bool) -> SolverResult {
+        if !self.fallback.get() {
+            if let Some(result) = self.query(solver, assumptions, model) {
+                return result;
+            }
+            println!("warning: {} failed, using boolector instead", self.command);
+            self.fallback.set(true);
+            self.process.replace(None);
+        }
+        BoolectorBackend.check(solver, assumptions, model)
+    }
+
+    fn value(&self, solver: &Solver, bv: &BitVec) -> Option<String> {
+        if self.fallback.get() {
+            return BoolectorBackend.value(solver, bv);
+        }
+        // boolector evaluates the term with the variables fixed to the model
+        let model = self.model.borrow();
+        solver.btor.push(1);
+        for var in solver.vars.borrow().iter() {
+            if let Some(bits) = model.get(&node_name(var)) {
+                var._eq(&BV::from_binary_str(solver.btor.clone(), bits))
+                    .assert();
+            }
+        }
+        let value = if solver.btor.sat() == SolverResult::Sat {
+            BoolectorBackend.value(solver, bv)
+        } else {
+            None
+        };
+        solver.btor.pop(1);
+        value
+    }
+
+    fn failed(&self, assumption: &BitVec) -> bool {
+        !self.fallback.get() || assumption.is_failed_assumption()
+    }
+}
+
+// the bits of a constant in a model like #b0101, #x41, or (_ bv65 8)
+#[cfg(feature = "smtlib")]
+fn binary_value(value: &Sexp, width: u32) -> Option<String> {
+    match value {
+        Sexp::Atom(atom) => {
+            if let Some(bits) = atom.strip_prefix("#b") {
+                Some(bits.to_owned())
+            } else {
+                let hex = atom.strip_prefix("#x")?;
+                let mut bits = String::new();
+                for c in hex.chars() {
+                    bits += &format!("{:04b}", c.to_digit(16)?);
+                }
+                Some(bits)
+            }
+        }
+        Sexp::List(list) => match list.as_slice() {
+            [Sexp::Atom(u), Sexp::Atom(value), _] if u == "_" => {
+                let value: u128 = value.strip_prefix("bv")?.parse().ok()?;
+                let bits = format!("{:0width$b}", value, width = width as usize);
+                Some(bits[bits.len() - width as usize..].to_owned())
+            }
+            _ => None,
+        },
+    }
+}
+
+/// Which `SolverBackend` makes the queries, set with `RadiusOption::Solver`.
+/// The external solvers need the `smtlib` feature
+///
+/// ## Example
+/// ```no_run
+/// use radius2::radius::{Radius, RadiusOption};
+/// use radius2::solver::SolverKind;
+/// let options = [RadiusOption::Solver(SolverKind::from_name("bitwuzla"))];
+/// let mut radius = Radius::new_with_options(Some("../tests/r100"), &options);
+/// let mut state = radius.call_state(0x004006fd);
+/// state.symbolize_memory("flag", 0x100000, 12);
+/// state.registers.set("rdi", radius2::vc(0x100000));
+/// let mut found = radius.run_until(state, 0x004007a1, &[0x00400790]).unwrap();
+/// assert_eq!(found.dump_inputs()["flag"], b"Code_Talkers");
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum SolverKind {
+    #[default]
+    Boolector,
+    /// The bitwuzla binary on the PATH
+    #[cfg(feature = "smtlib")]
+    Bitwuzla,
+    /// The cvc5 binary on the PATH
+    #[cfg(feature = "smtlib")]
+    Cvc5,
+    /// Another command that reads SMT-LIB from stdin
+    #[cfg(feature = "smtlib")]
+    SmtLib(String),
+}
+
+impl SolverKind {
+    pub fn backend(&self) -> Rc<dyn SolverBackend> {
+        match self {
+            SolverKind::Boolector => Rc::new(BoolectorBackend),
+            #[cfg(feature = "smtlib")]
+            SolverKind::Bitwuzla => Rc::new(SmtLibBackend::new("bitwuzla", &[])),
+            #[cfg(feature = "smtlib")]
+            SolverKind::Cvc5 => Rc::new(SmtLibBackend::new(
+                "cvc5",
+                &["--lang=smt2", "--incremental"],
+            )),
+            #[cfg(feature = "smtlib")]
+            SolverKind::SmtLib(command) => Rc::new(SmtLibBackend::new(command, &[])),
+        }
+    }
+
+    /// Parse a solver name from the command line, other names are commands.
+    /// Without the `smtlib` feature every name is boolector
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "boolector" => SolverKind::Boolector,
+            #[cfg(feature = "smtlib")]
+            "bitwuzla" => SolverKind::Bitwuzla,
+            #[cfg(feature = "smtlib")]
+            "cvc5" => SolverKind::Cvc5,
+            #[cfg(feature = "smtlib")]
+            command => SolverKind::SmtLib(command.to_owned()),
+            #[cfg(not(feature = "smtlib"))]
+            other => {
+                println!(
+                    "warning: {} needs the smtlib feature, using boolector",
+                    other
+                );
+                SolverKind::Boolector
+            }
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Solver {
@@ -14,6 +522,23 @@ pub struct Solver {
     pub assertions: Vec<BitVec>,
     pub indexes: Vec<usize>,
     pub eval_max: usize,
+    /// shared by all solvers cloned or duplicated from this one
+    pub stats: Rc<Cell<SolverStats>>,
+    /// log of every query, also shared
+    pub log: Option<Rc<RefCell<QueryLog>>>,
+    /// conditions that models satisfy when possible, eg. input bytes equal to a seed
+    pub seed: Vec<BitVec>,
+    /// makes the queries, see `SolverBackend`
+    pub backend: Rc<dyn SolverBackend>,
+    /// longest a boolector query may take before its result is unknown
+    pub timeout: Option<Duration>,
+    // assertions asserted in the btor context one level each, shared
+    // by every solver of the context, see `apply_assertions`
+    applied: Rc<RefCell<Vec<BitVec>>>,
+    // conditions assumed for the next query only
+    assumed: RefCell<Vec<BitVec>>,
+    // variables created in the btor context, shared like `applied`
+    vars: Rc<RefCell<Vec<BitVec>>>,
 }
 
 impl Default for Solver {
@@ -36,10 +561,20 @@ impl Solver {
             assertions: Vec::with_capacity(256),
             indexes: Vec::with_capacity(256),
             eval_max,
+            stats: Rc::new(Cell::new(SolverStats::default())),
+            log: None,
+            seed: vec![],
+            backend: Rc::new(BoolectorBackend),
+            timeout: None,
+            applied: Rc::new(RefCell::new(vec![])),
+            assumed: RefCell::new(vec![]),
+            vars: Rc::new(RefCell::new(vec![])),
         }
     }
 
     pub fn duplicate(&self) -> Self {
+        // the copy has the levels of the applied assertions too, so both
+        // keep the prefix and what btor learned from it
         let btor = Arc::new(self.btor.duplicate());
 
         let mut solver = Solver {
@@ -47,7 +582,16 @@ impl Solver {
             assertions: Vec::with_capacity(256),
             indexes: self.indexes.clone(),
             eval_max: self.eval_max,
+            stats: self.stats.clone(),
+            log: self.log.clone(),
+            seed: vec![],
+            backend: self.backend.clone(),
+            timeout: None,
+            applied: Rc::new(RefCell::new(vec![])),
+            assumed: RefCell::new(vec![]),
+            vars: Rc::new(RefCell::new(vec![])),
         };
+        solver.set_timeout(self.timeout);
 
         solver.assertions = self
             .assertions
@@ -55,19 +599,96 @@ impl Solver {
             .map(|a| solver.translate(a).unwrap())
             .collect();
 
+        solver.seed = self
+            .seed
+            .iter()
+            .map(|s| solver.translate(s).unwrap())
+            .collect();
+
+        let vars = self
+            .vars
+            .borrow()
+            .iter()
+            .filter_map(|v| solver.translate(v))
+            .collect();
+        solver.vars.replace(vars);
+
+        let applied = self
+            .applied
+            .borrow()
+            .iter()
+            .map(|a| solver.translate(a).unwrap())
+            .collect();
+        solver.applied.replace(applied);
+
         solver
     }
 
+    /// Assert the assertions in the btor context incrementally, the ones
+    /// already asserted for a previous query of this solver or another of
+    /// the context that shares a prefix of them are kept along with what
+    /// btor learned from them. A duplicated solver starts with the levels
+    /// applied in the original, so a forked state only asserts what it
+    /// added since. Queries push a level above them
     pub fn apply_assertions(&self) {
-        for assertion in &self.assertions {
+        let mut applied = self.applied.borrow_mut();
+        let common = applied
+            .iter()
+            .zip(&self.assertions)
+            .take_while(|(a, b)| a.get_id() == b.get_id())
+            .count();
+
+        if applied.len() > common {
+            self.btor.pop((applied.len() - common) as u32);
+            applied.truncate(common);
+        }
+        drop(applied);
+        self.push_applied(&self.assertions[common..]);
+    }
+
+    // assert each of `assertions` in a level of its own
+    fn push_applied(&self, assertions: &[BitVec]) {
+        let mut applied = self.applied.borrow_mut();
+        for assertion in assertions {
+            self.btor.push(1);
             assertion.assert();
+            applied.push(assertion.to_owned());
+        }
+    }
+
+    // pop the levels of the applied assertions, before a scope is pushed or popped
+    fn clear_applied(&self) {
+        let mut applied = self.applied.borrow_mut();
+        if !applied.is_empty() {
+            self.btor.pop(applied.len() as u32);
+            applied.clear();
         }
     }
 
     #[inline]
     pub fn bv(&self, s: &str, n: u32) -> BitVec {
-        // check if it already exists
-        BV::new(self.btor.clone(), n, Some(s))
+        let bv = BV::new(self.btor.clone(), n, Some(s));
+        self.vars.borrow_mut().push(bv.to_owned());
+        bv
+    }
+
+    /// Run `f` with every variable renamed to `name@id`, where id is its
+    /// btor node id, so the terms dumped by `f` tell apart distinct
+    /// variables with the same name. The names are restored after
+    pub fn with_node_ids<T>(&self, f: impl FnOnce() -> T) -> T {
+        let vars = self.vars.borrow().clone();
+        let names: Vec<Option<String>> = vars
+            .iter()
+            .map(|v| v.get_symbol().map(str::to_owned))
+            .collect();
+        for var in &vars {
+            var.to_owned().set_symbol(Some(&node_name(var)));
+        }
+        let result = f();
+        for (var, name) in vars.iter().zip(&names) {
+            var.to_owned().set_symbol(name.as_deref());
+        }
+        result
     }
 
     #[inline]
@@ -81,6 +702,11 @@ impl Solver {
         }
     }
 
+    /// An unconstrained array of `element` bit values at `index` bit indexes
+    pub fn array(&self, index: u32, element: u32) -> BitArray {
+        Array::new(self.btor.clone(), index, element, None)
+    }
+
     pub fn translate(&self, bv: &BitVec) -> Option<BitVec> {
         Btor::get_matching_bv(self.btor.clone(), bv)
     }
@@ -88,7 +714,11 @@ impl Solver {
     pub fn translate_value(&self, value: &Value) -> Value {
         match value {
             Value::Concrete(val, t) => Value::Concrete(*val, *t),
-            Value::Symbolic(val, t) => Value::Symbolic(self.translate(val).unwrap(), *t),
+            Value::Symbolic(val, t) => {
+                let translated = self.translate(val).unwrap();
+                interval::set(&translated, interval::get(val));
+                Value::Symbolic(translated, *t)
+            }
         }
     }
 
@@ -148,15 +778,94 @@ impl Solver {
             }
             Value::Symbolic(val, t) => {
                 let taint = if_val.get_taint() | else_val.get_taint();
-                Value::Symbolic(
+                let if_interval = interval::of(if_val, max_bit);
+                let else_interval = interval::of(else_val, max_bit);
+                let interval = match interval::get(val).slice(0, 0).as_constant() {
+                    Some(0) => else_interval,
+                    Some(_) => if_interval,
+                    None => if_interval.join(&else_interval),
+                };
+                let value = Value::Symbolic(
                     val.slice(0, 0)
                         .cond_bv(&self.to_bv(if_val, max_bit), &self.to_bv(else_val, max_bit)),
                     taint | t,
-                )
+                );
+                interval::track(value, interval)
             }
         }
     }
 
+    // a query that needs a model
+    fn sat(&self) -> SolverResult {
+        self.query(true)
+    }
+
+    // a query that needs no model
+    fn decide(&self) -> SolverResult {
+        self.query(false)
+    }
+
+    // every sat query goes through here to keep the stats
+    fn query(&self, model: bool) -> SolverResult {
+        let assumed = self.assumed.take();
+        let start = Instant::now();
+        let result = self.backend.check(self, &assumed, model);
+        let time = start.elapsed();
+        let mut stats = self.stats.get();
+        stats.queries += 1;
+        stats.time += time;
+        self.stats.set(stats);
+
+        if let Some(log) = &self.log {
+            let mut log = log.borrow_mut();
+            let smt = if log.is_slow(time) {
+                // assumptions are gone after the query, assert them to print
+                self.btor.push(1);
+                for cond in &assumed {
+                    cond.assert();
+                }
+                let smt = self.btor.print_constraints();
+                self.btor.pop(1);
+                Some(smt)
+            } else {
+                None
+            };
+            let query = SolverQuery {
+                addr: log.addr,
+                assertions: self.assertions.len(),
+                assumptions: assumed.len(),
+                sat: result == SolverResult::Sat,
+                time,
+                smt,
+            };
+            log.add(query);
+        }
+        result
+    }
+
+    // assume the condition for the next query only
+    fn assume(&self, cond: &BitVec) {
+        self.assumed.borrow_mut().push(cond.to_owned());
+    }
+
+    // the value of `bv` in the model of the last query
+    fn model_value(&self, bv: &BitVec) -> Option<String> {
+        self.backend.value(self, bv)
+    }
+
+    // the low 64 bits of the value of `bv` in the model of the last query
+    fn model_u64(&self, bv: &BitVec) -> Option<u64> {
+        let bits = self.model_value(bv)?;
+        u64::from_str_radix(&bits[bits.len().saturating_sub(64)..], 2).ok()
+    }
+
+    /// Set the address that following queries are attributed to
+    pub fn set_address(&self, addr: u64) {
+        if let Some(log) = &self.log {
+            log.borrow_mut().addr = addr;
+        }
+    }
+
     pub fn enable_model(&self, b: bool) {
         if b {
             self.btor.set_opt(BtorOption::ModelGen(ModelGen::All));
@@ -165,14 +874,38 @@ impl Solver {
         }
     }
 
+    // check sat for a model, satisfying as many seed conditions as possible
+    fn model_sat(&self) -> SolverResult {
+        let mut seed: Vec<&BitVec> = self.seed.iter().collect();
+        while !seed.is_empty() {
+            for cond in &seed {
+                self.assume(cond);
+            }
+            if self.sat() == SolverResult::Sat {
+                return SolverResult::Sat;
+            }
+            // drop the conditions that conflict with the assertions
+            let kept: Vec<&BitVec> = seed
+                .iter()
+                .copied()
+                .filter(|cond| !self.backend.failed(cond))
+                .collect();
+            if kept.len() == seed.len() {
+                break;
+            }
+            seed = kept;
+        }
+        self.sat()
+    }
+
     pub fn evaluate(&self, bv: &BitVec) -> Option<Value> {
         self.enable_model(true);
 
-        self.btor.push(1);
         self.apply_assertions();
+        self.btor.push(1);
         //let new_bv = self.translate(bv).unwrap();
-        let sol = if self.btor.sat() == SolverResult::Sat {
-            Some(Value::Concrete(bv.get_a_solution().as_u64().unwrap(), 0))
+        let sol = if self.model_sat() == SolverResult::Sat {
+            self.model_u64(bv).map(|v| Value::Concrete(v, 0))
         } else {
             None
         };
@@ -189,11 +922,11 @@ impl Solver {
             Value::Symbolic(bv, t) => {
                 self.enable_model(true);
 
-                self.btor.push(1);
                 self.apply_assertions();
+                self.btor.push(1);
                 //let new_bv = self.translate(bv).unwrap();
-                let sol = if self.btor.sat() == SolverResult::Sat {
-                    Some(Value::Concrete(bv.get_a_solution().as_u64().unwrap(), *t))
+                let sol = if self.model_sat() == SolverResult::Sat {
+                    self.model_u64(bv).map(|v| Value::Concrete(v, *t))
                 } else {
                     None
                 };
@@ -220,13 +953,12 @@ impl Solver {
             Value::Symbolic(bv, _t) => {
                 self.enable_model(true);
 
-                self.btor.push(1);
                 self.apply_assertions();
+                self.btor.push(1);
                 //let new_bv = self.translate(bv).unwrap();
-                let sol_bv = if self.btor.sat() == SolverResult::Sat {
-                    let sol = bv.get_a_solution().disambiguate();
-                    let bv_str = sol.as_01x_str();
-                    Some(BV::from_binary_str(self.btor.clone(), bv_str))
+                let sol_bv = if self.model_sat() == SolverResult::Sat {
+                    self.model_value(bv)
+                        .map(|bits| BV::from_binary_str(self.btor.clone(), &bits))
                 } else {
                     None
                 };
@@ -245,14 +977,18 @@ impl Solver {
         }
     }
 
+    /// Open a scope, the assertions made until `pop` are dropped then
     #[inline]
     pub fn push(&mut self) {
+        self.clear_applied();
         self.indexes.push(self.assertions.len());
         self.btor.push(1)
     }
 
+    /// Drop the assertions made since the last `push`
     #[inline]
     pub fn pop(&mut self) {
+        self.clear_applied();
         self.btor.pop(1);
         let index = self.indexes.pop().unwrap();
         self.assertions = self.assertions[..index].to_owned();
@@ -277,14 +1013,16 @@ impl Solver {
         };
 
         self.enable_model(true);
-        self.btor.push(1);
         self.apply_assertions();
+        self.btor.push(1);
         //let new_bv = self.translate(bv).unwrap();
-        let sol = if self.btor.sat() == SolverResult::Sat {
-            let conval = bv.get_a_solution().as_u64().unwrap();
-            let assertion = bv._eq(&self.bvv(conval, bv.get_width()));
-            self.assert_bv(&assertion);
-            Some(conval)
+        let sol = if self.model_sat() == SolverResult::Sat {
+            let conval = self.model_u64(&bv);
+            if let Some(conval) = conval {
+                let assertion = bv._eq(&self.bvv(conval, bv.get_width()));
+                self.assert_bv(&assertion);
+            }
+            conval
         } else {
             None
         };
@@ -313,32 +1051,56 @@ impl Solver {
             .push(self.to_bv(&!value.eq(&Value::Concrete(0, 0)), 1));
     }
 
+    /// Set the time limit of boolector queries, after which they are unknown
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+        self.btor.set_opt(BtorOption::SolverTimeout(timeout));
+    }
+
     #[inline]
     pub fn is_sat(&self) -> bool {
+        self.sat_result() == SolverResult::Sat
+    }
+
+    /// check the satisfiability of the assertions, which is unknown if the query times out
+    pub fn sat_result(&self) -> SolverResult {
         if self.assertions.is_empty() {
-            true
+            SolverResult::Sat
         } else {
-            self.btor.push(1);
             self.apply_assertions();
-            let sat = self.btor.sat() == SolverResult::Sat;
+            self.btor.push(1);
+            let result = self.decide();
             self.btor.pop(1);
-            sat
+            result
         }
     }
 
     /// check the satisfiability of the assertion
     #[inline]
     pub fn check_sat(&mut self, assertion: &Value) -> bool {
+        self.check_result(assertion) == SolverResult::Sat
+    }
+
+    /// check the satisfiability of the assertion, which is unknown if the query times out
+    pub fn check_result(&mut self, assertion: &Value) -> SolverResult {
         match assertion {
-            Value::Concrete(v, _t) => *v != 0,
+            Value::Concrete(0, _t) => SolverResult::Unsat,
+            Value::Concrete(_v, _t) => SolverResult::Sat,
+            // assertions that are zero for every value need no query
+            Value::Symbolic(_v, _t) if assertion.interval().as_constant() == Some(0) => {
+                let mut stats = self.stats.get();
+                stats.skipped += 1;
+                self.stats.set(stats);
+                SolverResult::Unsat
+            }
             Value::Symbolic(_v, _t) => {
-                self.btor.push(1);
-                self.assert(assertion);
                 self.apply_assertions();
-                let sat = self.btor.sat() == SolverResult::Sat;
-                self.assertions.pop();
+                self.btor.push(1);
+                self.to_bv(&!assertion.eq(&Value::Concrete(0, 0)), 1)
+                    .assert();
+                let result = self.decide();
                 self.btor.pop(1);
-                sat
+                result
             }
         }
     }
@@ -347,11 +1109,11 @@ impl Solver {
         self.enable_model(true);
         let mut solutions: Vec<u64> = Vec::with_capacity(self.eval_max);
         //let new_bv = self.translate(bv).unwrap();
-        self.btor.push(1);
         self.apply_assertions();
+        self.btor.push(1);
         for _i in 0..self.eval_max {
-            if self.btor.sat() == SolverResult::Sat {
-                let solopt = bv.get_a_solution().as_u64();
+            if self.sat() == SolverResult::Sat {
+                let solopt = self.model_u64(bv);
                 if let Some(sol) = solopt {
                     solutions.push(sol);
                     let sol_bv = BV::from_u64(self.btor.clone(), sol, bv.get_width());
@@ -378,12 +1140,10 @@ impl Solver {
     pub fn solution(&self, bv: &BitVec) -> Option<String> {
         self.enable_model(true);
 
-        self.btor.push(1);
         self.apply_assertions();
-        let sol = if self.btor.sat() == SolverResult::Sat {
-            let solution = bv.get_a_solution().disambiguate();
-            let sol_str = solution.as_01x_str();
-            Some(sol_str.to_string())
+        self.btor.push(1);
+        let sol = if self.model_sat() == SolverResult::Sat {
+            self.model_value(bv)
         } else {
             None
         };
@@ -426,18 +1186,18 @@ impl Solver {
 
     // surprisingly fast binary search to max
     pub fn max(&self, bv: &BitVec) -> u64 {
-        self.btor.push(1);
         self.apply_assertions();
+        self.btor.push(1);
 
         let len = bv.get_width();
         let mut low = 0;
         let mut high = 1 << (len - 1);
 
         while high != low {
-            bv.ugte(&self.bvv(high, len)).assume();
-            while self.btor.sat() != SolverResult::Sat && high != low {
+            self.assume(&bv.ugte(&self.bvv(high, len)));
+            while self.decide() != SolverResult::Sat && high != low {
                 high = low + (high - low) / 2;
-                bv.ugte(&self.bvv(high, len)).assume();
+                self.assume(&bv.ugte(&self.bvv(high, len)));
             }
 
             let tmp = high;
@@ -450,18 +1210,18 @@ impl Solver {
     }
 
     pub fn min(&self, bv: &BitVec) -> u64 {
-        self.btor.push(1);
         self.apply_assertions();
+        self.btor.push(1);
 
         let len = bv.get_width();
         let mut low = 0;
         let mut high = 1 << (len - 1);
 
         while high != low {
-            bv.ult(&self.bvv(high, len)).assume();
-            while self.btor.sat() == SolverResult::Sat && high != low {
+            self.assume(&bv.ult(&self.bvv(high, len)));
+            while self.decide() == SolverResult::Sat && high != low {
                 high = low + (high - low) / 2;
-                bv.ult(&self.bvv(high, len)).assume();
+                self.assume(&bv.ult(&self.bvv(high, len)));
             }
 
             let tmp = high;
@@ -486,3 +1246,8 @@ impl Solver {
         }
     }
 }
+
+// the name of a variable in dumps made in `Solver::with_node_ids`
+fn node_name(var: &BitVec) -> String {
+    format!("{}@{}", var.get_symbol().unwrap_or("v"), var.get_id())
+}
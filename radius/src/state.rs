@@ -1,12 +1,25 @@
-use crate::memory::{Memory, READ_CACHE};
-use crate::r2_api::{Endian, Information, R2Api};
+use crate::arch::Arch;
+use crate::diff::StateDiff;
+use crate::exception::Dispatch;
+use crate::memory::{HeapError, Memory, Uninit, READ_CACHE};
+use crate::primitives::PrimitiveCatalog;
+use crate::r2_api::{CallingConvention, Endian, Information, R2Api};
 use crate::registers::Registers;
-use crate::sims::fs::SimFilesytem;
+use crate::sims::fs::{SimFilesytem, SymbolicStream};
+use crate::sims::windows::WindowsState;
+use crate::snapshot::Snapshot;
 use crate::solver::{BitVec, Solver};
+use crate::stream::Stream;
+use crate::trace::{translate_write, MemoryWrite, Recording};
+use crate::types::TypeDefs;
 use crate::value::{byte_values, vc, Value};
+use crate::watch::{Access, Watchpoints};
+use boolector::SolverResult;
 
+use std::cell::RefCell;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::mem;
 use std::rc::Rc;
 use std::u8;
 
@@ -15,6 +28,9 @@ use std::u8;
 // but also i hate the code for them and want to remove it
 pub const DO_EVENT_HOOKS: bool = false;
 
+/// How many times longer `State::verify` waits for a query that timed out
+pub const TIMEOUT_ESCALATION: u32 = 10;
+
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 pub enum EventTrigger {
     Before, // call hook before event occurs
@@ -76,6 +92,9 @@ pub struct EsilState {
     pub temp1: Vec<StackItem>,
     pub temp2: Vec<StackItem>,
     pub pcs: Vec<u64>,
+    /// The delay slot of a branch likely whose pc is symbolic and the
+    /// address it falls through to, the slot runs if it is not taken
+    pub likely_slot: Option<(u64, u64)>,
 }
 
 #[derive(Debug, Clone)]
@@ -90,6 +109,13 @@ impl Default for StackItem {
     }
 }
 
+// where a source level variable is, see `State::read_var`
+#[derive(Debug, Clone)]
+enum VarPlace {
+    Register(String),
+    Memory(Value),
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum StateStatus {
     Active,
@@ -99,9 +125,77 @@ pub enum StateStatus {
     Unsat,
     Inactive,
     Crash(u64, char),
+    /// A heap bug at the address, see `RadiusOption::HeapCheck`
+    Violation(u64, HeapError),
     Exit,
 }
 
+/// A branch on a symbolic condition along the path of a state,
+/// `taken` is true if `target` is the jump target of the branch
+#[derive(Debug, Clone)]
+pub struct PathBranch {
+    pub addr: u64,
+    pub condition: Value,
+    pub target: u64,
+    pub taken: bool,
+    /// The successor not taken if the branch has two
+    pub other: Option<u64>,
+    /// The number of recorded steps when the branch was taken
+    pub step: Option<usize>,
+    /// The number of solver assertions before the branch condition
+    pub constraints: usize,
+}
+
+/// A branch or memory write that depends on tainted values,
+/// logged after `State::log_taint` is called
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaintEvent {
+    /// A conditional at `pc` on a value with the `taint`
+    Branch { pc: u64, taint: u64 },
+    /// A write at `pc` of a value or to an address with the `taint`,
+    /// `addr` is None if the address is symbolic
+    Write {
+        pc: u64,
+        addr: Option<u64>,
+        taint: u64,
+    },
+}
+
+/// A field of a structured input laid out with `State::layout_input`
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputField {
+    /// Concrete bytes, eg. a magic number
+    Bytes(Vec<u8>),
+    /// A concrete little endian integer of the given byte size
+    Le(u64, usize),
+    /// A concrete big endian integer of the given byte size
+    Be(u64, usize),
+    /// Unconstrained symbolic bytes
+    Symbolic(usize),
+    /// Symbolic bytes constrained with a pattern like `constrain_bytes`
+    Pattern(usize, String),
+    /// A symbolic length field of `size` bytes in the target endianness,
+    /// at most `max`, followed by `max` symbolic payload bytes
+    LengthPrefixed { size: usize, max: usize },
+}
+
+/// An endless iterator over function arguments, see `State::varargs`
+pub struct VarArgs<'a> {
+    state: &'a mut State,
+    cc: CallingConvention,
+    index: usize,
+}
+
+impl Iterator for VarArgs<'_> {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        let value = self.state.get_arg(&self.cc, self.index);
+        self.index += 1;
+        Some(value)
+    }
+}
+
 /// A program state, including memory, registers, and solver data
 #[derive(Clone)]
 pub struct State {
@@ -116,14 +210,45 @@ pub struct State {
     pub filesystem: SimFilesytem,
     pub status: StateStatus,
     pub context: HashMap<String, Vec<Value>>,
+    pub symbols: BTreeMap<String, Value>,
+    /// the next number of the names in each namespace, see `symbol_name`
+    pub symbol_counts: HashMap<String, usize>,
+    pub inputs: BTreeMap<String, Vec<Value>>,
+    /// preferred bytes of inputs when evaluating, see `set_seed`
+    pub seeds: BTreeMap<String, Vec<u8>>,
+    pub scope: Option<HashSet<String>>,
+    pub path: Vec<PathBranch>,
+    pub recording: Option<Recording>,
+    /// catalog of write-what-where primitives, shared by all states
+    pub primitives: Option<Rc<RefCell<PrimitiveCatalog>>>,
+    /// struct definitions, shared by all states
+    pub types: Rc<RefCell<TypeDefs>>,
+    /// Callbacks on memory and register accesses, see `Radius::hook_mem_write`
+    pub watchpoints: Rc<RefCell<Watchpoints>>,
+    // in a watchpoint callback, whose accesses are not watched
+    watching: bool,
     pub taints: HashMap<String, u64>,
+    /// Tainted branches and writes, if logging them
+    pub taint_log: Option<Vec<TaintEvent>>,
     pub hooks: HashMap<Event, Rc<EventHook>>,
     pub visits: HashMap<u64, usize>,
     pub pid: u64,
     pub backtrace: Vec<(u64, u64)>,
+    /// Largest length assumed for symbolic length operations
+    pub max_length: Option<u64>,
     pub blank: bool,
     pub debug: bool,
     pub check: bool,
+    /// Check heap accesses for overflows and use after free
+    pub heap_check: bool,
+    /// Forked on a branch whose query timed out, see `verify`
+    pub unverified: bool,
+    /// Calling convention of the sim being run, see `format::with_varargs`
+    pub sim_cc: Option<CallingConvention>,
+    /// Exception being dispatched to the handlers, see `exception::raise`
+    pub exception: Option<Dispatch>,
+    /// Exception handlers and registry of Windows targets
+    pub windows: WindowsState,
     pub strict: bool,
     pub has_event_hooks: bool,
 }
@@ -170,11 +295,13 @@ impl State {
             temp1: vec![], // these instances are actually not used
             temp2: vec![],
             pcs: Vec::with_capacity(64),
+            likely_slot: None,
         };
 
         let solver = Solver::new(eval_max);
         let registers = Registers::new(r2api, solver.clone(), blank);
         let memory = Memory::new(r2api, solver.clone(), blank);
+        let types = TypeDefs::new(memory.bits / 8);
 
         State {
             solver,
@@ -188,15 +315,33 @@ impl State {
             filesystem: SimFilesytem::new(),
             status: StateStatus::Active,
             context: HashMap::new(),
+            symbols: BTreeMap::new(),
+            symbol_counts: HashMap::new(),
+            inputs: BTreeMap::new(),
+            seeds: BTreeMap::new(),
+            scope: None,
+            path: vec![],
+            recording: None,
+            primitives: None,
+            types: Rc::new(RefCell::new(types)),
+            watchpoints: Rc::new(RefCell::new(Watchpoints::default())),
+            watching: false,
             taints: HashMap::new(),
+            taint_log: None,
             hooks: HashMap::new(),
             visits: HashMap::with_capacity(512),
             backtrace: Vec::with_capacity(128),
             pid: 1337, // sup3rh4x0r
+            max_length: None,
             blank,
             debug,
             check,
             strict,
+            heap_check: false,
+            unverified: false,
+            sim_cc: None,
+            exception: None,
+            windows: WindowsState::default(),
             has_event_hooks: false,
         }
     }
@@ -216,6 +361,12 @@ impl State {
 
         let mut memory = self.memory.clone();
         memory.solver = solver.clone();
+        if let Some(journal) = &mut memory.journal {
+            *journal = journal
+                .iter()
+                .map(|w| translate_write(w, &solver))
+                .collect();
+        }
 
         let addrs = memory.addresses();
         for addr in addrs {
@@ -234,11 +385,46 @@ impl State {
             context.insert(key.to_owned(), new_values);
         }
 
+        let symbols = self
+            .symbols
+            .iter()
+            .map(|(k, v)| (k.to_owned(), solver.translate_value(v)))
+            .collect();
+
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|(k, v)| {
+                let values = v.iter().map(|b| solver.translate_value(b)).collect();
+                (k.to_owned(), values)
+            })
+            .collect();
+
+        let path = self
+            .path
+            .iter()
+            .map(|b| PathBranch {
+                condition: solver.translate_value(&b.condition),
+                ..b.to_owned()
+            })
+            .collect();
+
+        let recording = self.recording.clone();
+
         let mut filesystem = self.filesystem.clone();
         for f in &mut filesystem.files {
             let content = f.content.clone();
             f.content = content.iter().map(|v| solver.translate_value(v)).collect();
         }
+        for socket in filesystem.sockets.values_mut() {
+            socket.sent = socket
+                .sent
+                .iter()
+                .map(|v| solver.translate_value(v))
+                .collect();
+        }
+
+        let windows = self.windows.translate(&solver);
 
         let esil_state = EsilState {
             mode: ExecMode::Uncon,
@@ -250,6 +436,7 @@ impl State {
             temp1: Vec::with_capacity(128),
             temp2: Vec::with_capacity(128),
             pcs: Vec::with_capacity(64),
+            likely_slot: self.esil.likely_slot,
         };
 
         State {
@@ -264,14 +451,32 @@ impl State {
             filesystem,
             status: self.status.clone(),
             context,
+            symbols,
+            symbol_counts: self.symbol_counts.clone(),
+            inputs,
+            seeds: self.seeds.clone(),
+            scope: self.scope.clone(),
+            path,
+            recording,
+            primitives: self.primitives.clone(),
+            types: self.types.clone(),
+            watchpoints: self.watchpoints.clone(),
+            watching: false,
             taints: self.taints.clone(),
+            taint_log: self.taint_log.clone(),
             hooks: self.hooks.clone(),
             visits: self.visits.clone(),
             backtrace: self.backtrace.clone(),
             pid: self.pid,
+            max_length: self.max_length,
             blank: self.blank,
             debug: self.debug,
             check: self.check,
+            heap_check: self.heap_check,
+            unverified: self.unverified,
+            sim_cc: None,
+            exception: self.exception,
+            windows,
             strict: self.strict,
             has_event_hooks: self.has_event_hooks,
         }
@@ -329,6 +534,13 @@ impl State {
         if self.check && self.check_crash(addr, &vc(1), 'r') {
             return vc(-1i64 as u64);
         }
+        if self.heap_check {
+            let address = self.solver.evalcon_to_u64(addr).unwrap_or_default();
+            if let Some(error) = self.memory.free_error(address) {
+                self.set_status(StateStatus::Violation(address, error));
+                return vc(-1i64 as u64);
+            }
+        }
 
         let ret = self.memory.free_sym(addr, &mut self.solver);
 
@@ -344,8 +556,226 @@ impl State {
         ret
     }
 
+    /// Assume that a symbolic `length` is at most the `max_length` of the
+    /// state, so operations with it do not materialize unbounded memory.
+    /// The assumption is added to the constraints with a warning and the
+    /// address of the operation is kept, see `length_assumptions`
+    pub fn bound_length(&mut self, length: &Value) -> Value {
+        let cap = match self.max_length {
+            Some(cap) if length.is_symbolic() => cap,
+            _ => return length.to_owned(),
+        };
+        if self.solver.max_value(length) <= cap {
+            return length.to_owned();
+        }
+
+        let bound = length.ulte(&vc(cap));
+        if self.check(&bound) {
+            let pc = self.registers.get_pc().as_u64().unwrap_or_default();
+            println!(
+                "warning: assuming symbolic length at 0x{:x} is at most {}",
+                pc, cap
+            );
+            self.assert(&bound);
+            self.context
+                .entry("length_assumptions".to_owned())
+                .or_default()
+                .push(vc(pc));
+        }
+        length.to_owned()
+    }
+
+    /// Get the addresses where a symbolic length was assumed to be at most `max_length`
+    pub fn length_assumptions(&self) -> Vec<u64> {
+        self.context
+            .get("length_assumptions")
+            .map(|a| a.iter().filter_map(|pc| pc.as_u64()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Serialize the registers, memory, constraints, and context of the
+    /// state to JSON, see `Snapshot`
+    pub fn serialize(&self) -> String {
+        Snapshot::new(self).to_json()
+    }
+
+    /// Restore a state serialized with `serialize` into this fresh state
+    pub fn deserialize(&mut self, data: &str) -> Result<(), String> {
+        Snapshot::from_json(data)?.restore(self)
+    }
+
+    /// Read the fields of the struct `name` at `address`, with
+    /// definitions added with `Radius::define_types` or from radare2
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// radius.define_types("struct pair { int a; long b; };").unwrap();
+    /// let mut state = radius.call_state(0x004006fd);
+    /// let pair = state.read_struct("struct pair", &radius2::vc(0x100000)).unwrap();
+    /// assert_eq!(pair.len(), 2);
+    /// assert_eq!(pair["b"].size(), 64);
+    /// ```
+    pub fn read_struct(&mut self, name: &str, address: &Value) -> Option<BTreeMap<String, Value>> {
+        let def = self.types.borrow_mut().lookup(name, &mut self.r2api)?;
+        let mut fields = BTreeMap::new();
+        for field in &def.fields {
+            let addr = address.add(&vc(field.offset));
+            let value = self.memory_read_value(&addr, field.size as usize);
+            fields.insert(field.name.to_owned(), value);
+        }
+        Some(fields)
+    }
+
+    /// Read a single field, like `sin_addr.s_addr`, of the struct `name` at `address`
+    pub fn read_field(&mut self, name: &str, field: &str, address: &Value) -> Option<Value> {
+        let def = self.types.borrow_mut().lookup(name, &mut self.r2api)?;
+        let field = def.field(field)?;
+        let addr = address.add(&vc(field.offset));
+        Some(self.memory_read_value(&addr, field.size as usize))
+    }
+
+    /// Write the given fields of the struct `name` at `address`,
+    /// returns false if the struct or a field is not defined
+    pub fn write_struct(
+        &mut self,
+        name: &str,
+        address: &Value,
+        values: &BTreeMap<String, Value>,
+    ) -> bool {
+        let def = if let Some(def) = self.types.borrow_mut().lookup(name, &mut self.r2api) {
+            def
+        } else {
+            return false;
+        };
+        for (name, value) in values {
+            if let Some(field) = def.field(name) {
+                let addr = address.add(&vc(field.offset));
+                self.memory_write_value(&addr, value, field.size as usize);
+            } else {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Read the variable `name` using the debug info or analysis of r2,
+    /// `func::name` for the locals and args of a function, which are only
+    /// where they are once its frame is set up, or the name of a global
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// assert!(state.read_var("fcn.004006fd::no_such_var").is_none());
+    /// ```
+    pub fn read_var(&mut self, name: &str) -> Option<Value> {
+        let (place, size) = self.locate_var(name, false)?;
+        Some(self.read_place(&place, size))
+    }
+
+    /// Write the variable `name`, see `read_var`. False if there is no such variable
+    pub fn write_var(&mut self, name: &str, value: &Value) -> bool {
+        match self.locate_var(name, false) {
+            Some((place, size)) => {
+                self.write_place(&place, size, value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Read the arg `name` of the function `func` on entry to it, eg. in a
+    /// state from `Radius::call_state`
+    pub fn read_arg(&mut self, func: &str, name: &str) -> Option<Value> {
+        let (place, size) = self.locate_var(&format!("{}::{}", func, name), true)?;
+        Some(self.read_place(&place, size))
+    }
+
+    /// Write the arg `name` of the function `func` on entry to it.
+    /// False if the function has no such arg
+    pub fn write_arg(&mut self, func: &str, name: &str, value: &Value) -> bool {
+        match self.locate_var(&format!("{}::{}", func, name), true) {
+            Some((place, size)) => {
+                self.write_place(&place, size, value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // where a variable is and its size in bytes. on entry the frame
+    // pointer is not set up yet, so its args are found from the sp
+    fn locate_var(&mut self, name: &str, entry: bool) -> Option<(VarPlace, usize)> {
+        let ptr = self.memory.bits / 8;
+        if let Some((func, var_name)) = name.rsplit_once("::") {
+            let addr = self.r2api.get_address(func).ok().filter(|a| *a != 0)?;
+            let info = self.r2api.get_function_info(addr).ok()?;
+            let bp = info.bpvars.iter().find(|v| v.name == var_name);
+            let sp = info.spvars.iter().find(|v| v.name == var_name);
+            let reg = info.regvars.iter().find(|v| v.name == var_name);
+
+            if let Some(var) = bp.or(sp).or(reg) {
+                let size = self.types.borrow_mut().size_of(&var.r#type);
+                let size = size.unwrap_or(ptr) as usize;
+                let offset = vc(var.r#ref.offset);
+                let place = if reg.is_some() {
+                    VarPlace::Register(var.r#ref.base.to_owned())
+                } else if bp.is_some() && entry {
+                    // bp will point to the saved bp pushed on entry
+                    let sp = self.registers.get_with_alias("SP");
+                    VarPlace::Memory(sp.add(&offset).sub(&vc(ptr)))
+                } else if bp.is_some() {
+                    VarPlace::Memory(self.registers.get_with_alias("BP").add(&offset))
+                } else {
+                    VarPlace::Memory(self.registers.get_with_alias("SP").add(&offset))
+                };
+                return Some((place, size));
+            }
+            if entry {
+                return None;
+            }
+            // static locals are symbols of their own
+            return self.locate_var(var_name, entry);
+        }
+
+        let addr = self.r2api.get_address(name).ok().filter(|a| *a != 0)?;
+        let symbols = self.r2api.get_symbols().unwrap_or_default();
+        let size = symbols
+            .iter()
+            .find(|s| s.vaddr == addr && s.size != 0)
+            .map(|s| s.size)
+            .unwrap_or(ptr as usize);
+        Some((VarPlace::Memory(vc(addr)), size))
+    }
+
+    fn read_place(&mut self, place: &VarPlace, size: usize) -> Value {
+        match place {
+            VarPlace::Register(reg) => {
+                let value = self.registers.get(reg);
+                let bits = (size as u64 * 8).min(value.size() as u64);
+                value.slice(bits - 1, 0)
+            }
+            VarPlace::Memory(addr) => self.memory_read_value(addr, size),
+        }
+    }
+
+    fn write_place(&mut self, place: &VarPlace, size: usize, value: &Value) {
+        match place {
+            VarPlace::Register(reg) => {
+                let width = self.registers.get(reg).size() as u64;
+                self.registers
+                    .set_bits(reg, 0, (size as u64 * 8).min(width), value)
+            }
+            VarPlace::Memory(addr) => self.memory_write_value(addr, value, size),
+        }
+    }
+
     /// Read `length` bytes from `address`
     pub fn memory_read(&mut self, address: &Value, length: &Value) -> Vec<Value> {
+        let length = &self.bound_length(length);
         if DO_EVENT_HOOKS && self.has_event_hooks && (address.is_symbolic() || length.is_symbolic())
         {
             self.do_hooked(
@@ -357,8 +787,15 @@ impl State {
         if self.check && self.check_crash(address, length, 'r') {
             return vec![];
         }
+        if self.heap_check && self.check_heap(address, length, false) {
+            return vec![];
+        }
 
         let ret = self.memory.read_sym_len(address, length, &mut self.solver);
+        if self.watching_memory() {
+            let value = self.pack(&ret);
+            self.watch_memory(Access::Read, address, ret.len() as u64, &value);
+        }
 
         if DO_EVENT_HOOKS && self.has_event_hooks && (address.is_symbolic() || length.is_symbolic())
         {
@@ -373,6 +810,7 @@ impl State {
 
     /// Write `length` bytes to `address`
     pub fn memory_write(&mut self, address: &Value, values: &[Value], length: &Value) {
+        let length = &self.bound_length(length);
         if DO_EVENT_HOOKS && self.has_event_hooks && (address.is_symbolic() || length.is_symbolic())
         {
             self.do_hooked(
@@ -381,12 +819,30 @@ impl State {
             );
         }
 
-        if self.check && self.check_crash(address, length, 'r') {
+        if self.check && self.check_crash(address, length, 'w') {
             return;
         }
+        if self.heap_check && self.check_heap(address, length, true) {
+            return;
+        }
+        if self.primitives.is_some() && address.get_taint() != 0 && !values.is_empty() {
+            let value = self.pack(values);
+            self.check_primitive(address, &value, values.len());
+        }
+
         let ret = self
             .memory
             .write_sym_len(address, values, length, &mut self.solver);
+        if self.taint_log.is_some() {
+            let taint = values
+                .iter()
+                .fold(address.get_taint(), |t, v| t | v.get_taint());
+            self.log_tainted_write(address, taint);
+        }
+        if self.watching_memory() && !values.is_empty() {
+            let value = self.pack(values);
+            self.watch_memory(Access::Write, address, values.len() as u64, &value);
+        }
 
         if DO_EVENT_HOOKS && self.has_event_hooks && (address.is_symbolic() || length.is_symbolic())
         {
@@ -412,8 +868,14 @@ impl State {
         if self.check && self.check_crash(address, &vc(length as u64), 'r') {
             return vc(-1i64 as u64);
         }
+        if self.heap_check && self.check_heap(address, &vc(length as u64), false) {
+            return vc(-1i64 as u64);
+        }
 
         let ret = self.memory.read_sym(address, length, &mut self.solver);
+        if self.watching_memory() {
+            self.watch_memory(Access::Read, address, length as u64, &ret);
+        }
 
         if DO_EVENT_HOOKS && self.has_event_hooks && address.is_symbolic() {
             self.do_hooked(
@@ -438,10 +900,21 @@ impl State {
         if self.check && self.check_crash(address, &vc(length as u64), 'w') {
             return;
         }
+        if self.heap_check && self.check_heap(address, &vc(length as u64), true) {
+            return;
+        }
+
+        self.check_primitive(address, value, length);
 
         let ret = self
             .memory
             .write_sym(address, value, length, &mut self.solver);
+        if self.taint_log.is_some() {
+            self.log_tainted_write(address, address.get_taint() | value.get_taint());
+        }
+        if self.watching_memory() {
+            self.watch_memory(Access::Write, address, length as u64, value);
+        }
 
         if DO_EVENT_HOOKS && self.has_event_hooks && address.is_symbolic() {
             self.do_hooked(
@@ -462,6 +935,7 @@ impl State {
         length: &Value,
         reverse: bool,
     ) -> Value {
+        let length = &self.bound_length(length);
         if DO_EVENT_HOOKS && self.has_event_hooks {
             let event = if addr.is_symbolic() || length.is_symbolic() {
                 Event::SymbolicSearch(EventTrigger::Before)
@@ -500,6 +974,17 @@ impl State {
     /// Compare memory at `dst` and `src` address up to `length` bytes.
     /// This is akin to memcmp but will handle symbolic addrs and length
     pub fn memory_compare(&mut self, dst: &Value, src: &Value, length: &Value) -> Value {
+        self.compare_with(dst, src, length, false)
+    }
+
+    /// Compare the strings at `dst` and `src` up to `length` bytes, like
+    /// strncmp. Symbolic strings are compared without forking
+    pub fn memory_strcmp(&mut self, dst: &Value, src: &Value, length: &Value) -> Value {
+        self.compare_with(dst, src, length, true)
+    }
+
+    fn compare_with(&mut self, dst: &Value, src: &Value, length: &Value, strings: bool) -> Value {
+        let length = &self.bound_length(length);
         if DO_EVENT_HOOKS && self.has_event_hooks {
             let event = if dst.is_symbolic() || src.is_symbolic() || length.is_symbolic() {
                 Event::SymbolicCompare(EventTrigger::Before)
@@ -517,7 +1002,12 @@ impl State {
             return vc(-1i64 as u64);
         }
 
-        let ret = self.memory.compare(dst, src, length, &mut self.solver);
+        let ret = if strings {
+            self.memory
+                .compare_strings(dst, src, length, &mut self.solver)
+        } else {
+            self.memory.compare(dst, src, length, &mut self.solver)
+        };
 
         if DO_EVENT_HOOKS && self.has_event_hooks {
             let event = if dst.is_symbolic() || src.is_symbolic() || length.is_symbolic() {
@@ -572,6 +1062,7 @@ impl State {
 
     /// Move `length` bytes from `src` to `dst`
     pub fn memory_move(&mut self, dst: &Value, src: &Value, length: &Value) {
+        let length = &self.bound_length(length);
         if DO_EVENT_HOOKS && self.has_event_hooks {
             let event = if dst.is_symbolic() || src.is_symbolic() || length.is_symbolic() {
                 Event::SymbolicMove(EventTrigger::Before)
@@ -588,6 +1079,11 @@ impl State {
         {
             return;
         }
+        if self.heap_check
+            && (self.check_heap(src, length, false) || self.check_heap(dst, length, true))
+        {
+            return;
+        }
 
         self.memory.memmove(dst, src, length, &mut self.solver);
 
@@ -604,6 +1100,39 @@ impl State {
         }
     }
 
+    // add the write to the primitive catalog if it is enabled
+    fn check_primitive(&self, address: &Value, value: &Value, length: usize) {
+        if let Some(catalog) = &self.primitives {
+            catalog.borrow_mut().check(self, address, value, length);
+        }
+    }
+
+    fn watching_memory(&self) -> bool {
+        !self.watching && !self.watchpoints.borrow().memory.is_empty()
+    }
+
+    // accesses made by the callbacks do not trigger watchpoints
+    fn watch_memory(&mut self, access: Access, address: &Value, length: u64, value: &Value) {
+        self.watching = true;
+        let watchpoints = self.watchpoints.clone();
+        watchpoints
+            .borrow()
+            .memory(self, access, address, length, value);
+        self.watching = false;
+    }
+
+    /// Call the watchpoints on the register at `index` after it is set
+    pub fn watch_register(&mut self, index: usize) {
+        if self.watching || self.watchpoints.borrow().registers.is_empty() {
+            return;
+        }
+        self.watching = true;
+        let value = self.registers.get_value(index);
+        let watchpoints = self.watchpoints.clone();
+        watchpoints.borrow().register(self, index, &value);
+        self.watching = false;
+    }
+
     /// Read pointer from `address`
     pub fn memory_read_ptr(&mut self, address: &Value) -> Value {
         let ptr_len = self.memory.bits as usize / 8;
@@ -616,6 +1145,159 @@ impl State {
         self.memory_write_value(address, value, ptr_len)
     }
 
+    /// Read an integer of `length` bytes at `address` with the given
+    /// endianness instead of the target's, eg. for network data
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// use radius2::r2_api::Endian;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// let addr = radius2::vc(0x100000);
+    /// state.write_be(&addr, &radius2::vc(0x1f90), 2);
+    /// assert_eq!(state.read_u16(&addr).as_u64(), Some(0x901f));
+    /// assert_eq!(state.memory_read_endian(&addr, 2, &Endian::Big).as_u64(), Some(0x1f90));
+    /// ```
+    pub fn memory_read_endian(&mut self, address: &Value, length: usize, endian: &Endian) -> Value {
+        let bytes = self.memory_read(address, &vc(length as u64));
+        self.memory.pack_endian(&bytes, endian)
+    }
+
+    /// Write the `length` byte integer `value` to `address` with the given endianness
+    pub fn memory_write_endian(
+        &mut self,
+        address: &Value,
+        value: &Value,
+        length: usize,
+        endian: &Endian,
+    ) {
+        let mut bytes = vec![vc(0); length];
+        self.memory.unpack_endian(value, length, &mut bytes, endian);
+        self.memory_write(address, &bytes, &vc(length as u64));
+    }
+
+    pub fn read_u16(&mut self, address: &Value) -> Value {
+        self.memory_read_value(address, 2)
+    }
+
+    pub fn read_u32(&mut self, address: &Value) -> Value {
+        self.memory_read_value(address, 4)
+    }
+
+    pub fn read_u64(&mut self, address: &Value) -> Value {
+        self.memory_read_value(address, 8)
+    }
+
+    pub fn write_u16(&mut self, address: &Value, value: &Value) {
+        self.memory_write_value(address, value, 2)
+    }
+
+    pub fn write_u32(&mut self, address: &Value, value: &Value) {
+        self.memory_write_value(address, value, 4)
+    }
+
+    pub fn write_u64(&mut self, address: &Value, value: &Value) {
+        self.memory_write_value(address, value, 8)
+    }
+
+    /// Read a little endian integer of `length` bytes
+    pub fn read_le(&mut self, address: &Value, length: usize) -> Value {
+        self.memory_read_endian(address, length, &Endian::Little)
+    }
+
+    /// Read a big endian integer of `length` bytes
+    pub fn read_be(&mut self, address: &Value, length: usize) -> Value {
+        self.memory_read_endian(address, length, &Endian::Big)
+    }
+
+    pub fn write_le(&mut self, address: &Value, value: &Value, length: usize) {
+        self.memory_write_endian(address, value, length, &Endian::Little)
+    }
+
+    pub fn write_be(&mut self, address: &Value, value: &Value, length: usize) {
+        self.memory_write_endian(address, value, length, &Endian::Big)
+    }
+
+    // the little endian bytes holding a bit field, and the offset of the field in them
+    fn bit_span(address: &Value, bit_offset: u64, width: u64) -> (Value, u64, usize) {
+        let low = bit_offset % 8;
+        assert!(
+            width > 0 && low + width <= 64,
+            "bit fields are 1 to 64 bits within 8 bytes"
+        );
+        let start = address.add(&vc(bit_offset / 8));
+        (start, low, ((low + width + 7) / 8) as usize)
+    }
+
+    /// Read the `width` bit field at `bit_offset` bits past `address`.
+    /// Bits count from the least significant bit of the first byte, the
+    /// way packed structures are laid out on little endian targets
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// let addr = radius2::vc(0x100000);
+    /// state.write_u16(&addr, &radius2::vc(0xffff));
+    /// state.write_bits(&addr, 5, 6, &radius2::vc(0b101010));
+    /// assert_eq!(state.read_bits(&addr, 5, 6).as_u64(), Some(0b101010));
+    /// assert_eq!(state.read_u16(&addr).as_u64(), Some(0xfd5f));
+    /// ```
+    pub fn read_bits(&mut self, address: &Value, bit_offset: u64, width: u64) -> Value {
+        let (start, low, length) = State::bit_span(address, bit_offset, width);
+        let value = self.read_le(&start, length);
+        value.slice(low + width - 1, low)
+    }
+
+    /// Write the low `width` bits of `value` to the bit field at `bit_offset`
+    /// bits past `address`, leaving the bits around it as they were
+    pub fn write_bits(&mut self, address: &Value, bit_offset: u64, width: u64, value: &Value) {
+        let (start, low, length) = State::bit_span(address, bit_offset, width);
+        let old = self.read_le(&start, length);
+        let new = old.insert_bits(low, width, value);
+        self.write_le(&start, &new, length);
+    }
+
+    /// Read `count` integers of `size` bytes at `address`, in the
+    /// target's endianness if `endian` is None
+    pub fn read_array(
+        &mut self,
+        address: &Value,
+        count: usize,
+        size: usize,
+        endian: Option<&Endian>,
+    ) -> Vec<Value> {
+        let endian = endian
+            .cloned()
+            .unwrap_or_else(|| self.memory.endian.clone());
+        (0..count)
+            .map(|i| {
+                let addr = address.add(&vc((i * size) as u64));
+                self.memory_read_endian(&addr, size, &endian)
+            })
+            .collect()
+    }
+
+    /// Write `values` as integers of `size` bytes at `address`, in the
+    /// target's endianness if `endian` is None
+    pub fn write_array(
+        &mut self,
+        address: &Value,
+        values: &[Value],
+        size: usize,
+        endian: Option<&Endian>,
+    ) {
+        let endian = endian
+            .cloned()
+            .unwrap_or_else(|| self.memory.endian.clone());
+        for (i, value) in values.iter().enumerate() {
+            let addr = address.add(&vc((i * size) as u64));
+            self.memory_write_endian(&addr, value, size, &endian);
+        }
+    }
+
     /// Read `length` bytes from `address`
     pub fn memory_read_bytes(&mut self, address: u64, length: usize) -> Vec<u8> {
         self.memory.read_bytes(address, length, &mut self.solver)
@@ -634,6 +1316,47 @@ impl State {
             .read_string(address, len as usize, &mut self.solver)
     }
 
+    /// Render `length` bytes from `address` like xxd, symbolic bytes
+    /// are shown as `??` and reading them does not fault or change the
+    /// memory of the state, see `Memory::peek`
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.memory_write_string(0x100000, "flag");
+    /// state.symbolize_memory("flag", 0x100004, 4);
+    /// let dump = state.hexdump(0x100000, 8);
+    /// assert!(dump.starts_with("0x00100000  66 6c 61 67 ?? ?? ?? ??"));
+    /// assert!(dump.ends_with("|flag????|\n"));
+    /// ```
+    pub fn hexdump(&mut self, address: u64, length: usize) -> String {
+        let values = self.memory.peek(address, length);
+
+        let mut dump = String::new();
+        for (i, line) in values.chunks(16).enumerate() {
+            let (mut hex, mut ascii) = (String::new(), String::new());
+            for value in line {
+                if let Some(Value::Concrete(byte, _)) = value.as_ref().map(|v| v.try_con()) {
+                    let c = byte as u8 as char;
+                    hex += &format!("{:02x} ", byte as u8);
+                    ascii.push(if c.is_ascii_graphic() || c == ' ' {
+                        c
+                    } else {
+                        '.'
+                    });
+                } else {
+                    hex += "?? ";
+                    ascii.push('?');
+                }
+            }
+            let addr = address + 16 * i as u64;
+            dump += &format!("0x{:08x}  {:<48} |{}|\n", addr, hex, ascii);
+        }
+        dump
+    }
+
     // this doesnt need to be here, just for consistency sake
     /// Write `string` to `address`
     pub fn memory_write_string(&mut self, address: u64, string: &str) {
@@ -652,6 +1375,73 @@ impl State {
         values
     }
 
+    /// Make the file `fd` a stream of symbolic input bytes that grows
+    /// as it is read, up to `max_length` bytes. The bytes are the input
+    /// "stdin" for fd 0 and the file path otherwise
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.entry_state();
+    /// state.symbolic_stream(0, 32);
+    /// let bytes = state.read_file(0, 8);
+    /// assert!(bytes[0].is_symbolic());
+    /// assert_eq!(state.stdin().bytes().len(), 8);
+    /// ```
+    pub fn symbolic_stream(&mut self, fd: usize, max_length: usize) {
+        let stream = SymbolicStream {
+            max_length,
+            printable: false,
+        };
+        self.filesystem.streams.insert(fd, stream);
+    }
+
+    /// Read `length` bytes from the file `fd`, growing it first if it
+    /// is a symbolic stream
+    pub fn read_file(&mut self, fd: usize, length: usize) -> Vec<Value> {
+        if let Some(stream) = self.filesystem.streams.get(&fd).copied() {
+            let remaining = self.filesystem.remaining(fd);
+            let size = self.filesystem.files[fd].content.len();
+            let grow = length
+                .saturating_sub(remaining)
+                .min(stream.max_length.saturating_sub(size));
+            if grow > 0 {
+                let source = if fd == 0 {
+                    "stdin".to_owned()
+                } else {
+                    self.filesystem.files[fd].path.to_owned()
+                };
+                let bytes = self.input_bytes(&source, size, grow);
+                if stream.printable {
+                    for byte in &bytes {
+                        self.constrain_printable(byte);
+                    }
+                }
+                self.filesystem.fill(fd, &bytes);
+            }
+        }
+        self.filesystem.read(fd, length)
+    }
+
+    /// Get the stdin of the state, to read or constrain it
+    pub fn stdin(&mut self) -> Stream<'_> {
+        Stream::new(self, 0)
+    }
+
+    /// Get the stdout of the state, to check what was written
+    pub fn stdout(&mut self) -> Stream<'_> {
+        Stream::new(self, 1)
+    }
+
+    // constrain a byte to be a printable ascii character
+    pub(crate) fn constrain_printable(&mut self, byte: &Value) {
+        if byte.is_symbolic() {
+            let printable = byte.ugte(&vc(0x20)).and(&byte.ulte(&vc(0x7e)));
+            self.assert(&printable);
+        }
+    }
+
     pub fn fill_file(&mut self, fd: usize, data: &[Value]) {
         self.filesystem.fill(fd, data)
     }
@@ -761,6 +1551,11 @@ impl State {
             }
         }
 
+        // code either state wrote is decoded from memory
+        for (start, end) in &state.memory.code_writes {
+            self.memory.mark_code(*start, end - start);
+        }
+
         // TODO merge context
 
         // TODO merge filesystem
@@ -800,6 +1595,643 @@ impl State {
         Value::Symbolic(self.bv(s, n), 0)
     }
 
+    /// Create a `Value::Symbolic` with the next free name in `namespace`,
+    /// eg. "scanf:0", "scanf:1", and register it in the state symbols
+    pub fn new_symbol(&mut self, namespace: &str, n: u32) -> Value {
+        if !self.in_scope(namespace) {
+            return vc(0);
+        }
+        let name = self.symbol_name(namespace);
+        let value = self.symbolic_value(&name, n);
+        self.register_symbol(&name, &value);
+        value
+    }
+
+    /// Get the next unused name in `namespace`. Names are numbered in order
+    /// of creation so they are the same for every run of a given path
+    pub fn symbol_name(&self, namespace: &str) -> String {
+        let next = self.symbol_counts.get(namespace).copied().unwrap_or(0);
+        format!("{}:{}", namespace, next)
+    }
+
+    /// Register a named symbolic value so it can be found with `symbols`
+    pub fn register_symbol(&mut self, name: &str, value: &Value) {
+        // numbered names like "ns:3" or "ns:3[0]" move the next name of ns past them
+        for (i, _) in name.match_indices(':') {
+            let number = name[i + 1..].split([':', '[']).next();
+            if let Some(Ok(n)) = number.map(str::parse::<usize>) {
+                let count = self.symbol_counts.entry(name[..i].to_owned()).or_default();
+                *count = (*count).max(n + 1);
+            }
+        }
+        self.symbols.insert(name.to_owned(), value.to_owned());
+    }
+
+    /// Get all registered symbols by name
+    pub fn symbols(&self) -> &BTreeMap<String, Value> {
+        &self.symbols
+    }
+
+    /// Get the registered symbol `name`
+    pub fn get_symbol(&self, name: &str) -> Option<&Value> {
+        self.symbols.get(name)
+    }
+
+    /// Create `length` symbolic bytes of the input `source` starting at `offset`.
+    /// The bytes are named like "stdin[3]", tainted with the source name,
+    /// and registered as both symbols and inputs
+    pub fn input_bytes(&mut self, source: &str, offset: usize, length: usize) -> Vec<Value> {
+        let taint_name = source.split('[').next().unwrap_or(source);
+        let taint = self.get_tainted_identifier(taint_name);
+
+        let mut bytes = Vec::with_capacity(length);
+        for i in offset..offset + length {
+            let name = format!("{}[{}]", source, i);
+            let byte = Value::Symbolic(self.bv(&name, 8), taint);
+            self.register_symbol(&name, &byte);
+            bytes.push(byte);
+        }
+
+        self.declare_input_bytes(source, offset, &bytes);
+        bytes
+    }
+
+    /// Declare that the bytes of `value` are the bytes of the input
+    /// `source` (eg. stdin, argv\[1\], a file path) starting at `offset`
+    pub fn declare_input(&mut self, source: &str, offset: usize, value: &Value) {
+        let bytes = self.unpack(value, value.size() as usize / 8);
+        self.declare_input_bytes(source, offset, &bytes);
+    }
+
+    /// Declare that `bytes` are the bytes of `source` starting at `offset`
+    pub fn declare_input_bytes(&mut self, source: &str, offset: usize, bytes: &[Value]) {
+        let input = self.inputs.entry(source.to_owned()).or_default();
+        if input.len() < offset + bytes.len() {
+            input.resize(offset + bytes.len(), vc(0));
+        }
+        input[offset..offset + bytes.len()].clone_from_slice(bytes);
+        self.seed_bytes(source, offset, bytes);
+    }
+
+    /// Prefer the bytes of `seed` for the input or symbol `source` whenever
+    /// values are evaluated or concretized, so solutions stay as close as
+    /// possible to a known input. Bytes of the input declared later are
+    /// also seeded
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.symbolize_memory("flag", 0x100000, 12);
+    /// state.set_seed("flag", b"Code_Talkerz");
+    /// assert_eq!(state.dump_inputs()["flag"], b"Code_Talkerz");
+    /// ```
+    pub fn set_seed(&mut self, source: &str, seed: &[u8]) {
+        self.seeds.insert(source.to_owned(), seed.to_vec());
+        let bytes = if let Some(input) = self.inputs.get(source) {
+            input.to_owned()
+        } else if let Some(symbol) = self.symbols.get(source).cloned() {
+            self.unpack(&symbol, symbol.size() as usize / 8)
+        } else {
+            return;
+        };
+        self.seed_bytes(source, 0, &bytes);
+    }
+
+    /// Fill the general purpose registers other than the pc, stack and frame
+    /// pointers, and stack memory that is read before it is written, with
+    /// `uninit` instead of the values r2 starts with
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::memory::Uninit;
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.set_uninit(Uninit::Poison(0xcc));
+    /// assert_eq!(state.registers.get("rbx").as_u64(), Some(0xcccccccccccccccc));
+    /// ```
+    pub fn set_uninit(&mut self, uninit: Uninit) {
+        self.registers.fill_uninit(uninit);
+        self.memory.uninit = Some(uninit);
+    }
+
+    // add the preferred values of symbolic input bytes to the solver
+    fn seed_bytes(&mut self, source: &str, offset: usize, bytes: &[Value]) {
+        let seed = if let Some(seed) = self.seeds.get(source) {
+            seed
+        } else {
+            return;
+        };
+        for (i, byte) in bytes.iter().enumerate() {
+            if let (true, Some(b)) = (byte.is_symbolic(), seed.get(offset + i)) {
+                let cond = self.solver.to_bv(&byte.eq(&vc(*b as u64)), 1);
+                self.solver.seed.push(cond);
+            }
+        }
+    }
+
+    /// Get the byte values of the input `source`
+    pub fn get_input(&self, source: &str) -> Option<&Vec<Value>> {
+        self.inputs.get(source)
+    }
+
+    /// Evaluate every input source to a consistent set of concrete bytes
+    pub fn dump_inputs(&mut self) -> BTreeMap<String, Vec<u8>> {
+        let mut dumped = BTreeMap::new();
+        let solver = &mut self.solver;
+        solver.push();
+        for (source, bytes) in &self.inputs {
+            let data = bytes
+                .iter()
+                .map(|b| solver.evalcon_to_u64(b).unwrap_or(0) as u8)
+                .collect();
+            dumped.insert(source.to_owned(), data);
+        }
+        solver.pop();
+        dumped
+    }
+
+    /// Get the input sources and byte offsets that `value` depends on,
+    /// mapping a constraint or value back to positions in the inputs
+    pub fn input_positions(&self, value: &Value) -> Vec<(String, usize)> {
+        let mut positions = vec![];
+        let expr = if let Value::Symbolic(bv, _t) = value {
+            format!("{:?}", bv)
+        } else {
+            return positions;
+        };
+
+        // taints are shared by a whole source so match on the symbol names
+        for (source, bytes) in &self.inputs {
+            for (i, byte) in bytes.iter().enumerate() {
+                if let Value::Symbolic(bv, _t) = byte {
+                    if bv.get_symbol().map(|s| expr.contains(s)) == Some(true) {
+                        positions.push((source.to_owned(), i));
+                    }
+                }
+            }
+        }
+        positions
+    }
+
+    /// Start recording the register and memory changes of every
+    /// instruction so the state can be stepped backwards
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::new());
+        self.memory.journal = Some(vec![]);
+    }
+
+    /// Stop recording and get the recording
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.record_writes();
+        self.memory.journal = None;
+        self.recording.take()
+    }
+
+    /// Record the registers changed by the current step, given their old
+    /// values, along with the memory it wrote
+    pub fn record_registers(&mut self, old: &[Value]) {
+        self.record_writes();
+        let values = &self.registers.values;
+        let solver = &self.solver;
+        if let Some(step) = self.recording.as_mut().and_then(|r| r.last_mut(solver)) {
+            for (i, (o, n)) in old.iter().zip(values).enumerate() {
+                if o != n {
+                    step.registers.push((i, o.to_owned(), n.to_owned()));
+                }
+            }
+        }
+    }
+
+    // add the memory writes made since the last call to the current step,
+    // this includes the ones sims make directly with `memory.write`
+    fn record_writes(&mut self) {
+        let writes = match &mut self.memory.journal {
+            Some(journal) if !journal.is_empty() => mem::take(journal),
+            _ => return,
+        };
+        let solver = &self.solver;
+        if let Some(step) = self.recording.as_mut().and_then(|r| r.last_mut(solver)) {
+            step.writes.extend(writes);
+        }
+    }
+
+    // write recorded bytes back without recording the write again
+    fn replay_write(&mut self, addr: u64, values: &[Value]) {
+        let journal = self.memory.journal.take();
+        self.memory.write(addr, &mut values.to_vec());
+        self.memory.journal = journal;
+    }
+
+    /// Undo the last recorded step, restoring registers and memory.
+    /// Returns false if there are no steps to undo
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("/bin/sh");
+    /// let mut state = radius.entry_state();
+    /// state.start_recording();
+    /// let mut states = radius.processor.run(state, radius2::processor::RunMode::Step);
+    /// let mut state = states.remove(0);
+    /// state.step_back();
+    /// ```
+    pub fn step_back(&mut self) -> bool {
+        self.record_writes();
+        let step = match &mut self.recording {
+            Some(recording) if recording.position > 0 => {
+                recording.position -= 1;
+                recording.step(recording.position, &self.solver)
+            }
+            _ => return false,
+        };
+
+        if let Some(step) = step {
+            for (index, old, _new) in step.registers {
+                self.registers.values[index] = old;
+            }
+            for write in step.writes.iter().rev() {
+                self.memory.restore(write.addr, &write.old);
+            }
+        }
+        true
+    }
+
+    /// Redo the next recorded step. Returns false if there are none
+    pub fn step_forward(&mut self) -> bool {
+        self.record_writes();
+        let step = match &mut self.recording {
+            Some(recording) if recording.position < recording.len() => {
+                recording.position += 1;
+                recording.step(recording.position - 1, &self.solver)
+            }
+            _ => return false,
+        };
+
+        if let Some(step) = step {
+            for (index, _old, new) in step.registers {
+                self.registers.values[index] = new;
+            }
+            for write in &step.writes {
+                self.replay_write(write.addr, &write.new);
+            }
+        }
+        true
+    }
+
+    /// Get the index and pc of the last recorded step that wrote to `addr`
+    pub fn last_write(&self, addr: u64) -> Option<(usize, u64)> {
+        let recording = self.recording.as_ref()?;
+        recording.last_write(addr).map(|(i, s)| (i, s.pc))
+    }
+
+    /// Get the index and pc of the last recorded step that changed `reg`
+    pub fn last_register_write(&self, reg: &str) -> Option<(usize, u64)> {
+        let index = self.registers.get_register(reg)?.value_index;
+        let recording = self.recording.as_ref()?;
+        recording.last_register_write(index).map(|(i, s)| (i, s.pc))
+    }
+
+    /// Get the value `reg` had after `step` recorded steps, reconstructed
+    /// from the recording without moving the state
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// use radius2::processor::RunMode;
+    /// let mut radius = Radius::new("/bin/sh");
+    /// let mut state = radius.entry_state();
+    /// state.start_recording();
+    /// let mut state = radius.processor.run(state, RunMode::Step).remove(0);
+    /// let mut state = radius.processor.run(state, RunMode::Step).remove(0);
+    /// let rsp = state.value_at_step(0, "rsp").unwrap();
+    /// let first = state.memory_at_step(1, 0x400000, 4).unwrap();
+    /// ```
+    pub fn value_at_step(&self, step: usize, reg: &str) -> Option<Value> {
+        let recording = self.recording.as_ref()?;
+        if step > recording.len() {
+            return None;
+        }
+        let register = self.registers.get_register(reg).or_else(|| {
+            let alias = self.registers.aliases.get(reg)?;
+            self.registers.get_register(&alias.reg)
+        })?;
+
+        let slot = register.value_index;
+        let mut value = self.registers.values[slot].to_owned();
+        if step < recording.position {
+            for s in (step..recording.position)
+                .rev()
+                .filter_map(|i| recording.get(i))
+            {
+                if let Some((_, old, _)) = s.registers.iter().find(|r| r.0 == slot) {
+                    value = self.solver.translate_value(old);
+                }
+            }
+        } else {
+            for s in (recording.position..step).filter_map(|i| recording.get(i)) {
+                if let Some((_, _, new)) = s.registers.iter().find(|r| r.0 == slot) {
+                    value = self.solver.translate_value(new);
+                }
+            }
+        }
+        Some(self.registers.extract(register.index, &value))
+    }
+
+    /// Get the `length` bytes at `addr` after `step` recorded steps,
+    /// reconstructed from the recording without moving the state
+    pub fn memory_at_step(&mut self, step: usize, addr: u64, length: usize) -> Option<Vec<Value>> {
+        self.record_writes();
+        let (len, position) = {
+            let recording = self.recording.as_ref()?;
+            (recording.len(), recording.position)
+        };
+        if step > len {
+            return None;
+        }
+
+        let mut bytes = vec![Value::Concrete(0, 0); length];
+        self.memory.read(addr, length, &mut bytes);
+
+        let recording = self.recording.as_ref()?;
+        let solver = &self.solver;
+        // old bytes that were not made yet keep what memory has now
+        let apply = |bytes: &mut Vec<Value>, write: &MemoryWrite, values: Vec<Option<&Value>>| {
+            for (i, value) in values.into_iter().enumerate() {
+                let byte = write.addr.wrapping_add(i as u64).wrapping_sub(addr);
+                if let (true, Some(value)) = (byte < length as u64, value) {
+                    bytes[byte as usize] = solver.translate_value(value);
+                }
+            }
+        };
+        if step < position {
+            for s in (step..position).rev().filter_map(|i| recording.get(i)) {
+                for write in s.writes.iter().rev() {
+                    apply(
+                        &mut bytes,
+                        write,
+                        write.old.iter().map(Option::as_ref).collect(),
+                    );
+                }
+            }
+        } else {
+            for s in (position..step).filter_map(|i| recording.get(i)) {
+                for write in &s.writes {
+                    apply(&mut bytes, write, write.new.iter().map(Some).collect());
+                }
+            }
+        }
+        Some(bytes)
+    }
+
+    /// Record a symbolic branch to `target` at `addr` on the path, before
+    /// its condition is asserted
+    pub fn add_branch(
+        &mut self,
+        addr: u64,
+        condition: &BitVec,
+        target: u64,
+        taken: bool,
+        other: Option<u64>,
+    ) {
+        self.path.push(PathBranch {
+            addr,
+            condition: Value::Symbolic(condition.to_owned(), 0),
+            target,
+            taken,
+            other,
+            step: self.recording.as_ref().map(|r| r.position),
+            constraints: self.solver.assertions.len(),
+        });
+    }
+
+    /// Fork the state at the earliest two way branch on its path whose
+    /// other side is satisfiable with the constraints of the state that
+    /// do not come from that branch or later ones. The fork is constrained
+    /// to take the other side and its registers and memory are stepped
+    /// back to just after the branch, so the state must be recording
+    /// since before the branch, see `start_recording`. Context like the
+    /// backtrace is kept from this state
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.start_recording();
+    /// state.symbolize_memory("flag", 0x100000, 12);
+    /// state.registers.set("rdi", radius2::vc(0x100000));
+    /// let mut found = radius.run_until(state, 0x004007a1, &[0x00400790]).unwrap();
+    /// let fork = found.backtrack().unwrap();
+    /// assert!(fork.path.len() <= found.path.len());
+    /// ```
+    pub fn backtrack(&mut self) -> Option<State> {
+        self.recording.as_ref()?;
+        let mut fork = self.duplicate();
+        let assertions = fork.solver.assertions.clone();
+        let branches = fork.path.clone();
+
+        for (i, branch) in branches.iter().enumerate() {
+            let (step, other) = match (branch.step, branch.other) {
+                (Some(step), Some(other)) => (step, other),
+                _ => continue,
+            };
+
+            // check without the conditions of this branch and the ones after
+            let later: Vec<usize> = branches[i..].iter().map(|b| b.constraints).collect();
+            fork.solver.assertions = assertions
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| !later.contains(j))
+                .map(|(_, a)| a.to_owned())
+                .collect();
+            let opposite = !branch.condition.to_owned();
+            if !fork.solver.check_sat(&opposite) {
+                continue;
+            }
+
+            fork.solver.assertions = assertions[..branch.constraints].to_vec();
+            fork.solver.assert(&opposite);
+            while fork.recording.as_ref().map(|r| r.position > step) == Some(true) {
+                fork.step_back();
+            }
+            fork.registers.set_pc(vc(other));
+            fork.path.truncate(i);
+            fork.path.push(PathBranch {
+                condition: opposite,
+                target: other,
+                taken: !branch.taken,
+                other: Some(branch.target),
+                ..branch.to_owned()
+            });
+            fork.status = StateStatus::Active;
+            return Some(fork);
+        }
+        None
+    }
+
+    /// Get the conjunction of the branch conditions on the path to this
+    /// state, along with each branch in order
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("/bin/sh");
+    /// let mut state = radius.entry_state();
+    /// let (predicate, branches) = state.path_predicate();
+    /// assert!(branches.is_empty());
+    /// ```
+    pub fn path_predicate(&self) -> (Value, Vec<PathBranch>) {
+        let predicate = self
+            .path
+            .iter()
+            .map(|b| b.condition.to_owned())
+            .reduce(|a, b| a.and(&b))
+            .unwrap_or_else(|| vc(1));
+
+        (predicate, self.path.clone())
+    }
+
+    /// Get the registers, memory, and constraints that differ from
+    /// `other`, eg. to see why only one of two paths to a target works
+    pub fn diff(&self, other: &State) -> StateDiff {
+        StateDiff::new(self, other)
+    }
+
+    /// Run concretely except for the marked symbolic `sources`. Symbols
+    /// from `new_symbol` in other namespaces (eg. "rand") become zero, as
+    /// do the inputs sims make (eg. "ioctl", "popen", "socket", "fork").
+    /// Reads are marked with "read" for all reads, "read:fd" for a file
+    /// descriptor, or "read@addr" where addr is the return address of the call
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("/bin/sh");
+    /// let mut state = radius.entry_state();
+    /// state.set_scope(&["read:0", "rand"]);
+    /// ```
+    pub fn set_scope(&mut self, sources: &[&str]) {
+        self.scope = Some(sources.iter().map(|s| s.to_string()).collect());
+    }
+
+    /// Mark another source as symbolic, running concretely otherwise
+    pub fn symbolize(&mut self, source: &str) {
+        self.scope
+            .get_or_insert_with(HashSet::new)
+            .insert(source.to_owned());
+    }
+
+    /// Check if `source` may produce symbols, true for any source
+    /// unless a scope has been set. A source like "ioctl:3:5401" is in
+    /// scope if it or a prefix before a colon, like "ioctl", was marked
+    pub fn in_scope(&self, source: &str) -> bool {
+        match &self.scope {
+            Some(scope) => {
+                scope.contains(source)
+                    || source
+                        .match_indices(':')
+                        .any(|(i, _)| scope.contains(&source[..i]))
+            }
+            None => true,
+        }
+    }
+
+    /// Create the input bytes of a sim like `input_bytes`, they are
+    /// zero if `source` is not in scope, see `set_scope`
+    pub fn sim_input(&mut self, source: &str, offset: usize, length: usize) -> Vec<Value> {
+        if self.in_scope(source) {
+            self.input_bytes(source, offset, length)
+        } else {
+            vec![vc(0); length]
+        }
+    }
+
+    /// Check if `source` was explicitly marked symbolic with a scope
+    pub fn is_marked(&self, source: &str) -> bool {
+        self.scope.as_ref().map(|s| s.contains(source)) == Some(true)
+    }
+
+    /// Create the bytes of the input `source` from a list of fields so that
+    /// parsers are explored past their magic and header checks
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// use radius2::state::InputField;
+    /// let mut radius = Radius::new("/bin/sh");
+    /// let mut state = radius.blank_state();
+    /// let bytes = state.layout_input("stdin", &[
+    ///     InputField::Bytes(b"\x7fELF".to_vec()),
+    ///     InputField::Le(1, 2),
+    ///     InputField::LengthPrefixed { size: 4, max: 16 },
+    /// ]);
+    /// assert_eq!(bytes.len(), 26);
+    /// ```
+    pub fn layout_input(&mut self, source: &str, fields: &[InputField]) -> Vec<Value> {
+        let mut bytes = vec![];
+        for field in fields {
+            let offset = bytes.len();
+            let field_bytes = match field {
+                InputField::Bytes(data) => data.iter().map(|b| vc(*b as u64)).collect(),
+                InputField::Le(value, size) => (0..*size)
+                    .map(|i| vc(value.checked_shr(8 * i as u32).unwrap_or(0) & 0xff))
+                    .collect(),
+                InputField::Be(value, size) => (0..*size)
+                    .rev()
+                    .map(|i| vc(value.checked_shr(8 * i as u32).unwrap_or(0) & 0xff))
+                    .collect(),
+                InputField::Symbolic(length) => self.input_bytes(source, offset, *length),
+                InputField::Pattern(length, pattern) => {
+                    let field_bytes = self.input_bytes(source, offset, *length);
+                    let value = self.pack(&field_bytes);
+                    self.constrain_bytes(&value, pattern);
+                    field_bytes
+                }
+                InputField::LengthPrefixed { size, max } => {
+                    let mut field_bytes = self.input_bytes(source, offset, *size);
+                    let length = self.pack(&field_bytes);
+                    self.assert(&length.ulte(&vc(*max as u64)));
+                    field_bytes.extend(self.input_bytes(source, offset + size, *max));
+                    field_bytes
+                }
+            };
+            bytes.extend(field_bytes);
+        }
+        self.declare_input_bytes(source, 0, &bytes);
+        bytes
+    }
+
+    /// Fill the file `fd` with an input laid out from `fields`
+    pub fn fill_file_layout(&mut self, fd: usize, fields: &[InputField]) -> Vec<Value> {
+        let source = match fd {
+            0 => "stdin".to_owned(),
+            _ => self
+                .filesystem
+                .getpath(fd)
+                .unwrap_or_else(|| fd.to_string()),
+        };
+        let bytes = self.layout_input(&source, fields);
+        self.filesystem.fill(fd, &bytes);
+        bytes
+    }
+
+    /// Add a file at `path` with an input laid out from `fields`
+    pub fn add_file_layout(&mut self, path: &str, fields: &[InputField]) -> Vec<Value> {
+        let bytes = self.layout_input(path, fields);
+        self.filesystem.add_file(path, &bytes);
+        bytes
+    }
+
+    /// Write `length` symbolic bytes of the input `source` to `addr`
+    pub fn symbolize_memory(&mut self, source: &str, addr: u64, length: usize) -> Vec<Value> {
+        let bytes = self.input_bytes(source, 0, length);
+        self.memory.write(addr, &mut bytes.clone());
+        bytes
+    }
+
     /// Create a tainted `Value::Concrete` from a value `v` and bit width `n`
     pub fn tainted_concrete_value(&mut self, t: &str, v: u64, n: u32) -> Value {
         let mask = if n < 64 { (1 << n) - 1 } else { -1i64 as u64 };
@@ -836,6 +2268,94 @@ impl State {
         (value.get_taint() & self.get_tainted_identifier(taint)) != 0
     }
 
+    /// Get a copy of `value` tainted with the `label`
+    pub fn taint_value(&mut self, value: &Value, label: &str) -> Value {
+        let taint = self.get_tainted_identifier(label);
+        value.with_taint(taint)
+    }
+
+    /// Get the names of the taints of `value`
+    pub fn get_taint(&self, value: &Value) -> Vec<String> {
+        let taint = value.get_taint();
+        let mut labels: Vec<(&String, &u64)> = self
+            .taints
+            .iter()
+            .filter(|(_, t)| taint & *t != 0)
+            .collect();
+
+        labels.sort_by_key(|(_, t)| **t);
+        labels.into_iter().map(|(l, _)| l.to_owned()).collect()
+    }
+
+    /// Taint the `length` bytes of memory at `addr` with the `label`
+    pub fn taint_memory(&mut self, addr: u64, length: usize, label: &str) {
+        let taint = self.get_tainted_identifier(label);
+        let mut values = vec![vc(0); length];
+        self.memory.read(addr, length, &mut values);
+        let mut tainted: Vec<Value> = values.iter().map(|v| v.with_taint(taint)).collect();
+        self.memory.write(addr, &mut tainted);
+    }
+
+    /// Taint the register `reg` with the `label`
+    pub fn taint_register(&mut self, reg: &str, label: &str) {
+        let value = self.registers.get(reg);
+        let tainted = self.taint_value(&value, label);
+        self.registers.set(reg, tainted);
+    }
+
+    /// Start logging the branches and memory writes that depend on
+    /// tainted values, see `taint_events`
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use radius2::radius::Radius;
+    /// use radius2::state::TaintEvent;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// state.memory.write_string(0x100000, "Code_Talkers");
+    /// state.taint_memory(0x100000, 12, "input");
+    /// state.registers.set("rdi", radius2::vc(0x100000));
+    /// state.log_taint();
+    /// let found = radius.run_until(state, 0x004007a1, &[0x00400790]).unwrap();
+    /// let branches = found.taint_events().iter().filter(|e| {
+    ///     matches!(e, TaintEvent::Branch { .. })
+    /// });
+    /// assert!(branches.count() > 0);
+    /// ```
+    pub fn log_taint(&mut self) {
+        if self.taint_log.is_none() {
+            self.taint_log = Some(vec![]);
+        }
+    }
+
+    /// The tainted branches and writes logged since `log_taint`
+    pub fn taint_events(&self) -> &[TaintEvent] {
+        self.taint_log.as_deref().unwrap_or_default()
+    }
+
+    /// Log a conditional on a value with the `taint` at the current pc
+    pub fn log_tainted_branch(&mut self, taint: u64) {
+        if taint == 0 {
+            return;
+        }
+        let pc = self.esil.prev_pc.as_u64().unwrap_or_default();
+        if let Some(log) = self.taint_log.as_mut() {
+            log.push(TaintEvent::Branch { pc, taint });
+        }
+    }
+
+    fn log_tainted_write(&mut self, address: &Value, taint: u64) {
+        if taint == 0 {
+            return;
+        }
+        let pc = self.esil.prev_pc.as_u64().unwrap_or_default();
+        let addr = address.as_u64();
+        if let Some(log) = self.taint_log.as_mut() {
+            log.push(TaintEvent::Write { pc, addr, taint });
+        }
+    }
+
     /// BitVectors will need to be translated if run is multithreaded
     pub fn translate(&self, bv: &BitVec) -> Option<BitVec> {
         self.solver.translate(bv)
@@ -930,6 +2450,31 @@ impl State {
         }
     }
 
+    /// Check if this state is satisfiable before it is reported, giving the
+    /// query of a state forked on a timed out branch query a longer timeout.
+    /// A state that still times out is kept and stays unverified
+    pub fn verify(&mut self) -> bool {
+        if !self.unverified {
+            return self.is_sat();
+        }
+        let timeout = self.solver.timeout;
+        self.solver
+            .set_timeout(timeout.map(|t| t * TIMEOUT_ESCALATION));
+        let result = self.solver.sat_result();
+        self.solver.set_timeout(timeout);
+        match result {
+            SolverResult::Sat => {
+                self.unverified = false;
+                true
+            }
+            SolverResult::Unsat => {
+                self.status = StateStatus::Unsat;
+                false
+            }
+            SolverResult::Unknown => true,
+        }
+    }
+
     /// Increment visit counter
     pub fn visit(&mut self) {
         if let Some(pc) = self.registers.get_pc().as_u64() {
@@ -969,6 +2514,30 @@ impl State {
         self.set_status(StateStatus::Inactive);
     }
 
+    /// Exit the process with the exit `code`
+    pub fn exit(&mut self, code: &Value) {
+        // only the low bits of the exit code are kept
+        self.context
+            .insert("exit_code".to_owned(), vec![code.slice(31, 0)]);
+        self.set_status(StateStatus::Exit);
+    }
+
+    /// Get the exit code of a state that exited with `exit` or by
+    /// returning from main
+    pub fn exit_code(&self) -> Option<Value> {
+        self.context.get("exit_code").map(|c| c[0].to_owned())
+    }
+
+    /// Get the return address of the function the state just entered
+    pub fn return_address(&mut self) -> Value {
+        if Arch::from_info(&self.info.bin).pushes_return() {
+            let sp = self.registers.get_with_alias("SP");
+            self.memory_read_ptr(&sp)
+        } else {
+            self.registers.get_with_alias("LR")
+        }
+    }
+
     /// Convenience method to mark state crashed
     pub fn set_crash(&mut self, addr: u64, perm: char) {
         self.set_status(StateStatus::Crash(addr, perm));
@@ -989,6 +2558,11 @@ impl State {
                 let max = self.solver.max(address);
                 let min_crash = !self.memory.check_permission(min, length, perm);
                 let max_crash = !self.memory.check_permission(max, length, perm);
+                if min_crash || max_crash {
+                    // keep the faulting address to find the input controlling it
+                    self.context
+                        .insert("crash_addr".to_owned(), vec![addr.to_owned()]);
+                }
                 if min_crash {
                     self.set_crash(min, perm);
                 } else if max_crash {
@@ -999,6 +2573,31 @@ impl State {
         }
     }
 
+    /// Check the heap accesses of the state for overflows, use after free, and
+    /// double free, keeping freed allocations from being reused
+    pub fn set_heap_check(&mut self, check: bool) {
+        self.heap_check = check;
+        self.memory.set_quarantine(check);
+    }
+
+    /// Mark the state violated if accessing `len` bytes at `addr` is a heap bug
+    pub fn check_heap(&mut self, addr: &Value, len: &Value, write: bool) -> bool {
+        let length = self.solver.max_value(len);
+        let addrs = match addr {
+            Value::Concrete(address, _t) => vec![*address],
+            Value::Symbolic(address, _t) => {
+                vec![self.solver.min(address), self.solver.max(address)]
+            }
+        };
+        for address in addrs {
+            if let Some(error) = self.memory.heap_error(address, length, write) {
+                self.set_status(StateStatus::Violation(address, error));
+                return true;
+            }
+        }
+        false
+    }
+
     /// convenience method to break
     pub fn set_break(&mut self) {
         self.set_status(StateStatus::Break);
@@ -1008,26 +2607,65 @@ impl State {
     pub fn get_args(&mut self) -> Vec<Value> {
         let pc = self.registers.get_pc().as_u64().unwrap();
         let cc = self.r2api.get_cc(pc).unwrap_or_default();
-        let mut args = Vec::with_capacity(16);
+        // read 8 args from the stack if there are no register args
+        let count = if cc.args.is_empty() { 8 } else { cc.args.len() };
+        self.varargs(&cc, 0).take(count).collect()
+    }
 
-        if !cc.args.is_empty() {
-            for arg in &cc.args {
-                args.push(self.registers.get_with_alias(arg));
-            }
+    /// Get the argument at `index` on entry to a function with the
+    /// calling convention `cc`, args after the register args are read
+    /// from the stack above the return address and shadow space
+    pub fn get_arg(&mut self, cc: &CallingConvention, index: usize) -> Value {
+        if let Some(reg) = cc.args.get(index) {
+            self.registers.get_with_alias(reg)
         } else {
-            // read args from stack?
-            let mut sp = self.registers.get_with_alias("SP");
+            let addr = self.stack_arg_address(cc, index);
             let length = self.memory.bits as usize / 8;
+            self.memory_read_value(&addr, length)
+        }
+    }
 
-            for _ in 0..8 {
-                // do 8 idk?
-                sp = sp + Value::Concrete(length as u64, 0);
-                let value = self.memory_read_value(&sp, length);
-                args.push(value);
-            }
+    /// Set the argument at `index` on entry to a function with the
+    /// calling convention `cc`
+    pub fn set_arg(&mut self, cc: &CallingConvention, index: usize, value: Value) {
+        if let Some(reg) = cc.args.get(index) {
+            self.registers.set_with_alias(reg, value);
+        } else {
+            let addr = self.stack_arg_address(cc, index);
+            let length = self.memory.bits as usize / 8;
+            self.memory_write_value(&addr, &value, length);
         }
+    }
 
-        args
+    // the stack address of an argument that is not in a register
+    fn stack_arg_address(&self, cc: &CallingConvention, index: usize) -> Value {
+        let length = self.memory.bits / 8;
+        let ret = if Arch::from_info(&self.info.bin).pushes_return() {
+            length
+        } else {
+            0
+        };
+        let offset = cc.stack_offset(index, length, ret);
+        self.registers.get_with_alias("SP") + vc(offset)
+    }
+
+    /// Iterate over the arguments of a variadic function starting at
+    /// `start`, following the registers and then the stack per `cc`
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("/bin/sh");
+    /// let mut state = radius.callsym_state("printf");
+    /// let cc = state.r2api.get_cc(0).unwrap_or_default();
+    /// let args: Vec<_> = state.varargs(&cc, 1).take(8).collect();
+    /// ```
+    pub fn varargs(&mut self, cc: &CallingConvention, start: usize) -> VarArgs<'_> {
+        VarArgs {
+            state: self,
+            cc: cc.to_owned(),
+            index: start,
+        }
     }
 
     /// get the return value from the right register
@@ -1038,28 +2676,12 @@ impl State {
     }
 
     /// Set the argument values for the current function
-    pub fn set_args(&mut self, mut values: Vec<Value>) {
+    pub fn set_args(&mut self, values: Vec<Value>) {
         let pc = self.registers.get_pc().as_u64().unwrap();
         let cc = self.r2api.get_cc(pc).unwrap_or_default();
 
-        if !cc.args.is_empty() {
-            for arg in &cc.args {
-                if !values.is_empty() {
-                    self.registers.set_with_alias(arg, values.remove(0));
-                }
-            }
-        } else {
-            // read args from stack?
-            let mut sp = self.registers.get_with_alias("SP");
-            let length = self.memory.bits as usize / 8;
-
-            for _ in 0..8 {
-                // do 8 idk?
-                sp = sp + Value::Concrete(length as u64, 0);
-                if !values.is_empty() {
-                    self.memory_write_value(&sp, &values.remove(0), length);
-                }
-            }
+        for (i, value) in values.into_iter().enumerate() {
+            self.set_arg(&cc, i, value);
         }
     }
 
@@ -1073,6 +2695,30 @@ impl State {
         self.solver.assert(value)
     }
 
+    /// Save the constraints of the state, the conditions assumed with
+    /// `assert` until `pop_constraints` are dropped then
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::radius::Radius;
+    /// let mut radius = Radius::new("../tests/r100");
+    /// let mut state = radius.call_state(0x004006fd);
+    /// let x = state.symbolic_value("x", 64);
+    /// state.push_constraints();
+    /// state.assert(&x.eq(&radius2::vc(1)));
+    /// assert!(!state.check(&x.eq(&radius2::vc(2))));
+    /// state.pop_constraints();
+    /// assert!(state.check(&x.eq(&radius2::vc(2))));
+    /// ```
+    pub fn push_constraints(&mut self) {
+        self.solver.push()
+    }
+
+    /// Drop the constraints asserted since the last `push_constraints`
+    pub fn pop_constraints(&mut self) {
+        self.solver.pop()
+    }
+
     /// Check the satisfiability of the given value
     pub fn check(&mut self, val: &Value) -> bool {
         self.solver.check_sat(val)
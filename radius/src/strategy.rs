@@ -0,0 +1,310 @@
+use crate::state::State;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet, VecDeque};
+use std::mem;
+use std::rc::Rc;
+
+/// The order the processor steps states in, the processor pops a state,
+/// steps it one instruction, and pushes its successors and then itself
+pub trait ExplorationStrategy {
+    /// Add a state to explore
+    fn push(&mut self, state: Rc<State>);
+    /// Take the next state to step
+    fn pop(&mut self) -> Option<Rc<State>>;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Take every state, in an order that pushing them back restores
+    fn drain(&mut self) -> Vec<Rc<State>> {
+        let mut states = vec![];
+        while let Some(state) = self.pop() {
+            states.push(state);
+        }
+        states.reverse();
+        states
+    }
+}
+
+/// Which `ExplorationStrategy` a run uses, set with `RadiusOption::Strategy`
+///
+/// ## Example
+/// ```
+/// use radius2::radius::{Radius, RadiusOption};
+/// use radius2::strategy::Strategy;
+/// let options = [RadiusOption::Strategy(Strategy::Coverage)];
+/// let mut radius = Radius::new_with_options(Some("../tests/r100"), &options);
+/// let mut state = radius.call_state(0x004006fd);
+/// state.symbolize_memory("flag", 0x100000, 12);
+/// state.registers.set("rdi", radius2::vc(0x100000));
+/// let found = radius.run_until(state, 0x004007a1, &[0x00400790]);
+/// assert!(found.is_some());
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Strategy {
+    /// Step the state with the fewest visits to its pc first, the default
+    #[default]
+    Visits,
+    /// Step the newest state until it ends before any other
+    DepthFirst,
+    /// Step states in turn, so all paths advance together
+    BreadthFirst,
+    /// Step a random state, seeded to repeat runs
+    Random(u64),
+    /// Step states at instructions no state has executed yet first
+    Coverage,
+    /// Step the state with the highest score first, scored when pushed
+    Score(StateScore),
+    /// Use the strategy made by the function
+    Custom(fn() -> Box<dyn ExplorationStrategy>),
+}
+
+/// Scores a state for `Strategy::Score`, higher scores are stepped first
+pub type StateScore = fn(&State) -> f64;
+
+impl PartialEq for Strategy {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Strategy::Random(a), Strategy::Random(b)) => a == b,
+            // function addresses are not unique, so custom strategies are never equal
+            (Strategy::Custom(_), _) | (Strategy::Score(_), _) => false,
+            _ => mem::discriminant(self) == mem::discriminant(other),
+        }
+    }
+}
+
+impl Strategy {
+    /// Make the queue of states for a run
+    pub fn queue(&self) -> Box<dyn ExplorationStrategy> {
+        match self {
+            Strategy::Visits => Box::new(BinaryHeap::new()),
+            Strategy::DepthFirst => Box::new(Vec::new()),
+            Strategy::BreadthFirst => Box::new(VecDeque::new()),
+            Strategy::Random(seed) => Box::new(RandomQueue::new(*seed)),
+            Strategy::Coverage => Box::new(CoverageQueue::default()),
+            Strategy::Score(score) => Box::new(ScoreQueue::new(*score)),
+            Strategy::Custom(make) => make(),
+        }
+    }
+
+    /// Parse a strategy name from the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "visits" => Some(Strategy::Visits),
+            "dfs" => Some(Strategy::DepthFirst),
+            "bfs" => Some(Strategy::BreadthFirst),
+            "random" => Some(Strategy::Random(0)),
+            "coverage" => Some(Strategy::Coverage),
+            _ => None,
+        }
+    }
+}
+
+// the states with the lowest number of visits for the current pc first
+impl ExplorationStrategy for BinaryHeap<Rc<State>> {
+    fn push(&mut self, state: Rc<State>) {
+        BinaryHeap::push(self, state);
+    }
+
+    fn pop(&mut self) -> Option<Rc<State>> {
+        BinaryHeap::pop(self)
+    }
+
+    fn len(&self) -> usize {
+        BinaryHeap::len(self)
+    }
+}
+
+impl ExplorationStrategy for Vec<Rc<State>> {
+    fn push(&mut self, state: Rc<State>) {
+        Vec::push(self, state);
+    }
+
+    fn pop(&mut self) -> Option<Rc<State>> {
+        Vec::pop(self)
+    }
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+}
+
+impl ExplorationStrategy for VecDeque<Rc<State>> {
+    fn push(&mut self, state: Rc<State>) {
+        self.push_back(state);
+    }
+
+    fn pop(&mut self) -> Option<Rc<State>> {
+        self.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        VecDeque::len(self)
+    }
+
+    fn drain(&mut self) -> Vec<Rc<State>> {
+        VecDeque::drain(self, ..).collect()
+    }
+}
+
+/// Queue that pops a random state
+pub struct RandomQueue {
+    states: Vec<Rc<State>>,
+    rng: StdRng,
+}
+
+impl RandomQueue {
+    pub fn new(seed: u64) -> Self {
+        RandomQueue {
+            states: vec![],
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl ExplorationStrategy for RandomQueue {
+    fn push(&mut self, state: Rc<State>) {
+        self.states.push(state);
+    }
+
+    fn pop(&mut self) -> Option<Rc<State>> {
+        if self.states.is_empty() {
+            None
+        } else {
+            let index = self.rng.gen_range(0..self.states.len());
+            Some(self.states.swap_remove(index))
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.states.len()
+    }
+}
+
+/// Queue that pops states at uncovered instructions first, and
+/// the others by visits like `Strategy::Visits`
+#[derive(Default)]
+pub struct CoverageQueue {
+    /// states at an instruction uncovered when they were pushed
+    fresh: Vec<Rc<State>>,
+    rest: BinaryHeap<Rc<State>>,
+    covered: HashSet<u64>,
+}
+
+impl ExplorationStrategy for CoverageQueue {
+    fn push(&mut self, state: Rc<State>) {
+        let pc = state.registers.get_pc().as_u64();
+        if pc.map(|pc| self.covered.contains(&pc)) == Some(false) {
+            self.fresh.push(state);
+        } else {
+            self.rest.push(state);
+        }
+    }
+
+    fn pop(&mut self) -> Option<Rc<State>> {
+        // fresh states may have been covered by another since
+        while let Some(state) = self.fresh.pop() {
+            let pc = state.registers.get_pc().as_u64().unwrap_or_default();
+            if self.covered.insert(pc) {
+                return Some(state);
+            }
+            self.rest.push(state);
+        }
+        let state = self.rest.pop()?;
+        if let Some(pc) = state.registers.get_pc().as_u64() {
+            self.covered.insert(pc);
+        }
+        Some(state)
+    }
+
+    fn len(&self) -> usize {
+        self.fresh.len() + self.rest.len()
+    }
+}
+
+struct Scored {
+    score: f64,
+    // states with equal scores are popped newest first
+    order: usize,
+    state: Rc<State>,
+}
+
+impl PartialEq for Scored {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored {}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then(self.order.cmp(&other.order))
+    }
+}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Queue that pops the state with the highest score
+///
+/// ## Example
+/// ```
+/// use radius2::radius::{Radius, RadiusOption};
+/// use radius2::state::State;
+/// use radius2::strategy::Strategy;
+/// // prefer states that have passed more symbolic branches
+/// fn score(state: &State) -> f64 {
+///     state.path.len() as f64
+/// }
+/// let options = [RadiusOption::Strategy(Strategy::Score(score))];
+/// let mut radius = Radius::new_with_options(Some("../tests/r100"), &options);
+/// let mut state = radius.call_state(0x004006fd);
+/// state.symbolize_memory("flag", 0x100000, 12);
+/// state.registers.set("rdi", radius2::vc(0x100000));
+/// let found = radius.run_until(state, 0x004007a1, &[0x00400790]);
+/// assert!(found.is_some());
+/// ```
+pub struct ScoreQueue {
+    states: BinaryHeap<Scored>,
+    score: StateScore,
+    pushed: usize,
+}
+
+impl ScoreQueue {
+    pub fn new(score: StateScore) -> Self {
+        ScoreQueue {
+            states: BinaryHeap::new(),
+            score,
+            pushed: 0,
+        }
+    }
+}
+
+impl ExplorationStrategy for ScoreQueue {
+    fn push(&mut self, state: Rc<State>) {
+        self.pushed += 1;
+        self.states.push(Scored {
+            score: (self.score)(&state),
+            order: self.pushed,
+            state,
+        });
+    }
+
+    fn pop(&mut self) -> Option<Rc<State>> {
+        self.states.pop().map(|s| s.state)
+    }
+
+    fn len(&self) -> usize {
+        self.states.len()
+    }
+}
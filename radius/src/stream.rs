@@ -0,0 +1,75 @@
+use crate::state::State;
+use crate::value::{vc, Value};
+
+/// A standard stream of a state, see `State::stdin` and `State::stdout`
+///
+/// ## Example
+/// ```
+/// use radius2::radius::{Radius, RadiusOption};
+/// let options = [RadiusOption::SymbolicStdin(32)];
+/// let mut radius = Radius::new_with_options(Some("../tests/r100"), &options);
+/// let mut state = radius.entry_state();
+/// state.stdin().constrain_printable();
+/// if let Some(mut end) = radius.run_all(state).pop() {
+///     if end.stdout().expect(b"Nice!") {
+///         println!("{:?}", end.dump_inputs()["stdin"]);
+///     }
+/// }
+/// ```
+pub struct Stream<'a> {
+    state: &'a mut State,
+    fd: usize,
+}
+
+impl<'a> Stream<'a> {
+    pub fn new(state: &'a mut State, fd: usize) -> Self {
+        Stream { state, fd }
+    }
+
+    /// The bytes read from or written to the stream so far
+    pub fn bytes(&self) -> Vec<Value> {
+        self.state
+            .filesystem
+            .files
+            .get(self.fd)
+            .map(|f| f.content.clone())
+            .unwrap_or_default()
+    }
+
+    /// Constrain the bytes of the stream to printable characters,
+    /// including the bytes a symbolic stream grows by later
+    pub fn constrain_printable(&mut self) -> &mut Self {
+        for byte in self.bytes() {
+            self.state.constrain_printable(&byte);
+        }
+        if let Some(stream) = self.state.filesystem.streams.get_mut(&self.fd) {
+            stream.printable = true;
+        }
+        self
+    }
+
+    /// Constrain the stream to contain `expected`, returning false
+    /// and leaving the state as it was if it can't
+    pub fn expect(&mut self, expected: &[u8]) -> bool {
+        let bytes = self.bytes();
+        if expected.is_empty() || bytes.len() < expected.len() {
+            return expected.is_empty();
+        }
+
+        let mut found = vc(0);
+        for window in bytes.windows(expected.len()) {
+            let mut matches = vc(1);
+            for (byte, e) in window.iter().zip(expected) {
+                matches = matches.and(&byte.eq(&vc(*e as u64)));
+            }
+            found = found.or(&matches);
+        }
+
+        if self.state.check(&found) {
+            self.state.assert(&found);
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -375,3 +375,579 @@ fn ioscrackme() {
     println!("FLAG: {}", flag.unwrap());
     radius.r2api.close();
 }
+
+#[test]
+fn symbols() {
+    use crate::radius::Radius;
+
+    let mut radius = Radius::new("../tests/hello");
+    let main = radius.r2api.get_address("main").unwrap();
+    let mut state = radius.call_state(main);
+
+    let first = state.new_symbol("scanf", 32);
+    let value = state.symbolic_value("scanf:1:arg1", 8);
+    state.register_symbol("scanf:1:arg1", &value);
+    state.new_symbol("scanf", 32);
+
+    let names: Vec<&String> = state.symbols().keys().collect();
+    assert_eq!(names, vec!["scanf:0", "scanf:1:arg1", "scanf:2"]);
+    assert_eq!(state.get_symbol("scanf:0"), Some(&first));
+}
+
+#[test]
+fn path_predicate() {
+    use crate::radius::Radius;
+
+    let mut radius = Radius::new("../tests/simple");
+    let mut state = radius.call_state(0x5fa);
+
+    let num = state.symbolic_value("num", 32);
+    state.registers.set("edi", num);
+    let new_state = radius.run_until(state, 0x60b, &[0x612]).unwrap();
+
+    let (predicate, branches) = new_state.path_predicate();
+    assert!(!branches.is_empty());
+    assert!(predicate.is_symbolic());
+}
+
+#[test]
+fn replay() {
+    use crate::radius::{Radius, RadiusOption};
+    use crate::value::vc;
+
+    let path = std::env::temp_dir().join("r100-test.r2rec");
+    let path = path.to_str().unwrap().to_owned();
+    let mut solutions = vec![];
+
+    // the replayed run must not need r2 but find the same flag
+    for option in [
+        RadiusOption::Record(path.clone()),
+        RadiusOption::Replay(path),
+    ] {
+        let mut radius = Radius::new_with_options(Some("../tests/r100"), &[option]);
+        let mut state = radius.call_state(0x004006fd);
+        let addr: u64 = 0x100000;
+        let flag_val = state.symbolic_value("flag", 12 * 8);
+        state.memory.write_value(addr, &flag_val, 12);
+        state.registers.set("rdi", vc(addr));
+
+        let mut new_state = radius.run_until(state, 0x004007a1, &[0x00400790]).unwrap();
+        solutions.push(new_state.evaluate_string(&flag_val));
+        radius.close();
+    }
+    assert_eq!(solutions[0], solutions[1]);
+}
+
+#[test]
+fn float_esil() {
+    use crate::radius::Radius;
+    use crate::value::Value;
+
+    let mut radius = Radius::new("../tests/r100");
+    let mut state = radius.call_state(0x004006fd);
+    state.registers.set("rax", Value::from_f64(1.5, 0));
+    state.registers.set("rbx", Value::from_f64(-4.0, 0));
+
+    let esil = "rbx,rax,F*,rcx,=,rbx,D2I,rdx,=,rax,rbx,F<=,rsi,=,rax,rax,F!=,rdi,=";
+    radius.processor.parse_expression(&mut state, esil);
+    assert_eq!(state.registers.get("rcx").as_f64(), Some(-6.0));
+    assert_eq!(state.registers.get("rdx").as_u64(), Some(-4i64 as u64));
+    assert_eq!(state.registers.get("rsi").as_u64(), Some(1));
+    assert_eq!(state.registers.get("rdi").as_u64(), Some(0));
+}
+
+#[test]
+fn written_code() {
+    use crate::radius::Radius;
+    use crate::value::{vc, Value};
+
+    let mut radius = Radius::new("../tests/r100");
+    let mut state = radius.call_state(0x004006fd);
+
+    // mov eax, 0x1337 over the prologue, 0x00400702 is not an original instruction
+    let mut code: Vec<Value> = [0xb8, 0x37, 0x13, 0, 0].iter().map(|b| vc(*b)).collect();
+    state.memory.write(0x004006fd, &mut code);
+    assert!(state.memory.code_written(0x004006fd, 1));
+
+    let new_state = radius.run_until(state, 0x00400702, &[]).unwrap();
+    assert_eq!(new_state.registers.get("eax").as_u64(), Some(0x1337));
+}
+
+#[test]
+fn symbolic_strcmp() {
+    use crate::radius::Radius;
+    use crate::sims::libc;
+    use crate::value::vc;
+
+    let mut radius = Radius::new("../tests/r100");
+    let mut state = radius.call_state(0x004006fd);
+    let flag = state.symbolic_value("flag", 64 * 8);
+    state.memory_write_value(&vc(0x100000), &flag, 64);
+    state.memory_write_string(0x200000, "Code_Talkers");
+
+    // strcmp of a symbolic string doesn't fork, it is one value
+    let cmp = libc::strcmp(&mut state, &[vc(0x100000), vc(0x200000)]);
+    state.assert(&cmp.eq(&vc(0)));
+    let len = libc::strlen(&mut state, &[vc(0x100000)]);
+    assert_eq!(state.eval(&len).and_then(|l| l.as_u64()), Some(12));
+    assert_eq!(state.evaluate_string(&flag).unwrap(), "Code_Talkers");
+}
+
+#[test]
+fn trace_events() {
+    use crate::bus::{EventLevel, RunEvent};
+    use crate::radius::Radius;
+    use crate::value::vc;
+
+    let mut radius = Radius::new("../tests/r100");
+    let events = radius.subscribe_level(EventLevel::Trace);
+    let mut state = radius.call_state(0x004006fd);
+    state.symbolize_memory("flag", 0x100000, 12);
+    state.registers.set("rdi", vc(0x100000));
+    radius.run_until(state, 0x004007a1, &[0x00400790]);
+
+    let events: Vec<RunEvent> = events.try_iter().collect();
+    assert!(matches!(events[0], RunEvent::Executed { pc: 0x004006fd }));
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, RunEvent::SolverQueries { .. })));
+    assert_eq!(events[0].to_json()["event"], "executed");
+}
+
+#[test]
+fn protected_memory() {
+    use crate::radius::{Radius, RadiusOption};
+    use crate::sims::syscall;
+    use crate::state::StateStatus;
+    use crate::value::vc;
+
+    let options = [RadiusOption::Permissions(true)];
+    let mut radius = Radius::new_with_options(Some("../tests/r100"), &options);
+    let mut state = radius.call_state(0x004006fd);
+
+    // PROT_READ | PROT_WRITE, then PROT_READ
+    let addr = syscall::mmap(&mut state, &[vc(0), vc(0x1000), vc(3)]);
+    let addr = addr.as_u64().unwrap();
+    state.memory_write_value(&vc(addr), &vc(0x41), 1);
+    assert_eq!(state.status, StateStatus::Active);
+
+    syscall::mprotect(&mut state, &[vc(addr), vc(0x1000), vc(1)]);
+    assert_eq!(state.memory_read_value(&vc(addr), 1).as_u64(), Some(0x41));
+    state.memory_write_value(&vc(addr), &vc(0x42), 1);
+    assert_eq!(state.status, StateStatus::Crash(addr, 'w'));
+}
+
+#[test]
+fn socket_model() {
+    use crate::radius::Radius;
+    use crate::sims::net;
+    use crate::value::{byte_values, vc};
+
+    let mut radius = Radius::new("../tests/r100");
+    let mut state = radius.call_state(0x004006fd);
+    let response = byte_values("OK");
+    state
+        .filesystem
+        .add_file(&net::socket_path("10.0.0.1:80"), &response);
+
+    // AF_INET, SOCK_STREAM and a sockaddr_in for 10.0.0.1:80
+    let fd = net::socket(&mut state, &[vc(2), vc(1), vc(0)]);
+    let addr = vc(0x100000);
+    let sockaddr = [2, 0, 0, 80, 10, 0, 0, 1];
+    let sockaddr: Vec<_> = sockaddr.iter().map(|b| vc(*b)).collect();
+    state.memory_write(&addr, &sockaddr, &vc(8));
+    assert_eq!(
+        net::connect(&mut state, &[fd.clone(), addr, vc(16)]).as_u64(),
+        Some(0)
+    );
+
+    let buf = vc(0x100100);
+    let len = net::recv(&mut state, &[fd.clone(), buf.clone(), vc(16), vc(0)]);
+    assert_eq!(len.as_u64(), Some(2));
+    assert_eq!(state.memory_read_string(0x100100, 2), "OK");
+
+    state.memory_write_string(0x100200, "GET");
+    net::send(&mut state, &[fd, vc(0x100200), vc(3), vc(0)]);
+    let sent = net::sent(&state, "10.0.0.1:80");
+    assert_eq!(state.evaluate_string(&state.pack(&sent)).unwrap(), "GET");
+
+    // a second socket to an address without data receives symbolic bytes
+    let fd = net::socket(&mut state, &[vc(2), vc(1), vc(0)]);
+    state.memory_write_value(&vc(0x100007), &vc(2), 1);
+    net::connect(&mut state, &[fd.clone(), vc(0x100000), vc(16)]);
+    net::recv(&mut state, &[fd, buf.clone(), vc(4), vc(0)]);
+    assert!(state.memory_read_value(&buf, 4).is_symbolic());
+}
+
+#[test]
+fn state_manager() {
+    use crate::manager::{StateManager, ACTIVE, AVOIDED, FOUND, PRUNED};
+    use crate::radius::Radius;
+    use crate::value::vc;
+
+    let mut radius = Radius::new("../tests/r100");
+    let mut state = radius.call_state(0x004006fd);
+    state.symbolize_memory("flag", 0x100000, 12);
+    state.registers.set("rdi", vc(0x100000));
+
+    let mut manager = StateManager::new(state);
+    assert_eq!(
+        radius.explore(&mut manager, 0x004007a1, &[0x00400790], 1),
+        1
+    );
+    assert_eq!(manager.len(FOUND), 1);
+    assert!(manager.len(AVOIDED) > 0);
+
+    let mut found = manager.found()[0].clone();
+    let flag = found.memory_read_value(&vc(0x100000), 12);
+    assert_eq!(found.evaluate_string(&flag).unwrap(), "Code_Talkers");
+
+    let active = manager.len(ACTIVE);
+    assert_eq!(manager.move_states(ACTIVE, "later", |_| true), active);
+    assert!(manager.is_empty(ACTIVE));
+    assert_eq!(manager.prune(FOUND), 0);
+    assert!(manager.is_empty(PRUNED));
+}
+
+#[test]
+fn pretty_expression() {
+    use crate::pretty;
+
+    let smt = "(declare-fun flag () (_ BitVec 16))
+        (define-fun _3 () (_ BitVec 16) (concat ((_ extract 15 8) flag) ((_ extract 7 0) flag)))
+        (define-fun _5 () (_ BitVec 1) (ite (= (bvadd _3 #x0001) #x0041) #b1 #b0))";
+    assert_eq!(pretty::smt(smt), "(flag + 0x1) == 0x41");
+
+    let smt = "(declare-fun x () (_ BitVec 32))
+        (assert (bvult ((_ zero_extend 24) ((_ extract 7 0) x)) (_ bv10 32)))";
+    assert_eq!(pretty::smt(smt), "uext(x[7:0], 24) < 0xa");
+}
+
+#[test]
+fn interact() {
+    use crate::radius::Radius;
+    use crate::repl;
+    use crate::value::vc;
+
+    let mut radius = Radius::new("../tests/r100");
+    let mut state = radius.call_state(0x004006fd);
+    state.memory_write_string(0x100000, "flag");
+    state.registers.set("rdi", vc(0x100000));
+
+    let input = "x rdi 4\nx rdi 0x100000\ns 2\nstates\nq\n".as_bytes();
+    let mut output = vec![];
+    let state = repl::interact(&mut radius.processor, state, input, &mut output);
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("0x00100000  66 6c 61 67"));
+    assert!(output.contains("showing the first 0x1000 bytes"));
+    assert!(output.contains("*0 0x"));
+    assert_ne!(state.registers.get_pc().as_u64(), Some(0x004006fd));
+}
+
+#[test]
+fn websocket_handshake() {
+    use crate::server::{base64, sha1, websocket_accept};
+
+    // the example from RFC 6455
+    assert_eq!(
+        websocket_accept("dGhlIHNhbXBsZSBub25jZQ=="),
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+
+    // the vectors from RFC 3174 and FIPS 180, across block boundaries
+    let hex = |data: &[u8]| -> String { sha1(data).iter().map(|b| format!("{:02x}", b)).collect() };
+    assert_eq!(hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    assert_eq!(hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    assert_eq!(
+        hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+        "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+    );
+    assert_eq!(
+        hex(&[b'a'; 1_000_000]),
+        "34aa973cd4c4daa4f61eeb2bdbad27316534016f"
+    );
+
+    // the vectors from RFC 4648
+    let encoded: Vec<String> = ["", "f", "fo", "foo", "foob", "fooba", "foobar"]
+        .iter()
+        .map(|s| base64(s.as_bytes()))
+        .collect();
+    assert_eq!(
+        encoded,
+        ["", "Zg==", "Zm8=", "Zm9v", "Zm9vYg==", "Zm9vYmE=", "Zm9vYmFy"]
+    );
+}
+
+#[test]
+fn recording_forks() {
+    use crate::trace::Recording;
+
+    let mut recording = Recording::new();
+    for pc in 0..3 {
+        recording.begin(pc);
+    }
+    let mut fork = recording.clone();
+    recording.begin(3);
+    fork.position = 1;
+    fork.begin(10);
+
+    let pcs = |r: &Recording| -> Vec<u64> { (0..r.len()).map(|i| r.get(i).unwrap().pc).collect() };
+    assert_eq!(pcs(&recording), [0, 1, 2, 3]);
+    assert_eq!(pcs(&fork), [0, 10]);
+    assert_eq!(recording.visits(2), [2]);
+}
+
+#[test]
+fn query_log_keeps_slowest() {
+    use crate::solver::{QueryLog, SolverQuery};
+    use std::time::Duration;
+
+    let mut log = QueryLog::new(2);
+    for (addr, micros) in [(0x10, 5), (0x20, 30), (0x10, 20), (0x30, 1)] {
+        let time = Duration::from_micros(micros);
+        let smt = log.is_slow(time).then(String::new);
+        log.add(SolverQuery {
+            addr,
+            assertions: 0,
+            assumptions: 0,
+            sat: true,
+            time,
+            smt,
+        });
+    }
+
+    assert_eq!(log.count, 4);
+    let times: Vec<u128> = log.queries.iter().map(|q| q.time.as_micros()).collect();
+    assert_eq!(times, [30, 20]);
+    assert_eq!(log.by_address()[0], (0x20, Duration::from_micros(30), 1));
+    assert_eq!(log.addresses[&0x10], (Duration::from_micros(25), 2));
+}
+
+#[test]
+fn interval_arithmetic() {
+    use crate::interval::Interval;
+
+    // joins keep the stride both sides are on
+    let a = Interval::new(0x10, 0x30, 0x10, 32);
+    let b = Interval::new(0x18, 0x38, 0x10, 32);
+    assert_eq!(a.join(&b), Interval::new(0x10, 0x38, 8, 32));
+    assert_eq!(a.join(&a), a);
+
+    // results that can wrap around are every value
+    let one = Interval::constant(1, 8);
+    assert_eq!(
+        Interval::new(0, 0x10, 1, 8).add(&one),
+        Interval::new(1, 0x11, 1, 8)
+    );
+    assert!(Interval::top(8).add(&one).is_top());
+    assert!(Interval::new(0, 0x10, 1, 8).sub(&one).is_top());
+    assert!(Interval::new(0, 0x80, 1, 8)
+        .mul(&Interval::constant(2, 8))
+        .is_top());
+
+    // strides carry through arithmetic with constants
+    let four = Interval::constant(4, 32);
+    assert_eq!(
+        Interval::new(4, 0x10, 4, 32).sub(&four),
+        Interval::new(0, 0xc, 4, 32)
+    );
+    assert_eq!(
+        Interval::new(0, 0x7f, 1, 8).mul(&Interval::constant(2, 8)),
+        Interval::new(0, 0xfe, 2, 8)
+    );
+    assert_eq!(
+        Interval::new(0, 3, 1, 32).shl(&Interval::constant(2, 64)),
+        Interval::new(0, 0xc, 4, 32)
+    );
+    let index = Interval::new(0x1000, 0x10fc, 4, 64).add(&Interval::constant(2, 64));
+    assert_eq!(index, Interval::new(0x1002, 0x10fe, 4, 64));
+    assert!(index.contains(0x1006));
+    assert!(!index.contains(0x1004));
+
+    // comparisons are decided when the intervals don't overlap or line up
+    let index = Interval::new(0x1000, 0x10fc, 4, 64);
+    let eq = |c| index.eq(&Interval::constant(c, 64)).as_constant();
+    assert_eq!(eq(0x1001), Some(0));
+    assert_eq!(eq(0x2000), Some(0));
+    assert_eq!(eq(0x1004), None);
+
+    let low = Interval::new(0, 9, 1, 32);
+    let high = Interval::new(10, 20, 1, 32);
+    assert_eq!(low.ult(&high).as_constant(), Some(1));
+    assert_eq!(high.ult(&low).as_constant(), Some(0));
+    assert_eq!(low.ult(&Interval::new(5, 20, 1, 32)).as_constant(), None);
+
+    let negative = Interval::new(0x80, 0xff, 1, 8);
+    let positive = Interval::new(0, 0x7f, 1, 8);
+    assert_eq!(negative.slt(&positive).as_constant(), Some(1));
+    assert_eq!(positive.slt(&negative).as_constant(), Some(0));
+    assert_eq!(Interval::top(8).slt(&positive).as_constant(), None);
+}
+
+#[test]
+fn interval_skips_unsat() {
+    use crate::solver::Solver;
+    use crate::value::{vc, Value};
+
+    let mut solver = Solver::new(256);
+    let byte = Value::Symbolic(solver.bv("byte", 8), 0).uext(&vc(24));
+    let index = byte.and(&vc(0xfc)).add(&vc(0x1000));
+
+    // an unaligned index can't be reached, so no query is made
+    assert!(!solver.check_sat(&index.eq(&vc(0x1001))));
+    assert_eq!(solver.stats.get().skipped, 1);
+    assert!(solver.check_sat(&index.eq(&vc(0x1004))));
+    assert_eq!(solver.stats.get().skipped, 1);
+}
+
+#[test]
+fn hook_with_sim() {
+    use crate::processor::HookAction;
+    use crate::radius::Radius;
+    use crate::value::vc;
+
+    let mut radius = Radius::new("../tests/strstuff");
+    let main = radius.r2api.get_address("main").unwrap();
+    let strcmp = radius.r2api.get_address("sym.imp.strcmp").unwrap();
+    let mut state = radius.call_state(main);
+    let addr = 0x100000;
+    state.memory.write_value(addr + 8, &vc(addr + 24), 8);
+    state.memory_write_string(addr + 24, "p4s5w0rd");
+    state.registers.set("rsi", vc(addr));
+
+    // a hook that skips the instruction still lets the strcmp sim run
+    radius.hook(strcmp, |_| false);
+    assert!(radius.run_until(state.clone(), 0x1208, &[0x120f]).is_some());
+
+    // a hook that replaces the sim returns its own result
+    fn mismatch(state: &mut crate::state::State) -> HookAction {
+        state.registers.set("rax", vc(1));
+        HookAction::Replace
+    }
+    radius.hook_chained(strcmp, 0, mismatch);
+    assert!(radius.run_until(state, 0x120f, &[0x1208]).is_some());
+}
+
+#[test]
+fn mips_delay_slots() {
+    use crate::radius::{Radius, RadiusOption};
+
+    let options = [
+        RadiusOption::R2Argument("-a"),
+        RadiusOption::R2Argument("mips"),
+        RadiusOption::R2Argument("-b"),
+        RadiusOption::R2Argument("32"),
+        RadiusOption::R2Argument("-e"),
+        RadiusOption::R2Argument("cfg.bigendian=false"),
+    ];
+    let mut radius = Radius::new_with_options(Some("malloc://0x1000"), &options);
+    let code: [u32; 8] = [
+        0x24020001, // addiu v0, zero, 1
+        0x50020003, // beql zero, v0, 0x14 (not taken, the slot is annulled)
+        0x24420010, // addiu v0, v0, 0x10
+        0x10000002, // b 0x18 (the slot runs)
+        0x24030002, // addiu v1, zero, 2
+        0, 0, 0,
+    ];
+    let bytes = code.iter().flat_map(|w| w.to_le_bytes().to_vec()).collect();
+    radius.r2api.write(0, bytes);
+
+    let state = radius.call_state(0);
+    let state = radius.run_until(state, 0x18, &[0x14]).unwrap();
+    assert_eq!(state.registers.get("v0").as_u64(), Some(1));
+    assert_eq!(state.registers.get("v1").as_u64(), Some(2));
+}
+
+#[test]
+fn syscall_exit() {
+    use crate::radius::Radius;
+    use crate::sims::syscall::syscall;
+    use crate::state::StateStatus;
+    use crate::value::vc;
+
+    let mut radius = Radius::new("../tests/r100");
+    let mut state = radius.call_state(0x004006fd);
+
+    // dup and F_DUPFD give a new fd whose writes go to the old one
+    let fd = syscall("dup", &mut state, &[vc(1)]).as_u64().unwrap();
+    assert!(fd > 2);
+    let fd2 = syscall("fcntl", &mut state, &[vc(fd), vc(0), vc(0)]);
+    assert!(fd2.as_u64().unwrap() > fd);
+    state.filesystem.write(fd as usize, vec![vc(b'a' as u64)]);
+    assert_eq!(state.filesystem.dump(1), vec![vc(b'a' as u64)]);
+
+    // exit stops the state with the exit status, not just inactive
+    syscall("exit", &mut state, &[vc(3)]);
+    assert_eq!(state.status, StateStatus::Exit);
+    assert_eq!(state.exit_code().and_then(|c| c.as_u64()), Some(3));
+}
+
+#[test]
+fn heap_errors() {
+    use crate::memory::HeapError;
+    use crate::radius::Radius;
+    use crate::value::vc;
+
+    let mut radius = Radius::new("../tests/r100");
+    let mut state = radius.call_state(0x004006fd);
+    let memory = &mut state.memory;
+
+    let a = memory.alloc(&vc(0x20));
+    let b = memory.alloc(&vc(0x20));
+    assert_eq!(memory.heap_error(a + 0x18, 8, true), None);
+    assert_eq!(
+        memory.heap_error(a + 0x1c, 8, true),
+        Some(HeapError::Overflow)
+    );
+
+    // freed memory is a use after free even without quarantine
+    memory.free(&vc(a));
+    assert_eq!(
+        memory.heap_error(a, 8, false),
+        Some(HeapError::UseAfterFree)
+    );
+
+    // past the bytes right after the last chunk is not an overflow
+    let past = b + 0x20 + 0x10;
+    assert_eq!(memory.heap_error(past, 8, true), Some(HeapError::Overflow));
+    assert_eq!(memory.heap_error(past + 0x100, 8, true), None);
+
+    // until the freed memory is reused
+    memory.free(&vc(b));
+    let c = memory.alloc(&vc(0x20));
+    assert_eq!(c, a);
+    assert_eq!(memory.heap_error(c, 8, true), None);
+    assert_eq!(memory.heap_error(b, 8, true), Some(HeapError::UseAfterFree));
+}
+
+#[test]
+fn big_endian_memory() {
+    use crate::r2_api::Endian;
+    use crate::radius::Radius;
+    use crate::value::vc;
+
+    let mut radius = Radius::new("../tests/r100");
+    let mut state = radius.call_state(0x004006fd);
+    let memory = &mut state.memory;
+    memory.endian = Endian::Big;
+
+    // the most significant byte goes first and reads back the same
+    let addr = 0x100000;
+    memory.write_value(addr, &vc(0x11223344), 4);
+    let bytes: Vec<Option<u64>> = (0..4)
+        .map(|i| memory.read_value(addr + i, 1).as_u64())
+        .collect();
+    assert_eq!(bytes, vec![Some(0x11), Some(0x22), Some(0x33), Some(0x44)]);
+    assert_eq!(memory.read_value(addr, 4).as_u64(), Some(0x11223344));
+
+    let mut data = vec![vc(0); 2];
+    memory.unpack_endian(&vc(0xaabb), 2, &mut data, &Endian::Little);
+    assert_eq!(
+        memory.pack_endian(&data, &Endian::Little).as_u64(),
+        Some(0xaabb)
+    );
+    assert_eq!(
+        memory.pack_endian(&data, &Endian::Big).as_u64(),
+        Some(0xbbaa)
+    );
+}
@@ -0,0 +1,176 @@
+use crate::state::State;
+use crate::value::{vc, Value};
+
+/// The layout of a message of length-prefixed records, each a tag, the
+/// length of its value, and that many bytes of value
+///
+/// ## Example
+/// ```
+/// use radius2::radius::Radius;
+/// use radius2::tlv::TlvLayout;
+/// let mut radius = Radius::new("../tests/r100");
+/// let mut state = radius.call_state(0x004006fd);
+/// let layout = TlvLayout::new(3, 1, 2).max_value(16).tags(&[1, 2, 7]);
+/// let message = layout.symbolize(&mut state, "packet");
+/// message.write(&mut state, 0x100000);
+/// state.registers.set("rdi", radius2::vc(0x100000));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlvLayout {
+    /// Number of records in the message
+    pub records: usize,
+    /// Bytes of the tag of each record, can be 0
+    pub tag_size: usize,
+    /// Bytes of the length of each record
+    pub length_size: usize,
+    /// Tags and lengths are big endian, like in most network protocols
+    pub big_endian: bool,
+    /// Largest length of a value
+    pub max_value: u64,
+    /// The tags records may have, any tag if empty
+    pub tags: Vec<u64>,
+    /// Bytes of a prefix holding the length of the records, 0 for none
+    pub prefix_size: usize,
+}
+
+/// A symbolic message of a `TlvLayout`, whose length fields are
+/// constrained to the lengths of the values they prefix
+#[derive(Debug, Clone)]
+pub struct TlvMessage {
+    /// Every byte the message can span, the bytes of the input
+    pub bytes: Vec<Value>,
+    /// Offset of each record in the message
+    pub offsets: Vec<Value>,
+    /// Length of the value of each record
+    pub lengths: Vec<Value>,
+    /// Length of the whole message
+    pub length: Value,
+}
+
+impl TlvLayout {
+    pub fn new(records: usize, tag_size: usize, length_size: usize) -> Self {
+        TlvLayout {
+            records,
+            tag_size,
+            length_size,
+            big_endian: true,
+            max_value: 64,
+            tags: vec![],
+            prefix_size: 0,
+        }
+    }
+
+    /// Set the largest length of a value
+    pub fn max_value(mut self, max: u64) -> Self {
+        self.max_value = max;
+        self
+    }
+
+    /// Only allow records with these tags
+    pub fn tags(mut self, tags: &[u64]) -> Self {
+        self.tags = tags.to_vec();
+        self
+    }
+
+    /// Store tags and lengths little endian
+    pub fn little_endian(mut self) -> Self {
+        self.big_endian = false;
+        self
+    }
+
+    /// Start the message with a `size` byte length of the records
+    pub fn prefixed(mut self, size: usize) -> Self {
+        self.prefix_size = size;
+        self
+    }
+
+    fn header_size(&self) -> usize {
+        self.tag_size + self.length_size
+    }
+
+    /// The most bytes a message can span
+    pub fn size(&self) -> usize {
+        self.prefix_size + self.records * (self.header_size() + self.max_value as usize)
+    }
+
+    /// Make a message of the input `name`. The length of each record is a
+    /// new symbol, and every place the record can start at constrains the
+    /// bytes there to its tag and length if the record starts there
+    pub fn symbolize(&self, state: &mut State, name: &str) -> TlvMessage {
+        let bytes = state.input_bytes(name, 0, self.size());
+        let header = self.header_size() as u64;
+
+        let mut offsets = vec![];
+        let mut lengths = vec![];
+        let mut offset = vc(self.prefix_size as u64);
+        for i in 0..self.records {
+            let length = state.new_symbol(&format!("{}_length", name), 64);
+            state.assert(&length.ulte(&vc(self.max_value)));
+            // lengths that do not fit in the field can't be encoded
+            if self.length_size < 8 {
+                state.assert(&length.ult(&vc(1 << (8 * self.length_size))));
+            }
+
+            let first = self.prefix_size as u64 + i as u64 * header;
+            let last = first + i as u64 * self.max_value;
+            for start in first..=last {
+                let at = offset.eq(&vc(start)).eq(&vc(0));
+                if at.as_u64() == Some(1) {
+                    continue;
+                }
+                let start = start as usize;
+                let tag = &bytes[start..start + self.tag_size];
+                let field = &bytes[start + self.tag_size..start + header as usize];
+
+                let mut encoded = self.encodes(field, &length);
+                if !self.tags.is_empty() {
+                    let mut tagged = vc(0);
+                    for t in &self.tags {
+                        tagged = tagged.or(&self.encodes(tag, &vc(*t)));
+                    }
+                    encoded = encoded.and(&tagged);
+                }
+                state.assert(&at.or(&encoded));
+            }
+
+            offsets.push(offset.to_owned());
+            offset = offset + vc(header) + length.to_owned();
+            lengths.push(length);
+        }
+
+        if self.prefix_size > 0 {
+            let records = offset.to_owned() - vc(self.prefix_size as u64);
+            let prefix = self.encodes(&bytes[..self.prefix_size], &records);
+            state.assert(&prefix);
+        }
+
+        TlvMessage {
+            bytes,
+            offsets,
+            lengths,
+            length: offset,
+        }
+    }
+
+    // the condition that the bytes of a field hold the value
+    fn encodes(&self, field: &[Value], value: &Value) -> Value {
+        let mut encoded = vc(1);
+        for (i, byte) in field.iter().enumerate() {
+            let shift = if self.big_endian {
+                field.len() - 1 - i
+            } else {
+                i
+            };
+            let part = (value.to_owned() >> vc(8 * shift as u64)) & vc(0xff);
+            encoded = encoded.and(&byte.eq(&part));
+        }
+        encoded
+    }
+}
+
+impl TlvMessage {
+    /// Write the bytes of the message to memory at `addr`
+    pub fn write(&self, state: &mut State, addr: u64) {
+        state.memory.write(addr, &mut self.bytes.clone());
+    }
+}
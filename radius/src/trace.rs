@@ -0,0 +1,415 @@
+use crate::r2_api::{R2Api, R2Result};
+use crate::solver::Solver;
+use crate::value::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::mem;
+use std::rc::Rc;
+
+/// An execution trace recorded outside of radius, stored as the
+/// sequence of absolute addresses (usually block starts) that were hit
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub addrs: Vec<u64>,
+}
+
+impl Trace {
+    pub fn new(addrs: Vec<u64>) -> Self {
+        Trace { addrs }
+    }
+
+    /// Parse a Frida stalker trace in `module+offset` format, one entry
+    /// per line (`module!offset` is also accepted). Module names are
+    /// resolved to the base addresses of the files opened in r2
+    pub fn from_stalker(r2api: &mut R2Api, data: &str) -> R2Result<Self> {
+        let files = r2api.get_files()?;
+        let mut bases: HashMap<String, u64> = HashMap::new();
+        let mut addrs = Vec::with_capacity(data.len() / 16);
+
+        for line in data.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let sep = line.rfind(['+', '!']);
+            let (module, offset) = if let Some(i) = sep {
+                (&line[..i], &line[i + 1..])
+            } else {
+                return Err(format!("bad stalker entry: {}", line));
+            };
+
+            let offset = parse_number(offset)?;
+            let base = if let Some(base) = bases.get(module) {
+                *base
+            } else {
+                // match on the file name of the module
+                let base = files
+                    .iter()
+                    .find(|f| f.uri.rsplit('/').next() == Some(module))
+                    .map(|f| f.from)
+                    .ok_or(format!("module {} not loaded", module))?;
+
+                bases.insert(module.to_owned(), base);
+                base
+            };
+
+            addrs.push(base.wrapping_add(offset));
+        }
+
+        Ok(Trace { addrs })
+    }
+
+    /// Parse a QEMU execution log, either the output of `-d exec` with lines
+    /// like `Trace 0: 0x7f.. [00000000/0000000000401000/...]` or the execlog
+    /// plugin output with lines like `0, 0x401000, 0x55, "push rbp"`
+    pub fn from_qemu(data: &str) -> R2Result<Self> {
+        let mut addrs = Vec::with_capacity(data.len() / 32);
+
+        for line in data.lines() {
+            let line = line.trim();
+            let field = if let Some(start) = line.find('[') {
+                line[start + 1..].split('/').nth(1)
+            } else if line.contains(',') {
+                line.split(',').nth(1)
+            } else {
+                None
+            };
+
+            // skip lines that are not trace entries
+            if let Some(field) = field {
+                let field = field.trim();
+                let hex = field.strip_prefix("0x").unwrap_or(field);
+                if let Ok(addr) = u64::from_str_radix(hex, 16) {
+                    addrs.push(addr);
+                }
+            }
+        }
+
+        Ok(Trace { addrs })
+    }
+
+    /// Parse decoded branch records such as those from Intel PT with
+    /// `perf script --itrace=b`, lines containing `from => to`. The targets
+    /// of the branches become the trace addresses
+    pub fn from_branches(data: &str) -> R2Result<Self> {
+        let mut addrs = Vec::with_capacity(data.len() / 32);
+
+        for line in data.lines() {
+            if let Some(i) = line.find("=>") {
+                let target = line[i + 2..]
+                    .split_whitespace()
+                    .next()
+                    .ok_or(format!("bad branch entry: {}", line))?;
+
+                let hex = target.strip_prefix("0x").unwrap_or(target);
+                let addr = u64::from_str_radix(hex, 16)
+                    .map_err(|_| format!("bad branch entry: {}", line))?;
+
+                // perf uses 0 for branches into untraced code
+                if addr != 0 {
+                    addrs.push(addr);
+                }
+            }
+        }
+
+        Ok(Trace { addrs })
+    }
+
+    /// Parse a list of addresses, one per line in hex with or without 0x
+    pub fn from_pcs(data: &str) -> R2Result<Self> {
+        let mut addrs = Vec::with_capacity(data.len() / 8);
+
+        for line in data.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let hex = line.strip_prefix("0x").unwrap_or(line);
+            let addr =
+                u64::from_str_radix(hex, 16).map_err(|_| format!("bad trace entry: {}", line))?;
+            addrs.push(addr);
+        }
+
+        Ok(Trace { addrs })
+    }
+
+    /// Read a trace from a file, detecting whether it holds decoded
+    /// branches, a QEMU log, or a list of addresses. Stalker traces
+    /// need the loaded modules and are read with `from_stalker`
+    ///
+    /// ## Example
+    /// ```
+    /// use radius2::trace::Trace;
+    /// let path = std::env::temp_dir().join("r100.trace");
+    /// std::fs::write(&path, "Trace 0: 0x7f00 [00000000/00000000004006fd/0x0]\n").unwrap();
+    /// let trace = Trace::from_file(path.to_str().unwrap()).unwrap();
+    /// assert_eq!(trace.addrs, vec![0x004006fd]);
+    /// ```
+    pub fn from_file(path: &str) -> R2Result<Self> {
+        let data =
+            fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+
+        let entry = data
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#'))
+            .unwrap_or_default();
+
+        if data.contains("=>") {
+            Trace::from_branches(&data)
+        } else if data.contains("Trace ") || entry.contains(',') {
+            Trace::from_qemu(&data)
+        } else {
+            Trace::from_pcs(&data)
+        }
+    }
+
+    /// The set of unique addresses that the trace covers
+    pub fn coverage(&self) -> HashSet<u64> {
+        self.addrs.iter().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.addrs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addrs.is_empty()
+    }
+}
+
+/// A branch reached during trace replay whose direction depended on
+/// symbolic input, `taken` is the successor the trace followed
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceBranch {
+    pub addr: u64,
+    pub taken: u64,
+    pub alternatives: Vec<u64>,
+    pub index: usize,
+}
+
+/// Bytes written to memory by an instruction, before and after the write.
+/// Old bytes are None if they were not made yet, like unread memory of
+/// blank states
+#[derive(Debug, Clone)]
+pub struct MemoryWrite {
+    pub addr: u64,
+    pub old: Vec<Option<Value>>,
+    pub new: Vec<Value>,
+}
+
+/// The register and memory changes made by one executed instruction,
+/// registers are identified by their index in `Registers.values`
+#[derive(Debug, Clone, Default)]
+pub struct TraceStep {
+    pub pc: u64,
+    pub registers: Vec<(usize, Value, Value)>,
+    pub writes: Vec<MemoryWrite>,
+}
+
+/// Steps recorded before a fork, shared by the recordings of the states
+/// that forked. `len` of the `steps` are used, the first is step `start`
+#[derive(Debug)]
+struct Segment {
+    steps: Rc<Vec<TraceStep>>,
+    len: usize,
+    start: usize,
+    prefix: Option<Rc<Segment>>,
+}
+
+/// A per-instruction recording of the execution of a state which can be
+/// stepped through backwards and forwards with `State::step_back` and
+/// `State::step_forward`. `position` is the number of steps applied.
+/// Cloning shares the steps recorded so far, their values belong to the
+/// solver of the state that recorded them and are translated when read
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    prefix: Option<Rc<Segment>>,
+    start: usize,
+    steps: Rc<Vec<TraceStep>>,
+    pub position: usize,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Recording::default()
+    }
+
+    /// Start recording a new step, discarding any undone steps
+    pub fn begin(&mut self, pc: u64) {
+        self.truncate(self.position);
+        self.own().push(TraceStep {
+            pc,
+            ..TraceStep::default()
+        });
+        self.position = self.len();
+    }
+
+    /// The step at `index` as it was recorded, its values may belong to
+    /// the solver of another state, see `step`
+    pub fn get(&self, index: usize) -> Option<&TraceStep> {
+        if index >= self.start {
+            return self.steps.get(index - self.start);
+        }
+        let mut segment = self.prefix.as_ref();
+        while let Some(s) = segment {
+            if index >= s.start {
+                return s.steps[..s.len].get(index - s.start);
+            }
+            segment = s.prefix.as_ref();
+        }
+        None
+    }
+
+    /// The step at `index` with its values translated to `solver`
+    pub fn step(&self, index: usize, solver: &Solver) -> Option<TraceStep> {
+        self.get(index).map(|s| translate_step(s, solver))
+    }
+
+    /// The last step to add changes to, translated to `solver` and
+    /// copied out of the shared steps if it was recorded before a fork
+    pub fn last_mut(&mut self, solver: &Solver) -> Option<&mut TraceStep> {
+        if self.steps.is_empty() {
+            let last = self.step(self.len().checked_sub(1)?, solver)?;
+            self.truncate(self.len() - 1);
+            self.own().push(last);
+        }
+        self.own().last_mut()
+    }
+
+    /// The index and step of the last write to `addr` before the current position
+    pub fn last_write(&self, addr: u64) -> Option<(usize, &TraceStep)> {
+        (0..self.position)
+            .rev()
+            .filter_map(|i| self.get(i).map(|s| (i, s)))
+            .find(|(_, s)| {
+                s.writes
+                    .iter()
+                    .any(|w| addr >= w.addr && addr < w.addr + w.old.len() as u64)
+            })
+    }
+
+    /// The index and step of the last change to the register value at `index`
+    pub fn last_register_write(&self, index: usize) -> Option<(usize, &TraceStep)> {
+        (0..self.position)
+            .rev()
+            .filter_map(|i| self.get(i).map(|s| (i, s)))
+            .find(|(_, s)| s.registers.iter().any(|r| r.0 == index))
+    }
+
+    /// The indexes of every step that executed `pc`
+    pub fn visits(&self, pc: u64) -> Vec<usize> {
+        (0..self.len())
+            .filter(|i| self.get(*i).map(|s| s.pc) == Some(pc))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.start + self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // the steps recorded since the last fork, sharing the ones before
+    fn own(&mut self) -> &mut Vec<TraceStep> {
+        if Rc::get_mut(&mut self.steps).is_none() {
+            self.seal(self.steps.len());
+        }
+        Rc::get_mut(&mut self.steps).unwrap()
+    }
+
+    // move the first `len` own steps into a shared segment
+    fn seal(&mut self, len: usize) {
+        let steps = mem::take(&mut self.steps);
+        if len > 0 {
+            self.prefix = Some(Rc::new(Segment {
+                steps,
+                len,
+                start: self.start,
+                prefix: self.prefix.take(),
+            }));
+            self.start += len;
+        }
+    }
+
+    // drop the steps from `len` on
+    fn truncate(&mut self, len: usize) {
+        if len >= self.len() {
+            return;
+        }
+        if len >= self.start {
+            match Rc::get_mut(&mut self.steps) {
+                Some(steps) => steps.truncate(len - self.start),
+                None => self.seal(len - self.start),
+            }
+            return;
+        }
+
+        self.steps = Rc::default();
+        let mut segment = self.prefix.take();
+        while let Some(s) = segment {
+            if s.start < len {
+                segment = Some(Rc::new(Segment {
+                    steps: s.steps.clone(),
+                    len: len - s.start,
+                    start: s.start,
+                    prefix: s.prefix.clone(),
+                }));
+                break;
+            }
+            segment = s.prefix.clone();
+        }
+        self.prefix = segment;
+        self.start = len;
+    }
+}
+
+/// Copy a step with its values translated to `solver`
+pub fn translate_step(step: &TraceStep, solver: &Solver) -> TraceStep {
+    TraceStep {
+        pc: step.pc,
+        registers: step
+            .registers
+            .iter()
+            .map(|(i, o, n)| (*i, solver.translate_value(o), solver.translate_value(n)))
+            .collect(),
+        writes: step
+            .writes
+            .iter()
+            .map(|w| translate_write(w, solver))
+            .collect(),
+    }
+}
+
+/// Copy a memory write with its values translated to `solver`
+pub fn translate_write(write: &MemoryWrite, solver: &Solver) -> MemoryWrite {
+    MemoryWrite {
+        addr: write.addr,
+        old: write
+            .old
+            .iter()
+            .map(|v| v.as_ref().map(|v| solver.translate_value(v)))
+            .collect(),
+        new: write
+            .new
+            .iter()
+            .map(|v| solver.translate_value(v))
+            .collect(),
+    }
+}
+
+/// parse a hex (0x prefixed) or decimal number
+pub fn parse_number(s: &str) -> R2Result<u64> {
+    let s = s.trim();
+    let result = if let Some(hex) = s.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16)
+    } else {
+        s.parse()
+    };
+
+    result.map_err(|_| format!("bad number: {}", s))
+}
@@ -0,0 +1,471 @@
+use crate::r2_api::R2Api;
+use std::collections::HashMap;
+
+/// A field of a struct, nested struct fields are named like
+/// `sin_addr.s_addr` and array elements like `sin_zero[0]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructField {
+    pub name: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// The layout of a C struct or union
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructDef {
+    pub name: String,
+    pub size: u64,
+    pub align: u64,
+    pub fields: Vec<StructField>,
+}
+
+impl StructDef {
+    pub fn field(&self, name: &str) -> Option<&StructField> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+/// Struct definitions parsed from C, by name like `struct sockaddr_in`
+/// and by typedef name. Structs that are not defined are looked up
+/// in the types radare2 knows with `tsc`
+///
+/// ## Example
+/// ```
+/// use radius2::types::TypeDefs;
+/// let mut types = TypeDefs::new(8);
+/// types.define("struct in_addr { unsigned int s_addr; };
+///     struct sockaddr_in {
+///         short sin_family;
+///         unsigned short sin_port;
+///         struct in_addr sin_addr;
+///         char sin_zero[8];
+///     };").unwrap();
+///
+/// let def = types.get("struct sockaddr_in").unwrap();
+/// assert_eq!(def.size, 16);
+/// assert_eq!(def.field("sin_addr.s_addr").unwrap().offset, 4);
+/// assert_eq!(def.field("sin_zero[1]").unwrap().offset, 9);
+/// assert_eq!(types.size_of("struct sockaddr_in [2]"), Some(32));
+/// assert_eq!(types.size_of("char *"), Some(8));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TypeDefs {
+    pub structs: HashMap<String, StructDef>,
+    /// Size of pointers and longs in bytes
+    pub ptr_size: u64,
+}
+
+// a parsed type with its size, alignment, and fields if it is a struct
+type Layout = (u64, u64, Vec<StructField>);
+
+impl TypeDefs {
+    pub fn new(ptr_size: u64) -> Self {
+        TypeDefs {
+            structs: HashMap::new(),
+            ptr_size,
+        }
+    }
+
+    /// Parse the struct, union, and typedef definitions in `source`,
+    /// returning the names defined
+    pub fn define(&mut self, source: &str) -> Result<Vec<String>, String> {
+        let tokens = tokenize(source);
+        let mut defined = vec![];
+        let mut pos = 0;
+        while pos < tokens.len() {
+            if tokens[pos] == ";" {
+                pos += 1;
+                continue;
+            }
+            let typedef = tokens[pos] == "typedef";
+            if typedef {
+                pos += 1;
+            }
+            let (name, layout) = self.parse_type(&tokens, &mut pos)?;
+            if let Some(name) = &name {
+                defined.push(name.to_owned());
+            }
+
+            // the typedef names, or variables that are ignored
+            while pos < tokens.len() && tokens[pos] != ";" {
+                let (alias, (size, align, fields)) =
+                    self.parse_declarator(&tokens, &mut pos, layout.clone())?;
+                if typedef {
+                    let def = StructDef {
+                        name: alias.to_owned(),
+                        size,
+                        align,
+                        fields,
+                    };
+                    self.structs.insert(alias.to_owned(), def);
+                    defined.push(alias);
+                }
+                if pos < tokens.len() && tokens[pos] == "," {
+                    pos += 1;
+                }
+            }
+        }
+        Ok(defined)
+    }
+
+    /// Get the definition of a struct, from radare2 if it is not defined
+    pub fn lookup(&mut self, name: &str, r2api: &mut R2Api) -> Option<StructDef> {
+        if let Some(def) = self.get(name) {
+            return Some(def.to_owned());
+        }
+        let bare = name
+            .trim_start_matches("struct ")
+            .trim_start_matches("union ")
+            .trim();
+        let source = r2api.cmd(&format!("tsc {}", bare)).ok()?;
+        if source.trim().is_empty() {
+            return None;
+        }
+        if let Err(e) = self.define(&source) {
+            println!("could not parse type {}: {}", name, e);
+        }
+        self.get(name).cloned()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&StructDef> {
+        self.structs.get(name.trim())
+    }
+
+    /// Size of a type like `unsigned int`, `char *`, `char [16]`, or a
+    /// defined struct, None if it is not known
+    pub fn size_of(&mut self, name: &str) -> Option<u64> {
+        let name = name.trim();
+        if name.ends_with('*') {
+            return Some(self.ptr_size);
+        }
+        if let Some((base, count)) = name.strip_suffix(']').and_then(|n| n.rsplit_once('[')) {
+            let count: u64 = count.trim().parse().ok()?;
+            return self.size_of(base).map(|size| size * count);
+        }
+        let tokens = tokenize(name);
+        let mut pos = 0;
+        let (_, (size, _, _)) = self.parse_type(&tokens, &mut pos).ok()?;
+        (size != 0).then_some(size)
+    }
+
+    // parse a base type, defining it if it is a struct with a body
+    fn parse_type(
+        &mut self,
+        tokens: &[String],
+        pos: &mut usize,
+    ) -> Result<(Option<String>, Layout), String> {
+        let token = tokens.get(*pos).ok_or("expected a type")?.as_str();
+        if token == "struct" || token == "union" {
+            *pos += 1;
+            let mut name = None;
+            if tokens.get(*pos).map(|t| is_ident(t)) == Some(true) {
+                name = Some(format!("{} {}", token, tokens[*pos]));
+                *pos += 1;
+            }
+            if tokens.get(*pos).map(|t| t.as_str()) != Some("{") {
+                let name = name.ok_or("expected a struct body")?;
+                return Ok((None, self.layout(&name)));
+            }
+            *pos += 1;
+            let layout = self.parse_body(tokens, pos, token == "union")?;
+            if let Some(name) = &name {
+                let def = StructDef {
+                    name: name.to_owned(),
+                    size: layout.0,
+                    align: layout.1,
+                    fields: layout.2.clone(),
+                };
+                self.structs.insert(name.to_owned(), def);
+            }
+            return Ok((name, layout));
+        }
+        if token == "enum" {
+            // skip the name and any body, enums are ints
+            *pos += 1;
+            while *pos < tokens.len() && is_ident(&tokens[*pos]) {
+                *pos += 1;
+            }
+            if tokens.get(*pos).map(|t| t.as_str()) == Some("{") {
+                while *pos < tokens.len() && tokens[*pos] != "}" {
+                    *pos += 1;
+                }
+                *pos += 1;
+            }
+            return Ok((None, (4, 4, vec![])));
+        }
+
+        // a run of keywords like unsigned long long, or a typedef name
+        let mut words = vec![];
+        while let Some(word) = tokens.get(*pos) {
+            let keyword = KEYWORDS.contains(&word.as_str());
+            // after the type comes the name of the declarator
+            if !is_ident(word) || (!keyword && words.iter().any(|w| !QUALIFIERS.contains(w))) {
+                break;
+            }
+            words.push(word.as_str());
+            *pos += 1;
+        }
+        let words: Vec<&str> = words
+            .into_iter()
+            .filter(|w| !QUALIFIERS.contains(w))
+            .collect();
+        let size = self.primitive_size(&words)?;
+        if size == 0 {
+            return Ok((None, self.layout(&words.join(" "))));
+        }
+        Ok((None, (size, size.min(8), vec![])))
+    }
+
+    // layout of a defined type, types that are not defined yet have
+    // size 0 and can only be pointed to
+    fn layout(&self, name: &str) -> Layout {
+        self.get(name)
+            .map(|def| (def.size, def.align, def.fields.clone()))
+            .unwrap_or((0, 1, vec![]))
+    }
+
+    // size of a primitive type, or 0 for a typedef name
+    fn primitive_size(&self, words: &[&str]) -> Result<u64, String> {
+        let longs = words.iter().filter(|w| **w == "long").count();
+        let size = match words.iter().find(|w| !SIGNS.contains(w) && **w != "long") {
+            Some(&"char") | Some(&"bool") | Some(&"_Bool") => 1,
+            Some(&"short") => 2,
+            Some(&"float") => 4,
+            Some(&"double") => 8 + 8 * longs as u64,
+            Some(&"int") | None if longs == 0 => {
+                if words.is_empty() {
+                    return Err("expected a type".to_owned());
+                }
+                4
+            }
+            Some(&"int") | None if longs == 1 => self.ptr_size,
+            Some(&"int") | None => 8,
+            Some(name) => match *name {
+                "int8_t" | "uint8_t" => 1,
+                "int16_t" | "uint16_t" => 2,
+                "int32_t" | "uint32_t" | "pid_t" | "uid_t" | "gid_t" | "socklen_t" => 4,
+                "int64_t" | "uint64_t" => 8,
+                "size_t" | "ssize_t" | "off_t" | "intptr_t" | "uintptr_t" | "ptrdiff_t" => {
+                    self.ptr_size
+                }
+                "void" => 1,
+                _ => 0,
+            },
+        };
+        Ok(size)
+    }
+
+    // parse the fields between braces
+    fn parse_body(
+        &mut self,
+        tokens: &[String],
+        pos: &mut usize,
+        union: bool,
+    ) -> Result<Layout, String> {
+        let mut fields = vec![];
+        let mut size = 0;
+        let mut align = 1;
+        while tokens.get(*pos).map(|t| t.as_str()) != Some("}") {
+            if *pos >= tokens.len() {
+                return Err("unterminated struct".to_owned());
+            }
+            let (_, base) = self.parse_type(tokens, pos)?;
+            loop {
+                let (name, (fsize, falign, nested)) =
+                    self.parse_declarator(tokens, pos, base.clone())?;
+                if tokens.get(*pos).map(|t| t.as_str()) == Some(":") {
+                    return Err("bitfields are not supported".to_owned());
+                }
+                let offset = if union { 0 } else { align_to(size, falign) };
+                if nested.is_empty() {
+                    fields.push(StructField {
+                        name,
+                        offset,
+                        size: fsize,
+                    });
+                } else {
+                    for field in nested {
+                        let sep = if field.name.starts_with('[') { "" } else { "." };
+                        fields.push(StructField {
+                            name: format!("{}{}{}", name, sep, field.name),
+                            offset: offset + field.offset,
+                            size: field.size,
+                        });
+                    }
+                }
+                size = if union {
+                    size.max(fsize)
+                } else {
+                    offset + fsize
+                };
+                align = align.max(falign);
+
+                match tokens.get(*pos).map(|t| t.as_str()) {
+                    Some(",") => *pos += 1,
+                    Some(";") => {
+                        *pos += 1;
+                        break;
+                    }
+                    _ => return Err(format!("expected ; after field {}", name_at(tokens, *pos))),
+                }
+            }
+        }
+        *pos += 1;
+        Ok((align_to(size, align), align, fields))
+    }
+
+    // parse pointers, a name, and array sizes
+    fn parse_declarator(
+        &self,
+        tokens: &[String],
+        pos: &mut usize,
+        base: Layout,
+    ) -> Result<(String, Layout), String> {
+        // function pointers like (*name)(args)
+        if tokens.get(*pos).map(|t| t.as_str()) == Some("(") {
+            let name = tokens.get(*pos + 2).ok_or("expected a function pointer")?;
+            *pos += 4;
+            let mut depth = 0;
+            while let Some(token) = tokens.get(*pos) {
+                match token.as_str() {
+                    "(" => depth += 1,
+                    ")" => depth -= 1,
+                    _ => {}
+                }
+                *pos += 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            return Ok((name.to_owned(), (self.ptr_size, self.ptr_size, vec![])));
+        }
+
+        let mut pointer = false;
+        while tokens.get(*pos).map(|t| t.as_str()) == Some("*") {
+            pointer = true;
+            *pos += 1;
+            while tokens.get(*pos).map(|t| QUALIFIERS.contains(&t.as_str())) == Some(true) {
+                *pos += 1;
+            }
+        }
+        let name = tokens
+            .get(*pos)
+            .filter(|t| is_ident(t))
+            .ok_or(format!("expected a name at {}", name_at(tokens, *pos)))?
+            .to_owned();
+        *pos += 1;
+
+        let (mut size, align, mut fields) = if pointer {
+            (self.ptr_size, self.ptr_size, vec![])
+        } else if base.0 == 0 {
+            return Err(format!("unknown type of {}", name));
+        } else {
+            base
+        };
+
+        // arrays are expanded into their elements, innermost last
+        let mut dims = vec![];
+        while tokens.get(*pos).map(|t| t.as_str()) == Some("[") {
+            let count = tokens.get(*pos + 1).ok_or("expected an array size")?;
+            dims.push(parse_number(count).ok_or(format!("bad array size {}", count))?);
+            *pos += 3;
+        }
+        for count in dims.into_iter().rev() {
+            let mut elements = vec![];
+            for i in 0..count {
+                let prefix = format!("[{}]", i);
+                if fields.is_empty() {
+                    elements.push(StructField {
+                        name: prefix,
+                        offset: i * size,
+                        size,
+                    });
+                } else {
+                    for field in &fields {
+                        let sep = if field.name.starts_with('[') { "" } else { "." };
+                        elements.push(StructField {
+                            name: format!("{}{}{}", prefix, sep, field.name),
+                            offset: i * size + field.offset,
+                            size: field.size,
+                        });
+                    }
+                }
+            }
+            size *= count;
+            fields = elements;
+        }
+        Ok((name, (size, align, fields)))
+    }
+}
+
+const SIGNS: [&str; 2] = ["signed", "unsigned"];
+const QUALIFIERS: [&str; 4] = ["const", "volatile", "restrict", "register"];
+const KEYWORDS: [&str; 15] = [
+    "signed", "unsigned", "char", "short", "int", "long", "float", "double", "void", "bool",
+    "_Bool", "const", "volatile", "restrict", "register",
+];
+
+fn align_to(offset: u64, align: u64) -> u64 {
+    (offset + align - 1) / align * align
+}
+
+fn is_ident(token: &str) -> bool {
+    token
+        .chars()
+        .next()
+        .map(|c| c.is_alphabetic() || c == '_')
+        .unwrap_or(false)
+}
+
+fn parse_number(token: &str) -> Option<u64> {
+    if let Some(hex) = token.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).ok()
+    } else {
+        token.parse().ok()
+    }
+}
+
+fn name_at(tokens: &[String], pos: usize) -> String {
+    tokens.get(pos).cloned().unwrap_or_else(|| "end".to_owned())
+}
+
+// split C into identifiers, numbers, and punctuation, without
+// comments and preprocessor lines
+fn tokenize(source: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+    let mut line_start = true;
+    while let Some(c) = chars.next() {
+        if c == '#' && line_start {
+            while chars.next().map(|c| c != '\n').unwrap_or(false) {}
+            continue;
+        }
+        line_start = c == '\n' || (line_start && c.is_whitespace());
+        if c == '/' && chars.peek() == Some(&'/') {
+            while chars.next().map(|c| c != '\n').unwrap_or(false) {}
+            line_start = true;
+        } else if c == '/' && chars.peek() == Some(&'*') {
+            chars.next();
+            let mut last = ' ';
+            for c in chars.by_ref() {
+                if last == '*' && c == '/' {
+                    break;
+                }
+                last = c;
+            }
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut token = c.to_string();
+            while let Some(&c) = chars.peek() {
+                if !c.is_alphanumeric() && c != '_' {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        } else if !c.is_whitespace() {
+            tokens.push(c.to_string());
+        }
+    }
+    tokens
+}
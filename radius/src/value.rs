@@ -1,3 +1,4 @@
+use crate::interval::{self, Interval, Op};
 use crate::solver::BitVec;
 use boolector::{Btor, BV};
 use std::cmp::Ordering;
@@ -45,8 +46,8 @@ pub fn cond_value(cond: &BitVec, v1: Value, v2: Value) -> BitVec {
 }
 
 macro_rules! binary_ops {
-    ($self:expr, $rhs:expr, $method:ident, $op:tt) => {
-        match ($self, $rhs) {
+    ($self:expr, $rhs:expr, $method:ident, $op:tt, $kind:ident) => {
+        interval::binary(Op::$kind, $self, $rhs, match ($self, $rhs) {
             (Value::Concrete(a, t1), Value::Concrete(b, t2)) => {
                 Value::Concrete(*a $op *b, *t1 | *t2)
             },
@@ -66,37 +67,42 @@ macro_rules! binary_ops {
                     Ordering::Less => Value::Symbolic(a.uext((-width_diff) as u32).$method(&b), *t1 | *t2)
                 }
             }
-        }
+        })
     };
 }
 
 macro_rules! wrapping_binary_ops {
-    ($self:expr, $rhs:expr, $method:ident, $wrapping:ident) => {
-        match ($self, $rhs) {
-            (Value::Concrete(a, t1), Value::Concrete(b, t2)) => {
-                Value::Concrete(a.$wrapping(*b), *t1 | *t2)
-            }
-            (Value::Symbolic(a, t1), Value::Concrete(b, t2)) => {
-                let bv = make_bv(a, *b, a.get_width());
-                Value::Symbolic(a.$method(&bv), *t1 | *t2)
-            }
-            (Value::Concrete(a, t1), Value::Symbolic(b, t2)) => {
-                let bv = make_bv(b, *a, b.get_width());
-                Value::Symbolic(bv.$method(&b), *t1 | *t2)
-            }
-            (Value::Symbolic(a, t1), Value::Symbolic(b, t2)) => {
-                let width_diff = a.get_width() as i32 - b.get_width() as i32;
-                match width_diff.cmp(&0) {
-                    Ordering::Equal => Value::Symbolic(a.$method(&b), *t1 | *t2),
-                    Ordering::Greater => {
-                        Value::Symbolic(a.$method(&b.uext(width_diff as u32)), *t1 | *t2)
-                    }
-                    Ordering::Less => {
-                        Value::Symbolic(a.uext((-width_diff) as u32).$method(&b), *t1 | *t2)
+    ($self:expr, $rhs:expr, $method:ident, $wrapping:ident, $kind:ident) => {
+        interval::binary(
+            Op::$kind,
+            $self,
+            $rhs,
+            match ($self, $rhs) {
+                (Value::Concrete(a, t1), Value::Concrete(b, t2)) => {
+                    Value::Concrete(a.$wrapping(*b), *t1 | *t2)
+                }
+                (Value::Symbolic(a, t1), Value::Concrete(b, t2)) => {
+                    let bv = make_bv(a, *b, a.get_width());
+                    Value::Symbolic(a.$method(&bv), *t1 | *t2)
+                }
+                (Value::Concrete(a, t1), Value::Symbolic(b, t2)) => {
+                    let bv = make_bv(b, *a, b.get_width());
+                    Value::Symbolic(bv.$method(&b), *t1 | *t2)
+                }
+                (Value::Symbolic(a, t1), Value::Symbolic(b, t2)) => {
+                    let width_diff = a.get_width() as i32 - b.get_width() as i32;
+                    match width_diff.cmp(&0) {
+                        Ordering::Equal => Value::Symbolic(a.$method(&b), *t1 | *t2),
+                        Ordering::Greater => {
+                            Value::Symbolic(a.$method(&b.uext(width_diff as u32)), *t1 | *t2)
+                        }
+                        Ordering::Less => {
+                            Value::Symbolic(a.uext((-width_diff) as u32).$method(&b), *t1 | *t2)
+                        }
                     }
                 }
-            }
-        }
+            },
+        )
     };
 }
 
@@ -105,7 +111,7 @@ impl ops::Add<Value> for Value {
 
     #[inline]
     fn add(self, rhs: Value) -> Value {
-        wrapping_binary_ops!(&self, &rhs, add, wrapping_add)
+        wrapping_binary_ops!(&self, &rhs, add, wrapping_add, Add)
     }
 }
 
@@ -114,7 +120,7 @@ impl ops::Sub<Value> for Value {
 
     #[inline]
     fn sub(self, rhs: Value) -> Value {
-        wrapping_binary_ops!(&self, &rhs, sub, wrapping_sub)
+        wrapping_binary_ops!(&self, &rhs, sub, wrapping_sub, Sub)
     }
 }
 
@@ -123,7 +129,7 @@ impl ops::Mul<Value> for Value {
 
     #[inline]
     fn mul(self, rhs: Value) -> Value {
-        wrapping_binary_ops!(&self, &rhs, mul, wrapping_mul)
+        wrapping_binary_ops!(&self, &rhs, mul, wrapping_mul, Mul)
     }
 }
 
@@ -132,7 +138,8 @@ impl ops::Div<Value> for Value {
 
     #[inline]
     fn div(self, rhs: Value) -> Value {
-        match (self, rhs) {
+        let operands = interval::operands(Op::Div, &self, &rhs);
+        let result = match (self, rhs) {
             (Value::Concrete(a, t1), Value::Concrete(b, t2)) => {
                 // boolector makes /0 always -1 so
                 if b != 0 {
@@ -159,7 +166,8 @@ impl ops::Div<Value> for Value {
                     Ordering::Less => Value::Symbolic(a.uext(-width_diff as u32).udiv(&b), t1 | t2),
                 }
             }
-        }
+        };
+        interval::apply(Op::Div, operands, result)
     }
 }
 
@@ -168,7 +176,8 @@ impl ops::Rem<Value> for Value {
 
     #[inline]
     fn rem(self, rhs: Value) -> Value {
-        match (self, rhs) {
+        let operands = interval::operands(Op::Rem, &self, &rhs);
+        let result = match (self, rhs) {
             (Value::Concrete(a, t1), Value::Concrete(b, t2)) => {
                 if b != 0 {
                     Value::Concrete(a.wrapping_rem(b), t1 | t2)
@@ -194,7 +203,8 @@ impl ops::Rem<Value> for Value {
                     Ordering::Less => Value::Symbolic(a.uext(-width_diff as u32).urem(&b), t1 | t2),
                 }
             }
-        }
+        };
+        interval::apply(Op::Rem, operands, result)
     }
 }
 
@@ -203,7 +213,7 @@ impl ops::BitAnd<Value> for Value {
 
     #[inline]
     fn bitand(self, rhs: Value) -> Value {
-        binary_ops!(&self, &rhs, and, &)
+        binary_ops!(&self, &rhs, and, &, And)
     }
 }
 
@@ -212,7 +222,7 @@ impl ops::BitOr<Value> for Value {
 
     #[inline]
     fn bitor(self, rhs: Value) -> Value {
-        binary_ops!(&self, &rhs, or, |)
+        binary_ops!(&self, &rhs, or, |, Or)
     }
 }
 
@@ -224,7 +234,8 @@ impl ops::BitXor<Value> for Value {
     // to stop wild overtainting
     #[inline]
     fn bitxor(self, rhs: Value) -> Value {
-        match (self, rhs) {
+        let operands = interval::operands(Op::Xor, &self, &rhs);
+        let result = match (self, rhs) {
             (Value::Concrete(a, t1), Value::Concrete(b, t2)) => {
                 Value::Concrete(a ^ b, (t1 | t2) * ((a != b || t1 != t2) as u64))
             }
@@ -246,7 +257,8 @@ impl ops::BitXor<Value> for Value {
                     Ordering::Less => Value::Symbolic(a.uext(-width_diff as u32).xor(&b), t1 | t2),
                 }
             }
-        }
+        };
+        interval::apply(Op::Xor, operands, result)
     }
 }
 
@@ -259,7 +271,11 @@ impl ops::Not for Value {
             Value::Concrete(a, t) => Value::Concrete((a == 0) as u64, t),
             Value::Symbolic(a, t) => {
                 let zero = BV::zero(a.get_btor(), a.get_width());
-                Value::Symbolic(a._eq(&zero).uext(a.get_width() - 1), t)
+                let interval = interval::get(&a).is_nonzero().not().at(a.get_width());
+                interval::track(
+                    Value::Symbolic(a._eq(&zero).uext(a.get_width() - 1), t),
+                    interval,
+                )
             }
         }
     }
@@ -272,7 +288,10 @@ impl ops::Neg for Value {
     fn neg(self) -> Value {
         match self {
             Value::Concrete(a, t) => Value::Concrete(a.wrapping_neg(), t),
-            Value::Symbolic(a, t) => Value::Symbolic(a.neg(), t),
+            Value::Symbolic(a, t) => {
+                let interval = interval::get(&a).neg();
+                interval::track(Value::Symbolic(a.neg(), t), interval)
+            }
         }
     }
 }
@@ -282,7 +301,8 @@ impl ops::Shl<Value> for Value {
 
     #[inline]
     fn shl(self, rhs: Value) -> Value {
-        match (self, rhs) {
+        let operands = interval::operands(Op::Shl, &self, &rhs);
+        let result = match (self, rhs) {
             (Value::Concrete(a, t1), Value::Concrete(b, t2)) => {
                 if b > 63 {
                     Value::Concrete(0, t1 | t2)
@@ -301,7 +321,8 @@ impl ops::Shl<Value> for Value {
             (Value::Symbolic(a, t1), Value::Symbolic(b, t2)) => {
                 Value::Symbolic(a.sll(&b.slice(log2(a.get_width()) - 1, 0)), t1 | t2)
             }
-        }
+        };
+        interval::apply(Op::Shl, operands, result)
     }
 }
 
@@ -310,7 +331,8 @@ impl ops::Shr<Value> for Value {
 
     #[inline]
     fn shr(self, rhs: Value) -> Value {
-        match (self, rhs) {
+        let operands = interval::operands(Op::Shr, &self, &rhs);
+        let result = match (self, rhs) {
             (Value::Concrete(a, t1), Value::Concrete(b, t2)) => {
                 if b > 63 {
                     Value::Concrete(0, t1 | t2)
@@ -330,7 +352,8 @@ impl ops::Shr<Value> for Value {
             (Value::Symbolic(a, t1), Value::Symbolic(b, t2)) => {
                 Value::Symbolic(a.srl(&b.slice(log2(a.get_width()) - 1, 0)), t1 | t2)
             }
-        }
+        };
+        interval::apply(Op::Shr, operands, result)
     }
 }
 
@@ -472,7 +495,7 @@ impl Value {
     // get whether values are equivalent
     #[inline]
     pub fn eq(&self, rhs: &Value) -> Value {
-        match (self, rhs) {
+        let result = match (self, rhs) {
             (Value::Concrete(a, t1), Value::Concrete(b, t2)) => {
                 Value::Concrete((*a == *b) as u64, *t1 | *t2)
             }
@@ -496,7 +519,8 @@ impl Value {
                     }
                 }
             }
-        }
+        };
+        interval::binary(Op::Eq, self, rhs, result)
     }
 
     // check if values are *identical*
@@ -528,7 +552,7 @@ impl Value {
 
     #[inline]
     pub fn slt(&self, rhs: &Value) -> Value {
-        match (self, rhs) {
+        let result = match (self, rhs) {
             (Value::Concrete(a, t1), Value::Concrete(b, t2)) => {
                 Value::Concrete(((*a as i64) < (*b as i64)) as u64, *t1 | *t2)
             }
@@ -552,7 +576,8 @@ impl Value {
                     }
                 }
             }
-        }
+        };
+        interval::binary(Op::Slt, self, rhs, result)
     }
 
     #[inline]
@@ -572,7 +597,7 @@ impl Value {
 
     #[inline]
     pub fn ult(&self, rhs: &Value) -> Value {
-        match (self, rhs) {
+        let result = match (self, rhs) {
             (Value::Concrete(a, t1), Value::Concrete(b, t2)) => {
                 Value::Concrete((*a < *b) as u64, *t1 | *t2)
             }
@@ -596,7 +621,8 @@ impl Value {
                     }
                 }
             }
-        }
+        };
+        interval::binary(Op::Ult, self, rhs, result)
     }
 
     #[inline]
@@ -623,7 +649,10 @@ impl Value {
             (Value::Symbolic(a, t1), Value::Concrete(b, t2)) => {
                 //let bv = make_bv(&a, b, a.get_width());
                 let bits = if *b <= 64 { *b } else { 64 };
-                Value::Symbolic(a.slice(*b as u32 - 1, 0).uext(64 - bits as u32), *t1 | *t2)
+                let interval = interval::get(a).slice(*b as u32 - 1, 0).at(64);
+                let value =
+                    Value::Symbolic(a.slice(*b as u32 - 1, 0).uext(64 - bits as u32), *t1 | *t2);
+                interval::track(value, interval)
             }
             (Value::Concrete(a, t), Value::Symbolic(_b, _t)) => {
                 // uh hopefully this doesnt happen
@@ -646,10 +675,12 @@ impl Value {
             }
             (Value::Symbolic(a, t1), Value::Concrete(b, t2)) => {
                 //let bv = make_bv(&a, b, a.get_width());
-                Value::Symbolic(
+                let interval = interval::get(a).slice(*b as u32 - 1, 0).sext(64);
+                let value = Value::Symbolic(
                     a.slice(*b as u32 - 1, 0).sext(64 - (*b % 64) as u32),
                     *t1 | *t2,
-                )
+                );
+                interval::track(value, interval)
             }
             (Value::Concrete(a, t), Value::Symbolic(_b, _t)) => {
                 // uh hopefully this doesnt happen
@@ -663,6 +694,31 @@ impl Value {
         }
     }
 
+    /// Replace the `width` bits starting at bit `low` with the low bits of `field`
+    #[inline]
+    pub fn insert_bits(&self, low: u64, width: u64, field: &Value) -> Value {
+        let mask = if width < 64 {
+            (1 << width) - 1
+        } else {
+            u64::MAX
+        };
+        let cleared = self.and(&Value::Concrete(!(mask << low), 0));
+        // symbolic fields may be narrower than the bits they go in
+        let bits = width.min(field.size() as u64);
+        let field = if bits < 64 {
+            field.uext(&Value::Concrete(bits, 0))
+        } else {
+            field.to_owned()
+        };
+        let field = field << Value::Concrete(low, 0);
+        let value = cleared.or(&field);
+        if self.size() < value.size() {
+            value.slice(self.size() as u64 - 1, 0)
+        } else {
+            value
+        }
+    }
+
     #[inline]
     pub fn slice(&self, high: u64, low: u64) -> Value {
         match self {
@@ -674,7 +730,13 @@ impl Value {
                 };
                 Value::Concrete((*a >> low) & mask, *t)
             }
-            Value::Symbolic(a, t) => Value::Symbolic(a.slice(high as u32, low as u32), *t),
+            Value::Symbolic(a, t) => {
+                let interval = interval::get(a).slice(high as u32, low as u32);
+                interval::track(
+                    Value::Symbolic(a.slice(high as u32, low as u32), *t),
+                    interval,
+                )
+            }
         }
     }
 
@@ -686,6 +748,28 @@ impl Value {
         }
     }
 
+    /// The value as an IEEE-754 double, if it is concrete
+    #[inline]
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_u64().map(f64::from_bits)
+    }
+
+    /// The low 32 bits of the value as an IEEE-754 float, if it is concrete
+    #[inline]
+    pub fn as_f32(&self) -> Option<f32> {
+        self.as_u64().map(|v| f32::from_bits(v as u32))
+    }
+
+    #[inline]
+    pub fn from_f64(value: f64, taint: u64) -> Value {
+        Value::Concrete(value.to_bits(), taint)
+    }
+
+    #[inline]
+    pub fn from_f32(value: f32, taint: u64) -> Value {
+        Value::Concrete(value.to_bits() as u64, taint)
+    }
+
     /// tries to convert to Concrete
     #[inline]
     pub fn try_con(&self) -> Self {
@@ -741,6 +825,12 @@ impl Value {
         }
     }
 
+    /// The range of values this can take, without its constraints
+    #[inline]
+    pub fn interval(&self) -> Interval {
+        interval::of(self, self.size())
+    }
+
     #[inline]
     pub fn is_concrete(&self) -> bool {
         matches!(self, Value::Concrete(_, _))
@@ -753,42 +843,42 @@ impl Value {
 
     #[inline]
     pub fn add(&self, rhs: &Value) -> Value {
-        wrapping_binary_ops!(self, rhs, add, wrapping_add)
+        wrapping_binary_ops!(self, rhs, add, wrapping_add, Add)
     }
 
     #[inline]
     pub fn sub(&self, rhs: &Value) -> Value {
-        wrapping_binary_ops!(self, rhs, sub, wrapping_sub)
+        wrapping_binary_ops!(self, rhs, sub, wrapping_sub, Sub)
     }
 
     #[inline]
     pub fn mul(&self, rhs: &Value) -> Value {
-        wrapping_binary_ops!(self, rhs, mul, wrapping_mul)
+        wrapping_binary_ops!(self, rhs, mul, wrapping_mul, Mul)
     }
 
     #[inline]
     pub fn div(&self, rhs: &Value) -> Value {
-        wrapping_binary_ops!(self, rhs, udiv, wrapping_div)
+        wrapping_binary_ops!(self, rhs, udiv, wrapping_div, Div)
     }
 
     #[inline]
     pub fn rem(&self, rhs: &Value) -> Value {
-        wrapping_binary_ops!(self, rhs, urem, wrapping_rem)
+        wrapping_binary_ops!(self, rhs, urem, wrapping_rem, Rem)
     }
 
     #[inline]
     pub fn and(&self, rhs: &Value) -> Value {
-        binary_ops!(self, rhs, and, &)
+        binary_ops!(self, rhs, and, &, And)
     }
 
     #[inline]
     pub fn or(&self, rhs: &Value) -> Value {
-        binary_ops!(self, rhs, or, |)
+        binary_ops!(self, rhs, or, |, Or)
     }
 
     #[inline]
     pub fn xor(&self, rhs: &Value) -> Value {
-        binary_ops!(self, rhs, xor, ^)
+        binary_ops!(self, rhs, xor, ^, Xor)
     }
 
     #[inline]
@@ -0,0 +1,167 @@
+use crate::processor::InstructionEntry;
+use crate::state::State;
+use crate::value::vc;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Distinct targets after which an indirect jump is taken to be a dispatcher
+pub const DISPATCH_TARGETS: usize = 8;
+
+// instruction types of indirect jumps and calls that can dispatch handlers
+const INDIRECT_TYPES: [&str; 8] = [
+    "ujmp", "rjmp", "mjmp", "ijmp", "ucall", "rcall", "icall", "ircall",
+];
+
+/// Records the handlers a virtualized code dispatcher jumps to, with the
+/// bytecode at the virtual pc, in the context of each state. If no
+/// dispatcher is given the first indirect jump reaching
+/// `DISPATCH_TARGETS` distinct targets is taken to be the dispatcher
+#[derive(Debug, Clone, Default)]
+pub struct VmTracer {
+    /// Address of the indirect jump to the handlers
+    pub dispatcher: Option<u64>,
+    /// Register holding the virtual pc, the address of the bytecode
+    pub vpc: Option<String>,
+    /// Number of operand bytes read at the virtual pc
+    pub operands: usize,
+    /// Targets of each indirect jump, to recognize the dispatcher
+    pub targets: HashMap<u64, HashSet<u64>>,
+}
+
+impl VmTracer {
+    pub fn new(dispatcher: Option<u64>, vpc: Option<&str>, operands: usize) -> Self {
+        VmTracer {
+            dispatcher,
+            vpc: vpc.map(|r| r.to_owned()),
+            operands,
+            targets: HashMap::new(),
+        }
+    }
+
+    /// Record the handler at `pc` if the state was just dispatched to it
+    pub fn enter(&mut self, state: &mut State, pc: u64) {
+        let jump = if let Some(jump) = state.context.remove("vm_jump") {
+            jump
+        } else {
+            return;
+        };
+        let site = jump[0].as_u64().unwrap_or_default();
+
+        let targets = self.targets.entry(site).or_default();
+        targets.insert(pc);
+        if self.dispatcher.is_none() && targets.len() >= DISPATCH_TARGETS {
+            self.dispatcher = Some(site);
+        }
+        if self.dispatcher != Some(site) {
+            return;
+        }
+
+        let vpc = jump[1].to_owned();
+        let operands = if self.operands > 0 && self.vpc.is_some() {
+            state.memory_read(&vpc, &vc(self.operands as u64))
+        } else {
+            vec![]
+        };
+
+        let trace = state.context.entry("vm_trace".to_owned()).or_default();
+        trace.push(vc(pc));
+        trace.push(vpc);
+        trace.push(vc(operands.len() as u64));
+        trace.extend(operands);
+    }
+
+    /// Note the jump if the instruction at `pc` that the state just
+    /// executed is an indirect jump that can be the dispatcher
+    pub fn exit(&self, state: &mut State, pc: u64, entry: Option<&InstructionEntry>) {
+        let indirect = entry
+            .map(|e| INDIRECT_TYPES.contains(&e.instruction.r#type.as_str()))
+            .unwrap_or_default();
+
+        if indirect && self.dispatcher.map(|d| d == pc).unwrap_or(true) {
+            let vpc = self
+                .vpc
+                .as_ref()
+                .map(|r| state.registers.get(r))
+                .unwrap_or_else(|| vc(0));
+            state
+                .context
+                .insert("vm_jump".to_owned(), vec![vc(pc), vpc]);
+        }
+    }
+}
+
+/// A handler executed by the dispatcher
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmStep {
+    pub handler: u64,
+    /// Virtual pc when the handler was dispatched
+    pub vpc: u64,
+    pub operands: Vec<u8>,
+}
+
+/// The handlers a state executed in order, decoded from its context
+///
+/// ## Example
+/// ```
+/// use radius2::radius::Radius;
+/// use radius2::vm::VmTrace;
+/// let mut radius = Radius::new("../tests/r100");
+/// radius.trace_vm(None, Some("rsi"), 4);
+/// let state = radius.call_state(0x004006fd);
+/// let found = radius.run_until(state, 0x004007a1, &[0x00400790]).unwrap();
+/// let trace = VmTrace::new(&found);
+/// assert!(trace.steps.is_empty());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VmTrace {
+    pub steps: Vec<VmStep>,
+}
+
+impl VmTrace {
+    pub fn new(state: &State) -> Self {
+        let values = state.context.get("vm_trace").cloned().unwrap_or_default();
+        let mut steps = vec![];
+        let mut i = 0;
+        while i + 3 <= values.len() {
+            let count = values[i + 2].as_u64().unwrap_or_default() as usize;
+            // a trace cut short has no operands for its last step
+            let operands = match values.get(i + 3..i + 3 + count) {
+                Some(operands) => operands,
+                None => break,
+            };
+            let operands = operands
+                .iter()
+                .map(|b| state.solver.eval_to_u64(b).unwrap_or_default() as u8)
+                .collect();
+            steps.push(VmStep {
+                handler: values[i].as_u64().unwrap_or_default(),
+                vpc: state.solver.eval_to_u64(&values[i + 1]).unwrap_or_default(),
+                operands,
+            });
+            i += 3 + count;
+        }
+        VmTrace { steps }
+    }
+
+    /// Get the steps of each handler, to write a summary of the handler
+    pub fn handlers(&self) -> BTreeMap<u64, Vec<&VmStep>> {
+        let mut handlers: BTreeMap<u64, Vec<&VmStep>> = BTreeMap::new();
+        for step in &self.steps {
+            handlers.entry(step.handler).or_default().push(step);
+        }
+        handlers
+    }
+
+    /// Print a line for each step with the handler and operand bytes
+    pub fn print(&self) {
+        for step in &self.steps {
+            let operands: Vec<String> =
+                step.operands.iter().map(|b| format!("{:02x}", b)).collect();
+            println!(
+                "0x{:08x}  vpc 0x{:08x}  {}",
+                step.handler,
+                step.vpc,
+                operands.join(" ")
+            );
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use crate::state::State;
+use crate::value::Value;
+use std::ops::Range;
+
+/// Called with the address and the value read or written
+pub type MemoryWatchMethod = fn(&mut State, &Value, &Value);
+/// Called with the name of the register written and its new value
+pub type RegisterWatchMethod = fn(&mut State, &str, &Value);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A callback on reads or writes of a range of memory
+#[derive(Debug, Clone)]
+pub struct MemoryWatch {
+    pub range: Range<u64>,
+    pub access: Access,
+    pub callback: MemoryWatchMethod,
+}
+
+/// A callback on writes of a register or the registers that overlap it,
+/// eg. a watch on `rax` is called on writes of `eax` and `al`
+#[derive(Debug, Clone)]
+pub struct RegisterWatch {
+    pub register: String,
+    pub callback: RegisterWatchMethod,
+}
+
+/// The watchpoints of a run, shared by all of its states
+#[derive(Debug, Clone, Default)]
+pub struct Watchpoints {
+    pub memory: Vec<MemoryWatch>,
+    pub registers: Vec<RegisterWatch>,
+}
+
+impl Watchpoints {
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_empty() && self.registers.is_empty()
+    }
+
+    /// Call the watches on `access` of memory that `length` bytes at
+    /// `addr` can overlap. A symbolic address overlaps if any of its
+    /// values between its min and max do
+    pub fn memory(
+        &self,
+        state: &mut State,
+        access: Access,
+        addr: &Value,
+        length: u64,
+        value: &Value,
+    ) {
+        let watches = self.memory.iter().filter(|w| w.access == access);
+        let mut bounds = None;
+        for watch in watches {
+            let (min, max) = *bounds.get_or_insert_with(|| match addr.as_u64() {
+                Some(a) => (a, a),
+                None => (state.solver.min_value(addr), state.solver.max_value(addr)),
+            });
+            if min < watch.range.end && max.saturating_add(length) > watch.range.start {
+                (watch.callback)(state, addr, value);
+            }
+        }
+    }
+
+    /// Call the watches on registers that overlap the register at `index`
+    pub fn register(&self, state: &mut State, index: usize, value: &Value) {
+        let written = state.registers.indexes[index].reg_info.clone();
+        for watch in &self.registers {
+            let overlaps = state.registers.get_register(&watch.register).map(|r| {
+                let info = &r.reg_info;
+                info.type_str == written.type_str
+                    && info.offset < written.offset + written.size
+                    && written.offset < info.offset + info.size
+            });
+            if overlaps == Some(true) {
+                (watch.callback)(state, &written.name, value);
+            }
+        }
+    }
+}